@@ -0,0 +1,252 @@
+//! Instruction builders for concentrated liquidity pools
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+
+use crate::{instructions::AmmInstruction, utils::find_config_address};
+
+/// Accounts of a concentrated liquidity position, shared by the builders
+/// that change or collect from it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionAccounts {
+    /// The pool account
+    pub pool: Pubkey,
+    /// The pool authority PDA
+    pub pool_authority: Pubkey,
+    /// The position PDA
+    pub position: Pubkey,
+    /// The tick array holding the lower tick
+    pub tick_array_lower: Pubkey,
+    /// The tick array holding the upper tick
+    pub tick_array_upper: Pubkey,
+    /// The owner's token A account
+    pub owner_token_a: Pubkey,
+    /// The owner's token B account
+    pub owner_token_b: Pubkey,
+    /// The token A vault
+    pub token_a_vault: Pubkey,
+    /// The token B vault
+    pub token_b_vault: Pubkey,
+}
+
+/// Creates an InitializeClPool instruction
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_cl_pool(
+    program_id: &Pubkey,
+    creator: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    token_a_vault: &Pubkey,
+    token_b_vault: &Pubkey,
+    tick_spacing: u16,
+    fee_basis_points: u16,
+    initial_sqrt_price: u128,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*creator, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*token_a_vault, false),
+        AccountMeta::new_readonly(*token_b_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ];
+
+    let data = AmmInstruction::InitializeClPool {
+        tick_spacing,
+        fee_basis_points,
+        initial_sqrt_price,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an InitializeTickArray instruction
+pub fn initialize_tick_array(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    pool: &Pubkey,
+    tick_array: &Pubkey,
+    start_tick_index: i32,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new(*tick_array, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    let data = AmmInstruction::InitializeTickArray { start_tick_index };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an OpenPosition instruction
+pub fn open_position(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    pool: &Pubkey,
+    position: &Pubkey,
+    tick_lower: i32,
+    tick_upper: i32,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*owner, true),
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new(*position, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    let data = AmmInstruction::OpenPosition {
+        tick_lower,
+        tick_upper,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an IncreaseLiquidity instruction
+pub fn increase_liquidity(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    position: &PositionAccounts,
+    liquidity: u128,
+    max_amount_a: u64,
+    max_amount_b: u64,
+) -> Instruction {
+    let data = AmmInstruction::IncreaseLiquidity {
+        liquidity,
+        max_amount_a,
+        max_amount_b,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts: position_account_metas(owner, position, true),
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a DecreaseLiquidity instruction
+pub fn decrease_liquidity(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    position: &PositionAccounts,
+    liquidity: u128,
+    min_amount_a: u64,
+    min_amount_b: u64,
+) -> Instruction {
+    let data = AmmInstruction::DecreaseLiquidity {
+        liquidity,
+        min_amount_a,
+        min_amount_b,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts: position_account_metas(owner, position, true),
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a CollectFees instruction
+pub fn collect_fees(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    position: &PositionAccounts,
+) -> Instruction {
+    let data = AmmInstruction::CollectFees;
+
+    Instruction {
+        program_id: *program_id,
+        accounts: position_account_metas(owner, position, false),
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a ClSwap instruction
+#[allow(clippy::too_many_arguments)]
+pub fn cl_swap(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_source: &Pubkey,
+    user_destination: &Pubkey,
+    vault_in: &Pubkey,
+    vault_out: &Pubkey,
+    tick_arrays: &[Pubkey],
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new(*user_source, false),
+        AccountMeta::new(*user_destination, false),
+        AccountMeta::new(*vault_in, false),
+        AccountMeta::new(*vault_out, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    accounts.extend(
+        tick_arrays
+            .iter()
+            .map(|tick_array| AccountMeta::new(*tick_array, false)),
+    );
+
+    let data = AmmInstruction::ClSwap {
+        amount_in,
+        minimum_amount_out,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Account metas shared by IncreaseLiquidity, DecreaseLiquidity and CollectFees
+fn position_account_metas(
+    owner: &Pubkey,
+    position: &PositionAccounts,
+    writable_pool: bool,
+) -> Vec<AccountMeta> {
+    let pool_and_ticks = |pubkey: &Pubkey| {
+        if writable_pool {
+            AccountMeta::new(*pubkey, false)
+        } else {
+            AccountMeta::new_readonly(*pubkey, false)
+        }
+    };
+    vec![
+        AccountMeta::new_readonly(*owner, true),
+        pool_and_ticks(&position.pool),
+        AccountMeta::new_readonly(position.pool_authority, false),
+        AccountMeta::new(position.position, false),
+        pool_and_ticks(&position.tick_array_lower),
+        pool_and_ticks(&position.tick_array_upper),
+        AccountMeta::new(position.owner_token_a, false),
+        AccountMeta::new(position.owner_token_b, false),
+        AccountMeta::new(position.token_a_vault, false),
+        AccountMeta::new(position.token_b_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ]
+}
@@ -0,0 +1,321 @@
+//! Tick and liquidity math for concentrated liquidity pools
+//!
+//! Prices are stored as square roots in Q64.64 fixed point. Tick `i` has the
+//! price `1.0001^i`, so its square root is `1.0001^(i / 2)`. Amounts owed to
+//! the pool round up and amounts paid out round down.
+
+use crate::{errors::AmmError, quote::BASIS_POINTS_DENOMINATOR};
+
+#[allow(
+    clippy::assign_op_pattern,
+    clippy::ptr_offset_with_cast,
+    clippy::manual_range_contains,
+    clippy::manual_div_ceil
+)]
+mod wide {
+    uint::construct_uint! {
+        /// 256-bit unsigned integer for intermediate products
+        pub struct U256(4);
+    }
+}
+pub use wide::U256;
+
+/// Smallest tick a position can use
+pub const MIN_TICK: i32 = -443_636;
+
+/// Largest tick a position can use
+pub const MAX_TICK: i32 = 443_636;
+
+/// Square root price of `MIN_TICK`, Q64.64
+pub const MIN_SQRT_PRICE: u128 = 4_295_048_016;
+
+/// Square root price of `MAX_TICK`, Q64.64
+pub const MAX_SQRT_PRICE: u128 = 79_226_673_515_401_279_963_822_778_343;
+
+/// Number of fractional bits in Q64.64 values
+const Q64_FRACTIONAL_BITS: usize = 64;
+
+/// `sqrt(1.0001)^(2^i)` in Q64.64, for each bit of a tick index
+const SQRT_PRICE_FACTORS: [u128; 19] = [
+    18_447_666_387_855_959_850,
+    18_448_588_748_116_922_571,
+    18_450_433_606_991_734_263,
+    18_454_123_878_217_468_680,
+    18_461_506_635_090_006_701,
+    18_476_281_010_653_910_144,
+    18_505_865_242_158_250_041,
+    18_565_175_891_880_433_522,
+    18_684_368_066_214_940_582,
+    18_925_053_041_275_764_671,
+    19_415_764_168_677_886_926,
+    20_435_687_552_633_177_494,
+    22_639_080_592_224_303_007,
+    27_784_196_929_998_399_742,
+    41_848_122_137_994_986_128,
+    94_936_283_578_220_370_716,
+    488_590_176_327_622_479_860,
+    12_941_056_668_319_229_769_860,
+    9_078_618_265_828_848_800_676_189,
+];
+
+/// Returns the Q64.64 square root price of `tick`
+pub fn sqrt_price_at_tick(tick: i32) -> Result<u128, AmmError> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(AmmError::InvalidTickRange);
+    }
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio = U256::one() << Q64_FRACTIONAL_BITS;
+    for (bit, factor) in SQRT_PRICE_FACTORS.iter().enumerate() {
+        if abs_tick & (1 << bit) != 0 {
+            ratio = (ratio * U256::from(*factor)) >> Q64_FRACTIONAL_BITS;
+        }
+    }
+    if tick < 0 {
+        ratio = (U256::one() << (2 * Q64_FRACTIONAL_BITS)) / ratio;
+    }
+    Ok(ratio.as_u128())
+}
+
+/// Returns the largest tick whose square root price is at most `sqrt_price`
+pub fn tick_at_sqrt_price(sqrt_price: u128) -> Result<i32, AmmError> {
+    if !(MIN_SQRT_PRICE..MAX_SQRT_PRICE).contains(&sqrt_price) {
+        return Err(AmmError::InvalidSqrtPrice);
+    }
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if sqrt_price_at_tick(mid)? <= sqrt_price {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Ok(low)
+}
+
+/// Returns the amount of token A between two square root prices
+///
+/// `liquidity * (upper - lower) / (upper * lower)`, scaled back from Q64.64.
+pub fn amount_a_delta(
+    sqrt_price_a: u128,
+    sqrt_price_b: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<u128, AmmError> {
+    let (lower, upper) = sorted(sqrt_price_a, sqrt_price_b);
+    if lower == 0 {
+        return Err(AmmError::InvalidSqrtPrice);
+    }
+    let numerator = (U256::from(liquidity) * U256::from(upper - lower)) << Q64_FRACTIONAL_BITS;
+    let denominator = U256::from(upper) * U256::from(lower);
+    to_u128(div_round(numerator, denominator, round_up))
+}
+
+/// Returns the amount of token B between two square root prices
+///
+/// `liquidity * (upper - lower)`, scaled back from Q64.64.
+pub fn amount_b_delta(
+    sqrt_price_a: u128,
+    sqrt_price_b: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<u128, AmmError> {
+    let (lower, upper) = sorted(sqrt_price_a, sqrt_price_b);
+    let product = U256::from(liquidity) * U256::from(upper - lower);
+    to_u128(div_round(
+        product,
+        U256::one() << Q64_FRACTIONAL_BITS,
+        round_up,
+    ))
+}
+
+/// Returns the token amounts `(amount_a, amount_b)` backing `liquidity` in a tick range
+pub fn amounts_for_liquidity(
+    sqrt_price: u128,
+    sqrt_price_lower: u128,
+    sqrt_price_upper: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<(u128, u128), AmmError> {
+    if sqrt_price <= sqrt_price_lower {
+        // Below the range, the position is all token A
+        Ok((
+            amount_a_delta(sqrt_price_lower, sqrt_price_upper, liquidity, round_up)?,
+            0,
+        ))
+    } else if sqrt_price >= sqrt_price_upper {
+        // Above the range, the position is all token B
+        Ok((
+            0,
+            amount_b_delta(sqrt_price_lower, sqrt_price_upper, liquidity, round_up)?,
+        ))
+    } else {
+        Ok((
+            amount_a_delta(sqrt_price, sqrt_price_upper, liquidity, round_up)?,
+            amount_b_delta(sqrt_price_lower, sqrt_price, liquidity, round_up)?,
+        ))
+    }
+}
+
+/// Result of swapping within a single tick range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SwapStep {
+    /// Square root price after the step
+    pub sqrt_price_next: u128,
+    /// Input spent on the swap, excluding the fee
+    pub amount_in: u128,
+    /// Output paid out
+    pub amount_out: u128,
+    /// Fee taken from the input
+    pub fee_amount: u128,
+}
+
+/// Swaps up to `amount_remaining` input without moving past `sqrt_price_target`
+///
+/// `a_to_b` swaps token A for token B, which moves the price down.
+pub fn compute_swap_step(
+    sqrt_price_current: u128,
+    sqrt_price_target: u128,
+    liquidity: u128,
+    amount_remaining: u128,
+    fee_basis_points: u16,
+    a_to_b: bool,
+) -> Result<SwapStep, AmmError> {
+    let fee_multiplier = (BASIS_POINTS_DENOMINATOR as u128)
+        .checked_sub(fee_basis_points as u128)
+        .filter(|multiplier| *multiplier > 0)
+        .ok_or(AmmError::InvalidFee)?;
+    let amount_remaining_less_fee = to_u128(
+        U256::from(amount_remaining) * U256::from(fee_multiplier)
+            / U256::from(BASIS_POINTS_DENOMINATOR),
+    )?;
+
+    // Input needed to reach the target price
+    let amount_to_target = if a_to_b {
+        amount_a_delta(sqrt_price_target, sqrt_price_current, liquidity, true)?
+    } else {
+        amount_b_delta(sqrt_price_current, sqrt_price_target, liquidity, true)?
+    };
+
+    let reached_target = amount_remaining_less_fee >= amount_to_target;
+    let sqrt_price_next = if reached_target {
+        sqrt_price_target
+    } else if a_to_b {
+        next_sqrt_price_from_amount_a(sqrt_price_current, liquidity, amount_remaining_less_fee)?
+    } else {
+        next_sqrt_price_from_amount_b(sqrt_price_current, liquidity, amount_remaining_less_fee)?
+    };
+
+    let (amount_in, amount_out) = if a_to_b {
+        (
+            amount_a_delta(sqrt_price_next, sqrt_price_current, liquidity, true)?,
+            amount_b_delta(sqrt_price_next, sqrt_price_current, liquidity, false)?,
+        )
+    } else {
+        (
+            amount_b_delta(sqrt_price_current, sqrt_price_next, liquidity, true)?,
+            amount_a_delta(sqrt_price_current, sqrt_price_next, liquidity, false)?,
+        )
+    };
+
+    // A partial step keeps all remaining input, the rest of it being fee
+    let fee_amount = if reached_target {
+        to_u128(div_round(
+            U256::from(amount_in) * U256::from(fee_basis_points),
+            U256::from(fee_multiplier),
+            true,
+        ))?
+    } else {
+        amount_remaining
+            .checked_sub(amount_in)
+            .ok_or(AmmError::MathOverflow)?
+    };
+
+    Ok(SwapStep {
+        sqrt_price_next,
+        amount_in,
+        amount_out,
+        fee_amount,
+    })
+}
+
+/// Returns the square root price after adding `amount` of token A, rounding up
+fn next_sqrt_price_from_amount_a(
+    sqrt_price: u128,
+    liquidity: u128,
+    amount: u128,
+) -> Result<u128, AmmError> {
+    if amount == 0 {
+        return Ok(sqrt_price);
+    }
+    // liquidity * sqrt_price / (liquidity + amount * sqrt_price), in Q64.64
+    let shifted_liquidity = U256::from(liquidity) << Q64_FRACTIONAL_BITS;
+    let numerator = shifted_liquidity * U256::from(sqrt_price);
+    let denominator = shifted_liquidity + U256::from(amount) * U256::from(sqrt_price);
+    to_u128(div_round(numerator, denominator, true))
+}
+
+/// Returns the square root price after adding `amount` of token B, rounding down
+fn next_sqrt_price_from_amount_b(
+    sqrt_price: u128,
+    liquidity: u128,
+    amount: u128,
+) -> Result<u128, AmmError> {
+    if liquidity == 0 {
+        return Err(AmmError::InsufficientLiquidity);
+    }
+    let delta = to_u128((U256::from(amount) << Q64_FRACTIONAL_BITS) / U256::from(liquidity))?;
+    sqrt_price.checked_add(delta).ok_or(AmmError::MathOverflow)
+}
+
+/// Adds a signed liquidity delta to `liquidity`
+pub fn add_liquidity_delta(liquidity: u128, delta: i128) -> Result<u128, AmmError> {
+    if delta < 0 {
+        liquidity.checked_sub(delta.unsigned_abs())
+    } else {
+        liquidity.checked_add(delta as u128)
+    }
+    .ok_or(AmmError::MathOverflow)
+}
+
+/// Returns `growth * liquidity` scaled back from Q64.64, truncated to u64
+pub fn fees_for_growth(growth: u128, liquidity: u128) -> Result<u64, AmmError> {
+    let fees = (U256::from(growth) * U256::from(liquidity)) >> Q64_FRACTIONAL_BITS;
+    if fees > U256::from(u64::MAX) {
+        return Err(AmmError::MathOverflow);
+    }
+    Ok(fees.as_u64())
+}
+
+/// Returns the Q64.64 fee growth per unit of liquidity for `fee_amount`
+pub fn fee_growth(fee_amount: u128, liquidity: u128) -> u128 {
+    if liquidity == 0 {
+        return 0;
+    }
+    // Fee growth is tracked modulo 2^128, so truncation is intended
+    ((U256::from(fee_amount) << Q64_FRACTIONAL_BITS) / U256::from(liquidity)).low_u128()
+}
+
+fn sorted(a: u128, b: u128) -> (u128, u128) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn div_round(numerator: U256, denominator: U256, round_up: bool) -> U256 {
+    let (quotient, remainder) = numerator.div_mod(denominator);
+    if round_up && !remainder.is_zero() {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+fn to_u128(value: U256) -> Result<u128, AmmError> {
+    if value > U256::from(u128::MAX) {
+        return Err(AmmError::MathOverflow);
+    }
+    Ok(value.as_u128())
+}
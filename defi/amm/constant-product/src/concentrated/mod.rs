@@ -0,0 +1,56 @@
+//! Concentrated liquidity pools
+//!
+//! Enabled by the `concentrated` feature. A concentrated pool sits beside the
+//! full-range `Pool` and shares its pool authority, fee tiers and vault
+//! checks, but liquidity is provided by positions over tick ranges instead of
+//! LP tokens. Ticks are stored in `TickArray` program accounts and each
+//! position is its own account that accrues swap fees while it is in range.
+
+pub mod instruction;
+pub mod math;
+pub mod processor;
+pub mod state;
+
+use solana_program::pubkey::Pubkey;
+
+/// Seed prefix of tick array PDAs
+pub const TICK_ARRAY_SEED: &[u8] = b"tick_array";
+
+/// Seed prefix of position PDAs
+pub const POSITION_SEED: &[u8] = b"position";
+
+/// Finds the PDA of the tick array starting at `start_tick_index`
+pub fn find_tick_array_address(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    start_tick_index: i32,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            TICK_ARRAY_SEED,
+            pool.as_ref(),
+            &start_tick_index.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+/// Finds the PDA of `owner`'s position over `[tick_lower, tick_upper)`
+pub fn find_position_address(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    owner: &Pubkey,
+    tick_lower: i32,
+    tick_upper: i32,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            POSITION_SEED,
+            pool.as_ref(),
+            owner.as_ref(),
+            &tick_lower.to_le_bytes(),
+            &tick_upper.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
@@ -0,0 +1,873 @@
+//! Concentrated liquidity instruction processor
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    concentrated::{
+        find_position_address, find_tick_array_address,
+        math::{
+            add_liquidity_delta, amounts_for_liquidity, compute_swap_step, fee_growth,
+            fees_for_growth, sqrt_price_at_tick, tick_at_sqrt_price, MAX_SQRT_PRICE, MAX_TICK,
+            MIN_SQRT_PRICE, MIN_TICK,
+        },
+        state::{ClPool, Position, Tick, TickArray, TICK_ARRAY_SIZE},
+        POSITION_SEED, TICK_ARRAY_SEED,
+    },
+    errors::AmmError,
+    instructions::processor::{load_config, transfer_from_vault, transfer_to_vault, unpack_vault},
+    utils::{check_account_owner, check_pool_authority, check_signer, find_pool_authority},
+};
+
+/// Processes an InitializeClPool instruction
+pub fn process_initialize_cl_pool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    tick_spacing: u16,
+    fee_basis_points: u16,
+    initial_sqrt_price: u128,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let creator_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let token_a_vault_info = next_account_info(account_info_iter)?;
+    let token_b_vault_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    // Check the creator is a signer
+    check_signer(creator_info)?;
+
+    // Check pool account ownership
+    check_account_owner(pool_info, program_id)?;
+
+    // Check for rent exemption
+    let rent = &Rent::from_account_info(rent_info)?;
+    if !rent.is_exempt(pool_info.lamports(), pool_info.data_len()) {
+        return Err(AmmError::NotRentExempt.into());
+    }
+
+    // Check the pool has not been initialized yet
+    if pool_info.data_len() != ClPool::get_size() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if pool_info.data.borrow()[0] != 0 {
+        return Err(AmmError::PoolAlreadyInitialized.into());
+    }
+
+    // Validate token program
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidTokenProgram.into());
+    }
+
+    // Validate fee, tick spacing and starting price
+    let config = load_config(program_id, config_info)?;
+    if !config.is_fee_tier(fee_basis_points) {
+        return Err(AmmError::FeeTierNotAllowed.into());
+    }
+    if tick_spacing == 0 {
+        return Err(AmmError::InvalidTickSpacing.into());
+    }
+    let tick_current = tick_at_sqrt_price(initial_sqrt_price)?;
+
+    // Validate pool authority
+    let (pool_authority, bump_seed) = find_pool_authority(program_id, pool_info.key);
+    if pool_authority != *pool_authority_info.key {
+        return Err(AmmError::InvalidPoolAuthority.into());
+    }
+
+    // Validate vaults
+    let token_a_vault = unpack_vault(token_a_vault_info, &pool_authority)?;
+    let token_b_vault = unpack_vault(token_b_vault_info, &pool_authority)?;
+    if token_a_vault.mint == token_b_vault.mint {
+        return Err(AmmError::RepeatedMint.into());
+    }
+
+    // Initialize pool data
+    let pool = ClPool {
+        is_initialized: true,
+        bump_seed,
+        token_a_mint: token_a_vault.mint,
+        token_b_mint: token_b_vault.mint,
+        token_a_vault: *token_a_vault_info.key,
+        token_b_vault: *token_b_vault_info.key,
+        config: *config_info.key,
+        tick_spacing,
+        fee_basis_points,
+        liquidity: 0,
+        sqrt_price: initial_sqrt_price,
+        tick_current,
+        fee_growth_global_a: 0,
+        fee_growth_global_b: 0,
+    };
+
+    // Save pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes an InitializeTickArray instruction
+pub fn process_initialize_tick_array(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    start_tick_index: i32,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let payer_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let tick_array_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    // Check the payer is a signer
+    check_signer(payer_info)?;
+
+    // Validate the start index against the pool's tick spacing
+    let pool = read_cl_pool(program_id, pool_info)?;
+    if pool.tick_array_start_index(start_tick_index) != start_tick_index
+        || start_tick_index > MAX_TICK
+        || start_tick_index + pool.ticks_per_array() <= MIN_TICK
+    {
+        return Err(AmmError::InvalidTickArray.into());
+    }
+
+    // Validate the tick array address
+    let (tick_array_address, bump_seed) =
+        find_tick_array_address(program_id, pool_info.key, start_tick_index);
+    if tick_array_address != *tick_array_info.key {
+        return Err(AmmError::InvalidTickArray.into());
+    }
+    if !tick_array_info.data_is_empty() {
+        return Err(AmmError::InvalidTickArray.into());
+    }
+
+    // Create the tick array account at its PDA
+    let start_tick_index_bytes = start_tick_index.to_le_bytes();
+    let seeds: &[&[u8]] = &[
+        TICK_ARRAY_SEED,
+        pool_info.key.as_ref(),
+        &start_tick_index_bytes,
+        &[bump_seed],
+    ];
+    create_pda_account(
+        program_id,
+        payer_info,
+        tick_array_info,
+        system_program_info,
+        TickArray::get_size(),
+        seeds,
+    )?;
+
+    // Initialize tick array data
+    let tick_array = TickArray {
+        is_initialized: true,
+        pool: *pool_info.key,
+        start_tick_index,
+        ticks: vec![Tick::default(); TICK_ARRAY_SIZE as usize],
+    };
+
+    // Save tick array data
+    tick_array.serialize(&mut &mut tick_array_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes an OpenPosition instruction
+pub fn process_open_position(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    tick_lower: i32,
+    tick_upper: i32,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let position_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    // Validate the tick range
+    let pool = read_cl_pool(program_id, pool_info)?;
+    let spacing = pool.tick_spacing as i32;
+    if tick_lower >= tick_upper
+        || tick_lower < MIN_TICK
+        || tick_upper > MAX_TICK
+        || tick_lower % spacing != 0
+        || tick_upper % spacing != 0
+    {
+        return Err(AmmError::InvalidTickRange.into());
+    }
+
+    // Validate the position address
+    let (position_address, bump_seed) = find_position_address(
+        program_id,
+        pool_info.key,
+        owner_info.key,
+        tick_lower,
+        tick_upper,
+    );
+    if position_address != *position_info.key || !position_info.data_is_empty() {
+        return Err(AmmError::InvalidPosition.into());
+    }
+
+    // Create the position account at its PDA
+    let tick_lower_bytes = tick_lower.to_le_bytes();
+    let tick_upper_bytes = tick_upper.to_le_bytes();
+    let seeds: &[&[u8]] = &[
+        POSITION_SEED,
+        pool_info.key.as_ref(),
+        owner_info.key.as_ref(),
+        &tick_lower_bytes,
+        &tick_upper_bytes,
+        &[bump_seed],
+    ];
+    create_pda_account(
+        program_id,
+        owner_info,
+        position_info,
+        system_program_info,
+        Position::get_size(),
+        seeds,
+    )?;
+
+    // Initialize position data
+    let position = Position {
+        is_initialized: true,
+        pool: *pool_info.key,
+        owner: *owner_info.key,
+        tick_lower,
+        tick_upper,
+        ..Position::default()
+    };
+
+    // Save position data
+    position.serialize(&mut &mut position_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes an IncreaseLiquidity or DecreaseLiquidity instruction
+///
+/// A positive `liquidity_delta` takes at most `amount_a_limit` and
+/// `amount_b_limit` from the owner; a negative one pays out at least them.
+pub fn process_modify_liquidity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    liquidity_delta: i128,
+    amount_a_limit: u64,
+    amount_b_limit: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let position_info = next_account_info(account_info_iter)?;
+    let tick_array_lower_info = next_account_info(account_info_iter)?;
+    let tick_array_upper_info = next_account_info(account_info_iter)?;
+    let owner_token_a_info = next_account_info(account_info_iter)?;
+    let owner_token_b_info = next_account_info(account_info_iter)?;
+    let token_a_vault_info = next_account_info(account_info_iter)?;
+    let token_b_vault_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    // Load and validate the pool, the position and the tick arrays
+    let mut pool = load_cl_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+    check_vaults(&pool, token_a_vault_info, token_b_vault_info)?;
+    let mut position = load_position(program_id, pool_info.key, owner_info.key, position_info)?;
+    let (tick_lower, tick_upper) = (position.tick_lower, position.tick_upper);
+    let mut lower_array = load_tick_array(program_id, pool_info.key, tick_array_lower_info)?;
+    let mut upper_array = if tick_array_upper_info.key == tick_array_lower_info.key {
+        None
+    } else {
+        Some(load_tick_array(
+            program_id,
+            pool_info.key,
+            tick_array_upper_info,
+        )?)
+    };
+
+    if liquidity_delta == 0 {
+        return Err(AmmError::ZeroAmount.into());
+    }
+
+    // Update both bounding ticks
+    update_tick(
+        &pool,
+        lower_array.tick_mut(tick_lower, pool.tick_spacing)?,
+        tick_lower,
+        liquidity_delta,
+        false,
+    )?;
+    update_tick(
+        &pool,
+        upper_array
+            .as_mut()
+            .unwrap_or(&mut lower_array)
+            .tick_mut(tick_upper, pool.tick_spacing)?,
+        tick_upper,
+        liquidity_delta,
+        true,
+    )?;
+
+    // Settle fees at the old liquidity, then change it
+    let lower = *lower_array.tick(tick_lower, pool.tick_spacing)?;
+    let upper = *upper_array
+        .as_ref()
+        .unwrap_or(&lower_array)
+        .tick(tick_upper, pool.tick_spacing)?;
+    accrue_position_fees(&pool, &mut position, &lower, &upper)?;
+    position.liquidity = add_liquidity_delta(position.liquidity, liquidity_delta)
+        .map_err(|_| AmmError::InsufficientLiquidity)?;
+    if tick_lower <= pool.tick_current && pool.tick_current < tick_upper {
+        pool.liquidity = add_liquidity_delta(pool.liquidity, liquidity_delta)?;
+    }
+
+    // Ticks no position uses any more are cleared
+    clear_unused_tick(&mut lower_array, tick_lower, pool.tick_spacing)?;
+    clear_unused_tick(
+        upper_array.as_mut().unwrap_or(&mut lower_array),
+        tick_upper,
+        pool.tick_spacing,
+    )?;
+
+    // Token amounts backing the liquidity change
+    let (amount_a, amount_b) = amounts_for_liquidity(
+        pool.sqrt_price,
+        sqrt_price_at_tick(tick_lower)?,
+        sqrt_price_at_tick(tick_upper)?,
+        liquidity_delta.unsigned_abs(),
+        liquidity_delta > 0,
+    )?;
+    let amount_a = u64::try_from(amount_a).map_err(|_| AmmError::MathOverflow)?;
+    let amount_b = u64::try_from(amount_b).map_err(|_| AmmError::MathOverflow)?;
+
+    if liquidity_delta > 0 {
+        if amount_a > amount_a_limit || amount_b > amount_b_limit {
+            return Err(AmmError::SlippageExceeded.into());
+        }
+        for (source_info, vault_info, amount) in [
+            (owner_token_a_info, token_a_vault_info, amount_a),
+            (owner_token_b_info, token_b_vault_info, amount_b),
+        ] {
+            if amount > 0 {
+                transfer_to_vault(
+                    token_program_info,
+                    source_info,
+                    vault_info,
+                    owner_info,
+                    amount,
+                )?;
+            }
+        }
+    } else {
+        if amount_a < amount_a_limit || amount_b < amount_b_limit {
+            return Err(AmmError::SlippageExceeded.into());
+        }
+        let pool_seeds: &[&[u8]] = &[pool_info.key.as_ref(), &[pool.bump_seed]];
+        for (vault_info, destination_info, amount) in [
+            (token_a_vault_info, owner_token_a_info, amount_a),
+            (token_b_vault_info, owner_token_b_info, amount_b),
+        ] {
+            if amount > 0 {
+                transfer_from_vault(
+                    token_program_info,
+                    vault_info,
+                    destination_info,
+                    pool_authority_info,
+                    pool_seeds,
+                    amount,
+                )?;
+            }
+        }
+    }
+
+    // Save updated data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+    position.serialize(&mut &mut position_info.data.borrow_mut()[..])?;
+    lower_array.serialize(&mut &mut tick_array_lower_info.data.borrow_mut()[..])?;
+    if let Some(upper_array) = upper_array {
+        upper_array.serialize(&mut &mut tick_array_upper_info.data.borrow_mut()[..])?;
+    }
+
+    Ok(())
+}
+
+/// Processes a CollectFees instruction
+pub fn process_collect_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let position_info = next_account_info(account_info_iter)?;
+    let tick_array_lower_info = next_account_info(account_info_iter)?;
+    let tick_array_upper_info = next_account_info(account_info_iter)?;
+    let owner_token_a_info = next_account_info(account_info_iter)?;
+    let owner_token_b_info = next_account_info(account_info_iter)?;
+    let token_a_vault_info = next_account_info(account_info_iter)?;
+    let token_b_vault_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    // Load and validate the pool, the position and the tick arrays
+    let pool = load_cl_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+    check_vaults(&pool, token_a_vault_info, token_b_vault_info)?;
+    let mut position = load_position(program_id, pool_info.key, owner_info.key, position_info)?;
+    let lower_array = load_tick_array(program_id, pool_info.key, tick_array_lower_info)?;
+    let upper_array = load_tick_array(program_id, pool_info.key, tick_array_upper_info)?;
+
+    // Bring the position's fees up to date
+    let lower = *lower_array.tick(position.tick_lower, pool.tick_spacing)?;
+    let upper = *upper_array.tick(position.tick_upper, pool.tick_spacing)?;
+    accrue_position_fees(&pool, &mut position, &lower, &upper)?;
+
+    // Pay out everything owed
+    let pool_seeds: &[&[u8]] = &[pool_info.key.as_ref(), &[pool.bump_seed]];
+    for (vault_info, destination_info, amount) in [
+        (token_a_vault_info, owner_token_a_info, position.fees_owed_a),
+        (token_b_vault_info, owner_token_b_info, position.fees_owed_b),
+    ] {
+        if amount > 0 {
+            transfer_from_vault(
+                token_program_info,
+                vault_info,
+                destination_info,
+                pool_authority_info,
+                pool_seeds,
+                amount,
+            )?;
+        }
+    }
+    position.fees_owed_a = 0;
+    position.fees_owed_b = 0;
+
+    // Save updated position data
+    position.serialize(&mut &mut position_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a ClSwap instruction
+pub fn process_cl_swap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let user_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let user_source_info = next_account_info(account_info_iter)?;
+    let user_destination_info = next_account_info(account_info_iter)?;
+    let vault_in_info = next_account_info(account_info_iter)?;
+    let vault_out_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let tick_array_infos = account_info_iter.as_slice();
+
+    // Check the user is a signer
+    check_signer(user_info)?;
+
+    // Load and validate the pool
+    let mut pool = load_cl_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+
+    // Resolve swap direction from the vaults
+    let a_to_b = if *vault_in_info.key == pool.token_a_vault
+        && *vault_out_info.key == pool.token_b_vault
+    {
+        true
+    } else if *vault_in_info.key == pool.token_b_vault && *vault_out_info.key == pool.token_a_vault
+    {
+        false
+    } else {
+        return Err(AmmError::InvalidVault.into());
+    };
+
+    if amount_in == 0 {
+        return Err(AmmError::ZeroAmount.into());
+    }
+
+    // The tick arrays must be contiguous and include the current tick
+    let mut tick_arrays = Vec::with_capacity(tick_array_infos.len());
+    for tick_array_info in tick_array_infos {
+        let tick_array = load_tick_array(program_id, pool_info.key, tick_array_info)?;
+        tick_arrays.push((tick_array_info, tick_array));
+    }
+    tick_arrays.sort_by_key(|(_, tick_array)| tick_array.start_tick_index);
+    let ticks_per_array = pool.ticks_per_array();
+    let (lowest_tick, highest_tick) = match (tick_arrays.first(), tick_arrays.last()) {
+        (Some((_, first)), Some((_, last))) => (
+            first.start_tick_index,
+            last.start_tick_index + ticks_per_array - 1,
+        ),
+        _ => return Err(AmmError::InvalidTickArray.into()),
+    };
+    if tick_arrays
+        .windows(2)
+        .any(|pair| pair[1].1.start_tick_index != pair[0].1.start_tick_index + ticks_per_array)
+        || !(lowest_tick..=highest_tick).contains(&pool.tick_current)
+    {
+        return Err(AmmError::InvalidTickArray.into());
+    }
+
+    // Swap tick range by tick range until the input is used up or the
+    // price reaches the end of the supplied tick arrays
+    let mut amount_remaining = amount_in as u128;
+    let mut amount_out = 0u128;
+    while amount_remaining > 0 {
+        // Next initialized tick in the swap direction, or the edge of the arrays
+        let next_tick = if a_to_b {
+            tick_arrays
+                .iter()
+                .flat_map(|(_, tick_array)| tick_array.initialized_ticks(pool.tick_spacing))
+                .map(|(tick_index, _)| tick_index)
+                .filter(|tick_index| *tick_index <= pool.tick_current)
+                .max()
+        } else {
+            tick_arrays
+                .iter()
+                .flat_map(|(_, tick_array)| tick_array.initialized_ticks(pool.tick_spacing))
+                .map(|(tick_index, _)| tick_index)
+                .filter(|tick_index| *tick_index > pool.tick_current)
+                .min()
+        };
+        let target_tick = next_tick
+            .unwrap_or(if a_to_b { lowest_tick } else { highest_tick })
+            .clamp(MIN_TICK, MAX_TICK);
+        let sqrt_price_target = sqrt_price_at_tick(target_tick)?;
+        if (a_to_b && sqrt_price_target > pool.sqrt_price)
+            || (!a_to_b && sqrt_price_target < pool.sqrt_price)
+        {
+            break;
+        }
+
+        let step = compute_swap_step(
+            pool.sqrt_price,
+            sqrt_price_target,
+            pool.liquidity,
+            amount_remaining,
+            pool.fee_basis_points,
+            a_to_b,
+        )?;
+        amount_remaining = amount_remaining
+            .checked_sub(step.amount_in + step.fee_amount)
+            .ok_or(AmmError::MathOverflow)?;
+        amount_out = amount_out
+            .checked_add(step.amount_out)
+            .ok_or(AmmError::MathOverflow)?;
+
+        // Fees go to the liquidity in range during the step
+        let growth = fee_growth(step.fee_amount, pool.liquidity);
+        if a_to_b {
+            pool.fee_growth_global_a = pool.fee_growth_global_a.wrapping_add(growth);
+        } else {
+            pool.fee_growth_global_b = pool.fee_growth_global_b.wrapping_add(growth);
+        }
+        pool.sqrt_price = step.sqrt_price_next;
+
+        if step.sqrt_price_next != sqrt_price_target {
+            // Stopped inside a tick range with the input used up
+            pool.tick_current = tick_at_sqrt_price(pool.sqrt_price)?;
+            break;
+        }
+        match next_tick {
+            Some(tick_index) => {
+                // Cross the tick, flipping its outside fee growth
+                let (_, tick_array) = tick_arrays
+                    .iter_mut()
+                    .find(|(_, tick_array)| {
+                        (tick_array.start_tick_index..tick_array.start_tick_index + ticks_per_array)
+                            .contains(&tick_index)
+                    })
+                    .ok_or(AmmError::InvalidTickArray)?;
+                let tick = tick_array.tick_mut(tick_index, pool.tick_spacing)?;
+                tick.fee_growth_outside_a = pool
+                    .fee_growth_global_a
+                    .wrapping_sub(tick.fee_growth_outside_a);
+                tick.fee_growth_outside_b = pool
+                    .fee_growth_global_b
+                    .wrapping_sub(tick.fee_growth_outside_b);
+                if a_to_b {
+                    pool.liquidity = add_liquidity_delta(pool.liquidity, -tick.liquidity_net)?;
+                    pool.tick_current = tick_index - 1;
+                } else {
+                    pool.liquidity = add_liquidity_delta(pool.liquidity, tick.liquidity_net)?;
+                    pool.tick_current = tick_index;
+                }
+            }
+            None => {
+                // Reached the edge of the supplied tick arrays
+                pool.tick_current = target_tick;
+                break;
+            }
+        }
+    }
+
+    // Only the input actually swapped is charged
+    let amount_in = amount_in - amount_remaining as u64;
+    let amount_out = u64::try_from(amount_out).map_err(|_| AmmError::MathOverflow)?;
+    if amount_in == 0 || amount_out == 0 {
+        return Err(AmmError::ZeroAmount.into());
+    }
+    if amount_out < minimum_amount_out {
+        return Err(AmmError::SlippageExceeded.into());
+    }
+    if !(MIN_SQRT_PRICE..MAX_SQRT_PRICE).contains(&pool.sqrt_price) {
+        return Err(AmmError::InsufficientLiquidity.into());
+    }
+
+    // Transfer input tokens to the pool and output tokens to the user
+    transfer_to_vault(
+        token_program_info,
+        user_source_info,
+        vault_in_info,
+        user_info,
+        amount_in,
+    )?;
+    let pool_seeds: &[&[u8]] = &[pool_info.key.as_ref(), &[pool.bump_seed]];
+    transfer_from_vault(
+        token_program_info,
+        vault_out_info,
+        user_destination_info,
+        pool_authority_info,
+        pool_seeds,
+        amount_out,
+    )?;
+
+    // Save updated pool and tick data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+    for (tick_array_info, tick_array) in tick_arrays {
+        tick_array.serialize(&mut &mut tick_array_info.data.borrow_mut()[..])?;
+    }
+
+    Ok(())
+}
+
+/// Applies a position's liquidity change to one of its bounding ticks
+fn update_tick(
+    pool: &ClPool,
+    tick: &mut Tick,
+    tick_index: i32,
+    liquidity_delta: i128,
+    is_upper: bool,
+) -> ProgramResult {
+    if tick.liquidity_gross == 0 {
+        // By convention all growth so far happened below a newly used tick
+        if tick_index <= pool.tick_current {
+            tick.fee_growth_outside_a = pool.fee_growth_global_a;
+            tick.fee_growth_outside_b = pool.fee_growth_global_b;
+        }
+        tick.initialized = true;
+    }
+    tick.liquidity_gross = add_liquidity_delta(tick.liquidity_gross, liquidity_delta)
+        .map_err(|_| AmmError::InsufficientLiquidity)?;
+    tick.liquidity_net = if is_upper {
+        tick.liquidity_net.checked_sub(liquidity_delta)
+    } else {
+        tick.liquidity_net.checked_add(liquidity_delta)
+    }
+    .ok_or(AmmError::MathOverflow)?;
+    Ok(())
+}
+
+/// Resets a tick that no position uses as a bound any more
+fn clear_unused_tick(
+    tick_array: &mut TickArray,
+    tick_index: i32,
+    tick_spacing: u16,
+) -> ProgramResult {
+    let tick = tick_array.tick_mut(tick_index, tick_spacing)?;
+    if tick.liquidity_gross == 0 {
+        *tick = Tick::default();
+    }
+    Ok(())
+}
+
+/// Credits a position with the fees earned inside its range since its last update
+fn accrue_position_fees(
+    pool: &ClPool,
+    position: &mut Position,
+    lower: &Tick,
+    upper: &Tick,
+) -> ProgramResult {
+    let (inside_a, inside_b) =
+        pool.fee_growth_inside(position.tick_lower, lower, position.tick_upper, upper);
+    let earned_a = fees_for_growth(
+        inside_a.wrapping_sub(position.fee_growth_inside_last_a),
+        position.liquidity,
+    )?;
+    let earned_b = fees_for_growth(
+        inside_b.wrapping_sub(position.fee_growth_inside_last_b),
+        position.liquidity,
+    )?;
+    position.fees_owed_a = position
+        .fees_owed_a
+        .checked_add(earned_a)
+        .ok_or(AmmError::MathOverflow)?;
+    position.fees_owed_b = position
+        .fees_owed_b
+        .checked_add(earned_b)
+        .ok_or(AmmError::MathOverflow)?;
+    position.fee_growth_inside_last_a = inside_a;
+    position.fee_growth_inside_last_b = inside_b;
+    Ok(())
+}
+
+/// Reads an initialized concentrated pool owned by this program
+fn read_cl_pool(program_id: &Pubkey, pool_info: &AccountInfo) -> Result<ClPool, ProgramError> {
+    check_account_owner(pool_info, program_id)?;
+    if pool_info.data_len() != ClPool::get_size() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let pool = ClPool::try_from_slice(&pool_info.data.borrow())?;
+    if !pool.is_initialized {
+        return Err(AmmError::PoolNotInitialized.into());
+    }
+    Ok(pool)
+}
+
+/// Loads an initialized concentrated pool and validates its authority and token program
+fn load_cl_pool(
+    program_id: &Pubkey,
+    pool_info: &AccountInfo,
+    pool_authority_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+) -> Result<ClPool, ProgramError> {
+    let pool = read_cl_pool(program_id, pool_info)?;
+
+    check_pool_authority(
+        program_id,
+        pool_info.key,
+        pool.bump_seed,
+        pool_authority_info.key,
+    )?;
+
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidTokenProgram.into());
+    }
+
+    Ok(pool)
+}
+
+/// Loads an initialized tick array of `pool`
+fn load_tick_array(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    tick_array_info: &AccountInfo,
+) -> Result<TickArray, ProgramError> {
+    check_account_owner(tick_array_info, program_id)?;
+    if tick_array_info.data_len() != TickArray::get_size() {
+        return Err(AmmError::InvalidTickArray.into());
+    }
+    let tick_array = TickArray::try_from_slice(&tick_array_info.data.borrow())?;
+    if !tick_array.is_initialized || tick_array.pool != *pool {
+        return Err(AmmError::InvalidTickArray.into());
+    }
+    Ok(tick_array)
+}
+
+/// Loads an initialized position of `pool` held by `owner`
+fn load_position(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    owner: &Pubkey,
+    position_info: &AccountInfo,
+) -> Result<Position, ProgramError> {
+    check_account_owner(position_info, program_id)?;
+    if position_info.data_len() != Position::get_size() {
+        return Err(AmmError::InvalidPosition.into());
+    }
+    let position = Position::try_from_slice(&position_info.data.borrow())?;
+    if !position.is_initialized || position.pool != *pool {
+        return Err(AmmError::InvalidPosition.into());
+    }
+    if position.owner != *owner {
+        return Err(AmmError::InvalidAuthority.into());
+    }
+    Ok(position)
+}
+
+/// Checks the vault accounts are the pool's vaults
+fn check_vaults(
+    pool: &ClPool,
+    token_a_vault_info: &AccountInfo,
+    token_b_vault_info: &AccountInfo,
+) -> ProgramResult {
+    if *token_a_vault_info.key != pool.token_a_vault
+        || *token_b_vault_info.key != pool.token_b_vault
+    {
+        return Err(AmmError::InvalidVault.into());
+    }
+    Ok(())
+}
+
+/// Creates a program-owned account at a PDA, paid for by `payer_info`
+fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer_info: &AccountInfo<'a>,
+    account_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    size: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let rent = Rent::get()?;
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_info.key,
+            account_info.key,
+            rent.minimum_balance(size),
+            size as u64,
+            program_id,
+        ),
+        &[
+            payer_info.clone(),
+            account_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[seeds],
+    )
+}
@@ -0,0 +1,253 @@
+//! State objects for concentrated liquidity pools
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::errors::AmmError;
+
+/// Number of ticks stored in each tick array
+pub const TICK_ARRAY_SIZE: i32 = 64;
+
+/// Concentrated liquidity pool
+///
+/// Liquidity is provided by positions over tick ranges. Only positions whose
+/// range contains the current tick are active, and their combined liquidity
+/// is `liquidity`. The vaults are owned by the same pool authority PDA as
+/// full-range pools (see `utils::find_pool_authority`).
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct ClPool {
+    /// Is the pool initialized
+    pub is_initialized: bool,
+
+    /// Bump seed of the pool authority PDA
+    pub bump_seed: u8,
+
+    /// Mint of token A
+    pub token_a_mint: Pubkey,
+
+    /// Mint of token B
+    pub token_b_mint: Pubkey,
+
+    /// Vault holding token A, owned by the pool authority
+    pub token_a_vault: Pubkey,
+
+    /// Vault holding token B, owned by the pool authority
+    pub token_b_vault: Pubkey,
+
+    /// AMM config that lists the allowed fee tiers
+    pub config: Pubkey,
+
+    /// Distance between usable ticks
+    pub tick_spacing: u16,
+
+    /// Swap fee in basis points, paid to in-range positions
+    pub fee_basis_points: u16,
+
+    /// Liquidity of the positions in range of the current tick
+    pub liquidity: u128,
+
+    /// Square root of the price of token A in token B, Q64.64
+    pub sqrt_price: u128,
+
+    /// Largest tick whose square root price is at most `sqrt_price`
+    pub tick_current: i32,
+
+    /// Token A fees earned per unit of liquidity over the pool's life, Q64.64, wrapping
+    pub fee_growth_global_a: u128,
+
+    /// Token B fees earned per unit of liquidity over the pool's life, Q64.64, wrapping
+    pub fee_growth_global_b: u128,
+}
+
+impl ClPool {
+    /// Get the size of ClPool struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + bump_seed (1 byte) + Pubkey (32 bytes) * 5 +
+        // tick_spacing (2 bytes) + fee_basis_points (2 bytes) + liquidity (16 bytes) +
+        // sqrt_price (16 bytes) + tick_current (4 bytes) + fee growths (16 bytes * 2)
+        1 + 1 + 32 * 5 + 2 + 2 + 16 + 16 + 4 + 16 * 2
+    }
+
+    /// Number of ticks covered by one tick array
+    pub fn ticks_per_array(&self) -> i32 {
+        self.tick_spacing as i32 * TICK_ARRAY_SIZE
+    }
+
+    /// Returns the start index of the tick array holding `tick`
+    pub fn tick_array_start_index(&self, tick: i32) -> i32 {
+        tick.div_euclid(self.ticks_per_array()) * self.ticks_per_array()
+    }
+
+    /// Returns the fee growth `(a, b)` inside `[tick_lower, tick_upper)`
+    ///
+    /// Each tick records the growth on the side away from the current tick,
+    /// so the growth inside is the global growth minus both outsides. All
+    /// values wrap; only differences between two readings are meaningful.
+    pub fn fee_growth_inside(
+        &self,
+        tick_lower: i32,
+        lower: &Tick,
+        tick_upper: i32,
+        upper: &Tick,
+    ) -> (u128, u128) {
+        let (below_a, below_b) = if self.tick_current >= tick_lower {
+            (lower.fee_growth_outside_a, lower.fee_growth_outside_b)
+        } else {
+            (
+                self.fee_growth_global_a
+                    .wrapping_sub(lower.fee_growth_outside_a),
+                self.fee_growth_global_b
+                    .wrapping_sub(lower.fee_growth_outside_b),
+            )
+        };
+        let (above_a, above_b) = if self.tick_current < tick_upper {
+            (upper.fee_growth_outside_a, upper.fee_growth_outside_b)
+        } else {
+            (
+                self.fee_growth_global_a
+                    .wrapping_sub(upper.fee_growth_outside_a),
+                self.fee_growth_global_b
+                    .wrapping_sub(upper.fee_growth_outside_b),
+            )
+        };
+        (
+            self.fee_growth_global_a
+                .wrapping_sub(below_a)
+                .wrapping_sub(above_a),
+            self.fee_growth_global_b
+                .wrapping_sub(below_b)
+                .wrapping_sub(above_b),
+        )
+    }
+}
+
+/// Liquidity and fee bookkeeping at a tick boundary
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tick {
+    /// Whether any position uses this tick as a bound
+    pub initialized: bool,
+
+    /// Liquidity added when the price crosses this tick upwards
+    pub liquidity_net: i128,
+
+    /// Total liquidity of the positions bounded by this tick
+    pub liquidity_gross: u128,
+
+    /// Token A fee growth on the other side of this tick, Q64.64, wrapping
+    pub fee_growth_outside_a: u128,
+
+    /// Token B fee growth on the other side of this tick, Q64.64, wrapping
+    pub fee_growth_outside_b: u128,
+}
+
+impl Tick {
+    /// Size of a serialized tick
+    pub const LEN: usize = 1 + 16 + 16 + 16 + 16;
+}
+
+/// A run of `TICK_ARRAY_SIZE` ticks of one pool
+///
+/// Lives at the PDA derived from `[b"tick_array", pool, start_tick_index]`
+/// (see `concentrated::find_tick_array_address`).
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct TickArray {
+    /// Is the tick array initialized
+    pub is_initialized: bool,
+
+    /// Pool the ticks belong to
+    pub pool: Pubkey,
+
+    /// First tick index covered, a multiple of `tick_spacing * TICK_ARRAY_SIZE`
+    pub start_tick_index: i32,
+
+    /// Ticks at `start_tick_index + i * tick_spacing`
+    pub ticks: Vec<Tick>,
+}
+
+impl TickArray {
+    /// Get the size of TickArray struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + Pubkey (32 bytes) + start_tick_index (4 bytes) +
+        // ticks length (4 bytes) + Tick::LEN * TICK_ARRAY_SIZE
+        1 + 32 + 4 + 4 + Tick::LEN * TICK_ARRAY_SIZE as usize
+    }
+
+    /// Returns the tick at `tick_index`, which must be in this array
+    pub fn tick(&self, tick_index: i32, tick_spacing: u16) -> Result<&Tick, AmmError> {
+        let offset = self.offset(tick_index, tick_spacing)?;
+        Ok(&self.ticks[offset])
+    }
+
+    /// Returns the tick at `tick_index` mutably, which must be in this array
+    pub fn tick_mut(&mut self, tick_index: i32, tick_spacing: u16) -> Result<&mut Tick, AmmError> {
+        let offset = self.offset(tick_index, tick_spacing)?;
+        Ok(&mut self.ticks[offset])
+    }
+
+    /// Returns the initialized ticks of this array as `(tick_index, tick)`
+    pub fn initialized_ticks(&self, tick_spacing: u16) -> impl Iterator<Item = (i32, &Tick)> {
+        let start_tick_index = self.start_tick_index;
+        self.ticks
+            .iter()
+            .enumerate()
+            .filter(|(_, tick)| tick.initialized)
+            .map(move |(offset, tick)| {
+                (start_tick_index + offset as i32 * tick_spacing as i32, tick)
+            })
+    }
+
+    fn offset(&self, tick_index: i32, tick_spacing: u16) -> Result<usize, AmmError> {
+        let spacing = tick_spacing as i32;
+        let distance = tick_index - self.start_tick_index;
+        if distance < 0 || distance % spacing != 0 || distance / spacing >= TICK_ARRAY_SIZE {
+            return Err(AmmError::InvalidTickArray);
+        }
+        Ok((distance / spacing) as usize)
+    }
+}
+
+/// A liquidity position over `[tick_lower, tick_upper)`
+///
+/// Lives at the PDA derived from `[b"position", pool, owner, tick_lower, tick_upper]`
+/// (see `concentrated::find_position_address`).
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct Position {
+    /// Is the position initialized
+    pub is_initialized: bool,
+
+    /// Pool the position provides liquidity to
+    pub pool: Pubkey,
+
+    /// Owner allowed to change the position and collect its fees
+    pub owner: Pubkey,
+
+    /// Lower tick bound, inclusive
+    pub tick_lower: i32,
+
+    /// Upper tick bound, exclusive
+    pub tick_upper: i32,
+
+    /// Liquidity provided by the position
+    pub liquidity: u128,
+
+    /// Token A fee growth inside the range at the last update, Q64.64
+    pub fee_growth_inside_last_a: u128,
+
+    /// Token B fee growth inside the range at the last update, Q64.64
+    pub fee_growth_inside_last_b: u128,
+
+    /// Token A fees earned and not yet collected
+    pub fees_owed_a: u64,
+
+    /// Token B fees earned and not yet collected
+    pub fees_owed_b: u64,
+}
+
+impl Position {
+    /// Get the size of Position struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + Pubkey (32 bytes) * 2 + ticks (4 bytes * 2) +
+        // liquidity (16 bytes) + fee growths (16 bytes * 2) + fees owed (8 bytes * 2)
+        1 + 32 * 2 + 4 * 2 + 16 + 16 * 2 + 8 * 2
+    }
+}
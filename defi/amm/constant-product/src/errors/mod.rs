@@ -4,31 +4,127 @@ use solana_program::program_error::ProgramError;
 use thiserror::Error;
 
 /// Errors that may be returned by the constant-product-amm program
-#[derive(Error, Debug, Copy, Clone)]
-pub enum TemplateError {
+#[derive(Error, Debug, Copy, Clone, PartialEq)]
+pub enum AmmError {
     /// Invalid instruction
     #[error("Invalid instruction")]
     InvalidInstruction,
-    
+
     /// Not rent exempt
     #[error("Not rent exempt")]
     NotRentExempt,
-    
+
     /// Expected amount mismatch
     #[error("Expected amount mismatch")]
     ExpectedAmountMismatch,
-    
+
     /// Invalid authority
     #[error("Invalid authority")]
     InvalidAuthority,
-    
+
     /// Math operation overflow
     #[error("Math operation overflow")]
     MathOverflow,
+
+    /// Pool already initialized
+    #[error("Pool already initialized")]
+    PoolAlreadyInitialized,
+
+    /// Pool not initialized
+    #[error("Pool not initialized")]
+    PoolNotInitialized,
+
+    /// Invalid token program
+    #[error("Invalid token program")]
+    InvalidTokenProgram,
+
+    /// Pool authority does not match the derived address
+    #[error("Invalid pool authority")]
+    InvalidPoolAuthority,
+
+    /// Vault does not belong to the pool or is misconfigured
+    #[error("Invalid vault")]
+    InvalidVault,
+
+    /// LP mint does not belong to the pool or is misconfigured
+    #[error("Invalid LP mint")]
+    InvalidLpMint,
+
+    /// Both sides of the pool use the same mint
+    #[error("Pool tokens must use different mints")]
+    RepeatedMint,
+
+    /// Fee is outside the allowed range
+    #[error("Invalid fee")]
+    InvalidFee,
+
+    /// Amount must be greater than zero
+    #[error("Amount must be greater than zero")]
+    ZeroAmount,
+
+    /// Operation would leave the pool without liquidity
+    #[error("Insufficient liquidity")]
+    InsufficientLiquidity,
+
+    /// Output is below the caller's minimum
+    #[error("Slippage tolerance exceeded")]
+    SlippageExceeded,
+
+    /// Constant product decreased after a swap
+    #[error("Constant product invariant violated")]
+    InvariantViolated,
+
+    /// AMM config does not match the derived address or is not initialized
+    #[error("Invalid AMM config")]
+    InvalidConfig,
+
+    /// Fee is not one of the configured fee tiers
+    #[error("Fee is not an allowed fee tier")]
+    FeeTierNotAllowed,
+
+    /// Too many or duplicate fee tiers
+    #[error("Invalid fee tiers")]
+    InvalidFeeTiers,
+
+    /// Protocol fee account does not hold the pool's LP token for the fee owner
+    #[error("Invalid protocol fee account")]
+    InvalidProtocolFeeAccount,
+
+    /// TWAP observations are out of order or cover no time
+    #[error("Invalid TWAP window")]
+    InvalidTwapWindow,
+
+    /// Route has no hops, too many hops or hops whose tokens do not chain
+    #[error("Invalid swap route")]
+    InvalidRoute,
+
+    /// Tick spacing must be greater than zero
+    #[error("Invalid tick spacing")]
+    InvalidTickSpacing,
+
+    /// Tick is out of range or not a multiple of the tick spacing
+    #[error("Invalid tick range")]
+    InvalidTickRange,
+
+    /// Tick array does not belong to the pool or does not cover the tick
+    #[error("Invalid tick array")]
+    InvalidTickArray,
+
+    /// Square root price is outside the supported range
+    #[error("Invalid sqrt price")]
+    InvalidSqrtPrice,
+
+    /// Position does not match the derived address or the pool
+    #[error("Invalid position")]
+    InvalidPosition,
+
+    /// Program data account is not this program's upgradeable loader state
+    #[error("Invalid program data account")]
+    InvalidProgramData,
 }
 
-impl From<TemplateError> for ProgramError {
-    fn from(e: TemplateError) -> Self {
+impl From<AmmError> for ProgramError {
+    fn from(e: AmmError) -> Self {
         ProgramError::Custom(e as u32)
     }
 }
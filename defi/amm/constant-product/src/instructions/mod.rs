@@ -9,60 +9,566 @@ use solana_program::{
     system_program, sysvar,
 };
 
+use crate::utils::{find_config_address, find_program_data_address};
+
+/// Maximum number of pools a RouteSwap can trade through
+pub const MAX_ROUTE_HOPS: usize = 4;
+
+/// Number of accounts each RouteSwap hop takes
+pub const ROUTE_HOP_ACCOUNTS: usize = 4;
+
 /// Instructions supported by the constant-product-amm program
 #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
-pub enum TemplateInstruction {
-    /// Initialize a new instance
+pub enum AmmInstruction {
+    /// Initialize a new pool
+    ///
+    /// The vaults must be empty token accounts owned by the pool authority,
+    /// and the LP mint must have zero supply, the pool authority as mint
+    /// authority and no freeze authority.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The pool creator
+    /// 1. `[writable]` The pool account, owned by this program
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[]` The token A vault
+    /// 4. `[]` The token B vault
+    /// 5. `[]` The LP mint
+    /// 6. `[]` The token program
+    /// 7. `[]` The rent sysvar
+    /// 8. `[]` The AMM config
+    ///
+    InitializePool {
+        /// Swap fee in basis points, must be one of the config's fee tiers
+        fee_basis_points: u16,
+    },
+
+    /// Deposit both tokens in exchange for LP tokens
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The depositor
+    /// 1. `[writable]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The depositor's token A account
+    /// 4. `[writable]` The depositor's token B account
+    /// 5. `[writable]` The token A vault
+    /// 6. `[writable]` The token B vault
+    /// 7. `[writable]` The LP mint
+    /// 8. `[writable]` The depositor's LP token account
+    /// 9. `[]` The token program
+    /// 10. `[]` The AMM config
+    /// 11. `[writable]` The protocol fee LP token account
+    ///
+    DepositLiquidity {
+        /// Maximum amount of token A to deposit
+        max_amount_a: u64,
+        /// Maximum amount of token B to deposit
+        max_amount_b: u64,
+        /// Minimum amount of LP tokens to receive
+        min_lp_amount: u64,
+    },
+
+    /// Burn LP tokens and withdraw both tokens
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The LP token owner
+    /// 1. `[writable]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The owner's LP token account
+    /// 4. `[writable]` The LP mint
+    /// 5. `[writable]` The token A vault
+    /// 6. `[writable]` The token B vault
+    /// 7. `[writable]` The owner's token A account
+    /// 8. `[writable]` The owner's token B account
+    /// 9. `[]` The token program
+    /// 10. `[]` The AMM config
+    /// 11. `[writable]` The protocol fee LP token account
+    ///
+    WithdrawLiquidity {
+        /// Amount of LP tokens to burn
+        lp_amount: u64,
+        /// Minimum amount of token A to receive
+        min_amount_a: u64,
+        /// Minimum amount of token B to receive
+        min_amount_b: u64,
+    },
+
+    /// Swap one pool token for the other
+    ///
+    /// The direction is given by the vault order: tokens are deposited into
+    /// the input vault and paid out of the output vault.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The user
+    /// 1. `[writable]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The user's source token account
+    /// 4. `[writable]` The user's destination token account
+    /// 5. `[writable]` The input vault
+    /// 6. `[writable]` The output vault
+    /// 7. `[]` The token program
+    ///
+    Swap {
+        /// Amount of input tokens to swap
+        amount_in: u64,
+        /// Minimum amount of output tokens to receive
+        minimum_amount_out: u64,
+    },
+
+    /// Swap one pool token for an exact amount of the other
+    ///
+    /// Takes the same accounts as `Swap`.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The user
+    /// 1. `[writable]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The user's source token account
+    /// 4. `[writable]` The user's destination token account
+    /// 5. `[writable]` The input vault
+    /// 6. `[writable]` The output vault
+    /// 7. `[]` The token program
+    ///
+    SwapExactOut {
+        /// Exact amount of output tokens to receive
+        amount_out: u64,
+        /// Maximum amount of input tokens to pay
+        maximum_amount_in: u64,
+    },
+
+    /// Create the global AMM config
+    ///
+    /// Only the program's upgrade authority can create the config, so it
+    /// cannot be claimed by whoever calls first after deployment.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable, signer]` The program's upgrade authority, pays for the config account
+    /// 1. `[]` The program's `ProgramData` account
+    /// 2. `[writable]` The AMM config PDA
+    /// 3. `[]` The system program
+    ///
+    InitializeConfig {
+        /// Admin allowed to change pool fees
+        admin: Pubkey,
+        /// Owner of the LP token accounts that receive protocol fees
+        protocol_fee_owner: Pubkey,
+        /// Protocol fee share given to new pools, in basis points of the swap fee
+        default_protocol_fee_share: u16,
+        /// Allowed swap fees in basis points
+        fee_tiers: Vec<u16>,
+    },
+
+    /// Change a pool's swap fee and protocol fee share
+    ///
+    /// Protocol fees accrued under the old share are minted first.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The config admin
+    /// 1. `[]` The AMM config
+    /// 2. `[writable]` The pool account
+    /// 3. `[]` The pool authority PDA
+    /// 4. `[writable]` The LP mint
+    /// 5. `[writable]` The protocol fee LP token account
+    /// 6. `[]` The token program
+    ///
+    SetFees {
+        /// New swap fee in basis points, must be one of the config's fee tiers
+        fee_basis_points: u16,
+        /// New protocol fee share in basis points of the swap fee
+        protocol_fee_share: u16,
+    },
+
+    /// Read a pool's price accumulators brought forward to the current time
+    ///
+    /// Does not modify any account. The `twap::PriceObservation` is written
+    /// as return data, so other programs can CPI into this instruction, keep
+    /// the observation and later call `twap::twap` with a newer one.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The pool account
+    ///
+    ObserveTwap,
+
+    /// Swap through up to `MAX_ROUTE_HOPS` pools in one instruction
+    ///
+    /// Each hop spends the whole output of the previous one, which is paid
+    /// straight from the previous pool's output vault into the next pool's
+    /// input vault. Only the final output is checked against
+    /// `minimum_amount_out`.
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to initialize
-    /// 2. `[]` The system program
-    /// 3. `[]` The rent sysvar
+    /// 0. `[signer]` The user
+    /// 1. `[writable]` The user's source token account
+    /// 2. `[writable]` The user's destination token account
+    /// 3. `[]` The token program
     ///
-    Initialize {
-        /// Custom parameter 1
-        param1: u64,
-        /// Custom parameter 2
-        param2: u8,
+    /// Then for each hop, in order:
+    /// 0. `[writable]` The pool account
+    /// 1. `[]` The pool authority PDA
+    /// 2. `[writable]` The input vault
+    /// 3. `[writable]` The output vault
+    ///
+    RouteSwap {
+        /// Amount of input tokens to swap on the first hop
+        amount_in: u64,
+        /// Minimum amount of output tokens to receive from the last hop
+        minimum_amount_out: u64,
+    },
+
+    /// Initialize a concentrated liquidity pool
+    ///
+    /// The vaults must be empty token accounts owned by the pool authority.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The pool creator
+    /// 1. `[writable]` The pool account, owned by this program
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[]` The token A vault
+    /// 4. `[]` The token B vault
+    /// 5. `[]` The token program
+    /// 6. `[]` The rent sysvar
+    /// 7. `[]` The AMM config
+    ///
+    #[cfg(feature = "concentrated")]
+    InitializeClPool {
+        /// Distance between usable ticks
+        tick_spacing: u16,
+        /// Swap fee in basis points, must be one of the config's fee tiers
+        fee_basis_points: u16,
+        /// Starting square root price of token A in token B, Q64.64
+        initial_sqrt_price: u128,
     },
 
-    /// Perform operation 1
+    /// Create a tick array of a concentrated pool
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to modify
+    /// 0. `[writable, signer]` The payer
+    /// 1. `[]` The pool account
+    /// 2. `[writable]` The tick array PDA
+    /// 3. `[]` The system program
     ///
-    Operation1 {
-        /// Operation amount
-        amount: u64,
+    #[cfg(feature = "concentrated")]
+    InitializeTickArray {
+        /// First tick index covered, a multiple of `tick_spacing * TICK_ARRAY_SIZE`
+        start_tick_index: i32,
     },
 
-    /// Perform operation 2
+    /// Create an empty position over `[tick_lower, tick_upper)`
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to modify
+    /// 0. `[writable, signer]` The position owner, pays for the account
+    /// 1. `[]` The pool account
+    /// 2. `[writable]` The position PDA
+    /// 3. `[]` The system program
+    ///
+    #[cfg(feature = "concentrated")]
+    OpenPosition {
+        /// Lower tick bound, a multiple of the tick spacing
+        tick_lower: i32,
+        /// Upper tick bound, a multiple of the tick spacing
+        tick_upper: i32,
+    },
+
+    /// Add liquidity to a position
     ///
-    Operation2,
+    /// Accounts expected:
+    /// 0. `[signer]` The position owner
+    /// 1. `[writable]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The position
+    /// 4. `[writable]` The tick array holding the lower tick
+    /// 5. `[writable]` The tick array holding the upper tick
+    /// 6. `[writable]` The owner's token A account
+    /// 7. `[writable]` The owner's token B account
+    /// 8. `[writable]` The token A vault
+    /// 9. `[writable]` The token B vault
+    /// 10. `[]` The token program
+    ///
+    #[cfg(feature = "concentrated")]
+    IncreaseLiquidity {
+        /// Liquidity to add
+        liquidity: u128,
+        /// Maximum amount of token A to deposit
+        max_amount_a: u64,
+        /// Maximum amount of token B to deposit
+        max_amount_b: u64,
+    },
+
+    /// Remove liquidity from a position
+    ///
+    /// Takes the same accounts as `IncreaseLiquidity`. Fees stay in the
+    /// position until `CollectFees`.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The position owner
+    /// 1. `[writable]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The position
+    /// 4. `[writable]` The tick array holding the lower tick
+    /// 5. `[writable]` The tick array holding the upper tick
+    /// 6. `[writable]` The owner's token A account
+    /// 7. `[writable]` The owner's token B account
+    /// 8. `[writable]` The token A vault
+    /// 9. `[writable]` The token B vault
+    /// 10. `[]` The token program
+    ///
+    #[cfg(feature = "concentrated")]
+    DecreaseLiquidity {
+        /// Liquidity to remove
+        liquidity: u128,
+        /// Minimum amount of token A to receive
+        min_amount_a: u64,
+        /// Minimum amount of token B to receive
+        min_amount_b: u64,
+    },
+
+    /// Pay out the swap fees a position has earned
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The position owner
+    /// 1. `[]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The position
+    /// 4. `[]` The tick array holding the lower tick
+    /// 5. `[]` The tick array holding the upper tick
+    /// 6. `[writable]` The owner's token A account
+    /// 7. `[writable]` The owner's token B account
+    /// 8. `[writable]` The token A vault
+    /// 9. `[writable]` The token B vault
+    /// 10. `[]` The token program
+    ///
+    #[cfg(feature = "concentrated")]
+    CollectFees,
+
+    /// Swap against a concentrated pool
+    ///
+    /// The direction is given by the vault order, as for `Swap`. The tick
+    /// arrays must be contiguous and include the current tick; the swap stops
+    /// early at their edge and only the input used is charged.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The user
+    /// 1. `[writable]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The user's source token account
+    /// 4. `[writable]` The user's destination token account
+    /// 5. `[writable]` The input vault
+    /// 6. `[writable]` The output vault
+    /// 7. `[]` The token program
+    /// 8. `[writable]` One or more tick arrays the swap may cross
+    ///
+    #[cfg(feature = "concentrated")]
+    ClSwap {
+        /// Maximum amount of input tokens to swap
+        amount_in: u64,
+        /// Minimum amount of output tokens to receive
+        minimum_amount_out: u64,
+    },
 }
 
-/// Creates an Initialize instruction
-pub fn initialize(
+/// Accounts of one RouteSwap hop
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RouteHop {
+    /// The pool to swap through
+    pub pool: Pubkey,
+    /// The pool authority PDA
+    pub pool_authority: Pubkey,
+    /// The vault the hop's input is paid into
+    pub vault_in: Pubkey,
+    /// The vault the hop's output is paid out of
+    pub vault_out: Pubkey,
+}
+
+/// Creates an InitializePool instruction
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_pool(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
-    param1: u64,
-    param2: u8,
+    creator: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    token_a_vault: &Pubkey,
+    token_b_vault: &Pubkey,
+    lp_mint: &Pubkey,
+    fee_basis_points: u16,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
-        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*creator, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*token_a_vault, false),
+        AccountMeta::new_readonly(*token_b_vault, false),
+        AccountMeta::new_readonly(*lp_mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
         AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ];
+
+    let data = AmmInstruction::InitializePool { fee_basis_points };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a DepositLiquidity instruction
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_liquidity(
+    program_id: &Pubkey,
+    depositor: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_token_a: &Pubkey,
+    user_token_b: &Pubkey,
+    token_a_vault: &Pubkey,
+    token_b_vault: &Pubkey,
+    lp_mint: &Pubkey,
+    user_lp_token: &Pubkey,
+    protocol_fee_account: &Pubkey,
+    max_amount_a: u64,
+    max_amount_b: u64,
+    min_lp_amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*depositor, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new(*user_token_a, false),
+        AccountMeta::new(*user_token_b, false),
+        AccountMeta::new(*token_a_vault, false),
+        AccountMeta::new(*token_b_vault, false),
+        AccountMeta::new(*lp_mint, false),
+        AccountMeta::new(*user_lp_token, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(*protocol_fee_account, false),
+    ];
+
+    let data = AmmInstruction::DepositLiquidity {
+        max_amount_a,
+        max_amount_b,
+        min_lp_amount,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a WithdrawLiquidity instruction
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_liquidity(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_lp_token: &Pubkey,
+    lp_mint: &Pubkey,
+    token_a_vault: &Pubkey,
+    token_b_vault: &Pubkey,
+    user_token_a: &Pubkey,
+    user_token_b: &Pubkey,
+    protocol_fee_account: &Pubkey,
+    lp_amount: u64,
+    min_amount_a: u64,
+    min_amount_b: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new(*user_lp_token, false),
+        AccountMeta::new(*lp_mint, false),
+        AccountMeta::new(*token_a_vault, false),
+        AccountMeta::new(*token_b_vault, false),
+        AccountMeta::new(*user_token_a, false),
+        AccountMeta::new(*user_token_b, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(*protocol_fee_account, false),
+    ];
+
+    let data = AmmInstruction::WithdrawLiquidity {
+        lp_amount,
+        min_amount_a,
+        min_amount_b,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a Swap instruction
+#[allow(clippy::too_many_arguments)]
+pub fn swap(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_source: &Pubkey,
+    user_destination: &Pubkey,
+    vault_in: &Pubkey,
+    vault_out: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new(*user_source, false),
+        AccountMeta::new(*user_destination, false),
+        AccountMeta::new(*vault_in, false),
+        AccountMeta::new(*vault_out, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = AmmInstruction::Swap {
+        amount_in,
+        minimum_amount_out,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a SwapExactOut instruction
+#[allow(clippy::too_many_arguments)]
+pub fn swap_exact_out(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_source: &Pubkey,
+    user_destination: &Pubkey,
+    vault_in: &Pubkey,
+    vault_out: &Pubkey,
+    amount_out: u64,
+    maximum_amount_in: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new(*user_source, false),
+        AccountMeta::new(*user_destination, false),
+        AccountMeta::new(*vault_in, false),
+        AccountMeta::new(*vault_out, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
     ];
 
-    let data = TemplateInstruction::Initialize { param1, param2 };
+    let data = AmmInstruction::SwapExactOut {
+        amount_out,
+        maximum_amount_in,
+    };
 
     Instruction {
         program_id: *program_id,
@@ -71,19 +577,28 @@ pub fn initialize(
     }
 }
 
-/// Creates an Operation1 instruction
-pub fn operation1(
+/// Creates an InitializeConfig instruction
+pub fn initialize_config(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
-    amount: u64,
+    upgrade_authority: &Pubkey,
+    admin: &Pubkey,
+    protocol_fee_owner: &Pubkey,
+    default_protocol_fee_share: u16,
+    fee_tiers: Vec<u16>,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
+        AccountMeta::new(*upgrade_authority, true),
+        AccountMeta::new_readonly(find_program_data_address(program_id), false),
+        AccountMeta::new(find_config_address(program_id).0, false),
+        AccountMeta::new_readonly(system_program::id(), false),
     ];
 
-    let data = TemplateInstruction::Operation1 { amount };
+    let data = AmmInstruction::InitializeConfig {
+        admin: *admin,
+        protocol_fee_owner: *protocol_fee_owner,
+        default_protocol_fee_share,
+        fee_tiers,
+    };
 
     Instruction {
         program_id: *program_id,
@@ -92,18 +607,80 @@ pub fn operation1(
     }
 }
 
-/// Creates an Operation2 instruction
-pub fn operation2(
+/// Creates a SetFees instruction
+#[allow(clippy::too_many_arguments)]
+pub fn set_fees(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
+    admin: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    lp_mint: &Pubkey,
+    protocol_fee_account: &Pubkey,
+    fee_basis_points: u16,
+    protocol_fee_share: u16,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new(*lp_mint, false),
+        AccountMeta::new(*protocol_fee_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
     ];
 
-    let data = TemplateInstruction::Operation2;
+    let data = AmmInstruction::SetFees {
+        fee_basis_points,
+        protocol_fee_share,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an ObserveTwap instruction
+pub fn observe_twap(program_id: &Pubkey, pool: &Pubkey) -> Instruction {
+    let accounts = vec![AccountMeta::new_readonly(*pool, false)];
+
+    let data = AmmInstruction::ObserveTwap;
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a RouteSwap instruction
+pub fn route_swap(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    user_source: &Pubkey,
+    user_destination: &Pubkey,
+    hops: &[RouteHop],
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new(*user_source, false),
+        AccountMeta::new(*user_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    for hop in hops {
+        accounts.push(AccountMeta::new(hop.pool, false));
+        accounts.push(AccountMeta::new_readonly(hop.pool_authority, false));
+        accounts.push(AccountMeta::new(hop.vault_in, false));
+        accounts.push(AccountMeta::new(hop.vault_out, false));
+    }
+
+    let data = AmmInstruction::RouteSwap {
+        amount_in,
+        minimum_amount_out,
+    };
 
     Instruction {
         program_id: *program_id,
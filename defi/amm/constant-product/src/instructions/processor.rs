@@ -3,18 +3,34 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
+    program::{invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
     pubkey::Pubkey,
     rent::Rent,
+    system_instruction,
     sysvar::Sysvar,
 };
 
+#[cfg(feature = "concentrated")]
+use crate::concentrated;
 use crate::{
-    errors::TemplateError,
-    instructions::TemplateInstruction,
-    state::TemplateAccount,
+    errors::AmmError,
+    instructions::{AmmInstruction, MAX_ROUTE_HOPS, ROUTE_HOP_ACCOUNTS},
+    quote::{
+        quote_deposit, quote_exact_in, quote_exact_out, quote_protocol_fee, quote_withdraw,
+        BASIS_POINTS_DENOMINATOR, MAX_PROTOCOL_FEE_SHARE,
+    },
+    state::{AmmConfig, Pool, MAX_FEE_TIERS},
+    twap::observe,
+    utils::{
+        check_account_owner, check_pool_authority, check_signer, check_upgrade_authority,
+        find_config_address, find_pool_authority, CONFIG_SEED,
+    },
 };
 
 /// Processes an instruction
@@ -23,145 +39,1170 @@ pub fn process_instruction(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    let instruction = TemplateInstruction::try_from_slice(instruction_data)
+    let instruction = AmmInstruction::try_from_slice(instruction_data)
         .map_err(|_| ProgramError::InvalidInstructionData)?;
 
     match instruction {
-        TemplateInstruction::Initialize { param1, param2 } => {
-            msg!("Instruction: Initialize");
-            process_initialize(program_id, accounts, param1, param2)
+        AmmInstruction::InitializePool { fee_basis_points } => {
+            msg!("Instruction: Initialize Pool");
+            process_initialize_pool(program_id, accounts, fee_basis_points)
         }
-        TemplateInstruction::Operation1 { amount } => {
-            msg!("Instruction: Operation1");
-            process_operation1(program_id, accounts, amount)
+        AmmInstruction::DepositLiquidity {
+            max_amount_a,
+            max_amount_b,
+            min_lp_amount,
+        } => {
+            msg!("Instruction: Deposit Liquidity");
+            process_deposit_liquidity(
+                program_id,
+                accounts,
+                max_amount_a,
+                max_amount_b,
+                min_lp_amount,
+            )
         }
-        TemplateInstruction::Operation2 => {
-            msg!("Instruction: Operation2");
-            process_operation2(program_id, accounts)
+        AmmInstruction::WithdrawLiquidity {
+            lp_amount,
+            min_amount_a,
+            min_amount_b,
+        } => {
+            msg!("Instruction: Withdraw Liquidity");
+            process_withdraw_liquidity(program_id, accounts, lp_amount, min_amount_a, min_amount_b)
+        }
+        AmmInstruction::Swap {
+            amount_in,
+            minimum_amount_out,
+        } => {
+            msg!("Instruction: Swap");
+            process_swap(
+                program_id,
+                accounts,
+                SwapAmount::ExactIn {
+                    amount_in,
+                    minimum_amount_out,
+                },
+            )
+        }
+        AmmInstruction::SwapExactOut {
+            amount_out,
+            maximum_amount_in,
+        } => {
+            msg!("Instruction: Swap Exact Out");
+            process_swap(
+                program_id,
+                accounts,
+                SwapAmount::ExactOut {
+                    amount_out,
+                    maximum_amount_in,
+                },
+            )
+        }
+        AmmInstruction::InitializeConfig {
+            admin,
+            protocol_fee_owner,
+            default_protocol_fee_share,
+            fee_tiers,
+        } => {
+            msg!("Instruction: Initialize Config");
+            process_initialize_config(
+                program_id,
+                accounts,
+                admin,
+                protocol_fee_owner,
+                default_protocol_fee_share,
+                fee_tiers,
+            )
+        }
+        AmmInstruction::SetFees {
+            fee_basis_points,
+            protocol_fee_share,
+        } => {
+            msg!("Instruction: Set Fees");
+            process_set_fees(program_id, accounts, fee_basis_points, protocol_fee_share)
+        }
+        AmmInstruction::ObserveTwap => {
+            msg!("Instruction: Observe TWAP");
+            process_observe_twap(program_id, accounts)
+        }
+        AmmInstruction::RouteSwap {
+            amount_in,
+            minimum_amount_out,
+        } => {
+            msg!("Instruction: Route Swap");
+            process_route_swap(program_id, accounts, amount_in, minimum_amount_out)
+        }
+        #[cfg(feature = "concentrated")]
+        AmmInstruction::InitializeClPool {
+            tick_spacing,
+            fee_basis_points,
+            initial_sqrt_price,
+        } => {
+            msg!("Instruction: Initialize Concentrated Pool");
+            concentrated::processor::process_initialize_cl_pool(
+                program_id,
+                accounts,
+                tick_spacing,
+                fee_basis_points,
+                initial_sqrt_price,
+            )
+        }
+        #[cfg(feature = "concentrated")]
+        AmmInstruction::InitializeTickArray { start_tick_index } => {
+            msg!("Instruction: Initialize Tick Array");
+            concentrated::processor::process_initialize_tick_array(
+                program_id,
+                accounts,
+                start_tick_index,
+            )
+        }
+        #[cfg(feature = "concentrated")]
+        AmmInstruction::OpenPosition {
+            tick_lower,
+            tick_upper,
+        } => {
+            msg!("Instruction: Open Position");
+            concentrated::processor::process_open_position(
+                program_id, accounts, tick_lower, tick_upper,
+            )
+        }
+        #[cfg(feature = "concentrated")]
+        AmmInstruction::IncreaseLiquidity {
+            liquidity,
+            max_amount_a,
+            max_amount_b,
+        } => {
+            msg!("Instruction: Increase Liquidity");
+            let liquidity_delta = i128::try_from(liquidity).map_err(|_| AmmError::MathOverflow)?;
+            concentrated::processor::process_modify_liquidity(
+                program_id,
+                accounts,
+                liquidity_delta,
+                max_amount_a,
+                max_amount_b,
+            )
+        }
+        #[cfg(feature = "concentrated")]
+        AmmInstruction::DecreaseLiquidity {
+            liquidity,
+            min_amount_a,
+            min_amount_b,
+        } => {
+            msg!("Instruction: Decrease Liquidity");
+            let liquidity_delta = i128::try_from(liquidity).map_err(|_| AmmError::MathOverflow)?;
+            concentrated::processor::process_modify_liquidity(
+                program_id,
+                accounts,
+                -liquidity_delta,
+                min_amount_a,
+                min_amount_b,
+            )
+        }
+        #[cfg(feature = "concentrated")]
+        AmmInstruction::CollectFees => {
+            msg!("Instruction: Collect Fees");
+            concentrated::processor::process_collect_fees(program_id, accounts)
+        }
+        #[cfg(feature = "concentrated")]
+        AmmInstruction::ClSwap {
+            amount_in,
+            minimum_amount_out,
+        } => {
+            msg!("Instruction: Concentrated Swap");
+            concentrated::processor::process_cl_swap(
+                program_id,
+                accounts,
+                amount_in,
+                minimum_amount_out,
+            )
         }
     }
 }
 
-/// Processes an Initialize instruction
-fn process_initialize(
+/// Processes an InitializePool instruction
+fn process_initialize_pool(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    param1: u64,
-    param2: u8,
+    fee_basis_points: u16,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    let system_program_info = next_account_info(account_info_iter)?;
+    let creator_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let token_a_vault_info = next_account_info(account_info_iter)?;
+    let token_b_vault_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
-        return Err(TemplateError::InvalidAuthority.into());
-    }
-    
+    let config_info = next_account_info(account_info_iter)?;
+
+    // Check the creator is a signer
+    check_signer(creator_info)?;
+
+    // Check pool account ownership
+    check_account_owner(pool_info, program_id)?;
+
     // Check for rent exemption
     let rent = &Rent::from_account_info(rent_info)?;
-    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
-        return Err(TemplateError::NotRentExempt.into());
-    }
-    
-    // Initialize account data
-    let template_account = TemplateAccount {
-        authority: *authority_info.key,
-        value1: param1,
-        value2: param2,
+    if !rent.is_exempt(pool_info.lamports(), pool_info.data_len()) {
+        return Err(AmmError::NotRentExempt.into());
+    }
+
+    // Check the pool has not been initialized yet
+    if pool_info.data_len() != Pool::get_size() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if pool_info.data.borrow()[0] != 0 {
+        return Err(AmmError::PoolAlreadyInitialized.into());
+    }
+
+    // Validate token program
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidTokenProgram.into());
+    }
+
+    // Validate fee against the configured tiers
+    let config = load_config(program_id, config_info)?;
+    if !config.is_fee_tier(fee_basis_points) {
+        return Err(AmmError::FeeTierNotAllowed.into());
+    }
+
+    // Validate pool authority
+    let (pool_authority, bump_seed) = find_pool_authority(program_id, pool_info.key);
+    if pool_authority != *pool_authority_info.key {
+        return Err(AmmError::InvalidPoolAuthority.into());
+    }
+
+    // Validate vaults
+    let token_a_vault = unpack_vault(token_a_vault_info, &pool_authority)?;
+    let token_b_vault = unpack_vault(token_b_vault_info, &pool_authority)?;
+    if token_a_vault.mint == token_b_vault.mint {
+        return Err(AmmError::RepeatedMint.into());
+    }
+
+    // Validate LP mint
+    check_account_owner(lp_mint_info, &spl_token::id()).map_err(|_| AmmError::InvalidLpMint)?;
+    let lp_mint = spl_token::state::Mint::unpack(&lp_mint_info.data.borrow())?;
+    if lp_mint.mint_authority != COption::Some(pool_authority)
+        || lp_mint.freeze_authority.is_some()
+        || lp_mint.supply != 0
+    {
+        return Err(AmmError::InvalidLpMint.into());
+    }
+
+    // Initialize pool data
+    let pool = Pool {
         is_initialized: true,
+        bump_seed,
+        token_a_mint: token_a_vault.mint,
+        token_b_mint: token_b_vault.mint,
+        token_a_vault: *token_a_vault_info.key,
+        token_b_vault: *token_b_vault_info.key,
+        lp_mint: *lp_mint_info.key,
+        config: *config_info.key,
+        fee_basis_points,
+        protocol_fee_share: config.default_protocol_fee_share,
+        reserve_a: 0,
+        reserve_b: 0,
+        k_last: 0,
+        price_a_cumulative: 0,
+        price_b_cumulative: 0,
+        last_update_timestamp: Clock::get()?.unix_timestamp,
     };
-    
-    // Save account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+
+    // Save pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
     Ok(())
 }
 
-/// Processes an Operation1 instruction
-fn process_operation1(
+/// Processes a DepositLiquidity instruction
+fn process_deposit_liquidity(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    amount: u64,
+    max_amount_a: u64,
+    max_amount_b: u64,
+    min_lp_amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let depositor_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let user_token_a_info = next_account_info(account_info_iter)?;
+    let user_token_b_info = next_account_info(account_info_iter)?;
+    let token_a_vault_info = next_account_info(account_info_iter)?;
+    let token_b_vault_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let user_lp_token_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let protocol_fee_info = next_account_info(account_info_iter)?;
+
+    // Check the depositor is a signer
+    check_signer(depositor_info)?;
+
+    // Load and validate the pool
+    let mut pool = load_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+    if pool.token_a_vault != *token_a_vault_info.key
+        || pool.token_b_vault != *token_b_vault_info.key
+    {
+        return Err(AmmError::InvalidVault.into());
+    }
+    if pool.lp_mint != *lp_mint_info.key {
+        return Err(AmmError::InvalidLpMint.into());
+    }
+
+    let config = load_config(program_id, config_info)?;
+    check_protocol_fee_account(&config, &pool, protocol_fee_info)?;
+
+    // Validate amounts
+    if max_amount_a == 0 || max_amount_b == 0 {
+        return Err(AmmError::ZeroAmount.into());
+    }
+
+    // Mint accrued protocol fees before pricing the deposit
+    let lp_supply = mint_protocol_fee(
+        &pool,
+        pool_info,
+        pool_authority_info,
+        lp_mint_info,
+        protocol_fee_info,
+        token_program_info,
+    )?;
+
+    // Calculate LP tokens to mint and amounts to take
+    let (lp_amount, amount_a, amount_b) = quote_deposit(
+        max_amount_a,
+        max_amount_b,
+        pool.reserve_a,
+        pool.reserve_b,
+        lp_supply,
+    )?;
+    if lp_amount == 0 {
+        return Err(AmmError::ZeroAmount.into());
+    }
+    if lp_amount < min_lp_amount {
+        return Err(AmmError::SlippageExceeded.into());
+    }
+
+    // Transfer tokens from the depositor to the vaults
+    transfer_to_vault(
+        token_program_info,
+        user_token_a_info,
+        token_a_vault_info,
+        depositor_info,
+        amount_a,
+    )?;
+    transfer_to_vault(
+        token_program_info,
+        user_token_b_info,
+        token_b_vault_info,
+        depositor_info,
+        amount_b,
+    )?;
+
+    // Mint LP tokens to the depositor
+    mint_lp(
+        &pool,
+        pool_info,
+        pool_authority_info,
+        lp_mint_info,
+        user_lp_token_info,
+        token_program_info,
+        lp_amount,
+    )?;
+
+    // Accumulate prices at the old reserves
+    pool.update_price_cumulative(Clock::get()?.unix_timestamp);
+
+    // Update reserves
+    pool.reserve_a = pool
+        .reserve_a
+        .checked_add(amount_a)
+        .ok_or(AmmError::MathOverflow)?;
+    pool.reserve_b = pool
+        .reserve_b
+        .checked_add(amount_b)
+        .ok_or(AmmError::MathOverflow)?;
+    pool.update_k_last();
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a WithdrawLiquidity instruction
+fn process_withdraw_liquidity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lp_amount: u64,
+    min_amount_a: u64,
+    min_amount_b: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let user_lp_token_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let token_a_vault_info = next_account_info(account_info_iter)?;
+    let token_b_vault_info = next_account_info(account_info_iter)?;
+    let user_token_a_info = next_account_info(account_info_iter)?;
+    let user_token_b_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let protocol_fee_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    // Load and validate the pool
+    let mut pool = load_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+    if pool.token_a_vault != *token_a_vault_info.key
+        || pool.token_b_vault != *token_b_vault_info.key
+    {
+        return Err(AmmError::InvalidVault.into());
+    }
+    if pool.lp_mint != *lp_mint_info.key {
+        return Err(AmmError::InvalidLpMint.into());
+    }
+
+    let config = load_config(program_id, config_info)?;
+    check_protocol_fee_account(&config, &pool, protocol_fee_info)?;
+
+    // Validate amount
+    if lp_amount == 0 {
+        return Err(AmmError::ZeroAmount.into());
+    }
+
+    // Mint accrued protocol fees before pricing the withdrawal
+    let lp_supply = mint_protocol_fee(
+        &pool,
+        pool_info,
+        pool_authority_info,
+        lp_mint_info,
+        protocol_fee_info,
+        token_program_info,
+    )?;
+
+    // Calculate amounts to return
+    let (amount_a, amount_b) =
+        quote_withdraw(lp_amount, pool.reserve_a, pool.reserve_b, lp_supply)?;
+    if amount_a < min_amount_a || amount_b < min_amount_b {
+        return Err(AmmError::SlippageExceeded.into());
+    }
+
+    // Burn the owner's LP tokens
+    invoke(
+        &spl_token::instruction::burn(
+            token_program_info.key,
+            user_lp_token_info.key,
+            lp_mint_info.key,
+            owner_info.key,
+            &[],
+            lp_amount,
+        )?,
+        &[
+            user_lp_token_info.clone(),
+            lp_mint_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+    )?;
+
+    // Transfer tokens from the vaults to the owner
+    let pool_seeds: &[&[u8]] = &[pool_info.key.as_ref(), &[pool.bump_seed]];
+    transfer_from_vault(
+        token_program_info,
+        token_a_vault_info,
+        user_token_a_info,
+        pool_authority_info,
+        pool_seeds,
+        amount_a,
+    )?;
+    transfer_from_vault(
+        token_program_info,
+        token_b_vault_info,
+        user_token_b_info,
+        pool_authority_info,
+        pool_seeds,
+        amount_b,
+    )?;
+
+    // Accumulate prices at the old reserves
+    pool.update_price_cumulative(Clock::get()?.unix_timestamp);
+
+    // Update reserves
+    pool.reserve_a = pool
+        .reserve_a
+        .checked_sub(amount_a)
+        .ok_or(AmmError::MathOverflow)?;
+    pool.reserve_b = pool
+        .reserve_b
+        .checked_sub(amount_b)
+        .ok_or(AmmError::MathOverflow)?;
+    pool.update_k_last();
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Amount a swap is specified in, with its slippage bound
+enum SwapAmount {
+    /// Spend exactly `amount_in`, receive at least `minimum_amount_out`
+    ExactIn {
+        amount_in: u64,
+        minimum_amount_out: u64,
+    },
+    /// Receive exactly `amount_out`, spend at most `maximum_amount_in`
+    ExactOut {
+        amount_out: u64,
+        maximum_amount_in: u64,
+    },
+}
+
+/// Processes a Swap or SwapExactOut instruction
+fn process_swap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    swap_amount: SwapAmount,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let user_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let user_source_info = next_account_info(account_info_iter)?;
+    let user_destination_info = next_account_info(account_info_iter)?;
+    let vault_in_info = next_account_info(account_info_iter)?;
+    let vault_out_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the user is a signer
+    check_signer(user_info)?;
+
+    // Load and validate the pool
+    let mut pool = load_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+
+    // Resolve swap direction from the vaults
+    let (vault_in, vault_out, reserve_in, reserve_out) = pool
+        .swap_direction(vault_in_info.key)
+        .ok_or(AmmError::InvalidVault)?;
+    if vault_in != *vault_in_info.key || vault_out != *vault_out_info.key {
+        return Err(AmmError::InvalidVault.into());
+    }
+
+    // Price the swap and check the slippage bound
+    let (amount_in, amount_out) = match swap_amount {
+        SwapAmount::ExactIn {
+            amount_in,
+            minimum_amount_out,
+        } => {
+            let amount_out =
+                quote_exact_in(amount_in, reserve_in, reserve_out, pool.fee_basis_points)?;
+            if amount_out < minimum_amount_out {
+                return Err(AmmError::SlippageExceeded.into());
+            }
+            (amount_in, amount_out)
+        }
+        SwapAmount::ExactOut {
+            amount_out,
+            maximum_amount_in,
+        } => {
+            let amount_in =
+                quote_exact_out(amount_out, reserve_in, reserve_out, pool.fee_basis_points)?;
+            if amount_in > maximum_amount_in {
+                return Err(AmmError::SlippageExceeded.into());
+            }
+            (amount_in, amount_out)
+        }
+    };
+
+    // Check the invariant and update the reserves
+    apply_swap(
+        &mut pool,
+        &vault_in,
+        amount_in,
+        amount_out,
+        Clock::get()?.unix_timestamp,
+    )?;
+
+    // Transfer input tokens to the pool and output tokens to the user
+    transfer_to_vault(
+        token_program_info,
+        user_source_info,
+        vault_in_info,
+        user_info,
+        amount_in,
+    )?;
+    let pool_seeds: &[&[u8]] = &[pool_info.key.as_ref(), &[pool.bump_seed]];
+    transfer_from_vault(
+        token_program_info,
+        vault_out_info,
+        user_destination_info,
+        pool_authority_info,
+        pool_seeds,
+        amount_out,
+    )?;
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a RouteSwap instruction
+fn process_route_swap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let user_info = next_account_info(account_info_iter)?;
+    let user_source_info = next_account_info(account_info_iter)?;
+    let user_destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let hop_infos = account_info_iter.as_slice();
+
+    // Check the user is a signer
+    check_signer(user_info)?;
+
+    // Each hop takes a pool, its authority, an input vault and an output vault
+    if hop_infos.is_empty()
+        || hop_infos.len() % ROUTE_HOP_ACCOUNTS != 0
+        || hop_infos.len() / ROUTE_HOP_ACCOUNTS > MAX_ROUTE_HOPS
+    {
+        return Err(AmmError::InvalidRoute.into());
+    }
+    let hops: Vec<&[AccountInfo]> = hop_infos.chunks(ROUTE_HOP_ACCOUNTS).collect();
+
+    let now = Clock::get()?.unix_timestamp;
+    let mut amount = amount_in;
+    for (index, hop) in hops.iter().enumerate() {
+        let [pool_info, pool_authority_info, vault_in_info, vault_out_info] = hop else {
+            return Err(AmmError::InvalidRoute.into());
+        };
+
+        // Load and validate the pool
+        let mut pool = load_pool(
+            program_id,
+            pool_info,
+            pool_authority_info,
+            token_program_info,
+        )?;
+
+        // Resolve swap direction from the vaults
+        let (vault_in, vault_out, reserve_in, reserve_out) = pool
+            .swap_direction(vault_in_info.key)
+            .ok_or(AmmError::InvalidVault)?;
+        if vault_in != *vault_in_info.key || vault_out != *vault_out_info.key {
+            return Err(AmmError::InvalidVault.into());
+        }
+
+        // Price the hop and apply it to the pool
+        let amount_out = quote_exact_in(amount, reserve_in, reserve_out, pool.fee_basis_points)?;
+        apply_swap(&mut pool, &vault_in, amount, amount_out, now)?;
+
+        // The first hop is paid by the user
+        if index == 0 {
+            transfer_to_vault(
+                token_program_info,
+                user_source_info,
+                vault_in_info,
+                user_info,
+                amount,
+            )?;
+        }
+
+        // Pay out straight into the next hop's input vault, which must hold
+        // the token this hop pays out, or to the user
+        let destination_info = match hops.get(index + 1) {
+            Some(next_hop) => {
+                let mint_out = if vault_out == pool.token_a_vault {
+                    pool.token_a_mint
+                } else {
+                    pool.token_b_mint
+                };
+                check_account_owner(&next_hop[2], &spl_token::id())?;
+                let next_vault_in = spl_token::state::Account::unpack(&next_hop[2].data.borrow())?;
+                if next_vault_in.mint != mint_out {
+                    return Err(AmmError::InvalidRoute.into());
+                }
+                &next_hop[2]
+            }
+            None => user_destination_info,
+        };
+        let pool_seeds: &[&[u8]] = &[pool_info.key.as_ref(), &[pool.bump_seed]];
+        transfer_from_vault(
+            token_program_info,
+            vault_out_info,
+            destination_info,
+            pool_authority_info,
+            pool_seeds,
+            amount_out,
+        )?;
+
+        // Save each pool before the next hop so a repeated pool sees this hop
+        pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+        amount = amount_out;
+    }
+
+    // One slippage check on the final output
+    if amount < minimum_amount_out {
+        return Err(AmmError::SlippageExceeded.into());
+    }
+
+    Ok(())
+}
+
+/// Processes an InitializeConfig instruction
+fn process_initialize_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    admin: Pubkey,
+    protocol_fee_owner: Pubkey,
+    default_protocol_fee_share: u16,
+    fee_tiers: Vec<u16>,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
-        return Err(TemplateError::InvalidAuthority.into());
-    }
-    
-    // Deserialize account data
-    let mut template_account = TemplateAccount::try_from_slice(&account_info.data.borrow())?;
-    
-    // Verify authority
-    if template_account.authority != *authority_info.key {
-        return Err(TemplateError::InvalidAuthority.into());
-    }
-    
-    // Update account data
-    template_account.value1 = template_account.value1.checked_add(amount)
-        .ok_or(TemplateError::MathOverflow)?;
-    
-    // Save updated account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+    let upgrade_authority_info = next_account_info(account_info_iter)?;
+    let program_data_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    // Only the upgrade authority can create the config
+    check_signer(upgrade_authority_info)?;
+    check_upgrade_authority(program_id, program_data_info, upgrade_authority_info.key)?;
+
+    // Validate the config address
+    let (config_address, bump_seed) = find_config_address(program_id);
+    if config_address != *config_info.key {
+        return Err(AmmError::InvalidConfig.into());
+    }
+    if !config_info.data_is_empty() {
+        return Err(AmmError::PoolAlreadyInitialized.into());
+    }
+
+    // Validate fees
+    if default_protocol_fee_share > MAX_PROTOCOL_FEE_SHARE {
+        return Err(AmmError::InvalidFee.into());
+    }
+    if fee_tiers.is_empty() || fee_tiers.len() > MAX_FEE_TIERS {
+        return Err(AmmError::InvalidFeeTiers.into());
+    }
+    let mut tiers = [0u16; MAX_FEE_TIERS];
+    for (i, fee_basis_points) in fee_tiers.iter().enumerate() {
+        if *fee_basis_points as u64 >= BASIS_POINTS_DENOMINATOR {
+            return Err(AmmError::InvalidFee.into());
+        }
+        if fee_tiers[..i].contains(fee_basis_points) {
+            return Err(AmmError::InvalidFeeTiers.into());
+        }
+        tiers[i] = *fee_basis_points;
+    }
+
+    // Create the config account at its PDA
+    let rent = Rent::get()?;
+    let config_size = AmmConfig::get_size();
+    invoke_signed(
+        &system_instruction::create_account(
+            upgrade_authority_info.key,
+            config_info.key,
+            rent.minimum_balance(config_size),
+            config_size as u64,
+            program_id,
+        ),
+        &[
+            upgrade_authority_info.clone(),
+            config_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&[CONFIG_SEED, &[bump_seed]]],
+    )?;
+
+    // Initialize config data
+    let config = AmmConfig {
+        is_initialized: true,
+        bump_seed,
+        admin,
+        protocol_fee_owner,
+        default_protocol_fee_share,
+        fee_tier_count: fee_tiers.len() as u8,
+        fee_tiers: tiers,
+    };
+
+    // Save config data
+    config.serialize(&mut &mut config_info.data.borrow_mut()[..])?;
+
     Ok(())
 }
 
-/// Processes an Operation2 instruction
-fn process_operation2(
+/// Processes a SetFees instruction
+fn process_set_fees(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
+    fee_basis_points: u16,
+    protocol_fee_share: u16,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
+    // Get accounts
+    let admin_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let protocol_fee_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the admin is a signer
+    check_signer(admin_info)?;
+
+    // Load and validate the config and the pool
+    let config = load_config(program_id, config_info)?;
+    if config.admin != *admin_info.key {
+        return Err(AmmError::InvalidAuthority.into());
+    }
+    let mut pool = load_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+    if pool.config != *config_info.key {
+        return Err(AmmError::InvalidConfig.into());
+    }
+    if pool.lp_mint != *lp_mint_info.key {
+        return Err(AmmError::InvalidLpMint.into());
+    }
+    check_protocol_fee_account(&config, &pool, protocol_fee_info)?;
+
+    // Validate the new fees
+    if !config.is_fee_tier(fee_basis_points) {
+        return Err(AmmError::FeeTierNotAllowed.into());
+    }
+    if protocol_fee_share > MAX_PROTOCOL_FEE_SHARE {
+        return Err(AmmError::InvalidFee.into());
+    }
+
+    // Settle protocol fees earned under the old share
+    mint_protocol_fee(
+        &pool,
+        pool_info,
+        pool_authority_info,
+        lp_mint_info,
+        protocol_fee_info,
+        token_program_info,
+    )?;
+
+    // Update fees
+    pool.fee_basis_points = fee_basis_points;
+    pool.protocol_fee_share = protocol_fee_share;
+    pool.update_k_last();
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes an ObserveTwap instruction
+fn process_observe_twap(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
-        return Err(TemplateError::InvalidAuthority.into());
-    }
-    
-    // Deserialize account data
-    let mut template_account = TemplateAccount::try_from_slice(&account_info.data.borrow())?;
-    
-    // Verify authority
-    if template_account.authority != *authority_info.key {
-        return Err(TemplateError::InvalidAuthority.into());
-    }
-    
-    // Update account data
-    template_account.value2 = template_account.value2.checked_add(1)
-        .ok_or(TemplateError::MathOverflow)?;
-    
-    // Save updated account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+    let pool_info = next_account_info(account_info_iter)?;
+
+    // Load the pool
+    check_account_owner(pool_info, program_id)?;
+    let pool = Pool::try_from_slice(&pool_info.data.borrow())?;
+    if !pool.is_initialized {
+        return Err(AmmError::PoolNotInitialized.into());
+    }
+
+    // Return the accumulators as of now
+    let observation = observe(&pool, Clock::get()?.unix_timestamp);
+    set_return_data(&borsh::to_vec(&observation)?);
+
+    Ok(())
+}
+
+/// Checks a priced swap and applies it to the pool reserves
+///
+/// Rejects swaps with no input or output and any swap that would decrease
+/// `k`, then accumulates prices at the old reserves.
+fn apply_swap(
+    pool: &mut Pool,
+    vault_in: &Pubkey,
+    amount_in: u64,
+    amount_out: u64,
+    now: i64,
+) -> ProgramResult {
+    if amount_in == 0 || amount_out == 0 {
+        return Err(AmmError::ZeroAmount.into());
+    }
+
+    let (reserve_in, reserve_out) = if *vault_in == pool.token_a_vault {
+        (pool.reserve_a, pool.reserve_b)
+    } else {
+        (pool.reserve_b, pool.reserve_a)
+    };
+    let new_reserve_in = reserve_in
+        .checked_add(amount_in)
+        .ok_or(AmmError::MathOverflow)?;
+    let new_reserve_out = reserve_out
+        .checked_sub(amount_out)
+        .ok_or(AmmError::InsufficientLiquidity)?;
+
+    // Check the invariant never decreases
+    if (new_reserve_in as u128) * (new_reserve_out as u128)
+        < (reserve_in as u128) * (reserve_out as u128)
+    {
+        return Err(AmmError::InvariantViolated.into());
+    }
+
+    // Accumulate prices at the old reserves
+    pool.update_price_cumulative(now);
+
+    // Update reserves
+    if *vault_in == pool.token_a_vault {
+        pool.reserve_a = new_reserve_in;
+        pool.reserve_b = new_reserve_out;
+    } else {
+        pool.reserve_b = new_reserve_in;
+        pool.reserve_a = new_reserve_out;
+    }
+
+    Ok(())
+}
+
+/// Loads an initialized pool and validates its authority and the token program
+fn load_pool(
+    program_id: &Pubkey,
+    pool_info: &AccountInfo,
+    pool_authority_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+) -> Result<Pool, ProgramError> {
+    check_account_owner(pool_info, program_id)?;
+
+    let pool = Pool::try_from_slice(&pool_info.data.borrow())?;
+    if !pool.is_initialized {
+        return Err(AmmError::PoolNotInitialized.into());
+    }
+
+    check_pool_authority(
+        program_id,
+        pool_info.key,
+        pool.bump_seed,
+        pool_authority_info.key,
+    )?;
+
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidTokenProgram.into());
+    }
+
+    Ok(pool)
+}
+
+/// Loads the AMM config and checks it lives at the derived address
+pub(crate) fn load_config(
+    program_id: &Pubkey,
+    config_info: &AccountInfo,
+) -> Result<AmmConfig, ProgramError> {
+    check_account_owner(config_info, program_id)?;
+    if *config_info.key != find_config_address(program_id).0 {
+        return Err(AmmError::InvalidConfig.into());
+    }
+
+    let config = AmmConfig::try_from_slice(&config_info.data.borrow())?;
+    if !config.is_initialized {
+        return Err(AmmError::InvalidConfig.into());
+    }
+    Ok(config)
+}
+
+/// Checks the protocol fee account holds the pool's LP token for the fee owner
+fn check_protocol_fee_account(
+    config: &AmmConfig,
+    pool: &Pool,
+    protocol_fee_info: &AccountInfo,
+) -> ProgramResult {
+    check_account_owner(protocol_fee_info, &spl_token::id())
+        .map_err(|_| AmmError::InvalidProtocolFeeAccount)?;
+    let protocol_fee_account = spl_token::state::Account::unpack(&protocol_fee_info.data.borrow())?;
+    if protocol_fee_account.mint != pool.lp_mint
+        || protocol_fee_account.owner != config.protocol_fee_owner
+    {
+        return Err(AmmError::InvalidProtocolFeeAccount.into());
+    }
     Ok(())
 }
+
+/// Mints protocol fees accrued since the last liquidity change
+///
+/// Returns the LP supply after minting.
+fn mint_protocol_fee<'a>(
+    pool: &Pool,
+    pool_info: &AccountInfo<'a>,
+    pool_authority_info: &AccountInfo<'a>,
+    lp_mint_info: &AccountInfo<'a>,
+    protocol_fee_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+) -> Result<u64, ProgramError> {
+    let lp_supply = spl_token::state::Mint::unpack(&lp_mint_info.data.borrow())?.supply;
+    let fee_amount = quote_protocol_fee(
+        lp_supply,
+        pool.k_last,
+        pool.reserve_a,
+        pool.reserve_b,
+        pool.protocol_fee_share,
+    )?;
+    if fee_amount == 0 {
+        return Ok(lp_supply);
+    }
+
+    mint_lp(
+        pool,
+        pool_info,
+        pool_authority_info,
+        lp_mint_info,
+        protocol_fee_info,
+        token_program_info,
+        fee_amount,
+    )?;
+
+    Ok(lp_supply
+        .checked_add(fee_amount)
+        .ok_or(AmmError::MathOverflow)?)
+}
+
+/// Mints LP tokens, signing as the pool authority
+fn mint_lp<'a>(
+    pool: &Pool,
+    pool_info: &AccountInfo<'a>,
+    pool_authority_info: &AccountInfo<'a>,
+    lp_mint_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    let pool_seeds: &[&[u8]] = &[pool_info.key.as_ref(), &[pool.bump_seed]];
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            token_program_info.key,
+            lp_mint_info.key,
+            destination_info.key,
+            pool_authority_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            lp_mint_info.clone(),
+            destination_info.clone(),
+            pool_authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[pool_seeds],
+    )
+}
+
+/// Unpacks a vault token account and checks it can be handed to the pool
+pub(crate) fn unpack_vault(
+    vault_info: &AccountInfo,
+    pool_authority: &Pubkey,
+) -> Result<spl_token::state::Account, ProgramError> {
+    check_account_owner(vault_info, &spl_token::id()).map_err(|_| AmmError::InvalidVault)?;
+    let vault = spl_token::state::Account::unpack(&vault_info.data.borrow())?;
+    if vault.owner != *pool_authority
+        || vault.amount != 0
+        || vault.delegate.is_some()
+        || vault.close_authority.is_some()
+    {
+        return Err(AmmError::InvalidVault.into());
+    }
+    Ok(vault)
+}
+
+/// Transfers tokens from a user-owned account into a pool vault
+pub(crate) fn transfer_to_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            source_info.key,
+            vault_info.key,
+            owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            source_info.clone(),
+            vault_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+/// Transfers tokens out of a pool vault, signing as the pool authority
+pub(crate) fn transfer_from_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    pool_authority_info: &AccountInfo<'a>,
+    pool_seeds: &[&[u8]],
+    amount: u64,
+) -> ProgramResult {
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            vault_info.key,
+            destination_info.key,
+            pool_authority_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            vault_info.clone(),
+            destination_info.clone(),
+            pool_authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[pool_seeds],
+    )
+}
@@ -1,19 +1,28 @@
 //! Constant Product Automated Market Maker (x*y=k)
 //!
-//! This program provides [TEMPLATE FUNCTIONALITY].
+//! This program provides two-token liquidity pools priced by the constant
+//! product invariant. Liquidity providers deposit both tokens into vaults owned
+//! by a pool PDA and receive LP tokens; traders swap against the reserves and
+//! pay a fee that stays in the pool. Each pool also keeps time-weighted price
+//! accumulators that other programs can read through `ObserveTwap`.
 
 pub mod instructions;
+pub mod quote;
 pub mod state;
+pub mod twap;
 pub mod utils;
 pub mod errors;
 
+#[cfg(feature = "concentrated")]
+pub mod concentrated;
+
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
 
 // Export current sdk types for downstream users building with a different sdk version
 pub use solana_program;
 
-solana_program::declare_id!("constant-product-amm111111111111111111111111111111");
+solana_program::declare_id!("CPAmm11111111111111111111111111111111111111");
 
 /// Program entrypoint's implementation
 pub fn process_instruction(
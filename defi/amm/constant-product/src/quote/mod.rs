@@ -0,0 +1,180 @@
+//! Pure-Rust pricing for constant product pools
+//!
+//! The on-chain processor prices every swap and liquidity change through these
+//! functions, so off-chain clients and routers that link this crate get the
+//! exact same amounts, including rounding. Every division rounds in favor of
+//! the pool: amounts paid out round down and amounts owed round up.
+
+use crate::errors::AmmError;
+
+/// Basis point denominator used for fees
+pub const BASIS_POINTS_DENOMINATOR: u64 = 10_000;
+
+/// Largest protocol share of swap fees, in basis points
+pub const MAX_PROTOCOL_FEE_SHARE: u16 = 5_000;
+
+/// Returns the output of swapping exactly `amount_in` against the reserves
+///
+/// The fee is taken from the input and stays in the pool, so the product of
+/// the reserves can only grow.
+pub fn quote_exact_in(
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    fee_basis_points: u16,
+) -> Result<u64, AmmError> {
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(AmmError::InsufficientLiquidity);
+    }
+    let amount_in_after_fee = (amount_in as u128)
+        .checked_mul(fee_multiplier(fee_basis_points)?)
+        .ok_or(AmmError::MathOverflow)?;
+    let numerator = amount_in_after_fee
+        .checked_mul(reserve_out as u128)
+        .ok_or(AmmError::MathOverflow)?;
+    let denominator = (reserve_in as u128)
+        .checked_mul(BASIS_POINTS_DENOMINATOR as u128)
+        .and_then(|value| value.checked_add(amount_in_after_fee))
+        .ok_or(AmmError::MathOverflow)?;
+    let amount_out = numerator / denominator;
+    u64::try_from(amount_out).map_err(|_| AmmError::MathOverflow)
+}
+
+/// Returns the smallest input that makes `quote_exact_in` pay at least `amount_out`
+///
+/// `quote_exact_in(quote_exact_out(x)) >= x` always holds, and one unit less
+/// of input would return less than `x`.
+pub fn quote_exact_out(
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    fee_basis_points: u16,
+) -> Result<u64, AmmError> {
+    if reserve_in == 0 || amount_out >= reserve_out {
+        return Err(AmmError::InsufficientLiquidity);
+    }
+    let numerator = (reserve_in as u128)
+        .checked_mul(amount_out as u128)
+        .and_then(|value| value.checked_mul(BASIS_POINTS_DENOMINATOR as u128))
+        .ok_or(AmmError::MathOverflow)?;
+    let denominator = ((reserve_out - amount_out) as u128)
+        .checked_mul(fee_multiplier(fee_basis_points)?)
+        .ok_or(AmmError::MathOverflow)?;
+    let amount_in = numerator.div_ceil(denominator);
+    u64::try_from(amount_in).map_err(|_| AmmError::MathOverflow)
+}
+
+/// Returns the LP tokens to mint and the token amounts to take for a deposit
+///
+/// Returns `(lp_amount, amount_a, amount_b)`. The first deposit mints
+/// `sqrt(a * b)`; later deposits mint in proportion to the reserves and round
+/// the required token amounts up so the pool never loses value.
+pub fn quote_deposit(
+    max_amount_a: u64,
+    max_amount_b: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+    lp_supply: u64,
+) -> Result<(u64, u64, u64), AmmError> {
+    if lp_supply == 0 {
+        let lp_amount = integer_sqrt(max_amount_a as u128 * max_amount_b as u128);
+        let lp_amount = u64::try_from(lp_amount).map_err(|_| AmmError::MathOverflow)?;
+        return Ok((lp_amount, max_amount_a, max_amount_b));
+    }
+    if reserve_a == 0 || reserve_b == 0 {
+        return Err(AmmError::InsufficientLiquidity);
+    }
+
+    let supply = lp_supply as u128;
+    let lp_from_a = max_amount_a as u128 * supply / reserve_a as u128;
+    let lp_from_b = max_amount_b as u128 * supply / reserve_b as u128;
+    let lp_amount = lp_from_a.min(lp_from_b);
+
+    let amount_a = (lp_amount * reserve_a as u128).div_ceil(supply);
+    let amount_b = (lp_amount * reserve_b as u128).div_ceil(supply);
+
+    Ok((
+        u64::try_from(lp_amount).map_err(|_| AmmError::MathOverflow)?,
+        u64::try_from(amount_a).map_err(|_| AmmError::MathOverflow)?,
+        u64::try_from(amount_b).map_err(|_| AmmError::MathOverflow)?,
+    ))
+}
+
+/// Returns the token amounts paid out for burning `lp_amount` LP tokens
+///
+/// Returns `(amount_a, amount_b)`, rounded down in favor of the pool.
+pub fn quote_withdraw(
+    lp_amount: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+    lp_supply: u64,
+) -> Result<(u64, u64), AmmError> {
+    if lp_supply == 0 || lp_amount > lp_supply {
+        return Err(AmmError::InsufficientLiquidity);
+    }
+    let amount_a = lp_amount as u128 * reserve_a as u128 / lp_supply as u128;
+    let amount_b = lp_amount as u128 * reserve_b as u128 / lp_supply as u128;
+    Ok((amount_a as u64, amount_b as u64))
+}
+
+/// Returns the LP tokens to mint to the protocol for fees earned since `k_last`
+///
+/// Swap fees grow `sqrt(k)`. The protocol receives `protocol_fee_share` basis
+/// points of that growth as newly minted LP tokens, the way Uniswap v2 mints
+/// its fee to `feeTo`:
+///
+/// `lp_supply * share * (root_k - root_k_last) / ((10000 - share) * root_k + share * root_k_last)`
+pub fn quote_protocol_fee(
+    lp_supply: u64,
+    k_last: u128,
+    reserve_a: u64,
+    reserve_b: u64,
+    protocol_fee_share: u16,
+) -> Result<u64, AmmError> {
+    if protocol_fee_share == 0 || k_last == 0 || lp_supply == 0 {
+        return Ok(0);
+    }
+    let share = protocol_fee_share as u128;
+    if share > BASIS_POINTS_DENOMINATOR as u128 {
+        return Err(AmmError::InvalidFee);
+    }
+
+    let root_k = integer_sqrt(reserve_a as u128 * reserve_b as u128);
+    let root_k_last = integer_sqrt(k_last);
+    if root_k <= root_k_last {
+        return Ok(0);
+    }
+
+    let numerator = (lp_supply as u128)
+        .checked_mul(share)
+        .and_then(|value| value.checked_mul(root_k - root_k_last))
+        .ok_or(AmmError::MathOverflow)?;
+    let denominator = (BASIS_POINTS_DENOMINATOR as u128 - share)
+        .checked_mul(root_k)
+        .and_then(|value| value.checked_add(share * root_k_last))
+        .ok_or(AmmError::MathOverflow)?;
+    let lp_amount = numerator / denominator;
+    u64::try_from(lp_amount).map_err(|_| AmmError::MathOverflow)
+}
+
+/// Integer square root (floor) using Newton's method
+pub fn integer_sqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    let mut x = value;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
+
+/// Share of the input left after the fee, scaled by `BASIS_POINTS_DENOMINATOR`
+fn fee_multiplier(fee_basis_points: u16) -> Result<u128, AmmError> {
+    (BASIS_POINTS_DENOMINATOR as u128)
+        .checked_sub(fee_basis_points as u128)
+        .filter(|multiplier| *multiplier > 0)
+        .ok_or(AmmError::InvalidFee)
+}
@@ -1,20 +1,167 @@
-//! State objects for template account
+//! State objects for constant product pools
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::pubkey::Pubkey;
 
-/// Template account data
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-pub struct TemplateAccount {
-    /// The account authority (owner)
-    pub authority: Pubkey,
-    
-    /// Example value 1
-    pub value1: u64,
-    
-    /// Example value 2
-    pub value2: u8,
-    
-    /// Is the account initialized
+use crate::twap::observe;
+
+/// Maximum number of fee tiers an AMM config can list
+pub const MAX_FEE_TIERS: usize = 8;
+
+/// Global AMM configuration
+///
+/// A single config account lives at the PDA derived from `[b"amm_config"]`
+/// (see `utils::find_config_address`). Its admin may change pool fees, but only
+/// to one of the listed fee tiers.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct AmmConfig {
+    /// Is the config initialized
+    pub is_initialized: bool,
+
+    /// Bump seed of the config PDA
+    pub bump_seed: u8,
+
+    /// Authority allowed to change pool fees
+    pub admin: Pubkey,
+
+    /// Owner of the LP token accounts that receive protocol fees
+    pub protocol_fee_owner: Pubkey,
+
+    /// Protocol fee share given to new pools, in basis points of the swap fee
+    pub default_protocol_fee_share: u16,
+
+    /// Number of entries of `fee_tiers` in use
+    pub fee_tier_count: u8,
+
+    /// Allowed swap fees in basis points
+    pub fee_tiers: [u16; MAX_FEE_TIERS],
+}
+
+impl AmmConfig {
+    /// Get the size of AmmConfig struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + bump_seed (1 byte) + Pubkey (32 bytes) * 2 +
+        // default_protocol_fee_share (2 bytes) + fee_tier_count (1 byte) +
+        // fee_tiers (2 bytes * MAX_FEE_TIERS)
+        1 + 1 + 32 * 2 + 2 + 1 + 2 * MAX_FEE_TIERS
+    }
+
+    /// Returns the fee tiers in use
+    pub fn fee_tiers(&self) -> &[u16] {
+        &self.fee_tiers[..(self.fee_tier_count as usize).min(MAX_FEE_TIERS)]
+    }
+
+    /// Checks whether `fee_basis_points` is one of the allowed fee tiers
+    pub fn is_fee_tier(&self, fee_basis_points: u16) -> bool {
+        self.fee_tiers().contains(&fee_basis_points)
+    }
+}
+
+/// Constant product (x*y=k) liquidity pool
+///
+/// Both vaults and the LP mint are controlled by the pool authority, a PDA
+/// derived from the pool address (see `utils::find_pool_authority`).
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct Pool {
+    /// Is the pool initialized
     pub is_initialized: bool,
+
+    /// Bump seed of the pool authority PDA
+    pub bump_seed: u8,
+
+    /// Mint of token A
+    pub token_a_mint: Pubkey,
+
+    /// Mint of token B
+    pub token_b_mint: Pubkey,
+
+    /// Vault holding token A, owned by the pool authority
+    pub token_a_vault: Pubkey,
+
+    /// Vault holding token B, owned by the pool authority
+    pub token_b_vault: Pubkey,
+
+    /// Mint of the LP token, mint authority is the pool authority
+    pub lp_mint: Pubkey,
+
+    /// AMM config that lists the allowed fee tiers and the fee admin
+    pub config: Pubkey,
+
+    /// Swap fee in basis points (e.g., 30 = 0.3%), retained by the pool
+    pub fee_basis_points: u16,
+
+    /// Share of the swap fee growth minted to the protocol as LP tokens, in basis points
+    pub protocol_fee_share: u16,
+
+    /// Token A reserve tracked by the pool
+    pub reserve_a: u64,
+
+    /// Token B reserve tracked by the pool
+    pub reserve_b: u64,
+
+    /// `reserve_a * reserve_b` after the last liquidity change, zero while
+    /// the protocol fee is off
+    pub k_last: u128,
+
+    /// Time-weighted sum of the price of token A in token B, Q64.64, wrapping
+    pub price_a_cumulative: u128,
+
+    /// Time-weighted sum of the price of token B in token A, Q64.64, wrapping
+    pub price_b_cumulative: u128,
+
+    /// Unix timestamp of the last accumulator update
+    pub last_update_timestamp: i64,
+}
+
+impl Pool {
+    /// Get the size of Pool struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + bump_seed (1 byte) + Pubkey (32 bytes) * 6 +
+        // fee_basis_points (2 bytes) + protocol_fee_share (2 bytes) +
+        // reserve_a (8 bytes) + reserve_b (8 bytes) + k_last (16 bytes) +
+        // price cumulatives (16 bytes * 2) + last_update_timestamp (8 bytes)
+        1 + 1 + 32 * 6 + 2 + 2 + 8 + 8 + 16 + 16 * 2 + 8
+    }
+
+    /// Credits the time since the last update to the price accumulators
+    ///
+    /// Must be called before the reserves change so the elapsed time is
+    /// weighted by the prices that were actually in effect.
+    pub fn update_price_cumulative(&mut self, now: i64) {
+        let observation = observe(self, now);
+        self.price_a_cumulative = observation.price_a_cumulative;
+        self.price_b_cumulative = observation.price_b_cumulative;
+        self.last_update_timestamp = now.max(self.last_update_timestamp);
+    }
+
+    /// Records `k` after a liquidity change, or clears it while the protocol fee is off
+    pub fn update_k_last(&mut self) {
+        self.k_last = if self.protocol_fee_share > 0 {
+            self.reserve_a as u128 * self.reserve_b as u128
+        } else {
+            0
+        };
+    }
+
+    /// Returns the (vault in, vault out, reserve in, reserve out) for a swap
+    /// that deposits into `vault_in`, or `None` if it is not a pool vault
+    pub fn swap_direction(&self, vault_in: &Pubkey) -> Option<(Pubkey, Pubkey, u64, u64)> {
+        if *vault_in == self.token_a_vault {
+            Some((
+                self.token_a_vault,
+                self.token_b_vault,
+                self.reserve_a,
+                self.reserve_b,
+            ))
+        } else if *vault_in == self.token_b_vault {
+            Some((
+                self.token_b_vault,
+                self.token_a_vault,
+                self.reserve_b,
+                self.reserve_a,
+            ))
+        } else {
+            None
+        }
+    }
 }
@@ -0,0 +1,88 @@
+//! Time-weighted average prices for constant product pools
+//!
+//! Every pool keeps two price accumulators in Q64.64 fixed point: the sum over
+//! time of the price of token A in token B (`reserve_b / reserve_a`) and of
+//! token B in token A, each multiplied by the seconds it was in effect. They are
+//! updated with the reserves from before every swap and liquidity change, so a
+//! price only counts once it has survived to a later transaction.
+//!
+//! The accumulators wrap on overflow. Consumers take two observations and
+//! divide the wrapping difference by the elapsed time, which gives the right
+//! average as long as `price * window` fits in 128 bits. The same approach is
+//! used by Uniswap v2 price oracles.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{errors::AmmError, state::Pool};
+
+/// Number of fractional bits in the Q64.64 prices
+pub const Q64_FRACTIONAL_BITS: u32 = 64;
+
+/// Snapshot of a pool's price accumulators
+///
+/// Returned by the `ObserveTwap` instruction as return data.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct PriceObservation {
+    /// Unix timestamp the accumulators are valid for
+    pub timestamp: i64,
+
+    /// Time-weighted sum of the price of token A in token B, Q64.64
+    pub price_a_cumulative: u128,
+
+    /// Time-weighted sum of the price of token B in token A, Q64.64
+    pub price_b_cumulative: u128,
+}
+
+/// Returns `numerator / denominator` as a Q64.64 price, or zero for an empty reserve
+pub fn price_q64(numerator: u64, denominator: u64) -> u128 {
+    if numerator == 0 || denominator == 0 {
+        return 0;
+    }
+    ((numerator as u128) << Q64_FRACTIONAL_BITS) / denominator as u128
+}
+
+/// Returns the pool's accumulators brought forward to `now`
+///
+/// Time since the last update is credited at the current reserves, so the
+/// result matches what the pool would store if it were touched at `now`.
+pub fn observe(pool: &Pool, now: i64) -> PriceObservation {
+    let elapsed = now.saturating_sub(pool.last_update_timestamp).max(0) as u128;
+    PriceObservation {
+        timestamp: now,
+        price_a_cumulative: pool
+            .price_a_cumulative
+            .wrapping_add(price_q64(pool.reserve_b, pool.reserve_a).wrapping_mul(elapsed)),
+        price_b_cumulative: pool
+            .price_b_cumulative
+            .wrapping_add(price_q64(pool.reserve_a, pool.reserve_b).wrapping_mul(elapsed)),
+    }
+}
+
+/// Returns the average Q64.64 prices `(price_a, price_b)` between two observations
+///
+/// `price_a` is the price of token A in token B and `price_b` the reverse.
+pub fn twap(older: &PriceObservation, newer: &PriceObservation) -> Result<(u128, u128), AmmError> {
+    if newer.timestamp <= older.timestamp {
+        return Err(AmmError::InvalidTwapWindow);
+    }
+    let elapsed = (newer.timestamp - older.timestamp) as u128;
+    Ok((
+        newer
+            .price_a_cumulative
+            .wrapping_sub(older.price_a_cumulative)
+            / elapsed,
+        newer
+            .price_b_cumulative
+            .wrapping_sub(older.price_b_cumulative)
+            / elapsed,
+    ))
+}
+
+/// Converts `amount` at a Q64.64 price, rounding down
+pub fn amount_at_price(amount: u64, price: u128) -> Result<u64, AmmError> {
+    let value = (amount as u128)
+        .checked_mul(price)
+        .ok_or(AmmError::MathOverflow)?
+        >> Q64_FRACTIONAL_BITS;
+    u64::try_from(value).map_err(|_| AmmError::MathOverflow)
+}
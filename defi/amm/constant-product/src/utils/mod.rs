@@ -1,13 +1,11 @@
 //! Utility functions for the program
 
 use solana_program::{
-    account_info::AccountInfo,
-    entrypoint::ProgramResult,
-    program_error::ProgramError,
-    pubkey::Pubkey,
+    account_info::AccountInfo, bpf_loader_upgradeable, entrypoint::ProgramResult,
+    program_error::ProgramError, pubkey::Pubkey,
 };
 
-use crate::errors::TemplateError;
+use crate::errors::AmmError;
 
 /// Checks that the account is owned by the expected program
 pub fn check_account_owner(account_info: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
@@ -28,6 +26,72 @@ pub fn check_signer(account_info: &AccountInfo) -> ProgramResult {
 }
 
 /// Safely performs a mathematical addition that errors on overflow
-pub fn safe_addition(a: u64, b: u64) -> Result<u64, TemplateError> {
-    a.checked_add(b).ok_or(TemplateError::MathOverflow)
+pub fn safe_addition(a: u64, b: u64) -> Result<u64, AmmError> {
+    a.checked_add(b).ok_or(AmmError::MathOverflow)
+}
+
+/// Finds the pool authority PDA that owns the vaults and the LP mint
+pub fn find_pool_authority(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[pool.as_ref()], program_id)
+}
+
+/// Seed of the global AMM config PDA
+pub const CONFIG_SEED: &[u8] = b"amm_config";
+
+/// Finds the global AMM config PDA
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+/// Finds the upgradeable loader's `ProgramData` account for a program
+pub fn find_program_data_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+/// Size of a `ProgramData` account header: a u32 state tag, the deployment
+/// slot and an optional upgrade authority
+const PROGRAM_DATA_METADATA_SIZE: usize = 45;
+
+/// `UpgradeableLoaderState::ProgramData` tag
+const PROGRAM_DATA_TAG: u32 = 3;
+
+/// Checks that `authority` is the upgrade authority recorded in the program's
+/// `ProgramData` account
+pub fn check_upgrade_authority(
+    program_id: &Pubkey,
+    program_data_info: &AccountInfo,
+    authority: &Pubkey,
+) -> ProgramResult {
+    let expected = find_program_data_address(program_id);
+    if *program_data_info.key != expected {
+        return Err(AmmError::InvalidProgramData.into());
+    }
+    check_account_owner(program_data_info, &bpf_loader_upgradeable::id())?;
+
+    let data = program_data_info.try_borrow_data()?;
+    if data.len() < PROGRAM_DATA_METADATA_SIZE
+        || u32::from_le_bytes(data[0..4].try_into().unwrap()) != PROGRAM_DATA_TAG
+    {
+        return Err(AmmError::InvalidProgramData.into());
+    }
+    // A program frozen without an upgrade authority cannot create a config
+    if data[12] != 1 || data[13..45] != authority.to_bytes() {
+        return Err(AmmError::InvalidAuthority.into());
+    }
+    Ok(())
+}
+
+/// Checks that `authority` is the pool authority PDA for the given bump seed
+pub fn check_pool_authority(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    bump_seed: u8,
+    authority: &Pubkey,
+) -> ProgramResult {
+    let expected = Pubkey::create_program_address(&[pool.as_ref(), &[bump_seed]], program_id)
+        .map_err(|_| AmmError::InvalidPoolAuthority)?;
+    if expected != *authority {
+        return Err(AmmError::InvalidPoolAuthority.into());
+    }
+    Ok(())
 }
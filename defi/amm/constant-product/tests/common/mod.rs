@@ -0,0 +1,166 @@
+//! Fixtures shared by the constant-product-amm integration tests
+//!
+//! Every test binary compiles this module but uses only part of it.
+
+#![allow(dead_code)]
+
+use {
+    constant_product_amm::{
+        errors::AmmError, process_instruction, utils::find_program_data_address,
+    },
+    solana_program::{
+        bpf_loader_upgradeable,
+        instruction::{Instruction, InstructionError},
+        program_pack::Pack,
+        pubkey::Pubkey,
+        rent::Rent,
+        system_instruction,
+    },
+    solana_program_test::{processor, BanksClient, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        signature::{keypair_from_seed, Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+pub async fn process(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &all_signers,
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+pub async fn create_mint(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint_authority: &Pubkey,
+) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                &mint.pubkey(),
+                mint_authority,
+                None,
+                6,
+            )
+            .unwrap(),
+        ],
+        &[&mint],
+    )
+    .await
+    .unwrap();
+    mint.pubkey()
+}
+
+pub async fn create_token_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Pubkey {
+    let account = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &account.pubkey(),
+                mint,
+                owner,
+            )
+            .unwrap(),
+        ],
+        &[&account],
+    )
+    .await
+    .unwrap();
+    account.pubkey()
+}
+
+pub async fn token_balance(banks_client: &mut BanksClient, account: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*account).await.unwrap().unwrap();
+    spl_token::state::Account::unpack(&account.data)
+        .unwrap()
+        .amount
+}
+
+pub fn program_test() -> (ProgramTest, Pubkey) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "constant_product_amm",
+        program_id,
+        processor!(process_instruction),
+    );
+    add_program_data(&mut program_test, &program_id);
+    (program_test, program_id)
+}
+
+/// Upgrade authority recorded in the test program's `ProgramData` account
+pub fn upgrade_authority() -> Keypair {
+    keypair_from_seed(&[7; 32]).unwrap()
+}
+
+/// Adds a `ProgramData` account naming `upgrade_authority()` and funds the authority
+pub fn add_program_data(program_test: &mut ProgramTest, program_id: &Pubkey) {
+    let authority = upgrade_authority().pubkey();
+    let mut data = vec![0; 45];
+    data[0..4].copy_from_slice(&3u32.to_le_bytes());
+    data[12] = 1;
+    data[13..45].copy_from_slice(authority.as_ref());
+    program_test.add_account(
+        find_program_data_address(program_id),
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: bpf_loader_upgradeable::id(),
+            ..Account::default()
+        },
+    );
+    program_test.add_account(
+        authority,
+        Account {
+            lamports: 1_000_000_000,
+            ..Account::default()
+        },
+    );
+}
+
+pub fn custom_error(error: AmmError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
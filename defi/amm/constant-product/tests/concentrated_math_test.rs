@@ -0,0 +1,84 @@
+//! Property tests for constant-product-amm concentrated liquidity math
+
+#![cfg(feature = "concentrated")]
+
+use {
+    constant_product_amm::concentrated::math::{
+        amount_a_delta, amount_b_delta, compute_swap_step, sqrt_price_at_tick, tick_at_sqrt_price,
+        MAX_SQRT_PRICE, MAX_TICK, MIN_SQRT_PRICE, MIN_TICK,
+    },
+    proptest::prelude::*,
+};
+
+#[test]
+fn tick_bounds_match_sqrt_price_bounds() {
+    assert_eq!(sqrt_price_at_tick(0).unwrap(), 1 << 64);
+    assert_eq!(sqrt_price_at_tick(MIN_TICK).unwrap(), MIN_SQRT_PRICE);
+    assert_eq!(sqrt_price_at_tick(MAX_TICK).unwrap(), MAX_SQRT_PRICE);
+    assert!(sqrt_price_at_tick(MIN_TICK - 1).is_err());
+    assert!(sqrt_price_at_tick(MAX_TICK + 1).is_err());
+}
+
+proptest! {
+    #[test]
+    fn sqrt_price_increases_with_tick(tick in MIN_TICK..MAX_TICK) {
+        prop_assert!(sqrt_price_at_tick(tick).unwrap() < sqrt_price_at_tick(tick + 1).unwrap());
+    }
+
+    #[test]
+    fn tick_at_sqrt_price_round_trips(tick in MIN_TICK..MAX_TICK) {
+        let sqrt_price = sqrt_price_at_tick(tick).unwrap();
+        prop_assert_eq!(tick_at_sqrt_price(sqrt_price).unwrap(), tick);
+        let next = sqrt_price_at_tick(tick + 1).unwrap();
+        prop_assert_eq!(tick_at_sqrt_price(next - 1).unwrap(), tick);
+    }
+
+    #[test]
+    fn swap_step_never_undercharges(
+        tick_current in -100_000i32..100_000,
+        tick_distance in 1i32..10_000,
+        liquidity in 1_000u128..=1_000_000_000_000_000_000,
+        amount_remaining in 1u128..=1_000_000_000_000_000_000,
+        fee_basis_points in 0u16..=1_000,
+        a_to_b in any::<bool>(),
+    ) {
+        let tick_target = if a_to_b { tick_current - tick_distance } else { tick_current + tick_distance };
+        let sqrt_price_current = sqrt_price_at_tick(tick_current).unwrap();
+        let sqrt_price_target = sqrt_price_at_tick(tick_target).unwrap();
+
+        let step = compute_swap_step(
+            sqrt_price_current,
+            sqrt_price_target,
+            liquidity,
+            amount_remaining,
+            fee_basis_points,
+            a_to_b,
+        )
+        .unwrap();
+
+        // The step spends no more than it was given and stays within the target
+        prop_assert!(step.amount_in + step.fee_amount <= amount_remaining);
+        if a_to_b {
+            prop_assert!(step.sqrt_price_next >= sqrt_price_target);
+            prop_assert!(step.sqrt_price_next <= sqrt_price_current);
+        } else {
+            prop_assert!(step.sqrt_price_next <= sqrt_price_target);
+            prop_assert!(step.sqrt_price_next >= sqrt_price_current);
+        }
+
+        // Input covers the exact amount needed for the price move and output never exceeds it
+        let (exact_in, exact_out) = if a_to_b {
+            (
+                amount_a_delta(step.sqrt_price_next, sqrt_price_current, liquidity, true).unwrap(),
+                amount_b_delta(step.sqrt_price_next, sqrt_price_current, liquidity, false).unwrap(),
+            )
+        } else {
+            (
+                amount_b_delta(sqrt_price_current, step.sqrt_price_next, liquidity, true).unwrap(),
+                amount_a_delta(sqrt_price_current, step.sqrt_price_next, liquidity, false).unwrap(),
+            )
+        };
+        prop_assert!(step.amount_in >= exact_in);
+        prop_assert!(step.amount_out <= exact_out);
+    }
+}
@@ -0,0 +1,483 @@
+//! Integration tests for constant-product-amm concentrated liquidity pools
+
+#![cfg(feature = "concentrated")]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::common::{
+            create_mint, create_token_account, custom_error, process, program_test, token_balance,
+            upgrade_authority,
+        },
+        borsh::BorshDeserialize,
+        constant_product_amm::{
+            concentrated::{
+                find_position_address, find_tick_array_address,
+                instruction::{
+                    cl_swap, collect_fees, decrease_liquidity, increase_liquidity,
+                    initialize_cl_pool, initialize_tick_array, open_position, PositionAccounts,
+                },
+                state::{ClPool, Position, TickArray},
+            },
+            errors::AmmError,
+            instructions::initialize_config,
+            utils::find_pool_authority,
+        },
+        solana_program::{
+            instruction::Instruction, pubkey::Pubkey, rent::Rent, system_instruction,
+        },
+        solana_program_test::BanksClient,
+        solana_sdk::{
+            signature::{Keypair, Signer},
+            transaction::TransactionError,
+        },
+    };
+
+    const FEE_BASIS_POINTS: u16 = 30;
+    const TICK_SPACING: u16 = 64;
+    const TICK_LOWER: i32 = -128;
+    const TICK_UPPER: i32 = 128;
+    const LIQUIDITY: u128 = 1_000_000_000_000;
+
+    /// Accounts of a concentrated pool set up for testing
+    struct ClPoolFixture {
+        program_id: Pubkey,
+        pool: Pubkey,
+        pool_authority: Pubkey,
+        token_a_vault: Pubkey,
+        token_b_vault: Pubkey,
+        tick_arrays: [Pubkey; 2],
+        user: Keypair,
+        user_token_a: Pubkey,
+        user_token_b: Pubkey,
+    }
+
+    impl ClPoolFixture {
+        fn position_accounts(&self, tick_lower: i32, tick_upper: i32) -> PositionAccounts {
+            let tick_array = |tick: i32| {
+                find_tick_array_address(
+                    &self.program_id,
+                    &self.pool,
+                    tick.div_euclid(TICK_SPACING as i32 * 64) * TICK_SPACING as i32 * 64,
+                )
+                .0
+            };
+            PositionAccounts {
+                pool: self.pool,
+                pool_authority: self.pool_authority,
+                position: find_position_address(
+                    &self.program_id,
+                    &self.pool,
+                    &self.user.pubkey(),
+                    tick_lower,
+                    tick_upper,
+                )
+                .0,
+                tick_array_lower: tick_array(tick_lower),
+                tick_array_upper: tick_array(tick_upper),
+                owner_token_a: self.user_token_a,
+                owner_token_b: self.user_token_b,
+                token_a_vault: self.token_a_vault,
+                token_b_vault: self.token_b_vault,
+            }
+        }
+
+        fn swap(&self, a_to_b: bool, amount_in: u64, minimum_amount_out: u64) -> Instruction {
+            let (source, destination, vault_in, vault_out) = if a_to_b {
+                (
+                    self.user_token_a,
+                    self.user_token_b,
+                    self.token_a_vault,
+                    self.token_b_vault,
+                )
+            } else {
+                (
+                    self.user_token_b,
+                    self.user_token_a,
+                    self.token_b_vault,
+                    self.token_a_vault,
+                )
+            };
+            cl_swap(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.pool,
+                &self.pool_authority,
+                &source,
+                &destination,
+                &vault_in,
+                &vault_out,
+                &self.tick_arrays,
+                amount_in,
+                minimum_amount_out,
+            )
+        }
+    }
+
+    async fn get_cl_pool(banks_client: &mut BanksClient, pool: &Pubkey) -> ClPool {
+        let account = banks_client.get_account(*pool).await.unwrap().unwrap();
+        ClPool::try_from_slice(&account.data).unwrap()
+    }
+
+    async fn get_position(banks_client: &mut BanksClient, position: &Pubkey) -> Position {
+        let account = banks_client.get_account(*position).await.unwrap().unwrap();
+        Position::try_from_slice(&account.data).unwrap()
+    }
+
+    /// Creates the config, a pool priced at 1.0, the tick arrays around
+    /// tick 0 and a funded user
+    async fn setup_cl_pool(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        program_id: Pubkey,
+    ) -> ClPoolFixture {
+        let authority = upgrade_authority();
+        process(
+            banks_client,
+            payer,
+            &[initialize_config(
+                &program_id,
+                &authority.pubkey(),
+                &payer.pubkey(),
+                &payer.pubkey(),
+                0,
+                vec![FEE_BASIS_POINTS],
+            )],
+            &[&authority],
+        )
+        .await
+        .unwrap();
+
+        let mint_a = create_mint(banks_client, payer, &payer.pubkey()).await;
+        let mint_b = create_mint(banks_client, payer, &payer.pubkey()).await;
+        let pool = Keypair::new();
+        let (pool_authority, _) = find_pool_authority(&program_id, &pool.pubkey());
+        let token_a_vault =
+            create_token_account(banks_client, payer, &mint_a, &pool_authority).await;
+        let token_b_vault =
+            create_token_account(banks_client, payer, &mint_b, &pool_authority).await;
+
+        let rent = Rent::default();
+        process(
+            banks_client,
+            payer,
+            &[
+                system_instruction::create_account(
+                    &payer.pubkey(),
+                    &pool.pubkey(),
+                    rent.minimum_balance(ClPool::get_size()),
+                    ClPool::get_size() as u64,
+                    &program_id,
+                ),
+                initialize_cl_pool(
+                    &program_id,
+                    &payer.pubkey(),
+                    &pool.pubkey(),
+                    &pool_authority,
+                    &token_a_vault,
+                    &token_b_vault,
+                    TICK_SPACING,
+                    FEE_BASIS_POINTS,
+                    1 << 64,
+                ),
+            ],
+            &[&pool],
+        )
+        .await
+        .unwrap();
+
+        // Tick arrays on both sides of tick 0
+        let ticks_per_array = TICK_SPACING as i32 * 64;
+        let mut tick_arrays = [Pubkey::default(); 2];
+        for (tick_array, start_tick_index) in tick_arrays.iter_mut().zip([-ticks_per_array, 0]) {
+            *tick_array = find_tick_array_address(&program_id, &pool.pubkey(), start_tick_index).0;
+            process(
+                banks_client,
+                payer,
+                &[initialize_tick_array(
+                    &program_id,
+                    &payer.pubkey(),
+                    &pool.pubkey(),
+                    tick_array,
+                    start_tick_index,
+                )],
+                &[],
+            )
+            .await
+            .unwrap();
+        }
+
+        // Fund the user with both tokens
+        let user = Keypair::new();
+        let user_token_a = create_token_account(banks_client, payer, &mint_a, &user.pubkey()).await;
+        let user_token_b = create_token_account(banks_client, payer, &mint_b, &user.pubkey()).await;
+        process(
+            banks_client,
+            payer,
+            &[
+                system_instruction::transfer(&payer.pubkey(), &user.pubkey(), 1_000_000_000),
+                spl_token::instruction::mint_to(
+                    &spl_token::id(),
+                    &mint_a,
+                    &user_token_a,
+                    &payer.pubkey(),
+                    &[],
+                    100_000_000_000,
+                )
+                .unwrap(),
+                spl_token::instruction::mint_to(
+                    &spl_token::id(),
+                    &mint_b,
+                    &user_token_b,
+                    &payer.pubkey(),
+                    &[],
+                    100_000_000_000,
+                )
+                .unwrap(),
+            ],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        ClPoolFixture {
+            program_id,
+            pool: pool.pubkey(),
+            pool_authority,
+            token_a_vault,
+            token_b_vault,
+            tick_arrays,
+            user,
+            user_token_a,
+            user_token_b,
+        }
+    }
+
+    /// Opens the user's position over `[tick_lower, tick_upper)` and adds `liquidity`
+    async fn provide_liquidity(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &ClPoolFixture,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u128,
+    ) -> Result<(), TransactionError> {
+        let accounts = fixture.position_accounts(tick_lower, tick_upper);
+        process(
+            banks_client,
+            payer,
+            &[
+                open_position(
+                    &fixture.program_id,
+                    &fixture.user.pubkey(),
+                    &fixture.pool,
+                    &accounts.position,
+                    tick_lower,
+                    tick_upper,
+                ),
+                increase_liquidity(
+                    &fixture.program_id,
+                    &fixture.user.pubkey(),
+                    &accounts,
+                    liquidity,
+                    u64::MAX,
+                    u64::MAX,
+                ),
+            ],
+            &[&fixture.user],
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_initialize_cl_pool() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_cl_pool(&mut banks_client, &payer, program_id).await;
+
+        let pool = get_cl_pool(&mut banks_client, &fixture.pool).await;
+        assert!(pool.is_initialized);
+        assert_eq!(pool.sqrt_price, 1 << 64);
+        assert_eq!(pool.tick_current, 0);
+        assert_eq!(pool.liquidity, 0);
+        assert_eq!(pool.tick_spacing, TICK_SPACING);
+
+        let account = banks_client
+            .get_account(fixture.tick_arrays[1])
+            .await
+            .unwrap()
+            .unwrap();
+        let tick_array = TickArray::try_from_slice(&account.data).unwrap();
+        assert_eq!(tick_array.start_tick_index, 0);
+        assert_eq!(tick_array.pool, fixture.pool);
+    }
+
+    #[tokio::test]
+    async fn test_position_lifecycle() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_cl_pool(&mut banks_client, &payer, program_id).await;
+        let accounts = fixture.position_accounts(TICK_LOWER, TICK_UPPER);
+
+        provide_liquidity(
+            &mut banks_client,
+            &payer,
+            &fixture,
+            TICK_LOWER,
+            TICK_UPPER,
+            LIQUIDITY,
+        )
+        .await
+        .unwrap();
+
+        // The range straddles the current price, so both tokens were deposited
+        let vault_a = token_balance(&mut banks_client, &fixture.token_a_vault).await;
+        let vault_b = token_balance(&mut banks_client, &fixture.token_b_vault).await;
+        assert!(vault_a > 0 && vault_b > 0);
+        let pool = get_cl_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(pool.liquidity, LIQUIDITY);
+
+        // Swap back and forth to earn fees in both tokens
+        process(
+            &mut banks_client,
+            &payer,
+            &[
+                fixture.swap(true, 1_000_000, 1),
+                fixture.swap(false, 1_000_000, 1),
+            ],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        let pool = get_cl_pool(&mut banks_client, &fixture.pool).await;
+        assert!(pool.fee_growth_global_a > 0 && pool.fee_growth_global_b > 0);
+
+        // Collect the fees
+        let user_a_before = token_balance(&mut banks_client, &fixture.user_token_a).await;
+        let user_b_before = token_balance(&mut banks_client, &fixture.user_token_b).await;
+        process(
+            &mut banks_client,
+            &payer,
+            &[collect_fees(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &accounts,
+            )],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        let fees_a = token_balance(&mut banks_client, &fixture.user_token_a).await - user_a_before;
+        let fees_b = token_balance(&mut banks_client, &fixture.user_token_b).await - user_b_before;
+        // 30 bps of 1_000_000, less rounding
+        assert!((2_990..=3_000).contains(&fees_a));
+        assert!((2_990..=3_000).contains(&fees_b));
+
+        // Withdraw all liquidity, leaving no more than rounding dust behind
+        process(
+            &mut banks_client,
+            &payer,
+            &[decrease_liquidity(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &accounts,
+                LIQUIDITY,
+                0,
+                0,
+            )],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        let position = get_position(&mut banks_client, &accounts.position).await;
+        assert_eq!(position.liquidity, 0);
+        let pool = get_cl_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(pool.liquidity, 0);
+        assert!(token_balance(&mut banks_client, &fixture.token_a_vault).await < 10);
+        assert!(token_balance(&mut banks_client, &fixture.token_b_vault).await < 10);
+    }
+
+    #[tokio::test]
+    async fn test_swap_crosses_out_of_range() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_cl_pool(&mut banks_client, &payer, program_id).await;
+        provide_liquidity(
+            &mut banks_client,
+            &payer,
+            &fixture,
+            TICK_LOWER,
+            TICK_UPPER,
+            LIQUIDITY,
+        )
+        .await
+        .unwrap();
+
+        // Selling far more token A than the range holds moves the price below
+        // the position, leaving no liquidity in range
+        let user_a_before = token_balance(&mut banks_client, &fixture.user_token_a).await;
+        process(
+            &mut banks_client,
+            &payer,
+            &[fixture.swap(true, 50_000_000_000, 1)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        let pool = get_cl_pool(&mut banks_client, &fixture.pool).await;
+        assert!(pool.tick_current < TICK_LOWER);
+        assert_eq!(pool.liquidity, 0);
+
+        // Only the input needed to exhaust the range was charged
+        let spent = user_a_before - token_balance(&mut banks_client, &fixture.user_token_a).await;
+        assert!(spent < 50_000_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_swap_slippage() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_cl_pool(&mut banks_client, &payer, program_id).await;
+        provide_liquidity(
+            &mut banks_client,
+            &payer,
+            &fixture,
+            TICK_LOWER,
+            TICK_UPPER,
+            LIQUIDITY,
+        )
+        .await
+        .unwrap();
+
+        let err = process(
+            &mut banks_client,
+            &payer,
+            &[fixture.swap(true, 1_000_000, 1_000_000)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(AmmError::SlippageExceeded));
+    }
+
+    #[tokio::test]
+    async fn test_open_position_rejects_unaligned_ticks() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_cl_pool(&mut banks_client, &payer, program_id).await;
+
+        let err = provide_liquidity(
+            &mut banks_client,
+            &payer,
+            &fixture,
+            TICK_LOWER + 1,
+            TICK_UPPER,
+            LIQUIDITY,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(AmmError::InvalidTickRange));
+    }
+}
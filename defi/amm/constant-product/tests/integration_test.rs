@@ -1,41 +1,1044 @@
 //! Integration tests for constant-product-amm
 
+mod common;
+
 #[cfg(test)]
 mod tests {
     use {
-        borsh::BorshSerialize,
-        solana_program::{
-            instruction::{AccountMeta, Instruction},
-            pubkey::Pubkey,
-            rent::Rent,
-            system_instruction,
+        super::common::{
+            create_mint, create_token_account, custom_error, process, program_test, token_balance,
+            upgrade_authority,
+        },
+        borsh::BorshDeserialize,
+        constant_product_amm::{
+            errors::AmmError,
+            instructions::{
+                deposit_liquidity, initialize_config, initialize_pool, observe_twap, route_swap,
+                set_fees, swap, swap_exact_out, withdraw_liquidity, RouteHop,
+            },
+            quote::{quote_exact_in, quote_protocol_fee},
+            state::{AmmConfig, Pool},
+            twap::{twap, PriceObservation},
+            utils::{find_config_address, find_pool_authority},
         },
-        solana_program_test::{processor, ProgramTest},
+        solana_program::{clock::Clock, pubkey::Pubkey, rent::Rent, system_instruction},
+        solana_program_test::{BanksClient, ProgramTestContext},
         solana_sdk::{
-            account::Account,
             signature::{Keypair, Signer},
-            transaction::Transaction,
+            transaction::{Transaction, TransactionError},
         },
-        constant-product-amm::{
-            instructions::TemplateInstruction,
-            process_instruction,
-            state::TemplateAccount,
-        },
-        std::str::FromStr,
     };
 
+    const FEE_BASIS_POINTS: u16 = 30;
+    const FEE_TIERS: [u16; 3] = [5, 30, 100];
+
+    /// Accounts of a pool set up for testing
+    struct PoolFixture {
+        program_id: Pubkey,
+        pool: Pubkey,
+        pool_authority: Pubkey,
+        token_a_vault: Pubkey,
+        token_b_vault: Pubkey,
+        lp_mint: Pubkey,
+        user: Keypair,
+        user_token_a: Pubkey,
+        user_token_b: Pubkey,
+        user_lp_token: Pubkey,
+        protocol_fee_account: Pubkey,
+    }
+
+    async fn get_pool(banks_client: &mut BanksClient, pool: &Pubkey) -> Pool {
+        let account = banks_client.get_account(*pool).await.unwrap().unwrap();
+        Pool::try_from_slice(&account.data).unwrap()
+    }
+
+    async fn get_config(banks_client: &mut BanksClient, program_id: &Pubkey) -> AmmConfig {
+        let (config, _) = find_config_address(program_id);
+        let account = banks_client.get_account(config).await.unwrap().unwrap();
+        AmmConfig::try_from_slice(&account.data).unwrap()
+    }
+
+    /// Creates the AMM config with the payer as admin and protocol fee owner
+    async fn setup_config(banks_client: &mut BanksClient, payer: &Keypair, program_id: Pubkey) {
+        let authority = upgrade_authority();
+        process(
+            banks_client,
+            payer,
+            &[initialize_config(
+                &program_id,
+                &authority.pubkey(),
+                &payer.pubkey(),
+                &payer.pubkey(),
+                0,
+                FEE_TIERS.to_vec(),
+            )],
+            &[&authority],
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Creates the config, two mints, an initialized pool and a funded user
+    async fn setup_pool(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        program_id: Pubkey,
+    ) -> PoolFixture {
+        setup_config(banks_client, payer, program_id).await;
+
+        let mint_a = create_mint(banks_client, payer, &payer.pubkey()).await;
+        let mint_b = create_mint(banks_client, payer, &payer.pubkey()).await;
+        create_pool(
+            banks_client,
+            payer,
+            program_id,
+            &mint_a,
+            &mint_b,
+            Keypair::new(),
+        )
+        .await
+    }
+
+    /// Creates an initialized pool for two existing mints and funds `user` with both
+    async fn create_pool(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        program_id: Pubkey,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+        user: Keypair,
+    ) -> PoolFixture {
+        let pool = Keypair::new();
+        let (pool_authority, _) = find_pool_authority(&program_id, &pool.pubkey());
+
+        let lp_mint = create_mint(banks_client, payer, &pool_authority).await;
+        let token_a_vault =
+            create_token_account(banks_client, payer, mint_a, &pool_authority).await;
+        let token_b_vault =
+            create_token_account(banks_client, payer, mint_b, &pool_authority).await;
+
+        let rent = Rent::default();
+        process(
+            banks_client,
+            payer,
+            &[
+                system_instruction::create_account(
+                    &payer.pubkey(),
+                    &pool.pubkey(),
+                    rent.minimum_balance(Pool::get_size()),
+                    Pool::get_size() as u64,
+                    &program_id,
+                ),
+                initialize_pool(
+                    &program_id,
+                    &payer.pubkey(),
+                    &pool.pubkey(),
+                    &pool_authority,
+                    &token_a_vault,
+                    &token_b_vault,
+                    &lp_mint,
+                    FEE_BASIS_POINTS,
+                ),
+            ],
+            &[&pool],
+        )
+        .await
+        .unwrap();
+
+        // Fund the user with both tokens
+        let user_token_a = create_token_account(banks_client, payer, mint_a, &user.pubkey()).await;
+        let user_token_b = create_token_account(banks_client, payer, mint_b, &user.pubkey()).await;
+        let user_lp_token =
+            create_token_account(banks_client, payer, &lp_mint, &user.pubkey()).await;
+        let protocol_fee_account =
+            create_token_account(banks_client, payer, &lp_mint, &payer.pubkey()).await;
+        process(
+            banks_client,
+            payer,
+            &[
+                spl_token::instruction::mint_to(
+                    &spl_token::id(),
+                    mint_a,
+                    &user_token_a,
+                    &payer.pubkey(),
+                    &[],
+                    100_000_000_000,
+                )
+                .unwrap(),
+                spl_token::instruction::mint_to(
+                    &spl_token::id(),
+                    mint_b,
+                    &user_token_b,
+                    &payer.pubkey(),
+                    &[],
+                    100_000_000_000,
+                )
+                .unwrap(),
+            ],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        PoolFixture {
+            program_id,
+            pool: pool.pubkey(),
+            pool_authority,
+            token_a_vault,
+            token_b_vault,
+            lp_mint,
+            user,
+            user_token_a,
+            user_token_b,
+            user_lp_token,
+            protocol_fee_account,
+        }
+    }
+
+    async fn deposit(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &PoolFixture,
+        max_amount_a: u64,
+        max_amount_b: u64,
+    ) -> Result<(), TransactionError> {
+        process(
+            banks_client,
+            payer,
+            &[deposit_liquidity(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &fixture.pool,
+                &fixture.pool_authority,
+                &fixture.user_token_a,
+                &fixture.user_token_b,
+                &fixture.token_a_vault,
+                &fixture.token_b_vault,
+                &fixture.lp_mint,
+                &fixture.user_lp_token,
+                &fixture.protocol_fee_account,
+                max_amount_a,
+                max_amount_b,
+                0,
+            )],
+            &[&fixture.user],
+        )
+        .await
+    }
+
+    async fn swap_a_to_b(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &PoolFixture,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<(), TransactionError> {
+        process(
+            banks_client,
+            payer,
+            &[swap(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &fixture.pool,
+                &fixture.pool_authority,
+                &fixture.user_token_a,
+                &fixture.user_token_b,
+                &fixture.token_a_vault,
+                &fixture.token_b_vault,
+                amount_in,
+                minimum_amount_out,
+            )],
+            &[&fixture.user],
+        )
+        .await
+    }
+
+    async fn swap_b_to_a(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &PoolFixture,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<(), TransactionError> {
+        process(
+            banks_client,
+            payer,
+            &[swap(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &fixture.pool,
+                &fixture.pool_authority,
+                &fixture.user_token_b,
+                &fixture.user_token_a,
+                &fixture.token_b_vault,
+                &fixture.token_a_vault,
+                amount_in,
+                minimum_amount_out,
+            )],
+            &[&fixture.user],
+        )
+        .await
+    }
+
+    /// Moves the cluster clock to `unix_timestamp`
+    async fn set_timestamp(context: &mut ProgramTestContext, unix_timestamp: i64) {
+        let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp = unix_timestamp;
+        context.set_sysvar(&clock);
+    }
+
+    /// Runs ObserveTwap in a simulated transaction and decodes its return data
+    async fn observe(context: &mut ProgramTestContext, fixture: &PoolFixture) -> PriceObservation {
+        let transaction = Transaction::new_signed_with_payer(
+            &[observe_twap(&fixture.program_id, &fixture.pool)],
+            Some(&context.payer.pubkey()),
+            &[&context.payer],
+            context.last_blockhash,
+        );
+        let simulation = context
+            .banks_client
+            .simulate_transaction(transaction)
+            .await
+            .unwrap();
+        simulation.result.unwrap().unwrap();
+        let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+        assert_eq!(return_data.program_id, fixture.program_id);
+        PriceObservation::try_from_slice(&return_data.data).unwrap()
+    }
+
     #[tokio::test]
-    async fn test_initialize() {
-        // TODO: Implement test logic for initialization
+    async fn test_initialize_pool() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+
+        let fixture = setup_pool(&mut banks_client, &payer, program_id).await;
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+
+        assert!(pool.is_initialized);
+        assert_eq!(pool.token_a_vault, fixture.token_a_vault);
+        assert_eq!(pool.token_b_vault, fixture.token_b_vault);
+        assert_eq!(pool.lp_mint, fixture.lp_mint);
+        assert_eq!(pool.fee_basis_points, FEE_BASIS_POINTS);
+        assert_eq!(pool.config, find_config_address(&program_id).0);
+        assert_eq!(pool.protocol_fee_share, 0);
+        assert_eq!(pool.reserve_a, 0);
+        assert_eq!(pool.reserve_b, 0);
+        assert_eq!(pool.k_last, 0);
+
+        let config = get_config(&mut banks_client, &program_id).await;
+        assert!(config.is_initialized);
+        assert_eq!(config.admin, payer.pubkey());
+        assert_eq!(config.fee_tiers(), &FEE_TIERS);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_config_requires_upgrade_authority() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+
+        // Any other signer is refused, so the config cannot be front-run
+        let error = process(
+            &mut banks_client,
+            &payer,
+            &[initialize_config(
+                &program_id,
+                &payer.pubkey(),
+                &payer.pubkey(),
+                &payer.pubkey(),
+                0,
+                FEE_TIERS.to_vec(),
+            )],
+            &[],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error, custom_error(AmmError::InvalidAuthority));
+
+        setup_config(&mut banks_client, &payer, program_id).await;
+        let config = get_config(&mut banks_client, &program_id).await;
+        assert_eq!(config.admin, payer.pubkey());
     }
 
     #[tokio::test]
-    async fn test_operation1() {
-        // TODO: Implement test logic for operation1
+    async fn test_initialize_pool_rejects_unlisted_fee_tier() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        setup_config(&mut banks_client, &payer, program_id).await;
+
+        let pool = Keypair::new();
+        let (pool_authority, _) = find_pool_authority(&program_id, &pool.pubkey());
+        let mint_a = create_mint(&mut banks_client, &payer, &payer.pubkey()).await;
+        let mint_b = create_mint(&mut banks_client, &payer, &payer.pubkey()).await;
+        let lp_mint = create_mint(&mut banks_client, &payer, &pool_authority).await;
+        let token_a_vault =
+            create_token_account(&mut banks_client, &payer, &mint_a, &pool_authority).await;
+        let token_b_vault =
+            create_token_account(&mut banks_client, &payer, &mint_b, &pool_authority).await;
+
+        let rent = Rent::default();
+        process(
+            &mut banks_client,
+            &payer,
+            &[system_instruction::create_account(
+                &payer.pubkey(),
+                &pool.pubkey(),
+                rent.minimum_balance(Pool::get_size()),
+                Pool::get_size() as u64,
+                &program_id,
+            )],
+            &[&pool],
+        )
+        .await
+        .unwrap();
+
+        // 25 bps is a valid fee but not one of the config's tiers
+        let error = process(
+            &mut banks_client,
+            &payer,
+            &[initialize_pool(
+                &program_id,
+                &payer.pubkey(),
+                &pool.pubkey(),
+                &pool_authority,
+                &token_a_vault,
+                &token_b_vault,
+                &lp_mint,
+                25,
+            )],
+            &[],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error, custom_error(AmmError::FeeTierNotAllowed));
     }
 
     #[tokio::test]
-    async fn test_operation2() {
-        // TODO: Implement test logic for operation2
+    async fn test_deposit_and_withdraw_liquidity() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_pool(&mut banks_client, &payer, program_id).await;
+
+        // First deposit mints sqrt(a * b)
+        deposit(&mut banks_client, &payer, &fixture, 1_000_000, 4_000_000)
+            .await
+            .unwrap();
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.user_lp_token).await,
+            2_000_000
+        );
+
+        // Later deposits are taken in proportion to the reserves
+        deposit(&mut banks_client, &payer, &fixture, 500_000, 10_000_000)
+            .await
+            .unwrap();
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(pool.reserve_a, 1_500_000);
+        assert_eq!(pool.reserve_b, 6_000_000);
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.user_lp_token).await,
+            3_000_000
+        );
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.token_a_vault).await,
+            1_500_000
+        );
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.token_b_vault).await,
+            6_000_000
+        );
+
+        // Withdrawing all LP tokens empties the pool
+        process(
+            &mut banks_client,
+            &payer,
+            &[withdraw_liquidity(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &fixture.pool,
+                &fixture.pool_authority,
+                &fixture.user_lp_token,
+                &fixture.lp_mint,
+                &fixture.token_a_vault,
+                &fixture.token_b_vault,
+                &fixture.user_token_a,
+                &fixture.user_token_b,
+                &fixture.protocol_fee_account,
+                3_000_000,
+                1_500_000,
+                6_000_000,
+            )],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(pool.reserve_a, 0);
+        assert_eq!(pool.reserve_b, 0);
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.user_lp_token).await,
+            0
+        );
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.user_token_a).await,
+            100_000_000_000
+        );
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.user_token_b).await,
+            100_000_000_000
+        );
+    }
+
+    #[tokio::test]
+    async fn test_swap_invariant_never_decreases() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_pool(&mut banks_client, &payer, program_id).await;
+
+        deposit(&mut banks_client, &payer, &fixture, 10_000_000, 20_000_000)
+            .await
+            .unwrap();
+
+        let amounts: [u64; 8] = [1, 7, 1_000, 123_456, 2_500_000, 999, 9_000_000, 31];
+        let mut previous_k = 10_000_000u128 * 20_000_000u128;
+        for (i, amount_in) in amounts.iter().enumerate() {
+            let result = if i % 2 == 0 {
+                swap_a_to_b(&mut banks_client, &payer, &fixture, *amount_in, 0).await
+            } else {
+                swap_b_to_a(&mut banks_client, &payer, &fixture, *amount_in, 0).await
+            };
+
+            // Swaps too small to produce any output are rejected outright
+            if let Err(error) = result {
+                assert_eq!(error, custom_error(AmmError::ZeroAmount));
+                continue;
+            }
+
+            let pool = get_pool(&mut banks_client, &fixture.pool).await;
+            let vault_a = token_balance(&mut banks_client, &fixture.token_a_vault).await;
+            let vault_b = token_balance(&mut banks_client, &fixture.token_b_vault).await;
+            assert_eq!(pool.reserve_a, vault_a);
+            assert_eq!(pool.reserve_b, vault_b);
+
+            let k = vault_a as u128 * vault_b as u128;
+            assert!(k >= previous_k, "k dropped from {} to {}", previous_k, k);
+            previous_k = k;
+        }
+
+        // Fees make k strictly larger than the initial deposit
+        assert!(previous_k > 10_000_000u128 * 20_000_000u128);
+    }
+
+    #[tokio::test]
+    async fn test_swap_slippage() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_pool(&mut banks_client, &payer, program_id).await;
+
+        deposit(&mut banks_client, &payer, &fixture, 1_000_000, 1_000_000)
+            .await
+            .unwrap();
+
+        // 100_000 in with a 0.3% fee yields 90_661 out
+        let error = swap_a_to_b(&mut banks_client, &payer, &fixture, 100_000, 90_662)
+            .await
+            .unwrap_err();
+        assert_eq!(error, custom_error(AmmError::SlippageExceeded));
+
+        swap_a_to_b(&mut banks_client, &payer, &fixture, 100_000, 90_661)
+            .await
+            .unwrap();
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.user_token_b).await,
+            100_000_000_000 - 1_000_000 + 90_661
+        );
+    }
+
+    #[tokio::test]
+    async fn test_swap_exact_out() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_pool(&mut banks_client, &payer, program_id).await;
+
+        deposit(&mut banks_client, &payer, &fixture, 1_000_000, 1_000_000)
+            .await
+            .unwrap();
+
+        let swap_exact_out_ix = |amount_out, maximum_amount_in| {
+            swap_exact_out(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &fixture.pool,
+                &fixture.pool_authority,
+                &fixture.user_token_a,
+                &fixture.user_token_b,
+                &fixture.token_a_vault,
+                &fixture.token_b_vault,
+                amount_out,
+                maximum_amount_in,
+            )
+        };
+
+        // 50_000 out of a 1_000_000 / 1_000_000 pool costs 52_790 in
+        let error = process(
+            &mut banks_client,
+            &payer,
+            &[swap_exact_out_ix(50_000, 52_789)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error, custom_error(AmmError::SlippageExceeded));
+
+        process(
+            &mut banks_client,
+            &payer,
+            &[swap_exact_out_ix(50_000, 52_790)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.user_token_a).await,
+            100_000_000_000 - 1_000_000 - 52_790
+        );
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.user_token_b).await,
+            100_000_000_000 - 1_000_000 + 50_000
+        );
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(pool.reserve_a, 1_052_790);
+        assert_eq!(pool.reserve_b, 950_000);
+        assert!(pool.reserve_a as u128 * pool.reserve_b as u128 >= 1_000_000u128 * 1_000_000u128);
+    }
+
+    #[tokio::test]
+    async fn test_swap_rejects_foreign_vault() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_pool(&mut banks_client, &payer, program_id).await;
+
+        deposit(&mut banks_client, &payer, &fixture, 1_000_000, 1_000_000)
+            .await
+            .unwrap();
+
+        // Paying out of the user's own account instead of the vault must fail
+        let error = process(
+            &mut banks_client,
+            &payer,
+            &[swap(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &fixture.pool,
+                &fixture.pool_authority,
+                &fixture.user_token_a,
+                &fixture.user_token_b,
+                &fixture.token_a_vault,
+                &fixture.user_token_b,
+                1_000,
+                0,
+            )],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error, custom_error(AmmError::InvalidVault));
+    }
+
+    fn set_fees_ix(
+        fixture: &PoolFixture,
+        admin: &Pubkey,
+        fee_basis_points: u16,
+        protocol_fee_share: u16,
+    ) -> solana_program::instruction::Instruction {
+        set_fees(
+            &fixture.program_id,
+            admin,
+            &fixture.pool,
+            &fixture.pool_authority,
+            &fixture.lp_mint,
+            &fixture.protocol_fee_account,
+            fee_basis_points,
+            protocol_fee_share,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_set_fees() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_pool(&mut banks_client, &payer, program_id).await;
+
+        // Only the config admin can change fees
+        let error = process(
+            &mut banks_client,
+            &payer,
+            &[set_fees_ix(&fixture, &fixture.user.pubkey(), 100, 0)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error, custom_error(AmmError::InvalidAuthority));
+
+        // The swap fee must be one of the config's tiers
+        let error = process(
+            &mut banks_client,
+            &payer,
+            &[set_fees_ix(&fixture, &payer.pubkey(), 50, 0)],
+            &[],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error, custom_error(AmmError::FeeTierNotAllowed));
+
+        // The protocol share is capped
+        let error = process(
+            &mut banks_client,
+            &payer,
+            &[set_fees_ix(&fixture, &payer.pubkey(), 100, 5_001)],
+            &[],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error, custom_error(AmmError::InvalidFee));
+
+        process(
+            &mut banks_client,
+            &payer,
+            &[set_fees_ix(&fixture, &payer.pubkey(), 100, 2_500)],
+            &[],
+        )
+        .await
+        .unwrap();
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(pool.fee_basis_points, 100);
+        assert_eq!(pool.protocol_fee_share, 2_500);
+    }
+
+    #[tokio::test]
+    async fn test_protocol_fee_accrues_on_liquidity_change() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_pool(&mut banks_client, &payer, program_id).await;
+
+        deposit(&mut banks_client, &payer, &fixture, 10_000_000, 10_000_000)
+            .await
+            .unwrap();
+
+        // Turn on a 1/6 protocol share of swap fees
+        process(
+            &mut banks_client,
+            &payer,
+            &[set_fees_ix(
+                &fixture,
+                &payer.pubkey(),
+                FEE_BASIS_POINTS,
+                1_667,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(pool.k_last, 10_000_000u128 * 10_000_000u128);
+
+        // Swaps grow k but mint nothing until liquidity changes
+        for _ in 0..5 {
+            swap_a_to_b(&mut banks_client, &payer, &fixture, 2_000_000, 0)
+                .await
+                .unwrap();
+            swap_b_to_a(&mut banks_client, &payer, &fixture, 2_000_000, 0)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.protocol_fee_account).await,
+            0
+        );
+
+        // The next deposit mints the accrued protocol fee first
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        let expected_fee = quote_protocol_fee(
+            10_000_000,
+            pool.k_last,
+            pool.reserve_a,
+            pool.reserve_b,
+            pool.protocol_fee_share,
+        )
+        .unwrap();
+        assert!(expected_fee > 0);
+
+        deposit(&mut banks_client, &payer, &fixture, 1_000_000, 1_000_000)
+            .await
+            .unwrap();
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.protocol_fee_account).await,
+            expected_fee
+        );
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(pool.k_last, pool.reserve_a as u128 * pool.reserve_b as u128);
+
+        // With no swaps since, withdrawing mints nothing more
+        process(
+            &mut banks_client,
+            &payer,
+            &[withdraw_liquidity(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &fixture.pool,
+                &fixture.pool_authority,
+                &fixture.user_lp_token,
+                &fixture.lp_mint,
+                &fixture.token_a_vault,
+                &fixture.token_b_vault,
+                &fixture.user_token_a,
+                &fixture.user_token_b,
+                &fixture.protocol_fee_account,
+                1_000_000,
+                0,
+                0,
+            )],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.protocol_fee_account).await,
+            expected_fee
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deposit_rejects_foreign_protocol_fee_account() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let mut fixture = setup_pool(&mut banks_client, &payer, program_id).await;
+
+        // An LP account not owned by the protocol fee owner
+        fixture.protocol_fee_account = fixture.user_lp_token;
+        let error = deposit(&mut banks_client, &payer, &fixture, 1_000_000, 1_000_000)
+            .await
+            .unwrap_err();
+        assert_eq!(error, custom_error(AmmError::InvalidProtocolFeeAccount));
+    }
+
+    #[tokio::test]
+    async fn test_twap_accumulates_between_observations() {
+        let (program_test, program_id) = program_test();
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+
+        set_timestamp(&mut context, 1_000).await;
+        let fixture = setup_pool(&mut context.banks_client, &payer, program_id).await;
+        let pool = get_pool(&mut context.banks_client, &fixture.pool).await;
+        assert_eq!(pool.last_update_timestamp, 1_000);
+
+        // An empty pool accrues nothing
+        set_timestamp(&mut context, 1_050).await;
+        deposit(
+            &mut context.banks_client,
+            &payer,
+            &fixture,
+            1_000_000,
+            4_000_000,
+        )
+        .await
+        .unwrap();
+        let pool = get_pool(&mut context.banks_client, &fixture.pool).await;
+        assert_eq!(pool.price_a_cumulative, 0);
+        assert_eq!(pool.price_b_cumulative, 0);
+        assert_eq!(pool.last_update_timestamp, 1_050);
+        let first = observe(&mut context, &fixture).await;
+
+        // 100 seconds at a price of 4 B per A, then a swap moves the price
+        set_timestamp(&mut context, 1_150).await;
+        swap_a_to_b(&mut context.banks_client, &payer, &fixture, 1_000_000, 0)
+            .await
+            .unwrap();
+        let pool = get_pool(&mut context.banks_client, &fixture.pool).await;
+        assert_eq!(pool.price_a_cumulative, 400u128 << 64);
+        assert_eq!(pool.price_b_cumulative, 25u128 << 64);
+
+        // The swap's price only counts from the swap onwards
+        set_timestamp(&mut context, 1_250).await;
+        let second = observe(&mut context, &fixture).await;
+        assert_eq!(second.timestamp, 1_250);
+        let spot_a = ((pool.reserve_b as u128) << 64) / pool.reserve_a as u128;
+        assert_eq!(second.price_a_cumulative, (400u128 << 64) + spot_a * 100);
+
+        // Observing does not write to the pool
+        assert_eq!(
+            get_pool(&mut context.banks_client, &fixture.pool).await,
+            pool
+        );
+
+        let (price_a, price_b) = twap(&first, &second).unwrap();
+        assert_eq!(price_a, ((400u128 << 64) + spot_a * 100) / 200);
+        assert!(price_a < 4u128 << 64);
+        assert!(price_b > 1u128 << 62);
+        assert_eq!(
+            twap(&second, &first).unwrap_err(),
+            AmmError::InvalidTwapWindow
+        );
+    }
+
+    /// Creates an A/B pool and a B/C pool sharing token B, both with liquidity
+    async fn setup_route(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        program_id: Pubkey,
+    ) -> (PoolFixture, PoolFixture) {
+        let first = setup_pool(banks_client, payer, program_id).await;
+        let first_mint_b = get_pool(banks_client, &first.pool).await.token_b_mint;
+        let mint_c = create_mint(banks_client, payer, &payer.pubkey()).await;
+        let second = create_pool(
+            banks_client,
+            payer,
+            program_id,
+            &first_mint_b,
+            &mint_c,
+            first.user.insecure_clone(),
+        )
+        .await;
+
+        deposit(banks_client, payer, &first, 1_000_000, 2_000_000)
+            .await
+            .unwrap();
+        deposit(banks_client, payer, &second, 3_000_000, 1_000_000)
+            .await
+            .unwrap();
+        (first, second)
+    }
+
+    fn route_hops(first: &PoolFixture, second: &PoolFixture) -> [RouteHop; 2] {
+        [
+            RouteHop {
+                pool: first.pool,
+                pool_authority: first.pool_authority,
+                vault_in: first.token_a_vault,
+                vault_out: first.token_b_vault,
+            },
+            RouteHop {
+                pool: second.pool,
+                pool_authority: second.pool_authority,
+                vault_in: second.token_a_vault,
+                vault_out: second.token_b_vault,
+            },
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_route_swap() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let (first, second) = setup_route(&mut banks_client, &payer, program_id).await;
+
+        // A -> B through the first pool, then B -> C through the second
+        let amount_b = quote_exact_in(100_000, 1_000_000, 2_000_000, FEE_BASIS_POINTS).unwrap();
+        let amount_c = quote_exact_in(amount_b, 3_000_000, 1_000_000, FEE_BASIS_POINTS).unwrap();
+        let route_swap_ix = |minimum_amount_out| {
+            route_swap(
+                &program_id,
+                &first.user.pubkey(),
+                &first.user_token_a,
+                &second.user_token_b,
+                &route_hops(&first, &second),
+                100_000,
+                minimum_amount_out,
+            )
+        };
+
+        // Slippage is only checked on the final output
+        let error = process(
+            &mut banks_client,
+            &payer,
+            &[route_swap_ix(amount_c + 1)],
+            &[&first.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error, custom_error(AmmError::SlippageExceeded));
+
+        process(
+            &mut banks_client,
+            &payer,
+            &[route_swap_ix(amount_c)],
+            &[&first.user],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            token_balance(&mut banks_client, &first.user_token_a).await,
+            100_000_000_000 - 1_000_000 - 100_000
+        );
+        assert_eq!(
+            token_balance(&mut banks_client, &second.user_token_b).await,
+            100_000_000_000 - 1_000_000 + amount_c
+        );
+
+        // The intermediate token never reaches the user
+        assert_eq!(
+            token_balance(&mut banks_client, &first.user_token_b).await,
+            100_000_000_000 - 2_000_000
+        );
+        let first_pool = get_pool(&mut banks_client, &first.pool).await;
+        assert_eq!(first_pool.reserve_a, 1_100_000);
+        assert_eq!(first_pool.reserve_b, 2_000_000 - amount_b);
+        let second_pool = get_pool(&mut banks_client, &second.pool).await;
+        assert_eq!(second_pool.reserve_a, 3_000_000 + amount_b);
+        assert_eq!(second_pool.reserve_b, 1_000_000 - amount_c);
+        assert_eq!(
+            token_balance(&mut banks_client, &second.token_a_vault).await,
+            second_pool.reserve_a
+        );
+    }
+
+    #[tokio::test]
+    async fn test_route_swap_rejects_broken_route() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let (first, second) = setup_route(&mut banks_client, &payer, program_id).await;
+
+        // The second hop takes C instead of the B the first hop pays out
+        let mut hops = route_hops(&first, &second);
+        std::mem::swap(&mut hops[1].vault_in, &mut hops[1].vault_out);
+        let error = process(
+            &mut banks_client,
+            &payer,
+            &[route_swap(
+                &program_id,
+                &first.user.pubkey(),
+                &first.user_token_a,
+                &second.user_token_a,
+                &hops,
+                100_000,
+                0,
+            )],
+            &[&first.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error, custom_error(AmmError::InvalidRoute));
+
+        // A route needs at least one hop
+        let error = process(
+            &mut banks_client,
+            &payer,
+            &[route_swap(
+                &program_id,
+                &first.user.pubkey(),
+                &first.user_token_a,
+                &second.user_token_b,
+                &[],
+                100_000,
+                0,
+            )],
+            &[&first.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error, custom_error(AmmError::InvalidRoute));
     }
 }
@@ -0,0 +1,151 @@
+//! Property tests for constant-product-amm quoting
+
+use {
+    constant_product_amm::quote::{
+        integer_sqrt, quote_deposit, quote_exact_in, quote_exact_out, quote_protocol_fee,
+        quote_withdraw, MAX_PROTOCOL_FEE_SHARE,
+    },
+    proptest::prelude::*,
+};
+
+/// Reserves large enough to matter but small enough that `k` fits in a u128
+fn reserve() -> impl Strategy<Value = u64> {
+    1_000u64..=1_000_000_000_000_000
+}
+
+fn fee() -> impl Strategy<Value = u16> {
+    0u16..=1_000
+}
+
+proptest! {
+    #[test]
+    fn exact_in_never_decreases_k(
+        reserve_in in reserve(),
+        reserve_out in reserve(),
+        amount_in in 1u64..=1_000_000_000_000_000,
+        fee_basis_points in fee(),
+    ) {
+        let amount_out = quote_exact_in(amount_in, reserve_in, reserve_out, fee_basis_points).unwrap();
+        prop_assert!(amount_out < reserve_out);
+
+        let k_before = reserve_in as u128 * reserve_out as u128;
+        let k_after = (reserve_in + amount_in) as u128 * (reserve_out - amount_out) as u128;
+        prop_assert!(k_after >= k_before);
+    }
+
+    #[test]
+    fn exact_out_agrees_with_exact_in(
+        reserve_in in reserve(),
+        reserve_out in reserve(),
+        output_share in 1u64..10_000,
+        fee_basis_points in fee(),
+    ) {
+        let amount_out = ((reserve_out as u128 * output_share as u128) / 10_000).max(1) as u64;
+        prop_assume!(amount_out < reserve_out);
+
+        let amount_in = match quote_exact_out(amount_out, reserve_in, reserve_out, fee_basis_points) {
+            Ok(amount_in) => amount_in,
+            Err(_) => return Ok(()),
+        };
+
+        // The quoted input always buys at least the requested output
+        let received = quote_exact_in(amount_in, reserve_in, reserve_out, fee_basis_points).unwrap();
+        prop_assert!(received >= amount_out);
+
+        // One unit less of input never does, so the quote is the exact minimum
+        let short = quote_exact_in(amount_in - 1, reserve_in, reserve_out, fee_basis_points).unwrap();
+        prop_assert!(short < amount_out);
+
+        // And the pool keeps k when paying exactly the requested amount
+        let k_before = reserve_in as u128 * reserve_out as u128;
+        let k_after = (reserve_in as u128 + amount_in as u128) * (reserve_out - amount_out) as u128;
+        prop_assert!(k_after >= k_before);
+    }
+
+    #[test]
+    fn deposit_never_undercharges(
+        reserve_a in reserve(),
+        reserve_b in reserve(),
+        lp_supply in 1u64..=1_000_000_000_000_000,
+        max_amount_a in 1u64..=1_000_000_000_000,
+        max_amount_b in 1u64..=1_000_000_000_000,
+    ) {
+        let (lp_amount, amount_a, amount_b) =
+            quote_deposit(max_amount_a, max_amount_b, reserve_a, reserve_b, lp_supply).unwrap();
+        prop_assert!(amount_a <= max_amount_a);
+        prop_assert!(amount_b <= max_amount_b);
+
+        // The depositor pays at least the value of the minted share on both sides
+        prop_assert!(amount_a as u128 * lp_supply as u128 >= lp_amount as u128 * reserve_a as u128);
+        prop_assert!(amount_b as u128 * lp_supply as u128 >= lp_amount as u128 * reserve_b as u128);
+    }
+
+    #[test]
+    fn withdraw_never_overpays(
+        reserve_a in reserve(),
+        reserve_b in reserve(),
+        lp_supply in 1u64..=1_000_000_000_000_000,
+        lp_share in 0u64..=10_000,
+    ) {
+        let lp_amount = (lp_supply as u128 * lp_share as u128 / 10_000) as u64;
+        let (amount_a, amount_b) = quote_withdraw(lp_amount, reserve_a, reserve_b, lp_supply).unwrap();
+
+        prop_assert!(amount_a as u128 * lp_supply as u128 <= lp_amount as u128 * reserve_a as u128);
+        prop_assert!(amount_b as u128 * lp_supply as u128 <= lp_amount as u128 * reserve_b as u128);
+    }
+
+    #[test]
+    fn deposit_then_withdraw_is_not_profitable(
+        reserve_a in reserve(),
+        reserve_b in reserve(),
+        lp_supply in 1u64..=1_000_000_000_000_000,
+        max_amount_a in 1u64..=1_000_000_000_000,
+        max_amount_b in 1u64..=1_000_000_000_000,
+    ) {
+        let (lp_amount, amount_a, amount_b) =
+            quote_deposit(max_amount_a, max_amount_b, reserve_a, reserve_b, lp_supply).unwrap();
+        let (returned_a, returned_b) = quote_withdraw(
+            lp_amount,
+            reserve_a + amount_a,
+            reserve_b + amount_b,
+            lp_supply + lp_amount,
+        )
+        .unwrap();
+
+        prop_assert!(returned_a <= amount_a);
+        prop_assert!(returned_b <= amount_b);
+    }
+
+    #[test]
+    fn protocol_fee_never_exceeds_its_share_of_growth(
+        reserve_a in 1_000u64..=1_000_000_000_000,
+        reserve_b in 1_000u64..=1_000_000_000_000,
+        growth_a in 0u64..=1_000_000_000,
+        growth_b in 0u64..=1_000_000_000,
+        lp_supply in 1u64..=1_000_000_000_000,
+        protocol_fee_share in 0u16..=MAX_PROTOCOL_FEE_SHARE,
+    ) {
+        let k_last = reserve_a as u128 * reserve_b as u128;
+        let fee = quote_protocol_fee(
+            lp_supply,
+            k_last,
+            reserve_a + growth_a,
+            reserve_b + growth_b,
+            protocol_fee_share,
+        )
+        .unwrap() as u128;
+
+        // The minted LP claims at most `share` of the growth in sqrt(k)
+        let root_k = integer_sqrt((reserve_a + growth_a) as u128 * (reserve_b + growth_b) as u128);
+        let root_k_last = integer_sqrt(k_last);
+        let claimed = fee * root_k * 10_000;
+        let allowed = protocol_fee_share as u128 * (root_k - root_k_last) * (lp_supply as u128 + fee);
+        prop_assert!(claimed <= allowed);
+    }
+}
+
+#[test]
+fn exact_out_rejects_draining_the_pool() {
+    assert!(quote_exact_out(1_000, 1_000, 1_000, 30).is_err());
+    assert!(quote_exact_out(1_001, 1_000, 1_000, 30).is_err());
+}
@@ -0,0 +1,44 @@
+//! Property tests for constant-product-amm price accumulators
+
+use {
+    constant_product_amm::{
+        state::Pool,
+        twap::{observe, price_q64, twap},
+    },
+    proptest::prelude::*,
+};
+
+proptest! {
+    #[test]
+    fn twap_survives_accumulator_wrap(
+        start_a in any::<u128>(),
+        start_b in any::<u128>(),
+        reserve_a in 1u64..=u64::MAX,
+        reserve_b in 1u64..=u64::MAX,
+        elapsed in 1i64..=1_000_000,
+    ) {
+        // Accumulators can start anywhere, including just below the wrap point
+        let pool = Pool {
+            reserve_a,
+            reserve_b,
+            price_a_cumulative: start_a,
+            price_b_cumulative: start_b,
+            last_update_timestamp: 1_000,
+            ..Pool::default()
+        };
+        let older = observe(&pool, 1_000);
+        let newer = observe(&pool, 1_000 + elapsed);
+
+        let (price_a, price_b) = twap(&older, &newer).unwrap();
+        let spot_a = price_q64(reserve_b, reserve_a);
+        let spot_b = price_q64(reserve_a, reserve_b);
+
+        // Exact whenever `price * elapsed` fits in 128 bits
+        if spot_a.checked_mul(elapsed as u128).is_some() {
+            prop_assert_eq!(price_a, spot_a);
+        }
+        if spot_b.checked_mul(elapsed as u128).is_some() {
+            prop_assert_eq!(price_b, spot_b);
+        }
+    }
+}
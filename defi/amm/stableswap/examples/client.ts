@@ -5,62 +5,76 @@ import {
   Connection,
   Keypair,
   PublicKey,
-  SystemProgram,
   SYSVAR_RENT_PUBKEY,
   Transaction,
   sendAndConfirmTransaction,
 } from '@solana/web3.js';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
 import * as borsh from 'borsh';
 import BN from 'bn.js';
 
 // Define the program ID (replace with your actual program ID)
-const PROGRAM_ID = new PublicKey('stableswap-amm111111111111111111111111111111');
+const PROGRAM_ID = new PublicKey('SSwap11111111111111111111111111111111111111');
 
 // Define instruction types
 enum TemplateInstructionType {
-  Initialize = 0,
-  Operation1 = 1,
-  Operation2 = 2,
+  InitializePool = 0,
+  Swap = 1,
+  AddLiquidity = 2,
+  RemoveLiquidity = 3,
+  RemoveLiquidityOneToken = 4,
+  RemoveLiquidityImbalance = 5,
+  RampA = 6,
+  InitializeMetapool = 7,
+  SwapUnderlying = 8,
+  WithdrawAdminFees = 9,
+  Pause = 10,
+  Unpause = 11,
+  CommitNewAdmin = 12,
+  ApplyNewAdmin = 13,
 }
 
 // Define instruction schema
-class InitializeInstruction {
-  param1: BN;
-  param2: number;
-  
-  constructor(props: { param1: BN; param2: number }) {
-    this.param1 = props.param1;
-    this.param2 = props.param2;
+class InitializePoolInstruction {
+  amp: BN;
+  fee: BN;
+
+  constructor(props: { amp: BN; fee: BN }) {
+    this.amp = props.amp;
+    this.fee = props.fee;
   }
-  
+
   static schema = new Map([
     [
-      InitializeInstruction,
+      InitializePoolInstruction,
       {
         kind: 'struct',
         fields: [
-          ['param1', 'u64'],
-          ['param2', 'u8'],
+          ['amp', 'u64'],
+          ['fee', 'u64'],
         ],
       },
     ],
   ]);
 }
 
-class Operation1Instruction {
-  amount: BN;
-  
-  constructor(props: { amount: BN }) {
-    this.amount = props.amount;
+class SwapInstruction {
+  amount_in: BN;
+  minimum_amount_out: BN;
+
+  constructor(props: { amount_in: BN; minimum_amount_out: BN }) {
+    this.amount_in = props.amount_in;
+    this.minimum_amount_out = props.minimum_amount_out;
   }
-  
+
   static schema = new Map([
     [
-      Operation1Instruction,
+      SwapInstruction,
       {
         kind: 'struct',
         fields: [
-          ['amount', 'u64'],
+          ['amount_in', 'u64'],
+          ['minimum_amount_out', 'u64'],
         ],
       },
     ],
@@ -68,47 +82,100 @@ class Operation1Instruction {
 }
 
 /**
- * Initialize a new template account
+ * Derive the pool authority PDA that owns the vaults and the LP mint
+ */
+async function findPoolAuthority(pool: PublicKey): Promise<PublicKey> {
+  const [authority] = await PublicKey.findProgramAddress([pool.toBuffer()], PROGRAM_ID);
+  return authority;
+}
+
+/**
+ * Initialize a pool whose pool account, vaults and LP mint already exist
  */
-async function initialize(
+async function initializePool(
   connection: Connection,
-  authority: Keypair,
-  account: Keypair,
-  param1: BN,
-  param2: number
+  admin: Keypair,
+  pool: PublicKey,
+  lpMint: PublicKey,
+  tokenVaults: PublicKey[],
+  tokenMints: PublicKey[],
+  amp: BN,
+  fee: BN
 ) {
-  // Create initialize instruction
-  const initializeInstruction = new InitializeInstruction({
-    param1,
-    param2,
+  const poolAuthority = await findPoolAuthority(pool);
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.InitializePool]),
+    borsh.serialize(
+      InitializePoolInstruction.schema,
+      new InitializePoolInstruction({ amp, fee })
+    ),
+  ]);
+
+  const keys = [
+    { pubkey: admin.publicKey, isSigner: true, isWritable: false },
+    { pubkey: pool, isSigner: false, isWritable: true },
+    { pubkey: poolAuthority, isSigner: false, isWritable: false },
+    { pubkey: lpMint, isSigner: false, isWritable: false },
+    { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+  ];
+  tokenVaults.forEach((vault, index) => {
+    keys.push({ pubkey: vault, isSigner: false, isWritable: false });
+    keys.push({ pubkey: tokenMints[index], isSigner: false, isWritable: false });
   });
-  
-  const instructionData = Buffer.from([TemplateInstructionType.Initialize]);
-  const serializedData = borsh.serialize(
-    InitializeInstruction.schema,
-    initializeInstruction
-  );
-  
-  const fullData = Buffer.concat([instructionData, serializedData]);
-  
+
+  const transaction = new Transaction().add({
+    keys,
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [admin]);
+
+  console.log('Pool initialized successfully!');
+}
+
+/**
+ * Swap tokens, depositing into `vaultIn` and receiving from `vaultOut`
+ */
+async function swap(
+  connection: Connection,
+  user: Keypair,
+  pool: PublicKey,
+  userSource: PublicKey,
+  userDestination: PublicKey,
+  vaultIn: PublicKey,
+  vaultOut: PublicKey,
+  amountIn: BN,
+  minimumAmountOut: BN
+) {
+  const poolAuthority = await findPoolAuthority(pool);
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.Swap]),
+    borsh.serialize(
+      SwapInstruction.schema,
+      new SwapInstruction({ amount_in: amountIn, minimum_amount_out: minimumAmountOut })
+    ),
+  ]);
+
   const transaction = new Transaction().add({
     keys: [
-      { pubkey: authority.publicKey, isSigner: true, isWritable: true },
-      { pubkey: account.publicKey, isSigner: true, isWritable: true },
-      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
-      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+      { pubkey: user.publicKey, isSigner: true, isWritable: false },
+      { pubkey: pool, isSigner: false, isWritable: true },
+      { pubkey: poolAuthority, isSigner: false, isWritable: false },
+      { pubkey: userSource, isSigner: false, isWritable: true },
+      { pubkey: userDestination, isSigner: false, isWritable: true },
+      { pubkey: vaultIn, isSigner: false, isWritable: true },
+      { pubkey: vaultOut, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
     ],
     programId: PROGRAM_ID,
-    data: fullData,
+    data,
   });
-  
-  await sendAndConfirmTransaction(
-    connection,
-    transaction,
-    [authority, account]
-  );
-  
-  console.log('Account initialized successfully!');
+
+  await sendAndConfirmTransaction(connection, transaction, [user]);
+
+  console.log('Swap executed successfully!');
 }
 
 /**
@@ -117,25 +184,16 @@ async function initialize(
 async function main() {
   // Connect to devnet
   const connection = new Connection('https://api.devnet.solana.com', 'confirmed');
-  
+
   // Generate keypairs for testing
-  const authority = Keypair.generate();
-  const account = Keypair.generate();
-  
-  console.log('Requesting airdrop for authority...');
-  const airdropSignature = await connection.requestAirdrop(authority.publicKey, 1000000000);
+  const admin = Keypair.generate();
+
+  console.log('Requesting airdrop for admin...');
+  const airdropSignature = await connection.requestAirdrop(admin.publicKey, 1000000000);
   await connection.confirmTransaction(airdropSignature);
-  
-  // Initialize with example values
-  await initialize(
-    connection,
-    authority,
-    account,
-    new BN(100),
-    5
-  );
-  
-  // TODO: Add examples for other operations
+
+  // TODO: Create the pool account, the vaults (owned by the pool authority)
+  // and the LP mint, then call initializePool, add liquidity and swap
 }
 
 main().then(
@@ -0,0 +1,201 @@
+//! Newton solvers for the StableSwap invariant
+//!
+//! A step-for-step port of `get_D`, `get_y` and `get_y_D` from Curve's plain
+//! pools. Balances and results are `u128`, while every product is taken in
+//! `U192` so the `D^(n+1)` terms cannot wrap; results that do not fit back
+//! into `u128` fail with `MathOverflow`. Each solver runs at most
+//! `MAX_ITERATIONS` Newton steps and fails with a convergence error rather
+//! than return an unconverged value.
+//!
+//! The crate is `no_std`, depends only on `core` and `uint` and never
+//! allocates, so it can be reused by an off-chain quoter or another program.
+//! `stableswap-amm` re-exports it as `math`, and its `tests/math_test.rs`
+//! checks it against vectors from a Python port of Curve's solvers.
+
+#![no_std]
+
+use core::fmt;
+
+#[allow(
+    clippy::assign_op_pattern,
+    clippy::ptr_offset_with_cast,
+    clippy::manual_range_contains,
+    clippy::manual_div_ceil
+)]
+mod wide {
+    uint::construct_uint! {
+        /// 192-bit unsigned integer for intermediate products
+        pub struct U192(3);
+    }
+}
+
+use wide::U192;
+
+/// Errors returned by the solvers
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MathError {
+    /// An intermediate exceeded 192 bits or a result exceeded `u128`
+    MathOverflow,
+
+    /// Token index is out of range, or the two indices are equal
+    InvalidTokenIndex,
+
+    /// Newton iteration for the invariant `D` hit `MAX_ITERATIONS`
+    InvariantNotConverged,
+
+    /// Newton iteration for a token balance `y` hit `MAX_ITERATIONS`
+    BalanceNotConverged,
+}
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            MathError::MathOverflow => "Math operation overflow",
+            MathError::InvalidTokenIndex => "Invalid token index",
+            MathError::InvariantNotConverged => "Invariant calculation did not converge",
+            MathError::BalanceNotConverged => "Balance calculation did not converge",
+        })
+    }
+}
+
+/// Precision the amplification coefficient is stored with
+pub const A_PRECISION: u64 = 100;
+
+/// Maximum Newton iterations for `D` and `y`
+pub const MAX_ITERATIONS: usize = 255;
+
+/// Computes the invariant `D` of normalized balances `xp`
+///
+/// `amp` is the amplification coefficient times `A_PRECISION`.
+pub fn compute_d(xp: &[u128], amp: u64) -> Result<u128, MathError> {
+    let n_tokens = xp.len() as u64;
+    let n = U192::from(n_tokens);
+    let sum = xp
+        .iter()
+        .try_fold(U192::zero(), |acc, x| add(acc, U192::from(*x)))?;
+    if sum.is_zero() {
+        return Ok(0);
+    }
+
+    let ann = mul(U192::from(amp), n)?;
+    let a_precision = U192::from(A_PRECISION);
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        // D^(n+1) / (n^n * prod(x))
+        let mut d_p = d;
+        for x in xp {
+            d_p = div(mul(d_p, d)?, mul(U192::from(*x), n)?)?;
+        }
+        let d_prev = d;
+        let numerator = mul(add(div(mul(ann, sum)?, a_precision)?, mul(d_p, n)?)?, d)?;
+        let denominator = add(
+            div(mul(sub(ann, a_precision)?, d)?, a_precision)?,
+            mul(U192::from(n_tokens + 1), d_p)?,
+        )?;
+        d = div(numerator, denominator)?;
+        if converged(d, d_prev) {
+            return to_u128(d);
+        }
+    }
+    Err(MathError::InvariantNotConverged)
+}
+
+/// Computes the new balance of token `j` after token `i` is set to `x`
+///
+/// All balances are normalized, and `D` is held at its value for `xp`.
+pub fn compute_y(i: usize, j: usize, x: u128, xp: &[u128], amp: u64) -> Result<u128, MathError> {
+    if i == j || i >= xp.len() || j >= xp.len() {
+        return Err(MathError::InvalidTokenIndex);
+    }
+    let d = compute_d(xp, amp)?;
+    let others = xp
+        .iter()
+        .enumerate()
+        .filter(|(k, _)| *k != j)
+        .map(|(k, balance)| if k == i { x } else { *balance });
+    solve_y(others, xp.len(), amp, d)
+}
+
+/// Computes the balance of token `i` that gives the other balances in `xp` the invariant `d`
+pub fn compute_y_d(i: usize, xp: &[u128], amp: u64, d: u128) -> Result<u128, MathError> {
+    if i >= xp.len() {
+        return Err(MathError::InvalidTokenIndex);
+    }
+    let others = xp
+        .iter()
+        .enumerate()
+        .filter(|(k, _)| *k != i)
+        .map(|(_, balance)| *balance);
+    solve_y(others, xp.len(), amp, d)
+}
+
+/// Solves `y^2 + (b - D) * y = c` for the one balance missing from `others`
+fn solve_y(
+    others: impl Iterator<Item = u128>,
+    n_tokens: usize,
+    amp: u64,
+    d: u128,
+) -> Result<u128, MathError> {
+    let n = U192::from(n_tokens as u64);
+    let ann = mul(U192::from(amp), n)?;
+    let a_precision = U192::from(A_PRECISION);
+    let d = U192::from(d);
+
+    let mut c = d;
+    let mut sum = U192::zero();
+    for x in others {
+        let x = U192::from(x);
+        sum = add(sum, x)?;
+        c = div(mul(c, d)?, mul(x, n)?)?;
+    }
+    c = div(mul(mul(c, d)?, a_precision)?, mul(ann, n)?)?;
+    let b = add(sum, div(mul(d, a_precision)?, ann)?)?;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        y = div(
+            add(mul(y, y)?, c)?,
+            sub(add(mul(U192::from(2u64), y)?, b)?, d)?,
+        )?;
+        if converged(y, y_prev) {
+            return to_u128(y);
+        }
+    }
+    Err(MathError::BalanceNotConverged)
+}
+
+/// Computes `a * b / c`, rounded down, without overflowing the product
+pub fn mul_div(a: u128, b: u128, c: u128) -> Result<u128, MathError> {
+    to_u128(div(mul(U192::from(a), U192::from(b))?, U192::from(c))?)
+}
+
+/// Newton steps stop once they move by at most one unit
+fn converged(value: U192, previous: U192) -> bool {
+    let difference = if value > previous {
+        value - previous
+    } else {
+        previous - value
+    };
+    difference <= U192::one()
+}
+
+fn add(a: U192, b: U192) -> Result<U192, MathError> {
+    a.checked_add(b).ok_or(MathError::MathOverflow)
+}
+
+fn sub(a: U192, b: U192) -> Result<U192, MathError> {
+    a.checked_sub(b).ok_or(MathError::MathOverflow)
+}
+
+fn mul(a: U192, b: U192) -> Result<U192, MathError> {
+    a.checked_mul(b).ok_or(MathError::MathOverflow)
+}
+
+fn div(a: U192, b: U192) -> Result<U192, MathError> {
+    a.checked_div(b).ok_or(MathError::MathOverflow)
+}
+
+fn to_u128(value: U192) -> Result<u128, MathError> {
+    u128::try_from(value).map_err(|_| MathError::MathOverflow)
+}
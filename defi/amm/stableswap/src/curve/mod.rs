@@ -0,0 +1,347 @@
+//! StableSwap invariant and quoting
+//!
+//! Implements the Curve invariant for `n` tokens
+//!
+//! ```text
+//! A * n^n * sum(x) + D = A * D * n^n + D^(n+1) / (n^n * prod(x))
+//! ```
+//!
+//! where `x` are the balances scaled to a common precision by each token's
+//! rate and `D` is the
+//! total value of the pool when all balances are equal. `D` and the balance
+//! `y` that keeps `D` constant have no closed form and are found by the
+//! Newton solvers in `math`. Amounts paid out round down and amounts owed to
+//! the pool round up.
+
+use crate::{
+    errors::TemplateError,
+    math::{compute_d, compute_y, compute_y_d, mul_div},
+};
+
+/// Smallest amplification coefficient, without `A_PRECISION`
+pub const MIN_AMP: u64 = 1;
+
+/// Largest amplification coefficient, without `A_PRECISION`
+pub const MAX_AMP: u64 = 1_000_000;
+
+/// Largest factor the amplification coefficient can change by in one ramp
+pub const MAX_AMP_CHANGE: u64 = 10;
+
+/// Shortest ramp, and the shortest time between the start of two ramps, in seconds
+pub const MIN_RAMP_DURATION: i64 = 86_400;
+
+/// Denominator of the swap fee
+pub const FEE_DENOMINATOR: u64 = 10_000_000_000;
+
+/// Largest swap fee, 50%
+pub const MAX_FEE: u64 = 5_000_000_000;
+
+/// Share of every fee kept for the admin, 50%, in units of `FEE_DENOMINATOR`
+pub const ADMIN_FEE: u64 = 5_000_000_000;
+
+/// Precision of token rates and virtual prices
+pub const RATE_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+/// Returns the rate of each token from its precision multiplier
+///
+/// A metapool's last token is the LP token of its base pool and is also
+/// scaled by the base pool's virtual price.
+pub fn rates(
+    precision_multipliers: &[u64],
+    base_virtual_price: Option<u128>,
+) -> Result<Vec<u128>, TemplateError> {
+    let mut rates: Vec<u128> = precision_multipliers
+        .iter()
+        .map(|multiplier| *multiplier as u128 * RATE_PRECISION)
+        .collect();
+    if let (Some(virtual_price), Some(last)) = (base_virtual_price, rates.last_mut()) {
+        *last = mul_div(*last, virtual_price, RATE_PRECISION)?;
+    }
+    Ok(rates)
+}
+
+/// Scales token balances to the pool's common precision
+pub fn normalize(balances: &[u64], rates: &[u128]) -> Result<Vec<u128>, TemplateError> {
+    if rates.len() != balances.len() {
+        return Err(TemplateError::InvalidTokenCount);
+    }
+    balances
+        .iter()
+        .zip(rates)
+        .map(|(balance, rate)| Ok(mul_div(*balance as u128, *rate, RATE_PRECISION)?))
+        .collect()
+}
+
+/// Computes the invariant `D` of token balances
+pub fn compute_d_for_balances(
+    balances: &[u64],
+    rates: &[u128],
+    amp: u64,
+) -> Result<u128, TemplateError> {
+    Ok(compute_d(&normalize(balances, rates)?, amp)?)
+}
+
+/// Computes the value of one LP token in units of `RATE_PRECISION`
+///
+/// A metapool prices its base pool's LP token at this value.
+pub fn virtual_price(
+    balances: &[u64],
+    rates: &[u128],
+    amp: u64,
+    lp_supply: u64,
+) -> Result<u128, TemplateError> {
+    if lp_supply == 0 {
+        return Err(TemplateError::InsufficientLiquidity);
+    }
+    let d = compute_d_for_balances(balances, rates, amp)?;
+    Ok(mul_div(d, RATE_PRECISION, lp_supply as u128)?)
+}
+
+/// Returns the admin's share of a fee
+///
+/// It is left out of the tracked balances, so it stays in the vault for
+/// `WithdrawAdminFees` instead of accruing to LPs.
+pub fn admin_fee(fee_amount: u64) -> u64 {
+    (fee_amount as u128 * ADMIN_FEE as u128 / FEE_DENOMINATOR as u128) as u64
+}
+
+/// Fee charged on the imbalanced part of a liquidity change
+///
+/// Scaled so that a swap done as an imbalanced deposit and withdrawal pays
+/// about the same as the swap itself.
+pub fn imbalance_fee(fee: u64, n_tokens: usize) -> u64 {
+    let n = n_tokens as u128;
+    (fee as u128 * n / (4 * (n - 1))) as u64
+}
+
+/// Calculates the output of swapping `amount_in` of token `i` for token `j`
+///
+/// Returns `(amount_out, fee_amount)`, both in token `j`. The fee is taken
+/// from the output and, less the admin's share, stays in the pool.
+pub fn quote_swap(
+    balances: &[u64],
+    rates: &[u128],
+    amp: u64,
+    fee: u64,
+    i: usize,
+    j: usize,
+    amount_in: u64,
+) -> Result<(u64, u64), TemplateError> {
+    if i == j || i >= balances.len() || j >= balances.len() {
+        return Err(TemplateError::InvalidTokenIndex);
+    }
+    let xp = normalize(balances, rates)?;
+    let x = add(xp[i], mul_div(amount_in as u128, rates[i], RATE_PRECISION)?)?;
+    let y = compute_y(i, j, x, &xp, amp)?;
+
+    // One unit less to cover rounding in y
+    let dy = xp[j]
+        .checked_sub(y)
+        .and_then(|dy| dy.checked_sub(1))
+        .ok_or(TemplateError::InsufficientLiquidity)?;
+    let dy_fee = div(mul(dy, fee as u128)?, FEE_DENOMINATOR as u128)?;
+
+    let amount_out = mul_div(dy - dy_fee, RATE_PRECISION, rates[j])?;
+    let fee_amount = mul_div(dy_fee, RATE_PRECISION, rates[j])?;
+    Ok((to_u64(amount_out)?, to_u64(fee_amount)?))
+}
+
+/// Calculates the LP tokens minted for depositing `amounts`
+///
+/// Returns `(lp_amount, fees)`, where `fees` is the imbalance fee charged
+/// on each token. The first deposit must include every token and mints `D`.
+pub fn quote_add_liquidity(
+    balances: &[u64],
+    rates: &[u128],
+    amp: u64,
+    fee: u64,
+    amounts: &[u64],
+    lp_supply: u64,
+) -> Result<(u64, Vec<u64>), TemplateError> {
+    let n = balances.len();
+    if amounts.len() != n {
+        return Err(TemplateError::InvalidTokenCount);
+    }
+    if lp_supply == 0 && amounts.contains(&0) {
+        return Err(TemplateError::ZeroAmount);
+    }
+
+    let d0 = if lp_supply == 0 {
+        0
+    } else {
+        compute_d_for_balances(balances, rates, amp)?
+    };
+    let new_balances = balances
+        .iter()
+        .zip(amounts)
+        .map(|(balance, amount)| balance.checked_add(*amount))
+        .collect::<Option<Vec<u64>>>()
+        .ok_or(TemplateError::MathOverflow)?;
+    let d1 = compute_d_for_balances(&new_balances, rates, amp)?;
+    if d1 <= d0 {
+        return Err(TemplateError::ZeroAmount);
+    }
+
+    if lp_supply == 0 {
+        return Ok((to_u64(d1)?, vec![0; n]));
+    }
+
+    let (fees, reduced_balances) = charge_imbalance_fees(balances, &new_balances, fee, d0, d1)?;
+    let d2 = compute_d_for_balances(&reduced_balances, rates, amp)?;
+    let lp_amount = div(mul(lp_supply as u128, sub(d2, d0)?)?, d0)?;
+    Ok((to_u64(lp_amount)?, fees))
+}
+
+/// Calculates the tokens returned for burning `lp_amount` in proportion to the balances
+///
+/// Rounded down in favor of the pool.
+pub fn quote_remove_liquidity(
+    balances: &[u64],
+    lp_amount: u64,
+    lp_supply: u64,
+) -> Result<Vec<u64>, TemplateError> {
+    if lp_supply == 0 || lp_amount > lp_supply {
+        return Err(TemplateError::InsufficientLiquidity);
+    }
+    Ok(balances
+        .iter()
+        .map(|balance| (*balance as u128 * lp_amount as u128 / lp_supply as u128) as u64)
+        .collect())
+}
+
+/// Calculates the LP tokens burned to withdraw exactly `amounts`
+///
+/// Returns `(lp_amount, fees)`, where `fees` is the imbalance fee charged
+/// on each token. The burn rounds up in favor of the pool.
+pub fn quote_remove_liquidity_imbalance(
+    balances: &[u64],
+    rates: &[u128],
+    amp: u64,
+    fee: u64,
+    amounts: &[u64],
+    lp_supply: u64,
+) -> Result<(u64, Vec<u64>), TemplateError> {
+    if amounts.len() != balances.len() {
+        return Err(TemplateError::InvalidTokenCount);
+    }
+    if amounts.iter().all(|amount| *amount == 0) {
+        return Err(TemplateError::ZeroAmount);
+    }
+    if lp_supply == 0 {
+        return Err(TemplateError::InsufficientLiquidity);
+    }
+
+    let d0 = compute_d_for_balances(balances, rates, amp)?;
+    let new_balances = balances
+        .iter()
+        .zip(amounts)
+        .map(|(balance, amount)| balance.checked_sub(*amount))
+        .collect::<Option<Vec<u64>>>()
+        .ok_or(TemplateError::InsufficientLiquidity)?;
+    let d1 = compute_d_for_balances(&new_balances, rates, amp)?;
+
+    let (fees, reduced_balances) = charge_imbalance_fees(balances, &new_balances, fee, d0, d1)?;
+    let d2 = compute_d_for_balances(&reduced_balances, rates, amp)?;
+    let lp_amount = add(div(mul(sub(d0, d2)?, lp_supply as u128)?, d0)?, 1)?;
+    if lp_amount > lp_supply as u128 {
+        return Err(TemplateError::InsufficientLiquidity);
+    }
+    Ok((lp_amount as u64, fees))
+}
+
+/// Calculates the amount of token `i` returned for burning `lp_amount`
+///
+/// Returns `(amount_out, fee_amount)`, both in token `i`.
+pub fn quote_remove_liquidity_one_token(
+    balances: &[u64],
+    rates: &[u128],
+    amp: u64,
+    fee: u64,
+    lp_amount: u64,
+    lp_supply: u64,
+    i: usize,
+) -> Result<(u64, u64), TemplateError> {
+    if i >= balances.len() {
+        return Err(TemplateError::InvalidTokenIndex);
+    }
+    if lp_supply == 0 || lp_amount > lp_supply {
+        return Err(TemplateError::InsufficientLiquidity);
+    }
+
+    let xp = normalize(balances, rates)?;
+    let d0 = compute_d(&xp, amp)?;
+    let d1 = sub(d0, div(mul(lp_amount as u128, d0)?, lp_supply as u128)?)?;
+    let new_y = compute_y_d(i, &xp, amp, d1)?;
+
+    // Charge the imbalance fee on how far each balance moves from its
+    // proportional share of the new D
+    let base_fee = imbalance_fee(fee, balances.len()) as u128;
+    let mut xp_reduced = xp.clone();
+    for (k, reduced) in xp_reduced.iter_mut().enumerate() {
+        let proportional = div(mul(xp[k], d1)?, d0)?;
+        let expected = if k == i {
+            sub(proportional, new_y)?
+        } else {
+            sub(xp[k], proportional)?
+        };
+        *reduced = sub(
+            *reduced,
+            div(mul(base_fee, expected)?, FEE_DENOMINATOR as u128)?,
+        )?;
+    }
+    let dy = sub(xp_reduced[i], compute_y_d(i, &xp_reduced, amp, d1)?)?;
+
+    // One unit less to cover rounding
+    let amount_out = mul_div(dy.saturating_sub(1), RATE_PRECISION, rates[i])?;
+    let amount_without_fee = mul_div(sub(xp[i], new_y)?, RATE_PRECISION, rates[i])?;
+    let fee_amount = amount_without_fee.saturating_sub(amount_out);
+    Ok((to_u64(amount_out)?, to_u64(fee_amount)?))
+}
+
+/// Charges the imbalance fee on how far each new balance is from its
+/// proportional share of `d1`
+///
+/// Returns the fees and the new balances less the fees.
+fn charge_imbalance_fees(
+    old_balances: &[u64],
+    new_balances: &[u64],
+    fee: u64,
+    d0: u128,
+    d1: u128,
+) -> Result<(Vec<u64>, Vec<u64>), TemplateError> {
+    let base_fee = imbalance_fee(fee, old_balances.len()) as u128;
+    let mut fees = Vec::with_capacity(old_balances.len());
+    let mut reduced_balances = Vec::with_capacity(old_balances.len());
+    for (old_balance, new_balance) in old_balances.iter().zip(new_balances) {
+        let ideal_balance = div(mul(d1, *old_balance as u128)?, d0)?;
+        let difference = ideal_balance.abs_diff(*new_balance as u128);
+        let fee_amount = to_u64(div(mul(base_fee, difference)?, FEE_DENOMINATOR as u128)?)?;
+        fees.push(fee_amount);
+        reduced_balances.push(
+            new_balance
+                .checked_sub(fee_amount)
+                .ok_or(TemplateError::InsufficientLiquidity)?,
+        );
+    }
+    Ok((fees, reduced_balances))
+}
+
+fn add(a: u128, b: u128) -> Result<u128, TemplateError> {
+    a.checked_add(b).ok_or(TemplateError::MathOverflow)
+}
+
+fn sub(a: u128, b: u128) -> Result<u128, TemplateError> {
+    a.checked_sub(b).ok_or(TemplateError::MathOverflow)
+}
+
+fn mul(a: u128, b: u128) -> Result<u128, TemplateError> {
+    a.checked_mul(b).ok_or(TemplateError::MathOverflow)
+}
+
+fn div(a: u128, b: u128) -> Result<u128, TemplateError> {
+    a.checked_div(b).ok_or(TemplateError::MathOverflow)
+}
+
+fn to_u64(value: u128) -> Result<u64, TemplateError> {
+    u64::try_from(value).map_err(|_| TemplateError::MathOverflow)
+}
@@ -3,28 +3,118 @@
 use solana_program::program_error::ProgramError;
 use thiserror::Error;
 
+use crate::math::MathError;
+
 /// Errors that may be returned by the stableswap-amm program
-#[derive(Error, Debug, Copy, Clone)]
+#[derive(Error, Debug, Copy, Clone, PartialEq)]
 pub enum TemplateError {
     /// Invalid instruction
     #[error("Invalid instruction")]
     InvalidInstruction,
-    
+
     /// Not rent exempt
     #[error("Not rent exempt")]
     NotRentExempt,
-    
+
     /// Expected amount mismatch
     #[error("Expected amount mismatch")]
     ExpectedAmountMismatch,
-    
+
     /// Invalid authority
     #[error("Invalid authority")]
     InvalidAuthority,
-    
+
     /// Math operation overflow
     #[error("Math operation overflow")]
     MathOverflow,
+
+    /// Pool already initialized
+    #[error("Pool already initialized")]
+    PoolAlreadyInitialized,
+
+    /// Pool not initialized
+    #[error("Pool not initialized")]
+    PoolNotInitialized,
+
+    /// Invalid token program
+    #[error("Invalid token program")]
+    InvalidTokenProgram,
+
+    /// Pool authority does not match the derived address
+    #[error("Invalid pool authority")]
+    InvalidPoolAuthority,
+
+    /// Vault does not belong to the pool or is misconfigured
+    #[error("Invalid vault")]
+    InvalidVault,
+
+    /// LP mint does not belong to the pool or is misconfigured
+    #[error("Invalid LP mint")]
+    InvalidLpMint,
+
+    /// Two pool tokens use the same mint
+    #[error("Pool tokens must use different mints")]
+    RepeatedMint,
+
+    /// Token count or the number of per-token amounts is wrong
+    #[error("Invalid token count")]
+    InvalidTokenCount,
+
+    /// Token index is not one of the pool's tokens
+    #[error("Invalid token index")]
+    InvalidTokenIndex,
+
+    /// Fee is outside the allowed range
+    #[error("Invalid fee")]
+    InvalidFee,
+
+    /// Amplification coefficient is outside the allowed range
+    #[error("Invalid amplification coefficient")]
+    InvalidAmp,
+
+    /// Amplification ramp is too fast, too short or starts too soon
+    #[error("Invalid amplification ramp")]
+    InvalidRamp,
+
+    /// Amount must be greater than zero
+    #[error("Amount must be greater than zero")]
+    ZeroAmount,
+
+    /// Operation would leave the pool without liquidity
+    #[error("Insufficient liquidity")]
+    InsufficientLiquidity,
+
+    /// Output is below the caller's minimum or input above their maximum
+    #[error("Slippage tolerance exceeded")]
+    SlippageExceeded,
+
+    /// Newton iteration for the invariant `D` hit `MAX_ITERATIONS`
+    #[error("Invariant calculation did not converge")]
+    InvariantNotConverged,
+
+    /// Newton iteration for a token balance `y` hit `MAX_ITERATIONS`
+    #[error("Balance calculation did not converge")]
+    BalanceNotConverged,
+
+    /// Base pool does not match the metapool, or cannot be a base pool
+    #[error("Invalid base pool")]
+    InvalidBasePool,
+
+    /// Instruction needs a metapool
+    #[error("Pool is not a metapool")]
+    NotMetapool,
+
+    /// Swaps and deposits are paused
+    #[error("Pool is paused")]
+    PoolPaused,
+
+    /// No admin transfer has been committed
+    #[error("No admin transfer is pending")]
+    NoPendingAdmin,
+
+    /// Admin transfer is applied before its deadline
+    #[error("Admin transfer delay has not passed")]
+    AdminTransferNotReady,
 }
 
 impl From<TemplateError> for ProgramError {
@@ -32,3 +122,14 @@ impl From<TemplateError> for ProgramError {
         ProgramError::Custom(e as u32)
     }
 }
+
+impl From<MathError> for TemplateError {
+    fn from(e: MathError) -> Self {
+        match e {
+            MathError::MathOverflow => TemplateError::MathOverflow,
+            MathError::InvalidTokenIndex => TemplateError::InvalidTokenIndex,
+            MathError::InvariantNotConverged => TemplateError::InvariantNotConverged,
+            MathError::BalanceNotConverged => TemplateError::BalanceNotConverged,
+        }
+    }
+}
@@ -6,63 +6,445 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
-    system_program, sysvar,
+    sysvar,
 };
 
 /// Instructions supported by the stableswap-amm program
 #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
 pub enum TemplateInstruction {
-    /// Initialize a new instance
+    /// Initialize a new pool of 2 to 4 tokens
+    ///
+    /// The vaults must be empty token accounts owned by the pool authority,
+    /// and the LP mint must have zero supply, the pool authority as mint
+    /// authority and no freeze authority. The token order is the vault order.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The pool admin
+    /// 1. `[writable]` The pool account, owned by this program
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[]` The LP mint
+    /// 4. `[]` The token program
+    /// 5. `[]` The rent sysvar
+    /// 6. ..6+2N `[]` For each token, the vault then its mint
+    ///
+    InitializePool {
+        /// Amplification coefficient, between `MIN_AMP` and `MAX_AMP`
+        amp: u64,
+        /// Swap fee in units of `FEE_DENOMINATOR`, at most `MAX_FEE`
+        fee: u64,
+    },
+
+    /// Swap one pool token for another
+    ///
+    /// The tokens are given by the vault order: tokens are deposited into
+    /// the input vault and paid out of the output vault.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The user
+    /// 1. `[writable]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The user's source token account
+    /// 4. `[writable]` The user's destination token account
+    /// 5. `[writable]` The input vault
+    /// 6. `[writable]` The output vault
+    /// 7. `[]` The token program
+    /// 8. `[]` For a metapool, the base pool
+    /// 9. `[]` For a metapool, the base pool's LP mint
+    ///
+    Swap {
+        /// Amount of input tokens to swap
+        amount_in: u64,
+        /// Minimum amount of output tokens to receive
+        minimum_amount_out: u64,
+    },
+
+    /// Deposit any mix of the pool tokens in exchange for LP tokens
+    ///
+    /// The first deposit must include every token.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The depositor
+    /// 1. `[writable]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The LP mint
+    /// 4. `[writable]` The depositor's LP token account
+    /// 5. `[]` The token program
+    /// 6. ..6+2N `[writable]` For each token, the depositor's token account then the vault
+    ///
+    /// A metapool also expects the base pool then its LP mint after the token
+    /// accounts.
+    ///
+    AddLiquidity {
+        /// Amount of each token to deposit, in pool token order
+        amounts: Vec<u64>,
+        /// Minimum amount of LP tokens to receive
+        min_lp_amount: u64,
+    },
+
+    /// Burn LP tokens and withdraw every token in proportion to the balances
+    ///
+    /// Takes the same accounts as `AddLiquidity`.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The LP token owner
+    /// 1. `[writable]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The LP mint
+    /// 4. `[writable]` The owner's LP token account
+    /// 5. `[]` The token program
+    /// 6. ..6+2N `[writable]` For each token, the owner's token account then the vault
+    ///
+    RemoveLiquidity {
+        /// Amount of LP tokens to burn
+        lp_amount: u64,
+        /// Minimum amount of each token to receive, in pool token order
+        min_amounts: Vec<u64>,
+    },
+
+    /// Burn LP tokens and withdraw a single token
+    ///
+    /// The token is given by the vault.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The LP token owner
+    /// 1. `[writable]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The LP mint
+    /// 4. `[writable]` The owner's LP token account
+    /// 5. `[]` The token program
+    /// 6. `[writable]` The vault of the token to withdraw
+    /// 7. `[writable]` The owner's destination token account
+    /// 8. `[]` For a metapool, the base pool
+    /// 9. `[]` For a metapool, the base pool's LP mint
+    ///
+    RemoveLiquidityOneToken {
+        /// Amount of LP tokens to burn
+        lp_amount: u64,
+        /// Minimum amount of the token to receive
+        minimum_amount_out: u64,
+    },
+
+    /// Withdraw exact amounts of each token, burning the LP tokens they are worth
+    ///
+    /// Takes the same accounts as `AddLiquidity`.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The LP token owner
+    /// 1. `[writable]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[writable]` The LP mint
+    /// 4. `[writable]` The owner's LP token account
+    /// 5. `[]` The token program
+    /// 6. ..6+2N `[writable]` For each token, the owner's token account then the vault
+    ///
+    /// A metapool also expects the base pool then its LP mint after the token
+    /// accounts.
+    ///
+    RemoveLiquidityImbalance {
+        /// Amount of each token to withdraw, in pool token order
+        amounts: Vec<u64>,
+        /// Maximum amount of LP tokens to burn
+        max_lp_amount: u64,
+    },
+
+    /// Ramp the amplification coefficient linearly to `target_amp`
+    ///
+    /// The ramp must last at least `MIN_RAMP_DURATION`, start at least
+    /// `MIN_RAMP_DURATION` after the previous one and change the coefficient
+    /// by at most a factor of `MAX_AMP_CHANGE`.
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to initialize
-    /// 2. `[]` The system program
-    /// 3. `[]` The rent sysvar
-    ///
-    Initialize {
-        /// Custom parameter 1
-        param1: u64,
-        /// Custom parameter 2
-        param2: u8,
+    /// 0. `[signer]` The pool admin
+    /// 1. `[writable]` The pool account
+    ///
+    RampA {
+        /// Amplification coefficient at the end of the ramp
+        target_amp: u64,
+        /// Unix timestamp the ramp ends at
+        stop_ramp_ts: i64,
     },
 
-    /// Perform operation 1
+    /// Initialize a metapool pairing one token with the LP token of a base pool
+    ///
+    /// The base pool must be an initialized plain pool of this program. The
+    /// vaults and LP mint follow the rules of `InitializePool`; the base LP
+    /// token becomes the metapool's last token. Swaps and deposits on the
+    /// metapool then need the base pool accounts to read its virtual price.
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to modify
+    /// 0. `[signer]` The pool admin
+    /// 1. `[writable]` The metapool account, owned by this program
+    /// 2. `[]` The metapool authority PDA
+    /// 3. `[]` The metapool LP mint
+    /// 4. `[]` The token program
+    /// 5. `[]` The rent sysvar
+    /// 6. `[]` The vault of the paired token
+    /// 7. `[]` The mint of the paired token
+    /// 8. `[]` The vault of the base LP token
+    /// 9. `[]` The base pool
     ///
-    Operation1 {
-        /// Operation amount
-        amount: u64,
+    InitializeMetapool {
+        /// Amplification coefficient, between `MIN_AMP` and `MAX_AMP`
+        amp: u64,
+        /// Swap fee in units of `FEE_DENOMINATOR`, at most `MAX_FEE`
+        fee: u64,
     },
 
-    /// Perform operation 2
+    /// Swap between the paired token and one of the base pool's tokens
+    ///
+    /// Underlying token 0 is the paired token and tokens 1..=N are the base
+    /// pool's tokens. Selling the paired token swaps it for base LP tokens
+    /// and withdraws them from the base pool as token `j`; buying it deposits
+    /// token `i` into the base pool and swaps the minted LP tokens. Swaps
+    /// between two base tokens should go to the base pool directly.
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to modify
+    /// 0. `[signer]` The user
+    /// 1. `[writable]` The metapool account
+    /// 2. `[]` The metapool authority PDA
+    /// 3. `[writable]` The user's source token account
+    /// 4. `[writable]` The user's destination token account
+    /// 5. `[writable]` The metapool vault of the paired token
+    /// 6. `[writable]` The metapool vault of the base LP token
+    /// 7. `[]` The token program
+    /// 8. `[writable]` The base pool
+    /// 9. `[]` The base pool authority PDA
+    /// 10. `[writable]` The base pool's LP mint
+    /// 11. `[]` This program, to call the base pool
+    /// 12. ..12+N `[writable]` The base pool's vaults, in base token order
     ///
-    Operation2,
+    SwapUnderlying {
+        /// Underlying index of the input token
+        i: u8,
+        /// Underlying index of the output token
+        j: u8,
+        /// Amount of input tokens to swap
+        amount_in: u64,
+        /// Minimum amount of output tokens to receive
+        minimum_amount_out: u64,
+    },
+
+    /// Withdraw the admin's share of the fees
+    ///
+    /// Pays out everything in each vault beyond the pool's tracked balance.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The pool admin
+    /// 1. `[]` The pool account
+    /// 2. `[]` The pool authority PDA
+    /// 3. `[]` The token program
+    /// 4. ..4+2N `[writable]` For each token, the admin's token account then the vault
+    ///
+    WithdrawAdminFees,
+
+    /// Pause swaps and deposits, leaving withdrawals open
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The pool admin
+    /// 1. `[writable]` The pool account
+    ///
+    Pause,
+
+    /// Resume swaps and deposits
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The pool admin
+    /// 1. `[writable]` The pool account
+    ///
+    Unpause,
+
+    /// Commit to transferring the pool to a new admin
+    ///
+    /// The transfer can be applied `ADMIN_TRANSFER_DELAY` after the commit.
+    /// Committing again replaces the pending admin and restarts the delay.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The pool admin
+    /// 1. `[writable]` The pool account
+    ///
+    CommitNewAdmin {
+        /// Admin to transfer the pool to
+        new_admin: Pubkey,
+    },
+
+    /// Apply the committed admin transfer once its deadline has passed
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The pool admin
+    /// 1. `[writable]` The pool account
+    ///
+    ApplyNewAdmin,
 }
 
-/// Creates an Initialize instruction
-pub fn initialize(
+/// Creates an InitializePool instruction
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_pool(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
-    param1: u64,
-    param2: u8,
+    admin: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    lp_mint: &Pubkey,
+    token_vaults: &[Pubkey],
+    token_mints: &[Pubkey],
+    amp: u64,
+    fee: u64,
 ) -> Instruction {
-    let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
-        AccountMeta::new_readonly(system_program::id(), false),
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*lp_mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
         AccountMeta::new_readonly(sysvar::rent::id(), false),
     ];
+    for (vault, mint) in token_vaults.iter().zip(token_mints) {
+        accounts.push(AccountMeta::new_readonly(*vault, false));
+        accounts.push(AccountMeta::new_readonly(*mint, false));
+    }
+
+    let data = TemplateInstruction::InitializePool { amp, fee };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a Swap instruction
+#[allow(clippy::too_many_arguments)]
+pub fn swap(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_source: &Pubkey,
+    user_destination: &Pubkey,
+    vault_in: &Pubkey,
+    vault_out: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new(*user_source, false),
+        AccountMeta::new(*user_destination, false),
+        AccountMeta::new(*vault_in, false),
+        AccountMeta::new(*vault_out, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::Swap {
+        amount_in,
+        minimum_amount_out,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an AddLiquidity instruction
+#[allow(clippy::too_many_arguments)]
+pub fn add_liquidity(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    lp_mint: &Pubkey,
+    user_lp_token: &Pubkey,
+    user_tokens: &[Pubkey],
+    token_vaults: &[Pubkey],
+    amounts: Vec<u64>,
+    min_lp_amount: u64,
+) -> Instruction {
+    let data = TemplateInstruction::AddLiquidity {
+        amounts,
+        min_lp_amount,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts: liquidity_account_metas(
+            user,
+            pool,
+            pool_authority,
+            lp_mint,
+            user_lp_token,
+            user_tokens,
+            token_vaults,
+        ),
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a RemoveLiquidity instruction
+#[allow(clippy::too_many_arguments)]
+pub fn remove_liquidity(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    lp_mint: &Pubkey,
+    user_lp_token: &Pubkey,
+    user_tokens: &[Pubkey],
+    token_vaults: &[Pubkey],
+    lp_amount: u64,
+    min_amounts: Vec<u64>,
+) -> Instruction {
+    let data = TemplateInstruction::RemoveLiquidity {
+        lp_amount,
+        min_amounts,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts: liquidity_account_metas(
+            owner,
+            pool,
+            pool_authority,
+            lp_mint,
+            user_lp_token,
+            user_tokens,
+            token_vaults,
+        ),
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a RemoveLiquidityOneToken instruction
+#[allow(clippy::too_many_arguments)]
+pub fn remove_liquidity_one_token(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    lp_mint: &Pubkey,
+    user_lp_token: &Pubkey,
+    token_vault: &Pubkey,
+    user_destination: &Pubkey,
+    lp_amount: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new(*lp_mint, false),
+        AccountMeta::new(*user_lp_token, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*token_vault, false),
+        AccountMeta::new(*user_destination, false),
+    ];
 
-    let data = TemplateInstruction::Initialize { param1, param2 };
+    let data = TemplateInstruction::RemoveLiquidityOneToken {
+        lp_amount,
+        minimum_amount_out,
+    };
 
     Instruction {
         program_id: *program_id,
@@ -71,19 +453,57 @@ pub fn initialize(
     }
 }
 
-/// Creates an Operation1 instruction
-pub fn operation1(
+/// Creates a RemoveLiquidityImbalance instruction
+#[allow(clippy::too_many_arguments)]
+pub fn remove_liquidity_imbalance(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    lp_mint: &Pubkey,
+    user_lp_token: &Pubkey,
+    user_tokens: &[Pubkey],
+    token_vaults: &[Pubkey],
+    amounts: Vec<u64>,
+    max_lp_amount: u64,
+) -> Instruction {
+    let data = TemplateInstruction::RemoveLiquidityImbalance {
+        amounts,
+        max_lp_amount,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts: liquidity_account_metas(
+            owner,
+            pool,
+            pool_authority,
+            lp_mint,
+            user_lp_token,
+            user_tokens,
+            token_vaults,
+        ),
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a RampA instruction
+pub fn ramp_a(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
-    amount: u64,
+    admin: &Pubkey,
+    pool: &Pubkey,
+    target_amp: u64,
+    stop_ramp_ts: i64,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(*pool, false),
     ];
 
-    let data = TemplateInstruction::Operation1 { amount };
+    let data = TemplateInstruction::RampA {
+        target_amp,
+        stop_ramp_ts,
+    };
 
     Instruction {
         program_id: *program_id,
@@ -92,18 +512,200 @@ pub fn operation1(
     }
 }
 
-/// Creates an Operation2 instruction
-pub fn operation2(
+/// Creates an InitializeMetapool instruction
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_metapool(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
+    admin: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    lp_mint: &Pubkey,
+    token_vault: &Pubkey,
+    token_mint: &Pubkey,
+    base_lp_vault: &Pubkey,
+    base_pool: &Pubkey,
+    amp: u64,
+    fee: u64,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(*lp_mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*token_vault, false),
+        AccountMeta::new_readonly(*token_mint, false),
+        AccountMeta::new_readonly(*base_lp_vault, false),
+        AccountMeta::new_readonly(*base_pool, false),
     ];
 
-    let data = TemplateInstruction::Operation2;
+    let data = TemplateInstruction::InitializeMetapool { amp, fee };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a SwapUnderlying instruction
+///
+/// `token_vaults` are the metapool's vaults and `base_token_vaults` the base
+/// pool's vaults, each in pool token order.
+#[allow(clippy::too_many_arguments)]
+pub fn swap_underlying(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    user_source: &Pubkey,
+    user_destination: &Pubkey,
+    token_vaults: &[Pubkey; 2],
+    base_pool: &Pubkey,
+    base_pool_authority: &Pubkey,
+    base_lp_mint: &Pubkey,
+    base_token_vaults: &[Pubkey],
+    i: u8,
+    j: u8,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new(*user_source, false),
+        AccountMeta::new(*user_destination, false),
+        AccountMeta::new(token_vaults[0], false),
+        AccountMeta::new(token_vaults[1], false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*base_pool, false),
+        AccountMeta::new_readonly(*base_pool_authority, false),
+        AccountMeta::new(*base_lp_mint, false),
+        AccountMeta::new_readonly(*program_id, false),
+    ];
+    for vault in base_token_vaults {
+        accounts.push(AccountMeta::new(*vault, false));
+    }
+
+    let data = TemplateInstruction::SwapUnderlying {
+        i,
+        j,
+        amount_in,
+        minimum_amount_out,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a WithdrawAdminFees instruction
+pub fn withdraw_admin_fees(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    admin_tokens: &[Pubkey],
+    token_vaults: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    for (admin_token, vault) in admin_tokens.iter().zip(token_vaults) {
+        accounts.push(AccountMeta::new(*admin_token, false));
+        accounts.push(AccountMeta::new(*vault, false));
+    }
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&TemplateInstruction::WithdrawAdminFees).unwrap(),
+    }
+}
+
+/// Creates a Pause instruction
+pub fn pause(program_id: &Pubkey, admin: &Pubkey, pool: &Pubkey) -> Instruction {
+    admin_instruction(program_id, admin, pool, TemplateInstruction::Pause)
+}
+
+/// Creates an Unpause instruction
+pub fn unpause(program_id: &Pubkey, admin: &Pubkey, pool: &Pubkey) -> Instruction {
+    admin_instruction(program_id, admin, pool, TemplateInstruction::Unpause)
+}
+
+/// Creates a CommitNewAdmin instruction
+pub fn commit_new_admin(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    pool: &Pubkey,
+    new_admin: &Pubkey,
+) -> Instruction {
+    let data = TemplateInstruction::CommitNewAdmin {
+        new_admin: *new_admin,
+    };
+    admin_instruction(program_id, admin, pool, data)
+}
+
+/// Creates an ApplyNewAdmin instruction
+pub fn apply_new_admin(program_id: &Pubkey, admin: &Pubkey, pool: &Pubkey) -> Instruction {
+    admin_instruction(program_id, admin, pool, TemplateInstruction::ApplyNewAdmin)
+}
+
+/// Base pool accounts a metapool needs to price its base LP token
+///
+/// Append these to the accounts of `swap`, `add_liquidity`,
+/// `remove_liquidity_one_token` and `remove_liquidity_imbalance`
+/// instructions for a metapool.
+pub fn metapool_accounts(base_pool: &Pubkey, base_lp_mint: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(*base_pool, false),
+        AccountMeta::new_readonly(*base_lp_mint, false),
+    ]
+}
+
+/// Account metas shared by AddLiquidity, RemoveLiquidity and RemoveLiquidityImbalance
+fn liquidity_account_metas(
+    user: &Pubkey,
+    pool: &Pubkey,
+    pool_authority: &Pubkey,
+    lp_mint: &Pubkey,
+    user_lp_token: &Pubkey,
+    user_tokens: &[Pubkey],
+    token_vaults: &[Pubkey],
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new_readonly(*pool_authority, false),
+        AccountMeta::new(*lp_mint, false),
+        AccountMeta::new(*user_lp_token, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    for (user_token, vault) in user_tokens.iter().zip(token_vaults) {
+        accounts.push(AccountMeta::new(*user_token, false));
+        accounts.push(AccountMeta::new(*vault, false));
+    }
+    accounts
+}
+
+/// Instruction signed by the pool admin that updates the pool account
+fn admin_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    pool: &Pubkey,
+    data: TemplateInstruction,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(*pool, false),
+    ];
 
     Instruction {
         program_id: *program_id,
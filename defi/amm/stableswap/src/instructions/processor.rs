@@ -3,18 +3,29 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
     pubkey::Pubkey,
     rent::Rent,
     sysvar::Sysvar,
 };
 
 use crate::{
+    curve::{
+        admin_fee, quote_add_liquidity, quote_remove_liquidity, quote_remove_liquidity_imbalance,
+        quote_remove_liquidity_one_token, quote_swap, rates, virtual_price, MAX_AMP,
+        MAX_AMP_CHANGE, MAX_FEE, MIN_AMP, MIN_RAMP_DURATION,
+    },
     errors::TemplateError,
-    instructions::TemplateInstruction,
-    state::TemplateAccount,
+    instructions::{add_liquidity, remove_liquidity_one_token, TemplateInstruction},
+    math::A_PRECISION,
+    state::{StableSwap, ADMIN_TRANSFER_DELAY, MAX_TOKENS, MIN_TOKENS},
+    utils::{check_account_owner, check_pool_authority, check_signer, find_pool_authority},
 };
 
 /// Processes an instruction
@@ -27,141 +38,1453 @@ pub fn process_instruction(
         .map_err(|_| ProgramError::InvalidInstructionData)?;
 
     match instruction {
-        TemplateInstruction::Initialize { param1, param2 } => {
-            msg!("Instruction: Initialize");
-            process_initialize(program_id, accounts, param1, param2)
+        TemplateInstruction::InitializePool { amp, fee } => {
+            msg!("Instruction: Initialize Pool");
+            process_initialize_pool(program_id, accounts, amp, fee)
         }
-        TemplateInstruction::Operation1 { amount } => {
-            msg!("Instruction: Operation1");
-            process_operation1(program_id, accounts, amount)
+        TemplateInstruction::Swap {
+            amount_in,
+            minimum_amount_out,
+        } => {
+            msg!("Instruction: Swap");
+            process_swap(program_id, accounts, amount_in, minimum_amount_out)
         }
-        TemplateInstruction::Operation2 => {
-            msg!("Instruction: Operation2");
-            process_operation2(program_id, accounts)
+        TemplateInstruction::AddLiquidity {
+            amounts,
+            min_lp_amount,
+        } => {
+            msg!("Instruction: Add Liquidity");
+            process_add_liquidity(program_id, accounts, &amounts, min_lp_amount)
+        }
+        TemplateInstruction::RemoveLiquidity {
+            lp_amount,
+            min_amounts,
+        } => {
+            msg!("Instruction: Remove Liquidity");
+            process_remove_liquidity(program_id, accounts, lp_amount, &min_amounts)
+        }
+        TemplateInstruction::RemoveLiquidityOneToken {
+            lp_amount,
+            minimum_amount_out,
+        } => {
+            msg!("Instruction: Remove Liquidity One Token");
+            process_remove_liquidity_one_token(program_id, accounts, lp_amount, minimum_amount_out)
+        }
+        TemplateInstruction::RemoveLiquidityImbalance {
+            amounts,
+            max_lp_amount,
+        } => {
+            msg!("Instruction: Remove Liquidity Imbalance");
+            process_remove_liquidity_imbalance(program_id, accounts, &amounts, max_lp_amount)
+        }
+        TemplateInstruction::RampA {
+            target_amp,
+            stop_ramp_ts,
+        } => {
+            msg!("Instruction: Ramp A");
+            process_ramp_a(program_id, accounts, target_amp, stop_ramp_ts)
+        }
+        TemplateInstruction::InitializeMetapool { amp, fee } => {
+            msg!("Instruction: Initialize Metapool");
+            process_initialize_metapool(program_id, accounts, amp, fee)
+        }
+        TemplateInstruction::SwapUnderlying {
+            i,
+            j,
+            amount_in,
+            minimum_amount_out,
+        } => {
+            msg!("Instruction: Swap Underlying");
+            process_swap_underlying(program_id, accounts, i, j, amount_in, minimum_amount_out)
+        }
+        TemplateInstruction::WithdrawAdminFees => {
+            msg!("Instruction: Withdraw Admin Fees");
+            process_withdraw_admin_fees(program_id, accounts)
+        }
+        TemplateInstruction::Pause => {
+            msg!("Instruction: Pause");
+            process_set_paused(program_id, accounts, true)
+        }
+        TemplateInstruction::Unpause => {
+            msg!("Instruction: Unpause");
+            process_set_paused(program_id, accounts, false)
+        }
+        TemplateInstruction::CommitNewAdmin { new_admin } => {
+            msg!("Instruction: Commit New Admin");
+            process_commit_new_admin(program_id, accounts, new_admin)
+        }
+        TemplateInstruction::ApplyNewAdmin => {
+            msg!("Instruction: Apply New Admin");
+            process_apply_new_admin(program_id, accounts)
         }
     }
 }
 
-/// Processes an Initialize instruction
-fn process_initialize(
+/// Processes an InitializePool instruction
+fn process_initialize_pool(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    param1: u64,
-    param2: u8,
+    amp: u64,
+    fee: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    let system_program_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
-        return Err(TemplateError::InvalidAuthority.into());
+    let token_infos = account_info_iter.as_slice();
+
+    // Validate the pool account, authority and parameters
+    let bump_seed = check_new_pool(
+        program_id,
+        admin_info,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+        rent_info,
+        amp,
+        fee,
+    )?;
+    let n_tokens = token_infos.len() / 2;
+    if token_infos.len() % 2 != 0 || !(MIN_TOKENS..=MAX_TOKENS).contains(&n_tokens) {
+        return Err(TemplateError::InvalidTokenCount.into());
     }
-    
-    // Check for rent exemption
-    let rent = &Rent::from_account_info(rent_info)?;
-    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
-        return Err(TemplateError::NotRentExempt.into());
+
+    // Validate vaults and read each token's decimals
+    let mut token_mints = Vec::with_capacity(n_tokens);
+    let mut token_vaults = Vec::with_capacity(n_tokens);
+    let mut decimals = Vec::with_capacity(n_tokens);
+    for pair in token_infos.chunks(2) {
+        let (vault_info, mint_info) = (&pair[0], &pair[1]);
+        let vault = unpack_vault(vault_info, pool_authority_info.key)?;
+        if token_mints.contains(&vault.mint) {
+            return Err(TemplateError::RepeatedMint.into());
+        }
+        decimals.push(mint_decimals(mint_info, &vault.mint)?);
+        token_mints.push(vault.mint);
+        token_vaults.push(*vault_info.key);
     }
-    
-    // Initialize account data
-    let template_account = TemplateAccount {
-        authority: *authority_info.key,
-        value1: param1,
-        value2: param2,
-        is_initialized: true,
+
+    // Validate LP mint
+    check_lp_mint(lp_mint_info, pool_authority_info.key)?;
+
+    // Initialize pool data
+    let pool = new_pool(
+        bump_seed,
+        admin_info.key,
+        &token_mints,
+        &token_vaults,
+        &decimals,
+        lp_mint_info.key,
+        amp,
+        fee,
+        Pubkey::default(),
+    )?;
+
+    // Save pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a Swap instruction
+fn process_swap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let user_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let user_source_info = next_account_info(account_info_iter)?;
+    let user_destination_info = next_account_info(account_info_iter)?;
+    let vault_in_info = next_account_info(account_info_iter)?;
+    let vault_out_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let base_infos = account_info_iter.as_slice();
+
+    // Check the user is a signer
+    check_signer(user_info)?;
+
+    // Load and validate the pool
+    let mut pool = load_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+    if pool.is_paused {
+        return Err(TemplateError::PoolPaused.into());
+    }
+
+    // Resolve the tokens from the vaults
+    let (i, j) = match (
+        pool.token_index(vault_in_info.key),
+        pool.token_index(vault_out_info.key),
+    ) {
+        (Some(i), Some(j)) if i != j => (i, j),
+        _ => return Err(TemplateError::InvalidVault.into()),
     };
-    
-    // Save account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+
+    if amount_in == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    // Calculate the output
+    let now = Clock::get()?.unix_timestamp;
+    let rates = pool_rates(program_id, &pool, base_infos, now)?;
+    let (amount_out, fee_amount) = quote_swap(
+        pool.balances(),
+        &rates,
+        pool.current_amp(now),
+        pool.fee,
+        i,
+        j,
+        amount_in,
+    )?;
+    if amount_out == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+    if amount_out < minimum_amount_out {
+        return Err(TemplateError::SlippageExceeded.into());
+    }
+
+    // Transfer input tokens to the pool and output tokens to the user
+    transfer_to_vault(
+        token_program_info,
+        user_source_info,
+        vault_in_info,
+        user_info,
+        amount_in,
+    )?;
+    let pool_seeds: &[&[u8]] = &[pool_info.key.as_ref(), &[pool.bump_seed]];
+    transfer_from_vault(
+        token_program_info,
+        vault_out_info,
+        user_destination_info,
+        pool_authority_info,
+        pool_seeds,
+        amount_out,
+    )?;
+
+    // Update balances, leaving the admin fee in the vault
+    pool.balances[i] = pool.balances[i]
+        .checked_add(amount_in)
+        .ok_or(TemplateError::MathOverflow)?;
+    pool.balances[j] = pool.balances[j]
+        .checked_sub(amount_out)
+        .and_then(|balance| balance.checked_sub(admin_fee(fee_amount)))
+        .ok_or(TemplateError::MathOverflow)?;
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
     Ok(())
 }
 
-/// Processes an Operation1 instruction
-fn process_operation1(
+/// Processes an AddLiquidity instruction
+fn process_add_liquidity(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    amount: u64,
+    amounts: &[u64],
+    min_lp_amount: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
-        return Err(TemplateError::InvalidAuthority.into());
+    let user_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let user_lp_token_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let token_infos = account_info_iter.as_slice();
+
+    // Check the user is a signer
+    check_signer(user_info)?;
+
+    // Load and validate the pool
+    let mut pool = load_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+    if pool.lp_mint != *lp_mint_info.key {
+        return Err(TemplateError::InvalidLpMint.into());
     }
-    
-    // Deserialize account data
-    let mut template_account = TemplateAccount::try_from_slice(&account_info.data.borrow())?;
-    
-    // Verify authority
-    if template_account.authority != *authority_info.key {
-        return Err(TemplateError::InvalidAuthority.into());
+    if pool.is_paused {
+        return Err(TemplateError::PoolPaused.into());
+    }
+    let (token_infos, base_infos) = split_base_accounts(&pool, token_infos);
+    let token_pairs = token_account_pairs(&pool, token_infos)?;
+
+    // Calculate LP tokens to mint
+    let lp_supply = spl_token::state::Mint::unpack(&lp_mint_info.data.borrow())?.supply;
+    let now = Clock::get()?.unix_timestamp;
+    let rates = pool_rates(program_id, &pool, base_infos, now)?;
+    let (lp_amount, fees) = quote_add_liquidity(
+        pool.balances(),
+        &rates,
+        pool.current_amp(now),
+        pool.fee,
+        amounts,
+        lp_supply,
+    )?;
+    if lp_amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+    if lp_amount < min_lp_amount {
+        return Err(TemplateError::SlippageExceeded.into());
+    }
+
+    // Transfer tokens from the user to the vaults
+    for ((user_token_info, vault_info), amount) in token_pairs.iter().zip(amounts) {
+        if *amount > 0 {
+            transfer_to_vault(
+                token_program_info,
+                user_token_info,
+                vault_info,
+                user_info,
+                *amount,
+            )?;
+        }
+    }
+
+    // Mint LP tokens to the user
+    let pool_seeds: &[&[u8]] = &[pool_info.key.as_ref(), &[pool.bump_seed]];
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            token_program_info.key,
+            lp_mint_info.key,
+            user_lp_token_info.key,
+            pool_authority_info.key,
+            &[],
+            lp_amount,
+        )?,
+        &[
+            lp_mint_info.clone(),
+            user_lp_token_info.clone(),
+            pool_authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[pool_seeds],
+    )?;
+
+    // Update balances, leaving the admin fees in the vaults
+    for ((balance, amount), fee) in pool.balances.iter_mut().zip(amounts).zip(&fees) {
+        *balance = balance
+            .checked_add(*amount)
+            .and_then(|balance| balance.checked_sub(admin_fee(*fee)))
+            .ok_or(TemplateError::MathOverflow)?;
+    }
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a RemoveLiquidity instruction
+fn process_remove_liquidity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lp_amount: u64,
+    min_amounts: &[u64],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let user_lp_token_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let token_infos = account_info_iter.as_slice();
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    // Load and validate the pool
+    let mut pool = load_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+    if pool.lp_mint != *lp_mint_info.key {
+        return Err(TemplateError::InvalidLpMint.into());
+    }
+    let token_pairs = token_account_pairs(&pool, token_infos)?;
+    if min_amounts.len() != pool.n() {
+        return Err(TemplateError::InvalidTokenCount.into());
+    }
+
+    // Validate amount
+    if lp_amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    // Calculate amounts to return
+    let lp_supply = spl_token::state::Mint::unpack(&lp_mint_info.data.borrow())?.supply;
+    let amounts = quote_remove_liquidity(pool.balances(), lp_amount, lp_supply)?;
+    if amounts
+        .iter()
+        .zip(min_amounts)
+        .any(|(amount, min_amount)| amount < min_amount)
+    {
+        return Err(TemplateError::SlippageExceeded.into());
     }
-    
-    // Update account data
-    template_account.value1 = template_account.value1.checked_add(amount)
+
+    // Burn the owner's LP tokens and pay out every token
+    burn_lp(
+        token_program_info,
+        user_lp_token_info,
+        lp_mint_info,
+        owner_info,
+        lp_amount,
+    )?;
+    pay_out(
+        &pool,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+        &token_pairs,
+        &amounts,
+    )?;
+
+    // Update balances
+    for (balance, amount) in pool.balances.iter_mut().zip(&amounts) {
+        *balance = balance
+            .checked_sub(*amount)
+            .ok_or(TemplateError::MathOverflow)?;
+    }
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a RemoveLiquidityOneToken instruction
+fn process_remove_liquidity_one_token(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lp_amount: u64,
+    minimum_amount_out: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let user_lp_token_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let vault_info = next_account_info(account_info_iter)?;
+    let user_destination_info = next_account_info(account_info_iter)?;
+    let base_infos = account_info_iter.as_slice();
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    // Load and validate the pool
+    let mut pool = load_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+    if pool.lp_mint != *lp_mint_info.key {
+        return Err(TemplateError::InvalidLpMint.into());
+    }
+    let i = pool
+        .token_index(vault_info.key)
+        .ok_or(TemplateError::InvalidVault)?;
+
+    // Validate amount
+    if lp_amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    // Calculate the amount to return
+    let lp_supply = spl_token::state::Mint::unpack(&lp_mint_info.data.borrow())?.supply;
+    let now = Clock::get()?.unix_timestamp;
+    let rates = pool_rates(program_id, &pool, base_infos, now)?;
+    let (amount_out, fee_amount) = quote_remove_liquidity_one_token(
+        pool.balances(),
+        &rates,
+        pool.current_amp(now),
+        pool.fee,
+        lp_amount,
+        lp_supply,
+        i,
+    )?;
+    if amount_out == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+    if amount_out < minimum_amount_out {
+        return Err(TemplateError::SlippageExceeded.into());
+    }
+
+    // Burn the owner's LP tokens and pay out the token
+    burn_lp(
+        token_program_info,
+        user_lp_token_info,
+        lp_mint_info,
+        owner_info,
+        lp_amount,
+    )?;
+    let pool_seeds: &[&[u8]] = &[pool_info.key.as_ref(), &[pool.bump_seed]];
+    transfer_from_vault(
+        token_program_info,
+        vault_info,
+        user_destination_info,
+        pool_authority_info,
+        pool_seeds,
+        amount_out,
+    )?;
+
+    // Update balances, leaving the admin fee in the vault
+    pool.balances[i] = pool.balances[i]
+        .checked_sub(amount_out)
+        .and_then(|balance| balance.checked_sub(admin_fee(fee_amount)))
         .ok_or(TemplateError::MathOverflow)?;
-    
-    // Save updated account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
     Ok(())
 }
 
-/// Processes an Operation2 instruction
-fn process_operation2(
+/// Processes a RemoveLiquidityImbalance instruction
+fn process_remove_liquidity_imbalance(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
+    amounts: &[u64],
+    max_lp_amount: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
-        return Err(TemplateError::InvalidAuthority.into());
+    let owner_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let user_lp_token_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let token_infos = account_info_iter.as_slice();
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    // Load and validate the pool
+    let mut pool = load_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+    if pool.lp_mint != *lp_mint_info.key {
+        return Err(TemplateError::InvalidLpMint.into());
+    }
+    let (token_infos, base_infos) = split_base_accounts(&pool, token_infos);
+    let token_pairs = token_account_pairs(&pool, token_infos)?;
+
+    // Calculate LP tokens to burn
+    let lp_supply = spl_token::state::Mint::unpack(&lp_mint_info.data.borrow())?.supply;
+    let now = Clock::get()?.unix_timestamp;
+    let rates = pool_rates(program_id, &pool, base_infos, now)?;
+    let (lp_amount, fees) = quote_remove_liquidity_imbalance(
+        pool.balances(),
+        &rates,
+        pool.current_amp(now),
+        pool.fee,
+        amounts,
+        lp_supply,
+    )?;
+    if lp_amount > max_lp_amount {
+        return Err(TemplateError::SlippageExceeded.into());
+    }
+
+    // Burn the owner's LP tokens and pay out the requested amounts
+    burn_lp(
+        token_program_info,
+        user_lp_token_info,
+        lp_mint_info,
+        owner_info,
+        lp_amount,
+    )?;
+    pay_out(
+        &pool,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+        &token_pairs,
+        amounts,
+    )?;
+
+    // Update balances, leaving the admin fees in the vaults
+    for ((balance, amount), fee) in pool.balances.iter_mut().zip(amounts).zip(&fees) {
+        *balance = balance
+            .checked_sub(*amount)
+            .and_then(|balance| balance.checked_sub(admin_fee(*fee)))
+            .ok_or(TemplateError::MathOverflow)?;
+    }
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a RampA instruction
+fn process_ramp_a(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    target_amp: u64,
+    stop_ramp_ts: i64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let admin_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+
+    // Load the pool and verify the admin
+    let mut pool = load_admin_pool(program_id, admin_info, pool_info)?;
+
+    // Validate the ramp timing
+    let now = Clock::get()?.unix_timestamp;
+    if now < pool.initial_amp_time.saturating_add(MIN_RAMP_DURATION)
+        || stop_ramp_ts < now.saturating_add(MIN_RAMP_DURATION)
+    {
+        return Err(TemplateError::InvalidRamp.into());
+    }
+
+    // Validate the target and how far it moves the coefficient
+    if !(MIN_AMP..=MAX_AMP).contains(&target_amp) {
+        return Err(TemplateError::InvalidAmp.into());
+    }
+    let initial_amp = pool.current_amp(now);
+    let future_amp = target_amp * A_PRECISION;
+    let within_max_change = if future_amp < initial_amp {
+        future_amp * MAX_AMP_CHANGE >= initial_amp
+    } else {
+        future_amp <= initial_amp * MAX_AMP_CHANGE
+    };
+    if !within_max_change {
+        return Err(TemplateError::InvalidRamp.into());
+    }
+
+    // Start the ramp from the current coefficient
+    pool.initial_amp = initial_amp;
+    pool.future_amp = future_amp;
+    pool.initial_amp_time = now;
+    pool.future_amp_time = stop_ramp_ts;
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes an InitializeMetapool instruction
+fn process_initialize_metapool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amp: u64,
+    fee: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let admin_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let lp_mint_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let token_vault_info = next_account_info(account_info_iter)?;
+    let token_mint_info = next_account_info(account_info_iter)?;
+    let base_lp_vault_info = next_account_info(account_info_iter)?;
+    let base_pool_info = next_account_info(account_info_iter)?;
+
+    // Validate the pool account, authority and parameters
+    let bump_seed = check_new_pool(
+        program_id,
+        admin_info,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+        rent_info,
+        amp,
+        fee,
+    )?;
+
+    // The base pool must be an initialized plain pool of this program
+    check_account_owner(base_pool_info, program_id).map_err(|_| TemplateError::InvalidBasePool)?;
+    let base_pool = StableSwap::try_from_slice(&base_pool_info.data.borrow())?;
+    if !base_pool.is_initialized || base_pool.is_metapool() {
+        return Err(TemplateError::InvalidBasePool.into());
+    }
+
+    // Validate vaults
+    let vault = unpack_vault(token_vault_info, pool_authority_info.key)?;
+    let token_decimals = mint_decimals(token_mint_info, &vault.mint)?;
+    let base_lp_vault = unpack_vault(base_lp_vault_info, pool_authority_info.key)?;
+    if base_lp_vault.mint != base_pool.lp_mint {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    if vault.mint == base_lp_vault.mint {
+        return Err(TemplateError::RepeatedMint.into());
+    }
+
+    // Validate LP mint
+    check_lp_mint(lp_mint_info, pool_authority_info.key)?;
+
+    // Initialize pool data, scaling base LP tokens like the base pool's balances
+    let pool = new_pool(
+        bump_seed,
+        admin_info.key,
+        &[vault.mint, base_lp_vault.mint],
+        &[*token_vault_info.key, *base_lp_vault_info.key],
+        &[token_decimals, base_pool.decimals],
+        lp_mint_info.key,
+        amp,
+        fee,
+        *base_pool_info.key,
+    )?;
+
+    // Save pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a SwapUnderlying instruction
+fn process_swap_underlying(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    i: u8,
+    j: u8,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let user_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let user_source_info = next_account_info(account_info_iter)?;
+    let user_destination_info = next_account_info(account_info_iter)?;
+    let token_vault_info = next_account_info(account_info_iter)?;
+    let base_lp_vault_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let base_pool_info = next_account_info(account_info_iter)?;
+    let base_pool_authority_info = next_account_info(account_info_iter)?;
+    let base_lp_mint_info = next_account_info(account_info_iter)?;
+    let program_info = next_account_info(account_info_iter)?;
+    let base_vault_infos = account_info_iter.as_slice();
+
+    // Check the user is a signer
+    check_signer(user_info)?;
+
+    // Load and validate the metapool and its base pool
+    let mut pool = load_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+    if !pool.is_metapool() {
+        return Err(TemplateError::NotMetapool.into());
+    }
+    if pool.is_paused {
+        return Err(TemplateError::PoolPaused.into());
+    }
+    if *token_vault_info.key != pool.token_vaults[0]
+        || *base_lp_vault_info.key != pool.token_vaults[1]
+    {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    if program_info.key != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let base_pool = load_base_pool(program_id, &pool, base_pool_info)?;
+    if base_vault_infos.len() != base_pool.n() {
+        return Err(TemplateError::InvalidTokenCount.into());
+    }
+    if base_vault_infos
+        .iter()
+        .zip(base_pool.token_vaults())
+        .any(|(vault_info, vault)| vault_info.key != vault)
+    {
+        return Err(TemplateError::InvalidVault.into());
     }
-    
-    // Deserialize account data
-    let mut template_account = TemplateAccount::try_from_slice(&account_info.data.borrow())?;
-    
-    // Verify authority
-    if template_account.authority != *authority_info.key {
+
+    // Exactly one side must be the paired token
+    let (i, j) = (i as usize, j as usize);
+    if i > base_pool.n() || j > base_pool.n() || (i == 0) == (j == 0) {
+        return Err(TemplateError::InvalidTokenIndex.into());
+    }
+    if amount_in == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    let base_virtual_price = base_virtual_price(&base_pool, base_lp_mint_info, now)?;
+    let rates = rates(pool.precision_multipliers(), Some(base_virtual_price))?;
+    let amp = pool.current_amp(now);
+    let pool_seeds: &[&[u8]] = &[pool_info.key.as_ref(), &[pool.bump_seed]];
+
+    if i == 0 {
+        // Swap the paired token for base LP tokens, then withdraw those from
+        // the base pool as token j, which checks the caller's minimum
+        let (lp_amount, fee_amount) =
+            quote_swap(pool.balances(), &rates, amp, pool.fee, 0, 1, amount_in)?;
+        if lp_amount == 0 {
+            return Err(TemplateError::ZeroAmount.into());
+        }
+        transfer_to_vault(
+            token_program_info,
+            user_source_info,
+            token_vault_info,
+            user_info,
+            amount_in,
+        )?;
+        let base_vault_info = &base_vault_infos[j - 1];
+        invoke_signed(
+            &remove_liquidity_one_token(
+                program_id,
+                pool_authority_info.key,
+                base_pool_info.key,
+                base_pool_authority_info.key,
+                base_lp_mint_info.key,
+                base_lp_vault_info.key,
+                base_vault_info.key,
+                user_destination_info.key,
+                lp_amount,
+                minimum_amount_out,
+            ),
+            &[
+                pool_authority_info.clone(),
+                base_pool_info.clone(),
+                base_pool_authority_info.clone(),
+                base_lp_mint_info.clone(),
+                base_lp_vault_info.clone(),
+                token_program_info.clone(),
+                base_vault_info.clone(),
+                user_destination_info.clone(),
+                program_info.clone(),
+            ],
+            &[pool_seeds],
+        )?;
+
+        // Update balances
+        pool.balances[0] = pool.balances[0]
+            .checked_add(amount_in)
+            .ok_or(TemplateError::MathOverflow)?;
+        pool.balances[1] = pool.balances[1]
+            .checked_sub(lp_amount)
+            .and_then(|balance| balance.checked_sub(admin_fee(fee_amount)))
+            .ok_or(TemplateError::MathOverflow)?;
+    } else {
+        // Deposit token i into the base pool for the metapool's base LP
+        // vault, then swap the minted LP tokens for the paired token
+        let lp_before = token_amount(base_lp_vault_info)?;
+        let mut amounts = vec![0; base_pool.n()];
+        amounts[i - 1] = amount_in;
+        let mut account_infos = vec![
+            user_info.clone(),
+            base_pool_info.clone(),
+            base_pool_authority_info.clone(),
+            base_lp_mint_info.clone(),
+            base_lp_vault_info.clone(),
+            token_program_info.clone(),
+            user_source_info.clone(),
+            program_info.clone(),
+        ];
+        account_infos.extend(base_vault_infos.iter().cloned());
+        invoke(
+            &add_liquidity(
+                program_id,
+                user_info.key,
+                base_pool_info.key,
+                base_pool_authority_info.key,
+                base_lp_mint_info.key,
+                base_lp_vault_info.key,
+                &vec![*user_source_info.key; base_pool.n()],
+                base_pool.token_vaults(),
+                amounts,
+                0,
+            ),
+            &account_infos,
+        )?;
+        let lp_amount = token_amount(base_lp_vault_info)?
+            .checked_sub(lp_before)
+            .ok_or(TemplateError::MathOverflow)?;
+
+        let (amount_out, fee_amount) =
+            quote_swap(pool.balances(), &rates, amp, pool.fee, 1, 0, lp_amount)?;
+        if amount_out == 0 {
+            return Err(TemplateError::ZeroAmount.into());
+        }
+        if amount_out < minimum_amount_out {
+            return Err(TemplateError::SlippageExceeded.into());
+        }
+        transfer_from_vault(
+            token_program_info,
+            token_vault_info,
+            user_destination_info,
+            pool_authority_info,
+            pool_seeds,
+            amount_out,
+        )?;
+
+        // Update balances
+        pool.balances[1] = pool.balances[1]
+            .checked_add(lp_amount)
+            .ok_or(TemplateError::MathOverflow)?;
+        pool.balances[0] = pool.balances[0]
+            .checked_sub(amount_out)
+            .and_then(|balance| balance.checked_sub(admin_fee(fee_amount)))
+            .ok_or(TemplateError::MathOverflow)?;
+    }
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a WithdrawAdminFees instruction
+fn process_withdraw_admin_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let admin_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let token_infos = account_info_iter.as_slice();
+
+    // Check the admin is a signer
+    check_signer(admin_info)?;
+
+    // Load and validate the pool and verify the admin
+    let pool = load_pool(
+        program_id,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+    )?;
+    if pool.admin != *admin_info.key {
         return Err(TemplateError::InvalidAuthority.into());
     }
-    
-    // Update account data
-    template_account.value2 = template_account.value2.checked_add(1)
+    let token_pairs = token_account_pairs(&pool, token_infos)?;
+
+    // Everything in a vault beyond the tracked balance belongs to the admin
+    let amounts = token_pairs
+        .iter()
+        .zip(pool.balances())
+        .map(|((_, vault_info), balance)| {
+            token_amount(vault_info)?
+                .checked_sub(*balance)
+                .ok_or_else(|| TemplateError::MathOverflow.into())
+        })
+        .collect::<Result<Vec<u64>, ProgramError>>()?;
+    pay_out(
+        &pool,
+        pool_info,
+        pool_authority_info,
+        token_program_info,
+        &token_pairs,
+        &amounts,
+    )?;
+
+    Ok(())
+}
+
+/// Processes a Pause or Unpause instruction
+fn process_set_paused(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    paused: bool,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let admin_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+
+    // Load the pool and verify the admin
+    let mut pool = load_admin_pool(program_id, admin_info, pool_info)?;
+
+    pool.is_paused = paused;
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a CommitNewAdmin instruction
+fn process_commit_new_admin(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_admin: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let admin_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+
+    // Load the pool and verify the admin
+    let mut pool = load_admin_pool(program_id, admin_info, pool_info)?;
+
+    // Start the delay, replacing any pending transfer
+    let now = Clock::get()?.unix_timestamp;
+    pool.future_admin = new_admin;
+    pool.transfer_admin_deadline = now
+        .checked_add(ADMIN_TRANSFER_DELAY)
         .ok_or(TemplateError::MathOverflow)?;
-    
-    // Save updated account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes an ApplyNewAdmin instruction
+fn process_apply_new_admin(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let admin_info = next_account_info(account_info_iter)?;
+    let pool_info = next_account_info(account_info_iter)?;
+
+    // Load the pool and verify the admin
+    let mut pool = load_admin_pool(program_id, admin_info, pool_info)?;
+
+    // Validate the pending transfer
+    if pool.transfer_admin_deadline == 0 {
+        return Err(TemplateError::NoPendingAdmin.into());
+    }
+    if Clock::get()?.unix_timestamp < pool.transfer_admin_deadline {
+        return Err(TemplateError::AdminTransferNotReady.into());
+    }
+
+    // Hand the pool to the new admin
+    pool.admin = pool.future_admin;
+    pool.future_admin = Pubkey::default();
+    pool.transfer_admin_deadline = 0;
+
+    // Save updated pool data
+    pool.serialize(&mut &mut pool_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Checks the accounts and parameters of a pool being initialized
+///
+/// Returns the bump seed of the pool authority.
+#[allow(clippy::too_many_arguments)]
+fn check_new_pool(
+    program_id: &Pubkey,
+    admin_info: &AccountInfo,
+    pool_info: &AccountInfo,
+    pool_authority_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+    rent_info: &AccountInfo,
+    amp: u64,
+    fee: u64,
+) -> Result<u8, ProgramError> {
+    // Check the admin is a signer
+    check_signer(admin_info)?;
+
+    // Check pool account ownership
+    check_account_owner(pool_info, program_id)?;
+
+    // Check for rent exemption
+    let rent = &Rent::from_account_info(rent_info)?;
+    if !rent.is_exempt(pool_info.lamports(), pool_info.data_len()) {
+        return Err(TemplateError::NotRentExempt.into());
+    }
+
+    // Check the pool has not been initialized yet
+    if pool_info.data_len() != StableSwap::get_size() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if pool_info.data.borrow()[0] != 0 {
+        return Err(TemplateError::PoolAlreadyInitialized.into());
+    }
+
+    // Validate token program
+    if *token_program_info.key != spl_token::id() {
+        return Err(TemplateError::InvalidTokenProgram.into());
+    }
+
+    // Validate parameters
+    if !(MIN_AMP..=MAX_AMP).contains(&amp) {
+        return Err(TemplateError::InvalidAmp.into());
+    }
+    if fee > MAX_FEE {
+        return Err(TemplateError::InvalidFee.into());
+    }
+
+    // Validate pool authority
+    let (pool_authority, bump_seed) = find_pool_authority(program_id, pool_info.key);
+    if pool_authority != *pool_authority_info.key {
+        return Err(TemplateError::InvalidPoolAuthority.into());
+    }
+
+    Ok(bump_seed)
+}
+
+/// Checks that a new pool's LP mint is empty and controlled by the pool authority
+fn check_lp_mint(lp_mint_info: &AccountInfo, pool_authority: &Pubkey) -> ProgramResult {
+    check_account_owner(lp_mint_info, &spl_token::id())
+        .map_err(|_| TemplateError::InvalidLpMint)?;
+    let lp_mint = spl_token::state::Mint::unpack(&lp_mint_info.data.borrow())?;
+    if lp_mint.mint_authority != COption::Some(*pool_authority)
+        || lp_mint.freeze_authority.is_some()
+        || lp_mint.supply != 0
+    {
+        return Err(TemplateError::InvalidLpMint.into());
+    }
+    Ok(())
+}
+
+/// Reads the decimals of a vault's mint
+fn mint_decimals(mint_info: &AccountInfo, vault_mint: &Pubkey) -> Result<u8, ProgramError> {
+    if mint_info.key != vault_mint {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    check_account_owner(mint_info, &spl_token::id()).map_err(|_| TemplateError::InvalidVault)?;
+    Ok(spl_token::state::Mint::unpack(&mint_info.data.borrow())?.decimals)
+}
+
+/// Builds a new pool that scales every token to the largest decimals among them
+#[allow(clippy::too_many_arguments)]
+fn new_pool(
+    bump_seed: u8,
+    admin: &Pubkey,
+    mints: &[Pubkey],
+    vaults: &[Pubkey],
+    decimals: &[u8],
+    lp_mint: &Pubkey,
+    amp: u64,
+    fee: u64,
+    base_pool: Pubkey,
+) -> Result<StableSwap, ProgramError> {
+    let max_decimals = decimals.iter().copied().max().unwrap_or(0);
+    let mut token_mints = [Pubkey::default(); MAX_TOKENS];
+    let mut token_vaults = [Pubkey::default(); MAX_TOKENS];
+    let mut precision_multipliers = [0u64; MAX_TOKENS];
+    for index in 0..mints.len() {
+        token_mints[index] = mints[index];
+        token_vaults[index] = vaults[index];
+        precision_multipliers[index] = 10u64
+            .checked_pow((max_decimals - decimals[index]) as u32)
+            .ok_or(TemplateError::MathOverflow)?;
+    }
+
+    Ok(StableSwap {
+        is_initialized: true,
+        bump_seed,
+        admin: *admin,
+        n_tokens: mints.len() as u8,
+        token_mints,
+        token_vaults,
+        precision_multipliers,
+        decimals: max_decimals,
+        balances: [0; MAX_TOKENS],
+        lp_mint: *lp_mint,
+        fee,
+        initial_amp: amp * A_PRECISION,
+        future_amp: amp * A_PRECISION,
+        initial_amp_time: 0,
+        future_amp_time: 0,
+        base_pool,
+        is_paused: false,
+        future_admin: Pubkey::default(),
+        transfer_admin_deadline: 0,
+    })
+}
+
+/// Loads an initialized pool for an instruction the admin signs
+fn load_admin_pool(
+    program_id: &Pubkey,
+    admin_info: &AccountInfo,
+    pool_info: &AccountInfo,
+) -> Result<StableSwap, ProgramError> {
+    // Check the admin is a signer
+    check_signer(admin_info)?;
+
+    check_account_owner(pool_info, program_id)?;
+    let pool = StableSwap::try_from_slice(&pool_info.data.borrow())?;
+    if !pool.is_initialized {
+        return Err(TemplateError::PoolNotInitialized.into());
+    }
+    if pool.admin != *admin_info.key {
+        return Err(TemplateError::InvalidAuthority.into());
+    }
+
+    Ok(pool)
+}
+
+/// Loads the base pool of a metapool
+fn load_base_pool(
+    program_id: &Pubkey,
+    pool: &StableSwap,
+    base_pool_info: &AccountInfo,
+) -> Result<StableSwap, ProgramError> {
+    if *base_pool_info.key != pool.base_pool {
+        return Err(TemplateError::InvalidBasePool.into());
+    }
+    check_account_owner(base_pool_info, program_id)?;
+    Ok(StableSwap::try_from_slice(&base_pool_info.data.borrow())?)
+}
+
+/// Reads the virtual price of a base pool's LP token
+fn base_virtual_price(
+    base_pool: &StableSwap,
+    base_lp_mint_info: &AccountInfo,
+    now: i64,
+) -> Result<u128, ProgramError> {
+    if base_pool.lp_mint != *base_lp_mint_info.key {
+        return Err(TemplateError::InvalidLpMint.into());
+    }
+    let lp_supply = spl_token::state::Mint::unpack(&base_lp_mint_info.data.borrow())?.supply;
+    Ok(virtual_price(
+        base_pool.balances(),
+        &rates(base_pool.precision_multipliers(), None)?,
+        base_pool.current_amp(now),
+        lp_supply,
+    )?)
+}
+
+/// Returns the rate of each pool token
+///
+/// A metapool prices its base LP token with the base pool and base LP mint
+/// accounts at the start of `base_infos`; plain pools ignore them.
+fn pool_rates(
+    program_id: &Pubkey,
+    pool: &StableSwap,
+    base_infos: &[AccountInfo],
+    now: i64,
+) -> Result<Vec<u128>, ProgramError> {
+    if !pool.is_metapool() {
+        return Ok(rates(pool.precision_multipliers(), None)?);
+    }
+    let [base_pool_info, base_lp_mint_info, ..] = base_infos else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    let base_pool = load_base_pool(program_id, pool, base_pool_info)?;
+    let base_virtual_price = base_virtual_price(&base_pool, base_lp_mint_info, now)?;
+    Ok(rates(
+        pool.precision_multipliers(),
+        Some(base_virtual_price),
+    )?)
+}
+
+/// Splits the trailing accounts into the token account pairs and, for a
+/// metapool, the base pool accounts after them
+fn split_base_accounts<'a, 'b>(
+    pool: &StableSwap,
+    token_infos: &'b [AccountInfo<'a>],
+) -> (&'b [AccountInfo<'a>], &'b [AccountInfo<'a>]) {
+    if pool.is_metapool() {
+        token_infos.split_at(token_infos.len().min(2 * pool.n()))
+    } else {
+        (token_infos, &[])
+    }
+}
+
+/// Reads the balance of a token account
+fn token_amount(token_account_info: &AccountInfo) -> Result<u64, ProgramError> {
+    Ok(spl_token::state::Account::unpack(&token_account_info.data.borrow())?.amount)
+}
+
+/// Loads an initialized pool and checks its authority and the token program
+fn load_pool(
+    program_id: &Pubkey,
+    pool_info: &AccountInfo,
+    pool_authority_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+) -> Result<StableSwap, ProgramError> {
+    check_account_owner(pool_info, program_id)?;
+
+    let pool = StableSwap::try_from_slice(&pool_info.data.borrow())?;
+    if !pool.is_initialized {
+        return Err(TemplateError::PoolNotInitialized.into());
+    }
+
+    check_pool_authority(
+        program_id,
+        pool_info.key,
+        pool.bump_seed,
+        pool_authority_info.key,
+    )?;
+
+    if *token_program_info.key != spl_token::id() {
+        return Err(TemplateError::InvalidTokenProgram.into());
+    }
+
+    Ok(pool)
+}
+
+/// Splits the trailing accounts into `(user token account, vault)` pairs in pool token order
+fn token_account_pairs<'a, 'b>(
+    pool: &StableSwap,
+    token_infos: &'b [AccountInfo<'a>],
+) -> Result<Vec<(&'b AccountInfo<'a>, &'b AccountInfo<'a>)>, ProgramError> {
+    if token_infos.len() != 2 * pool.n() {
+        return Err(TemplateError::InvalidTokenCount.into());
+    }
+    token_infos
+        .chunks(2)
+        .zip(pool.token_vaults())
+        .map(|(pair, vault)| {
+            if pair[1].key != vault {
+                return Err(TemplateError::InvalidVault.into());
+            }
+            Ok((&pair[0], &pair[1]))
+        })
+        .collect()
+}
+
+/// Pays each non-zero amount from its vault to the user's token account
+fn pay_out<'a>(
+    pool: &StableSwap,
+    pool_info: &AccountInfo<'a>,
+    pool_authority_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+    token_pairs: &[(&AccountInfo<'a>, &AccountInfo<'a>)],
+    amounts: &[u64],
+) -> ProgramResult {
+    let pool_seeds: &[&[u8]] = &[pool_info.key.as_ref(), &[pool.bump_seed]];
+    for ((user_token_info, vault_info), amount) in token_pairs.iter().zip(amounts) {
+        if *amount > 0 {
+            transfer_from_vault(
+                token_program_info,
+                vault_info,
+                user_token_info,
+                pool_authority_info,
+                pool_seeds,
+                *amount,
+            )?;
+        }
+    }
     Ok(())
 }
+
+/// Burns LP tokens from an account the owner signs for
+fn burn_lp<'a>(
+    token_program_info: &AccountInfo<'a>,
+    user_lp_token_info: &AccountInfo<'a>,
+    lp_mint_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    invoke(
+        &spl_token::instruction::burn(
+            token_program_info.key,
+            user_lp_token_info.key,
+            lp_mint_info.key,
+            owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            user_lp_token_info.clone(),
+            lp_mint_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+/// Unpacks a vault token account and checks it can be handed to the pool
+fn unpack_vault(
+    vault_info: &AccountInfo,
+    pool_authority: &Pubkey,
+) -> Result<spl_token::state::Account, ProgramError> {
+    check_account_owner(vault_info, &spl_token::id()).map_err(|_| TemplateError::InvalidVault)?;
+    let vault = spl_token::state::Account::unpack(&vault_info.data.borrow())?;
+    if vault.owner != *pool_authority
+        || vault.amount != 0
+        || vault.delegate.is_some()
+        || vault.close_authority.is_some()
+    {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    Ok(vault)
+}
+
+/// Transfers tokens from a user-owned account into a pool vault
+fn transfer_to_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            source_info.key,
+            vault_info.key,
+            owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            source_info.clone(),
+            vault_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+/// Transfers tokens out of a pool vault, signing as the pool authority
+fn transfer_from_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    pool_authority_info: &AccountInfo<'a>,
+    pool_seeds: &[&[u8]],
+    amount: u64,
+) -> ProgramResult {
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            vault_info.key,
+            destination_info.key,
+            pool_authority_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            vault_info.clone(),
+            destination_info.clone(),
+            pool_authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[pool_seeds],
+    )
+}
@@ -1,8 +1,14 @@
 //! StableSwap AMM optimized for pegged-asset trading
 //!
-//! This program provides [TEMPLATE FUNCTIONALITY].
+//! This program provides liquidity pools of 2 to 4 pegged tokens priced by
+//! the Curve StableSwap invariant. Liquidity providers deposit any mix of the
+//! tokens into vaults owned by a pool PDA and receive LP tokens; traders swap
+//! between them at close to 1:1 with a fee that stays in the pool. The pool
+//! admin can ramp the amplification coefficient over time.
 
 pub mod instructions;
+pub mod curve;
+pub mod math;
 pub mod state;
 pub mod utils;
 pub mod errors;
@@ -13,7 +19,7 @@ pub mod entrypoint;
 // Export current sdk types for downstream users building with a different sdk version
 pub use solana_program;
 
-solana_program::declare_id!("stableswap-amm111111111111111111111111111111");
+solana_program::declare_id!("SSwap11111111111111111111111111111111111111");
 
 /// Program entrypoint's implementation
 pub fn process_instruction(
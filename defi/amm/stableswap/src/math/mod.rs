@@ -0,0 +1,6 @@
+//! Newton solvers for the StableSwap invariant
+//!
+//! Re-exports the `no_std` `stableswap-math` crate in `math/`. Its
+//! `MathError` converts into `TemplateError` with the same variants.
+
+pub use stableswap_math::*;
@@ -1,20 +1,145 @@
-//! State objects for template account
+//! State objects for stableswap pools
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::pubkey::Pubkey;
 
-/// Template account data
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-pub struct TemplateAccount {
-    /// The account authority (owner)
-    pub authority: Pubkey,
-    
-    /// Example value 1
-    pub value1: u64,
-    
-    /// Example value 2
-    pub value2: u8,
-    
-    /// Is the account initialized
+/// Maximum number of tokens in a pool
+pub const MAX_TOKENS: usize = 4;
+
+/// Minimum number of tokens in a pool
+pub const MIN_TOKENS: usize = 2;
+
+/// Delay between committing to a new admin and applying it, in seconds
+pub const ADMIN_TRANSFER_DELAY: i64 = 3 * 86_400;
+
+/// StableSwap pool
+///
+/// Holds 2 to `MAX_TOKENS` tokens priced by the Curve invariant. Only the
+/// first `n_tokens` entries of the per-token arrays are used. Balances are
+/// tracked here instead of being read from the vaults, so tokens sent
+/// directly to a vault cannot move the price.
+///
+/// A metapool pairs one token with the LP token of a base pool. The LP token
+/// is always the last token and is valued at the base pool's virtual price.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct StableSwap {
+    /// Is the pool initialized
     pub is_initialized: bool,
+
+    /// Bump seed of the pool authority PDA
+    pub bump_seed: u8,
+
+    /// Admin allowed to ramp the amplification coefficient, pause the pool
+    /// and withdraw admin fees
+    pub admin: Pubkey,
+
+    /// Number of tokens in the pool
+    pub n_tokens: u8,
+
+    /// Mint of each token
+    pub token_mints: [Pubkey; MAX_TOKENS],
+
+    /// Vault of each token, owned by the pool authority
+    pub token_vaults: [Pubkey; MAX_TOKENS],
+
+    /// Factor scaling each token's amounts to the largest decimals in the pool
+    pub precision_multipliers: [u64; MAX_TOKENS],
+
+    /// Decimals every token is scaled to by its precision multiplier
+    pub decimals: u8,
+
+    /// Tracked balance of each token, excluding admin fees
+    pub balances: [u64; MAX_TOKENS],
+
+    /// LP token mint, minted and burned by the pool authority
+    pub lp_mint: Pubkey,
+
+    /// Swap fee in units of `curve::FEE_DENOMINATOR`
+    pub fee: u64,
+
+    /// Amplification coefficient at the start of the ramp, times `math::A_PRECISION`
+    pub initial_amp: u64,
+
+    /// Amplification coefficient at the end of the ramp, times `math::A_PRECISION`
+    pub future_amp: u64,
+
+    /// Unix timestamp the ramp started at
+    pub initial_amp_time: i64,
+
+    /// Unix timestamp the ramp ends at
+    pub future_amp_time: i64,
+
+    /// Base pool whose LP token this metapool holds, or the default key for a plain pool
+    pub base_pool: Pubkey,
+
+    /// Are swaps and deposits paused
+    pub is_paused: bool,
+
+    /// Admin set by `CommitNewAdmin`, pending until applied
+    pub future_admin: Pubkey,
+
+    /// Unix timestamp `future_admin` can be applied from, or 0 if no transfer is pending
+    pub transfer_admin_deadline: i64,
+}
+
+impl StableSwap {
+    /// Get the size of StableSwap struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + bump_seed (1 byte) + admin (32 bytes) +
+        // n_tokens (1 byte) + mints and vaults (32 bytes * 2 * MAX_TOKENS) +
+        // multipliers and balances (8 bytes * 2 * MAX_TOKENS) + decimals (1 byte) +
+        // lp_mint (32 bytes) + fee (8 bytes) + amps (8 bytes * 2) +
+        // amp times (8 bytes * 2) + base_pool (32 bytes) + is_paused (1 byte) +
+        // future_admin (32 bytes) + transfer_admin_deadline (8 bytes)
+        let per_token = 32 * 2 * MAX_TOKENS + 8 * 2 * MAX_TOKENS;
+        1 + 1 + 32 + 1 + per_token + 1 + 32 + 8 + 8 * 2 + 8 * 2 + 32 + 1 + 32 + 8
+    }
+
+    /// Is the pool a metapool
+    pub fn is_metapool(&self) -> bool {
+        self.base_pool != Pubkey::default()
+    }
+
+    /// Number of tokens in the pool
+    pub fn n(&self) -> usize {
+        self.n_tokens as usize
+    }
+
+    /// Balances of the pool's tokens
+    pub fn balances(&self) -> &[u64] {
+        &self.balances[..self.n()]
+    }
+
+    /// Precision multipliers of the pool's tokens
+    pub fn precision_multipliers(&self) -> &[u64] {
+        &self.precision_multipliers[..self.n()]
+    }
+
+    /// Vaults of the pool's tokens
+    pub fn token_vaults(&self) -> &[Pubkey] {
+        &self.token_vaults[..self.n()]
+    }
+
+    /// Returns the index of the token held in `vault`
+    pub fn token_index(&self, vault: &Pubkey) -> Option<usize> {
+        self.token_vaults().iter().position(|v| v == vault)
+    }
+
+    /// Returns the amplification coefficient at `now`, times `math::A_PRECISION`
+    ///
+    /// Moves linearly from `initial_amp` to `future_amp` over the ramp.
+    pub fn current_amp(&self, now: i64) -> u64 {
+        if now >= self.future_amp_time {
+            return self.future_amp;
+        }
+        let elapsed = (now - self.initial_amp_time) as u128;
+        let duration = (self.future_amp_time - self.initial_amp_time) as u128;
+        let (initial, future) = (self.initial_amp as u128, self.future_amp as u128);
+        // The result lies between the two endpoints, so it fits in a u64
+        if future > initial {
+            (initial + (future - initial) * elapsed / duration) as u64
+        } else {
+            (initial - (initial - future) * elapsed / duration) as u64
+        }
+    }
 }
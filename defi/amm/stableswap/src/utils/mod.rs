@@ -1,9 +1,7 @@
 //! Utility functions for the program
 
 use solana_program::{
-    account_info::AccountInfo,
-    entrypoint::ProgramResult,
-    program_error::ProgramError,
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
     pubkey::Pubkey,
 };
 
@@ -31,3 +29,23 @@ pub fn check_signer(account_info: &AccountInfo) -> ProgramResult {
 pub fn safe_addition(a: u64, b: u64) -> Result<u64, TemplateError> {
     a.checked_add(b).ok_or(TemplateError::MathOverflow)
 }
+
+/// Finds the pool authority PDA that owns the vaults and the LP mint
+pub fn find_pool_authority(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[pool.as_ref()], program_id)
+}
+
+/// Checks that `authority` is the pool authority PDA for the given bump seed
+pub fn check_pool_authority(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    bump_seed: u8,
+    authority: &Pubkey,
+) -> ProgramResult {
+    let expected = Pubkey::create_program_address(&[pool.as_ref(), &[bump_seed]], program_id)
+        .map_err(|_| TemplateError::InvalidPoolAuthority)?;
+    if expected != *authority {
+        return Err(TemplateError::InvalidPoolAuthority.into());
+    }
+    Ok(())
+}
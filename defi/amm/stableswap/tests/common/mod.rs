@@ -0,0 +1,163 @@
+//! Fixtures shared by the stableswap-amm integration tests
+//!
+//! Every test binary compiles this module but uses only part of it.
+
+#![allow(dead_code)]
+
+use {
+    borsh::BorshDeserialize,
+    solana_program::{
+        hash::Hash,
+        instruction::{Instruction, InstructionError},
+        program_pack::Pack,
+        pubkey::Pubkey,
+        rent::Rent,
+        system_instruction,
+    },
+    solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestBanksClientExt},
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    stableswap_amm::{errors::TemplateError, process_instruction, state::StableSwap},
+};
+
+pub async fn process(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    process_with_blockhash(banks_client, payer, instructions, signers, recent_blockhash).await
+}
+
+/// Processes a transaction signed with `recent_blockhash`
+pub async fn process_with_blockhash(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+    recent_blockhash: Hash,
+) -> Result<(), TransactionError> {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &all_signers,
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+/// Waits for a blockhash newer than the bank's latest one
+///
+/// Resending a transaction byte for byte returns the status cached for
+/// the first attempt, so a retry must be signed with a blockhash that
+/// `process` cannot have used already.
+pub async fn new_blockhash(banks_client: &mut BanksClient) -> Hash {
+    let latest = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client
+        .get_new_latest_blockhash(&latest)
+        .await
+        .unwrap()
+}
+
+pub async fn create_mint(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint_authority: &Pubkey,
+    decimals: u8,
+) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                &mint.pubkey(),
+                mint_authority,
+                None,
+                decimals,
+            )
+            .unwrap(),
+        ],
+        &[&mint],
+    )
+    .await
+    .unwrap();
+    mint.pubkey()
+}
+
+pub async fn create_token_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Pubkey {
+    let account = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &account.pubkey(),
+                mint,
+                owner,
+            )
+            .unwrap(),
+        ],
+        &[&account],
+    )
+    .await
+    .unwrap();
+    account.pubkey()
+}
+
+pub async fn token_balance(banks_client: &mut BanksClient, account: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*account).await.unwrap().unwrap();
+    spl_token::state::Account::unpack(&account.data)
+        .unwrap()
+        .amount
+}
+
+pub async fn get_pool(banks_client: &mut BanksClient, pool: &Pubkey) -> StableSwap {
+    let account = banks_client.get_account(*pool).await.unwrap().unwrap();
+    StableSwap::try_from_slice(&account.data).unwrap()
+}
+
+pub fn program_test() -> (ProgramTest, Pubkey) {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        "stableswap_amm",
+        program_id,
+        processor!(process_instruction),
+    );
+    (program_test, program_id)
+}
+
+pub fn custom_error(error: TemplateError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
@@ -0,0 +1,120 @@
+//! Property tests for the stableswap-amm invariant
+
+use {
+    proptest::{collection::vec, prelude::*},
+    stableswap_amm::{
+        curve::{
+            compute_d_for_balances, quote_add_liquidity, quote_remove_liquidity_imbalance,
+            quote_remove_liquidity_one_token, quote_swap, MAX_FEE, RATE_PRECISION,
+        },
+        math::A_PRECISION,
+    },
+};
+
+/// Balances of 2 to 4 tokens, close enough to the peg for the solver and small
+/// enough that the u128 intermediates do not overflow
+fn balances() -> impl Strategy<Value = Vec<u64>> {
+    (2usize..=4).prop_flat_map(|n| vec(1_000_000u64..=1_000_000_000_000, n))
+}
+
+fn amp() -> impl Strategy<Value = u64> {
+    (1u64..=5_000).prop_map(|amp| amp * A_PRECISION)
+}
+
+fn fee() -> impl Strategy<Value = u64> {
+    0u64..=MAX_FEE / 100
+}
+
+proptest! {
+    #[test]
+    fn swap_never_decreases_d(
+        balances in balances(),
+        amp in amp(),
+        fee in fee(),
+        amount_in in 1u64..=1_000_000_000,
+        i in 0usize..4,
+        j in 0usize..4,
+    ) {
+        let n = balances.len();
+        let (i, j) = (i % n, j % n);
+        prop_assume!(i != j);
+        let multipliers = vec![RATE_PRECISION; n];
+
+        let (amount_out, _) = quote_swap(&balances, &multipliers, amp, fee, i, j, amount_in).unwrap();
+        prop_assert!(amount_out < balances[j]);
+
+        let mut after = balances.clone();
+        after[i] += amount_in;
+        after[j] -= amount_out;
+        let d_before = compute_d_for_balances(&balances, &multipliers, amp).unwrap();
+        let d_after = compute_d_for_balances(&after, &multipliers, amp).unwrap();
+        prop_assert!(d_after >= d_before);
+    }
+
+    #[test]
+    fn deposit_then_withdraw_never_profits(
+        balances in balances(),
+        amp in amp(),
+        fee in fee(),
+        deposit in 1u64..=1_000_000_000,
+        token in 0usize..4,
+    ) {
+        let n = balances.len();
+        let token = token % n;
+        let multipliers = vec![RATE_PRECISION; n];
+        let lp_supply = compute_d_for_balances(&balances, &multipliers, amp).unwrap() as u64;
+
+        let mut amounts = vec![0; n];
+        amounts[token] = deposit;
+        let (minted, _) = quote_add_liquidity(&balances, &multipliers, amp, fee, &amounts, lp_supply).unwrap();
+        prop_assume!(minted > 0);
+
+        let mut after = balances.clone();
+        after[token] += deposit;
+        let (amount_out, _) = quote_remove_liquidity_one_token(
+            &after,
+            &multipliers,
+            amp,
+            fee,
+            minted,
+            lp_supply + minted,
+            token,
+        )
+        .unwrap();
+        prop_assert!(amount_out <= deposit);
+    }
+
+    #[test]
+    fn withdraw_then_deposit_never_profits(
+        balances in balances(),
+        amp in amp(),
+        fee in fee(),
+        shares in vec(0u64..=1_000, 4),
+    ) {
+        let n = balances.len();
+        let multipliers = vec![RATE_PRECISION; n];
+        let lp_supply = compute_d_for_balances(&balances, &multipliers, amp).unwrap() as u64;
+
+        // Withdraw up to 10% of each balance, then deposit the same amounts back
+        let amounts: Vec<u64> = balances
+            .iter()
+            .zip(&shares)
+            .map(|(balance, share)| balance / 10_000 * share)
+            .collect();
+        prop_assume!(amounts.iter().any(|amount| *amount > 0));
+        let (burned, _) = quote_remove_liquidity_imbalance(
+            &balances,
+            &multipliers,
+            amp,
+            fee,
+            &amounts,
+            lp_supply,
+        )
+        .unwrap();
+
+        let after: Vec<u64> = balances.iter().zip(&amounts).map(|(b, a)| b - a).collect();
+        let (minted, _) =
+            quote_add_liquidity(&after, &multipliers, amp, fee, &amounts, lp_supply - burned).unwrap();
+        prop_assert!(minted <= burned);
+    }
+}
@@ -1,41 +1,922 @@
 //! Integration tests for stableswap-amm
 
+mod common;
+
 #[cfg(test)]
 mod tests {
     use {
-        borsh::BorshSerialize,
-        solana_program::{
-            instruction::{AccountMeta, Instruction},
-            pubkey::Pubkey,
-            rent::Rent,
-            system_instruction,
+        super::common::{
+            create_mint, create_token_account, custom_error, get_pool, new_blockhash, process,
+            process_with_blockhash, program_test, token_balance,
         },
-        solana_program_test::{processor, ProgramTest},
-        solana_sdk::{
-            account::Account,
-            signature::{Keypair, Signer},
-            transaction::Transaction,
+        solana_program::{
+            clock::Clock, instruction::Instruction, pubkey::Pubkey, rent::Rent, system_instruction,
         },
-        stableswap-amm::{
-            instructions::TemplateInstruction,
-            process_instruction,
-            state::TemplateAccount,
+        solana_program_test::{BanksClient, ProgramTestContext},
+        solana_sdk::signature::{Keypair, Signer},
+        stableswap_amm::{
+            curve::{admin_fee, quote_swap, MIN_RAMP_DURATION, RATE_PRECISION},
+            errors::TemplateError,
+            instructions::{
+                add_liquidity, apply_new_admin, commit_new_admin, initialize_pool, pause, ramp_a,
+                remove_liquidity, remove_liquidity_imbalance, remove_liquidity_one_token, swap,
+                unpause, withdraw_admin_fees,
+            },
+            math::A_PRECISION,
+            state::{StableSwap, ADMIN_TRANSFER_DELAY},
+            utils::find_pool_authority,
         },
-        std::str::FromStr,
     };
 
+    const AMP: u64 = 100;
+    /// 0.04%, in units of `FEE_DENOMINATOR`
+    const FEE: u64 = 4_000_000;
+    const USER_FUNDS: u64 = 1_000_000_000_000_000;
+
+    /// Accounts of a pool set up for testing
+    struct PoolFixture {
+        program_id: Pubkey,
+        pool: Pubkey,
+        pool_authority: Pubkey,
+        lp_mint: Pubkey,
+        vaults: Vec<Pubkey>,
+        user: Keypair,
+        user_tokens: Vec<Pubkey>,
+        user_lp_token: Pubkey,
+    }
+
+    impl PoolFixture {
+        fn add_liquidity(&self, amounts: Vec<u64>, min_lp_amount: u64) -> Instruction {
+            add_liquidity(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.pool,
+                &self.pool_authority,
+                &self.lp_mint,
+                &self.user_lp_token,
+                &self.user_tokens,
+                &self.vaults,
+                amounts,
+                min_lp_amount,
+            )
+        }
+
+        fn swap(&self, i: usize, j: usize, amount_in: u64, minimum_amount_out: u64) -> Instruction {
+            swap(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.pool,
+                &self.pool_authority,
+                &self.user_tokens[i],
+                &self.user_tokens[j],
+                &self.vaults[i],
+                &self.vaults[j],
+                amount_in,
+                minimum_amount_out,
+            )
+        }
+
+        fn remove_liquidity(&self, lp_amount: u64) -> Instruction {
+            remove_liquidity(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.pool,
+                &self.pool_authority,
+                &self.lp_mint,
+                &self.user_lp_token,
+                &self.user_tokens,
+                &self.vaults,
+                lp_amount,
+                vec![0; self.vaults.len()],
+            )
+        }
+    }
+
+    /// Creates mints with the given decimals, an initialized pool with the
+    /// payer as admin and a user funded with every token
+    async fn setup_pool(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        program_id: Pubkey,
+        decimals: &[u8],
+    ) -> PoolFixture {
+        let pool = Keypair::new();
+        let (pool_authority, _) = find_pool_authority(&program_id, &pool.pubkey());
+
+        let mut mints = Vec::new();
+        let mut vaults = Vec::new();
+        for decimals in decimals {
+            let mint = create_mint(banks_client, payer, &payer.pubkey(), *decimals).await;
+            vaults.push(create_token_account(banks_client, payer, &mint, &pool_authority).await);
+            mints.push(mint);
+        }
+        let lp_mint = create_mint(banks_client, payer, &pool_authority, 6).await;
+
+        let rent = Rent::default();
+        process(
+            banks_client,
+            payer,
+            &[
+                system_instruction::create_account(
+                    &payer.pubkey(),
+                    &pool.pubkey(),
+                    rent.minimum_balance(StableSwap::get_size()),
+                    StableSwap::get_size() as u64,
+                    &program_id,
+                ),
+                initialize_pool(
+                    &program_id,
+                    &payer.pubkey(),
+                    &pool.pubkey(),
+                    &pool_authority,
+                    &lp_mint,
+                    &vaults,
+                    &mints,
+                    AMP,
+                    FEE,
+                ),
+            ],
+            &[&pool],
+        )
+        .await
+        .unwrap();
+
+        // Fund the user with every token
+        let user = Keypair::new();
+        let mut user_tokens = Vec::new();
+        for mint in &mints {
+            let user_token = create_token_account(banks_client, payer, mint, &user.pubkey()).await;
+            process(
+                banks_client,
+                payer,
+                &[spl_token::instruction::mint_to(
+                    &spl_token::id(),
+                    mint,
+                    &user_token,
+                    &payer.pubkey(),
+                    &[],
+                    USER_FUNDS,
+                )
+                .unwrap()],
+                &[],
+            )
+            .await
+            .unwrap();
+            user_tokens.push(user_token);
+        }
+        let user_lp_token =
+            create_token_account(banks_client, payer, &lp_mint, &user.pubkey()).await;
+
+        PoolFixture {
+            program_id,
+            pool: pool.pubkey(),
+            pool_authority,
+            lp_mint,
+            vaults,
+            user,
+            user_tokens,
+            user_lp_token,
+        }
+    }
+
+    /// Sets up a pool and deposits `amount` of every token
+    async fn setup_funded_pool(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        program_id: Pubkey,
+        decimals: &[u8],
+        amount: u64,
+    ) -> PoolFixture {
+        let fixture = setup_pool(banks_client, payer, program_id, decimals).await;
+        process(
+            banks_client,
+            payer,
+            &[fixture.add_liquidity(vec![amount; decimals.len()], 1)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        fixture
+    }
+
+    /// Moves the cluster clock to `unix_timestamp`
+    async fn set_timestamp(context: &mut ProgramTestContext, unix_timestamp: i64) {
+        let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp = unix_timestamp;
+        context.set_sysvar(&clock);
+    }
+
     #[tokio::test]
-    async fn test_initialize() {
-        // TODO: Implement test logic for initialization
+    async fn test_initialize_pool() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_pool(&mut banks_client, &payer, program_id, &[6, 6, 9]).await;
+
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert!(pool.is_initialized);
+        assert_eq!(pool.admin, payer.pubkey());
+        assert_eq!(pool.n_tokens, 3);
+        assert_eq!(pool.token_vaults(), &fixture.vaults[..]);
+        assert_eq!(pool.precision_multipliers(), &[1_000, 1_000, 1]);
+        assert_eq!(pool.balances(), &[0, 0, 0]);
+        assert_eq!(pool.fee, FEE);
+        assert_eq!(pool.current_amp(0), AMP * A_PRECISION);
     }
 
     #[tokio::test]
-    async fn test_operation1() {
-        // TODO: Implement test logic for operation1
+    async fn test_initialize_rejects_single_token() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+
+        let pool = Keypair::new();
+        let (pool_authority, _) = find_pool_authority(&program_id, &pool.pubkey());
+        let mint = create_mint(&mut banks_client, &payer, &payer.pubkey(), 6).await;
+        let vault = create_token_account(&mut banks_client, &payer, &mint, &pool_authority).await;
+        let lp_mint = create_mint(&mut banks_client, &payer, &pool_authority, 6).await;
+        let rent = Rent::default();
+        process(
+            &mut banks_client,
+            &payer,
+            &[system_instruction::create_account(
+                &payer.pubkey(),
+                &pool.pubkey(),
+                rent.minimum_balance(StableSwap::get_size()),
+                StableSwap::get_size() as u64,
+                &program_id,
+            )],
+            &[&pool],
+        )
+        .await
+        .unwrap();
+
+        let err = process(
+            &mut banks_client,
+            &payer,
+            &[initialize_pool(
+                &program_id,
+                &payer.pubkey(),
+                &pool.pubkey(),
+                &pool_authority,
+                &lp_mint,
+                &[vault],
+                &[mint],
+                AMP,
+                FEE,
+            )],
+            &[],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidTokenCount));
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove_liquidity() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_funded_pool(
+            &mut banks_client,
+            &payer,
+            program_id,
+            &[6, 6, 6],
+            1_000_000_000,
+        )
+        .await;
+
+        // A balanced first deposit mints D, the sum of the balances
+        let lp_amount = token_balance(&mut banks_client, &fixture.user_lp_token).await;
+        assert_eq!(lp_amount, 3_000_000_000);
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(pool.balances(), &[1_000_000_000; 3]);
+
+        // Withdrawing half returns half of every balance
+        process(
+            &mut banks_client,
+            &payer,
+            &[remove_liquidity(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &fixture.pool,
+                &fixture.pool_authority,
+                &fixture.lp_mint,
+                &fixture.user_lp_token,
+                &fixture.user_tokens,
+                &fixture.vaults,
+                lp_amount / 2,
+                vec![500_000_000; 3],
+            )],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        for user_token in &fixture.user_tokens {
+            assert_eq!(
+                token_balance(&mut banks_client, user_token).await,
+                USER_FUNDS - 500_000_000
+            );
+        }
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(pool.balances(), &[500_000_000; 3]);
+    }
+
+    #[tokio::test]
+    async fn test_imbalanced_deposit_pays_fee() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_funded_pool(
+            &mut banks_client,
+            &payer,
+            program_id,
+            &[6, 6],
+            1_000_000_000,
+        )
+        .await;
+        let lp_before = token_balance(&mut banks_client, &fixture.user_lp_token).await;
+
+        // A single-sided deposit is worth slightly less than its face value
+        process(
+            &mut banks_client,
+            &payer,
+            &[fixture.add_liquidity(vec![100_000_000, 0], 1)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        let minted = token_balance(&mut banks_client, &fixture.user_lp_token).await - lp_before;
+        assert!(minted < 100_000_000);
+        assert!(minted > 99_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_swap_near_peg() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_funded_pool(
+            &mut banks_client,
+            &payer,
+            program_id,
+            &[6, 6, 6],
+            1_000_000_000_000,
+        )
+        .await;
+
+        let amount_in = 1_000_000_000;
+        process(
+            &mut banks_client,
+            &payer,
+            &[fixture.swap(0, 2, amount_in, 1)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        // A small trade in a balanced pool pays little more than the fee
+        let amount_out = token_balance(&mut banks_client, &fixture.user_tokens[2]).await
+            + 1_000_000_000_000
+            - USER_FUNDS;
+        let after_fee = amount_in - amount_in * FEE / 10_000_000_000;
+        assert!(amount_out < after_fee);
+        assert!(amount_out > after_fee - amount_in / 10_000);
+
+        // Half the fee stays in the vault for the admin
+        let (_, fee_amount) = quote_swap(
+            &[1_000_000_000_000; 3],
+            &[RATE_PRECISION; 3],
+            AMP * A_PRECISION,
+            FEE,
+            0,
+            2,
+            amount_in,
+        )
+        .unwrap();
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(
+            pool.balances(),
+            &[
+                1_000_000_000_000 + amount_in,
+                1_000_000_000_000,
+                1_000_000_000_000 - amount_out - admin_fee(fee_amount)
+            ]
+        );
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.vaults[2]).await,
+            1_000_000_000_000 - amount_out
+        );
+    }
+
+    #[tokio::test]
+    async fn test_swap_scales_decimals() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_pool(&mut banks_client, &payer, program_id, &[6, 9]).await;
+
+        // 1,000,000 of each token, at 6 and 9 decimals
+        process(
+            &mut banks_client,
+            &payer,
+            &[fixture.add_liquidity(vec![1_000_000_000_000, 1_000_000_000_000_000], 1)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        // One whole token of the first buys about one whole token of the second
+        let before = token_balance(&mut banks_client, &fixture.user_tokens[1]).await;
+        process(
+            &mut banks_client,
+            &payer,
+            &[fixture.swap(0, 1, 1_000_000, 1)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        let amount_out = token_balance(&mut banks_client, &fixture.user_tokens[1]).await - before;
+        assert!(amount_out > 999_000_000 && amount_out < 1_000_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_swap_slippage() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_funded_pool(
+            &mut banks_client,
+            &payer,
+            program_id,
+            &[6, 6],
+            1_000_000_000,
+        )
+        .await;
+
+        let err = process(
+            &mut banks_client,
+            &payer,
+            &[fixture.swap(0, 1, 1_000_000, 1_000_000)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::SlippageExceeded));
     }
 
     #[tokio::test]
-    async fn test_operation2() {
-        // TODO: Implement test logic for operation2
+    async fn test_swap_rejects_same_vault() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_funded_pool(
+            &mut banks_client,
+            &payer,
+            program_id,
+            &[6, 6],
+            1_000_000_000,
+        )
+        .await;
+
+        let err = process(
+            &mut banks_client,
+            &payer,
+            &[fixture.swap(1, 1, 1_000_000, 1)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidVault));
+    }
+
+    #[tokio::test]
+    async fn test_remove_liquidity_one_token() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_funded_pool(
+            &mut banks_client,
+            &payer,
+            program_id,
+            &[6, 6, 6],
+            1_000_000_000,
+        )
+        .await;
+
+        let before = token_balance(&mut banks_client, &fixture.user_tokens[1]).await;
+        process(
+            &mut banks_client,
+            &payer,
+            &[remove_liquidity_one_token(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &fixture.pool,
+                &fixture.pool_authority,
+                &fixture.lp_mint,
+                &fixture.user_lp_token,
+                &fixture.vaults[1],
+                &fixture.user_tokens[1],
+                30_000_000,
+                1,
+            )],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        // 1% of the LP supply is worth a little under 30,000,000 of one token
+        let amount_out = token_balance(&mut banks_client, &fixture.user_tokens[1]).await - before;
+        assert!(amount_out < 30_000_000);
+        assert!(amount_out > 29_900_000);
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert!(pool.balances()[1] < 1_000_000_000 - amount_out);
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.vaults[1]).await,
+            1_000_000_000 - amount_out
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_liquidity_imbalance() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_funded_pool(
+            &mut banks_client,
+            &payer,
+            program_id,
+            &[6, 6],
+            1_000_000_000,
+        )
+        .await;
+
+        let remove = |max_lp_amount| {
+            remove_liquidity_imbalance(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &fixture.pool,
+                &fixture.pool_authority,
+                &fixture.lp_mint,
+                &fixture.user_lp_token,
+                &fixture.user_tokens,
+                &fixture.vaults,
+                vec![10_000_000, 0],
+                max_lp_amount,
+            )
+        };
+
+        // Withdrawing 10,000,000 of one token burns a little more LP than that
+        let err = process(
+            &mut banks_client,
+            &payer,
+            &[remove(10_000_000)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::SlippageExceeded));
+
+        let lp_before = token_balance(&mut banks_client, &fixture.user_lp_token).await;
+        process(
+            &mut banks_client,
+            &payer,
+            &[remove(10_100_000)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        let burned = lp_before - token_balance(&mut banks_client, &fixture.user_lp_token).await;
+        assert!(burned > 10_000_000);
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.user_tokens[0]).await,
+            USER_FUNDS - 1_000_000_000 + 10_000_000
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ramp_a() {
+        let (program_test, program_id) = program_test();
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let fixture = setup_pool(&mut context.banks_client, &payer, program_id, &[6, 6]).await;
+
+        let start = 1_000_000;
+        set_timestamp(&mut context, start).await;
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[ramp_a(
+                &program_id,
+                &payer.pubkey(),
+                &fixture.pool,
+                1_000,
+                start + 2 * MIN_RAMP_DURATION,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        // The coefficient moves linearly and then stays at the target
+        let pool = get_pool(&mut context.banks_client, &fixture.pool).await;
+        assert_eq!(pool.current_amp(start), AMP * A_PRECISION);
+        assert_eq!(
+            pool.current_amp(start + MIN_RAMP_DURATION),
+            (AMP + 1_000) / 2 * A_PRECISION
+        );
+        assert_eq!(
+            pool.current_amp(start + 3 * MIN_RAMP_DURATION),
+            1_000 * A_PRECISION
+        );
+
+        // A new ramp cannot start within a day of the last one
+        let err = process(
+            &mut context.banks_client,
+            &payer,
+            &[ramp_a(
+                &program_id,
+                &payer.pubkey(),
+                &fixture.pool,
+                500,
+                start + 3 * MIN_RAMP_DURATION,
+            )],
+            &[],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidRamp));
+    }
+
+    #[tokio::test]
+    async fn test_ramp_a_limits() {
+        let (program_test, program_id) = program_test();
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let fixture = setup_pool(&mut context.banks_client, &payer, program_id, &[6, 6]).await;
+        set_timestamp(&mut context, 1_000_000).await;
+        let stop_ramp_ts = 1_000_000 + MIN_RAMP_DURATION;
+
+        // More than a tenfold change
+        let err = process(
+            &mut context.banks_client,
+            &payer,
+            &[ramp_a(
+                &program_id,
+                &payer.pubkey(),
+                &fixture.pool,
+                AMP * 10 + 1,
+                stop_ramp_ts,
+            )],
+            &[],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidRamp));
+
+        // Shorter than the minimum ramp
+        let err = process(
+            &mut context.banks_client,
+            &payer,
+            &[ramp_a(
+                &program_id,
+                &payer.pubkey(),
+                &fixture.pool,
+                AMP * 2,
+                stop_ramp_ts - 1,
+            )],
+            &[],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidRamp));
+
+        // Not the admin
+        let err = process(
+            &mut context.banks_client,
+            &payer,
+            &[ramp_a(
+                &program_id,
+                &fixture.user.pubkey(),
+                &fixture.pool,
+                AMP * 2,
+                stop_ramp_ts,
+            )],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidAuthority));
+    }
+
+    #[tokio::test]
+    async fn test_withdraw_admin_fees() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_funded_pool(
+            &mut banks_client,
+            &payer,
+            program_id,
+            &[6, 6, 6],
+            1_000_000_000_000,
+        )
+        .await;
+        process(
+            &mut banks_client,
+            &payer,
+            &[fixture.swap(0, 2, 1_000_000_000, 1)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        let mut admin_tokens = Vec::new();
+        for mint in &pool.token_mints[..pool.n()] {
+            admin_tokens
+                .push(create_token_account(&mut banks_client, &payer, mint, &payer.pubkey()).await);
+        }
+        let withdraw = |admin: &Pubkey| {
+            withdraw_admin_fees(
+                &program_id,
+                admin,
+                &fixture.pool,
+                &fixture.pool_authority,
+                &admin_tokens,
+                &fixture.vaults,
+            )
+        };
+
+        // Only the admin can withdraw
+        let err = process(
+            &mut banks_client,
+            &payer,
+            &[withdraw(&fixture.user.pubkey())],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidAuthority));
+
+        // The admin receives the untracked part of each vault, here only the
+        // share of the swap fee paid in token 2
+        let vault_before = token_balance(&mut banks_client, &fixture.vaults[2]).await;
+        process(&mut banks_client, &payer, &[withdraw(&payer.pubkey())], &[])
+            .await
+            .unwrap();
+        let fees = token_balance(&mut banks_client, &admin_tokens[2]).await;
+        assert_eq!(fees, vault_before - pool.balances[2]);
+        assert!(fees > 0);
+        for admin_token in &admin_tokens[..2] {
+            assert_eq!(token_balance(&mut banks_client, admin_token).await, 0);
+        }
+        for (vault, balance) in fixture.vaults.iter().zip(pool.balances()) {
+            assert_eq!(token_balance(&mut banks_client, vault).await, *balance);
+        }
+        assert_eq!(get_pool(&mut banks_client, &fixture.pool).await, pool);
+    }
+
+    #[tokio::test]
+    async fn test_pause_allows_only_withdrawals() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_funded_pool(
+            &mut banks_client,
+            &payer,
+            program_id,
+            &[6, 6],
+            1_000_000_000,
+        )
+        .await;
+
+        // Only the admin can pause
+        let err = process(
+            &mut banks_client,
+            &payer,
+            &[pause(&program_id, &fixture.user.pubkey(), &fixture.pool)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidAuthority));
+
+        process(
+            &mut banks_client,
+            &payer,
+            &[pause(&program_id, &payer.pubkey(), &fixture.pool)],
+            &[],
+        )
+        .await
+        .unwrap();
+        assert!(get_pool(&mut banks_client, &fixture.pool).await.is_paused);
+
+        // Swaps and deposits are rejected
+        for instruction in [
+            fixture.swap(0, 1, 1_000_000, 1),
+            fixture.add_liquidity(vec![1_000_000, 1_000_000], 1),
+        ] {
+            let err = process(&mut banks_client, &payer, &[instruction], &[&fixture.user])
+                .await
+                .unwrap_err();
+            assert_eq!(err, custom_error(TemplateError::PoolPaused));
+        }
+
+        // LPs can still withdraw
+        process(
+            &mut banks_client,
+            &payer,
+            &[fixture.remove_liquidity(1_000_000)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        process(
+            &mut banks_client,
+            &payer,
+            &[unpause(&program_id, &payer.pubkey(), &fixture.pool)],
+            &[],
+        )
+        .await
+        .unwrap();
+        process(
+            &mut banks_client,
+            &payer,
+            &[fixture.swap(0, 1, 1_000_000, 1)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admin_transfer() {
+        let (program_test, program_id) = program_test();
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let fixture = setup_pool(&mut context.banks_client, &payer, program_id, &[6, 6]).await;
+        let new_admin = fixture.user.pubkey();
+        let start = 1_000_000;
+        set_timestamp(&mut context, start).await;
+
+        // Nothing to apply before a commit
+        let err = process(
+            &mut context.banks_client,
+            &payer,
+            &[apply_new_admin(&program_id, &payer.pubkey(), &fixture.pool)],
+            &[],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::NoPendingAdmin));
+
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[commit_new_admin(
+                &program_id,
+                &payer.pubkey(),
+                &fixture.pool,
+                &new_admin,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+        let pool = get_pool(&mut context.banks_client, &fixture.pool).await;
+        assert_eq!(pool.future_admin, new_admin);
+        assert_eq!(pool.transfer_admin_deadline, start + ADMIN_TRANSFER_DELAY);
+
+        // The transfer waits for the deadline. Each retry of the same
+        // instruction is signed with a fresh blockhash so it is not answered
+        // with the cached result of the rejected attempt.
+        let recent_blockhash = new_blockhash(&mut context.banks_client).await;
+        set_timestamp(&mut context, start + ADMIN_TRANSFER_DELAY - 1).await;
+        let err = process_with_blockhash(
+            &mut context.banks_client,
+            &payer,
+            &[apply_new_admin(&program_id, &payer.pubkey(), &fixture.pool)],
+            &[],
+            recent_blockhash,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::AdminTransferNotReady));
+
+        let recent_blockhash = new_blockhash(&mut context.banks_client).await;
+        set_timestamp(&mut context, start + ADMIN_TRANSFER_DELAY).await;
+        process_with_blockhash(
+            &mut context.banks_client,
+            &payer,
+            &[apply_new_admin(&program_id, &payer.pubkey(), &fixture.pool)],
+            &[],
+            recent_blockhash,
+        )
+        .await
+        .unwrap();
+        let pool = get_pool(&mut context.banks_client, &fixture.pool).await;
+        assert_eq!(pool.admin, new_admin);
+        assert_eq!(pool.transfer_admin_deadline, 0);
+
+        // The old admin has lost control
+        let err = process(
+            &mut context.banks_client,
+            &payer,
+            &[pause(&program_id, &payer.pubkey(), &fixture.pool)],
+            &[],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidAuthority));
     }
 }
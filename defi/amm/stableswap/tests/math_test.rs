@@ -0,0 +1,111 @@
+//! Tests for the stableswap-amm Newton solvers
+//!
+//! `vectors/stableswap.txt` is generated by `vectors/generate.py`, a port of
+//! Curve's solvers with the on-chain overflow checks and iteration caps, so
+//! these tests pin every solver result to the last unit.
+
+use stableswap_amm::math::{compute_d, compute_y, compute_y_d, MathError, A_PRECISION};
+
+const VECTORS: &str = include_str!("vectors/stableswap.txt");
+
+fn expected(value: &str) -> Result<u128, MathError> {
+    match value {
+        "overflow" => Err(MathError::MathOverflow),
+        "invariant_not_converged" => Err(MathError::InvariantNotConverged),
+        "balance_not_converged" => Err(MathError::BalanceNotConverged),
+        value => Ok(value.parse().unwrap()),
+    }
+}
+
+fn numbers(fields: &[&str]) -> Vec<u128> {
+    fields.iter().map(|field| field.parse().unwrap()).collect()
+}
+
+#[test]
+fn test_reference_vectors() {
+    let mut checked = 0;
+    for line in VECTORS
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let want = expected(fields[1]);
+        let amp: u64 = fields[2].parse().unwrap();
+        let got = match fields[0] {
+            "d" => compute_d(&numbers(&fields[3..]), amp),
+            "y" => {
+                let (i, j) = (fields[3].parse().unwrap(), fields[4].parse().unwrap());
+                let x = fields[5].parse().unwrap();
+                compute_y(i, j, x, &numbers(&fields[6..]), amp)
+            }
+            "y_d" => {
+                let i = fields[3].parse().unwrap();
+                let d = fields[4].parse().unwrap();
+                compute_y_d(i, &numbers(&fields[5..]), amp, d)
+            }
+            kind => panic!("unknown vector kind {}", kind),
+        };
+        assert_eq!(got, want, "vector: {}", line);
+        checked += 1;
+    }
+    assert_eq!(checked, 1_201);
+}
+
+#[test]
+fn test_empty_pool_has_zero_invariant() {
+    assert_eq!(compute_d(&[0, 0, 0], 100 * A_PRECISION), Ok(0));
+}
+
+#[test]
+fn test_balanced_pool_invariant_is_sum() {
+    let xp = [1_000_000_000_000u128; 4];
+    assert_eq!(compute_d(&xp, 100 * A_PRECISION), Ok(4_000_000_000_000));
+}
+
+#[test]
+fn test_invalid_token_index() {
+    let xp = [1_000_000u128, 1_000_000];
+    let amp = 100 * A_PRECISION;
+    assert_eq!(
+        compute_y(0, 0, 2_000_000, &xp, amp),
+        Err(MathError::InvalidTokenIndex)
+    );
+    assert_eq!(
+        compute_y(0, 2, 2_000_000, &xp, amp),
+        Err(MathError::InvalidTokenIndex)
+    );
+    assert_eq!(
+        compute_y_d(2, &xp, amp, 2_000_000),
+        Err(MathError::InvalidTokenIndex)
+    );
+}
+
+#[test]
+fn test_intermediates_beyond_u128() {
+    // D^2 overflows u128 but not the 192-bit intermediates
+    let xp = [10u128.pow(25), 10u128.pow(25)];
+    assert_eq!(compute_d(&xp, 100 * A_PRECISION), Ok(2 * 10u128.pow(25)));
+}
+
+#[test]
+fn test_oscillating_invariant_does_not_converge() {
+    // Rounding flips D between two values that differ by more than one unit
+    let xp = [64_939_189u128, 11_258, 105];
+    assert_eq!(
+        compute_d(&xp, 488_725),
+        Err(MathError::InvariantNotConverged)
+    );
+    assert_eq!(
+        compute_y(0, 1, 70_000_000, &xp, 488_725),
+        Err(MathError::InvariantNotConverged)
+    );
+}
+
+#[test]
+fn test_empty_balance_is_an_error() {
+    let xp = [0u128, 1_000_000];
+    assert_eq!(
+        compute_d(&xp, 100 * A_PRECISION),
+        Err(MathError::MathOverflow)
+    );
+}
@@ -0,0 +1,605 @@
+//! Integration tests for stableswap-amm metapools
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::common::{
+            create_mint, create_token_account, custom_error, get_pool, process, program_test,
+            token_balance,
+        },
+        solana_program::{
+            clock::Clock,
+            instruction::{Instruction, InstructionError},
+            program_pack::Pack,
+            pubkey::Pubkey,
+            rent::Rent,
+            system_instruction,
+        },
+        solana_program_test::BanksClient,
+        solana_sdk::{
+            signature::{Keypair, Signer},
+            transaction::TransactionError,
+        },
+        stableswap_amm::{
+            curve::{admin_fee, quote_swap, rates, virtual_price, RATE_PRECISION},
+            errors::TemplateError,
+            instructions::{
+                add_liquidity, initialize_metapool, initialize_pool, metapool_accounts, swap,
+                swap_underlying,
+            },
+            state::StableSwap,
+            utils::find_pool_authority,
+        },
+    };
+
+    const AMP: u64 = 100;
+    /// 0.04%, in units of `FEE_DENOMINATOR`
+    const FEE: u64 = 4_000_000;
+    const USER_FUNDS: u64 = 1_000_000_000_000_000;
+    const DEPOSIT: u64 = 1_000_000_000_000;
+
+    /// A base pool and a metapool on top of it, with a user funded with every
+    /// base token, the paired token and base LP tokens
+    struct MetapoolFixture {
+        program_id: Pubkey,
+        base_pool: Pubkey,
+        base_pool_authority: Pubkey,
+        base_lp_mint: Pubkey,
+        base_vaults: Vec<Pubkey>,
+        pool: Pubkey,
+        pool_authority: Pubkey,
+        lp_mint: Pubkey,
+        vaults: [Pubkey; 2],
+        user: Keypair,
+        user_base_tokens: Vec<Pubkey>,
+        user_tokens: [Pubkey; 2],
+        user_lp_token: Pubkey,
+    }
+
+    impl MetapoolFixture {
+        fn swap(&self, i: usize, j: usize, amount_in: u64, minimum_amount_out: u64) -> Instruction {
+            let mut instruction = swap(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.pool,
+                &self.pool_authority,
+                &self.user_tokens[i],
+                &self.user_tokens[j],
+                &self.vaults[i],
+                &self.vaults[j],
+                amount_in,
+                minimum_amount_out,
+            );
+            instruction
+                .accounts
+                .extend(metapool_accounts(&self.base_pool, &self.base_lp_mint));
+            instruction
+        }
+
+        /// Swaps between underlying tokens: 0 is the paired token and
+        /// `1..` are the base pool's tokens
+        fn swap_underlying(
+            &self,
+            i: u8,
+            j: u8,
+            amount_in: u64,
+            minimum_amount_out: u64,
+        ) -> Instruction {
+            let user_token = |index: u8| match index {
+                0 => self.user_tokens[0],
+                index => self.user_base_tokens[index as usize - 1],
+            };
+            swap_underlying(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.pool,
+                &self.pool_authority,
+                &user_token(i),
+                &user_token(j),
+                &self.vaults,
+                &self.base_pool,
+                &self.base_pool_authority,
+                &self.base_lp_mint,
+                &self.base_vaults,
+                i,
+                j,
+                amount_in,
+                minimum_amount_out,
+            )
+        }
+    }
+
+    async fn fund(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        mint: &Pubkey,
+        account: &Pubkey,
+    ) {
+        process(
+            banks_client,
+            payer,
+            &[spl_token::instruction::mint_to(
+                &spl_token::id(),
+                mint,
+                account,
+                &payer.pubkey(),
+                &[],
+                USER_FUNDS,
+            )
+            .unwrap()],
+            &[],
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn mint_supply(banks_client: &mut BanksClient, mint: &Pubkey) -> u64 {
+        let account = banks_client.get_account(*mint).await.unwrap().unwrap();
+        spl_token::state::Mint::unpack(&account.data)
+            .unwrap()
+            .supply
+    }
+
+    async fn create_pool_account(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        program_id: &Pubkey,
+    ) -> Keypair {
+        let pool = Keypair::new();
+        let rent = Rent::default();
+        process(
+            banks_client,
+            payer,
+            &[system_instruction::create_account(
+                &payer.pubkey(),
+                &pool.pubkey(),
+                rent.minimum_balance(StableSwap::get_size()),
+                StableSwap::get_size() as u64,
+                program_id,
+            )],
+            &[&pool],
+        )
+        .await
+        .unwrap();
+        pool
+    }
+
+    /// Creates a funded 3-token base pool and an initialized metapool on top
+    /// of it, funded with `DEPOSIT` of the paired token and base LP tokens
+    async fn setup_metapool(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        program_id: Pubkey,
+    ) -> MetapoolFixture {
+        let user = Keypair::new();
+
+        // Base pool
+        let base_pool = create_pool_account(banks_client, payer, &program_id).await;
+        let (base_pool_authority, _) = find_pool_authority(&program_id, &base_pool.pubkey());
+        let mut base_mints = Vec::new();
+        let mut base_vaults = Vec::new();
+        let mut user_base_tokens = Vec::new();
+        for _ in 0..3 {
+            let mint = create_mint(banks_client, payer, &payer.pubkey(), 6).await;
+            base_vaults
+                .push(create_token_account(banks_client, payer, &mint, &base_pool_authority).await);
+            let user_token = create_token_account(banks_client, payer, &mint, &user.pubkey()).await;
+            fund(banks_client, payer, &mint, &user_token).await;
+            user_base_tokens.push(user_token);
+            base_mints.push(mint);
+        }
+        let base_lp_mint = create_mint(banks_client, payer, &base_pool_authority, 6).await;
+        let user_base_lp_token =
+            create_token_account(banks_client, payer, &base_lp_mint, &user.pubkey()).await;
+        process(
+            banks_client,
+            payer,
+            &[
+                initialize_pool(
+                    &program_id,
+                    &payer.pubkey(),
+                    &base_pool.pubkey(),
+                    &base_pool_authority,
+                    &base_lp_mint,
+                    &base_vaults,
+                    &base_mints,
+                    AMP,
+                    FEE,
+                ),
+                add_liquidity(
+                    &program_id,
+                    &user.pubkey(),
+                    &base_pool.pubkey(),
+                    &base_pool_authority,
+                    &base_lp_mint,
+                    &user_base_lp_token,
+                    &user_base_tokens,
+                    &base_vaults,
+                    vec![2 * DEPOSIT; 3],
+                    1,
+                ),
+            ],
+            &[&user],
+        )
+        .await
+        .unwrap();
+
+        // Metapool pairing a new token with the base LP token
+        let pool = create_pool_account(banks_client, payer, &program_id).await;
+        let (pool_authority, _) = find_pool_authority(&program_id, &pool.pubkey());
+        let mint = create_mint(banks_client, payer, &payer.pubkey(), 6).await;
+        let vaults = [
+            create_token_account(banks_client, payer, &mint, &pool_authority).await,
+            create_token_account(banks_client, payer, &base_lp_mint, &pool_authority).await,
+        ];
+        let lp_mint = create_mint(banks_client, payer, &pool_authority, 6).await;
+        process(
+            banks_client,
+            payer,
+            &[initialize_metapool(
+                &program_id,
+                &payer.pubkey(),
+                &pool.pubkey(),
+                &pool_authority,
+                &lp_mint,
+                &vaults[0],
+                &mint,
+                &vaults[1],
+                &base_pool.pubkey(),
+                AMP,
+                FEE,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let user_token = create_token_account(banks_client, payer, &mint, &user.pubkey()).await;
+        fund(banks_client, payer, &mint, &user_token).await;
+        let user_lp_token =
+            create_token_account(banks_client, payer, &lp_mint, &user.pubkey()).await;
+
+        let fixture = MetapoolFixture {
+            program_id,
+            base_pool: base_pool.pubkey(),
+            base_pool_authority,
+            base_lp_mint,
+            base_vaults,
+            pool: pool.pubkey(),
+            pool_authority,
+            lp_mint,
+            vaults,
+            user,
+            user_base_tokens,
+            user_tokens: [user_token, user_base_lp_token],
+            user_lp_token,
+        };
+
+        let mut deposit = add_liquidity(
+            &program_id,
+            &fixture.user.pubkey(),
+            &fixture.pool,
+            &fixture.pool_authority,
+            &fixture.lp_mint,
+            &fixture.user_lp_token,
+            &fixture.user_tokens,
+            &fixture.vaults,
+            vec![DEPOSIT; 2],
+            1,
+        );
+        deposit
+            .accounts
+            .extend(metapool_accounts(&fixture.base_pool, &fixture.base_lp_mint));
+        process(banks_client, payer, &[deposit], &[&fixture.user])
+            .await
+            .unwrap();
+        fixture
+    }
+
+    async fn now(banks_client: &mut BanksClient) -> i64 {
+        banks_client
+            .get_sysvar::<Clock>()
+            .await
+            .unwrap()
+            .unix_timestamp
+    }
+
+    async fn base_virtual_price(banks_client: &mut BanksClient, fixture: &MetapoolFixture) -> u128 {
+        let now = now(banks_client).await;
+        let base_pool = get_pool(banks_client, &fixture.base_pool).await;
+        let base_lp_supply = mint_supply(banks_client, &fixture.base_lp_mint).await;
+        virtual_price(
+            base_pool.balances(),
+            &rates(base_pool.precision_multipliers(), None).unwrap(),
+            base_pool.current_amp(now),
+            base_lp_supply,
+        )
+        .unwrap()
+    }
+
+    /// Quotes a metapool swap the way the program prices it
+    ///
+    /// Returns `(amount_out, fee_amount)`.
+    async fn quote_metapool_swap(
+        banks_client: &mut BanksClient,
+        fixture: &MetapoolFixture,
+        i: usize,
+        j: usize,
+        amount_in: u64,
+    ) -> (u64, u64) {
+        let now = now(banks_client).await;
+        let base_virtual_price = base_virtual_price(banks_client, fixture).await;
+        let pool = get_pool(banks_client, &fixture.pool).await;
+        let rates = rates(pool.precision_multipliers(), Some(base_virtual_price)).unwrap();
+        quote_swap(
+            pool.balances(),
+            &rates,
+            pool.current_amp(now),
+            pool.fee,
+            i,
+            j,
+            amount_in,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_initialize_metapool() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_metapool(&mut banks_client, &payer, program_id).await;
+
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert!(pool.is_metapool());
+        assert_eq!(pool.base_pool, fixture.base_pool);
+        assert_eq!(pool.token_mints[1], fixture.base_lp_mint);
+        assert_eq!(pool.token_vaults(), &fixture.vaults[..]);
+        assert_eq!(pool.balances(), &[DEPOSIT, DEPOSIT]);
+
+        let base_pool = get_pool(&mut banks_client, &fixture.base_pool).await;
+        assert!(!base_pool.is_metapool());
+    }
+
+    #[tokio::test]
+    async fn test_metapool_cannot_be_a_base_pool() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_metapool(&mut banks_client, &payer, program_id).await;
+
+        let pool = create_pool_account(&mut banks_client, &payer, &program_id).await;
+        let (pool_authority, _) = find_pool_authority(&program_id, &pool.pubkey());
+        let mint = create_mint(&mut banks_client, &payer, &payer.pubkey(), 6).await;
+        let token_vault =
+            create_token_account(&mut banks_client, &payer, &mint, &pool_authority).await;
+        let lp_vault =
+            create_token_account(&mut banks_client, &payer, &fixture.lp_mint, &pool_authority)
+                .await;
+        let lp_mint = create_mint(&mut banks_client, &payer, &pool_authority, 6).await;
+
+        let result = process(
+            &mut banks_client,
+            &payer,
+            &[initialize_metapool(
+                &program_id,
+                &payer.pubkey(),
+                &pool.pubkey(),
+                &pool_authority,
+                &lp_mint,
+                &token_vault,
+                &mint,
+                &lp_vault,
+                &fixture.pool,
+                AMP,
+                FEE,
+            )],
+            &[],
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err(),
+            custom_error(TemplateError::InvalidBasePool)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_swap_prices_base_lp_at_virtual_price() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_metapool(&mut banks_client, &payer, program_id).await;
+
+        // Base pool fees raise the virtual price above 1
+        for _ in 0..5 {
+            let (i, j) = (fixture.user_base_tokens[0], fixture.user_base_tokens[1]);
+            let trade = |source: &Pubkey, destination: &Pubkey, vault_in, vault_out| {
+                swap(
+                    &program_id,
+                    &fixture.user.pubkey(),
+                    &fixture.base_pool,
+                    &fixture.base_pool_authority,
+                    source,
+                    destination,
+                    &fixture.base_vaults[vault_in],
+                    &fixture.base_vaults[vault_out],
+                    DEPOSIT / 2,
+                    1,
+                )
+            };
+            process(
+                &mut banks_client,
+                &payer,
+                &[trade(&i, &j, 0, 1), trade(&j, &i, 1, 0)],
+                &[&fixture.user],
+            )
+            .await
+            .unwrap();
+        }
+
+        assert!(base_virtual_price(&mut banks_client, &fixture).await > RATE_PRECISION);
+
+        // Selling the paired token buys fewer of the now more valuable LP tokens
+        let amount_in = 1_000_000_000;
+        let (expected, fee_amount) =
+            quote_metapool_swap(&mut banks_client, &fixture, 0, 1, amount_in).await;
+        let before = token_balance(&mut banks_client, &fixture.user_tokens[1]).await;
+        process(
+            &mut banks_client,
+            &payer,
+            &[fixture.swap(0, 1, amount_in, 1)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        let received = token_balance(&mut banks_client, &fixture.user_tokens[1]).await - before;
+        assert_eq!(received, expected);
+        assert!(received < amount_in);
+
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(
+            pool.balances(),
+            &[
+                DEPOSIT + amount_in,
+                DEPOSIT - received - admin_fee(fee_amount)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_swap_requires_base_pool_accounts() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_metapool(&mut banks_client, &payer, program_id).await;
+
+        let mut instruction = fixture.swap(0, 1, 1_000_000, 1);
+        instruction.accounts.truncate(8);
+        let result = process(&mut banks_client, &payer, &[instruction], &[&fixture.user]).await;
+        assert_eq!(
+            result.unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+        );
+
+        // The base pool accounts must belong to the metapool
+        let mut instruction = fixture.swap(0, 1, 1_000_000, 1);
+        instruction.accounts[8].pubkey = fixture.pool;
+        let result = process(&mut banks_client, &payer, &[instruction], &[&fixture.user]).await;
+        assert_eq!(
+            result.unwrap_err(),
+            custom_error(TemplateError::InvalidBasePool)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_swap_underlying_to_base_token() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_metapool(&mut banks_client, &payer, program_id).await;
+
+        let amount_in = 1_000_000_000;
+        let (lp_amount, fee_amount) =
+            quote_metapool_swap(&mut banks_client, &fixture, 0, 1, amount_in).await;
+        let base_vault_before = token_balance(&mut banks_client, &fixture.base_vaults[2]).await;
+        let before = token_balance(&mut banks_client, &fixture.user_base_tokens[2]).await;
+        process(
+            &mut banks_client,
+            &payer,
+            &[fixture.swap_underlying(0, 3, amount_in, amount_in * 99 / 100)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        let received =
+            token_balance(&mut banks_client, &fixture.user_base_tokens[2]).await - before;
+        assert!(received < amount_in);
+
+        // The metapool swapped into base LP tokens and the base pool paid them out
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        assert_eq!(
+            pool.balances(),
+            &[
+                DEPOSIT + amount_in,
+                DEPOSIT - lp_amount - admin_fee(fee_amount)
+            ]
+        );
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.vaults[1]).await,
+            DEPOSIT - lp_amount
+        );
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.base_vaults[2]).await,
+            base_vault_before - received
+        );
+    }
+
+    #[tokio::test]
+    async fn test_swap_underlying_from_base_token() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_metapool(&mut banks_client, &payer, program_id).await;
+
+        let amount_in = 1_000_000_000;
+        let base_vault_before = token_balance(&mut banks_client, &fixture.base_vaults[0]).await;
+        let before = token_balance(&mut banks_client, &fixture.user_tokens[0]).await;
+        process(
+            &mut banks_client,
+            &payer,
+            &[fixture.swap_underlying(1, 0, amount_in, amount_in * 99 / 100)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        let received = token_balance(&mut banks_client, &fixture.user_tokens[0]).await - before;
+        assert!(received < amount_in);
+
+        // The deposit minted base LP tokens into the metapool's vault
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.base_vaults[0]).await,
+            base_vault_before + amount_in
+        );
+        let pool = get_pool(&mut banks_client, &fixture.pool).await;
+        let lp_vault = token_balance(&mut banks_client, &fixture.vaults[1]).await;
+        assert!(lp_vault > DEPOSIT);
+        assert_eq!(pool.balances()[1], lp_vault);
+
+        // The paired token left the vault, less the admin fee in the balance
+        assert_eq!(
+            token_balance(&mut banks_client, &fixture.vaults[0]).await,
+            DEPOSIT - received
+        );
+        assert!(pool.balances()[0] < DEPOSIT - received);
+
+        // Slippage is checked against the paired token received
+        let result = process(
+            &mut banks_client,
+            &payer,
+            &[fixture.swap_underlying(1, 0, amount_in, amount_in)],
+            &[&fixture.user],
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err(),
+            custom_error(TemplateError::SlippageExceeded)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_swap_underlying_rejects_base_to_base() {
+        let (program_test, program_id) = program_test();
+        let (mut banks_client, payer, _) = program_test.start().await;
+        let fixture = setup_metapool(&mut banks_client, &payer, program_id).await;
+
+        for (i, j) in [(1, 2), (3, 1), (0, 0)] {
+            let result = process(
+                &mut banks_client,
+                &payer,
+                &[fixture.swap_underlying(i, j, 1_000_000, 1)],
+                &[&fixture.user],
+            )
+            .await;
+            assert_eq!(
+                result.unwrap_err(),
+                custom_error(TemplateError::InvalidTokenIndex)
+            );
+        }
+    }
+}
@@ -5,62 +5,144 @@ import {
   Connection,
   Keypair,
   PublicKey,
-  SystemProgram,
   SYSVAR_RENT_PUBKEY,
   Transaction,
   sendAndConfirmTransaction,
 } from '@solana/web3.js';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
 import * as borsh from 'borsh';
 import BN from 'bn.js';
 
 // Define the program ID (replace with your actual program ID)
-const PROGRAM_ID = new PublicKey('options-protocol111111111111111111111111111111');
+const PROGRAM_ID = new PublicKey('Eopt111111111111111111111111111111111111111');
 
 // Define instruction types
 enum TemplateInstructionType {
-  Initialize = 0,
-  Operation1 = 1,
-  Operation2 = 2,
+  InitializeSeries = 0,
+  WriteOption = 1,
+  ExerciseOption = 2,
+  RedeemCollateral = 3,
+  InitializeCashSettledSeries = 4,
+  SettleSeries = 5,
+  ClaimSettlement = 6,
+  InitializePool = 7,
+  SetImpliedVolatility = 8,
+  BuyFromPool = 9,
+  SellToPool = 10,
+  WithdrawFromPool = 11,
+  InitializeOrderBook = 12,
+  PlaceOrder = 13,
+  CancelOrder = 14,
+  MatchOrders = 15,
+  ConsumeEvents = 16,
+  SettleFunds = 17,
+}
+
+// Define order sides
+enum Side {
+  Bid = 0,
+  Ask = 1,
+}
+
+// Define option kinds
+enum OptionKind {
+  Call = 0,
+  Put = 1,
 }
 
 // Define instruction schema
-class InitializeInstruction {
-  param1: BN;
-  param2: number;
-  
-  constructor(props: { param1: BN; param2: number }) {
-    this.param1 = props.param1;
-    this.param2 = props.param2;
+class InitializeSeriesInstruction {
+  kind: number;
+  strike: BN;
+  contract_size: BN;
+  expiry: BN;
+
+  constructor(props: { kind: number; strike: BN; contract_size: BN; expiry: BN }) {
+    this.kind = props.kind;
+    this.strike = props.strike;
+    this.contract_size = props.contract_size;
+    this.expiry = props.expiry;
   }
-  
+
   static schema = new Map([
     [
-      InitializeInstruction,
+      InitializeSeriesInstruction,
       {
         kind: 'struct',
         fields: [
-          ['param1', 'u64'],
-          ['param2', 'u8'],
+          ['kind', 'u8'],
+          ['strike', 'u64'],
+          ['contract_size', 'u64'],
+          ['expiry', 'i64'],
         ],
       },
     ],
   ]);
 }
 
-class Operation1Instruction {
+class AmountInstruction {
   amount: BN;
-  
+
   constructor(props: { amount: BN }) {
     this.amount = props.amount;
   }
-  
+
+  static schema = new Map([
+    [
+      AmountInstruction,
+      {
+        kind: 'struct',
+        fields: [
+          ['amount', 'u64'],
+        ],
+      },
+    ],
+  ]);
+}
+
+class BuyFromPoolInstruction {
+  amount: BN;
+  max_premium: BN;
+
+  constructor(props: { amount: BN; max_premium: BN }) {
+    this.amount = props.amount;
+    this.max_premium = props.max_premium;
+  }
+
   static schema = new Map([
     [
-      Operation1Instruction,
+      BuyFromPoolInstruction,
       {
         kind: 'struct',
         fields: [
           ['amount', 'u64'],
+          ['max_premium', 'u64'],
+        ],
+      },
+    ],
+  ]);
+}
+
+class PlaceOrderInstruction {
+  side: number;
+  price: BN;
+  quantity: BN;
+
+  constructor(props: { side: number; price: BN; quantity: BN }) {
+    this.side = props.side;
+    this.price = props.price;
+    this.quantity = props.quantity;
+  }
+
+  static schema = new Map([
+    [
+      PlaceOrderInstruction,
+      {
+        kind: 'struct',
+        fields: [
+          ['side', 'u8'],
+          ['price', 'u64'],
+          ['quantity', 'u64'],
         ],
       },
     ],
@@ -68,47 +150,255 @@ class Operation1Instruction {
 }
 
 /**
- * Initialize a new template account
+ * Derive the series authority PDA that owns the vaults and both mints
  */
-async function initialize(
+async function findSeriesAuthority(series: PublicKey): Promise<PublicKey> {
+  const [authority] = await PublicKey.findProgramAddress([series.toBuffer()], PROGRAM_ID);
+  return authority;
+}
+
+/**
+ * Initialize a series whose series account, mints and vaults already exist
+ */
+async function initializeSeries(
   connection: Connection,
-  authority: Keypair,
-  account: Keypair,
-  param1: BN,
-  param2: number
+  creator: Keypair,
+  series: PublicKey,
+  underlyingMint: PublicKey,
+  quoteMint: PublicKey,
+  optionMint: PublicKey,
+  writerMint: PublicKey,
+  underlyingVault: PublicKey,
+  quoteVault: PublicKey,
+  kind: OptionKind,
+  strike: BN,
+  contractSize: BN,
+  expiry: BN
 ) {
-  // Create initialize instruction
-  const initializeInstruction = new InitializeInstruction({
-    param1,
-    param2,
-  });
-  
-  const instructionData = Buffer.from([TemplateInstructionType.Initialize]);
-  const serializedData = borsh.serialize(
-    InitializeInstruction.schema,
-    initializeInstruction
-  );
-  
-  const fullData = Buffer.concat([instructionData, serializedData]);
-  
+  const seriesAuthority = await findSeriesAuthority(series);
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.InitializeSeries]),
+    borsh.serialize(
+      InitializeSeriesInstruction.schema,
+      new InitializeSeriesInstruction({ kind, strike, contract_size: contractSize, expiry })
+    ),
+  ]);
+
   const transaction = new Transaction().add({
     keys: [
-      { pubkey: authority.publicKey, isSigner: true, isWritable: true },
-      { pubkey: account.publicKey, isSigner: true, isWritable: true },
-      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: creator.publicKey, isSigner: true, isWritable: false },
+      { pubkey: series, isSigner: false, isWritable: true },
+      { pubkey: seriesAuthority, isSigner: false, isWritable: false },
+      { pubkey: underlyingMint, isSigner: false, isWritable: false },
+      { pubkey: quoteMint, isSigner: false, isWritable: false },
+      { pubkey: optionMint, isSigner: false, isWritable: false },
+      { pubkey: writerMint, isSigner: false, isWritable: false },
+      { pubkey: underlyingVault, isSigner: false, isWritable: false },
+      { pubkey: quoteVault, isSigner: false, isWritable: false },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
       { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
     ],
     programId: PROGRAM_ID,
-    data: fullData,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [creator]);
+
+  console.log('Series initialized successfully!');
+}
+
+/**
+ * Lock collateral from `writerCollateral` and mint options and writer tokens
+ */
+async function writeOption(
+  connection: Connection,
+  writer: Keypair,
+  series: PublicKey,
+  writerCollateral: PublicKey,
+  collateralVault: PublicKey,
+  optionMint: PublicKey,
+  writerMint: PublicKey,
+  optionDestination: PublicKey,
+  writerTokenDestination: PublicKey,
+  amount: BN
+) {
+  const seriesAuthority = await findSeriesAuthority(series);
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.WriteOption]),
+    borsh.serialize(AmountInstruction.schema, new AmountInstruction({ amount })),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: writer.publicKey, isSigner: true, isWritable: false },
+      { pubkey: series, isSigner: false, isWritable: false },
+      { pubkey: seriesAuthority, isSigner: false, isWritable: false },
+      { pubkey: writerCollateral, isSigner: false, isWritable: true },
+      { pubkey: collateralVault, isSigner: false, isWritable: true },
+      { pubkey: optionMint, isSigner: false, isWritable: true },
+      { pubkey: writerMint, isSigner: false, isWritable: true },
+      { pubkey: optionDestination, isSigner: false, isWritable: true },
+      { pubkey: writerTokenDestination, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [writer]);
+
+  console.log('Options written successfully!');
+}
+
+/**
+ * Record the settlement price of an expired cash-settled series
+ */
+async function settleSeries(
+  connection: Connection,
+  payer: Keypair,
+  series: PublicKey,
+  priceAccount: PublicKey
+) {
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: series, isSigner: false, isWritable: true },
+      { pubkey: priceAccount, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data: Buffer.from([TemplateInstructionType.SettleSeries]),
   });
-  
-  await sendAndConfirmTransaction(
-    connection,
-    transaction,
-    [authority, account]
-  );
-  
-  console.log('Account initialized successfully!');
+
+  await sendAndConfirmTransaction(connection, transaction, [payer]);
+
+  console.log('Series settled successfully!');
+}
+
+/**
+ * Buy options from a series' pool, paying at most `maxPremium` quote base units
+ */
+async function buyFromPool(
+  connection: Connection,
+  buyer: Keypair,
+  series: PublicKey,
+  priceAccount: PublicKey,
+  buyerQuote: PublicKey,
+  poolPremiumVault: PublicKey,
+  poolOptionVault: PublicKey,
+  optionDestination: PublicKey,
+  amount: BN,
+  maxPremium: BN
+) {
+  const seriesAuthority = await findSeriesAuthority(series);
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.BuyFromPool]),
+    borsh.serialize(
+      BuyFromPoolInstruction.schema,
+      new BuyFromPoolInstruction({ amount, max_premium: maxPremium })
+    ),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: buyer.publicKey, isSigner: true, isWritable: false },
+      { pubkey: series, isSigner: false, isWritable: false },
+      { pubkey: seriesAuthority, isSigner: false, isWritable: false },
+      { pubkey: priceAccount, isSigner: false, isWritable: false },
+      { pubkey: buyerQuote, isSigner: false, isWritable: true },
+      { pubkey: poolPremiumVault, isSigner: false, isWritable: true },
+      { pubkey: poolOptionVault, isSigner: false, isWritable: true },
+      { pubkey: optionDestination, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [buyer]);
+
+  console.log('Options bought from pool successfully!');
+}
+
+/**
+ * Place a limit order. `ownerTokens` and `vault` are the quote token accounts
+ * for bids and the option token accounts for asks.
+ */
+async function placeOrder(
+  connection: Connection,
+  owner: Keypair,
+  series: PublicKey,
+  orderBook: PublicKey,
+  openOrders: PublicKey,
+  ownerTokens: PublicKey,
+  vault: PublicKey,
+  side: Side,
+  price: BN,
+  quantity: BN
+) {
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.PlaceOrder]),
+    borsh.serialize(
+      PlaceOrderInstruction.schema,
+      new PlaceOrderInstruction({ side, price, quantity })
+    ),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: series, isSigner: false, isWritable: false },
+      { pubkey: orderBook, isSigner: false, isWritable: true },
+      { pubkey: openOrders, isSigner: false, isWritable: true },
+      { pubkey: ownerTokens, isSigner: false, isWritable: true },
+      { pubkey: vault, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [owner]);
+
+  console.log('Order placed successfully!');
+}
+
+/**
+ * Crank the order book: match crossing orders, then credit the fills to the
+ * open orders accounts of the traders involved
+ */
+async function crankOrderBook(
+  connection: Connection,
+  payer: Keypair,
+  series: PublicKey,
+  orderBook: PublicKey,
+  eventQueue: PublicKey,
+  openOrders: PublicKey[],
+  limit: number
+) {
+  const limitData = Buffer.alloc(2);
+  limitData.writeUInt16LE(limit);
+
+  const transaction = new Transaction()
+    .add({
+      keys: [
+        { pubkey: series, isSigner: false, isWritable: false },
+        { pubkey: orderBook, isSigner: false, isWritable: true },
+        { pubkey: eventQueue, isSigner: false, isWritable: true },
+      ],
+      programId: PROGRAM_ID,
+      data: Buffer.concat([Buffer.from([TemplateInstructionType.MatchOrders]), limitData]),
+    })
+    .add({
+      keys: [
+        { pubkey: eventQueue, isSigner: false, isWritable: true },
+        ...openOrders.map(pubkey => ({ pubkey, isSigner: false, isWritable: true })),
+      ],
+      programId: PROGRAM_ID,
+      data: Buffer.concat([Buffer.from([TemplateInstructionType.ConsumeEvents]), limitData]),
+    });
+
+  await sendAndConfirmTransaction(connection, transaction, [payer]);
+
+  console.log('Order book cranked successfully!');
 }
 
 /**
@@ -117,25 +407,17 @@ async function initialize(
 async function main() {
   // Connect to devnet
   const connection = new Connection('https://api.devnet.solana.com', 'confirmed');
-  
+
   // Generate keypairs for testing
-  const authority = Keypair.generate();
-  const account = Keypair.generate();
-  
-  console.log('Requesting airdrop for authority...');
-  const airdropSignature = await connection.requestAirdrop(authority.publicKey, 1000000000);
+  const creator = Keypair.generate();
+
+  console.log('Requesting airdrop for creator...');
+  const airdropSignature = await connection.requestAirdrop(creator.publicKey, 1000000000);
   await connection.confirmTransaction(airdropSignature);
-  
-  // Initialize with example values
-  await initialize(
-    connection,
-    authority,
-    account,
-    new BN(100),
-    5
-  );
-  
-  // TODO: Add examples for other operations
+
+  // TODO: Create the series account, the option and writer mints and the
+  // vaults (all controlled by the series authority), then call
+  // initializeSeries and writeOption
 }
 
 main().then(
@@ -4,27 +4,163 @@ use solana_program::program_error::ProgramError;
 use thiserror::Error;
 
 /// Errors that may be returned by the options-protocol program
-#[derive(Error, Debug, Copy, Clone)]
+#[derive(Error, Debug, Copy, Clone, PartialEq)]
 pub enum TemplateError {
     /// Invalid instruction
     #[error("Invalid instruction")]
     InvalidInstruction,
-    
+
     /// Not rent exempt
     #[error("Not rent exempt")]
     NotRentExempt,
-    
+
     /// Expected amount mismatch
     #[error("Expected amount mismatch")]
     ExpectedAmountMismatch,
-    
+
     /// Invalid authority
     #[error("Invalid authority")]
     InvalidAuthority,
-    
+
     /// Math operation overflow
     #[error("Math operation overflow")]
     MathOverflow,
+
+    /// Series already initialized
+    #[error("Series already initialized")]
+    SeriesAlreadyInitialized,
+
+    /// Series not initialized
+    #[error("Series not initialized")]
+    SeriesNotInitialized,
+
+    /// Invalid token program
+    #[error("Invalid token program")]
+    InvalidTokenProgram,
+
+    /// Series authority does not match the derived address
+    #[error("Invalid series authority")]
+    InvalidSeriesAuthority,
+
+    /// Vault does not belong to the series or is misconfigured
+    #[error("Invalid vault")]
+    InvalidVault,
+
+    /// Option or writer mint does not belong to the series or is misconfigured
+    #[error("Invalid option mint")]
+    InvalidOptionMint,
+
+    /// Underlying and quote tokens use the same mint
+    #[error("Underlying and quote mints must differ")]
+    RepeatedMint,
+
+    /// Strike or contract size is zero
+    #[error("Invalid series terms")]
+    InvalidTerms,
+
+    /// Expiry is not in the future
+    #[error("Invalid expiry")]
+    InvalidExpiry,
+
+    /// Amount must be greater than zero
+    #[error("Amount must be greater than zero")]
+    ZeroAmount,
+
+    /// Series has expired
+    #[error("Series has expired")]
+    SeriesExpired,
+
+    /// Series has not expired yet
+    #[error("Series has not expired")]
+    SeriesNotExpired,
+
+    /// Exercise outside the window before expiry
+    #[error("Outside the exercise window")]
+    OutsideExerciseWindow,
+
+    /// Instruction does not apply to the series settlement type
+    #[error("Invalid settlement type")]
+    InvalidSettlementType,
+
+    /// Price account is not the configured one or cannot be parsed
+    #[error("Invalid price account")]
+    InvalidPriceAccount,
+
+    /// Oracle price is not positive or not currently trading
+    #[error("Invalid oracle price")]
+    InvalidOraclePrice,
+
+    /// Oracle price was published before expiry
+    #[error("Oracle price published before expiry")]
+    PriceBeforeExpiry,
+
+    /// Settlement price has already been recorded
+    #[error("Series already settled")]
+    SeriesAlreadySettled,
+
+    /// Settlement price has not been recorded yet
+    #[error("Series not settled")]
+    SeriesNotSettled,
+
+    /// Pricing inputs are out of range
+    #[error("Invalid pricing input")]
+    InvalidPricingInput,
+
+    /// Series already has an option pool
+    #[error("Pool already initialized")]
+    PoolAlreadyInitialized,
+
+    /// Series has no option pool
+    #[error("Pool not initialized")]
+    PoolNotInitialized,
+
+    /// Implied volatility or spread is out of range
+    #[error("Invalid pool parameters")]
+    InvalidPoolParameters,
+
+    /// Oracle price is too old to trade against
+    #[error("Stale oracle price")]
+    StalePrice,
+
+    /// Premium is worse than the caller's limit
+    #[error("Slippage limit exceeded")]
+    SlippageExceeded,
+
+    /// Order book or event queue has already been initialized
+    #[error("Order book already initialized")]
+    OrderBookAlreadyInitialized,
+
+    /// Order book or event queue does not match
+    #[error("Invalid order book")]
+    InvalidOrderBook,
+
+    /// Order book authority does not match the derived PDA
+    #[error("Invalid order book authority")]
+    InvalidOrderBookAuthority,
+
+    /// Order price or quantity is zero
+    #[error("Invalid order")]
+    InvalidOrder,
+
+    /// No room for another order on this side of the book
+    #[error("Order book full")]
+    OrderBookFull,
+
+    /// No order with this id on this side of the book
+    #[error("Order not found")]
+    OrderNotFound,
+
+    /// No room for another fill in the event queue
+    #[error("Event queue full")]
+    EventQueueFull,
+
+    /// Open orders account does not belong to the order book or the owner
+    #[error("Invalid open orders account")]
+    InvalidOpenOrders,
+
+    /// Price was published after the settlement window closed
+    #[error("Price after settlement window")]
+    PriceAfterSettlementWindow,
 }
 
 impl From<TemplateError> for ProgramError {
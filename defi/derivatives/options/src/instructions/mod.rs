@@ -6,63 +6,719 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
-    system_program, sysvar,
+    sysvar,
 };
 
+use crate::{order_book::Side, state::OptionKind};
+
 /// Instructions supported by the options-protocol program
 #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
 pub enum TemplateInstruction {
-    /// Initialize a new instance
+    /// Initialize a new option series
+    ///
+    /// The vaults must be empty token accounts of the underlying and quote
+    /// mints owned by the series authority. The option and writer mints must
+    /// have zero supply, the series authority as mint authority and no freeze
+    /// authority. The optional price account is the one an option pool on
+    /// the series will read.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The series creator
+    /// 1. `[writable]` The series account, owned by this program
+    /// 2. `[]` The series authority PDA
+    /// 3. `[]` The underlying mint
+    /// 4. `[]` The quote mint
+    /// 5. `[]` The option mint
+    /// 6. `[]` The writer mint
+    /// 7. `[]` The underlying vault
+    /// 8. `[]` The quote vault
+    /// 9. `[]` The token program
+    /// 10. `[]` The rent sysvar
+    /// 11. `[]` (Optional) The price account of the underlying
+    ///
+    InitializeSeries {
+        /// Call or put
+        kind: OptionKind,
+        /// Quote tokens exchanged for one option's underlying on exercise
+        strike: u64,
+        /// Underlying tokens covered by one option
+        contract_size: u64,
+        /// Unix timestamp the series expires at
+        expiry: i64,
+    },
+
+    /// Lock collateral and mint option and writer tokens
+    ///
+    /// Calls lock `contract_size` of the underlying per option and puts lock
+    /// `strike` of the quote token. Only possible before expiry.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The writer
+    /// 1. `[]` The series account
+    /// 2. `[]` The series authority PDA
+    /// 3. `[writable]` The writer's collateral token account
+    /// 4. `[writable]` The collateral vault
+    /// 5. `[writable]` The option mint
+    /// 6. `[writable]` The writer mint
+    /// 7. `[writable]` The token account to receive the option tokens
+    /// 8. `[writable]` The token account to receive the writer tokens
+    /// 9. `[]` The token program
+    ///
+    WriteOption {
+        /// Number of options to write
+        amount: u64,
+    },
+
+    /// Burn option tokens to exchange the payment for the collateral at the strike
+    ///
+    /// Call holders pay `strike` of the quote token per option for
+    /// `contract_size` of the underlying; put holders pay `contract_size` of
+    /// the underlying for `strike` of the quote token. Only possible in the
+    /// `EXERCISE_WINDOW` before expiry.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The holder
+    /// 1. `[]` The series account
+    /// 2. `[]` The series authority PDA
+    /// 3. `[writable]` The option mint
+    /// 4. `[writable]` The holder's option token account
+    /// 5. `[writable]` The holder's payment token account
+    /// 6. `[writable]` The payment vault
+    /// 7. `[writable]` The collateral vault
+    /// 8. `[writable]` The token account to receive the collateral
+    /// 9. `[]` The token program
+    ///
+    ExerciseOption {
+        /// Number of options to exercise
+        amount: u64,
+    },
+
+    /// Burn writer tokens for a pro-rata share of both vaults after expiry
+    ///
+    /// Writers receive the unexercised collateral and the exercise payments
+    /// in proportion to the writer tokens burned. Writers of a cash-settled
+    /// series can only redeem once it is settled and receive `max_payoff`
+    /// less the payoff per writer token, all in the quote token.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The writer
+    /// 1. `[]` The series account
+    /// 2. `[]` The series authority PDA
+    /// 3. `[writable]` The writer mint
+    /// 4. `[writable]` The writer's writer token account
+    /// 5. `[writable]` The underlying vault
+    /// 6. `[writable]` The quote vault
+    /// 7. `[writable]` The token account to receive the underlying
+    /// 8. `[writable]` The token account to receive the quote token
+    /// 9. `[]` The token program
+    ///
+    RedeemCollateral {
+        /// Number of writer tokens to burn
+        amount: u64,
+    },
+
+    /// Initialize a new cash-settled option series
+    ///
+    /// Like `InitializeSeries`, but options are settled in the quote token
+    /// at a price read from the price account after expiry. Writers lock
+    /// `max_payoff` of the quote token per option, which caps the payout.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The series creator
+    /// 1. `[writable]` The series account, owned by this program
+    /// 2. `[]` The series authority PDA
+    /// 3. `[]` The underlying mint
+    /// 4. `[]` The quote mint
+    /// 5. `[]` The option mint
+    /// 6. `[]` The writer mint
+    /// 7. `[]` The underlying vault
+    /// 8. `[]` The quote vault
+    /// 9. `[]` The token program
+    /// 10. `[]` The rent sysvar
+    /// 11. `[]` The price account
+    ///
+    InitializeCashSettledSeries {
+        /// Call or put
+        kind: OptionKind,
+        /// Quote tokens `contract_size` of the underlying is struck at
+        strike: u64,
+        /// Underlying tokens covered by one option
+        contract_size: u64,
+        /// Unix timestamp the series expires at
+        expiry: i64,
+        /// Quote tokens locked per option and the most one option pays out
+        max_payoff: u64,
+    },
+
+    /// Record the settlement price of a cash-settled series after expiry
+    ///
+    /// Anyone can settle, once, with a price published at or after expiry and
+    /// at most `MAX_SETTLEMENT_DELAY` after it.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The series account
+    /// 1. `[]` The price account
+    ///
+    SettleSeries,
+
+    /// Burn option tokens of a settled series for their payoff in the quote token
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The holder
+    /// 1. `[]` The series account
+    /// 2. `[]` The series authority PDA
+    /// 3. `[writable]` The option mint
+    /// 4. `[writable]` The holder's option token account
+    /// 5. `[writable]` The quote vault
+    /// 6. `[writable]` The token account to receive the payoff
+    /// 7. `[]` The token program
+    ///
+    ClaimSettlement {
+        /// Number of options to burn
+        amount: u64,
+    },
+
+    /// Open an option pool on a physically settled series
+    ///
+    /// Only the series creator can open the pool, and becomes its admin. The
+    /// price account must be the one bound when the series was initialized.
+    /// The vaults must be empty token accounts of the option mint and the
+    /// quote mint owned by the series authority. The admin funds the pool by
+    /// transferring options and quote tokens into them.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The series creator, who becomes the pool admin
+    /// 1. `[writable]` The series account
+    /// 2. `[]` The series authority PDA
+    /// 3. `[]` The pool option vault
+    /// 4. `[]` The pool premium vault
+    /// 5. `[]` The price account of the underlying
+    ///
+    InitializePool {
+        /// Annualized implied volatility, in `WAD` units
+        implied_volatility: u64,
+        /// Spread on each side of the model price, in basis points
+        spread_bps: u64,
+    },
+
+    /// Update the implied volatility the pool prices options at
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The pool admin
+    /// 1. `[writable]` The series account
+    ///
+    SetImpliedVolatility {
+        /// Annualized implied volatility, in `WAD` units
+        implied_volatility: u64,
+    },
+
+    /// Buy options from the pool at the model price plus the spread
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to initialize
-    /// 2. `[]` The system program
-    /// 3. `[]` The rent sysvar
+    /// 0. `[signer]` The buyer
+    /// 1. `[]` The series account
+    /// 2. `[]` The series authority PDA
+    /// 3. `[]` The price account
+    /// 4. `[writable]` The buyer's quote token account
+    /// 5. `[writable]` The pool premium vault
+    /// 6. `[writable]` The pool option vault
+    /// 7. `[writable]` The token account to receive the options
+    /// 8. `[]` The token program
     ///
-    Initialize {
-        /// Custom parameter 1
-        param1: u64,
-        /// Custom parameter 2
-        param2: u8,
+    BuyFromPool {
+        /// Number of options to buy
+        amount: u64,
+        /// Most quote tokens to pay in total
+        max_premium: u64,
     },
 
-    /// Perform operation 1
+    /// Sell options to the pool at the model price minus the spread
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to modify
+    /// 0. `[signer]` The seller
+    /// 1. `[]` The series account
+    /// 2. `[]` The series authority PDA
+    /// 3. `[]` The price account
+    /// 4. `[writable]` The seller's option token account
+    /// 5. `[writable]` The pool option vault
+    /// 6. `[writable]` The pool premium vault
+    /// 7. `[writable]` The token account to receive the premium
+    /// 8. `[]` The token program
     ///
-    Operation1 {
-        /// Operation amount
+    SellToPool {
+        /// Number of options to sell
         amount: u64,
+        /// Fewest quote tokens to receive in total
+        min_premium: u64,
+    },
+
+    /// Withdraw options and quote tokens from the pool
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The pool admin
+    /// 1. `[]` The series account
+    /// 2. `[]` The series authority PDA
+    /// 3. `[writable]` The pool option vault
+    /// 4. `[writable]` The pool premium vault
+    /// 5. `[writable]` The token account to receive the options
+    /// 6. `[writable]` The token account to receive the quote tokens
+    /// 7. `[]` The token program
+    ///
+    WithdrawFromPool {
+        /// Options to withdraw
+        option_amount: u64,
+        /// Quote tokens to withdraw
+        premium_amount: u64,
+    },
+
+    /// Initialize a limit order book for a series' options
+    ///
+    /// The order book and event queue accounts must be owned by this program
+    /// and sized for full slabs. The vaults must be empty token accounts of
+    /// the option mint and the quote mint owned by the order book authority.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The series account
+    /// 1. `[writable]` The order book account, owned by this program
+    /// 2. `[writable]` The event queue account, owned by this program
+    /// 3. `[]` The order book authority PDA
+    /// 4. `[]` The option vault
+    /// 5. `[]` The quote vault
+    /// 6. `[]` The rent sysvar
+    ///
+    InitializeOrderBook,
+
+    /// Place a limit order, locking quote tokens for a bid or options for an ask
+    ///
+    /// The open orders account must be owned by this program. It is
+    /// initialized for the owner on first use. When the side is full, the
+    /// order must be priced better than the worst resting order, which is
+    /// evicted and credited to its open orders account.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The owner
+    /// 1. `[]` The series account
+    /// 2. `[writable]` The order book account
+    /// 3. `[writable]` The owner's open orders account
+    /// 4. `[writable]` The owner's quote token account for bids, or option token account for asks
+    /// 5. `[writable]` The order book quote vault for bids, or option vault for asks
+    /// 6. `[]` The token program
+    /// 7. `[writable]` (Optional) The open orders account of the worst order on a full side
+    ///
+    PlaceOrder {
+        /// Bid or ask
+        side: Side,
+        /// Quote tokens per option
+        price: u64,
+        /// Number of options
+        quantity: u64,
+    },
+
+    /// Cancel a resting order, crediting its locked tokens to the open orders account
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The owner
+    /// 1. `[writable]` The order book account
+    /// 2. `[writable]` The owner's open orders account
+    ///
+    CancelOrder {
+        /// Side the order rests on
+        side: Side,
+        /// Id of the order
+        order_id: u64,
+    },
+
+    /// Cross the best bids and asks into fill events
+    ///
+    /// Anyone can crank matching. Each fill trades at the price of the older
+    /// order.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` The series account
+    /// 1. `[writable]` The order book account
+    /// 2. `[writable]` The event queue account
+    ///
+    MatchOrders {
+        /// Most fills to make
+        limit: u16,
+    },
+
+    /// Credit fills from the front of the event queue to both traders
+    ///
+    /// Anyone can crank the queue. It stops at the first event whose open
+    /// orders accounts were not passed.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The event queue account
+    /// 1.. `[writable]` The open orders accounts of the traders in the queue
+    ///
+    ConsumeEvents {
+        /// Most events to consume
+        limit: u16,
     },
 
-    /// Perform operation 2
+    /// Withdraw the free balances of an open orders account
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to modify
+    /// 0. `[signer]` The owner
+    /// 1. `[]` The order book account
+    /// 2. `[writable]` The owner's open orders account
+    /// 3. `[]` The order book authority PDA
+    /// 4. `[writable]` The option vault
+    /// 5. `[writable]` The quote vault
+    /// 6. `[writable]` The token account to receive the options
+    /// 7. `[writable]` The token account to receive the quote tokens
+    /// 8. `[]` The token program
     ///
-    Operation2,
+    SettleFunds,
+}
+
+/// Creates an InitializeSeries instruction
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_series(
+    program_id: &Pubkey,
+    creator: &Pubkey,
+    series: &Pubkey,
+    series_authority: &Pubkey,
+    underlying_mint: &Pubkey,
+    quote_mint: &Pubkey,
+    option_mint: &Pubkey,
+    writer_mint: &Pubkey,
+    underlying_vault: &Pubkey,
+    quote_vault: &Pubkey,
+    price_account: Option<&Pubkey>,
+    kind: OptionKind,
+    strike: u64,
+    contract_size: u64,
+    expiry: i64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*creator, true),
+        AccountMeta::new(*series, false),
+        AccountMeta::new_readonly(*series_authority, false),
+        AccountMeta::new_readonly(*underlying_mint, false),
+        AccountMeta::new_readonly(*quote_mint, false),
+        AccountMeta::new_readonly(*option_mint, false),
+        AccountMeta::new_readonly(*writer_mint, false),
+        AccountMeta::new_readonly(*underlying_vault, false),
+        AccountMeta::new_readonly(*quote_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    if let Some(price_account) = price_account {
+        accounts.push(AccountMeta::new_readonly(*price_account, false));
+    }
+
+    let data = TemplateInstruction::InitializeSeries {
+        kind,
+        strike,
+        contract_size,
+        expiry,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a WriteOption instruction
+#[allow(clippy::too_many_arguments)]
+pub fn write_option(
+    program_id: &Pubkey,
+    writer: &Pubkey,
+    series: &Pubkey,
+    series_authority: &Pubkey,
+    writer_collateral: &Pubkey,
+    collateral_vault: &Pubkey,
+    option_mint: &Pubkey,
+    writer_mint: &Pubkey,
+    option_destination: &Pubkey,
+    writer_token_destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*writer, true),
+        AccountMeta::new_readonly(*series, false),
+        AccountMeta::new_readonly(*series_authority, false),
+        AccountMeta::new(*writer_collateral, false),
+        AccountMeta::new(*collateral_vault, false),
+        AccountMeta::new(*option_mint, false),
+        AccountMeta::new(*writer_mint, false),
+        AccountMeta::new(*option_destination, false),
+        AccountMeta::new(*writer_token_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::WriteOption { amount };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an ExerciseOption instruction
+#[allow(clippy::too_many_arguments)]
+pub fn exercise_option(
+    program_id: &Pubkey,
+    holder: &Pubkey,
+    series: &Pubkey,
+    series_authority: &Pubkey,
+    option_mint: &Pubkey,
+    holder_options: &Pubkey,
+    holder_payment: &Pubkey,
+    payment_vault: &Pubkey,
+    collateral_vault: &Pubkey,
+    collateral_destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*holder, true),
+        AccountMeta::new_readonly(*series, false),
+        AccountMeta::new_readonly(*series_authority, false),
+        AccountMeta::new(*option_mint, false),
+        AccountMeta::new(*holder_options, false),
+        AccountMeta::new(*holder_payment, false),
+        AccountMeta::new(*payment_vault, false),
+        AccountMeta::new(*collateral_vault, false),
+        AccountMeta::new(*collateral_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::ExerciseOption { amount };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a RedeemCollateral instruction
+#[allow(clippy::too_many_arguments)]
+pub fn redeem_collateral(
+    program_id: &Pubkey,
+    writer: &Pubkey,
+    series: &Pubkey,
+    series_authority: &Pubkey,
+    writer_mint: &Pubkey,
+    writer_tokens: &Pubkey,
+    underlying_vault: &Pubkey,
+    quote_vault: &Pubkey,
+    underlying_destination: &Pubkey,
+    quote_destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*writer, true),
+        AccountMeta::new_readonly(*series, false),
+        AccountMeta::new_readonly(*series_authority, false),
+        AccountMeta::new(*writer_mint, false),
+        AccountMeta::new(*writer_tokens, false),
+        AccountMeta::new(*underlying_vault, false),
+        AccountMeta::new(*quote_vault, false),
+        AccountMeta::new(*underlying_destination, false),
+        AccountMeta::new(*quote_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::RedeemCollateral { amount };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
 }
 
-/// Creates an Initialize instruction
-pub fn initialize(
+/// Creates an InitializeCashSettledSeries instruction
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_cash_settled_series(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
-    param1: u64,
-    param2: u8,
+    creator: &Pubkey,
+    series: &Pubkey,
+    series_authority: &Pubkey,
+    underlying_mint: &Pubkey,
+    quote_mint: &Pubkey,
+    option_mint: &Pubkey,
+    writer_mint: &Pubkey,
+    underlying_vault: &Pubkey,
+    quote_vault: &Pubkey,
+    price_account: &Pubkey,
+    kind: OptionKind,
+    strike: u64,
+    contract_size: u64,
+    expiry: i64,
+    max_payoff: u64,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
-        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*creator, true),
+        AccountMeta::new(*series, false),
+        AccountMeta::new_readonly(*series_authority, false),
+        AccountMeta::new_readonly(*underlying_mint, false),
+        AccountMeta::new_readonly(*quote_mint, false),
+        AccountMeta::new_readonly(*option_mint, false),
+        AccountMeta::new_readonly(*writer_mint, false),
+        AccountMeta::new_readonly(*underlying_vault, false),
+        AccountMeta::new_readonly(*quote_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
         AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*price_account, false),
+    ];
+
+    let data = TemplateInstruction::InitializeCashSettledSeries {
+        kind,
+        strike,
+        contract_size,
+        expiry,
+        max_payoff,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a SettleSeries instruction
+pub fn settle_series(program_id: &Pubkey, series: &Pubkey, price_account: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*series, false),
+        AccountMeta::new_readonly(*price_account, false),
+    ];
+
+    let data = TemplateInstruction::SettleSeries;
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a ClaimSettlement instruction
+#[allow(clippy::too_many_arguments)]
+pub fn claim_settlement(
+    program_id: &Pubkey,
+    holder: &Pubkey,
+    series: &Pubkey,
+    series_authority: &Pubkey,
+    option_mint: &Pubkey,
+    holder_options: &Pubkey,
+    quote_vault: &Pubkey,
+    quote_destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*holder, true),
+        AccountMeta::new_readonly(*series, false),
+        AccountMeta::new_readonly(*series_authority, false),
+        AccountMeta::new(*option_mint, false),
+        AccountMeta::new(*holder_options, false),
+        AccountMeta::new(*quote_vault, false),
+        AccountMeta::new(*quote_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::ClaimSettlement { amount };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an InitializePool instruction
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_pool(
+    program_id: &Pubkey,
+    creator: &Pubkey,
+    series: &Pubkey,
+    series_authority: &Pubkey,
+    pool_option_vault: &Pubkey,
+    pool_premium_vault: &Pubkey,
+    price_account: &Pubkey,
+    implied_volatility: u64,
+    spread_bps: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*creator, true),
+        AccountMeta::new(*series, false),
+        AccountMeta::new_readonly(*series_authority, false),
+        AccountMeta::new_readonly(*pool_option_vault, false),
+        AccountMeta::new_readonly(*pool_premium_vault, false),
+        AccountMeta::new_readonly(*price_account, false),
+    ];
+
+    let data = TemplateInstruction::InitializePool {
+        implied_volatility,
+        spread_bps,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a SetImpliedVolatility instruction
+pub fn set_implied_volatility(
+    program_id: &Pubkey,
+    pool_admin: &Pubkey,
+    series: &Pubkey,
+    implied_volatility: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool_admin, true),
+        AccountMeta::new(*series, false),
+    ];
+
+    let data = TemplateInstruction::SetImpliedVolatility { implied_volatility };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a BuyFromPool instruction
+#[allow(clippy::too_many_arguments)]
+pub fn buy_from_pool(
+    program_id: &Pubkey,
+    buyer: &Pubkey,
+    series: &Pubkey,
+    series_authority: &Pubkey,
+    price_account: &Pubkey,
+    buyer_quote: &Pubkey,
+    pool_premium_vault: &Pubkey,
+    pool_option_vault: &Pubkey,
+    option_destination: &Pubkey,
+    amount: u64,
+    max_premium: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*buyer, true),
+        AccountMeta::new_readonly(*series, false),
+        AccountMeta::new_readonly(*series_authority, false),
+        AccountMeta::new_readonly(*price_account, false),
+        AccountMeta::new(*buyer_quote, false),
+        AccountMeta::new(*pool_premium_vault, false),
+        AccountMeta::new(*pool_option_vault, false),
+        AccountMeta::new(*option_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
     ];
 
-    let data = TemplateInstruction::Initialize { param1, param2 };
+    let data = TemplateInstruction::BuyFromPool {
+        amount,
+        max_premium,
+    };
 
     Instruction {
         program_id: *program_id,
@@ -71,19 +727,217 @@ pub fn initialize(
     }
 }
 
-/// Creates an Operation1 instruction
-pub fn operation1(
+/// Creates a SellToPool instruction
+#[allow(clippy::too_many_arguments)]
+pub fn sell_to_pool(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
+    seller: &Pubkey,
+    series: &Pubkey,
+    series_authority: &Pubkey,
+    price_account: &Pubkey,
+    seller_options: &Pubkey,
+    pool_option_vault: &Pubkey,
+    pool_premium_vault: &Pubkey,
+    quote_destination: &Pubkey,
     amount: u64,
+    min_premium: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*seller, true),
+        AccountMeta::new_readonly(*series, false),
+        AccountMeta::new_readonly(*series_authority, false),
+        AccountMeta::new_readonly(*price_account, false),
+        AccountMeta::new(*seller_options, false),
+        AccountMeta::new(*pool_option_vault, false),
+        AccountMeta::new(*pool_premium_vault, false),
+        AccountMeta::new(*quote_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::SellToPool {
+        amount,
+        min_premium,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a WithdrawFromPool instruction
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_from_pool(
+    program_id: &Pubkey,
+    pool_admin: &Pubkey,
+    series: &Pubkey,
+    series_authority: &Pubkey,
+    pool_option_vault: &Pubkey,
+    pool_premium_vault: &Pubkey,
+    option_destination: &Pubkey,
+    quote_destination: &Pubkey,
+    option_amount: u64,
+    premium_amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool_admin, true),
+        AccountMeta::new_readonly(*series, false),
+        AccountMeta::new_readonly(*series_authority, false),
+        AccountMeta::new(*pool_option_vault, false),
+        AccountMeta::new(*pool_premium_vault, false),
+        AccountMeta::new(*option_destination, false),
+        AccountMeta::new(*quote_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::WithdrawFromPool {
+        option_amount,
+        premium_amount,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an InitializeOrderBook instruction
+pub fn initialize_order_book(
+    program_id: &Pubkey,
+    series: &Pubkey,
+    order_book: &Pubkey,
+    event_queue: &Pubkey,
+    order_book_authority: &Pubkey,
+    option_vault: &Pubkey,
+    quote_vault: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*series, false),
+        AccountMeta::new(*order_book, false),
+        AccountMeta::new(*event_queue, false),
+        AccountMeta::new_readonly(*order_book_authority, false),
+        AccountMeta::new_readonly(*option_vault, false),
+        AccountMeta::new_readonly(*quote_vault, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    let data = TemplateInstruction::InitializeOrderBook;
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a PlaceOrder instruction
+///
+/// `owner_tokens` and `vault` are the quote token accounts for bids and the
+/// option token accounts for asks.
+#[allow(clippy::too_many_arguments)]
+pub fn place_order(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    series: &Pubkey,
+    order_book: &Pubkey,
+    open_orders: &Pubkey,
+    owner_tokens: &Pubkey,
+    vault: &Pubkey,
+    evicted_open_orders: Option<&Pubkey>,
+    side: Side,
+    price: u64,
+    quantity: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(*series, false),
+        AccountMeta::new(*order_book, false),
+        AccountMeta::new(*open_orders, false),
+        AccountMeta::new(*owner_tokens, false),
+        AccountMeta::new(*vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if let Some(evicted_open_orders) = evicted_open_orders {
+        accounts.push(AccountMeta::new(*evicted_open_orders, false));
+    }
+
+    let data = TemplateInstruction::PlaceOrder {
+        side,
+        price,
+        quantity,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a CancelOrder instruction
+pub fn cancel_order(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    order_book: &Pubkey,
+    open_orders: &Pubkey,
+    side: Side,
+    order_id: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*order_book, false),
+        AccountMeta::new(*open_orders, false),
+    ];
+
+    let data = TemplateInstruction::CancelOrder { side, order_id };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a MatchOrders instruction
+pub fn match_orders(
+    program_id: &Pubkey,
+    series: &Pubkey,
+    order_book: &Pubkey,
+    event_queue: &Pubkey,
+    limit: u16,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*series, false),
+        AccountMeta::new(*order_book, false),
+        AccountMeta::new(*event_queue, false),
     ];
 
-    let data = TemplateInstruction::Operation1 { amount };
+    let data = TemplateInstruction::MatchOrders { limit };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a ConsumeEvents instruction
+pub fn consume_events(
+    program_id: &Pubkey,
+    event_queue: &Pubkey,
+    open_orders: &[Pubkey],
+    limit: u16,
+) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*event_queue, false)];
+    accounts.extend(
+        open_orders
+            .iter()
+            .map(|open_orders| AccountMeta::new(*open_orders, false)),
+    );
+
+    let data = TemplateInstruction::ConsumeEvents { limit };
 
     Instruction {
         program_id: *program_id,
@@ -92,18 +946,32 @@ pub fn operation1(
     }
 }
 
-/// Creates an Operation2 instruction
-pub fn operation2(
+/// Creates a SettleFunds instruction
+#[allow(clippy::too_many_arguments)]
+pub fn settle_funds(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
+    owner: &Pubkey,
+    order_book: &Pubkey,
+    open_orders: &Pubkey,
+    order_book_authority: &Pubkey,
+    option_vault: &Pubkey,
+    quote_vault: &Pubkey,
+    option_destination: &Pubkey,
+    quote_destination: &Pubkey,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(*order_book, false),
+        AccountMeta::new(*open_orders, false),
+        AccountMeta::new_readonly(*order_book_authority, false),
+        AccountMeta::new(*option_vault, false),
+        AccountMeta::new(*quote_vault, false),
+        AccountMeta::new(*option_destination, false),
+        AccountMeta::new(*quote_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
     ];
 
-    let data = TemplateInstruction::Operation2;
+    let data = TemplateInstruction::SettleFunds;
 
     Instruction {
         program_id: *program_id,
@@ -3,9 +3,13 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
     pubkey::Pubkey,
     rent::Rent,
     sysvar::Sysvar,
@@ -14,154 +18,1648 @@ use solana_program::{
 use crate::{
     errors::TemplateError,
     instructions::TemplateInstruction,
-    state::TemplateAccount,
+    oracle::{PriceReader, PythPriceReader},
+    order_book::{EventQueue, OpenOrders, OrderBook, Side, MAX_EVENTS},
+    state::{
+        OptionKind, OptionSeries, Settlement, BPS_DENOMINATOR, MAX_SETTLEMENT_DELAY,
+        POOL_PRICE_MAX_AGE,
+    },
+    utils::{
+        check_account_owner, check_order_book_authority, check_series_authority, check_signer,
+        find_order_book_authority, find_series_authority, safe_multiplication,
+    },
 };
 
-/// Processes an instruction
+/// Processes an instruction, reading settlement prices from Pyth price accounts
 pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     instruction_data: &[u8],
+) -> ProgramResult {
+    process_instruction_with_price_reader::<PythPriceReader>(program_id, accounts, instruction_data)
+}
+
+/// Processes an instruction, reading settlement prices with `R`
+pub fn process_instruction_with_price_reader<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
 ) -> ProgramResult {
     let instruction = TemplateInstruction::try_from_slice(instruction_data)
         .map_err(|_| ProgramError::InvalidInstructionData)?;
 
     match instruction {
-        TemplateInstruction::Initialize { param1, param2 } => {
-            msg!("Instruction: Initialize");
-            process_initialize(program_id, accounts, param1, param2)
+        TemplateInstruction::InitializeSeries {
+            kind,
+            strike,
+            contract_size,
+            expiry,
+        } => {
+            msg!("Instruction: Initialize Series");
+            process_initialize_series::<R>(
+                program_id,
+                accounts,
+                kind,
+                strike,
+                contract_size,
+                expiry,
+                None,
+            )
+        }
+        TemplateInstruction::WriteOption { amount } => {
+            msg!("Instruction: Write Option");
+            process_write_option(program_id, accounts, amount)
+        }
+        TemplateInstruction::ExerciseOption { amount } => {
+            msg!("Instruction: Exercise Option");
+            process_exercise_option(program_id, accounts, amount)
+        }
+        TemplateInstruction::RedeemCollateral { amount } => {
+            msg!("Instruction: Redeem Collateral");
+            process_redeem_collateral(program_id, accounts, amount)
+        }
+        TemplateInstruction::InitializeCashSettledSeries {
+            kind,
+            strike,
+            contract_size,
+            expiry,
+            max_payoff,
+        } => {
+            msg!("Instruction: Initialize Cash Settled Series");
+            process_initialize_series::<R>(
+                program_id,
+                accounts,
+                kind,
+                strike,
+                contract_size,
+                expiry,
+                Some(max_payoff),
+            )
+        }
+        TemplateInstruction::SettleSeries => {
+            msg!("Instruction: Settle Series");
+            process_settle_series::<R>(program_id, accounts)
+        }
+        TemplateInstruction::ClaimSettlement { amount } => {
+            msg!("Instruction: Claim Settlement");
+            process_claim_settlement(program_id, accounts, amount)
+        }
+        TemplateInstruction::InitializePool {
+            implied_volatility,
+            spread_bps,
+        } => {
+            msg!("Instruction: Initialize Pool");
+            process_initialize_pool::<R>(program_id, accounts, implied_volatility, spread_bps)
         }
-        TemplateInstruction::Operation1 { amount } => {
-            msg!("Instruction: Operation1");
-            process_operation1(program_id, accounts, amount)
+        TemplateInstruction::SetImpliedVolatility { implied_volatility } => {
+            msg!("Instruction: Set Implied Volatility");
+            process_set_implied_volatility(program_id, accounts, implied_volatility)
         }
-        TemplateInstruction::Operation2 => {
-            msg!("Instruction: Operation2");
-            process_operation2(program_id, accounts)
+        TemplateInstruction::BuyFromPool {
+            amount,
+            max_premium,
+        } => {
+            msg!("Instruction: Buy From Pool");
+            process_buy_from_pool::<R>(program_id, accounts, amount, max_premium)
+        }
+        TemplateInstruction::SellToPool {
+            amount,
+            min_premium,
+        } => {
+            msg!("Instruction: Sell To Pool");
+            process_sell_to_pool::<R>(program_id, accounts, amount, min_premium)
+        }
+        TemplateInstruction::WithdrawFromPool {
+            option_amount,
+            premium_amount,
+        } => {
+            msg!("Instruction: Withdraw From Pool");
+            process_withdraw_from_pool(program_id, accounts, option_amount, premium_amount)
+        }
+        TemplateInstruction::InitializeOrderBook => {
+            msg!("Instruction: Initialize Order Book");
+            process_initialize_order_book(program_id, accounts)
+        }
+        TemplateInstruction::PlaceOrder {
+            side,
+            price,
+            quantity,
+        } => {
+            msg!("Instruction: Place Order");
+            process_place_order(program_id, accounts, side, price, quantity)
+        }
+        TemplateInstruction::CancelOrder { side, order_id } => {
+            msg!("Instruction: Cancel Order");
+            process_cancel_order(program_id, accounts, side, order_id)
+        }
+        TemplateInstruction::MatchOrders { limit } => {
+            msg!("Instruction: Match Orders");
+            process_match_orders(program_id, accounts, limit)
+        }
+        TemplateInstruction::ConsumeEvents { limit } => {
+            msg!("Instruction: Consume Events");
+            process_consume_events(program_id, accounts, limit)
+        }
+        TemplateInstruction::SettleFunds => {
+            msg!("Instruction: Settle Funds");
+            process_settle_funds(program_id, accounts)
         }
     }
 }
 
-/// Processes an Initialize instruction
-fn process_initialize(
+/// Processes an InitializeSeries or, given `max_payoff`, an InitializeCashSettledSeries instruction
+fn process_initialize_series<R: PriceReader>(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    param1: u64,
-    param2: u8,
+    kind: OptionKind,
+    strike: u64,
+    contract_size: u64,
+    expiry: i64,
+    max_payoff: Option<u64>,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    let system_program_info = next_account_info(account_info_iter)?;
+    let creator_info = next_account_info(account_info_iter)?;
+    let series_info = next_account_info(account_info_iter)?;
+    let series_authority_info = next_account_info(account_info_iter)?;
+    let underlying_mint_info = next_account_info(account_info_iter)?;
+    let quote_mint_info = next_account_info(account_info_iter)?;
+    let option_mint_info = next_account_info(account_info_iter)?;
+    let writer_mint_info = next_account_info(account_info_iter)?;
+    let underlying_vault_info = next_account_info(account_info_iter)?;
+    let quote_vault_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
-        return Err(TemplateError::InvalidAuthority.into());
-    }
-    
+    // Cash-settled series need a price account, physically settled ones
+    // may bind one for an option pool
+    let price_info = match max_payoff {
+        Some(_) => Some(next_account_info(account_info_iter)?),
+        None => account_info_iter.next(),
+    };
+
+    // Check the creator is a signer
+    check_signer(creator_info)?;
+
+    // Check series account ownership
+    check_account_owner(series_info, program_id)?;
+
     // Check for rent exemption
     let rent = &Rent::from_account_info(rent_info)?;
-    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
+    if !rent.is_exempt(series_info.lamports(), series_info.data_len()) {
         return Err(TemplateError::NotRentExempt.into());
     }
-    
-    // Initialize account data
-    let template_account = TemplateAccount {
-        authority: *authority_info.key,
-        value1: param1,
-        value2: param2,
+
+    // Check the series has not been initialized yet
+    if series_info.data_len() != OptionSeries::get_size() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if series_info.data.borrow()[0] != 0 {
+        return Err(TemplateError::SeriesAlreadyInitialized.into());
+    }
+
+    // Validate token program
+    if *token_program_info.key != spl_token::id() {
+        return Err(TemplateError::InvalidTokenProgram.into());
+    }
+
+    // Validate terms
+    if strike == 0 || contract_size == 0 {
+        return Err(TemplateError::InvalidTerms.into());
+    }
+    if expiry <= Clock::get()?.unix_timestamp {
+        return Err(TemplateError::InvalidExpiry.into());
+    }
+    if max_payoff == Some(0) {
+        return Err(TemplateError::InvalidTerms.into());
+    }
+
+    // Check the price account can be read
+    if let Some(price_info) = price_info {
+        R::read_price(price_info)?;
+    }
+
+    // Validate series authority
+    let (series_authority, bump_seed) = find_series_authority(program_id, series_info.key);
+    if series_authority != *series_authority_info.key {
+        return Err(TemplateError::InvalidSeriesAuthority.into());
+    }
+
+    // Validate the underlying and quote mints and their vaults
+    if underlying_mint_info.key == quote_mint_info.key {
+        return Err(TemplateError::RepeatedMint.into());
+    }
+    for (vault_info, mint_info) in [
+        (underlying_vault_info, underlying_mint_info),
+        (quote_vault_info, quote_mint_info),
+    ] {
+        check_account_owner(mint_info, &spl_token::id())
+            .map_err(|_| TemplateError::InvalidVault)?;
+        check_vault(vault_info, mint_info.key, &series_authority)?;
+    }
+    let underlying_decimals = mint_decimals(underlying_mint_info)?;
+    let quote_decimals = mint_decimals(quote_mint_info)?;
+
+    // Validate the option and writer mints
+    for mint_info in [option_mint_info, writer_mint_info] {
+        check_option_mint(mint_info, &series_authority)?;
+    }
+    if option_mint_info.key == writer_mint_info.key {
+        return Err(TemplateError::InvalidOptionMint.into());
+    }
+
+    // Initialize series data
+    let series = OptionSeries {
         is_initialized: true,
+        bump_seed,
+        creator: *creator_info.key,
+        kind,
+        underlying_mint: *underlying_mint_info.key,
+        quote_mint: *quote_mint_info.key,
+        strike,
+        contract_size,
+        expiry,
+        option_mint: *option_mint_info.key,
+        writer_mint: *writer_mint_info.key,
+        underlying_vault: *underlying_vault_info.key,
+        quote_vault: *quote_vault_info.key,
+        settlement: match max_payoff {
+            Some(_) => Settlement::Cash,
+            None => Settlement::Physical,
+        },
+        price_account: price_info.map(|info| *info.key).unwrap_or_default(),
+        underlying_decimals,
+        quote_decimals,
+        max_payoff: max_payoff.unwrap_or_default(),
+        is_settled: false,
+        settlement_price: 0,
+        has_pool: false,
+        pool_admin: Pubkey::default(),
+        pool_option_vault: Pubkey::default(),
+        pool_premium_vault: Pubkey::default(),
+        implied_volatility: 0,
+        pool_spread_bps: 0,
     };
-    
-    // Save account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+
+    // Save series data
+    series.serialize(&mut &mut series_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a WriteOption instruction
+fn process_write_option(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let writer_info = next_account_info(account_info_iter)?;
+    let series_info = next_account_info(account_info_iter)?;
+    let series_authority_info = next_account_info(account_info_iter)?;
+    let writer_collateral_info = next_account_info(account_info_iter)?;
+    let collateral_vault_info = next_account_info(account_info_iter)?;
+    let option_mint_info = next_account_info(account_info_iter)?;
+    let writer_mint_info = next_account_info(account_info_iter)?;
+    let option_destination_info = next_account_info(account_info_iter)?;
+    let writer_token_destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the writer is a signer
+    check_signer(writer_info)?;
+
+    // Load and validate the series
+    let series = load_series(
+        program_id,
+        series_info,
+        series_authority_info,
+        token_program_info,
+    )?;
+    if collateral_vault_info.key != series.collateral_vault() {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    if *option_mint_info.key != series.option_mint || *writer_mint_info.key != series.writer_mint {
+        return Err(TemplateError::InvalidOptionMint.into());
+    }
+
+    // Validate amount and timing
+    if amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+    if series.is_expired(Clock::get()?.unix_timestamp) {
+        return Err(TemplateError::SeriesExpired.into());
+    }
+
+    // Lock the collateral
+    let collateral = safe_multiplication(amount, series.collateral_per_option())?;
+    transfer_to_vault(
+        token_program_info,
+        writer_collateral_info,
+        collateral_vault_info,
+        writer_info,
+        collateral,
+    )?;
+
+    // Mint option and writer tokens
+    let series_seeds: &[&[u8]] = &[series_info.key.as_ref(), &[series.bump_seed]];
+    for (mint_info, destination_info) in [
+        (option_mint_info, option_destination_info),
+        (writer_mint_info, writer_token_destination_info),
+    ] {
+        invoke_signed(
+            &spl_token::instruction::mint_to(
+                token_program_info.key,
+                mint_info.key,
+                destination_info.key,
+                series_authority_info.key,
+                &[],
+                amount,
+            )?,
+            &[
+                mint_info.clone(),
+                destination_info.clone(),
+                series_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[series_seeds],
+        )?;
+    }
+
     Ok(())
 }
 
-/// Processes an Operation1 instruction
-fn process_operation1(
+/// Processes an ExerciseOption instruction
+fn process_exercise_option(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     amount: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
+    let holder_info = next_account_info(account_info_iter)?;
+    let series_info = next_account_info(account_info_iter)?;
+    let series_authority_info = next_account_info(account_info_iter)?;
+    let option_mint_info = next_account_info(account_info_iter)?;
+    let holder_options_info = next_account_info(account_info_iter)?;
+    let holder_payment_info = next_account_info(account_info_iter)?;
+    let payment_vault_info = next_account_info(account_info_iter)?;
+    let collateral_vault_info = next_account_info(account_info_iter)?;
+    let collateral_destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the holder is a signer
+    check_signer(holder_info)?;
+
+    // Load and validate the series
+    let series = load_series(
+        program_id,
+        series_info,
+        series_authority_info,
+        token_program_info,
+    )?;
+    if series.settlement != Settlement::Physical {
+        return Err(TemplateError::InvalidSettlementType.into());
     }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
-        return Err(TemplateError::InvalidAuthority.into());
+    if *option_mint_info.key != series.option_mint {
+        return Err(TemplateError::InvalidOptionMint.into());
+    }
+    if payment_vault_info.key != series.payment_vault()
+        || collateral_vault_info.key != series.collateral_vault()
+    {
+        return Err(TemplateError::InvalidVault.into());
     }
-    
-    // Deserialize account data
-    let mut template_account = TemplateAccount::try_from_slice(&account_info.data.borrow())?;
-    
-    // Verify authority
-    if template_account.authority != *authority_info.key {
+
+    // Validate amount and timing
+    if amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+    if !series.is_exercisable(Clock::get()?.unix_timestamp) {
+        return Err(TemplateError::OutsideExerciseWindow.into());
+    }
+
+    // Burn the options, take the payment and release the collateral
+    let payment = safe_multiplication(amount, series.payment_per_option())?;
+    let collateral = safe_multiplication(amount, series.collateral_per_option())?;
+    burn(
+        token_program_info,
+        holder_options_info,
+        option_mint_info,
+        holder_info,
+        amount,
+    )?;
+    transfer_to_vault(
+        token_program_info,
+        holder_payment_info,
+        payment_vault_info,
+        holder_info,
+        payment,
+    )?;
+    let series_seeds: &[&[u8]] = &[series_info.key.as_ref(), &[series.bump_seed]];
+    transfer_from_vault(
+        token_program_info,
+        collateral_vault_info,
+        collateral_destination_info,
+        series_authority_info,
+        series_seeds,
+        collateral,
+    )?;
+
+    Ok(())
+}
+
+/// Processes a RedeemCollateral instruction
+fn process_redeem_collateral(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let writer_info = next_account_info(account_info_iter)?;
+    let series_info = next_account_info(account_info_iter)?;
+    let series_authority_info = next_account_info(account_info_iter)?;
+    let writer_mint_info = next_account_info(account_info_iter)?;
+    let writer_tokens_info = next_account_info(account_info_iter)?;
+    let underlying_vault_info = next_account_info(account_info_iter)?;
+    let quote_vault_info = next_account_info(account_info_iter)?;
+    let underlying_destination_info = next_account_info(account_info_iter)?;
+    let quote_destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the writer is a signer
+    check_signer(writer_info)?;
+
+    // Load and validate the series
+    let series = load_series(
+        program_id,
+        series_info,
+        series_authority_info,
+        token_program_info,
+    )?;
+    if *writer_mint_info.key != series.writer_mint {
+        return Err(TemplateError::InvalidOptionMint.into());
+    }
+    if *underlying_vault_info.key != series.underlying_vault
+        || *quote_vault_info.key != series.quote_vault
+    {
+        return Err(TemplateError::InvalidVault.into());
+    }
+
+    // Validate amount and timing
+    if amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+    if !series.is_expired(Clock::get()?.unix_timestamp) {
+        return Err(TemplateError::SeriesNotExpired.into());
+    }
+
+    let (underlying_amount, quote_amount) = match series.settlement {
+        // Each writer token claims an equal share of both vaults, rounded down
+        Settlement::Physical => {
+            let writer_supply =
+                spl_token::state::Mint::unpack(&writer_mint_info.data.borrow())?.supply;
+            if amount > writer_supply {
+                return Err(TemplateError::ExpectedAmountMismatch.into());
+            }
+            let share = |vault_info: &AccountInfo| -> Result<u64, ProgramError> {
+                let vault_amount = token_amount(vault_info)?;
+                Ok((vault_amount as u128 * amount as u128 / writer_supply as u128) as u64)
+            };
+            (share(underlying_vault_info)?, share(quote_vault_info)?)
+        }
+        // Each writer token claims what its option does not pay out
+        Settlement::Cash => {
+            if !series.is_settled {
+                return Err(TemplateError::SeriesNotSettled.into());
+            }
+            let remainder = series.max_payoff - series.settlement_payoff();
+            (0, safe_multiplication(amount, remainder)?)
+        }
+    };
+
+    // Burn the writer tokens and pay out both shares
+    burn(
+        token_program_info,
+        writer_tokens_info,
+        writer_mint_info,
+        writer_info,
+        amount,
+    )?;
+    let series_seeds: &[&[u8]] = &[series_info.key.as_ref(), &[series.bump_seed]];
+    for (vault_info, destination_info, vault_amount) in [
+        (
+            underlying_vault_info,
+            underlying_destination_info,
+            underlying_amount,
+        ),
+        (quote_vault_info, quote_destination_info, quote_amount),
+    ] {
+        if vault_amount > 0 {
+            transfer_from_vault(
+                token_program_info,
+                vault_info,
+                destination_info,
+                series_authority_info,
+                series_seeds,
+                vault_amount,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Processes a SettleSeries instruction
+fn process_settle_series<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let series_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
+
+    // Load and validate the series
+    check_account_owner(series_info, program_id)?;
+    let mut series = OptionSeries::try_from_slice(&series_info.data.borrow())?;
+    if !series.is_initialized {
+        return Err(TemplateError::SeriesNotInitialized.into());
+    }
+    if series.settlement != Settlement::Cash {
+        return Err(TemplateError::InvalidSettlementType.into());
+    }
+    if series.is_settled {
+        return Err(TemplateError::SeriesAlreadySettled.into());
+    }
+    if *price_info.key != series.price_account {
+        return Err(TemplateError::InvalidPriceAccount.into());
+    }
+    if !series.is_expired(Clock::get()?.unix_timestamp) {
+        return Err(TemplateError::SeriesNotExpired.into());
+    }
+
+    // Read a price published in the settlement window after expiry
+    let price = R::read_price(price_info)?;
+    if price.publish_time < series.expiry {
+        return Err(TemplateError::PriceBeforeExpiry.into());
+    }
+    if price.publish_time > series.expiry.saturating_add(MAX_SETTLEMENT_DELAY) {
+        return Err(TemplateError::PriceAfterSettlementWindow.into());
+    }
+
+    // Record the value of one option's underlying in the quote token
+    series.settlement_price = price.quote_amount(
+        series.contract_size,
+        series.underlying_decimals,
+        series.quote_decimals,
+    )?;
+    series.is_settled = true;
+
+    // Save series data
+    series.serialize(&mut &mut series_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a ClaimSettlement instruction
+fn process_claim_settlement(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let holder_info = next_account_info(account_info_iter)?;
+    let series_info = next_account_info(account_info_iter)?;
+    let series_authority_info = next_account_info(account_info_iter)?;
+    let option_mint_info = next_account_info(account_info_iter)?;
+    let holder_options_info = next_account_info(account_info_iter)?;
+    let quote_vault_info = next_account_info(account_info_iter)?;
+    let quote_destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the holder is a signer
+    check_signer(holder_info)?;
+
+    // Load and validate the series
+    let series = load_series(
+        program_id,
+        series_info,
+        series_authority_info,
+        token_program_info,
+    )?;
+    if series.settlement != Settlement::Cash {
+        return Err(TemplateError::InvalidSettlementType.into());
+    }
+    if *option_mint_info.key != series.option_mint {
+        return Err(TemplateError::InvalidOptionMint.into());
+    }
+    if *quote_vault_info.key != series.quote_vault {
+        return Err(TemplateError::InvalidVault.into());
+    }
+
+    // Validate amount and settlement
+    if amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+    if !series.is_settled {
+        return Err(TemplateError::SeriesNotSettled.into());
+    }
+
+    // Burn the options and pay out their payoff
+    let payoff = safe_multiplication(amount, series.settlement_payoff())?;
+    burn(
+        token_program_info,
+        holder_options_info,
+        option_mint_info,
+        holder_info,
+        amount,
+    )?;
+    if payoff > 0 {
+        let series_seeds: &[&[u8]] = &[series_info.key.as_ref(), &[series.bump_seed]];
+        transfer_from_vault(
+            token_program_info,
+            quote_vault_info,
+            quote_destination_info,
+            series_authority_info,
+            series_seeds,
+            payoff,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Processes an InitializePool instruction
+fn process_initialize_pool<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    implied_volatility: u64,
+    spread_bps: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let pool_admin_info = next_account_info(account_info_iter)?;
+    let series_info = next_account_info(account_info_iter)?;
+    let series_authority_info = next_account_info(account_info_iter)?;
+    let pool_option_vault_info = next_account_info(account_info_iter)?;
+    let pool_premium_vault_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
+
+    // Check the pool admin is a signer
+    check_signer(pool_admin_info)?;
+
+    // Load and validate the series
+    check_account_owner(series_info, program_id)?;
+    let mut series = OptionSeries::try_from_slice(&series_info.data.borrow())?;
+    if !series.is_initialized {
+        return Err(TemplateError::SeriesNotInitialized.into());
+    }
+    if series.creator != *pool_admin_info.key {
         return Err(TemplateError::InvalidAuthority.into());
     }
-    
-    // Update account data
-    template_account.value1 = template_account.value1.checked_add(amount)
-        .ok_or(TemplateError::MathOverflow)?;
-    
-    // Save updated account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+    check_series_authority(
+        program_id,
+        series_info.key,
+        series.bump_seed,
+        series_authority_info.key,
+    )?;
+    if series.settlement != Settlement::Physical {
+        return Err(TemplateError::InvalidSettlementType.into());
+    }
+    if series.has_pool {
+        return Err(TemplateError::PoolAlreadyInitialized.into());
+    }
+
+    // Validate pool parameters
+    if implied_volatility == 0 || spread_bps >= BPS_DENOMINATOR {
+        return Err(TemplateError::InvalidPoolParameters.into());
+    }
+
+    // Validate the pool vaults and the price account bound at initialization
+    check_vault(
+        pool_option_vault_info,
+        &series.option_mint,
+        series_authority_info.key,
+    )?;
+    check_vault(
+        pool_premium_vault_info,
+        &series.quote_mint,
+        series_authority_info.key,
+    )?;
+    if series.price_account == Pubkey::default() || *price_info.key != series.price_account {
+        return Err(TemplateError::InvalidPriceAccount.into());
+    }
+    R::read_price(price_info)?;
+
+    // Open the pool
+    series.has_pool = true;
+    series.pool_admin = *pool_admin_info.key;
+    series.pool_option_vault = *pool_option_vault_info.key;
+    series.pool_premium_vault = *pool_premium_vault_info.key;
+    series.implied_volatility = implied_volatility;
+    series.pool_spread_bps = spread_bps;
+
+    // Save series data
+    series.serialize(&mut &mut series_info.data.borrow_mut()[..])?;
+
     Ok(())
 }
 
-/// Processes an Operation2 instruction
-fn process_operation2(
+/// Processes a SetImpliedVolatility instruction
+fn process_set_implied_volatility(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
+    implied_volatility: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
+    let pool_admin_info = next_account_info(account_info_iter)?;
+    let series_info = next_account_info(account_info_iter)?;
+
+    // Check the pool admin is a signer
+    check_signer(pool_admin_info)?;
+
+    // Load the series and check the pool admin
+    check_account_owner(series_info, program_id)?;
+    let mut series = OptionSeries::try_from_slice(&series_info.data.borrow())?;
+    if !series.has_pool {
+        return Err(TemplateError::PoolNotInitialized.into());
     }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
+    if series.pool_admin != *pool_admin_info.key {
         return Err(TemplateError::InvalidAuthority.into());
     }
-    
-    // Deserialize account data
-    let mut template_account = TemplateAccount::try_from_slice(&account_info.data.borrow())?;
-    
-    // Verify authority
-    if template_account.authority != *authority_info.key {
+    if implied_volatility == 0 {
+        return Err(TemplateError::InvalidPoolParameters.into());
+    }
+
+    // Save series data
+    series.implied_volatility = implied_volatility;
+    series.serialize(&mut &mut series_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a BuyFromPool instruction
+fn process_buy_from_pool<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    max_premium: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let buyer_info = next_account_info(account_info_iter)?;
+    let series_info = next_account_info(account_info_iter)?;
+    let series_authority_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
+    let buyer_quote_info = next_account_info(account_info_iter)?;
+    let pool_premium_vault_info = next_account_info(account_info_iter)?;
+    let pool_option_vault_info = next_account_info(account_info_iter)?;
+    let option_destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the buyer is a signer
+    check_signer(buyer_info)?;
+
+    // Load and validate the series and its pool
+    let series = load_pool(
+        program_id,
+        series_info,
+        series_authority_info,
+        token_program_info,
+        pool_option_vault_info,
+        pool_premium_vault_info,
+    )?;
+    if amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    // Price the options above the model price by the spread, rounded up
+    let model_premium = pool_model_premium::<R>(&series, price_info, amount)?;
+    let premium = model_premium
+        .checked_mul((BPS_DENOMINATOR + series.pool_spread_bps) as u128)
+        .ok_or(TemplateError::MathOverflow)?
+        .div_ceil(BPS_DENOMINATOR as u128);
+    let premium = u64::try_from(premium).map_err(|_| TemplateError::MathOverflow)?;
+    if premium > max_premium {
+        return Err(TemplateError::SlippageExceeded.into());
+    }
+
+    // Take the premium and hand over the options
+    transfer_to_vault(
+        token_program_info,
+        buyer_quote_info,
+        pool_premium_vault_info,
+        buyer_info,
+        premium,
+    )?;
+    let series_seeds: &[&[u8]] = &[series_info.key.as_ref(), &[series.bump_seed]];
+    transfer_from_vault(
+        token_program_info,
+        pool_option_vault_info,
+        option_destination_info,
+        series_authority_info,
+        series_seeds,
+        amount,
+    )?;
+
+    Ok(())
+}
+
+/// Processes a SellToPool instruction
+fn process_sell_to_pool<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    min_premium: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let seller_info = next_account_info(account_info_iter)?;
+    let series_info = next_account_info(account_info_iter)?;
+    let series_authority_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
+    let seller_options_info = next_account_info(account_info_iter)?;
+    let pool_option_vault_info = next_account_info(account_info_iter)?;
+    let pool_premium_vault_info = next_account_info(account_info_iter)?;
+    let quote_destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the seller is a signer
+    check_signer(seller_info)?;
+
+    // Load and validate the series and its pool
+    let series = load_pool(
+        program_id,
+        series_info,
+        series_authority_info,
+        token_program_info,
+        pool_option_vault_info,
+        pool_premium_vault_info,
+    )?;
+    if amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    // Price the options below the model price by the spread, rounded down
+    let model_premium = pool_model_premium::<R>(&series, price_info, amount)?;
+    let premium = model_premium
+        .checked_mul((BPS_DENOMINATOR - series.pool_spread_bps) as u128)
+        .ok_or(TemplateError::MathOverflow)?
+        / BPS_DENOMINATOR as u128;
+    let premium = u64::try_from(premium).map_err(|_| TemplateError::MathOverflow)?;
+    if premium < min_premium {
+        return Err(TemplateError::SlippageExceeded.into());
+    }
+
+    // Take the options and pay the premium
+    transfer_to_vault(
+        token_program_info,
+        seller_options_info,
+        pool_option_vault_info,
+        seller_info,
+        amount,
+    )?;
+    if premium > 0 {
+        let series_seeds: &[&[u8]] = &[series_info.key.as_ref(), &[series.bump_seed]];
+        transfer_from_vault(
+            token_program_info,
+            pool_premium_vault_info,
+            quote_destination_info,
+            series_authority_info,
+            series_seeds,
+            premium,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Processes a WithdrawFromPool instruction
+fn process_withdraw_from_pool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    option_amount: u64,
+    premium_amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let pool_admin_info = next_account_info(account_info_iter)?;
+    let series_info = next_account_info(account_info_iter)?;
+    let series_authority_info = next_account_info(account_info_iter)?;
+    let pool_option_vault_info = next_account_info(account_info_iter)?;
+    let pool_premium_vault_info = next_account_info(account_info_iter)?;
+    let option_destination_info = next_account_info(account_info_iter)?;
+    let quote_destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the pool admin is a signer
+    check_signer(pool_admin_info)?;
+
+    // Load and validate the series and its pool
+    let series = load_pool(
+        program_id,
+        series_info,
+        series_authority_info,
+        token_program_info,
+        pool_option_vault_info,
+        pool_premium_vault_info,
+    )?;
+    if series.pool_admin != *pool_admin_info.key {
         return Err(TemplateError::InvalidAuthority.into());
     }
-    
-    // Update account data
-    template_account.value2 = template_account.value2.checked_add(1)
-        .ok_or(TemplateError::MathOverflow)?;
-    
-    // Save updated account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+
+    // Pay out whatever was asked for from each vault
+    let series_seeds: &[&[u8]] = &[series_info.key.as_ref(), &[series.bump_seed]];
+    for (vault_info, destination_info, amount) in [
+        (
+            pool_option_vault_info,
+            option_destination_info,
+            option_amount,
+        ),
+        (
+            pool_premium_vault_info,
+            quote_destination_info,
+            premium_amount,
+        ),
+    ] {
+        if amount > 0 {
+            transfer_from_vault(
+                token_program_info,
+                vault_info,
+                destination_info,
+                series_authority_info,
+                series_seeds,
+                amount,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Processes an InitializeOrderBook instruction
+fn process_initialize_order_book(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let series_info = next_account_info(account_info_iter)?;
+    let order_book_info = next_account_info(account_info_iter)?;
+    let event_queue_info = next_account_info(account_info_iter)?;
+    let order_book_authority_info = next_account_info(account_info_iter)?;
+    let option_vault_info = next_account_info(account_info_iter)?;
+    let quote_vault_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+
+    // Load and validate the series
+    check_account_owner(series_info, program_id)?;
+    let series = OptionSeries::try_from_slice(&series_info.data.borrow())?;
+    if !series.is_initialized {
+        return Err(TemplateError::SeriesNotInitialized.into());
+    }
+    if series.is_expired(Clock::get()?.unix_timestamp) {
+        return Err(TemplateError::SeriesExpired.into());
+    }
+
+    // Check the order book and event queue are new, rent exempt and full size
+    let rent = &Rent::from_account_info(rent_info)?;
+    for (account_info, size) in [
+        (order_book_info, OrderBook::get_size()),
+        (event_queue_info, EventQueue::get_size()),
+    ] {
+        check_account_owner(account_info, program_id)?;
+        if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
+            return Err(TemplateError::NotRentExempt.into());
+        }
+        if account_info.data_len() != size {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if account_info.data.borrow()[0] != 0 {
+            return Err(TemplateError::OrderBookAlreadyInitialized.into());
+        }
+    }
+    if order_book_info.key == event_queue_info.key {
+        return Err(TemplateError::InvalidOrderBook.into());
+    }
+
+    // Validate the order book authority and its vaults
+    let (order_book_authority, bump_seed) =
+        find_order_book_authority(program_id, order_book_info.key);
+    if order_book_authority != *order_book_authority_info.key {
+        return Err(TemplateError::InvalidOrderBookAuthority.into());
+    }
+    check_vault(
+        option_vault_info,
+        &series.option_mint,
+        &order_book_authority,
+    )?;
+    check_vault(quote_vault_info, &series.quote_mint, &order_book_authority)?;
+
+    // Initialize order book and event queue data
+    let order_book = OrderBook {
+        is_initialized: true,
+        bump_seed,
+        series: *series_info.key,
+        event_queue: *event_queue_info.key,
+        option_vault: *option_vault_info.key,
+        quote_vault: *quote_vault_info.key,
+        next_order_id: 0,
+        bids: Vec::new(),
+        asks: Vec::new(),
+    };
+    let event_queue = EventQueue {
+        is_initialized: true,
+        order_book: *order_book_info.key,
+        events: Vec::new(),
+    };
+
+    // Save order book and event queue data
+    order_book.serialize(&mut &mut order_book_info.data.borrow_mut()[..])?;
+    event_queue.serialize(&mut &mut event_queue_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a PlaceOrder instruction
+fn process_place_order(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    side: Side,
+    price: u64,
+    quantity: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let series_info = next_account_info(account_info_iter)?;
+    let order_book_info = next_account_info(account_info_iter)?;
+    let open_orders_info = next_account_info(account_info_iter)?;
+    let owner_tokens_info = next_account_info(account_info_iter)?;
+    let vault_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    // Load and validate the order book and its series
+    let mut order_book = load_order_book(program_id, order_book_info)?;
+    let series = load_order_book_series(program_id, &order_book, series_info)?;
+    if series.is_expired(Clock::get()?.unix_timestamp) {
+        return Err(TemplateError::SeriesExpired.into());
+    }
+    if *token_program_info.key != spl_token::id() {
+        return Err(TemplateError::InvalidTokenProgram.into());
+    }
+    let expected_vault = match side {
+        Side::Bid => &order_book.quote_vault,
+        Side::Ask => &order_book.option_vault,
+    };
+    if vault_info.key != expected_vault {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    if price == 0 || quantity == 0 {
+        return Err(TemplateError::InvalidOrder.into());
+    }
+
+    // Load the open orders account, initializing it on first use
+    check_account_owner(open_orders_info, program_id)?;
+    if open_orders_info.data_len() != OpenOrders::get_size() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let mut open_orders = OpenOrders::try_from_slice(&open_orders_info.data.borrow())?;
+    if !open_orders.is_initialized {
+        let rent = Rent::get()?;
+        if !rent.is_exempt(open_orders_info.lamports(), open_orders_info.data_len()) {
+            return Err(TemplateError::NotRentExempt.into());
+        }
+        open_orders = OpenOrders {
+            is_initialized: true,
+            order_book: *order_book_info.key,
+            owner: *owner_info.key,
+            base_free: 0,
+            quote_free: 0,
+        };
+    } else if open_orders.order_book != *order_book_info.key || open_orders.owner != *owner_info.key
+    {
+        return Err(TemplateError::InvalidOpenOrders.into());
+    }
+
+    // Rest the order and lock what it can trade
+    let locked_amount = match side {
+        Side::Bid => safe_multiplication(price, quantity)?,
+        Side::Ask => quantity,
+    };
+    let (order_id, evicted) = order_book.insert(side, *open_orders_info.key, price, quantity)?;
+    transfer_to_vault(
+        token_program_info,
+        owner_tokens_info,
+        vault_info,
+        owner_info,
+        locked_amount,
+    )?;
+    msg!("Order id: {}", order_id);
+
+    // Credit the owner of an order evicted from a full side
+    if let Some(evicted) = evicted {
+        msg!("Evicted order id: {}", evicted.order_id);
+        if evicted.open_orders == *open_orders_info.key {
+            open_orders.credit_order(side, &evicted)?;
+        } else {
+            let evicted_open_orders_info = next_account_info(account_info_iter)?;
+            if *evicted_open_orders_info.key != evicted.open_orders {
+                return Err(TemplateError::InvalidOpenOrders.into());
+            }
+            let mut evicted_open_orders =
+                load_open_orders(program_id, evicted_open_orders_info, order_book_info.key)?;
+            evicted_open_orders.credit_order(side, &evicted)?;
+            evicted_open_orders
+                .serialize(&mut &mut evicted_open_orders_info.data.borrow_mut()[..])?;
+        }
+    }
+
+    // Save order book and open orders data
+    order_book.serialize(&mut &mut order_book_info.data.borrow_mut()[..])?;
+    open_orders.serialize(&mut &mut open_orders_info.data.borrow_mut()[..])?;
+
     Ok(())
 }
+
+/// Processes a CancelOrder instruction
+fn process_cancel_order(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    side: Side,
+    order_id: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let order_book_info = next_account_info(account_info_iter)?;
+    let open_orders_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    // Load the order book and the owner's open orders account
+    let mut order_book = load_order_book(program_id, order_book_info)?;
+    let mut open_orders = load_open_orders(program_id, open_orders_info, order_book_info.key)?;
+    if open_orders.owner != *owner_info.key {
+        return Err(TemplateError::InvalidOpenOrders.into());
+    }
+
+    // Check the order belongs to the open orders account
+    let order = order_book
+        .side(side)
+        .iter()
+        .find(|order| order.order_id == order_id)
+        .ok_or(TemplateError::OrderNotFound)?;
+    if order.open_orders != *open_orders_info.key {
+        return Err(TemplateError::InvalidOpenOrders.into());
+    }
+
+    // Remove the order and credit what it locked
+    let order = order_book.remove(side, order_id)?;
+    open_orders.credit_order(side, &order)?;
+
+    // Save order book and open orders data
+    order_book.serialize(&mut &mut order_book_info.data.borrow_mut()[..])?;
+    open_orders.serialize(&mut &mut open_orders_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a MatchOrders instruction
+fn process_match_orders(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    limit: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let series_info = next_account_info(account_info_iter)?;
+    let order_book_info = next_account_info(account_info_iter)?;
+    let event_queue_info = next_account_info(account_info_iter)?;
+
+    // Load and validate the order book, its series and its event queue
+    let mut order_book = load_order_book(program_id, order_book_info)?;
+    let series = load_order_book_series(program_id, &order_book, series_info)?;
+    if series.is_expired(Clock::get()?.unix_timestamp) {
+        return Err(TemplateError::SeriesExpired.into());
+    }
+    if *event_queue_info.key != order_book.event_queue {
+        return Err(TemplateError::InvalidOrderBook.into());
+    }
+    let mut event_queue = load_event_queue(program_id, event_queue_info)?;
+
+    // Cross the book into fills
+    let fills = order_book.match_orders(&mut event_queue, limit as usize);
+    if fills == 0 && order_book.is_crossed() && event_queue.events.len() >= MAX_EVENTS {
+        return Err(TemplateError::EventQueueFull.into());
+    }
+
+    // Save order book and event queue data
+    order_book.serialize(&mut &mut order_book_info.data.borrow_mut()[..])?;
+    event_queue.serialize(&mut &mut event_queue_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a ConsumeEvents instruction
+fn process_consume_events(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    limit: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let event_queue_info = next_account_info(account_info_iter)?;
+    let mut event_queue = load_event_queue(program_id, event_queue_info)?;
+
+    // Load each distinct open orders account once, so fills of a trader
+    // against itself credit the same balances
+    let mut traders: Vec<(&AccountInfo, OpenOrders)> = Vec::new();
+    for open_orders_info in account_info_iter {
+        if traders
+            .iter()
+            .any(|(trader_info, _)| trader_info.key == open_orders_info.key)
+        {
+            continue;
+        }
+        let open_orders = load_open_orders(program_id, open_orders_info, &event_queue.order_book)?;
+        traders.push((open_orders_info, open_orders));
+    }
+    let find_trader = |key: &Pubkey, traders: &[(&AccountInfo, OpenOrders)]| {
+        traders
+            .iter()
+            .position(|(trader_info, _)| trader_info.key == key)
+    };
+
+    // Credit fills from the front of the queue until one is missing its traders
+    let mut consumed = 0;
+    for event in event_queue.events.iter().take(limit as usize) {
+        let (Some(buyer), Some(seller)) = (
+            find_trader(&event.buyer, &traders),
+            find_trader(&event.seller, &traders),
+        ) else {
+            break;
+        };
+        traders[buyer]
+            .1
+            .credit(event.quantity, event.price_improvement()?)?;
+        traders[seller].1.credit(0, event.quote_amount()?)?;
+        consumed += 1;
+    }
+    if consumed == 0 && limit > 0 && !event_queue.events.is_empty() {
+        return Err(TemplateError::InvalidOpenOrders.into());
+    }
+    event_queue.events.drain(..consumed);
+
+    // Save event queue and open orders data
+    event_queue.serialize(&mut &mut event_queue_info.data.borrow_mut()[..])?;
+    for (open_orders_info, open_orders) in traders {
+        open_orders.serialize(&mut &mut open_orders_info.data.borrow_mut()[..])?;
+    }
+
+    Ok(())
+}
+
+/// Processes a SettleFunds instruction
+fn process_settle_funds(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let order_book_info = next_account_info(account_info_iter)?;
+    let open_orders_info = next_account_info(account_info_iter)?;
+    let order_book_authority_info = next_account_info(account_info_iter)?;
+    let option_vault_info = next_account_info(account_info_iter)?;
+    let quote_vault_info = next_account_info(account_info_iter)?;
+    let option_destination_info = next_account_info(account_info_iter)?;
+    let quote_destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    // Load and validate the order book and the owner's open orders account
+    let order_book = load_order_book(program_id, order_book_info)?;
+    let mut open_orders = load_open_orders(program_id, open_orders_info, order_book_info.key)?;
+    if open_orders.owner != *owner_info.key {
+        return Err(TemplateError::InvalidOpenOrders.into());
+    }
+    check_order_book_authority(
+        program_id,
+        order_book_info.key,
+        order_book.bump_seed,
+        order_book_authority_info.key,
+    )?;
+    if *option_vault_info.key != order_book.option_vault
+        || *quote_vault_info.key != order_book.quote_vault
+    {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    if *token_program_info.key != spl_token::id() {
+        return Err(TemplateError::InvalidTokenProgram.into());
+    }
+
+    // Pay out both free balances
+    let order_book_seeds: &[&[u8]] = &[order_book_info.key.as_ref(), &[order_book.bump_seed]];
+    for (vault_info, destination_info, amount) in [
+        (
+            option_vault_info,
+            option_destination_info,
+            open_orders.base_free,
+        ),
+        (
+            quote_vault_info,
+            quote_destination_info,
+            open_orders.quote_free,
+        ),
+    ] {
+        if amount > 0 {
+            transfer_from_vault(
+                token_program_info,
+                vault_info,
+                destination_info,
+                order_book_authority_info,
+                order_book_seeds,
+                amount,
+            )?;
+        }
+    }
+    open_orders.base_free = 0;
+    open_orders.quote_free = 0;
+
+    // Save open orders data
+    open_orders.serialize(&mut &mut open_orders_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Loads an initialized order book
+fn load_order_book(
+    program_id: &Pubkey,
+    order_book_info: &AccountInfo,
+) -> Result<OrderBook, ProgramError> {
+    check_account_owner(order_book_info, program_id)?;
+    let order_book = OrderBook::deserialize(&mut &order_book_info.data.borrow()[..])?;
+    if !order_book.is_initialized {
+        return Err(TemplateError::InvalidOrderBook.into());
+    }
+    Ok(order_book)
+}
+
+/// Loads the series an order book trades
+fn load_order_book_series(
+    program_id: &Pubkey,
+    order_book: &OrderBook,
+    series_info: &AccountInfo,
+) -> Result<OptionSeries, ProgramError> {
+    if *series_info.key != order_book.series {
+        return Err(TemplateError::InvalidOrderBook.into());
+    }
+    check_account_owner(series_info, program_id)?;
+    Ok(OptionSeries::try_from_slice(&series_info.data.borrow())?)
+}
+
+/// Loads an initialized event queue
+fn load_event_queue(
+    program_id: &Pubkey,
+    event_queue_info: &AccountInfo,
+) -> Result<EventQueue, ProgramError> {
+    check_account_owner(event_queue_info, program_id)?;
+    let event_queue = EventQueue::deserialize(&mut &event_queue_info.data.borrow()[..])?;
+    if !event_queue.is_initialized {
+        return Err(TemplateError::InvalidOrderBook.into());
+    }
+    Ok(event_queue)
+}
+
+/// Loads an initialized open orders account of `order_book`
+fn load_open_orders(
+    program_id: &Pubkey,
+    open_orders_info: &AccountInfo,
+    order_book: &Pubkey,
+) -> Result<OpenOrders, ProgramError> {
+    check_account_owner(open_orders_info, program_id)?;
+    let open_orders = OpenOrders::try_from_slice(&open_orders_info.data.borrow())
+        .map_err(|_| TemplateError::InvalidOpenOrders)?;
+    if !open_orders.is_initialized || open_orders.order_book != *order_book {
+        return Err(TemplateError::InvalidOpenOrders.into());
+    }
+    Ok(open_orders)
+}
+
+/// Loads a series with an option pool and checks the pool vaults
+fn load_pool(
+    program_id: &Pubkey,
+    series_info: &AccountInfo,
+    series_authority_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+    pool_option_vault_info: &AccountInfo,
+    pool_premium_vault_info: &AccountInfo,
+) -> Result<OptionSeries, ProgramError> {
+    let series = load_series(
+        program_id,
+        series_info,
+        series_authority_info,
+        token_program_info,
+    )?;
+    if !series.has_pool {
+        return Err(TemplateError::PoolNotInitialized.into());
+    }
+    if *pool_option_vault_info.key != series.pool_option_vault
+        || *pool_premium_vault_info.key != series.pool_premium_vault
+    {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    Ok(series)
+}
+
+/// Model premium of `amount` options at the current oracle price, before the spread
+fn pool_model_premium<R: PriceReader>(
+    series: &OptionSeries,
+    price_info: &AccountInfo,
+    amount: u64,
+) -> Result<u128, ProgramError> {
+    if *price_info.key != series.price_account {
+        return Err(TemplateError::InvalidPriceAccount.into());
+    }
+    let now = Clock::get()?.unix_timestamp;
+    let price = R::read_price(price_info)?;
+    if price.publish_time < now.saturating_sub(POOL_PRICE_MAX_AGE) {
+        return Err(TemplateError::StalePrice.into());
+    }
+
+    let spot = price.quote_amount(
+        series.contract_size,
+        series.underlying_decimals,
+        series.quote_decimals,
+    )?;
+    let premium_per_option = series.model_premium(spot, now)?;
+    Ok(premium_per_option as u128 * amount as u128)
+}
+
+/// Loads an initialized series and checks its authority and the token program
+fn load_series(
+    program_id: &Pubkey,
+    series_info: &AccountInfo,
+    series_authority_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+) -> Result<OptionSeries, ProgramError> {
+    check_account_owner(series_info, program_id)?;
+
+    let series = OptionSeries::try_from_slice(&series_info.data.borrow())?;
+    if !series.is_initialized {
+        return Err(TemplateError::SeriesNotInitialized.into());
+    }
+
+    check_series_authority(
+        program_id,
+        series_info.key,
+        series.bump_seed,
+        series_authority_info.key,
+    )?;
+
+    if *token_program_info.key != spl_token::id() {
+        return Err(TemplateError::InvalidTokenProgram.into());
+    }
+
+    Ok(series)
+}
+
+/// Checks that a new vault is an empty token account of `mint` that `authority` controls
+fn check_vault(vault_info: &AccountInfo, mint: &Pubkey, authority: &Pubkey) -> ProgramResult {
+    check_account_owner(vault_info, &spl_token::id()).map_err(|_| TemplateError::InvalidVault)?;
+    let vault = spl_token::state::Account::unpack(&vault_info.data.borrow())?;
+    if vault.mint != *mint
+        || vault.owner != *authority
+        || vault.amount != 0
+        || vault.delegate.is_some()
+        || vault.close_authority.is_some()
+    {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    Ok(())
+}
+
+/// Checks that a new option or writer mint is empty and controlled by the series authority
+fn check_option_mint(mint_info: &AccountInfo, series_authority: &Pubkey) -> ProgramResult {
+    check_account_owner(mint_info, &spl_token::id())
+        .map_err(|_| TemplateError::InvalidOptionMint)?;
+    let mint = spl_token::state::Mint::unpack(&mint_info.data.borrow())?;
+    if mint.mint_authority != COption::Some(*series_authority)
+        || mint.freeze_authority.is_some()
+        || mint.supply != 0
+    {
+        return Err(TemplateError::InvalidOptionMint.into());
+    }
+    Ok(())
+}
+
+/// Reads the decimals of a mint
+fn mint_decimals(mint_info: &AccountInfo) -> Result<u8, ProgramError> {
+    Ok(spl_token::state::Mint::unpack(&mint_info.data.borrow())?.decimals)
+}
+
+/// Reads the balance of a token account
+fn token_amount(token_account_info: &AccountInfo) -> Result<u64, ProgramError> {
+    Ok(spl_token::state::Account::unpack(&token_account_info.data.borrow())?.amount)
+}
+
+/// Burns tokens from an account the owner signs for
+fn burn<'a>(
+    token_program_info: &AccountInfo<'a>,
+    token_account_info: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    invoke(
+        &spl_token::instruction::burn(
+            token_program_info.key,
+            token_account_info.key,
+            mint_info.key,
+            owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            token_account_info.clone(),
+            mint_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+/// Transfers tokens from a user-owned account into a vault
+fn transfer_to_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            source_info.key,
+            vault_info.key,
+            owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            source_info.clone(),
+            vault_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+/// Transfers tokens out of a vault, signing as the series or order book
+/// authority that owns it
+fn transfer_from_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    authority_seeds: &[&[u8]],
+    amount: u64,
+) -> ProgramResult {
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            vault_info.key,
+            destination_info.key,
+            authority_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            vault_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[authority_seeds],
+    )
+}
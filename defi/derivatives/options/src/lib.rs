@@ -1,8 +1,18 @@
 //! Options trading protocol for calls and puts
 //!
-//! This program provides [TEMPLATE FUNCTIONALITY].
+//! This program provides European-style covered calls and cash-secured puts.
+//! Writers lock collateral in vaults owned by a series PDA and receive
+//! option tokens to sell and writer tokens that claim the collateral back.
+//! Holders can exercise in the window before expiry, after which writers
+//! redeem what is left of the collateral and the exercise payments.
+//! Cash-settled series instead pay holders the intrinsic value in the quote
+//! token at a settlement price read from an oracle after expiry. Option
+//! tokens trade on per-series limit order books or against an option pool
+//! priced with Black-Scholes.
 
 pub mod instructions;
+pub mod oracle;
+pub mod order_book;
 pub mod state;
 pub mod utils;
 pub mod errors;
@@ -13,7 +23,7 @@ pub mod entrypoint;
 // Export current sdk types for downstream users building with a different sdk version
 pub use solana_program;
 
-solana_program::declare_id!("options-protocol111111111111111111111111111111");
+solana_program::declare_id!("Eopt111111111111111111111111111111111111111");
 
 /// Program entrypoint's implementation
 pub fn process_instruction(
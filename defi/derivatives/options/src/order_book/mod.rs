@@ -0,0 +1,338 @@
+//! Limit order book for secondary trading of option tokens
+//!
+//! Each order book trades the option tokens of one series against its quote
+//! token. Orders rest in two sorted slabs with price-time priority, and
+//! `match_orders` crosses them into fill events. A full slab takes a new
+//! order only at a better price than its worst order, which it evicts. The tokens stay in the order
+//! book vaults until a crank consumes the events into the traders'
+//! `OpenOrders` balances, from which the owners settle them out.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::errors::TemplateError;
+
+/// Most resting orders on each side of an order book
+pub const MAX_ORDERS_PER_SIDE: usize = 32;
+
+/// Most unconsumed fills an event queue holds
+pub const MAX_EVENTS: usize = 64;
+
+/// Side of an order
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Side {
+    /// Buy options with quote tokens
+    #[default]
+    Bid,
+    /// Sell options for quote tokens
+    Ask,
+}
+
+/// Resting limit order
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Order {
+    /// Sequence number, which also gives time priority
+    pub order_id: u64,
+
+    /// Open orders account of the owner
+    pub open_orders: Pubkey,
+
+    /// Quote tokens per option
+    pub price: u64,
+
+    /// Options left to fill
+    pub quantity: u64,
+}
+
+impl Order {
+    /// Serialized size of an order
+    pub const LEN: usize = 8 + 32 + 8 + 8;
+
+    /// Tokens the order locks: quote tokens for bids and options for asks
+    pub fn locked_amount(&self, side: Side) -> Result<u64, TemplateError> {
+        match side {
+            Side::Bid => self
+                .price
+                .checked_mul(self.quantity)
+                .ok_or(TemplateError::MathOverflow),
+            Side::Ask => Ok(self.quantity),
+        }
+    }
+}
+
+/// Limit order book of one option series
+///
+/// Bids are sorted by descending price and asks by ascending price, each
+/// with older orders first at the same price. Both vaults are owned by the
+/// order book authority PDA, so neither the series nor its pool can move
+/// the tokens locked by orders.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct OrderBook {
+    /// Is the order book initialized
+    pub is_initialized: bool,
+
+    /// Bump seed of the order book authority PDA
+    pub bump_seed: u8,
+
+    /// Series whose options are traded
+    pub series: Pubkey,
+
+    /// Event queue fills are pushed to
+    pub event_queue: Pubkey,
+
+    /// Vault of the option tokens locked by asks and owed to buyers
+    pub option_vault: Pubkey,
+
+    /// Vault of the quote tokens locked by bids and owed to sellers
+    pub quote_vault: Pubkey,
+
+    /// Sequence number of the next order
+    pub next_order_id: u64,
+
+    /// Resting bids, best first
+    pub bids: Vec<Order>,
+
+    /// Resting asks, best first
+    pub asks: Vec<Order>,
+}
+
+impl OrderBook {
+    /// Returns the account size needed for full slabs
+    pub fn get_size() -> usize {
+        // is_initialized and bump_seed (2 bytes)
+        // series, event_queue and vaults (4 * 32 bytes)
+        // next_order_id (8 bytes)
+        // bids and asks (2 * (4 + MAX_ORDERS_PER_SIDE * Order::LEN) bytes)
+        2 + 4 * 32 + 8 + 2 * (4 + MAX_ORDERS_PER_SIDE * Order::LEN)
+    }
+
+    /// Returns the orders on one side
+    pub fn side(&self, side: Side) -> &Vec<Order> {
+        match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        }
+    }
+
+    fn side_mut(&mut self, side: Side) -> &mut Vec<Order> {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        }
+    }
+
+    /// Adds a new order behind every order at the same or a better price
+    /// and returns its id
+    ///
+    /// On a full side the new order must beat the worst resting order,
+    /// which is removed and returned so its owner can be credited.
+    pub fn insert(
+        &mut self,
+        side: Side,
+        open_orders: Pubkey,
+        price: u64,
+        quantity: u64,
+    ) -> Result<(u64, Option<Order>), TemplateError> {
+        let order_id = self.next_order_id;
+        let orders = self.side_mut(side);
+        let mut evicted = None;
+        if orders.len() >= MAX_ORDERS_PER_SIDE {
+            let worst = orders.last().ok_or(TemplateError::OrderBookFull)?;
+            let is_better = match side {
+                Side::Bid => price > worst.price,
+                Side::Ask => price < worst.price,
+            };
+            if !is_better {
+                return Err(TemplateError::OrderBookFull);
+            }
+            evicted = orders.pop();
+        }
+        let position = orders
+            .iter()
+            .position(|order| match side {
+                Side::Bid => order.price < price,
+                Side::Ask => order.price > price,
+            })
+            .unwrap_or(orders.len());
+        orders.insert(
+            position,
+            Order {
+                order_id,
+                open_orders,
+                price,
+                quantity,
+            },
+        );
+        self.next_order_id = order_id.checked_add(1).ok_or(TemplateError::MathOverflow)?;
+        Ok((order_id, evicted))
+    }
+
+    /// Removes an order and returns it
+    pub fn remove(&mut self, side: Side, order_id: u64) -> Result<Order, TemplateError> {
+        let orders = self.side_mut(side);
+        let position = orders
+            .iter()
+            .position(|order| order.order_id == order_id)
+            .ok_or(TemplateError::OrderNotFound)?;
+        Ok(orders.remove(position))
+    }
+
+    /// Is the best bid at or above the best ask
+    pub fn is_crossed(&self) -> bool {
+        match (self.bids.first(), self.asks.first()) {
+            (Some(bid), Some(ask)) => bid.price >= ask.price,
+            _ => false,
+        }
+    }
+
+    /// Crosses the best bid and ask into fills until the book no longer
+    /// crosses, `limit` fills were made or the event queue is full, and
+    /// returns the number of fills
+    ///
+    /// Each fill trades at the price of the older of the two orders.
+    pub fn match_orders(&mut self, event_queue: &mut EventQueue, limit: usize) -> usize {
+        let mut fills = 0;
+        while fills < limit && event_queue.events.len() < MAX_EVENTS && self.is_crossed() {
+            let (bid, ask) = (&mut self.bids[0], &mut self.asks[0]);
+            let quantity = bid.quantity.min(ask.quantity);
+            let price = if bid.order_id < ask.order_id {
+                bid.price
+            } else {
+                ask.price
+            };
+            event_queue.events.push(FillEvent {
+                buyer: bid.open_orders,
+                seller: ask.open_orders,
+                quantity,
+                price,
+                bid_price: bid.price,
+            });
+            bid.quantity -= quantity;
+            ask.quantity -= quantity;
+            let (bid_filled, ask_filled) = (bid.quantity == 0, ask.quantity == 0);
+            if bid_filled {
+                self.bids.remove(0);
+            }
+            if ask_filled {
+                self.asks.remove(0);
+            }
+            fills += 1;
+        }
+        fills
+    }
+}
+
+/// Trade between a bid and an ask waiting to be credited to both traders
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FillEvent {
+    /// Open orders account of the bid
+    pub buyer: Pubkey,
+
+    /// Open orders account of the ask
+    pub seller: Pubkey,
+
+    /// Options traded
+    pub quantity: u64,
+
+    /// Quote tokens per option the trade executed at
+    pub price: u64,
+
+    /// Quote tokens per option the bid locked
+    pub bid_price: u64,
+}
+
+impl FillEvent {
+    /// Serialized size of a fill event
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8;
+
+    /// Quote tokens the seller receives
+    pub fn quote_amount(&self) -> Result<u64, TemplateError> {
+        self.price
+            .checked_mul(self.quantity)
+            .ok_or(TemplateError::MathOverflow)
+    }
+
+    /// Quote tokens the bid locked above the execution price, returned to the buyer
+    pub fn price_improvement(&self) -> Result<u64, TemplateError> {
+        (self.bid_price - self.price)
+            .checked_mul(self.quantity)
+            .ok_or(TemplateError::MathOverflow)
+    }
+}
+
+/// Queue of fills, oldest first
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct EventQueue {
+    /// Is the event queue initialized
+    pub is_initialized: bool,
+
+    /// Order book the queue belongs to
+    pub order_book: Pubkey,
+
+    /// Unconsumed fills
+    pub events: Vec<FillEvent>,
+}
+
+impl EventQueue {
+    /// Returns the account size needed for a full queue
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte)
+        // order_book (32 bytes)
+        // events (4 + MAX_EVENTS * FillEvent::LEN bytes)
+        1 + 32 + 4 + MAX_EVENTS * FillEvent::LEN
+    }
+}
+
+/// Tokens an order book owes one trader
+///
+/// Cancelled orders and consumed fills are credited here, and the owner
+/// withdraws the balances with `SettleFunds`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct OpenOrders {
+    /// Is the account initialized
+    pub is_initialized: bool,
+
+    /// Order book the account trades on
+    pub order_book: Pubkey,
+
+    /// Wallet that places orders and settles funds
+    pub owner: Pubkey,
+
+    /// Options the owner can withdraw
+    pub base_free: u64,
+
+    /// Quote tokens the owner can withdraw
+    pub quote_free: u64,
+}
+
+impl OpenOrders {
+    /// Returns the account size
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte)
+        // order_book and owner (2 * 32 bytes)
+        // base_free and quote_free (2 * 8 bytes)
+        1 + 2 * 32 + 2 * 8
+    }
+
+    /// Credits what a cancelled or evicted order locked
+    pub fn credit_order(&mut self, side: Side, order: &Order) -> Result<(), TemplateError> {
+        let locked_amount = order.locked_amount(side)?;
+        match side {
+            Side::Bid => self.credit(0, locked_amount),
+            Side::Ask => self.credit(locked_amount, 0),
+        }
+    }
+
+    /// Credits the tokens a filled or cancelled order hands back
+    pub fn credit(&mut self, base_amount: u64, quote_amount: u64) -> Result<(), TemplateError> {
+        self.base_free = self
+            .base_free
+            .checked_add(base_amount)
+            .ok_or(TemplateError::MathOverflow)?;
+        self.quote_free = self
+            .quote_free
+            .checked_add(quote_amount)
+            .ok_or(TemplateError::MathOverflow)?;
+        Ok(())
+    }
+}
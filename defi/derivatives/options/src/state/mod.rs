@@ -1,20 +1,233 @@
-//! State objects for template account
+//! State objects for option series
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::pubkey::Pubkey;
 
-/// Template account data
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-pub struct TemplateAccount {
-    /// The account authority (owner)
-    pub authority: Pubkey,
-    
-    /// Example value 1
-    pub value1: u64,
-    
-    /// Example value 2
-    pub value2: u8,
-    
-    /// Is the account initialized
+use crate::{
+    errors::TemplateError,
+    utils::black_scholes::{black_scholes, BlackScholesInputs, SECONDS_PER_YEAR, WAD},
+};
+
+/// Length of the window before expiry in which options can be exercised, in seconds
+pub const EXERCISE_WINDOW: i64 = 86_400;
+
+/// Latest a settlement price may be published after expiry, in seconds
+pub const MAX_SETTLEMENT_DELAY: i64 = 3_600;
+
+/// Oldest oracle price the option pool trades against, in seconds
+pub const POOL_PRICE_MAX_AGE: i64 = 60;
+
+/// Denominator of the option pool spread
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Right an option gives its holder
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OptionKind {
+    /// Buy `contract_size` of the underlying for `strike` of the quote token
+    #[default]
+    Call,
+    /// Sell `contract_size` of the underlying for `strike` of the quote token
+    Put,
+}
+
+/// How an option series settles
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Settlement {
+    /// Holders exercise by exchanging the payment for the collateral
+    #[default]
+    Physical,
+    /// Holders claim the intrinsic value in the quote token at a settlement price
+    Cash,
+}
+
+/// European-style option series
+///
+/// Each option covers `contract_size` base units of the underlying at a
+/// price of `strike` base units of the quote token. Calls are covered by the
+/// underlying and puts are secured by the quote token, both held in vaults
+/// owned by the series authority PDA. Options can be exercised in the
+/// `EXERCISE_WINDOW` before `expiry`; from `expiry` on, writer tokens redeem
+/// a pro-rata share of both vaults.
+///
+/// Cash-settled series lock `max_payoff` of the quote token per option
+/// instead. After expiry the settlement price is read from `price_account`
+/// once, and each option then pays out `settlement_payoff` while each writer
+/// token redeems the rest of the `max_payoff` it locked.
+///
+/// A physically settled series can also have an option pool that sells
+/// options from and buys options into its own vaults at the Black-Scholes
+/// price for `implied_volatility`, plus or minus `pool_spread_bps`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct OptionSeries {
+    /// Is the series initialized
     pub is_initialized: bool,
+
+    /// Bump seed of the series authority PDA
+    pub bump_seed: u8,
+
+    /// Account that initialized the series and can open its option pool
+    pub creator: Pubkey,
+
+    /// Call or put
+    pub kind: OptionKind,
+
+    /// Mint of the underlying token
+    pub underlying_mint: Pubkey,
+
+    /// Mint of the quote token the strike is paid in
+    pub quote_mint: Pubkey,
+
+    /// Quote tokens exchanged for one option's underlying on exercise
+    pub strike: u64,
+
+    /// Underlying tokens covered by one option
+    pub contract_size: u64,
+
+    /// Unix timestamp the series expires at
+    pub expiry: i64,
+
+    /// Mint of the option tokens held by buyers
+    pub option_mint: Pubkey,
+
+    /// Mint of the writer tokens that claim the collateral after expiry
+    pub writer_mint: Pubkey,
+
+    /// Vault of the underlying token, owned by the series authority
+    pub underlying_vault: Pubkey,
+
+    /// Vault of the quote token, owned by the series authority
+    pub quote_vault: Pubkey,
+
+    /// Physical or cash settlement
+    pub settlement: Settlement,
+
+    /// Price account settlement and the option pool read from, if any
+    pub price_account: Pubkey,
+
+    /// Decimals of the underlying mint
+    pub underlying_decimals: u8,
+
+    /// Decimals of the quote mint
+    pub quote_decimals: u8,
+
+    /// Quote tokens locked per option and the most one option pays out (cash settlement only)
+    pub max_payoff: u64,
+
+    /// Has the settlement price been recorded
+    pub is_settled: bool,
+
+    /// Quote tokens `contract_size` of the underlying was worth at expiry
+    pub settlement_price: u64,
+
+    /// Does the series have an option pool
+    pub has_pool: bool,
+
+    /// Account allowed to update and withdraw from the option pool
+    pub pool_admin: Pubkey,
+
+    /// Vault of the option tokens the pool sells, owned by the series authority
+    pub pool_option_vault: Pubkey,
+
+    /// Vault of the quote tokens the pool pays premiums from, owned by the series authority
+    pub pool_premium_vault: Pubkey,
+
+    /// Annualized implied volatility the pool prices options at, in `WAD` units
+    pub implied_volatility: u64,
+
+    /// Spread the pool charges on each side of the model price, in basis points
+    pub pool_spread_bps: u64,
+}
+
+impl OptionSeries {
+    /// Get the size of OptionSeries struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + bump_seed (1 byte) + creator (32 bytes) +
+        // kind (1 byte) + underlying and quote mints (32 bytes * 2) + strike (8 bytes) +
+        // contract_size (8 bytes) + expiry (8 bytes) +
+        // option and writer mints (32 bytes * 2) + vaults (32 bytes * 2) +
+        // settlement (1 byte) + price_account (32 bytes) + decimals (2 bytes) +
+        // max_payoff (8 bytes) + is_settled (1 byte) + settlement_price (8 bytes) +
+        // has_pool (1 byte) + pool_admin and pool vaults (32 bytes * 3) +
+        // implied_volatility (8 bytes) + pool_spread_bps (8 bytes)
+        let settlement = 1 + 32 + 2 + 8 + 1 + 8;
+        let pool = 1 + 32 * 3 + 8 + 8;
+        1 + 1 + 32 + 1 + 32 * 2 + 8 + 8 + 8 + 32 * 2 + 32 * 2 + settlement + pool
+    }
+
+    /// Collateral locked per option written, in the collateral token
+    pub fn collateral_per_option(&self) -> u64 {
+        if self.settlement == Settlement::Cash {
+            return self.max_payoff;
+        }
+        match self.kind {
+            OptionKind::Call => self.contract_size,
+            OptionKind::Put => self.strike,
+        }
+    }
+
+    /// Payment per option exercised, in the payment token
+    pub fn payment_per_option(&self) -> u64 {
+        match self.kind {
+            OptionKind::Call => self.strike,
+            OptionKind::Put => self.contract_size,
+        }
+    }
+
+    /// Vault holding the writers' collateral
+    pub fn collateral_vault(&self) -> &Pubkey {
+        if self.settlement == Settlement::Cash {
+            return &self.quote_vault;
+        }
+        match self.kind {
+            OptionKind::Call => &self.underlying_vault,
+            OptionKind::Put => &self.quote_vault,
+        }
+    }
+
+    /// Vault receiving exercise payments
+    pub fn payment_vault(&self) -> &Pubkey {
+        match self.kind {
+            OptionKind::Call => &self.quote_vault,
+            OptionKind::Put => &self.underlying_vault,
+        }
+    }
+
+    /// Can options be exercised at `now`
+    pub fn is_exercisable(&self, now: i64) -> bool {
+        now >= self.expiry.saturating_sub(EXERCISE_WINDOW) && now < self.expiry
+    }
+
+    /// Has the series expired at `now`
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.expiry
+    }
+
+    /// Quote tokens one option pays out at the settlement price, capped at `max_payoff`
+    pub fn settlement_payoff(&self) -> u64 {
+        let intrinsic_value = match self.kind {
+            OptionKind::Call => self.settlement_price.saturating_sub(self.strike),
+            OptionKind::Put => self.strike.saturating_sub(self.settlement_price),
+        };
+        intrinsic_value.min(self.max_payoff)
+    }
+
+    /// Black-Scholes value of one option at `now` at the pool's implied
+    /// volatility, rounded down
+    ///
+    /// `spot` is the value of `contract_size` of the underlying in the quote
+    /// token. The risk-free rate is taken to be zero.
+    pub fn model_premium(&self, spot: u64, now: i64) -> Result<u64, TemplateError> {
+        if self.is_expired(now) {
+            return Err(TemplateError::SeriesExpired);
+        }
+        let inputs = BlackScholesInputs {
+            spot: spot as i128 * WAD,
+            strike: self.strike as i128 * WAD,
+            time: (self.expiry - now) as i128 * WAD / SECONDS_PER_YEAR,
+            volatility: self.implied_volatility as i128,
+            rate: 0,
+        };
+        let greeks = black_scholes(self.kind, &inputs)?;
+        u64::try_from(greeks.price / WAD).map_err(|_| TemplateError::MathOverflow)
+    }
 }
@@ -0,0 +1,290 @@
+//! Fixed-point Black-Scholes pricing
+//!
+//! Every value is a signed fixed-point number with 18 decimals, so `WAD`
+//! stands for 1.0. Times are in years and rates and volatilities are
+//! annualized, so a volatility of `WAD / 2` is 50%.
+
+use crate::{errors::TemplateError, state::OptionKind};
+
+/// Fixed-point representation of 1.0
+pub const WAD: i128 = 1_000_000_000_000_000_000;
+
+/// ln(2)
+pub const LN_2: i128 = 693_147_180_559_945_309;
+
+/// sqrt(2 * pi)
+pub const SQRT_2_PI: i128 = 2_506_628_274_631_000_502;
+
+/// Seconds in the 365-day year times are measured in
+pub const SECONDS_PER_YEAR: i128 = 31_536_000;
+
+/// Largest argument of `exp` whose result fits in an i128
+const MAX_EXP_ARGUMENT: i128 = 46 * WAD;
+
+/// Arguments below which `exp` rounds to zero
+const MIN_EXP_ARGUMENT: i128 = -42 * WAD;
+
+// Coefficients of the Abramowitz and Stegun 26.2.17 approximation of the
+// normal CDF, which is accurate to 7.5e-8
+const CDF_P: i128 = 231_641_900_000_000_000;
+const CDF_B1: i128 = 319_381_530_000_000_000;
+const CDF_B2: i128 = -356_563_782_000_000_000;
+const CDF_B3: i128 = 1_781_477_937_000_000_000;
+const CDF_B4: i128 = -1_821_255_978_000_000_000;
+const CDF_B5: i128 = 1_330_274_429_000_000_000;
+
+/// Multiplies two fixed-point numbers, rounding toward zero
+pub fn mul(a: i128, b: i128) -> Result<i128, TemplateError> {
+    // Split both operands into integer and fractional parts so that no
+    // partial product overflows unless the result itself does
+    let (a_high, a_low) = (a / WAD, a % WAD);
+    let (b_high, b_low) = (b / WAD, b % WAD);
+    let high = a_high
+        .checked_mul(b_high)
+        .and_then(|product| product.checked_mul(WAD))
+        .ok_or(TemplateError::MathOverflow)?;
+    let cross = a_high
+        .checked_mul(b_low)
+        .and_then(|product| product.checked_add(a_low.checked_mul(b_high)?))
+        .ok_or(TemplateError::MathOverflow)?;
+    high.checked_add(cross)
+        .and_then(|sum| sum.checked_add(a_low * b_low / WAD))
+        .ok_or(TemplateError::MathOverflow)
+}
+
+/// Divides two fixed-point numbers, rounding toward zero
+pub fn div(a: i128, b: i128) -> Result<i128, TemplateError> {
+    if b == 0 {
+        return Err(TemplateError::MathOverflow);
+    }
+    let quotient = a / b;
+    let remainder = a % b;
+    if let Some(scaled) = remainder.checked_mul(WAD) {
+        return quotient
+            .checked_mul(WAD)
+            .and_then(|whole| whole.checked_add(scaled / b))
+            .ok_or(TemplateError::MathOverflow);
+    }
+
+    // Long division one decimal digit at a time for large divisors
+    let (mut quotient, mut remainder) = (quotient, remainder);
+    for _ in 0..18 {
+        remainder = remainder
+            .checked_mul(10)
+            .ok_or(TemplateError::MathOverflow)?;
+        quotient = quotient
+            .checked_mul(10)
+            .and_then(|shifted| shifted.checked_add(remainder / b))
+            .ok_or(TemplateError::MathOverflow)?;
+        remainder %= b;
+    }
+    Ok(quotient)
+}
+
+/// Computes e^x
+///
+/// The argument is reduced to `x = k * ln(2) + r` with `|r| <= ln(2) / 2`,
+/// e^r is summed as a Taylor series and the result is shifted by `k`.
+pub fn exp(x: i128) -> Result<i128, TemplateError> {
+    if x > MAX_EXP_ARGUMENT {
+        return Err(TemplateError::MathOverflow);
+    }
+    if x < MIN_EXP_ARGUMENT {
+        return Ok(0);
+    }
+
+    let k = (x + LN_2 / 2).div_euclid(LN_2);
+    let r = x - k * LN_2;
+
+    let mut sum = WAD;
+    let mut term = WAD;
+    let mut n = 1;
+    while term != 0 {
+        term = mul(term, r)? / n;
+        sum += term;
+        n += 1;
+    }
+
+    Ok(if k >= 0 { sum << k } else { sum >> -k })
+}
+
+/// Computes the natural logarithm of a positive `x`
+///
+/// `x` is scaled by a power of two into `m` in [1, 2), and ln(m) is summed
+/// as the series `2 * atanh((m - 1) / (m + 1))`.
+pub fn ln(x: i128) -> Result<i128, TemplateError> {
+    if x <= 0 {
+        return Err(TemplateError::InvalidPricingInput);
+    }
+
+    let mut k = 0;
+    let mut m = x;
+    while m >= 2 * WAD {
+        m >>= 1;
+        k += 1;
+    }
+    while m < WAD {
+        m <<= 1;
+        k -= 1;
+    }
+
+    let z = div(m - WAD, m + WAD)?;
+    let z_squared = mul(z, z)?;
+    let mut sum = 0;
+    let mut term = z;
+    let mut n = 1;
+    while term != 0 {
+        sum += term / n;
+        term = mul(term, z_squared)?;
+        n += 2;
+    }
+
+    Ok(k * LN_2 + 2 * sum)
+}
+
+/// Computes the square root of a non-negative `x`, rounding down
+pub fn sqrt(x: i128) -> Result<i128, TemplateError> {
+    if x < 0 {
+        return Err(TemplateError::InvalidPricingInput);
+    }
+    if x == 0 {
+        return Ok(0);
+    }
+
+    // sqrt(x / WAD) * WAD is the integer root of x * WAD. Large inputs are
+    // scaled by the largest even power of ten that fits instead, and the
+    // root is scaled back by the remaining half power.
+    let mut n = x as u128;
+    let mut half_powers = 9;
+    while half_powers > 0 && n <= u128::MAX / 100 {
+        n *= 100;
+        half_powers -= 1;
+    }
+
+    // Newton's method from a power of two above the root
+    let mut root = 1u128 << (n.ilog2() / 2 + 1);
+    loop {
+        let next = (root + n / root) / 2;
+        if next >= root {
+            break;
+        }
+        root = next;
+    }
+    (root as i128)
+        .checked_mul(10i128.pow(half_powers))
+        .ok_or(TemplateError::MathOverflow)
+}
+
+/// Computes the standard normal probability density at `x`
+pub fn normal_pdf(x: i128) -> Result<i128, TemplateError> {
+    div(exp(-mul(x, x)? / 2)?, SQRT_2_PI)
+}
+
+/// Computes the standard normal cumulative distribution at `x`
+pub fn normal_cdf(x: i128) -> Result<i128, TemplateError> {
+    let a = x.abs();
+    let t = div(WAD, WAD + mul(CDF_P, a)?)?;
+    let mut polynomial = CDF_B5;
+    for coefficient in [CDF_B4, CDF_B3, CDF_B2, CDF_B1] {
+        polynomial = coefficient + mul(t, polynomial)?;
+    }
+    polynomial = mul(t, polynomial)?;
+
+    let upper = WAD - mul(normal_pdf(a)?, polynomial)?;
+    Ok(if x >= 0 { upper } else { WAD - upper })
+}
+
+/// Inputs of the Black-Scholes model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlackScholesInputs {
+    /// Price of the underlying
+    pub spot: i128,
+
+    /// Strike price, in the same unit as `spot`
+    pub strike: i128,
+
+    /// Time to expiry in years
+    pub time: i128,
+
+    /// Annualized volatility of the underlying
+    pub volatility: i128,
+
+    /// Annualized continuously compounded risk-free rate
+    pub rate: i128,
+}
+
+/// Option price and sensitivities
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptionGreeks {
+    /// Fair price, in the unit of the spot price
+    pub price: i128,
+
+    /// Change in price per unit change in spot
+    pub delta: i128,
+
+    /// Change in delta per unit change in spot
+    pub gamma: i128,
+
+    /// Change in price per 1.0 (100 percentage points) change in volatility
+    pub vega: i128,
+
+    /// Change in price per year of time decay
+    pub theta: i128,
+}
+
+/// Prices a European option and its Greeks with the Black-Scholes model
+pub fn black_scholes(
+    kind: OptionKind,
+    inputs: &BlackScholesInputs,
+) -> Result<OptionGreeks, TemplateError> {
+    let BlackScholesInputs {
+        spot,
+        strike,
+        time,
+        volatility,
+        rate,
+    } = *inputs;
+    if spot <= 0 || strike <= 0 || time <= 0 || volatility <= 0 {
+        return Err(TemplateError::InvalidPricingInput);
+    }
+
+    let sqrt_time = sqrt(time)?;
+    let volatility_sqrt_time = mul(volatility, sqrt_time)?;
+    let drift = mul(rate + mul(volatility, volatility)? / 2, time)?;
+    let d1 = div(ln(div(spot, strike)?)? + drift, volatility_sqrt_time)?;
+    let d2 = d1 - volatility_sqrt_time;
+
+    let discounted_strike = mul(strike, exp(-mul(rate, time)?)?)?;
+    let pdf_d1 = normal_pdf(d1)?;
+    let spot_pdf_d1 = mul(spot, pdf_d1)?;
+    let gamma = div(pdf_d1, mul(spot, volatility_sqrt_time)?)?;
+    let vega = mul(spot_pdf_d1, sqrt_time)?;
+    let time_decay = -div(mul(spot_pdf_d1, volatility)?, 2 * sqrt_time)?;
+
+    let greeks = match kind {
+        OptionKind::Call => {
+            let n_d1 = normal_cdf(d1)?;
+            let n_d2 = normal_cdf(d2)?;
+            let discounted_payment = mul(discounted_strike, n_d2)?;
+            OptionGreeks {
+                price: (mul(spot, n_d1)? - discounted_payment).max(0),
+                delta: n_d1,
+                gamma,
+                vega,
+                theta: time_decay - mul(rate, discounted_payment)?,
+            }
+        }
+        OptionKind::Put => {
+            let n_minus_d2 = normal_cdf(-d2)?;
+            let discounted_payment = mul(discounted_strike, n_minus_d2)?;
+            OptionGreeks {
+                price: (discounted_payment - mul(spot, normal_cdf(-d1)?)?).max(0),
+                delta: normal_cdf(d1)? - WAD,
+                gamma,
+                vega,
+                theta: time_decay + mul(rate, discounted_payment)?,
+            }
+        }
+    };
+    Ok(greeks)
+}
@@ -1,9 +1,9 @@
 //! Utility functions for the program
 
+pub mod black_scholes;
+
 use solana_program::{
-    account_info::AccountInfo,
-    entrypoint::ProgramResult,
-    program_error::ProgramError,
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
     pubkey::Pubkey,
 };
 
@@ -31,3 +31,48 @@ pub fn check_signer(account_info: &AccountInfo) -> ProgramResult {
 pub fn safe_addition(a: u64, b: u64) -> Result<u64, TemplateError> {
     a.checked_add(b).ok_or(TemplateError::MathOverflow)
 }
+
+/// Safely performs a mathematical multiplication that errors on overflow
+pub fn safe_multiplication(a: u64, b: u64) -> Result<u64, TemplateError> {
+    a.checked_mul(b).ok_or(TemplateError::MathOverflow)
+}
+
+/// Finds the series authority PDA that owns the vaults and both mints
+pub fn find_series_authority(program_id: &Pubkey, series: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[series.as_ref()], program_id)
+}
+
+/// Checks that `authority` is the series authority PDA for the given bump seed
+pub fn check_series_authority(
+    program_id: &Pubkey,
+    series: &Pubkey,
+    bump_seed: u8,
+    authority: &Pubkey,
+) -> ProgramResult {
+    let expected = Pubkey::create_program_address(&[series.as_ref(), &[bump_seed]], program_id)
+        .map_err(|_| TemplateError::InvalidSeriesAuthority)?;
+    if expected != *authority {
+        return Err(TemplateError::InvalidSeriesAuthority.into());
+    }
+    Ok(())
+}
+
+/// Finds the order book authority PDA that owns the order book vaults
+pub fn find_order_book_authority(program_id: &Pubkey, order_book: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[order_book.as_ref()], program_id)
+}
+
+/// Checks that `authority` is the order book authority PDA for the given bump seed
+pub fn check_order_book_authority(
+    program_id: &Pubkey,
+    order_book: &Pubkey,
+    bump_seed: u8,
+    authority: &Pubkey,
+) -> ProgramResult {
+    let expected = Pubkey::create_program_address(&[order_book.as_ref(), &[bump_seed]], program_id)
+        .map_err(|_| TemplateError::InvalidOrderBookAuthority)?;
+    if expected != *authority {
+        return Err(TemplateError::InvalidOrderBookAuthority.into());
+    }
+    Ok(())
+}
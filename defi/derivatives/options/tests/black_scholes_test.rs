@@ -0,0 +1,294 @@
+//! Accuracy tests of the fixed-point Black-Scholes module against f64 references
+
+#[cfg(test)]
+mod tests {
+    use options_protocol::{
+        errors::TemplateError,
+        state::OptionKind,
+        utils::black_scholes::{
+            black_scholes, exp, ln, normal_cdf, normal_pdf, sqrt, BlackScholesInputs, WAD,
+        },
+    };
+
+    fn to_wad(x: f64) -> i128 {
+        (x * 1e18).round() as i128
+    }
+
+    fn from_wad(x: i128) -> f64 {
+        x as f64 / 1e18
+    }
+
+    /// Asserts `actual` is within `relative * |expected| + absolute` of `expected`
+    fn assert_close(actual: f64, expected: f64, relative: f64, absolute: f64, what: &str) {
+        let tolerance = relative * expected.abs() + absolute;
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{what}: got {actual}, expected {expected}, tolerance {tolerance}"
+        );
+    }
+
+    fn reference_pdf(x: f64) -> f64 {
+        (-x * x / 2.0).exp() / (2.0 * std::f64::consts::PI).sqrt()
+    }
+
+    /// Normal CDF from the series `0.5 + pdf(x) * sum(x^(2n+1) / (2n+1)!!)`,
+    /// whose terms are all positive so it stays accurate in f64
+    fn reference_cdf(x: f64) -> f64 {
+        let mut term = x;
+        let mut sum = x;
+        let mut n = 1.0;
+        while term.abs() > 1e-20 * sum.abs() {
+            n += 2.0;
+            term *= x * x / n;
+            sum += term;
+        }
+        (0.5 + reference_pdf(x) * sum).clamp(0.0, 1.0)
+    }
+
+    /// Reference Black-Scholes price, delta, gamma, vega and theta
+    fn reference_black_scholes(
+        kind: OptionKind,
+        spot: f64,
+        strike: f64,
+        time: f64,
+        volatility: f64,
+        rate: f64,
+    ) -> [f64; 5] {
+        let sqrt_time = time.sqrt();
+        let d1 = ((spot / strike).ln() + (rate + volatility * volatility / 2.0) * time)
+            / (volatility * sqrt_time);
+        let d2 = d1 - volatility * sqrt_time;
+        let discounted_strike = strike * (-rate * time).exp();
+        let gamma = reference_pdf(d1) / (spot * volatility * sqrt_time);
+        let vega = spot * reference_pdf(d1) * sqrt_time;
+        let decay = -spot * reference_pdf(d1) * volatility / (2.0 * sqrt_time);
+        match kind {
+            OptionKind::Call => [
+                spot * reference_cdf(d1) - discounted_strike * reference_cdf(d2),
+                reference_cdf(d1),
+                gamma,
+                vega,
+                decay - rate * discounted_strike * reference_cdf(d2),
+            ],
+            OptionKind::Put => [
+                discounted_strike * reference_cdf(-d2) - spot * reference_cdf(-d1),
+                reference_cdf(d1) - 1.0,
+                gamma,
+                vega,
+                decay + rate * discounted_strike * reference_cdf(-d2),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_exp() {
+        for x in [
+            -41.0, -20.0, -5.0, -1.0, -0.5, -1e-9, 0.0, 0.3, 1.0, 2.5, 10.0, 45.0,
+        ] {
+            assert_close(
+                from_wad(exp(to_wad(x)).unwrap()),
+                x.exp(),
+                1e-14,
+                1e-17,
+                &format!("exp({x})"),
+            );
+        }
+        assert_eq!(exp(0), Ok(WAD));
+        assert_eq!(exp(-50 * WAD), Ok(0));
+        assert_eq!(exp(47 * WAD), Err(TemplateError::MathOverflow));
+    }
+
+    #[test]
+    fn test_ln() {
+        for x in [
+            1e-15, 1e-9, 0.001, 0.5, 0.999, 1.0, 1.001, 2.0, 3.5, 1000.0, 1e9, 1e18,
+        ] {
+            assert_close(
+                from_wad(ln(to_wad(x)).unwrap()),
+                x.ln(),
+                1e-14,
+                1e-16,
+                &format!("ln({x})"),
+            );
+        }
+        assert_eq!(ln(WAD), Ok(0));
+        assert_eq!(ln(0), Err(TemplateError::InvalidPricingInput));
+        assert_eq!(ln(-WAD), Err(TemplateError::InvalidPricingInput));
+    }
+
+    #[test]
+    fn test_exp_ln_round_trip() {
+        for x in [-10.0, -0.1, 0.7, 5.0, 30.0] {
+            let round_trip = ln(exp(to_wad(x)).unwrap()).unwrap();
+            assert_close(
+                from_wad(round_trip),
+                x,
+                1e-14,
+                1e-15,
+                &format!("ln(exp({x}))"),
+            );
+        }
+    }
+
+    #[test]
+    fn test_sqrt() {
+        for x in [0.0, 1e-12, 0.25, 1.0, 2.0, 144.0, 1e12] {
+            assert_close(
+                from_wad(sqrt(to_wad(x)).unwrap()),
+                x.sqrt(),
+                1e-15,
+                1e-18,
+                &format!("sqrt({x})"),
+            );
+        }
+        assert_eq!(sqrt(4 * WAD), Ok(2 * WAD));
+        assert_eq!(sqrt(-1), Err(TemplateError::InvalidPricingInput));
+    }
+
+    #[test]
+    fn test_normal_pdf_and_cdf() {
+        let mut x = -8.0;
+        while x <= 8.0 {
+            assert_close(
+                from_wad(normal_pdf(to_wad(x)).unwrap()),
+                reference_pdf(x),
+                1e-14,
+                1e-17,
+                &format!("pdf({x})"),
+            );
+            // Abramowitz and Stegun 26.2.17 is accurate to 7.5e-8
+            assert_close(
+                from_wad(normal_cdf(to_wad(x)).unwrap()),
+                reference_cdf(x),
+                0.0,
+                7.5e-8,
+                &format!("cdf({x})"),
+            );
+            x += 0.125;
+        }
+        for x in [WAD / 3, 2 * WAD, 7 * WAD] {
+            assert_eq!(normal_cdf(x).unwrap() + normal_cdf(-x).unwrap(), WAD);
+        }
+    }
+
+    #[test]
+    fn test_black_scholes_matches_reference() {
+        // (spot, strike, time, volatility, rate)
+        let cases = [
+            (100.0, 100.0, 1.0, 0.2, 0.05),
+            (100.0, 120.0, 0.25, 0.3, 0.01),
+            (100.0, 80.0, 0.5, 0.5, 0.0),
+            (2_600.0, 3_000.0, 7.0 / 365.0, 0.8, 0.0),
+            (0.35, 0.4, 30.0 / 365.0, 1.2, 0.03),
+            (42_000.0, 40_000.0, 2.0, 0.65, 0.04),
+            (100.0, 100.0, 1.0 / 365.0 / 24.0, 0.5, 0.0),
+        ];
+        for (spot, strike, time, volatility, rate) in cases {
+            for kind in [OptionKind::Call, OptionKind::Put] {
+                let inputs = BlackScholesInputs {
+                    spot: to_wad(spot),
+                    strike: to_wad(strike),
+                    time: to_wad(time),
+                    volatility: to_wad(volatility),
+                    rate: to_wad(rate),
+                };
+                let greeks = black_scholes(kind, &inputs).unwrap();
+                let [price, delta, gamma, vega, theta] =
+                    reference_black_scholes(kind, spot, strike, time, volatility, rate);
+                let case = format!("{kind:?} {spot} {strike} {time} {volatility} {rate}");
+
+                // CDF errors move the price by up to 7.5e-8 of the spot and strike
+                assert_close(
+                    from_wad(greeks.price),
+                    price,
+                    1e-9,
+                    2e-7 * spot.max(strike),
+                    &format!("price {case}"),
+                );
+                assert_close(
+                    from_wad(greeks.delta),
+                    delta,
+                    0.0,
+                    1e-7,
+                    &format!("delta {case}"),
+                );
+                assert_close(
+                    from_wad(greeks.gamma),
+                    gamma,
+                    1e-9,
+                    1e-15,
+                    &format!("gamma {case}"),
+                );
+                assert_close(
+                    from_wad(greeks.vega),
+                    vega,
+                    1e-9,
+                    1e-12,
+                    &format!("vega {case}"),
+                );
+                assert_close(
+                    from_wad(greeks.theta),
+                    theta,
+                    1e-9,
+                    1e-7 * rate * strike + 1e-12,
+                    &format!("theta {case}"),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_put_call_parity() {
+        let inputs = BlackScholesInputs {
+            spot: to_wad(105.0),
+            strike: to_wad(100.0),
+            time: to_wad(0.75),
+            volatility: to_wad(0.35),
+            rate: to_wad(0.03),
+        };
+        let call = black_scholes(OptionKind::Call, &inputs).unwrap();
+        let put = black_scholes(OptionKind::Put, &inputs).unwrap();
+
+        // C - P = S - K * e^(-rT)
+        let forward_value = 105.0 - 100.0 * (-0.03f64 * 0.75).exp();
+        assert_close(
+            from_wad(call.price - put.price),
+            forward_value,
+            0.0,
+            1e-12,
+            "put-call parity",
+        );
+        assert_eq!(call.delta - put.delta, WAD);
+        assert_eq!(call.gamma, put.gamma);
+        assert_eq!(call.vega, put.vega);
+    }
+
+    #[test]
+    fn test_black_scholes_rejects_invalid_inputs() {
+        let valid = BlackScholesInputs {
+            spot: WAD,
+            strike: WAD,
+            time: WAD,
+            volatility: WAD / 2,
+            rate: 0,
+        };
+        assert!(black_scholes(OptionKind::Call, &valid).is_ok());
+        for inputs in [
+            BlackScholesInputs { spot: 0, ..valid },
+            BlackScholesInputs {
+                strike: -1,
+                ..valid
+            },
+            BlackScholesInputs { time: 0, ..valid },
+            BlackScholesInputs {
+                volatility: 0,
+                ..valid
+            },
+        ] {
+            assert_eq!(
+                black_scholes(OptionKind::Put, &inputs),
+                Err(TemplateError::InvalidPricingInput)
+            );
+        }
+    }
+}
@@ -0,0 +1,375 @@
+//! Fixtures shared by the options-protocol integration tests
+//!
+//! Every test binary compiles this module but uses only part of it.
+
+#![allow(dead_code)]
+
+use {
+    borsh::{BorshDeserialize, BorshSerialize},
+    options_protocol::{
+        errors::TemplateError,
+        instructions::processor::process_instruction_with_price_reader,
+        oracle::{OraclePrice, PriceReader},
+        process_instruction,
+        state::OptionSeries,
+        utils::find_series_authority,
+    },
+    solana_program::{
+        account_info::AccountInfo,
+        clock::Clock,
+        entrypoint::ProgramResult,
+        hash::Hash,
+        instruction::{Instruction, InstructionError},
+        program_error::ProgramError,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        rent::Rent,
+        system_instruction,
+    },
+    solana_program_test::{
+        processor, BanksClient, ProgramTest, ProgramTestBanksClientExt, ProgramTestContext,
+    },
+    solana_sdk::{
+        account::Account,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+pub const NOW: i64 = 1_700_000_000;
+pub const EXPIRY: i64 = NOW + 7 * 86_400;
+
+/// Price account format read by `FakeOracle`
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct FakePrice {
+    pub price: i64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+/// Reads a borsh-encoded `FakePrice`
+pub struct FakeOracle;
+
+impl PriceReader for FakeOracle {
+    fn read_price(price_info: &AccountInfo) -> Result<OraclePrice, ProgramError> {
+        let fake = FakePrice::try_from_slice(&price_info.data.borrow())
+            .map_err(|_| ProgramError::from(TemplateError::InvalidPriceAccount))?;
+        Ok(OraclePrice {
+            price: fake.price,
+            expo: fake.expo,
+            publish_time: fake.publish_time,
+        })
+    }
+}
+
+pub fn process_with_fake_oracle(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    process_instruction_with_price_reader::<FakeOracle>(program_id, accounts, instruction_data)
+}
+
+/// Program test reading prices from Pyth accounts
+pub fn program_test() -> (ProgramTest, Pubkey) {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        "options_protocol",
+        program_id,
+        processor!(process_instruction),
+    );
+    (program_test, program_id)
+}
+
+/// Program test reading prices with `FakeOracle`
+pub fn program_test_with_fake_oracle() -> (ProgramTest, Pubkey) {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        "options_protocol",
+        program_id,
+        processor!(process_with_fake_oracle),
+    );
+    (program_test, program_id)
+}
+
+/// Starts a test validator with the clock at `NOW`
+pub async fn start_at_now(program_test: ProgramTest) -> (ProgramTestContext, Keypair) {
+    let mut context = program_test.start_with_context().await;
+    set_timestamp(&mut context, NOW).await;
+    let payer = context.payer.insecure_clone();
+    (context, payer)
+}
+
+pub async fn process(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    process_with_blockhash(banks_client, payer, instructions, signers, recent_blockhash).await
+}
+
+/// Processes a transaction signed with `recent_blockhash`
+pub async fn process_with_blockhash(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+    recent_blockhash: Hash,
+) -> Result<(), TransactionError> {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &all_signers,
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+/// Returns a blockhash that no earlier transaction was signed with
+pub async fn new_blockhash(banks_client: &mut BanksClient) -> Hash {
+    let latest = banks_client.get_latest_blockhash().await.unwrap();
+    banks_client
+        .get_new_latest_blockhash(&latest)
+        .await
+        .unwrap()
+}
+
+pub async fn create_mint(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint_authority: &Pubkey,
+    decimals: u8,
+) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                &mint.pubkey(),
+                mint_authority,
+                None,
+                decimals,
+            )
+            .unwrap(),
+        ],
+        &[&mint],
+    )
+    .await
+    .unwrap();
+    mint.pubkey()
+}
+
+pub async fn create_token_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Pubkey {
+    let account = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &account.pubkey(),
+                mint,
+                owner,
+            )
+            .unwrap(),
+        ],
+        &[&account],
+    )
+    .await
+    .unwrap();
+    account.pubkey()
+}
+
+/// Creates a token account for `owner` holding `amount` tokens minted by the payer
+pub async fn create_funded_token_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let account = create_token_account(banks_client, payer, mint, owner).await;
+    process(
+        banks_client,
+        payer,
+        &[spl_token::instruction::mint_to(
+            &spl_token::id(),
+            mint,
+            &account,
+            &payer.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap()],
+        &[],
+    )
+    .await
+    .unwrap();
+    account
+}
+
+/// Creates an empty account of `size` bytes owned by the program
+pub async fn create_program_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    program_id: &Pubkey,
+    size: usize,
+) -> Pubkey {
+    let account = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[system_instruction::create_account(
+            &payer.pubkey(),
+            &account.pubkey(),
+            rent.minimum_balance(size),
+            size as u64,
+            program_id,
+        )],
+        &[&account],
+    )
+    .await
+    .unwrap();
+    account.pubkey()
+}
+
+pub async fn token_balance(banks_client: &mut BanksClient, account: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*account).await.unwrap().unwrap();
+    spl_token::state::Account::unpack(&account.data)
+        .unwrap()
+        .amount
+}
+
+pub async fn get_series(banks_client: &mut BanksClient, series: &Pubkey) -> OptionSeries {
+    let account = banks_client.get_account(*series).await.unwrap().unwrap();
+    OptionSeries::try_from_slice(&account.data).unwrap()
+}
+
+/// Moves the cluster clock to `unix_timestamp`
+pub async fn set_timestamp(context: &mut ProgramTestContext, unix_timestamp: i64) {
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = unix_timestamp;
+    context.set_sysvar(&clock);
+}
+
+pub fn custom_error(error: TemplateError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+/// Stores `data` in the account at `address`
+pub fn set_account_data(context: &mut ProgramTestContext, address: &Pubkey, data: Vec<u8>) {
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: Pubkey::new_unique(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    context.set_account(address, &account.into());
+}
+
+/// Stores a fake oracle price of `price * 10^-8` quote tokens per underlying token
+pub fn set_fake_price(
+    context: &mut ProgramTestContext,
+    price_account: &Pubkey,
+    price: i64,
+    publish_time: i64,
+) {
+    let fake = FakePrice {
+        price,
+        expo: -8,
+        publish_time,
+    };
+    set_account_data(context, price_account, fake.try_to_vec().unwrap());
+}
+
+/// Mints, vaults and account of a series before it is initialized
+pub struct SeriesAccounts {
+    pub series: Pubkey,
+    pub series_authority: Pubkey,
+    pub underlying_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub option_mint: Pubkey,
+    pub writer_mint: Pubkey,
+    pub underlying_vault: Pubkey,
+    pub quote_vault: Pubkey,
+}
+
+/// Creates an underlying mint with 9 decimals and a quote mint with 6, both
+/// minted by the payer, the option and writer mints, the vaults and the
+/// uninitialized series account
+pub async fn create_series_accounts(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    program_id: &Pubkey,
+) -> SeriesAccounts {
+    let series = Keypair::new();
+    let (series_authority, _) = find_series_authority(program_id, &series.pubkey());
+
+    let underlying_mint = create_mint(banks_client, payer, &payer.pubkey(), 9).await;
+    let quote_mint = create_mint(banks_client, payer, &payer.pubkey(), 6).await;
+    let option_mint = create_mint(banks_client, payer, &series_authority, 0).await;
+    let writer_mint = create_mint(banks_client, payer, &series_authority, 0).await;
+    let underlying_vault =
+        create_token_account(banks_client, payer, &underlying_mint, &series_authority).await;
+    let quote_vault =
+        create_token_account(banks_client, payer, &quote_mint, &series_authority).await;
+
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[system_instruction::create_account(
+            &payer.pubkey(),
+            &series.pubkey(),
+            rent.minimum_balance(OptionSeries::get_size()),
+            OptionSeries::get_size() as u64,
+            program_id,
+        )],
+        &[&series],
+    )
+    .await
+    .unwrap();
+
+    SeriesAccounts {
+        series: series.pubkey(),
+        series_authority,
+        underlying_mint,
+        quote_mint,
+        option_mint,
+        writer_mint,
+        underlying_vault,
+        quote_vault,
+    }
+}
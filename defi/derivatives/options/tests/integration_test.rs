@@ -1,41 +1,490 @@
 //! Integration tests for options-protocol
 
+mod common;
+
 #[cfg(test)]
 mod tests {
     use {
-        borsh::BorshSerialize,
-        solana_program::{
-            instruction::{AccountMeta, Instruction},
-            pubkey::Pubkey,
-            rent::Rent,
-            system_instruction,
-        },
-        solana_program_test::{processor, ProgramTest},
-        solana_sdk::{
-            account::Account,
-            signature::{Keypair, Signer},
-            transaction::Transaction,
+        super::common::{
+            create_funded_token_account, create_series_accounts, create_token_account,
+            custom_error, get_series, process, program_test, set_timestamp, start_at_now,
+            token_balance, EXPIRY, NOW,
         },
-        options-protocol::{
-            instructions::TemplateInstruction,
-            process_instruction,
-            state::TemplateAccount,
+        options_protocol::{
+            errors::TemplateError,
+            instructions::{exercise_option, initialize_series, redeem_collateral, write_option},
+            state::{OptionKind, EXERCISE_WINDOW},
         },
-        std::str::FromStr,
+        solana_program::{instruction::Instruction, pubkey::Pubkey},
+        solana_program_test::{BanksClient, ProgramTestContext},
+        solana_sdk::signature::{Keypair, Signer},
     };
 
+    const STRIKE: u64 = 2_000;
+    const CONTRACT_SIZE: u64 = 1_000;
+    const USER_FUNDS: u64 = 1_000_000_000;
+
+    /// Accounts of a series set up for testing
+    struct SeriesFixture {
+        program_id: Pubkey,
+        series: Pubkey,
+        series_authority: Pubkey,
+        underlying_mint: Pubkey,
+        quote_mint: Pubkey,
+        option_mint: Pubkey,
+        writer_mint: Pubkey,
+        underlying_vault: Pubkey,
+        quote_vault: Pubkey,
+        kind: OptionKind,
+        user: Keypair,
+        user_underlying: Pubkey,
+        user_quote: Pubkey,
+        user_options: Pubkey,
+        user_writer_tokens: Pubkey,
+    }
+
+    impl SeriesFixture {
+        fn initialize(&self, creator: &Pubkey, expiry: i64) -> Instruction {
+            initialize_series(
+                &self.program_id,
+                creator,
+                &self.series,
+                &self.series_authority,
+                &self.underlying_mint,
+                &self.quote_mint,
+                &self.option_mint,
+                &self.writer_mint,
+                &self.underlying_vault,
+                &self.quote_vault,
+                None,
+                self.kind,
+                STRIKE,
+                CONTRACT_SIZE,
+                expiry,
+            )
+        }
+
+        /// Vault, user collateral account and user payment account for the series kind
+        fn collateral_and_payment(&self) -> ((Pubkey, Pubkey), (Pubkey, Pubkey)) {
+            let underlying = (self.underlying_vault, self.user_underlying);
+            let quote = (self.quote_vault, self.user_quote);
+            match self.kind {
+                OptionKind::Call => (underlying, quote),
+                OptionKind::Put => (quote, underlying),
+            }
+        }
+
+        fn write(&self, amount: u64) -> Instruction {
+            let ((collateral_vault, user_collateral), _) = self.collateral_and_payment();
+            write_option(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.series,
+                &self.series_authority,
+                &user_collateral,
+                &collateral_vault,
+                &self.option_mint,
+                &self.writer_mint,
+                &self.user_options,
+                &self.user_writer_tokens,
+                amount,
+            )
+        }
+
+        fn exercise(&self, amount: u64) -> Instruction {
+            let ((collateral_vault, user_collateral), (payment_vault, user_payment)) =
+                self.collateral_and_payment();
+            exercise_option(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.series,
+                &self.series_authority,
+                &self.option_mint,
+                &self.user_options,
+                &user_payment,
+                &payment_vault,
+                &collateral_vault,
+                &user_collateral,
+                amount,
+            )
+        }
+
+        fn redeem(&self, amount: u64) -> Instruction {
+            redeem_collateral(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.series,
+                &self.series_authority,
+                &self.writer_mint,
+                &self.user_writer_tokens,
+                &self.underlying_vault,
+                &self.quote_vault,
+                &self.user_underlying,
+                &self.user_quote,
+                amount,
+            )
+        }
+    }
+
+    /// Creates the accounts of an uninitialized series and a user funded with both tokens
+    async fn setup_series(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        program_id: Pubkey,
+        kind: OptionKind,
+    ) -> SeriesFixture {
+        let accounts = create_series_accounts(banks_client, payer, &program_id).await;
+        let user = Keypair::new();
+        let user_underlying = create_funded_token_account(
+            banks_client,
+            payer,
+            &accounts.underlying_mint,
+            &user.pubkey(),
+            USER_FUNDS,
+        )
+        .await;
+        let user_quote = create_funded_token_account(
+            banks_client,
+            payer,
+            &accounts.quote_mint,
+            &user.pubkey(),
+            USER_FUNDS,
+        )
+        .await;
+        let user_options =
+            create_token_account(banks_client, payer, &accounts.option_mint, &user.pubkey()).await;
+        let user_writer_tokens =
+            create_token_account(banks_client, payer, &accounts.writer_mint, &user.pubkey()).await;
+
+        SeriesFixture {
+            program_id,
+            series: accounts.series,
+            series_authority: accounts.series_authority,
+            underlying_mint: accounts.underlying_mint,
+            quote_mint: accounts.quote_mint,
+            option_mint: accounts.option_mint,
+            writer_mint: accounts.writer_mint,
+            underlying_vault: accounts.underlying_vault,
+            quote_vault: accounts.quote_vault,
+            kind,
+            user,
+            user_underlying,
+            user_quote,
+            user_options,
+            user_writer_tokens,
+        }
+    }
+
+    /// Starts a test validator at `NOW` with an initialized series expiring at `EXPIRY`
+    async fn start_with_series(kind: OptionKind) -> (ProgramTestContext, Keypair, SeriesFixture) {
+        let (program_test, program_id) = program_test();
+        let (mut context, payer) = start_at_now(program_test).await;
+        let fixture = setup_series(&mut context.banks_client, &payer, program_id, kind).await;
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.initialize(&payer.pubkey(), EXPIRY)],
+            &[],
+        )
+        .await
+        .unwrap();
+        (context, payer, fixture)
+    }
+
+    #[tokio::test]
+    async fn test_initialize_series() {
+        let (mut context, payer, fixture) = start_with_series(OptionKind::Put).await;
+
+        let series = get_series(&mut context.banks_client, &fixture.series).await;
+        assert!(series.is_initialized);
+        assert_eq!(series.creator, payer.pubkey());
+        assert_eq!(series.kind, OptionKind::Put);
+        assert_eq!(series.underlying_mint, fixture.underlying_mint);
+        assert_eq!(series.quote_mint, fixture.quote_mint);
+        assert_eq!(series.strike, STRIKE);
+        assert_eq!(series.contract_size, CONTRACT_SIZE);
+        assert_eq!(series.expiry, EXPIRY);
+        assert_eq!(series.option_mint, fixture.option_mint);
+        assert_eq!(series.writer_mint, fixture.writer_mint);
+        assert_eq!(series.collateral_vault(), &fixture.quote_vault);
+        assert_eq!(series.payment_vault(), &fixture.underlying_vault);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_series_rejects_past_expiry() {
+        let (program_test, program_id) = program_test();
+        let (mut context, payer) = start_at_now(program_test).await;
+        let fixture = setup_series(
+            &mut context.banks_client,
+            &payer,
+            program_id,
+            OptionKind::Call,
+        )
+        .await;
+
+        let err = process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.initialize(&payer.pubkey(), NOW)],
+            &[],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidExpiry));
+    }
+
+    #[tokio::test]
+    async fn test_write_covered_call() {
+        let (mut context, payer, fixture) = start_with_series(OptionKind::Call).await;
+        let banks_client = &mut context.banks_client;
+
+        process(banks_client, &payer, &[fixture.write(10)], &[&fixture.user])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            token_balance(banks_client, &fixture.underlying_vault).await,
+            10 * CONTRACT_SIZE
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_underlying).await,
+            USER_FUNDS - 10 * CONTRACT_SIZE
+        );
+        assert_eq!(token_balance(banks_client, &fixture.quote_vault).await, 0);
+        assert_eq!(token_balance(banks_client, &fixture.user_options).await, 10);
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_writer_tokens).await,
+            10
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_cash_secured_put() {
+        let (mut context, payer, fixture) = start_with_series(OptionKind::Put).await;
+        let banks_client = &mut context.banks_client;
+
+        process(banks_client, &payer, &[fixture.write(10)], &[&fixture.user])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            token_balance(banks_client, &fixture.quote_vault).await,
+            10 * STRIKE
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.underlying_vault).await,
+            0
+        );
+        assert_eq!(token_balance(banks_client, &fixture.user_options).await, 10);
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_writer_tokens).await,
+            10
+        );
+    }
+
     #[tokio::test]
-    async fn test_initialize() {
-        // TODO: Implement test logic for initialization
+    async fn test_write_rejected_after_expiry() {
+        let (mut context, payer, fixture) = start_with_series(OptionKind::Call).await;
+        set_timestamp(&mut context, EXPIRY).await;
+
+        let err = process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.write(10)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::SeriesExpired));
     }
 
     #[tokio::test]
-    async fn test_operation1() {
-        // TODO: Implement test logic for operation1
+    async fn test_exercise_call() {
+        let (mut context, payer, fixture) = start_with_series(OptionKind::Call).await;
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.write(10)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        set_timestamp(&mut context, EXPIRY - 100).await;
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.exercise(4)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(token_balance(banks_client, &fixture.user_options).await, 6);
+        assert_eq!(
+            token_balance(banks_client, &fixture.underlying_vault).await,
+            6 * CONTRACT_SIZE
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.quote_vault).await,
+            4 * STRIKE
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_underlying).await,
+            USER_FUNDS - 6 * CONTRACT_SIZE
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_quote).await,
+            USER_FUNDS - 4 * STRIKE
+        );
     }
 
     #[tokio::test]
-    async fn test_operation2() {
-        // TODO: Implement test logic for operation2
+    async fn test_exercise_put() {
+        let (mut context, payer, fixture) = start_with_series(OptionKind::Put).await;
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.write(10)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        set_timestamp(&mut context, EXPIRY - EXERCISE_WINDOW).await;
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.exercise(3)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(token_balance(banks_client, &fixture.user_options).await, 7);
+        assert_eq!(
+            token_balance(banks_client, &fixture.quote_vault).await,
+            7 * STRIKE
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.underlying_vault).await,
+            3 * CONTRACT_SIZE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exercise_outside_window() {
+        let (mut context, payer, fixture) = start_with_series(OptionKind::Call).await;
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.write(10)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        // Before the window opens
+        set_timestamp(&mut context, EXPIRY - EXERCISE_WINDOW - 1).await;
+        let err = process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.exercise(1)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::OutsideExerciseWindow));
+
+        // At expiry
+        set_timestamp(&mut context, EXPIRY).await;
+        let err = process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.exercise(2)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::OutsideExerciseWindow));
+    }
+
+    #[tokio::test]
+    async fn test_redeem_collateral_after_exercise() {
+        let (mut context, payer, fixture) = start_with_series(OptionKind::Call).await;
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.write(10)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        set_timestamp(&mut context, EXPIRY - 100).await;
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.exercise(4)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        set_timestamp(&mut context, EXPIRY).await;
+        let banks_client = &mut context.banks_client;
+        let underlying_before = token_balance(banks_client, &fixture.user_underlying).await;
+        let quote_before = token_balance(banks_client, &fixture.user_quote).await;
+
+        // 4 of 10 writer tokens claim 40% of what is left in each vault
+        process(banks_client, &payer, &[fixture.redeem(4)], &[&fixture.user])
+            .await
+            .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_underlying).await,
+            underlying_before + 6 * CONTRACT_SIZE * 4 / 10
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_quote).await,
+            quote_before + 4 * STRIKE * 4 / 10
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_writer_tokens).await,
+            6
+        );
+
+        // The rest empties both vaults
+        process(banks_client, &payer, &[fixture.redeem(6)], &[&fixture.user])
+            .await
+            .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.underlying_vault).await,
+            0
+        );
+        assert_eq!(token_balance(banks_client, &fixture.quote_vault).await, 0);
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_writer_tokens).await,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redeem_rejected_before_expiry() {
+        let (mut context, payer, fixture) = start_with_series(OptionKind::Put).await;
+        let banks_client = &mut context.banks_client;
+        process(banks_client, &payer, &[fixture.write(10)], &[&fixture.user])
+            .await
+            .unwrap();
+
+        let err = process(
+            banks_client,
+            &payer,
+            &[fixture.redeem(10)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::SeriesNotExpired));
     }
 }
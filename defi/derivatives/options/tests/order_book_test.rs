@@ -0,0 +1,1000 @@
+//! Integration tests for the options-protocol order book
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::common::{
+            create_funded_token_account, create_program_account, create_series_accounts,
+            create_token_account, custom_error, get_series, new_blockhash, process,
+            process_with_blockhash, program_test, set_timestamp, start_at_now, token_balance,
+            SeriesAccounts, EXPIRY,
+        },
+        borsh::BorshDeserialize,
+        options_protocol::{
+            errors::TemplateError,
+            instructions::{
+                cancel_order, consume_events, initialize_order_book, initialize_series,
+                match_orders, place_order, settle_funds, write_option,
+            },
+            order_book::{
+                EventQueue, FillEvent, OpenOrders, Order, OrderBook, Side, MAX_EVENTS,
+                MAX_ORDERS_PER_SIDE,
+            },
+            state::OptionKind,
+            utils::{find_order_book_authority, find_series_authority},
+        },
+        solana_program::{instruction::Instruction, pubkey::Pubkey},
+        solana_program_test::{BanksClient, ProgramTestContext},
+        solana_sdk::signature::{Keypair, Signer},
+    };
+
+    const STRIKE: u64 = 2_000_000;
+    const CONTRACT_SIZE: u64 = 1_000_000_000;
+    const USER_FUNDS: u64 = 1_000_000_000_000;
+    const WRITTEN: u64 = 20;
+
+    /// Wallet and token accounts of one trader
+    struct Trader {
+        keypair: Keypair,
+        open_orders: Pubkey,
+        quote: Pubkey,
+        options: Pubkey,
+    }
+
+    /// Accounts of a call series with an initialized order book and two
+    /// traders who have each written `WRITTEN` options
+    struct BookFixture {
+        program_id: Pubkey,
+        series: Pubkey,
+        order_book: Pubkey,
+        event_queue: Pubkey,
+        order_book_authority: Pubkey,
+        option_vault: Pubkey,
+        quote_vault: Pubkey,
+        buyer: Trader,
+        seller: Trader,
+    }
+
+    impl BookFixture {
+        fn place(&self, trader: &Trader, side: Side, price: u64, quantity: u64) -> Instruction {
+            self.place_evicting(trader, side, price, quantity, None)
+        }
+
+        fn place_evicting(
+            &self,
+            trader: &Trader,
+            side: Side,
+            price: u64,
+            quantity: u64,
+            evicted_open_orders: Option<&Pubkey>,
+        ) -> Instruction {
+            let (owner_tokens, vault) = match side {
+                Side::Bid => (&trader.quote, &self.quote_vault),
+                Side::Ask => (&trader.options, &self.option_vault),
+            };
+            place_order(
+                &self.program_id,
+                &trader.keypair.pubkey(),
+                &self.series,
+                &self.order_book,
+                &trader.open_orders,
+                owner_tokens,
+                vault,
+                evicted_open_orders,
+                side,
+                price,
+                quantity,
+            )
+        }
+
+        fn cancel(&self, trader: &Trader, side: Side, order_id: u64) -> Instruction {
+            cancel_order(
+                &self.program_id,
+                &trader.keypair.pubkey(),
+                &self.order_book,
+                &trader.open_orders,
+                side,
+                order_id,
+            )
+        }
+
+        fn match_orders(&self, limit: u16) -> Instruction {
+            match_orders(
+                &self.program_id,
+                &self.series,
+                &self.order_book,
+                &self.event_queue,
+                limit,
+            )
+        }
+
+        fn consume(&self, open_orders: &[Pubkey], limit: u16) -> Instruction {
+            consume_events(&self.program_id, &self.event_queue, open_orders, limit)
+        }
+
+        fn settle(&self, trader: &Trader) -> Instruction {
+            settle_funds(
+                &self.program_id,
+                &trader.keypair.pubkey(),
+                &self.order_book,
+                &trader.open_orders,
+                &self.order_book_authority,
+                &self.option_vault,
+                &self.quote_vault,
+                &trader.options,
+                &trader.quote,
+            )
+        }
+    }
+
+    async fn get_order_book(banks_client: &mut BanksClient, order_book: &Pubkey) -> OrderBook {
+        let account = banks_client
+            .get_account(*order_book)
+            .await
+            .unwrap()
+            .unwrap();
+        OrderBook::deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    async fn get_event_queue(banks_client: &mut BanksClient, event_queue: &Pubkey) -> EventQueue {
+        let account = banks_client
+            .get_account(*event_queue)
+            .await
+            .unwrap()
+            .unwrap();
+        EventQueue::deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    async fn get_open_orders(banks_client: &mut BanksClient, open_orders: &Pubkey) -> OpenOrders {
+        let account = banks_client
+            .get_account(*open_orders)
+            .await
+            .unwrap()
+            .unwrap();
+        OpenOrders::try_from_slice(&account.data).unwrap()
+    }
+
+    /// Creates a trader funded with both tokens who has written `WRITTEN` options
+    #[allow(clippy::too_many_arguments)]
+    async fn setup_trader(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        program_id: &Pubkey,
+        series: &Pubkey,
+        series_authority: &Pubkey,
+        mints: [&Pubkey; 4],
+        underlying_vault: &Pubkey,
+    ) -> Trader {
+        let [underlying_mint, quote_mint, option_mint, writer_mint] = mints;
+        let keypair = Keypair::new();
+        let underlying = create_funded_token_account(
+            banks_client,
+            payer,
+            underlying_mint,
+            &keypair.pubkey(),
+            USER_FUNDS,
+        )
+        .await;
+        let quote = create_funded_token_account(
+            banks_client,
+            payer,
+            quote_mint,
+            &keypair.pubkey(),
+            USER_FUNDS,
+        )
+        .await;
+        let options =
+            create_token_account(banks_client, payer, option_mint, &keypair.pubkey()).await;
+        let writer_tokens =
+            create_token_account(banks_client, payer, writer_mint, &keypair.pubkey()).await;
+        process(
+            banks_client,
+            payer,
+            &[write_option(
+                program_id,
+                &keypair.pubkey(),
+                series,
+                series_authority,
+                &underlying,
+                underlying_vault,
+                option_mint,
+                writer_mint,
+                &options,
+                &writer_tokens,
+                WRITTEN,
+            )],
+            &[&keypair],
+        )
+        .await
+        .unwrap();
+        let open_orders =
+            create_program_account(banks_client, payer, program_id, OpenOrders::get_size()).await;
+        Trader {
+            keypair,
+            open_orders,
+            quote,
+            options,
+        }
+    }
+
+    /// Starts a test validator at `NOW` with a call series, an initialized
+    /// order book and two traders
+    async fn start_with_order_book() -> (ProgramTestContext, Keypair, BookFixture) {
+        let (program_test, program_id) = program_test();
+        let (mut context, payer) = start_at_now(program_test).await;
+        let banks_client = &mut context.banks_client;
+
+        let SeriesAccounts {
+            series,
+            series_authority,
+            underlying_mint,
+            quote_mint,
+            option_mint,
+            writer_mint,
+            underlying_vault,
+            quote_vault,
+        } = create_series_accounts(banks_client, &payer, &program_id).await;
+        process(
+            banks_client,
+            &payer,
+            &[initialize_series(
+                &program_id,
+                &payer.pubkey(),
+                &series,
+                &series_authority,
+                &underlying_mint,
+                &quote_mint,
+                &option_mint,
+                &writer_mint,
+                &underlying_vault,
+                &quote_vault,
+                None,
+                OptionKind::Call,
+                STRIKE,
+                CONTRACT_SIZE,
+                EXPIRY,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let mints = [&underlying_mint, &quote_mint, &option_mint, &writer_mint];
+        let mut traders = Vec::new();
+        for _ in 0..2 {
+            traders.push(
+                setup_trader(
+                    banks_client,
+                    &payer,
+                    &program_id,
+                    &series,
+                    &series_authority,
+                    mints,
+                    &underlying_vault,
+                )
+                .await,
+            );
+        }
+        let seller = traders.pop().unwrap();
+        let buyer = traders.pop().unwrap();
+
+        let order_book =
+            create_program_account(banks_client, &payer, &program_id, OrderBook::get_size()).await;
+        let event_queue =
+            create_program_account(banks_client, &payer, &program_id, EventQueue::get_size()).await;
+        let (order_book_authority, _) = find_order_book_authority(&program_id, &order_book);
+        let option_vault =
+            create_token_account(banks_client, &payer, &option_mint, &order_book_authority).await;
+        let book_quote_vault =
+            create_token_account(banks_client, &payer, &quote_mint, &order_book_authority).await;
+
+        let fixture = BookFixture {
+            program_id,
+            series,
+            order_book,
+            event_queue,
+            order_book_authority,
+            option_vault,
+            quote_vault: book_quote_vault,
+            buyer,
+            seller,
+        };
+        process(
+            banks_client,
+            &payer,
+            &[initialize_order_book(
+                &program_id,
+                &fixture.series,
+                &fixture.order_book,
+                &fixture.event_queue,
+                &fixture.order_book_authority,
+                &fixture.option_vault,
+                &fixture.quote_vault,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+        (context, payer, fixture)
+    }
+
+    fn order(order_id: u64, price: u64, quantity: u64) -> (u64, u64, u64) {
+        (order_id, price, quantity)
+    }
+
+    fn summary(orders: &[Order]) -> Vec<(u64, u64, u64)> {
+        orders
+            .iter()
+            .map(|order| (order.order_id, order.price, order.quantity))
+            .collect()
+    }
+
+    #[test]
+    fn test_slabs_keep_price_time_priority() {
+        let mut book = OrderBook::default();
+        let trader = Pubkey::new_unique();
+        for (side, price) in [
+            (Side::Bid, 100),
+            (Side::Bid, 120),
+            (Side::Ask, 130),
+            (Side::Bid, 120),
+            (Side::Ask, 125),
+            (Side::Ask, 130),
+            (Side::Bid, 90),
+        ] {
+            book.insert(side, trader, price, 1).unwrap();
+        }
+        assert_eq!(
+            summary(&book.bids),
+            vec![
+                order(1, 120, 1),
+                order(3, 120, 1),
+                order(0, 100, 1),
+                order(6, 90, 1)
+            ]
+        );
+        assert_eq!(
+            summary(&book.asks),
+            vec![order(4, 125, 1), order(2, 130, 1), order(5, 130, 1)]
+        );
+        assert!(!book.is_crossed());
+
+        assert_eq!(book.remove(Side::Bid, 3).unwrap().price, 120);
+        assert_eq!(book.remove(Side::Bid, 3), Err(TemplateError::OrderNotFound));
+        assert_eq!(book.remove(Side::Ask, 0), Err(TemplateError::OrderNotFound));
+    }
+
+    #[test]
+    fn test_slabs_are_bounded() {
+        let mut book = OrderBook::default();
+        for price in 1..=MAX_ORDERS_PER_SIDE as u64 {
+            book.insert(Side::Ask, Pubkey::new_unique(), price, 1)
+                .unwrap();
+        }
+        assert_eq!(
+            book.insert(
+                Side::Ask,
+                Pubkey::new_unique(),
+                MAX_ORDERS_PER_SIDE as u64,
+                1
+            ),
+            Err(TemplateError::OrderBookFull)
+        );
+        // The other side has its own slab
+        book.insert(Side::Bid, Pubkey::new_unique(), 1, 1).unwrap();
+
+        // A better price evicts the worst order
+        let worst = *book.asks.last().unwrap();
+        let (order_id, evicted) = book.insert(Side::Ask, Pubkey::new_unique(), 1, 1).unwrap();
+        assert_eq!(evicted, Some(worst));
+        assert_eq!(book.asks.len(), MAX_ORDERS_PER_SIDE);
+        assert_eq!(book.asks[1].order_id, order_id);
+        assert_eq!(
+            book.asks.last().unwrap().price,
+            MAX_ORDERS_PER_SIDE as u64 - 1
+        );
+    }
+
+    #[test]
+    fn test_match_orders_fills_at_the_older_price() {
+        let (buyer, seller) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut book = OrderBook::default();
+        let mut queue = EventQueue::default();
+        book.insert(Side::Ask, seller, 100, 2).unwrap();
+        book.insert(Side::Ask, seller, 105, 2).unwrap();
+        book.insert(Side::Bid, buyer, 110, 3).unwrap();
+        book.insert(Side::Bid, buyer, 90, 1).unwrap();
+        assert!(book.is_crossed());
+
+        assert_eq!(book.match_orders(&mut queue, 10), 2);
+        assert_eq!(
+            queue.events,
+            vec![
+                FillEvent {
+                    buyer,
+                    seller,
+                    quantity: 2,
+                    price: 100,
+                    bid_price: 110,
+                },
+                FillEvent {
+                    buyer,
+                    seller,
+                    quantity: 1,
+                    price: 105,
+                    bid_price: 110,
+                },
+            ]
+        );
+        assert_eq!(summary(&book.bids), vec![order(3, 90, 1)]);
+        assert_eq!(summary(&book.asks), vec![order(1, 105, 1)]);
+        assert!(!book.is_crossed());
+        assert_eq!(queue.events[0].quote_amount(), Ok(200));
+        assert_eq!(queue.events[0].price_improvement(), Ok(20));
+
+        // A newer ask crossing an older bid fills at the bid price
+        book.insert(Side::Ask, seller, 80, 5).unwrap();
+        assert_eq!(book.match_orders(&mut queue, 10), 1);
+        assert_eq!(queue.events[2].price, 90);
+        assert_eq!(queue.events[2].price_improvement(), Ok(0));
+        assert_eq!(summary(&book.asks), vec![order(4, 80, 4), order(1, 105, 1)]);
+    }
+
+    #[test]
+    fn test_match_orders_stops_when_the_queue_is_full() {
+        let mut book = OrderBook::default();
+        let mut queue = EventQueue::default();
+        for _ in 0..MAX_ORDERS_PER_SIDE {
+            book.insert(Side::Ask, Pubkey::new_unique(), 100, 1)
+                .unwrap();
+        }
+        book.insert(Side::Bid, Pubkey::new_unique(), 100, 1_000)
+            .unwrap();
+        queue.events = vec![FillEvent::default(); MAX_EVENTS - 3];
+
+        assert_eq!(book.match_orders(&mut queue, 1), 1);
+        assert_eq!(book.match_orders(&mut queue, 100), 2);
+        assert_eq!(queue.events.len(), MAX_EVENTS);
+        assert_eq!(book.match_orders(&mut queue, 100), 0);
+        assert_eq!(book.asks.len(), MAX_ORDERS_PER_SIDE - 3);
+        assert_eq!(book.bids[0].quantity, 997);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_order_book() {
+        let (mut context, payer, fixture) = start_with_order_book().await;
+        let banks_client = &mut context.banks_client;
+
+        let book = get_order_book(banks_client, &fixture.order_book).await;
+        assert!(book.is_initialized);
+        assert_eq!(book.series, fixture.series);
+        assert_eq!(book.event_queue, fixture.event_queue);
+        assert_eq!(book.option_vault, fixture.option_vault);
+        assert_eq!(book.quote_vault, fixture.quote_vault);
+        assert!(book.bids.is_empty() && book.asks.is_empty());
+        let queue = get_event_queue(banks_client, &fixture.event_queue).await;
+        assert_eq!(queue.order_book, fixture.order_book);
+
+        // The retry repeats the setup transaction, so it needs a fresh
+        // blockhash to be processed rather than answered from the status cache
+        let recent_blockhash = new_blockhash(&mut context.banks_client).await;
+        assert_eq!(
+            process_with_blockhash(
+                &mut context.banks_client,
+                &payer,
+                &[initialize_order_book(
+                    &fixture.program_id,
+                    &fixture.series,
+                    &fixture.order_book,
+                    &fixture.event_queue,
+                    &fixture.order_book_authority,
+                    &fixture.option_vault,
+                    &fixture.quote_vault,
+                )],
+                &[],
+                recent_blockhash,
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::OrderBookAlreadyInitialized)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_initialize_order_book_rejects_series_vaults() {
+        let (mut context, payer, fixture) = start_with_order_book().await;
+        let banks_client = &mut context.banks_client;
+        let series = get_series(banks_client, &fixture.series).await;
+        let (series_authority, _) = find_series_authority(&fixture.program_id, &fixture.series);
+
+        // Vaults must be owned by the new order book's own authority
+        let order_book = create_program_account(
+            banks_client,
+            &payer,
+            &fixture.program_id,
+            OrderBook::get_size(),
+        )
+        .await;
+        let event_queue = create_program_account(
+            banks_client,
+            &payer,
+            &fixture.program_id,
+            EventQueue::get_size(),
+        )
+        .await;
+        let (order_book_authority, _) = find_order_book_authority(&fixture.program_id, &order_book);
+        let option_vault =
+            create_token_account(banks_client, &payer, &series.option_mint, &series_authority)
+                .await;
+        let quote_vault = create_token_account(
+            banks_client,
+            &payer,
+            &series.quote_mint,
+            &order_book_authority,
+        )
+        .await;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[initialize_order_book(
+                    &fixture.program_id,
+                    &fixture.series,
+                    &order_book,
+                    &event_queue,
+                    &order_book_authority,
+                    &option_vault,
+                    &quote_vault,
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidVault)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_place_order_locks_tokens() {
+        let (mut context, payer, fixture) = start_with_order_book().await;
+        let banks_client = &mut context.banks_client;
+        let buyer_quote_before = token_balance(banks_client, &fixture.buyer.quote).await;
+
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.place(&fixture.buyer, Side::Bid, 100_000, 4),
+                fixture.place(&fixture.buyer, Side::Bid, 120_000, 1),
+            ],
+            &[&fixture.buyer.keypair],
+        )
+        .await
+        .unwrap();
+        process(
+            banks_client,
+            &payer,
+            &[fixture.place(&fixture.seller, Side::Ask, 150_000, 3)],
+            &[&fixture.seller.keypair],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            token_balance(banks_client, &fixture.buyer.quote).await,
+            buyer_quote_before - 520_000
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.quote_vault).await,
+            520_000
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.seller.options).await,
+            WRITTEN - 3
+        );
+        assert_eq!(token_balance(banks_client, &fixture.option_vault).await, 3);
+
+        let book = get_order_book(banks_client, &fixture.order_book).await;
+        assert_eq!(
+            summary(&book.bids),
+            vec![order(1, 120_000, 1), order(0, 100_000, 4)]
+        );
+        assert_eq!(summary(&book.asks), vec![order(2, 150_000, 3)]);
+        assert_eq!(book.bids[0].open_orders, fixture.buyer.open_orders);
+
+        let open_orders = get_open_orders(banks_client, &fixture.buyer.open_orders).await;
+        assert!(open_orders.is_initialized);
+        assert_eq!(open_orders.order_book, fixture.order_book);
+        assert_eq!(open_orders.owner, fixture.buyer.keypair.pubkey());
+
+        // Someone else cannot place orders through the buyer's open orders account
+        let mut instruction = fixture.place(&fixture.seller, Side::Bid, 1, 1);
+        instruction.accounts[3].pubkey = fixture.buyer.open_orders;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[instruction],
+                &[&fixture.seller.keypair]
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidOpenOrders)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_place_order_validation() {
+        let (mut context, payer, fixture) = start_with_order_book().await;
+        let banks_client = &mut context.banks_client;
+
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.place(&fixture.buyer, Side::Bid, 0, 1)],
+                &[&fixture.buyer.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidOrder)
+        );
+
+        // Asks lock options in the option vault
+        let mut instruction = fixture.place(&fixture.seller, Side::Ask, 1, 1);
+        instruction.accounts[5].pubkey = fixture.quote_vault;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[instruction],
+                &[&fixture.seller.keypair]
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidVault)
+        );
+
+        set_timestamp(&mut context, EXPIRY).await;
+        assert_eq!(
+            process(
+                &mut context.banks_client,
+                &payer,
+                &[fixture.place(&fixture.buyer, Side::Bid, 1, 1)],
+                &[&fixture.buyer.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::SeriesExpired)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_place_order_evicts_the_worst_order_of_a_full_side() {
+        let (mut context, payer, fixture) = start_with_order_book().await;
+        let banks_client = &mut context.banks_client;
+        let (buyer, seller) = (&fixture.buyer, &fixture.seller);
+
+        // The buyer fills the bids with one option each at 1 to 32
+        let bids: Vec<Instruction> = (1..=MAX_ORDERS_PER_SIDE as u64)
+            .map(|price| fixture.place(buyer, Side::Bid, price, 1))
+            .collect();
+        for instructions in bids.chunks(8) {
+            process(banks_client, &payer, instructions, &[&buyer.keypair])
+                .await
+                .unwrap();
+        }
+
+        // A bid no better than the worst one is rejected
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.place_evicting(seller, Side::Bid, 1, 5, Some(&buyer.open_orders))],
+                &[&seller.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::OrderBookFull)
+        );
+
+        // A better bid must pass the open orders account of the evicted order
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.place_evicting(seller, Side::Bid, 2, 5, Some(&seller.open_orders))],
+                &[&seller.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidOpenOrders)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[fixture.place_evicting(seller, Side::Bid, 2, 5, Some(&buyer.open_orders))],
+            &[&seller.keypair],
+        )
+        .await
+        .unwrap();
+
+        let book = get_order_book(banks_client, &fixture.order_book).await;
+        assert_eq!(book.bids.len(), MAX_ORDERS_PER_SIDE);
+        assert_eq!(book.bids.last().unwrap().price, 2);
+        assert_eq!(book.bids.last().unwrap().open_orders, seller.open_orders);
+        assert!(book.bids.iter().all(|order| order.price > 1));
+
+        // The evicted bid's quote tokens are credited to the buyer
+        let buyer_balances = get_open_orders(banks_client, &buyer.open_orders).await;
+        assert_eq!(
+            (buyer_balances.base_free, buyer_balances.quote_free),
+            (0, 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_match_consume_and_settle() {
+        let (mut context, payer, fixture) = start_with_order_book().await;
+        let banks_client = &mut context.banks_client;
+        let (buyer, seller) = (&fixture.buyer, &fixture.seller);
+        let buyer_quote_before = token_balance(banks_client, &buyer.quote).await;
+        let seller_quote_before = token_balance(banks_client, &seller.quote).await;
+
+        // The older ask sets the price of both fills
+        process(
+            banks_client,
+            &payer,
+            &[fixture.place(seller, Side::Ask, 100_000, 5)],
+            &[&seller.keypair],
+        )
+        .await
+        .unwrap();
+        process(
+            banks_client,
+            &payer,
+            &[fixture.place(buyer, Side::Bid, 110_000, 3)],
+            &[&buyer.keypair],
+        )
+        .await
+        .unwrap();
+        process(banks_client, &payer, &[fixture.match_orders(8)], &[])
+            .await
+            .unwrap();
+
+        let book = get_order_book(banks_client, &fixture.order_book).await;
+        assert!(book.bids.is_empty());
+        assert_eq!(summary(&book.asks), vec![order(0, 100_000, 2)]);
+        let queue = get_event_queue(banks_client, &fixture.event_queue).await;
+        assert_eq!(
+            queue.events,
+            vec![FillEvent {
+                buyer: buyer.open_orders,
+                seller: seller.open_orders,
+                quantity: 3,
+                price: 100_000,
+                bid_price: 110_000,
+            }]
+        );
+
+        // The crank needs both traders' open orders accounts
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.consume(&[buyer.open_orders], 8)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidOpenOrders)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[fixture.consume(&[seller.open_orders, buyer.open_orders], 8)],
+            &[],
+        )
+        .await
+        .unwrap();
+        assert!(get_event_queue(banks_client, &fixture.event_queue)
+            .await
+            .events
+            .is_empty());
+
+        let buyer_balances = get_open_orders(banks_client, &buyer.open_orders).await;
+        assert_eq!(buyer_balances.base_free, 3);
+        assert_eq!(buyer_balances.quote_free, 30_000);
+        let seller_balances = get_open_orders(banks_client, &seller.open_orders).await;
+        assert_eq!(seller_balances.base_free, 0);
+        assert_eq!(seller_balances.quote_free, 300_000);
+
+        // Only the owner can settle
+        let mut instruction = fixture.settle(buyer);
+        instruction.accounts[0].pubkey = seller.keypair.pubkey();
+        assert_eq!(
+            process(banks_client, &payer, &[instruction], &[&seller.keypair])
+                .await
+                .unwrap_err(),
+            custom_error(TemplateError::InvalidOpenOrders)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[fixture.settle(buyer)],
+            &[&buyer.keypair],
+        )
+        .await
+        .unwrap();
+        process(
+            banks_client,
+            &payer,
+            &[fixture.settle(seller)],
+            &[&seller.keypair],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            token_balance(banks_client, &buyer.options).await,
+            WRITTEN + 3
+        );
+        assert_eq!(
+            token_balance(banks_client, &buyer.quote).await,
+            buyer_quote_before - 300_000
+        );
+        assert_eq!(
+            token_balance(banks_client, &seller.options).await,
+            WRITTEN - 5
+        );
+        assert_eq!(
+            token_balance(banks_client, &seller.quote).await,
+            seller_quote_before + 300_000
+        );
+        // Only the unfilled part of the ask is left in the vaults
+        assert_eq!(token_balance(banks_client, &fixture.option_vault).await, 2);
+        assert_eq!(token_balance(banks_client, &fixture.quote_vault).await, 0);
+        let buyer_balances = get_open_orders(banks_client, &buyer.open_orders).await;
+        assert_eq!(
+            (buyer_balances.base_free, buyer_balances.quote_free),
+            (0, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_match_orders_limit_and_expiry() {
+        let (mut context, payer, fixture) = start_with_order_book().await;
+        let banks_client = &mut context.banks_client;
+        let (buyer, seller) = (&fixture.buyer, &fixture.seller);
+
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.place(seller, Side::Ask, 100_000, 1),
+                fixture.place(seller, Side::Ask, 101_000, 1),
+            ],
+            &[&seller.keypair],
+        )
+        .await
+        .unwrap();
+        process(
+            banks_client,
+            &payer,
+            &[fixture.place(buyer, Side::Bid, 105_000, 2)],
+            &[&buyer.keypair],
+        )
+        .await
+        .unwrap();
+
+        process(banks_client, &payer, &[fixture.match_orders(1)], &[])
+            .await
+            .unwrap();
+        let queue = get_event_queue(banks_client, &fixture.event_queue).await;
+        assert_eq!(queue.events.len(), 1);
+        assert_eq!(queue.events[0].price, 100_000);
+
+        set_timestamp(&mut context, EXPIRY).await;
+        assert_eq!(
+            process(
+                &mut context.banks_client,
+                &payer,
+                &[fixture.match_orders(2)],
+                &[]
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::SeriesExpired)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_order() {
+        let (mut context, payer, fixture) = start_with_order_book().await;
+        let banks_client = &mut context.banks_client;
+        let (buyer, seller) = (&fixture.buyer, &fixture.seller);
+
+        process(
+            banks_client,
+            &payer,
+            &[fixture.place(buyer, Side::Bid, 100_000, 4)],
+            &[&buyer.keypair],
+        )
+        .await
+        .unwrap();
+        process(
+            banks_client,
+            &payer,
+            &[fixture.place(seller, Side::Ask, 200_000, 6)],
+            &[&seller.keypair],
+        )
+        .await
+        .unwrap();
+
+        // Orders can only be cancelled through their own open orders account
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.cancel(seller, Side::Bid, 0)],
+                &[&seller.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidOpenOrders)
+        );
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.cancel(buyer, Side::Ask, 0)],
+                &[&buyer.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::OrderNotFound)
+        );
+
+        process(
+            banks_client,
+            &payer,
+            &[fixture.cancel(buyer, Side::Bid, 0)],
+            &[&buyer.keypair],
+        )
+        .await
+        .unwrap();
+        process(
+            banks_client,
+            &payer,
+            &[fixture.cancel(seller, Side::Ask, 1)],
+            &[&seller.keypair],
+        )
+        .await
+        .unwrap();
+
+        let book = get_order_book(banks_client, &fixture.order_book).await;
+        assert!(book.bids.is_empty() && book.asks.is_empty());
+        let buyer_balances = get_open_orders(banks_client, &buyer.open_orders).await;
+        assert_eq!(
+            (buyer_balances.base_free, buyer_balances.quote_free),
+            (0, 400_000)
+        );
+        let seller_balances = get_open_orders(banks_client, &seller.open_orders).await;
+        assert_eq!(
+            (seller_balances.base_free, seller_balances.quote_free),
+            (6, 0)
+        );
+
+        process(
+            banks_client,
+            &payer,
+            &[fixture.settle(seller)],
+            &[&seller.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(token_balance(banks_client, &seller.options).await, WRITTEN);
+    }
+}
@@ -0,0 +1,625 @@
+//! Integration tests for the Black-Scholes option pool in options-protocol
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::common::{
+            create_funded_token_account, create_series_accounts, create_token_account,
+            custom_error, get_series, process, program_test_with_fake_oracle, set_fake_price,
+            set_timestamp, start_at_now, token_balance, EXPIRY, NOW,
+        },
+        options_protocol::{
+            errors::TemplateError,
+            instructions::{
+                buy_from_pool, initialize_pool, initialize_series, sell_to_pool,
+                set_implied_volatility, withdraw_from_pool, write_option,
+            },
+            state::OptionKind,
+        },
+        solana_program::{instruction::Instruction, pubkey::Pubkey},
+        solana_program_test::ProgramTestContext,
+        solana_sdk::signature::{Keypair, Signer},
+    };
+
+    /// 2 quote tokens (6 decimals) for one underlying token (9 decimals)
+    const STRIKE: u64 = 2_000_000;
+    const CONTRACT_SIZE: u64 = 1_000_000_000;
+    /// Oracle price of 2 quote tokens per underlying token, with expo -8
+    const SPOT_PRICE: i64 = 200_000_000;
+    /// The oracle price converted to quote base units per contract
+    const SPOT: u64 = 2_000_000;
+    /// 80% annualized volatility
+    const IMPLIED_VOLATILITY: u64 = 800_000_000_000_000_000;
+    const SPREAD_BPS: u64 = 200;
+    const USER_FUNDS: u64 = 1_000_000_000_000;
+    const POOL_OPTIONS: u64 = 10;
+    const POOL_PREMIUM: u64 = 5_000_000;
+
+    /// Accounts of a physically settled call series with an option pool
+    struct PoolFixture {
+        program_id: Pubkey,
+        series: Pubkey,
+        series_authority: Pubkey,
+        pool_option_vault: Pubkey,
+        pool_premium_vault: Pubkey,
+        price_account: Pubkey,
+        user: Keypair,
+        user_quote: Pubkey,
+        user_options: Pubkey,
+    }
+
+    impl PoolFixture {
+        fn initialize_pool(&self, creator: &Pubkey, spread_bps: u64) -> Instruction {
+            initialize_pool(
+                &self.program_id,
+                creator,
+                &self.series,
+                &self.series_authority,
+                &self.pool_option_vault,
+                &self.pool_premium_vault,
+                &self.price_account,
+                IMPLIED_VOLATILITY,
+                spread_bps,
+            )
+        }
+
+        fn buy(&self, amount: u64, max_premium: u64) -> Instruction {
+            buy_from_pool(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.series,
+                &self.series_authority,
+                &self.price_account,
+                &self.user_quote,
+                &self.pool_premium_vault,
+                &self.pool_option_vault,
+                &self.user_options,
+                amount,
+                max_premium,
+            )
+        }
+
+        fn sell(&self, amount: u64, min_premium: u64) -> Instruction {
+            sell_to_pool(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.series,
+                &self.series_authority,
+                &self.price_account,
+                &self.user_options,
+                &self.pool_option_vault,
+                &self.pool_premium_vault,
+                &self.user_quote,
+                amount,
+                min_premium,
+            )
+        }
+
+        fn withdraw(
+            &self,
+            pool_admin: &Pubkey,
+            option_amount: u64,
+            premium_amount: u64,
+        ) -> Instruction {
+            withdraw_from_pool(
+                &self.program_id,
+                pool_admin,
+                &self.series,
+                &self.series_authority,
+                &self.pool_option_vault,
+                &self.pool_premium_vault,
+                &self.user_options,
+                &self.user_quote,
+                option_amount,
+                premium_amount,
+            )
+        }
+    }
+
+    /// Starts a test validator at `NOW` reading prices with `FakeOracle`, with
+    /// an initialized call series, empty pool vaults and a user who has
+    /// written 20 options. The payer mints both tokens.
+    async fn start_with_series() -> (ProgramTestContext, Keypair, PoolFixture) {
+        let (program_test, program_id) = program_test_with_fake_oracle();
+        let (mut context, payer) = start_at_now(program_test).await;
+        let price_account = Pubkey::new_unique();
+        set_fake_price(&mut context, &price_account, SPOT_PRICE, NOW);
+        let banks_client = &mut context.banks_client;
+
+        let accounts = create_series_accounts(banks_client, &payer, &program_id).await;
+        let series_authority = accounts.series_authority;
+        let pool_option_vault = create_token_account(
+            banks_client,
+            &payer,
+            &accounts.option_mint,
+            &series_authority,
+        )
+        .await;
+        let pool_premium_vault = create_token_account(
+            banks_client,
+            &payer,
+            &accounts.quote_mint,
+            &series_authority,
+        )
+        .await;
+        process(
+            banks_client,
+            &payer,
+            &[initialize_series(
+                &program_id,
+                &payer.pubkey(),
+                &accounts.series,
+                &series_authority,
+                &accounts.underlying_mint,
+                &accounts.quote_mint,
+                &accounts.option_mint,
+                &accounts.writer_mint,
+                &accounts.underlying_vault,
+                &accounts.quote_vault,
+                Some(&price_account),
+                OptionKind::Call,
+                STRIKE,
+                CONTRACT_SIZE,
+                EXPIRY,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        // Fund the user with both tokens and write options
+        let user = Keypair::new();
+        let user_underlying = create_funded_token_account(
+            banks_client,
+            &payer,
+            &accounts.underlying_mint,
+            &user.pubkey(),
+            USER_FUNDS,
+        )
+        .await;
+        let user_quote = create_funded_token_account(
+            banks_client,
+            &payer,
+            &accounts.quote_mint,
+            &user.pubkey(),
+            USER_FUNDS,
+        )
+        .await;
+        let user_options =
+            create_token_account(banks_client, &payer, &accounts.option_mint, &user.pubkey()).await;
+        let user_writer_tokens =
+            create_token_account(banks_client, &payer, &accounts.writer_mint, &user.pubkey()).await;
+        process(
+            banks_client,
+            &payer,
+            &[write_option(
+                &program_id,
+                &user.pubkey(),
+                &accounts.series,
+                &series_authority,
+                &user_underlying,
+                &accounts.underlying_vault,
+                &accounts.option_mint,
+                &accounts.writer_mint,
+                &user_options,
+                &user_writer_tokens,
+                20,
+            )],
+            &[&user],
+        )
+        .await
+        .unwrap();
+
+        let fixture = PoolFixture {
+            program_id,
+            series: accounts.series,
+            series_authority,
+            pool_option_vault,
+            pool_premium_vault,
+            price_account,
+            user,
+            user_quote,
+            user_options,
+        };
+        (context, payer, fixture)
+    }
+
+    /// Starts like `start_with_series`, then opens the pool with the payer
+    /// as admin and stocks it with options from the user and premium from
+    /// the payer
+    async fn start_with_pool() -> (ProgramTestContext, Keypair, PoolFixture) {
+        let (mut context, payer, fixture) = start_with_series().await;
+        let banks_client = &mut context.banks_client;
+        let quote_mint = get_series(banks_client, &fixture.series).await.quote_mint;
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.initialize_pool(&payer.pubkey(), SPREAD_BPS),
+                spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    &fixture.user_options,
+                    &fixture.pool_option_vault,
+                    &fixture.user.pubkey(),
+                    &[],
+                    POOL_OPTIONS,
+                )
+                .unwrap(),
+                spl_token::instruction::mint_to(
+                    &spl_token::id(),
+                    &quote_mint,
+                    &fixture.pool_premium_vault,
+                    &payer.pubkey(),
+                    &[],
+                    POOL_PREMIUM,
+                )
+                .unwrap(),
+            ],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        (context, payer, fixture)
+    }
+
+    #[tokio::test]
+    async fn test_initialize_pool() {
+        let (mut context, payer, fixture) = start_with_pool().await;
+        let banks_client = &mut context.banks_client;
+
+        let series = get_series(banks_client, &fixture.series).await;
+        assert!(series.has_pool);
+        assert_eq!(series.pool_admin, payer.pubkey());
+        assert_eq!(series.pool_option_vault, fixture.pool_option_vault);
+        assert_eq!(series.pool_premium_vault, fixture.pool_premium_vault);
+        assert_eq!(series.price_account, fixture.price_account);
+        assert_eq!(series.implied_volatility, IMPLIED_VOLATILITY);
+        assert_eq!(series.pool_spread_bps, SPREAD_BPS);
+
+        // The model premium of a one-week at-the-money call at 80% volatility
+        // is about 0.4 * 0.8 * sqrt(7 / 365) of the spot
+        let premium = series.model_premium(SPOT, NOW).unwrap();
+        assert!((88_000..89_000).contains(&premium), "{premium}");
+
+        // A second pool is rejected
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.initialize_pool(&payer.pubkey(), SPREAD_BPS + 1)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::PoolAlreadyInitialized)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_initialize_pool_rejects_invalid_parameters() {
+        let (mut context, payer, fixture) = start_with_series().await;
+        let banks_client = &mut context.banks_client;
+
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.initialize_pool(&payer.pubkey(), 10_000)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidPoolParameters)
+        );
+
+        // The option vault must hold the series' options
+        let mut instruction = fixture.initialize_pool(&payer.pubkey(), SPREAD_BPS);
+        instruction.accounts[3].pubkey = fixture.pool_premium_vault;
+        assert_eq!(
+            process(banks_client, &payer, &[instruction], &[])
+                .await
+                .unwrap_err(),
+            custom_error(TemplateError::InvalidVault)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_initialize_pool_rejects_others_and_other_price_accounts() {
+        let (mut context, payer, fixture) = start_with_series().await;
+        let other_price_account = Pubkey::new_unique();
+        set_fake_price(&mut context, &other_price_account, SPOT_PRICE / 2, NOW);
+        let banks_client = &mut context.banks_client;
+
+        // Only the series creator can open the pool
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.initialize_pool(&fixture.user.pubkey(), SPREAD_BPS)],
+                &[&fixture.user],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidAuthority)
+        );
+
+        // The pool reads the price account bound at initialization
+        let mut instruction = fixture.initialize_pool(&payer.pubkey(), SPREAD_BPS);
+        instruction.accounts[5].pubkey = other_price_account;
+        assert_eq!(
+            process(banks_client, &payer, &[instruction], &[])
+                .await
+                .unwrap_err(),
+            custom_error(TemplateError::InvalidPriceAccount)
+        );
+        assert_eq!(
+            get_series(banks_client, &fixture.series)
+                .await
+                .price_account,
+            fixture.price_account
+        );
+    }
+
+    #[tokio::test]
+    async fn test_buy_from_pool() {
+        let (mut context, payer, fixture) = start_with_pool().await;
+        let banks_client = &mut context.banks_client;
+
+        let series = get_series(banks_client, &fixture.series).await;
+        let model_premium = 3 * series.model_premium(SPOT, NOW).unwrap();
+        let premium = (model_premium * (10_000 + SPREAD_BPS)).div_ceil(10_000);
+        let user_quote_before = token_balance(banks_client, &fixture.user_quote).await;
+
+        process(
+            banks_client,
+            &payer,
+            &[fixture.buy(3, premium)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_quote).await,
+            user_quote_before - premium
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.pool_premium_vault).await,
+            POOL_PREMIUM + premium
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.pool_option_vault).await,
+            POOL_OPTIONS - 3
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_options).await,
+            20 - POOL_OPTIONS + 3
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sell_to_pool() {
+        let (mut context, payer, fixture) = start_with_pool().await;
+        let banks_client = &mut context.banks_client;
+
+        let series = get_series(banks_client, &fixture.series).await;
+        let model_premium = 4 * series.model_premium(SPOT, NOW).unwrap();
+        let premium = model_premium * (10_000 - SPREAD_BPS) / 10_000;
+        let user_quote_before = token_balance(banks_client, &fixture.user_quote).await;
+
+        process(
+            banks_client,
+            &payer,
+            &[fixture.sell(4, premium)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_quote).await,
+            user_quote_before + premium
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.pool_premium_vault).await,
+            POOL_PREMIUM - premium
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.pool_option_vault).await,
+            POOL_OPTIONS + 4
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pool_slippage() {
+        let (mut context, payer, fixture) = start_with_pool().await;
+        let banks_client = &mut context.banks_client;
+
+        let series = get_series(banks_client, &fixture.series).await;
+        let model_premium = series.model_premium(SPOT, NOW).unwrap();
+        let ask = (model_premium * (10_000 + SPREAD_BPS)).div_ceil(10_000);
+        let bid = model_premium * (10_000 - SPREAD_BPS) / 10_000;
+
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.buy(1, ask - 1)],
+                &[&fixture.user],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::SlippageExceeded)
+        );
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.sell(1, bid + 1)],
+                &[&fixture.user],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::SlippageExceeded)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pool_follows_spot_and_volatility() {
+        let (mut context, payer, fixture) = start_with_pool().await;
+
+        // A higher spot makes the call dearer
+        set_fake_price(&mut context, &fixture.price_account, 220_000_000, NOW);
+        let banks_client = &mut context.banks_client;
+        let series = get_series(banks_client, &fixture.series).await;
+        let at_the_money = series.model_premium(SPOT, NOW).unwrap();
+        let in_the_money = series.model_premium(2_200_000, NOW).unwrap();
+        assert!(in_the_money > at_the_money);
+        assert!(in_the_money > 2_200_000 - STRIKE);
+        let ask = (in_the_money * (10_000 + SPREAD_BPS)).div_ceil(10_000);
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.buy(1, ask - 1)],
+                &[&fixture.user],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::SlippageExceeded)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[fixture.buy(1, ask)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+
+        // Only the pool admin can change the implied volatility
+        let half_volatility = IMPLIED_VOLATILITY / 2;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[set_implied_volatility(
+                    &fixture.program_id,
+                    &fixture.user.pubkey(),
+                    &fixture.series,
+                    half_volatility,
+                )],
+                &[&fixture.user],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidAuthority)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[set_implied_volatility(
+                &fixture.program_id,
+                &payer.pubkey(),
+                &fixture.series,
+                half_volatility,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+        let series = get_series(banks_client, &fixture.series).await;
+        assert_eq!(series.implied_volatility, half_volatility);
+        assert!(series.model_premium(2_200_000, NOW).unwrap() < in_the_money);
+    }
+
+    #[tokio::test]
+    async fn test_pool_rejects_stale_price() {
+        let (mut context, payer, fixture) = start_with_pool().await;
+        set_fake_price(&mut context, &fixture.price_account, SPOT_PRICE, NOW - 61);
+        let banks_client = &mut context.banks_client;
+
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.buy(1, u64::MAX)],
+                &[&fixture.user],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::StalePrice)
+        );
+
+        // Other price accounts are rejected
+        let mut instruction = fixture.sell(1, 0);
+        instruction.accounts[3].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            process(banks_client, &payer, &[instruction], &[&fixture.user])
+                .await
+                .unwrap_err(),
+            custom_error(TemplateError::InvalidPriceAccount)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pool_closes_at_expiry() {
+        let (mut context, payer, fixture) = start_with_pool().await;
+        set_fake_price(&mut context, &fixture.price_account, SPOT_PRICE, EXPIRY);
+        set_timestamp(&mut context, EXPIRY).await;
+        let banks_client = &mut context.banks_client;
+
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.buy(1, u64::MAX)],
+                &[&fixture.user],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::SeriesExpired)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_withdraw_from_pool() {
+        let (mut context, payer, fixture) = start_with_pool().await;
+        let banks_client = &mut context.banks_client;
+
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.withdraw(&fixture.user.pubkey(), 1, 1)],
+                &[&fixture.user],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidAuthority)
+        );
+
+        let user_quote_before = token_balance(banks_client, &fixture.user_quote).await;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.withdraw(&payer.pubkey(), POOL_OPTIONS, 1_000_000)],
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.pool_option_vault).await,
+            0
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.pool_premium_vault).await,
+            POOL_PREMIUM - 1_000_000
+        );
+        assert_eq!(token_balance(banks_client, &fixture.user_options).await, 20);
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_quote).await,
+            user_quote_before + 1_000_000
+        );
+    }
+}
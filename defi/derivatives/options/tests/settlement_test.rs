@@ -0,0 +1,613 @@
+//! Integration tests for cash-settled series in options-protocol
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::common::{
+            create_funded_token_account, create_series_accounts, create_token_account,
+            custom_error, get_series, new_blockhash, process, process_with_blockhash, program_test,
+            program_test_with_fake_oracle, set_account_data, set_fake_price, set_timestamp,
+            start_at_now, token_balance, EXPIRY, NOW,
+        },
+        options_protocol::{
+            errors::TemplateError,
+            instructions::{
+                claim_settlement, exercise_option, initialize_cash_settled_series,
+                redeem_collateral, settle_series, write_option,
+            },
+            oracle::{
+                OraclePrice, PYTH_MAGIC, PYTH_PRICE_ACCOUNT_TYPE, PYTH_STATUS_TRADING, PYTH_VERSION,
+            },
+            state::{OptionKind, Settlement, MAX_SETTLEMENT_DELAY},
+        },
+        solana_program::{instruction::Instruction, pubkey::Pubkey},
+        solana_program_test::{BanksClient, ProgramTestContext},
+        solana_sdk::signature::{Keypair, Signer},
+    };
+
+    /// 2 quote tokens (6 decimals) for one underlying token (9 decimals)
+    const STRIKE: u64 = 2_000_000;
+    const CONTRACT_SIZE: u64 = 1_000_000_000;
+    const MAX_PAYOFF: u64 = 1_000_000;
+    const USER_FUNDS: u64 = 1_000_000_000_000;
+
+    /// Accounts of a cash-settled series set up for testing
+    struct SeriesFixture {
+        program_id: Pubkey,
+        series: Pubkey,
+        series_authority: Pubkey,
+        underlying_mint: Pubkey,
+        quote_mint: Pubkey,
+        option_mint: Pubkey,
+        writer_mint: Pubkey,
+        underlying_vault: Pubkey,
+        quote_vault: Pubkey,
+        price_account: Pubkey,
+        kind: OptionKind,
+        user: Keypair,
+        user_underlying: Pubkey,
+        user_quote: Pubkey,
+        user_options: Pubkey,
+        user_writer_tokens: Pubkey,
+    }
+
+    impl SeriesFixture {
+        fn initialize(&self, creator: &Pubkey) -> Instruction {
+            initialize_cash_settled_series(
+                &self.program_id,
+                creator,
+                &self.series,
+                &self.series_authority,
+                &self.underlying_mint,
+                &self.quote_mint,
+                &self.option_mint,
+                &self.writer_mint,
+                &self.underlying_vault,
+                &self.quote_vault,
+                &self.price_account,
+                self.kind,
+                STRIKE,
+                CONTRACT_SIZE,
+                EXPIRY,
+                MAX_PAYOFF,
+            )
+        }
+
+        fn write(&self, amount: u64) -> Instruction {
+            write_option(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.series,
+                &self.series_authority,
+                &self.user_quote,
+                &self.quote_vault,
+                &self.option_mint,
+                &self.writer_mint,
+                &self.user_options,
+                &self.user_writer_tokens,
+                amount,
+            )
+        }
+
+        fn settle(&self) -> Instruction {
+            settle_series(&self.program_id, &self.series, &self.price_account)
+        }
+
+        fn claim(&self, amount: u64) -> Instruction {
+            claim_settlement(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.series,
+                &self.series_authority,
+                &self.option_mint,
+                &self.user_options,
+                &self.quote_vault,
+                &self.user_quote,
+                amount,
+            )
+        }
+
+        fn redeem(&self, amount: u64) -> Instruction {
+            redeem_collateral(
+                &self.program_id,
+                &self.user.pubkey(),
+                &self.series,
+                &self.series_authority,
+                &self.writer_mint,
+                &self.user_writer_tokens,
+                &self.underlying_vault,
+                &self.quote_vault,
+                &self.user_underlying,
+                &self.user_quote,
+                amount,
+            )
+        }
+    }
+
+    /// Stores a Pyth v2 price account with the given aggregate price and status
+    fn set_pyth_price(
+        context: &mut ProgramTestContext,
+        price_account: &Pubkey,
+        price: i64,
+        expo: i32,
+        publish_time: i64,
+        status: u32,
+    ) {
+        let mut data = vec![0; 3312];
+        data[0..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&PYTH_VERSION.to_le_bytes());
+        data[8..12].copy_from_slice(&PYTH_PRICE_ACCOUNT_TYPE.to_le_bytes());
+        data[20..24].copy_from_slice(&expo.to_le_bytes());
+        data[96..104].copy_from_slice(&publish_time.to_le_bytes());
+        data[208..216].copy_from_slice(&price.to_le_bytes());
+        data[224..228].copy_from_slice(&status.to_le_bytes());
+        set_account_data(context, price_account, data);
+    }
+
+    /// Creates the accounts of an uninitialized cash-settled series and a
+    /// user funded with both tokens
+    async fn setup_series(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        program_id: Pubkey,
+        kind: OptionKind,
+    ) -> SeriesFixture {
+        let accounts = create_series_accounts(banks_client, payer, &program_id).await;
+        let user = Keypair::new();
+        let user_underlying = create_funded_token_account(
+            banks_client,
+            payer,
+            &accounts.underlying_mint,
+            &user.pubkey(),
+            USER_FUNDS,
+        )
+        .await;
+        let user_quote = create_funded_token_account(
+            banks_client,
+            payer,
+            &accounts.quote_mint,
+            &user.pubkey(),
+            USER_FUNDS,
+        )
+        .await;
+        let user_options =
+            create_token_account(banks_client, payer, &accounts.option_mint, &user.pubkey()).await;
+        let user_writer_tokens =
+            create_token_account(banks_client, payer, &accounts.writer_mint, &user.pubkey()).await;
+
+        SeriesFixture {
+            program_id,
+            series: accounts.series,
+            series_authority: accounts.series_authority,
+            underlying_mint: accounts.underlying_mint,
+            quote_mint: accounts.quote_mint,
+            option_mint: accounts.option_mint,
+            writer_mint: accounts.writer_mint,
+            underlying_vault: accounts.underlying_vault,
+            quote_vault: accounts.quote_vault,
+            price_account: Pubkey::new_unique(),
+            kind,
+            user,
+            user_underlying,
+            user_quote,
+            user_options,
+            user_writer_tokens,
+        }
+    }
+
+    /// Starts a test validator at `NOW` reading prices with `FakeOracle`, with
+    /// an initialized cash-settled series that the user has written 10 options of
+    async fn start_with_written_series(
+        kind: OptionKind,
+    ) -> (ProgramTestContext, Keypair, SeriesFixture) {
+        let (program_test, program_id) = program_test_with_fake_oracle();
+        let (mut context, payer) = start_at_now(program_test).await;
+        let fixture = setup_series(&mut context.banks_client, &payer, program_id, kind).await;
+        set_fake_price(&mut context, &fixture.price_account, 200_000_000, NOW);
+
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.initialize(&payer.pubkey())],
+            &[],
+        )
+        .await
+        .unwrap();
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.write(10)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        (context, payer, fixture)
+    }
+
+    #[test]
+    fn test_oracle_price_quote_amount() {
+        let price = |price, expo| OraclePrice {
+            price,
+            expo,
+            publish_time: 0,
+        };
+
+        // 2.6 quote tokens with 6 decimals for one underlying token with 9 decimals
+        assert_eq!(
+            price(260_000_000, -8).quote_amount(1_000_000_000, 9, 6),
+            Ok(2_600_000)
+        );
+        // Rounds down
+        assert_eq!(price(260_000_000, -8).quote_amount(1, 9, 6), Ok(0));
+        // Positive exponents scale up
+        assert_eq!(price(3, 2).quote_amount(5, 0, 2), Ok(150_000));
+        assert_eq!(
+            price(0, -8).quote_amount(1, 9, 6),
+            Err(TemplateError::InvalidOraclePrice)
+        );
+        assert_eq!(
+            price(-1, -8).quote_amount(1, 9, 6),
+            Err(TemplateError::InvalidOraclePrice)
+        );
+        assert_eq!(
+            price(i64::MAX, 10).quote_amount(u64::MAX, 0, 0),
+            Err(TemplateError::MathOverflow)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_initialize_cash_settled_series() {
+        let (mut context, _, fixture) = start_with_written_series(OptionKind::Call).await;
+        let banks_client = &mut context.banks_client;
+
+        let series = get_series(banks_client, &fixture.series).await;
+        assert_eq!(series.settlement, Settlement::Cash);
+        assert_eq!(series.price_account, fixture.price_account);
+        assert_eq!(series.underlying_decimals, 9);
+        assert_eq!(series.quote_decimals, 6);
+        assert_eq!(series.max_payoff, MAX_PAYOFF);
+        assert!(!series.is_settled);
+
+        // Calls are collateralized with the quote token too
+        assert_eq!(
+            token_balance(banks_client, &fixture.quote_vault).await,
+            10 * MAX_PAYOFF
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.underlying_vault).await,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_settle_call_in_the_money() {
+        let (mut context, payer, fixture) = start_with_written_series(OptionKind::Call).await;
+        set_fake_price(&mut context, &fixture.price_account, 260_000_000, EXPIRY);
+        set_timestamp(&mut context, EXPIRY).await;
+        let banks_client = &mut context.banks_client;
+
+        process(banks_client, &payer, &[fixture.settle()], &[])
+            .await
+            .unwrap();
+        let series = get_series(banks_client, &fixture.series).await;
+        assert!(series.is_settled);
+        assert_eq!(series.settlement_price, 2_600_000);
+        assert_eq!(series.settlement_payoff(), 600_000);
+
+        // Holders receive (S - K) per option
+        let quote_before = token_balance(banks_client, &fixture.user_quote).await;
+        process(banks_client, &payer, &[fixture.claim(10)], &[&fixture.user])
+            .await
+            .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_quote).await,
+            quote_before + 10 * 600_000
+        );
+        assert_eq!(token_balance(banks_client, &fixture.user_options).await, 0);
+
+        // Writers receive the rest of their collateral
+        process(
+            banks_client,
+            &payer,
+            &[fixture.redeem(10)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_quote).await,
+            quote_before + 10 * MAX_PAYOFF
+        );
+        assert_eq!(token_balance(banks_client, &fixture.quote_vault).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_settle_put_out_of_the_money() {
+        let (mut context, payer, fixture) = start_with_written_series(OptionKind::Put).await;
+        set_fake_price(
+            &mut context,
+            &fixture.price_account,
+            260_000_000,
+            EXPIRY + 30,
+        );
+        set_timestamp(&mut context, EXPIRY + 60).await;
+        let banks_client = &mut context.banks_client;
+
+        process(banks_client, &payer, &[fixture.settle()], &[])
+            .await
+            .unwrap();
+        let quote_before = token_balance(banks_client, &fixture.user_quote).await;
+
+        // Options expire worthless but are still burned
+        process(banks_client, &payer, &[fixture.claim(4)], &[&fixture.user])
+            .await
+            .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_quote).await,
+            quote_before
+        );
+        assert_eq!(token_balance(banks_client, &fixture.user_options).await, 6);
+
+        process(
+            banks_client,
+            &payer,
+            &[fixture.redeem(10)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_quote).await,
+            quote_before + 10 * MAX_PAYOFF
+        );
+    }
+
+    #[tokio::test]
+    async fn test_payoff_capped_at_max_payoff() {
+        let (mut context, payer, fixture) = start_with_written_series(OptionKind::Call).await;
+        set_fake_price(&mut context, &fixture.price_account, 500_000_000, EXPIRY);
+        set_timestamp(&mut context, EXPIRY).await;
+        let banks_client = &mut context.banks_client;
+
+        process(banks_client, &payer, &[fixture.settle()], &[])
+            .await
+            .unwrap();
+        let quote_before = token_balance(banks_client, &fixture.user_quote).await;
+
+        process(banks_client, &payer, &[fixture.claim(10)], &[&fixture.user])
+            .await
+            .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_quote).await,
+            quote_before + 10 * MAX_PAYOFF
+        );
+
+        // Nothing is left for the writers
+        process(
+            banks_client,
+            &payer,
+            &[fixture.redeem(10)],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_quote).await,
+            quote_before + 10 * MAX_PAYOFF
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.user_writer_tokens).await,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_settle_series_rejections() {
+        let (mut context, payer, fixture) = start_with_written_series(OptionKind::Call).await;
+
+        // Before expiry
+        let err = process(&mut context.banks_client, &payer, &[fixture.settle()], &[])
+            .await
+            .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::SeriesNotExpired));
+
+        // With a price published before expiry. Every retry of the same
+        // settle transaction is signed with a fresh blockhash so it is not
+        // answered with the status cached for the previous attempt.
+        let recent_blockhash = new_blockhash(&mut context.banks_client).await;
+        set_timestamp(&mut context, EXPIRY).await;
+        set_fake_price(
+            &mut context,
+            &fixture.price_account,
+            260_000_000,
+            EXPIRY - 1,
+        );
+        let err = process_with_blockhash(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.settle()],
+            &[],
+            recent_blockhash,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::PriceBeforeExpiry));
+
+        // With another price account
+        let other_price_account = Pubkey::new_unique();
+        set_fake_price(&mut context, &other_price_account, 260_000_000, EXPIRY);
+        let err = process(
+            &mut context.banks_client,
+            &payer,
+            &[settle_series(
+                &fixture.program_id,
+                &fixture.series,
+                &other_price_account,
+            )],
+            &[],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidPriceAccount));
+
+        // With a price published after the settlement window
+        let recent_blockhash = new_blockhash(&mut context.banks_client).await;
+        set_timestamp(&mut context, EXPIRY + MAX_SETTLEMENT_DELAY + 1).await;
+        set_fake_price(
+            &mut context,
+            &fixture.price_account,
+            260_000_000,
+            EXPIRY + MAX_SETTLEMENT_DELAY + 1,
+        );
+        let err = process_with_blockhash(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.settle()],
+            &[],
+            recent_blockhash,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::PriceAfterSettlementWindow));
+
+        // Settles once
+        let recent_blockhash = new_blockhash(&mut context.banks_client).await;
+        set_fake_price(&mut context, &fixture.price_account, 260_000_000, EXPIRY);
+        process_with_blockhash(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.settle()],
+            &[],
+            recent_blockhash,
+        )
+        .await
+        .unwrap();
+        let recent_blockhash = new_blockhash(&mut context.banks_client).await;
+        set_fake_price(
+            &mut context,
+            &fixture.price_account,
+            300_000_000,
+            EXPIRY + 1,
+        );
+        let err = process_with_blockhash(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.settle()],
+            &[],
+            recent_blockhash,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::SeriesAlreadySettled));
+        let series = get_series(&mut context.banks_client, &fixture.series).await;
+        assert_eq!(series.settlement_price, 2_600_000);
+    }
+
+    #[tokio::test]
+    async fn test_claim_and_redeem_require_settlement() {
+        let (mut context, payer, fixture) = start_with_written_series(OptionKind::Call).await;
+        set_timestamp(&mut context, EXPIRY).await;
+        let banks_client = &mut context.banks_client;
+
+        let err = process(banks_client, &payer, &[fixture.claim(1)], &[&fixture.user])
+            .await
+            .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::SeriesNotSettled));
+
+        let err = process(banks_client, &payer, &[fixture.redeem(1)], &[&fixture.user])
+            .await
+            .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::SeriesNotSettled));
+    }
+
+    #[tokio::test]
+    async fn test_exercise_rejected_for_cash_settled_series() {
+        let (mut context, payer, fixture) = start_with_written_series(OptionKind::Call).await;
+        set_timestamp(&mut context, EXPIRY - 100).await;
+
+        let err = process(
+            &mut context.banks_client,
+            &payer,
+            &[exercise_option(
+                &fixture.program_id,
+                &fixture.user.pubkey(),
+                &fixture.series,
+                &fixture.series_authority,
+                &fixture.option_mint,
+                &fixture.user_options,
+                &fixture.user_quote,
+                &fixture.quote_vault,
+                &fixture.underlying_vault,
+                &fixture.user_underlying,
+                1,
+            )],
+            &[&fixture.user],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidSettlementType));
+    }
+
+    #[tokio::test]
+    async fn test_settle_with_pyth_price_account() {
+        let (program_test, program_id) = program_test();
+        let (mut context, payer) = start_at_now(program_test).await;
+        let fixture = setup_series(
+            &mut context.banks_client,
+            &payer,
+            program_id,
+            OptionKind::Put,
+        )
+        .await;
+        set_pyth_price(
+            &mut context,
+            &fixture.price_account,
+            180_000,
+            -5,
+            NOW,
+            PYTH_STATUS_TRADING,
+        );
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.initialize(&payer.pubkey())],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        // Halted feeds cannot settle
+        set_timestamp(&mut context, EXPIRY).await;
+        set_pyth_price(&mut context, &fixture.price_account, 180_000, -5, EXPIRY, 2);
+        let err = process(&mut context.banks_client, &payer, &[fixture.settle()], &[])
+            .await
+            .unwrap_err();
+        assert_eq!(err, custom_error(TemplateError::InvalidOraclePrice));
+
+        // 1.8 quote tokens per underlying token. The retry needs a fresh
+        // blockhash to be processed rather than answered from the status cache.
+        let recent_blockhash = new_blockhash(&mut context.banks_client).await;
+        set_pyth_price(
+            &mut context,
+            &fixture.price_account,
+            180_000,
+            -5,
+            EXPIRY,
+            PYTH_STATUS_TRADING,
+        );
+        process_with_blockhash(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.settle()],
+            &[],
+            recent_blockhash,
+        )
+        .await
+        .unwrap();
+        let series = get_series(&mut context.banks_client, &fixture.series).await;
+        assert_eq!(series.settlement_price, 1_800_000);
+        assert_eq!(series.settlement_payoff(), 200_000);
+    }
+}
@@ -5,110 +5,718 @@ import {
   Connection,
   Keypair,
   PublicKey,
-  SystemProgram,
   SYSVAR_RENT_PUBKEY,
   Transaction,
   sendAndConfirmTransaction,
 } from '@solana/web3.js';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
 import * as borsh from 'borsh';
 import BN from 'bn.js';
 
 // Define the program ID (replace with your actual program ID)
-const PROGRAM_ID = new PublicKey('perpetual-futures111111111111111111111111111111');
+const PROGRAM_ID = new PublicKey('Perp111111111111111111111111111111111111111');
 
 // Define instruction types
 enum TemplateInstructionType {
-  Initialize = 0,
-  Operation1 = 1,
-  Operation2 = 2,
+  InitializeMarket = 0,
+  OpenPosition = 1,
+  IncreasePosition = 2,
+  ClosePosition = 3,
+  UpdateFunding = 4,
+  LiquidatePosition = 5,
+  InitializeMarginAccount = 6,
+  DepositMargin = 7,
+  WithdrawMargin = 8,
+  IncreaseMarginPosition = 9,
+  ReduceMarginPosition = 10,
+  RepegAmm = 11,
+}
+
+// Define position sides
+enum Side {
+  Long = 0,
+  Short = 1,
+}
+
+// Define pricing modes
+enum PricingMode {
+  Skew = 0,
+  Vamm = 1,
 }
 
 // Define instruction schema
-class InitializeInstruction {
-  param1: BN;
-  param2: number;
-  
-  constructor(props: { param1: BN; param2: number }) {
-    this.param1 = props.param1;
-    this.param2 = props.param2;
+class InitializeMarketInstruction {
+  base_decimals: number;
+  initial_margin_bps: BN;
+  maintenance_margin_bps: BN;
+  liquidation_reward_bps: BN;
+  taker_fee_bps: BN;
+  skew_scale: BN;
+  max_funding_rate_bps: BN;
+  pricing_mode: number;
+  amm_base_reserve: BN;
+
+  constructor(props: {
+    base_decimals: number;
+    initial_margin_bps: BN;
+    maintenance_margin_bps: BN;
+    liquidation_reward_bps: BN;
+    taker_fee_bps: BN;
+    skew_scale: BN;
+    max_funding_rate_bps: BN;
+    pricing_mode: number;
+    amm_base_reserve: BN;
+  }) {
+    this.base_decimals = props.base_decimals;
+    this.initial_margin_bps = props.initial_margin_bps;
+    this.maintenance_margin_bps = props.maintenance_margin_bps;
+    this.liquidation_reward_bps = props.liquidation_reward_bps;
+    this.taker_fee_bps = props.taker_fee_bps;
+    this.skew_scale = props.skew_scale;
+    this.max_funding_rate_bps = props.max_funding_rate_bps;
+    this.pricing_mode = props.pricing_mode;
+    this.amm_base_reserve = props.amm_base_reserve;
   }
-  
+
   static schema = new Map([
     [
-      InitializeInstruction,
+      InitializeMarketInstruction,
       {
         kind: 'struct',
         fields: [
-          ['param1', 'u64'],
-          ['param2', 'u8'],
+          ['base_decimals', 'u8'],
+          ['initial_margin_bps', 'u64'],
+          ['maintenance_margin_bps', 'u64'],
+          ['liquidation_reward_bps', 'u64'],
+          ['taker_fee_bps', 'u64'],
+          ['skew_scale', 'u64'],
+          ['max_funding_rate_bps', 'u64'],
+          ['pricing_mode', 'u8'],
+          ['amm_base_reserve', 'u64'],
         ],
       },
     ],
   ]);
 }
 
-class Operation1Instruction {
+class OpenPositionInstruction {
+  side: number;
+  size: BN;
+  collateral: BN;
+  price_limit: BN;
+
+  constructor(props: { side: number; size: BN; collateral: BN; price_limit: BN }) {
+    this.side = props.side;
+    this.size = props.size;
+    this.collateral = props.collateral;
+    this.price_limit = props.price_limit;
+  }
+
+  static schema = new Map([
+    [
+      OpenPositionInstruction,
+      {
+        kind: 'struct',
+        fields: [
+          ['side', 'u8'],
+          ['size', 'u64'],
+          ['collateral', 'u64'],
+          ['price_limit', 'u64'],
+        ],
+      },
+    ],
+  ]);
+}
+
+class IncreasePositionInstruction {
+  size: BN;
+  collateral: BN;
+  price_limit: BN;
+
+  constructor(props: { size: BN; collateral: BN; price_limit: BN }) {
+    this.size = props.size;
+    this.collateral = props.collateral;
+    this.price_limit = props.price_limit;
+  }
+
+  static schema = new Map([
+    [
+      IncreasePositionInstruction,
+      {
+        kind: 'struct',
+        fields: [
+          ['size', 'u64'],
+          ['collateral', 'u64'],
+          ['price_limit', 'u64'],
+        ],
+      },
+    ],
+  ]);
+}
+
+class ClosePositionInstruction {
+  size: BN;
+  price_limit: BN;
+
+  constructor(props: { size: BN; price_limit: BN }) {
+    this.size = props.size;
+    this.price_limit = props.price_limit;
+  }
+
+  static schema = new Map([
+    [
+      ClosePositionInstruction,
+      {
+        kind: 'struct',
+        fields: [
+          ['size', 'u64'],
+          ['price_limit', 'u64'],
+        ],
+      },
+    ],
+  ]);
+}
+
+class MarginAmountInstruction {
   amount: BN;
-  
+
   constructor(props: { amount: BN }) {
     this.amount = props.amount;
   }
-  
+
   static schema = new Map([
     [
-      Operation1Instruction,
+      MarginAmountInstruction,
+      {
+        kind: 'struct',
+        fields: [['amount', 'u64']],
+      },
+    ],
+  ]);
+}
+
+// IncreaseMarginPosition shares the layout of OpenPosition without the
+// collateral, and ReduceMarginPosition the layout of ClosePosition
+class IncreaseMarginPositionInstruction {
+  side: number;
+  size: BN;
+  price_limit: BN;
+
+  constructor(props: { side: number; size: BN; price_limit: BN }) {
+    this.side = props.side;
+    this.size = props.size;
+    this.price_limit = props.price_limit;
+  }
+
+  static schema = new Map([
+    [
+      IncreaseMarginPositionInstruction,
       {
         kind: 'struct',
         fields: [
-          ['amount', 'u64'],
+          ['side', 'u8'],
+          ['size', 'u64'],
+          ['price_limit', 'u64'],
         ],
       },
     ],
   ]);
 }
 
+// Accounts of one position a margin account backs, passed to instructions
+// that check the health of the margin account
+interface HealthAccounts {
+  position: PublicKey;
+  market: PublicKey;
+  priceAccount: PublicKey;
+}
+
+// Accounts of a cross-margin position
+interface MarginPositionAccounts {
+  marginAccount: PublicKey;
+  market: PublicKey;
+  position: PublicKey;
+  priceAccount: PublicKey;
+  marginVault: PublicKey;
+  collateralVault: PublicKey;
+}
+
+/**
+ * Derive the market authority PDA that owns the collateral vault
+ */
+async function findMarketAuthority(market: PublicKey): Promise<PublicKey> {
+  const [authority] = await PublicKey.findProgramAddress([market.toBuffer()], PROGRAM_ID);
+  return authority;
+}
+
+/**
+ * Derive the margin authority PDA that owns the vault of a margin account
+ */
+async function findMarginAuthority(marginAccount: PublicKey): Promise<PublicKey> {
+  const [authority] = await PublicKey.findProgramAddress([marginAccount.toBuffer()], PROGRAM_ID);
+  return authority;
+}
+
+function healthAccountMetas(healthAccounts: HealthAccounts[]) {
+  return healthAccounts.flatMap(accounts => [
+    { pubkey: accounts.position, isSigner: false, isWritable: false },
+    { pubkey: accounts.market, isSigner: false, isWritable: false },
+    { pubkey: accounts.priceAccount, isSigner: false, isWritable: false },
+  ]);
+}
+
+async function marginPositionAccountMetas(owner: PublicKey, accounts: MarginPositionAccounts) {
+  return [
+    { pubkey: owner, isSigner: true, isWritable: false },
+    { pubkey: accounts.marginAccount, isSigner: false, isWritable: true },
+    {
+      pubkey: await findMarginAuthority(accounts.marginAccount),
+      isSigner: false,
+      isWritable: false,
+    },
+    { pubkey: accounts.market, isSigner: false, isWritable: true },
+    { pubkey: await findMarketAuthority(accounts.market), isSigner: false, isWritable: false },
+    { pubkey: accounts.position, isSigner: false, isWritable: true },
+    { pubkey: accounts.priceAccount, isSigner: false, isWritable: false },
+    { pubkey: accounts.marginVault, isSigner: false, isWritable: true },
+    { pubkey: accounts.collateralVault, isSigner: false, isWritable: true },
+    { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+  ];
+}
+
+/**
+ * Initialize a market whose market account, collateral vault and insurance
+ * vault already exist
+ */
+async function initializeMarket(
+  connection: Connection,
+  admin: Keypair,
+  market: PublicKey,
+  collateralMint: PublicKey,
+  collateralVault: PublicKey,
+  insuranceVault: PublicKey,
+  priceAccount: PublicKey,
+  baseDecimals: number,
+  initialMarginBps: BN,
+  maintenanceMarginBps: BN,
+  liquidationRewardBps: BN,
+  takerFeeBps: BN,
+  skewScale: BN,
+  maxFundingRateBps: BN,
+  pricingMode: PricingMode,
+  ammBaseReserve: BN
+) {
+  const marketAuthority = await findMarketAuthority(market);
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.InitializeMarket]),
+    borsh.serialize(
+      InitializeMarketInstruction.schema,
+      new InitializeMarketInstruction({
+        base_decimals: baseDecimals,
+        initial_margin_bps: initialMarginBps,
+        maintenance_margin_bps: maintenanceMarginBps,
+        liquidation_reward_bps: liquidationRewardBps,
+        taker_fee_bps: takerFeeBps,
+        skew_scale: skewScale,
+        max_funding_rate_bps: maxFundingRateBps,
+        pricing_mode: pricingMode,
+        amm_base_reserve: ammBaseReserve,
+      })
+    ),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: admin.publicKey, isSigner: true, isWritable: false },
+      { pubkey: market, isSigner: false, isWritable: true },
+      { pubkey: marketAuthority, isSigner: false, isWritable: false },
+      { pubkey: collateralMint, isSigner: false, isWritable: false },
+      { pubkey: collateralVault, isSigner: false, isWritable: false },
+      { pubkey: insuranceVault, isSigner: false, isWritable: false },
+      { pubkey: priceAccount, isSigner: false, isWritable: false },
+      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [admin]);
+
+  console.log('Market initialized successfully!');
+}
+
 /**
- * Initialize a new template account
+ * Deposit collateral and open a position in an existing position account
  */
-async function initialize(
+async function openPosition(
   connection: Connection,
-  authority: Keypair,
-  account: Keypair,
-  param1: BN,
-  param2: number
+  owner: Keypair,
+  market: PublicKey,
+  position: PublicKey,
+  priceAccount: PublicKey,
+  ownerCollateral: PublicKey,
+  collateralVault: PublicKey,
+  side: Side,
+  size: BN,
+  collateral: BN,
+  priceLimit: BN
 ) {
-  // Create initialize instruction
-  const initializeInstruction = new InitializeInstruction({
-    param1,
-    param2,
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.OpenPosition]),
+    borsh.serialize(
+      OpenPositionInstruction.schema,
+      new OpenPositionInstruction({ side, size, collateral, price_limit: priceLimit })
+    ),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: market, isSigner: false, isWritable: true },
+      { pubkey: position, isSigner: false, isWritable: true },
+      { pubkey: priceAccount, isSigner: false, isWritable: false },
+      { pubkey: ownerCollateral, isSigner: false, isWritable: true },
+      { pubkey: collateralVault, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data,
   });
-  
-  const instructionData = Buffer.from([TemplateInstructionType.Initialize]);
-  const serializedData = borsh.serialize(
-    InitializeInstruction.schema,
-    initializeInstruction
-  );
-  
-  const fullData = Buffer.concat([instructionData, serializedData]);
-  
+
+  await sendAndConfirmTransaction(connection, transaction, [owner]);
+
+  console.log('Position opened successfully!');
+}
+
+/**
+ * Deposit collateral into an open position and add to its size
+ */
+async function increasePosition(
+  connection: Connection,
+  owner: Keypair,
+  market: PublicKey,
+  position: PublicKey,
+  priceAccount: PublicKey,
+  ownerCollateral: PublicKey,
+  collateralVault: PublicKey,
+  size: BN,
+  collateral: BN,
+  priceLimit: BN
+) {
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.IncreasePosition]),
+    borsh.serialize(
+      IncreasePositionInstruction.schema,
+      new IncreasePositionInstruction({ size, collateral, price_limit: priceLimit })
+    ),
+  ]);
+
   const transaction = new Transaction().add({
     keys: [
-      { pubkey: authority.publicKey, isSigner: true, isWritable: true },
-      { pubkey: account.publicKey, isSigner: true, isWritable: true },
-      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: market, isSigner: false, isWritable: true },
+      { pubkey: position, isSigner: false, isWritable: true },
+      { pubkey: priceAccount, isSigner: false, isWritable: false },
+      { pubkey: ownerCollateral, isSigner: false, isWritable: true },
+      { pubkey: collateralVault, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [owner]);
+
+  console.log('Position increased successfully!');
+}
+
+/**
+ * Close `size` of a position, paying out the collateral if it closes fully
+ */
+async function closePosition(
+  connection: Connection,
+  owner: Keypair,
+  market: PublicKey,
+  position: PublicKey,
+  priceAccount: PublicKey,
+  collateralVault: PublicKey,
+  destination: PublicKey,
+  size: BN,
+  priceLimit: BN
+) {
+  const marketAuthority = await findMarketAuthority(market);
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.ClosePosition]),
+    borsh.serialize(
+      ClosePositionInstruction.schema,
+      new ClosePositionInstruction({ size, price_limit: priceLimit })
+    ),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: market, isSigner: false, isWritable: true },
+      { pubkey: position, isSigner: false, isWritable: true },
+      { pubkey: marketAuthority, isSigner: false, isWritable: false },
+      { pubkey: priceAccount, isSigner: false, isWritable: false },
+      { pubkey: collateralVault, isSigner: false, isWritable: true },
+      { pubkey: destination, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [owner]);
+
+  console.log('Position closed successfully!');
+}
+
+/**
+ * Accrue funding at the current premium of the mark over the index price
+ */
+async function updateFunding(
+  connection: Connection,
+  payer: Keypair,
+  market: PublicKey,
+  priceAccount: PublicKey
+) {
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: market, isSigner: false, isWritable: true },
+      { pubkey: priceAccount, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data: Buffer.from([TemplateInstructionType.UpdateFunding]),
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [payer]);
+
+  console.log('Funding updated successfully!');
+}
+
+/**
+ * Liquidate a position below its maintenance margin, receiving the reward
+ * in the keeper's token account
+ */
+async function liquidatePosition(
+  connection: Connection,
+  keeper: Keypair,
+  market: PublicKey,
+  position: PublicKey,
+  priceAccount: PublicKey,
+  collateralVault: PublicKey,
+  insuranceVault: PublicKey,
+  keeperTokenAccount: PublicKey
+) {
+  const marketAuthority = await findMarketAuthority(market);
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: keeper.publicKey, isSigner: true, isWritable: false },
+      { pubkey: market, isSigner: false, isWritable: true },
+      { pubkey: position, isSigner: false, isWritable: true },
+      { pubkey: marketAuthority, isSigner: false, isWritable: false },
+      { pubkey: priceAccount, isSigner: false, isWritable: false },
+      { pubkey: collateralVault, isSigner: false, isWritable: true },
+      { pubkey: insuranceVault, isSigner: false, isWritable: true },
+      { pubkey: keeperTokenAccount, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data: Buffer.from([TemplateInstructionType.LiquidatePosition]),
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [keeper]);
+
+  console.log('Position liquidated successfully!');
+}
+
+/**
+ * Initialize a margin account whose account and vault already exist
+ */
+async function initializeMarginAccount(
+  connection: Connection,
+  owner: Keypair,
+  marginAccount: PublicKey,
+  collateralMint: PublicKey,
+  marginVault: PublicKey
+) {
+  const marginAuthority = await findMarginAuthority(marginAccount);
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: marginAccount, isSigner: false, isWritable: true },
+      { pubkey: marginAuthority, isSigner: false, isWritable: false },
+      { pubkey: collateralMint, isSigner: false, isWritable: false },
+      { pubkey: marginVault, isSigner: false, isWritable: false },
       { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
     ],
     programId: PROGRAM_ID,
-    data: fullData,
+    data: Buffer.from([TemplateInstructionType.InitializeMarginAccount]),
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [owner]);
+
+  console.log('Margin account initialized successfully!');
+}
+
+/**
+ * Deposit collateral into a margin account
+ */
+async function depositMargin(
+  connection: Connection,
+  owner: Keypair,
+  marginAccount: PublicKey,
+  ownerCollateral: PublicKey,
+  marginVault: PublicKey,
+  amount: BN
+) {
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.DepositMargin]),
+    borsh.serialize(MarginAmountInstruction.schema, new MarginAmountInstruction({ amount })),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: marginAccount, isSigner: false, isWritable: true },
+      { pubkey: ownerCollateral, isSigner: false, isWritable: true },
+      { pubkey: marginVault, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [owner]);
+
+  console.log('Margin deposited successfully!');
+}
+
+/**
+ * Withdraw collateral from a margin account, passing every position it backs
+ */
+async function withdrawMargin(
+  connection: Connection,
+  owner: Keypair,
+  marginAccount: PublicKey,
+  marginVault: PublicKey,
+  destination: PublicKey,
+  healthAccounts: HealthAccounts[],
+  amount: BN
+) {
+  const marginAuthority = await findMarginAuthority(marginAccount);
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.WithdrawMargin]),
+    borsh.serialize(MarginAmountInstruction.schema, new MarginAmountInstruction({ amount })),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: marginAccount, isSigner: false, isWritable: true },
+      { pubkey: marginAuthority, isSigner: false, isWritable: false },
+      { pubkey: marginVault, isSigner: false, isWritable: true },
+      { pubkey: destination, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+      ...healthAccountMetas(healthAccounts),
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [owner]);
+
+  console.log('Margin withdrawn successfully!');
+}
+
+/**
+ * Open or add to a cross-margin position, passing every position the margin
+ * account backs after the trade
+ */
+async function increaseMarginPosition(
+  connection: Connection,
+  owner: Keypair,
+  accounts: MarginPositionAccounts,
+  healthAccounts: HealthAccounts[],
+  side: Side,
+  size: BN,
+  priceLimit: BN
+) {
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.IncreaseMarginPosition]),
+    borsh.serialize(
+      IncreaseMarginPositionInstruction.schema,
+      new IncreaseMarginPositionInstruction({ side, size, price_limit: priceLimit })
+    ),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      ...(await marginPositionAccountMetas(owner.publicKey, accounts)),
+      ...healthAccountMetas(healthAccounts),
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [owner]);
+
+  console.log('Margin position increased successfully!');
+}
+
+/**
+ * Reduce or close a cross-margin position
+ */
+async function reduceMarginPosition(
+  connection: Connection,
+  owner: Keypair,
+  accounts: MarginPositionAccounts,
+  size: BN,
+  priceLimit: BN
+) {
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.ReduceMarginPosition]),
+    borsh.serialize(
+      ClosePositionInstruction.schema,
+      new ClosePositionInstruction({ size, price_limit: priceLimit })
+    ),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: await marginPositionAccountMetas(owner.publicKey, accounts),
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [owner]);
+
+  console.log('Margin position reduced successfully!');
+}
+
+/**
+ * Repeg the virtual reserves of a vAMM market toward the index price
+ */
+async function repegAmm(
+  connection: Connection,
+  admin: Keypair,
+  market: PublicKey,
+  priceAccount: PublicKey
+) {
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: admin.publicKey, isSigner: true, isWritable: false },
+      { pubkey: market, isSigner: false, isWritable: true },
+      { pubkey: priceAccount, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data: Buffer.from([TemplateInstructionType.RepegAmm]),
   });
-  
-  await sendAndConfirmTransaction(
-    connection,
-    transaction,
-    [authority, account]
-  );
-  
-  console.log('Account initialized successfully!');
+
+  await sendAndConfirmTransaction(connection, transaction, [admin]);
+
+  console.log('AMM repegged successfully!');
 }
 
 /**
@@ -117,25 +725,17 @@ async function initialize(
 async function main() {
   // Connect to devnet
   const connection = new Connection('https://api.devnet.solana.com', 'confirmed');
-  
+
   // Generate keypairs for testing
-  const authority = Keypair.generate();
-  const account = Keypair.generate();
-  
-  console.log('Requesting airdrop for authority...');
-  const airdropSignature = await connection.requestAirdrop(authority.publicKey, 1000000000);
+  const admin = Keypair.generate();
+
+  console.log('Requesting airdrop for admin...');
+  const airdropSignature = await connection.requestAirdrop(admin.publicKey, 1000000000);
   await connection.confirmTransaction(airdropSignature);
-  
-  // Initialize with example values
-  await initialize(
-    connection,
-    authority,
-    account,
-    new BN(100),
-    5
-  );
-  
-  // TODO: Add examples for other operations
+
+  // TODO: Create the market account and the collateral and insurance vaults
+  // owned by the market authority, then call initializeMarket, create a
+  // position account and call openPosition
 }
 
 main().then(
@@ -4,27 +4,127 @@ use solana_program::program_error::ProgramError;
 use thiserror::Error;
 
 /// Errors that may be returned by the perpetual-futures program
-#[derive(Error, Debug, Copy, Clone)]
+#[derive(Error, Debug, Copy, Clone, PartialEq)]
 pub enum TemplateError {
     /// Invalid instruction
     #[error("Invalid instruction")]
     InvalidInstruction,
-    
+
     /// Not rent exempt
     #[error("Not rent exempt")]
     NotRentExempt,
-    
+
     /// Expected amount mismatch
     #[error("Expected amount mismatch")]
     ExpectedAmountMismatch,
-    
+
     /// Invalid authority
     #[error("Invalid authority")]
     InvalidAuthority,
-    
+
     /// Math operation overflow
     #[error("Math operation overflow")]
     MathOverflow,
+
+    /// Market already initialized
+    #[error("Market already initialized")]
+    MarketAlreadyInitialized,
+
+    /// Market not initialized
+    #[error("Market not initialized")]
+    MarketNotInitialized,
+
+    /// Invalid market authority
+    #[error("Invalid market authority")]
+    InvalidMarketAuthority,
+
+    /// Invalid market parameters
+    #[error("Invalid market parameters")]
+    InvalidMarketParameters,
+
+    /// Invalid vault
+    #[error("Invalid vault")]
+    InvalidVault,
+
+    /// Invalid token program
+    #[error("Invalid token program")]
+    InvalidTokenProgram,
+
+    /// Invalid price account
+    #[error("Invalid price account")]
+    InvalidPriceAccount,
+
+    /// Invalid oracle price
+    #[error("Invalid oracle price")]
+    InvalidOraclePrice,
+
+    /// Oracle price is stale
+    #[error("Oracle price is stale")]
+    StalePrice,
+
+    /// Mark price is not positive at this skew
+    #[error("Mark price is not positive at this skew")]
+    InvalidMarkPrice,
+
+    /// Position does not belong to this owner and market
+    #[error("Position does not belong to this owner and market")]
+    InvalidPosition,
+
+    /// Position already open
+    #[error("Position already open")]
+    PositionAlreadyOpen,
+
+    /// Position not open
+    #[error("Position not open")]
+    PositionNotOpen,
+
+    /// Invalid amount
+    #[error("Invalid amount")]
+    InvalidAmount,
+
+    /// Insufficient margin
+    #[error("Insufficient margin")]
+    InsufficientMargin,
+
+    /// Fill price beyond the price limit
+    #[error("Fill price beyond the price limit")]
+    PriceLimitExceeded,
+
+    /// Position is above its maintenance margin
+    #[error("Position is above its maintenance margin")]
+    PositionHealthy,
+
+    /// Margin account already initialized
+    #[error("Margin account already initialized")]
+    MarginAccountAlreadyInitialized,
+
+    /// Margin account does not match
+    #[error("Margin account does not match")]
+    InvalidMarginAccount,
+
+    /// Invalid margin authority
+    #[error("Invalid margin authority")]
+    InvalidMarginAuthority,
+
+    /// Margin account has no free position slot
+    #[error("Margin account has no free position slot")]
+    MarginAccountFull,
+
+    /// Health accounts do not match the margin account's positions
+    #[error("Health accounts do not match the margin account's positions")]
+    InvalidHealthAccounts,
+
+    /// Trade exceeds the virtual reserves
+    #[error("Trade exceeds the virtual reserves")]
+    InsufficientAmmLiquidity,
+
+    /// Market does not price trades with virtual reserves
+    #[error("Market does not price trades with virtual reserves")]
+    NotVammMarket,
+
+    /// Margin account is above the maintenance margin of its positions
+    #[error("Margin account is above the maintenance margin of its positions")]
+    MarginAccountHealthy,
 }
 
 impl From<TemplateError> for ProgramError {
@@ -6,63 +6,471 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
-    system_program, sysvar,
+    sysvar,
 };
 
+use crate::state::{PricingMode, Side};
+
+/// Accounts of one position a margin account backs, passed after the fixed
+/// accounts of instructions that check the health of the margin account
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HealthAccounts {
+    /// The position account
+    pub position: Pubkey,
+    /// The market the position trades on
+    pub market: Pubkey,
+    /// The index price account of the market
+    pub price_account: Pubkey,
+}
+
+/// Accounts of a cross-margin position, shared by the builders that trade it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MarginPositionAccounts {
+    /// The margin account
+    pub margin_account: Pubkey,
+    /// The margin authority PDA
+    pub margin_authority: Pubkey,
+    /// The market account
+    pub market: Pubkey,
+    /// The market authority PDA
+    pub market_authority: Pubkey,
+    /// The position account
+    pub position: Pubkey,
+    /// The index price account of the market
+    pub price_account: Pubkey,
+    /// The vault of the margin account
+    pub margin_vault: Pubkey,
+    /// The collateral vault of the market
+    pub collateral_vault: Pubkey,
+}
+
+/// Accounts of one position a margin account backs, passed after the fixed
+/// accounts of LiquidateMarginAccount
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LiquidationAccounts {
+    /// The position account
+    pub position: Pubkey,
+    /// The market the position trades on
+    pub market: Pubkey,
+    /// The index price account of the market
+    pub price_account: Pubkey,
+    /// The market authority PDA
+    pub market_authority: Pubkey,
+    /// The collateral vault of the market
+    pub collateral_vault: Pubkey,
+    /// The insurance vault of the market
+    pub insurance_vault: Pubkey,
+}
+
 /// Instructions supported by the perpetual-futures program
 #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
 pub enum TemplateInstruction {
-    /// Initialize a new instance
+    /// Initialize a new perpetual market
+    ///
+    /// The collateral vault and the insurance vault must be distinct token
+    /// accounts of the collateral mint owned by the market authority.
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to initialize
-    /// 2. `[]` The system program
-    /// 3. `[]` The rent sysvar
-    ///
-    Initialize {
-        /// Custom parameter 1
-        param1: u64,
-        /// Custom parameter 2
-        param2: u8,
+    /// 0. `[signer]` The market admin
+    /// 1. `[writable]` The market account, owned by this program
+    /// 2. `[]` The market authority PDA
+    /// 3. `[]` The collateral mint
+    /// 4. `[]` The collateral vault
+    /// 5. `[]` The insurance vault
+    /// 6. `[]` The index price account
+    /// 7. `[]` The rent sysvar
+    ///
+    InitializeMarket {
+        /// Decimals of the synthetic asset
+        base_decimals: u8,
+        /// Equity a position needs after a trade, in basis points of its notional
+        initial_margin_bps: u64,
+        /// Equity below which a position can be liquidated, in basis points of its notional
+        maintenance_margin_bps: u64,
+        /// Keeper reward, in basis points of the notional liquidated
+        liquidation_reward_bps: u64,
+        /// Fee charged on the notional of every trade, in basis points
+        taker_fee_bps: u64,
+        /// Skew, in base units, at which the mark price is twice the index
+        /// price in `PricingMode::Skew`
+        skew_scale: u64,
+        /// Largest premium funding accrues at, in basis points of the index price
+        max_funding_rate_bps: u64,
+        /// How trades are priced
+        pricing_mode: PricingMode,
+        /// Virtual base reserve in `PricingMode::Vamm`, in base units. The
+        /// virtual quote reserve starts at the index price.
+        amm_base_reserve: u64,
     },
 
-    /// Perform operation 1
+    /// Deposit collateral and open a position
+    ///
+    /// The position account must be new or hold a closed position of the
+    /// same owner in the same market. Fails if the fill price is beyond
+    /// `price_limit` or the position would be below its initial margin.
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to modify
+    /// 0. `[signer]` The position owner
+    /// 1. `[writable]` The market account
+    /// 2. `[writable]` The position account, owned by this program
+    /// 3. `[]` The index price account
+    /// 4. `[writable]` The owner's collateral token account
+    /// 5. `[writable]` The collateral vault
+    /// 6. `[]` The token program
+    ///
+    OpenPosition {
+        /// Long or short
+        side: Side,
+        /// Base units of the synthetic asset to buy or sell
+        size: u64,
+        /// Collateral to deposit
+        collateral: u64,
+        /// Highest fill price for longs or lowest for shorts
+        price_limit: u64,
+    },
+
+    /// Deposit more collateral into an open position and add to its size
+    ///
+    /// Either amount may be zero, so this also tops up margin. Fails if the
+    /// fill price is beyond `price_limit` or the position would be below its
+    /// initial margin.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The position owner
+    /// 1. `[writable]` The market account
+    /// 2. `[writable]` The position account
+    /// 3. `[]` The index price account
+    /// 4. `[writable]` The owner's collateral token account
+    /// 5. `[writable]` The collateral vault
+    /// 6. `[]` The token program
+    ///
+    IncreasePosition {
+        /// Base units of the synthetic asset to add
+        size: u64,
+        /// Collateral to deposit
+        collateral: u64,
+        /// Highest fill price for longs or lowest for shorts
+        price_limit: u64,
+    },
+
+    /// Reduce or close a position, realizing its PnL
+    ///
+    /// A partial close keeps the realized PnL in the collateral. Closing the
+    /// whole size pays out all the collateral left. A size of zero withdraws
+    /// the collateral left in a position fully closed by a liquidation.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The position owner
+    /// 1. `[writable]` The market account
+    /// 2. `[writable]` The position account
+    /// 3. `[]` The market authority PDA
+    /// 4. `[]` The index price account
+    /// 5. `[writable]` The collateral vault
+    /// 6. `[writable]` The token account to receive the collateral
+    /// 7. `[]` The token program
+    ///
+    ClosePosition {
+        /// Base units of the synthetic asset to close
+        size: u64,
+        /// Lowest fill price for longs or highest for shorts
+        price_limit: u64,
+    },
+
+    /// Accrue funding at the current premium of the mark over the index price
     ///
-    Operation1 {
-        /// Operation amount
+    /// Permissionless crank. Trades accrue funding as well, so the crank only
+    /// keeps the funding index current in quiet markets.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The market account
+    /// 1. `[]` The index price account
+    ///
+    UpdateFunding,
+
+    /// Liquidate a position below its maintenance margin
+    ///
+    /// Permissionless. Closes just enough of the position at the index price
+    /// to bring it back above its maintenance margin and pays the keeper a
+    /// reward on the notional closed. Bad debt of a fully liquidated position
+    /// is covered by the insurance vault, and any shortfall is socialized
+    /// across the opposite side.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The keeper
+    /// 1. `[writable]` The market account
+    /// 2. `[writable]` The position account
+    /// 3. `[]` The market authority PDA
+    /// 4. `[]` The index price account
+    /// 5. `[writable]` The collateral vault
+    /// 6. `[writable]` The insurance vault
+    /// 7. `[writable]` The keeper's token account to receive the reward
+    /// 8. `[]` The token program
+    ///
+    LiquidatePosition,
+
+    /// Initialize a cross-margin account
+    ///
+    /// The vault must be an empty token account of the collateral mint owned
+    /// by the margin authority.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The owner
+    /// 1. `[writable]` The margin account, owned by this program
+    /// 2. `[]` The margin authority PDA
+    /// 3. `[]` The collateral mint
+    /// 4. `[]` The margin vault
+    /// 5. `[]` The rent sysvar
+    ///
+    InitializeMarginAccount,
+
+    /// Deposit collateral into a margin account
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The owner
+    /// 1. `[writable]` The margin account
+    /// 2. `[writable]` The owner's collateral token account
+    /// 3. `[writable]` The margin vault
+    /// 4. `[]` The token program
+    ///
+    DepositMargin {
+        /// Collateral to deposit
         amount: u64,
     },
 
-    /// Perform operation 2
+    /// Withdraw collateral from a margin account
+    ///
+    /// Fails if the equity left would be below the initial margin of the
+    /// positions the account backs.
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to modify
+    /// 0. `[signer]` The owner
+    /// 1. `[writable]` The margin account
+    /// 2. `[]` The margin authority PDA
+    /// 3. `[writable]` The margin vault
+    /// 4. `[writable]` The token account to receive the collateral
+    /// 5. `[]` The token program
+    /// 6. `[]` For every position the account backs, in any order: the
+    ///    position, its market and the market's index price account
     ///
-    Operation2,
+    WithdrawMargin {
+        /// Collateral to withdraw
+        amount: u64,
+    },
+
+    /// Open or add to a cross-margin position
+    ///
+    /// The position account must be new, hold a closed position of the same
+    /// margin account in the same market, or hold an open one on `side`.
+    /// Fees and funding are paid from the margin account, which must meet
+    /// the initial margin of all its positions afterwards.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The owner of the margin account
+    /// 1. `[writable]` The margin account
+    /// 2. `[]` The margin authority PDA
+    /// 3. `[writable]` The market account
+    /// 4. `[]` The market authority PDA
+    /// 5. `[writable]` The position account, owned by this program
+    /// 6. `[]` The index price account
+    /// 7. `[writable]` The margin vault
+    /// 8. `[writable]` The collateral vault of the market
+    /// 9. `[]` The token program
+    /// 10. `[]` For every position the account backs after the trade, in
+    ///     any order: the position, its market and the market's index price
+    ///     account
+    ///
+    IncreaseMarginPosition {
+        /// Long or short
+        side: Side,
+        /// Base units of the synthetic asset to buy or sell
+        size: u64,
+        /// Highest fill price for longs or lowest for shorts
+        price_limit: u64,
+    },
+
+    /// Reduce or close a cross-margin position, settling its PnL into the
+    /// margin account
+    ///
+    /// A loss the margin account cannot pay fails the trade unless it closes
+    /// the last position of the account, in which case the shortfall is left
+    /// with the market's collateral vault.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The owner of the margin account
+    /// 1. `[writable]` The margin account
+    /// 2. `[]` The margin authority PDA
+    /// 3. `[writable]` The market account
+    /// 4. `[]` The market authority PDA
+    /// 5. `[writable]` The position account
+    /// 6. `[]` The index price account
+    /// 7. `[writable]` The margin vault
+    /// 8. `[writable]` The collateral vault of the market
+    /// 9. `[]` The token program
+    ///
+    ReduceMarginPosition {
+        /// Base units of the synthetic asset to close
+        size: u64,
+        /// Lowest fill price for longs or highest for shorts
+        price_limit: u64,
+    },
+
+    /// Move the virtual reserves of a vAMM market toward the index price
+    ///
+    /// Only the market admin can repeg. The quote reserve is set so the mark
+    /// price equals the index price, or moved only as far as the fee pool
+    /// can pay for when repegging costs the market.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The market admin
+    /// 1. `[writable]` The market account
+    /// 2. `[]` The index price account
+    ///
+    RepegAmm,
+
+    /// Liquidate a margin account below the maintenance margin of its
+    /// positions
+    ///
+    /// Permissionless. Closes every position the account backs at its index
+    /// price, settles the PnL into the margin account and pays the keeper a
+    /// reward on each notional closed out of the market's collateral vault.
+    /// Losses the margin account cannot pay are covered by the market's
+    /// insurance vault, and any shortfall is socialized across the opposite
+    /// side of that market.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The keeper
+    /// 1. `[writable]` The margin account
+    /// 2. `[]` The margin authority PDA
+    /// 3. `[writable]` The margin vault
+    /// 4. `[writable]` The keeper's token account to receive the rewards
+    /// 5. `[]` The token program
+    /// 6. `[]` For every position the account backs, in any order: the
+    ///    position, its market, the market's index price account, the
+    ///    market authority PDA, the collateral vault and the insurance vault,
+    ///    all but the price account and the market authority writable
+    ///
+    LiquidateMarginAccount,
 }
 
-/// Creates an Initialize instruction
-pub fn initialize(
+/// Creates an InitializeMarket instruction
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_market(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
-    param1: u64,
-    param2: u8,
+    admin: &Pubkey,
+    market: &Pubkey,
+    market_authority: &Pubkey,
+    collateral_mint: &Pubkey,
+    collateral_vault: &Pubkey,
+    insurance_vault: &Pubkey,
+    price_account: &Pubkey,
+    base_decimals: u8,
+    initial_margin_bps: u64,
+    maintenance_margin_bps: u64,
+    liquidation_reward_bps: u64,
+    taker_fee_bps: u64,
+    skew_scale: u64,
+    max_funding_rate_bps: u64,
+    pricing_mode: PricingMode,
+    amm_base_reserve: u64,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
-        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(*market, false),
+        AccountMeta::new_readonly(*market_authority, false),
+        AccountMeta::new_readonly(*collateral_mint, false),
+        AccountMeta::new_readonly(*collateral_vault, false),
+        AccountMeta::new_readonly(*insurance_vault, false),
+        AccountMeta::new_readonly(*price_account, false),
         AccountMeta::new_readonly(sysvar::rent::id(), false),
     ];
 
-    let data = TemplateInstruction::Initialize { param1, param2 };
+    let data = TemplateInstruction::InitializeMarket {
+        base_decimals,
+        initial_margin_bps,
+        maintenance_margin_bps,
+        liquidation_reward_bps,
+        taker_fee_bps,
+        skew_scale,
+        max_funding_rate_bps,
+        pricing_mode,
+        amm_base_reserve,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an OpenPosition instruction
+#[allow(clippy::too_many_arguments)]
+pub fn open_position(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    market: &Pubkey,
+    position: &Pubkey,
+    price_account: &Pubkey,
+    owner_collateral: &Pubkey,
+    collateral_vault: &Pubkey,
+    side: Side,
+    size: u64,
+    collateral: u64,
+    price_limit: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*market, false),
+        AccountMeta::new(*position, false),
+        AccountMeta::new_readonly(*price_account, false),
+        AccountMeta::new(*owner_collateral, false),
+        AccountMeta::new(*collateral_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::OpenPosition {
+        side,
+        size,
+        collateral,
+        price_limit,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an IncreasePosition instruction
+#[allow(clippy::too_many_arguments)]
+pub fn increase_position(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    market: &Pubkey,
+    position: &Pubkey,
+    price_account: &Pubkey,
+    owner_collateral: &Pubkey,
+    collateral_vault: &Pubkey,
+    size: u64,
+    collateral: u64,
+    price_limit: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*market, false),
+        AccountMeta::new(*position, false),
+        AccountMeta::new_readonly(*price_account, false),
+        AccountMeta::new(*owner_collateral, false),
+        AccountMeta::new(*collateral_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::IncreasePosition {
+        size,
+        collateral,
+        price_limit,
+    };
 
     Instruction {
         program_id: *program_id,
@@ -71,19 +479,166 @@ pub fn initialize(
     }
 }
 
-/// Creates an Operation1 instruction
-pub fn operation1(
+/// Creates a ClosePosition instruction
+#[allow(clippy::too_many_arguments)]
+pub fn close_position(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
+    owner: &Pubkey,
+    market: &Pubkey,
+    position: &Pubkey,
+    market_authority: &Pubkey,
+    price_account: &Pubkey,
+    collateral_vault: &Pubkey,
+    destination: &Pubkey,
+    size: u64,
+    price_limit: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*market, false),
+        AccountMeta::new(*position, false),
+        AccountMeta::new_readonly(*market_authority, false),
+        AccountMeta::new_readonly(*price_account, false),
+        AccountMeta::new(*collateral_vault, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::ClosePosition { size, price_limit };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an UpdateFunding instruction
+pub fn update_funding(program_id: &Pubkey, market: &Pubkey, price_account: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*market, false),
+        AccountMeta::new_readonly(*price_account, false),
+    ];
+
+    let data = TemplateInstruction::UpdateFunding;
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a LiquidatePosition instruction
+#[allow(clippy::too_many_arguments)]
+pub fn liquidate_position(
+    program_id: &Pubkey,
+    keeper: &Pubkey,
+    market: &Pubkey,
+    position: &Pubkey,
+    market_authority: &Pubkey,
+    price_account: &Pubkey,
+    collateral_vault: &Pubkey,
+    insurance_vault: &Pubkey,
+    keeper_token_account: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*keeper, true),
+        AccountMeta::new(*market, false),
+        AccountMeta::new(*position, false),
+        AccountMeta::new_readonly(*market_authority, false),
+        AccountMeta::new_readonly(*price_account, false),
+        AccountMeta::new(*collateral_vault, false),
+        AccountMeta::new(*insurance_vault, false),
+        AccountMeta::new(*keeper_token_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::LiquidatePosition;
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an InitializeMarginAccount instruction
+pub fn initialize_margin_account(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    margin_account: &Pubkey,
+    margin_authority: &Pubkey,
+    collateral_mint: &Pubkey,
+    margin_vault: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*margin_account, false),
+        AccountMeta::new_readonly(*margin_authority, false),
+        AccountMeta::new_readonly(*collateral_mint, false),
+        AccountMeta::new_readonly(*margin_vault, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    let data = TemplateInstruction::InitializeMarginAccount;
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a DepositMargin instruction
+pub fn deposit_margin(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    margin_account: &Pubkey,
+    owner_collateral: &Pubkey,
+    margin_vault: &Pubkey,
     amount: u64,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*margin_account, false),
+        AccountMeta::new(*owner_collateral, false),
+        AccountMeta::new(*margin_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::DepositMargin { amount };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a WithdrawMargin instruction
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_margin(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    margin_account: &Pubkey,
+    margin_authority: &Pubkey,
+    margin_vault: &Pubkey,
+    destination: &Pubkey,
+    health_accounts: &[HealthAccounts],
+    amount: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*margin_account, false),
+        AccountMeta::new_readonly(*margin_authority, false),
+        AccountMeta::new(*margin_vault, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
     ];
+    accounts.extend(health_account_metas(health_accounts));
 
-    let data = TemplateInstruction::Operation1 { amount };
+    let data = TemplateInstruction::WithdrawMargin { amount };
 
     Instruction {
         program_id: *program_id,
@@ -92,18 +647,133 @@ pub fn operation1(
     }
 }
 
-/// Creates an Operation2 instruction
-pub fn operation2(
+/// Creates an IncreaseMarginPosition instruction
+pub fn increase_margin_position(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    position: &MarginPositionAccounts,
+    health_accounts: &[HealthAccounts],
+    side: Side,
+    size: u64,
+    price_limit: u64,
+) -> Instruction {
+    let mut accounts = margin_position_account_metas(owner, position);
+    accounts.extend(health_account_metas(health_accounts));
+
+    let data = TemplateInstruction::IncreaseMarginPosition {
+        side,
+        size,
+        price_limit,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a ReduceMarginPosition instruction
+pub fn reduce_margin_position(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    position: &MarginPositionAccounts,
+    size: u64,
+    price_limit: u64,
+) -> Instruction {
+    let data = TemplateInstruction::ReduceMarginPosition { size, price_limit };
+
+    Instruction {
+        program_id: *program_id,
+        accounts: margin_position_account_metas(owner, position),
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Account metas shared by IncreaseMarginPosition and ReduceMarginPosition
+fn margin_position_account_metas(
+    owner: &Pubkey,
+    position: &MarginPositionAccounts,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(position.margin_account, false),
+        AccountMeta::new_readonly(position.margin_authority, false),
+        AccountMeta::new(position.market, false),
+        AccountMeta::new_readonly(position.market_authority, false),
+        AccountMeta::new(position.position, false),
+        AccountMeta::new_readonly(position.price_account, false),
+        AccountMeta::new(position.margin_vault, false),
+        AccountMeta::new(position.collateral_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ]
+}
+
+/// Account metas of the positions a health check covers
+fn health_account_metas(
+    health_accounts: &[HealthAccounts],
+) -> impl Iterator<Item = AccountMeta> + '_ {
+    health_accounts.iter().flat_map(|accounts| {
+        [
+            AccountMeta::new_readonly(accounts.position, false),
+            AccountMeta::new_readonly(accounts.market, false),
+            AccountMeta::new_readonly(accounts.price_account, false),
+        ]
+    })
+}
+
+/// Creates a RepegAmm instruction
+pub fn repeg_amm(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
+    admin: &Pubkey,
+    market: &Pubkey,
+    price_account: &Pubkey,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(*market, false),
+        AccountMeta::new_readonly(*price_account, false),
+    ];
+
+    let data = TemplateInstruction::RepegAmm;
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a LiquidateMarginAccount instruction
+pub fn liquidate_margin_account(
+    program_id: &Pubkey,
+    keeper: &Pubkey,
+    margin_account: &Pubkey,
+    margin_authority: &Pubkey,
+    margin_vault: &Pubkey,
+    keeper_account: &Pubkey,
+    liquidation_accounts: &[LiquidationAccounts],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*keeper, true),
+        AccountMeta::new(*margin_account, false),
+        AccountMeta::new_readonly(*margin_authority, false),
+        AccountMeta::new(*margin_vault, false),
+        AccountMeta::new(*keeper_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
     ];
+    accounts.extend(liquidation_accounts.iter().flat_map(|accounts| {
+        [
+            AccountMeta::new(accounts.position, false),
+            AccountMeta::new(accounts.market, false),
+            AccountMeta::new_readonly(accounts.price_account, false),
+            AccountMeta::new_readonly(accounts.market_authority, false),
+            AccountMeta::new(accounts.collateral_vault, false),
+            AccountMeta::new(accounts.insurance_vault, false),
+        ]
+    }));
 
-    let data = TemplateInstruction::Operation2;
+    let data = TemplateInstruction::LiquidateMarginAccount;
 
     Instruction {
         program_id: *program_id,
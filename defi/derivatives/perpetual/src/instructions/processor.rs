@@ -3,9 +3,12 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
+    program_pack::Pack,
     pubkey::Pubkey,
     rent::Rent,
     sysvar::Sysvar,
@@ -14,154 +17,1669 @@ use solana_program::{
 use crate::{
     errors::TemplateError,
     instructions::TemplateInstruction,
-    state::TemplateAccount,
+    oracle::{PriceReader, PythPriceReader},
+    state::{
+        clamp_to_u64, MarginAccount, PerpMarket, Position, PricingMode, Side, BPS_DENOMINATOR,
+        PRICE_MAX_AGE,
+    },
+    utils::{
+        check_account_owner, check_margin_authority, check_market_authority, check_signer,
+        find_margin_authority, find_market_authority, safe_addition,
+    },
 };
 
-/// Processes an instruction
+/// Processes an instruction, reading index prices from Pyth price accounts
 pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     instruction_data: &[u8],
+) -> ProgramResult {
+    process_instruction_with_price_reader::<PythPriceReader>(program_id, accounts, instruction_data)
+}
+
+/// Processes an instruction, reading index prices with `R`
+pub fn process_instruction_with_price_reader<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
 ) -> ProgramResult {
     let instruction = TemplateInstruction::try_from_slice(instruction_data)
         .map_err(|_| ProgramError::InvalidInstructionData)?;
 
     match instruction {
-        TemplateInstruction::Initialize { param1, param2 } => {
-            msg!("Instruction: Initialize");
-            process_initialize(program_id, accounts, param1, param2)
+        TemplateInstruction::InitializeMarket {
+            base_decimals,
+            initial_margin_bps,
+            maintenance_margin_bps,
+            liquidation_reward_bps,
+            taker_fee_bps,
+            skew_scale,
+            max_funding_rate_bps,
+            pricing_mode,
+            amm_base_reserve,
+        } => {
+            msg!("Instruction: Initialize Market");
+            process_initialize_market::<R>(
+                program_id,
+                accounts,
+                base_decimals,
+                initial_margin_bps,
+                maintenance_margin_bps,
+                liquidation_reward_bps,
+                taker_fee_bps,
+                skew_scale,
+                max_funding_rate_bps,
+                pricing_mode,
+                amm_base_reserve,
+            )
+        }
+        TemplateInstruction::OpenPosition {
+            side,
+            size,
+            collateral,
+            price_limit,
+        } => {
+            msg!("Instruction: Open Position");
+            process_open_position::<R>(program_id, accounts, side, size, collateral, price_limit)
+        }
+        TemplateInstruction::IncreasePosition {
+            size,
+            collateral,
+            price_limit,
+        } => {
+            msg!("Instruction: Increase Position");
+            process_increase_position::<R>(program_id, accounts, size, collateral, price_limit)
+        }
+        TemplateInstruction::ClosePosition { size, price_limit } => {
+            msg!("Instruction: Close Position");
+            process_close_position::<R>(program_id, accounts, size, price_limit)
+        }
+        TemplateInstruction::UpdateFunding => {
+            msg!("Instruction: Update Funding");
+            process_update_funding::<R>(program_id, accounts)
+        }
+        TemplateInstruction::LiquidatePosition => {
+            msg!("Instruction: Liquidate Position");
+            process_liquidate_position::<R>(program_id, accounts)
+        }
+        TemplateInstruction::InitializeMarginAccount => {
+            msg!("Instruction: Initialize Margin Account");
+            process_initialize_margin_account(program_id, accounts)
         }
-        TemplateInstruction::Operation1 { amount } => {
-            msg!("Instruction: Operation1");
-            process_operation1(program_id, accounts, amount)
+        TemplateInstruction::DepositMargin { amount } => {
+            msg!("Instruction: Deposit Margin");
+            process_deposit_margin(program_id, accounts, amount)
         }
-        TemplateInstruction::Operation2 => {
-            msg!("Instruction: Operation2");
-            process_operation2(program_id, accounts)
+        TemplateInstruction::WithdrawMargin { amount } => {
+            msg!("Instruction: Withdraw Margin");
+            process_withdraw_margin::<R>(program_id, accounts, amount)
+        }
+        TemplateInstruction::IncreaseMarginPosition {
+            side,
+            size,
+            price_limit,
+        } => {
+            msg!("Instruction: Increase Margin Position");
+            process_increase_margin_position::<R>(program_id, accounts, side, size, price_limit)
+        }
+        TemplateInstruction::ReduceMarginPosition { size, price_limit } => {
+            msg!("Instruction: Reduce Margin Position");
+            process_reduce_margin_position::<R>(program_id, accounts, size, price_limit)
+        }
+        TemplateInstruction::RepegAmm => {
+            msg!("Instruction: Repeg AMM");
+            process_repeg_amm::<R>(program_id, accounts)
+        }
+        TemplateInstruction::LiquidateMarginAccount => {
+            msg!("Instruction: Liquidate Margin Account");
+            process_liquidate_margin_account::<R>(program_id, accounts)
         }
     }
 }
 
-/// Processes an Initialize instruction
-fn process_initialize(
+/// Processes an InitializeMarket instruction
+#[allow(clippy::too_many_arguments)]
+fn process_initialize_market<R: PriceReader>(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    param1: u64,
-    param2: u8,
+    base_decimals: u8,
+    initial_margin_bps: u64,
+    maintenance_margin_bps: u64,
+    liquidation_reward_bps: u64,
+    taker_fee_bps: u64,
+    skew_scale: u64,
+    max_funding_rate_bps: u64,
+    pricing_mode: PricingMode,
+    amm_base_reserve: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    let system_program_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let market_authority_info = next_account_info(account_info_iter)?;
+    let collateral_mint_info = next_account_info(account_info_iter)?;
+    let collateral_vault_info = next_account_info(account_info_iter)?;
+    let insurance_vault_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
+
+    // Check the admin is a signer
+    check_signer(admin_info)?;
+
+    // Check market account ownership
+    check_account_owner(market_info, program_id)?;
+
+    // Check for rent exemption
+    let rent = &Rent::from_account_info(rent_info)?;
+    if !rent.is_exempt(market_info.lamports(), market_info.data_len()) {
+        return Err(TemplateError::NotRentExempt.into());
+    }
+
+    // Check the market has not been initialized yet
+    if market_info.data_len() != PerpMarket::get_size() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if market_info.data.borrow()[0] != 0 {
+        return Err(TemplateError::MarketAlreadyInitialized.into());
+    }
+
+    // Validate risk parameters
+    if base_decimals > 18
+        || initial_margin_bps == 0
+        || initial_margin_bps > BPS_DENOMINATOR
+        || maintenance_margin_bps == 0
+        || maintenance_margin_bps > initial_margin_bps
+        || liquidation_reward_bps >= maintenance_margin_bps
+        || taker_fee_bps > BPS_DENOMINATOR
+        || max_funding_rate_bps > BPS_DENOMINATOR
+    {
+        return Err(TemplateError::InvalidMarketParameters.into());
+    }
+    let pricing_parameter = match pricing_mode {
+        PricingMode::Skew => skew_scale,
+        PricingMode::Vamm => amm_base_reserve,
+    };
+    if pricing_parameter == 0 {
+        return Err(TemplateError::InvalidMarketParameters.into());
+    }
+
+    // Validate market authority
+    let (market_authority, bump_seed) = find_market_authority(program_id, market_info.key);
+    if market_authority != *market_authority_info.key {
+        return Err(TemplateError::InvalidMarketAuthority.into());
+    }
+
+    // Validate the collateral mint and vaults
+    check_account_owner(collateral_mint_info, &spl_token::id())
+        .map_err(|_| TemplateError::InvalidVault)?;
+    check_vault(
+        collateral_vault_info,
+        collateral_mint_info.key,
+        &market_authority,
+    )?;
+    check_vault(
+        insurance_vault_info,
+        collateral_mint_info.key,
+        &market_authority,
+    )?;
+    if insurance_vault_info.key == collateral_vault_info.key {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    let quote_decimals =
+        spl_token::state::Mint::unpack(&collateral_mint_info.data.borrow())?.decimals;
+
+    let now = Clock::get()?.unix_timestamp;
+    let mut market = PerpMarket {
+        is_initialized: true,
+        bump_seed,
+        admin: *admin_info.key,
+        collateral_mint: *collateral_mint_info.key,
+        collateral_vault: *collateral_vault_info.key,
+        price_account: *price_info.key,
+        insurance_vault: *insurance_vault_info.key,
+        base_decimals,
+        quote_decimals,
+        initial_margin_bps,
+        maintenance_margin_bps,
+        liquidation_reward_bps,
+        taker_fee_bps,
+        skew_scale,
+        max_funding_rate_bps,
+        pricing_mode,
+        amm_base_reserve: 0,
+        amm_quote_reserve: 0,
+        long_open_interest: 0,
+        short_open_interest: 0,
+        cumulative_funding: 0,
+        funding_rate: 0,
+        last_funding_time: now,
+        fee_pool: 0,
+        cumulative_long_loss: 0,
+        cumulative_short_loss: 0,
+        socialized_loss: 0,
+        cumulative_repeg_cost: 0,
+    };
+
+    // Check the index price can be read, and peg the virtual reserves to it
+    let index_price = read_index_price::<R>(&market, price_info, now)?;
+    if pricing_mode == PricingMode::Vamm {
+        market.amm_base_reserve = amm_base_reserve as u128;
+        market.amm_quote_reserve =
+            amm_base_reserve as u128 * index_price as u128 / market.base_unit() as u128;
+        if market.amm_quote_reserve == 0 {
+            return Err(TemplateError::InvalidMarketParameters.into());
+        }
+    }
+
+    // Save market data
+    market.serialize(&mut &mut market_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes an OpenPosition instruction
+fn process_open_position<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    side: Side,
+    size: u64,
+    collateral: u64,
+    price_limit: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let position_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
+    let owner_collateral_info = next_account_info(account_info_iter)?;
+    let collateral_vault_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    let mut market = load_market(program_id, market_info)?;
+    check_collateral_vault(&market, collateral_vault_info, token_program_info)?;
+
+    // Check the position account, which may hold a closed position
+    check_account_owner(position_info, program_id)?;
+    if position_info.data_len() != Position::get_size() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if !Rent::get()?.is_exempt(position_info.lamports(), position_info.data_len()) {
+        return Err(TemplateError::NotRentExempt.into());
+    }
+    let mut position = if position_info.data.borrow()[0] == 0 {
+        Position {
+            is_initialized: true,
+            market: *market_info.key,
+            owner: *owner_info.key,
+            ..Position::default()
+        }
+    } else {
+        let position = Position::try_from_slice(&position_info.data.borrow())?;
+        if position.market != *market_info.key
+            || position.owner != *owner_info.key
+            || position.is_cross_margin()
+        {
+            return Err(TemplateError::InvalidPosition.into());
+        }
+        if position.is_open() {
+            return Err(TemplateError::PositionAlreadyOpen.into());
+        }
+        position
+    };
+
+    if size == 0 {
+        return Err(TemplateError::InvalidAmount.into());
+    }
+
+    // Bring funding up to date before the trade moves the skew
+    let now = Clock::get()?.unix_timestamp;
+    let index_price = read_index_price::<R>(&market, price_info, now)?;
+    market.accrue_funding(index_price, now)?;
+    position.side = side;
+    position.reset_indexes(&market);
+
+    // Deposit the collateral and trade
+    if collateral > 0 {
+        transfer_to_vault(
+            token_program_info,
+            owner_collateral_info,
+            collateral_vault_info,
+            owner_info,
+            collateral,
+        )?;
+        position.collateral = safe_addition(position.collateral, collateral)?;
+    }
+    trade_into_position(&mut market, &mut position, index_price, size, price_limit)?;
+
+    // Save market and position data
+    market.serialize(&mut &mut market_info.data.borrow_mut()[..])?;
+    position.serialize(&mut &mut position_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes an IncreasePosition instruction
+fn process_increase_position<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    size: u64,
+    collateral: u64,
+    price_limit: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let position_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
+    let owner_collateral_info = next_account_info(account_info_iter)?;
+    let collateral_vault_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    let mut market = load_market(program_id, market_info)?;
+    check_collateral_vault(&market, collateral_vault_info, token_program_info)?;
+    let mut position = load_owned_position(program_id, position_info, market_info, owner_info)?;
+    if !position.is_open() {
+        return Err(TemplateError::PositionNotOpen.into());
+    }
+
+    if size == 0 && collateral == 0 {
+        return Err(TemplateError::InvalidAmount.into());
+    }
+
+    // Settle funding up to now
+    let now = Clock::get()?.unix_timestamp;
+    let index_price = read_index_price::<R>(&market, price_info, now)?;
+    market.accrue_funding(index_price, now)?;
+    position.settle(&market)?;
+
+    // Deposit the collateral and trade
+    if collateral > 0 {
+        transfer_to_vault(
+            token_program_info,
+            owner_collateral_info,
+            collateral_vault_info,
+            owner_info,
+            collateral,
+        )?;
+        position.collateral = safe_addition(position.collateral, collateral)?;
+    }
+    if size > 0 {
+        trade_into_position(&mut market, &mut position, index_price, size, price_limit)?;
+    }
+
+    // Save market and position data
+    market.serialize(&mut &mut market_info.data.borrow_mut()[..])?;
+    position.serialize(&mut &mut position_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a ClosePosition instruction
+fn process_close_position<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    size: u64,
+    price_limit: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let position_info = next_account_info(account_info_iter)?;
+    let market_authority_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
+    let collateral_vault_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    let mut market = load_market(program_id, market_info)?;
+    check_market_authority(
+        program_id,
+        market_info.key,
+        market.bump_seed,
+        market_authority_info.key,
+    )?;
+    check_collateral_vault(&market, collateral_vault_info, token_program_info)?;
+    let mut position = load_owned_position(program_id, position_info, market_info, owner_info)?;
+    if !position.is_open() && position.collateral == 0 {
+        return Err(TemplateError::PositionNotOpen.into());
+    }
+
+    // Only a position emptied by a liquidation is closed with a size of zero
+    if size > position.size || (size == 0 && position.is_open()) {
+        return Err(TemplateError::InvalidAmount.into());
+    }
+
+    // Settle funding up to now
+    let now = Clock::get()?.unix_timestamp;
+    let index_price = read_index_price::<R>(&market, price_info, now)?;
+    market.accrue_funding(index_price, now)?;
+    position.settle(&market)?;
+
+    // Trade the other way and realize the PnL of the closed part. A
+    // position emptied by a liquidation has nothing left to trade, so it
+    // is neither priced nor charged a fee.
+    let trade_side = position.side.opposite();
+    let exit_notional = if size == 0 {
+        0
+    } else {
+        let fill_price = market.fill_price(index_price, trade_side, size)?;
+        check_price_limit(trade_side, fill_price, price_limit)?;
+        market.notional(size, fill_price)?
+    };
+    let entry_notional = position.entry_notional_of(size);
+    let realized_pnl = match position.side {
+        Side::Long => exit_notional as i128 - entry_notional as i128,
+        Side::Short => entry_notional as i128 - exit_notional as i128,
+    };
+    let balance = position.collateral as i128 + realized_pnl;
+    let fee = market.taker_fee(exit_notional).min(clamp_to_u64(balance));
+    let balance = balance - fee as i128;
+    market.fee_pool = safe_addition(market.fee_pool, fee)?;
+    market.remove_open_interest(position.side, size)?;
+    position.size -= size;
+    position.entry_notional -= entry_notional;
+
+    if position.is_open() {
+        // A partial close must leave the position solvent
+        if balance < 0 {
+            return Err(TemplateError::InsufficientMargin.into());
+        }
+        position.collateral = balance as u64;
+    } else {
+        // Pay out what is left; a shortfall stays with the vault
+        let payout = clamp_to_u64(balance);
+        position.collateral = 0;
+        position.entry_notional = 0;
+        if payout > 0 {
+            let market_key = market_info.key.to_bytes();
+            transfer_from_vault(
+                token_program_info,
+                collateral_vault_info,
+                destination_info,
+                market_authority_info,
+                &[&market_key[..], &[market.bump_seed]],
+                payout,
+            )?;
+        }
+    }
+
+    // Save market and position data
+    market.serialize(&mut &mut market_info.data.borrow_mut()[..])?;
+    position.serialize(&mut &mut position_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes an UpdateFunding instruction
+fn process_update_funding<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let market_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
+
+    let mut market = load_market(program_id, market_info)?;
+
+    // Accrue funding at the current premium
+    let now = Clock::get()?.unix_timestamp;
+    let index_price = read_index_price::<R>(&market, price_info, now)?;
+    market.accrue_funding(index_price, now)?;
+    msg!(
+        "Funding rate {} per period, cumulative funding {}",
+        market.funding_rate,
+        market.cumulative_funding
+    );
+
+    // Save market data
+    market.serialize(&mut &mut market_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a LiquidatePosition instruction
+fn process_liquidate_position<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let keeper_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let position_info = next_account_info(account_info_iter)?;
+    let market_authority_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
+    let collateral_vault_info = next_account_info(account_info_iter)?;
+    let insurance_vault_info = next_account_info(account_info_iter)?;
+    let keeper_token_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the keeper is a signer
+    check_signer(keeper_info)?;
+
+    let mut market = load_market(program_id, market_info)?;
+    check_market_authority(
+        program_id,
+        market_info.key,
+        market.bump_seed,
+        market_authority_info.key,
+    )?;
+    check_collateral_vault(&market, collateral_vault_info, token_program_info)?;
+    if *insurance_vault_info.key != market.insurance_vault {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    let mut position = load_position(program_id, position_info, market_info)?;
+    if position.is_cross_margin() {
+        return Err(TemplateError::InvalidPosition.into());
+    }
+    if !position.is_open() {
+        return Err(TemplateError::PositionNotOpen.into());
+    }
+
+    // Settle funding up to now and check the position is unhealthy
+    let now = Clock::get()?.unix_timestamp;
+    let index_price = read_index_price::<R>(&market, price_info, now)?;
+    market.accrue_funding(index_price, now)?;
+    position.settle(&market)?;
+    if !position.is_liquidatable(&market, index_price)? {
+        return Err(TemplateError::PositionHealthy.into());
+    }
+
+    // Close just enough at the index price to restore the maintenance margin
+    let size = position.liquidation_size(&market, index_price)?;
+    let exit_notional = market.notional(size, index_price)?;
+    let entry_notional = position.entry_notional_of(size);
+    let realized_pnl = match position.side {
+        Side::Long => exit_notional as i128 - entry_notional as i128,
+        Side::Short => entry_notional as i128 - exit_notional as i128,
+    };
+    let reward = market.liquidation_reward(exit_notional);
+    let balance = position.collateral as i128 + realized_pnl - reward as i128;
+    market.remove_open_interest(position.side, size)?;
+    position.size -= size;
+    position.entry_notional -= entry_notional;
+    msg!("Liquidated {} of the position, reward {}", size, reward);
+
+    let market_key = market_info.key.to_bytes();
+    let authority_seeds: &[&[u8]] = &[&market_key[..], &[market.bump_seed]];
+    if balance >= 0 {
+        // What is left stays with the owner, to withdraw by closing the
+        // position if it was fully liquidated
+        position.collateral = balance as u64;
+    } else {
+        position.collateral = 0;
+        cover_bad_debt(
+            &mut market,
+            position.side,
+            clamp_to_u64(-balance),
+            token_program_info,
+            insurance_vault_info,
+            collateral_vault_info,
+            market_authority_info,
+            authority_seeds,
+        )?;
+    }
+
+    // Pay the keeper
+    if reward > 0 {
+        transfer_from_vault(
+            token_program_info,
+            collateral_vault_info,
+            keeper_token_info,
+            market_authority_info,
+            authority_seeds,
+            reward,
+        )?;
+    }
+
+    // Save market and position data
+    market.serialize(&mut &mut market_info.data.borrow_mut()[..])?;
+    position.serialize(&mut &mut position_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a RepegAmm instruction
+fn process_repeg_amm<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let admin_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
+
+    // Check the admin is a signer
+    check_signer(admin_info)?;
+
+    let mut market = load_market(program_id, market_info)?;
+    if market.admin != *admin_info.key {
         return Err(TemplateError::InvalidAuthority.into());
     }
-    
+    if market.pricing_mode != PricingMode::Vamm {
+        return Err(TemplateError::NotVammMarket.into());
+    }
+
+    // Accrue funding at the old mark price, then move the curve
+    let now = Clock::get()?.unix_timestamp;
+    let index_price = read_index_price::<R>(&market, price_info, now)?;
+    market.accrue_funding(index_price, now)?;
+    let cost = market.repeg(index_price)?;
+    msg!(
+        "Repegged quote reserve to {} at a cost of {}",
+        market.amm_quote_reserve,
+        cost
+    );
+
+    // Save market data
+    market.serialize(&mut &mut market_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes an InitializeMarginAccount instruction
+fn process_initialize_margin_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let margin_account_info = next_account_info(account_info_iter)?;
+    let margin_authority_info = next_account_info(account_info_iter)?;
+    let collateral_mint_info = next_account_info(account_info_iter)?;
+    let vault_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    // Check margin account ownership
+    check_account_owner(margin_account_info, program_id)?;
+
     // Check for rent exemption
     let rent = &Rent::from_account_info(rent_info)?;
-    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
+    if !rent.is_exempt(
+        margin_account_info.lamports(),
+        margin_account_info.data_len(),
+    ) {
         return Err(TemplateError::NotRentExempt.into());
     }
-    
-    // Initialize account data
-    let template_account = TemplateAccount {
-        authority: *authority_info.key,
-        value1: param1,
-        value2: param2,
+
+    // Check the margin account has not been initialized yet
+    if margin_account_info.data_len() != MarginAccount::get_size() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if margin_account_info.data.borrow()[0] != 0 {
+        return Err(TemplateError::MarginAccountAlreadyInitialized.into());
+    }
+
+    // Validate margin authority
+    let (margin_authority, bump_seed) = find_margin_authority(program_id, margin_account_info.key);
+    if margin_authority != *margin_authority_info.key {
+        return Err(TemplateError::InvalidMarginAuthority.into());
+    }
+
+    // Validate the collateral mint and vault
+    check_account_owner(collateral_mint_info, &spl_token::id())
+        .map_err(|_| TemplateError::InvalidVault)?;
+    check_vault(vault_info, collateral_mint_info.key, &margin_authority)?;
+
+    let margin_account = MarginAccount {
         is_initialized: true,
+        bump_seed,
+        owner: *owner_info.key,
+        collateral_mint: *collateral_mint_info.key,
+        vault: *vault_info.key,
+        ..MarginAccount::default()
     };
-    
-    // Save account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+
+    // Save margin account data
+    margin_account.serialize(&mut &mut margin_account_info.data.borrow_mut()[..])?;
+
     Ok(())
 }
 
-/// Processes an Operation1 instruction
-fn process_operation1(
+/// Processes a DepositMargin instruction
+fn process_deposit_margin(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     amount: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
-        return Err(TemplateError::InvalidAuthority.into());
+    let owner_info = next_account_info(account_info_iter)?;
+    let margin_account_info = next_account_info(account_info_iter)?;
+    let owner_collateral_info = next_account_info(account_info_iter)?;
+    let vault_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    let mut margin_account =
+        load_owned_margin_account(program_id, margin_account_info, owner_info)?;
+    check_margin_vault(&margin_account, vault_info, token_program_info)?;
+
+    if amount == 0 {
+        return Err(TemplateError::InvalidAmount.into());
     }
-    
-    // Deserialize account data
-    let mut template_account = TemplateAccount::try_from_slice(&account_info.data.borrow())?;
-    
-    // Verify authority
-    if template_account.authority != *authority_info.key {
-        return Err(TemplateError::InvalidAuthority.into());
+
+    // Deposit the collateral
+    transfer_to_vault(
+        token_program_info,
+        owner_collateral_info,
+        vault_info,
+        owner_info,
+        amount,
+    )?;
+    margin_account.collateral = safe_addition(margin_account.collateral, amount)?;
+
+    // Save margin account data
+    margin_account.serialize(&mut &mut margin_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a WithdrawMargin instruction
+fn process_withdraw_margin<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let margin_account_info = next_account_info(account_info_iter)?;
+    let margin_authority_info = next_account_info(account_info_iter)?;
+    let vault_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let health_infos = account_info_iter.as_slice();
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    let mut margin_account =
+        load_owned_margin_account(program_id, margin_account_info, owner_info)?;
+    check_margin_authority(
+        program_id,
+        margin_account_info.key,
+        margin_account.bump_seed,
+        margin_authority_info.key,
+    )?;
+    check_margin_vault(&margin_account, vault_info, token_program_info)?;
+
+    if amount == 0 {
+        return Err(TemplateError::InvalidAmount.into());
     }
-    
-    // Update account data
-    template_account.value1 = template_account.value1.checked_add(amount)
-        .ok_or(TemplateError::MathOverflow)?;
-    
-    // Save updated account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+    margin_account.collateral = margin_account
+        .collateral
+        .checked_sub(amount)
+        .ok_or(TemplateError::InsufficientMargin)?;
+
+    // What is left must still meet the initial margin of every position
+    let now = Clock::get()?.unix_timestamp;
+    check_initial_margin::<R>(
+        program_id,
+        margin_account_info.key,
+        &margin_account,
+        health_infos,
+        now,
+    )?;
+
+    // Pay out the collateral
+    let margin_account_key = margin_account_info.key.to_bytes();
+    transfer_from_vault(
+        token_program_info,
+        vault_info,
+        destination_info,
+        margin_authority_info,
+        &[&margin_account_key[..], &[margin_account.bump_seed]],
+        amount,
+    )?;
+
+    // Save margin account data
+    margin_account.serialize(&mut &mut margin_account_info.data.borrow_mut()[..])?;
+
     Ok(())
 }
 
-/// Processes an Operation2 instruction
-fn process_operation2(
+/// Processes an IncreaseMarginPosition instruction
+fn process_increase_margin_position<R: PriceReader>(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
+    side: Side,
+    size: u64,
+    price_limit: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
-        return Err(TemplateError::InvalidAuthority.into());
+    let owner_info = next_account_info(account_info_iter)?;
+    let margin_account_info = next_account_info(account_info_iter)?;
+    let margin_authority_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let market_authority_info = next_account_info(account_info_iter)?;
+    let position_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
+    let margin_vault_info = next_account_info(account_info_iter)?;
+    let collateral_vault_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let health_infos = account_info_iter.as_slice();
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    let mut margin_account =
+        load_owned_margin_account(program_id, margin_account_info, owner_info)?;
+    let mut market = load_market(program_id, market_info)?;
+    check_margin_trade_accounts(
+        program_id,
+        &margin_account,
+        &market,
+        margin_account_info,
+        margin_authority_info,
+        market_info,
+        market_authority_info,
+        margin_vault_info,
+        collateral_vault_info,
+        token_program_info,
+    )?;
+
+    // Check the position account, which may hold a closed position or an
+    // open one on the same side
+    check_account_owner(position_info, program_id)?;
+    if position_info.data_len() != Position::get_size() {
+        return Err(ProgramError::InvalidAccountData);
     }
-    
-    // Deserialize account data
-    let mut template_account = TemplateAccount::try_from_slice(&account_info.data.borrow())?;
-    
-    // Verify authority
-    if template_account.authority != *authority_info.key {
-        return Err(TemplateError::InvalidAuthority.into());
+    if !Rent::get()?.is_exempt(position_info.lamports(), position_info.data_len()) {
+        return Err(TemplateError::NotRentExempt.into());
+    }
+    let mut position = if position_info.data.borrow()[0] == 0 {
+        Position {
+            is_initialized: true,
+            market: *market_info.key,
+            owner: *owner_info.key,
+            margin_account: *margin_account_info.key,
+            ..Position::default()
+        }
+    } else {
+        let position = Position::try_from_slice(&position_info.data.borrow())?;
+        if position.market != *market_info.key
+            || position.margin_account != *margin_account_info.key
+            || (position.is_open() && position.side != side)
+        {
+            return Err(TemplateError::InvalidPosition.into());
+        }
+        position
+    };
+
+    if size == 0 {
+        return Err(TemplateError::InvalidAmount.into());
     }
-    
-    // Update account data
-    template_account.value2 = template_account.value2.checked_add(1)
-        .ok_or(TemplateError::MathOverflow)?;
-    
-    // Save updated account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+
+    // Settle funding up to now before the trade moves the skew
+    let now = Clock::get()?.unix_timestamp;
+    let index_price = read_index_price::<R>(&market, price_info, now)?;
+    market.accrue_funding(index_price, now)?;
+    let owed = if position.is_open() {
+        position.take_owed(&market)?
+    } else {
+        position.side = side;
+        position.reset_indexes(&market);
+        0
+    };
+
+    // Trade and charge the fee to the margin account
+    let fill_price = market.fill_price(index_price, side, size)?;
+    check_price_limit(side, fill_price, price_limit)?;
+    let notional = market.notional(size, fill_price)?;
+    let fee = market.taker_fee(notional);
+    market.fee_pool = safe_addition(market.fee_pool, fee)?;
+    market.add_open_interest(side, size)?;
+    position.size = safe_addition(position.size, size)?;
+    position.entry_notional = safe_addition(position.entry_notional, notional)?;
+    settle_margin_cash_flow(
+        &mut margin_account,
+        &market,
+        -owed - fee as i128,
+        token_program_info,
+        margin_authority_info,
+        margin_vault_info,
+        market_authority_info,
+        collateral_vault_info,
+        margin_account_info.key,
+        market_info.key,
+    )?;
+    margin_account.add_position(position_info.key)?;
+
+    // Save margin account, market and position data
+    margin_account.serialize(&mut &mut margin_account_info.data.borrow_mut()[..])?;
+    market.serialize(&mut &mut market_info.data.borrow_mut()[..])?;
+    position.serialize(&mut &mut position_info.data.borrow_mut()[..])?;
+
+    // The margin account must meet the initial margin of all its positions
+    check_initial_margin::<R>(
+        program_id,
+        margin_account_info.key,
+        &margin_account,
+        health_infos,
+        now,
+    )
+}
+
+/// Processes a ReduceMarginPosition instruction
+fn process_reduce_margin_position<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    size: u64,
+    price_limit: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let margin_account_info = next_account_info(account_info_iter)?;
+    let margin_authority_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let market_authority_info = next_account_info(account_info_iter)?;
+    let position_info = next_account_info(account_info_iter)?;
+    let price_info = next_account_info(account_info_iter)?;
+    let margin_vault_info = next_account_info(account_info_iter)?;
+    let collateral_vault_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    let mut margin_account =
+        load_owned_margin_account(program_id, margin_account_info, owner_info)?;
+    let mut market = load_market(program_id, market_info)?;
+    check_margin_trade_accounts(
+        program_id,
+        &margin_account,
+        &market,
+        margin_account_info,
+        margin_authority_info,
+        market_info,
+        market_authority_info,
+        margin_vault_info,
+        collateral_vault_info,
+        token_program_info,
+    )?;
+    let mut position = load_position(program_id, position_info, market_info)?;
+    if position.margin_account != *margin_account_info.key {
+        return Err(TemplateError::InvalidPosition.into());
+    }
+    if !position.is_open() {
+        return Err(TemplateError::PositionNotOpen.into());
+    }
+    if size == 0 || size > position.size {
+        return Err(TemplateError::InvalidAmount.into());
+    }
+
+    // Settle funding up to now
+    let now = Clock::get()?.unix_timestamp;
+    let index_price = read_index_price::<R>(&market, price_info, now)?;
+    market.accrue_funding(index_price, now)?;
+    let owed = position.take_owed(&market)?;
+
+    // Trade the other way and realize the PnL of the closed part
+    let trade_side = position.side.opposite();
+    let fill_price = market.fill_price(index_price, trade_side, size)?;
+    check_price_limit(trade_side, fill_price, price_limit)?;
+    let exit_notional = market.notional(size, fill_price)?;
+    let entry_notional = position.entry_notional_of(size);
+    let realized_pnl = match position.side {
+        Side::Long => exit_notional as i128 - entry_notional as i128,
+        Side::Short => entry_notional as i128 - exit_notional as i128,
+    };
+    let fee = market.taker_fee(exit_notional);
+    market.fee_pool = safe_addition(market.fee_pool, fee)?;
+    market.remove_open_interest(position.side, size)?;
+    position.size -= size;
+    position.entry_notional -= entry_notional;
+    if !position.is_open() {
+        margin_account.remove_position(position_info.key);
+    }
+
+    // A loss the margin account cannot pay is only written off once it
+    // backs no other position
+    let mut cash_flow = realized_pnl - owed - fee as i128;
+    let shortfall = -(margin_account.collateral as i128 + cash_flow);
+    if shortfall > 0 {
+        if margin_account.open_positions().next().is_some() {
+            return Err(TemplateError::InsufficientMargin.into());
+        }
+        msg!("Unpaid margin account loss {}", shortfall);
+        cash_flow += shortfall;
+    }
+    settle_margin_cash_flow(
+        &mut margin_account,
+        &market,
+        cash_flow,
+        token_program_info,
+        margin_authority_info,
+        margin_vault_info,
+        market_authority_info,
+        collateral_vault_info,
+        margin_account_info.key,
+        market_info.key,
+    )?;
+
+    // Save margin account, market and position data
+    margin_account.serialize(&mut &mut margin_account_info.data.borrow_mut()[..])?;
+    market.serialize(&mut &mut market_info.data.borrow_mut()[..])?;
+    position.serialize(&mut &mut position_info.data.borrow_mut()[..])?;
+
     Ok(())
 }
+
+/// Processes a LiquidateMarginAccount instruction
+fn process_liquidate_margin_account<R: PriceReader>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let keeper_info = next_account_info(account_info_iter)?;
+    let margin_account_info = next_account_info(account_info_iter)?;
+    let margin_authority_info = next_account_info(account_info_iter)?;
+    let margin_vault_info = next_account_info(account_info_iter)?;
+    let keeper_token_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let liquidation_infos = account_info_iter.as_slice();
+
+    // Check the keeper is a signer
+    check_signer(keeper_info)?;
+
+    let mut margin_account = load_margin_account(program_id, margin_account_info)?;
+    check_margin_authority(
+        program_id,
+        margin_account_info.key,
+        margin_account.bump_seed,
+        margin_authority_info.key,
+    )?;
+    check_margin_vault(&margin_account, margin_vault_info, token_program_info)?;
+    if liquidation_infos.len() != 6 * margin_account.open_positions().count() {
+        return Err(TemplateError::InvalidHealthAccounts.into());
+    }
+
+    // Check the equity is below the maintenance margin of all the positions,
+    // walking them as the initial margin check does
+    let now = Clock::get()?.unix_timestamp;
+    let mut equity = margin_account.collateral as i128;
+    let mut maintenance_margin: u64 = 0;
+    let mut cash_flows = Vec::with_capacity(liquidation_infos.len() / 6);
+    let mut seen = Vec::with_capacity(liquidation_infos.len() / 6);
+    for (index, accounts) in liquidation_infos.chunks(6).enumerate() {
+        if seen.contains(accounts[0].key) {
+            return Err(TemplateError::InvalidHealthAccounts.into());
+        }
+        seen.push(*accounts[0].key);
+
+        let (market, position, index_price) = load_health_position::<R>(
+            program_id,
+            margin_account_info.key,
+            &margin_account,
+            accounts,
+            now,
+        )?;
+        let unrealized_pnl = position.unrealized_pnl(&market, index_price)?;
+        let owed = position.owed(&market)?;
+        equity += unrealized_pnl - owed;
+        let notional = market.notional(position.size, index_price)?;
+        maintenance_margin =
+            safe_addition(maintenance_margin, market.maintenance_margin(notional))?;
+        let reward = market.liquidation_reward(notional);
+        cash_flows.push((index, unrealized_pnl - owed - reward as i128));
+    }
+    if equity >= maintenance_margin as i128 {
+        return Err(TemplateError::MarginAccountHealthy.into());
+    }
+
+    // Close the positions at their index prices, the winning ones first so
+    // their profits pay for the losses of the others
+    cash_flows.sort_by_key(|(_, cash_flow)| std::cmp::Reverse(*cash_flow));
+    for (index, _) in cash_flows {
+        let accounts = &liquidation_infos[6 * index..6 * (index + 1)];
+        let (position_info, market_info) = (&accounts[0], &accounts[1]);
+        let (market_authority_info, collateral_vault_info, insurance_vault_info) =
+            (&accounts[3], &accounts[4], &accounts[5]);
+
+        // Reload the market, which an earlier position may have changed
+        let (mut market, mut position, index_price) = load_health_position::<R>(
+            program_id,
+            margin_account_info.key,
+            &margin_account,
+            accounts,
+            now,
+        )?;
+        check_market_authority(
+            program_id,
+            market_info.key,
+            market.bump_seed,
+            market_authority_info.key,
+        )?;
+        check_collateral_vault(&market, collateral_vault_info, token_program_info)?;
+        if *insurance_vault_info.key != market.insurance_vault {
+            return Err(TemplateError::InvalidVault.into());
+        }
+
+        let owed = position.take_owed(&market)?;
+        let realized_pnl = position.unrealized_pnl(&market, index_price)?;
+        let exit_notional = market.notional(position.size, index_price)?;
+        let reward = market.liquidation_reward(exit_notional);
+        market.remove_open_interest(position.side, position.size)?;
+        msg!(
+            "Liquidated {} of the position, reward {}",
+            position.size,
+            reward
+        );
+        position.size = 0;
+        position.entry_notional = 0;
+        margin_account.remove_position(position_info.key);
+
+        // The margin account pays what it can of a loss, and the rest is bad
+        // debt of the market
+        let cash_flow = realized_pnl - owed - reward as i128;
+        let bad_debt = clamp_to_u64(-(margin_account.collateral as i128 + cash_flow));
+        settle_margin_cash_flow(
+            &mut margin_account,
+            &market,
+            cash_flow + bad_debt as i128,
+            token_program_info,
+            margin_authority_info,
+            margin_vault_info,
+            market_authority_info,
+            collateral_vault_info,
+            margin_account_info.key,
+            market_info.key,
+        )?;
+        let market_key = market_info.key.to_bytes();
+        let authority_seeds: &[&[u8]] = &[&market_key[..], &[market.bump_seed]];
+        if bad_debt > 0 {
+            cover_bad_debt(
+                &mut market,
+                position.side,
+                bad_debt,
+                token_program_info,
+                insurance_vault_info,
+                collateral_vault_info,
+                market_authority_info,
+                authority_seeds,
+            )?;
+        }
+
+        // Pay the keeper
+        if reward > 0 {
+            transfer_from_vault(
+                token_program_info,
+                collateral_vault_info,
+                keeper_token_info,
+                market_authority_info,
+                authority_seeds,
+                reward,
+            )?;
+        }
+
+        // Save market and position data
+        market.serialize(&mut &mut market_info.data.borrow_mut()[..])?;
+        position.serialize(&mut &mut position_info.data.borrow_mut()[..])?;
+    }
+
+    // Save margin account data
+    margin_account.serialize(&mut &mut margin_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Checks the accounts a cross-margin trade moves collateral between
+#[allow(clippy::too_many_arguments)]
+fn check_margin_trade_accounts(
+    program_id: &Pubkey,
+    margin_account: &MarginAccount,
+    market: &PerpMarket,
+    margin_account_info: &AccountInfo,
+    margin_authority_info: &AccountInfo,
+    market_info: &AccountInfo,
+    market_authority_info: &AccountInfo,
+    margin_vault_info: &AccountInfo,
+    collateral_vault_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+) -> ProgramResult {
+    if market.collateral_mint != margin_account.collateral_mint {
+        return Err(TemplateError::InvalidMarginAccount.into());
+    }
+    check_margin_authority(
+        program_id,
+        margin_account_info.key,
+        margin_account.bump_seed,
+        margin_authority_info.key,
+    )?;
+    check_market_authority(
+        program_id,
+        market_info.key,
+        market.bump_seed,
+        market_authority_info.key,
+    )?;
+    check_margin_vault(margin_account, margin_vault_info, token_program_info)?;
+    check_collateral_vault(market, collateral_vault_info, token_program_info)
+}
+
+/// Applies a cash flow to the collateral of a margin account, moving the
+/// tokens between the margin vault and the market's collateral vault
+///
+/// Positive cash flows are paid by the market and negative ones by the
+/// margin account, which must hold enough collateral.
+#[allow(clippy::too_many_arguments)]
+fn settle_margin_cash_flow<'a>(
+    margin_account: &mut MarginAccount,
+    market: &PerpMarket,
+    cash_flow: i128,
+    token_program_info: &AccountInfo<'a>,
+    margin_authority_info: &AccountInfo<'a>,
+    margin_vault_info: &AccountInfo<'a>,
+    market_authority_info: &AccountInfo<'a>,
+    collateral_vault_info: &AccountInfo<'a>,
+    margin_account_key: &Pubkey,
+    market_key: &Pubkey,
+) -> ProgramResult {
+    let amount =
+        u64::try_from(cash_flow.unsigned_abs()).map_err(|_| TemplateError::MathOverflow)?;
+    if amount == 0 {
+        return Ok(());
+    }
+    if cash_flow > 0 {
+        let market_key = market_key.to_bytes();
+        transfer_from_vault(
+            token_program_info,
+            collateral_vault_info,
+            margin_vault_info,
+            market_authority_info,
+            &[&market_key[..], &[market.bump_seed]],
+            amount,
+        )?;
+        margin_account.collateral = safe_addition(margin_account.collateral, amount)?;
+    } else {
+        let margin_account_key = margin_account_key.to_bytes();
+        margin_account.collateral = margin_account
+            .collateral
+            .checked_sub(amount)
+            .ok_or(TemplateError::InsufficientMargin)?;
+        transfer_from_vault(
+            token_program_info,
+            margin_vault_info,
+            collateral_vault_info,
+            margin_authority_info,
+            &[&margin_account_key[..], &[margin_account.bump_seed]],
+            amount,
+        )?;
+    }
+    Ok(())
+}
+
+/// Checks that the equity of a margin account covers the initial margin of
+/// every position it backs
+///
+/// `health_infos` holds a position, its market and the market's index price
+/// account for each of those positions. Funding is accrued on a copy of each
+/// market, so the equity includes funding owed up to now.
+fn check_initial_margin<R: PriceReader>(
+    program_id: &Pubkey,
+    margin_account_key: &Pubkey,
+    margin_account: &MarginAccount,
+    health_infos: &[AccountInfo],
+    now: i64,
+) -> ProgramResult {
+    if health_infos.len() != 3 * margin_account.open_positions().count() {
+        return Err(TemplateError::InvalidHealthAccounts.into());
+    }
+
+    let mut equity = margin_account.collateral as i128;
+    let mut initial_margin: u64 = 0;
+    let mut seen = Vec::with_capacity(health_infos.len() / 3);
+    for accounts in health_infos.chunks(3) {
+        if seen.contains(accounts[0].key) {
+            return Err(TemplateError::InvalidHealthAccounts.into());
+        }
+        seen.push(*accounts[0].key);
+
+        let (market, position, index_price) = load_health_position::<R>(
+            program_id,
+            margin_account_key,
+            margin_account,
+            accounts,
+            now,
+        )?;
+        equity += position.unrealized_pnl(&market, index_price)? - position.owed(&market)?;
+        let notional = market.notional(position.size, index_price)?;
+        initial_margin = safe_addition(initial_margin, market.initial_margin(notional))?;
+    }
+
+    if equity < initial_margin as i128 {
+        return Err(TemplateError::InsufficientMargin.into());
+    }
+    Ok(())
+}
+
+/// Loads a position the margin account backs from health accounts starting
+/// with the position, its market and the market's index price account
+///
+/// Returns the position with its market, with funding accrued up to `now`,
+/// and the index price.
+fn load_health_position<R: PriceReader>(
+    program_id: &Pubkey,
+    margin_account_key: &Pubkey,
+    margin_account: &MarginAccount,
+    accounts: &[AccountInfo],
+    now: i64,
+) -> Result<(PerpMarket, Position, u64), ProgramError> {
+    let (position_info, market_info, price_info) = (&accounts[0], &accounts[1], &accounts[2]);
+    if !margin_account.has_position(position_info.key) {
+        return Err(TemplateError::InvalidHealthAccounts.into());
+    }
+
+    let mut market = load_market(program_id, market_info)?;
+    let position = load_position(program_id, position_info, market_info)?;
+    if position.margin_account != *margin_account_key {
+        return Err(TemplateError::InvalidHealthAccounts.into());
+    }
+    let index_price = read_index_price::<R>(&market, price_info, now)?;
+    market.accrue_funding(index_price, now)?;
+    Ok((market, position, index_price))
+}
+
+/// Pays bad debt left in the collateral vault from the insurance vault as
+/// far as it holds, and charges the rest to the positions on the other side
+/// of `side`
+#[allow(clippy::too_many_arguments)]
+fn cover_bad_debt<'a>(
+    market: &mut PerpMarket,
+    side: Side,
+    bad_debt: u64,
+    token_program_info: &AccountInfo<'a>,
+    insurance_vault_info: &AccountInfo<'a>,
+    collateral_vault_info: &AccountInfo<'a>,
+    market_authority_info: &AccountInfo<'a>,
+    authority_seeds: &[&[u8]],
+) -> ProgramResult {
+    let insurance_balance =
+        spl_token::state::Account::unpack(&insurance_vault_info.data.borrow())?.amount;
+    let covered = bad_debt.min(insurance_balance);
+    if covered > 0 {
+        transfer_from_vault(
+            token_program_info,
+            insurance_vault_info,
+            collateral_vault_info,
+            market_authority_info,
+            authority_seeds,
+            covered,
+        )?;
+    }
+    let shortfall = bad_debt - covered;
+    if shortfall > 0 && !market.socialize_loss(side.opposite(), shortfall)? {
+        // Nobody is left on the other side, so the vault absorbs it
+        msg!("Unsocialized bad debt {}", shortfall);
+    }
+    Ok(())
+}
+
+/// Charges the fee on a trade of `size` on the position's side at the fill
+/// price and adds it to the position, which must meet its initial margin
+/// afterwards
+fn trade_into_position(
+    market: &mut PerpMarket,
+    position: &mut Position,
+    index_price: u64,
+    size: u64,
+    price_limit: u64,
+) -> ProgramResult {
+    let fill_price = market.fill_price(index_price, position.side, size)?;
+    check_price_limit(position.side, fill_price, price_limit)?;
+    let notional = market.notional(size, fill_price)?;
+
+    let fee = market.taker_fee(notional);
+    position.collateral = position
+        .collateral
+        .checked_sub(fee)
+        .ok_or(TemplateError::InsufficientMargin)?;
+    market.fee_pool = safe_addition(market.fee_pool, fee)?;
+
+    market.add_open_interest(position.side, size)?;
+    position.size = safe_addition(position.size, size)?;
+    position.entry_notional = safe_addition(position.entry_notional, notional)?;
+
+    // Margin is measured at the index price so that skew cannot inflate it
+    let required = market.initial_margin(market.notional(position.size, index_price)?);
+    if position.equity(market, index_price)? < required as i128 {
+        return Err(TemplateError::InsufficientMargin.into());
+    }
+    Ok(())
+}
+
+/// Checks that a buy fills at or below `price_limit` and a sell at or above it
+fn check_price_limit(trade_side: Side, fill_price: u64, price_limit: u64) -> ProgramResult {
+    let beyond_limit = match trade_side {
+        Side::Long => fill_price > price_limit,
+        Side::Short => fill_price < price_limit,
+    };
+    if beyond_limit {
+        return Err(TemplateError::PriceLimitExceeded.into());
+    }
+    Ok(())
+}
+
+/// Reads the index price in quote base units per whole synthetic token,
+/// rejecting stale prices
+fn read_index_price<R: PriceReader>(
+    market: &PerpMarket,
+    price_info: &AccountInfo,
+    now: i64,
+) -> Result<u64, ProgramError> {
+    if *price_info.key != market.price_account {
+        return Err(TemplateError::InvalidPriceAccount.into());
+    }
+    let price = R::read_price(price_info)?;
+    if price.publish_time < now.saturating_sub(PRICE_MAX_AGE) {
+        return Err(TemplateError::StalePrice.into());
+    }
+    let index_price = price.quote_amount(
+        market.base_unit(),
+        market.base_decimals,
+        market.quote_decimals,
+    )?;
+    if index_price == 0 {
+        return Err(TemplateError::InvalidOraclePrice.into());
+    }
+    Ok(index_price)
+}
+
+/// Loads an initialized market
+fn load_market(program_id: &Pubkey, market_info: &AccountInfo) -> Result<PerpMarket, ProgramError> {
+    check_account_owner(market_info, program_id)?;
+    let market = PerpMarket::try_from_slice(&market_info.data.borrow())?;
+    if !market.is_initialized {
+        return Err(TemplateError::MarketNotInitialized.into());
+    }
+    Ok(market)
+}
+
+/// Loads an initialized position in the market
+fn load_position(
+    program_id: &Pubkey,
+    position_info: &AccountInfo,
+    market_info: &AccountInfo,
+) -> Result<Position, ProgramError> {
+    check_account_owner(position_info, program_id)?;
+    let position = Position::try_from_slice(&position_info.data.borrow())?;
+    if !position.is_initialized || position.market != *market_info.key {
+        return Err(TemplateError::InvalidPosition.into());
+    }
+    Ok(position)
+}
+
+/// Loads an isolated position of `owner_info` in the market
+fn load_owned_position(
+    program_id: &Pubkey,
+    position_info: &AccountInfo,
+    market_info: &AccountInfo,
+    owner_info: &AccountInfo,
+) -> Result<Position, ProgramError> {
+    let position = load_position(program_id, position_info, market_info)?;
+    if position.owner != *owner_info.key || position.is_cross_margin() {
+        return Err(TemplateError::InvalidPosition.into());
+    }
+    Ok(position)
+}
+
+/// Loads an initialized margin account
+fn load_margin_account(
+    program_id: &Pubkey,
+    margin_account_info: &AccountInfo,
+) -> Result<MarginAccount, ProgramError> {
+    check_account_owner(margin_account_info, program_id)?;
+    let margin_account = MarginAccount::try_from_slice(&margin_account_info.data.borrow())?;
+    if !margin_account.is_initialized {
+        return Err(TemplateError::InvalidMarginAccount.into());
+    }
+    Ok(margin_account)
+}
+
+/// Loads a margin account of `owner_info`
+fn load_owned_margin_account(
+    program_id: &Pubkey,
+    margin_account_info: &AccountInfo,
+    owner_info: &AccountInfo,
+) -> Result<MarginAccount, ProgramError> {
+    let margin_account = load_margin_account(program_id, margin_account_info)?;
+    if margin_account.owner != *owner_info.key {
+        return Err(TemplateError::InvalidMarginAccount.into());
+    }
+    Ok(margin_account)
+}
+
+/// Checks the vault of a margin account and the token program
+fn check_margin_vault(
+    margin_account: &MarginAccount,
+    vault_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+) -> ProgramResult {
+    if *vault_info.key != margin_account.vault {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    if *token_program_info.key != spl_token::id() {
+        return Err(TemplateError::InvalidTokenProgram.into());
+    }
+    Ok(())
+}
+
+/// Checks the collateral vault of a market and the token program
+fn check_collateral_vault(
+    market: &PerpMarket,
+    collateral_vault_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+) -> ProgramResult {
+    if *collateral_vault_info.key != market.collateral_vault {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    if *token_program_info.key != spl_token::id() {
+        return Err(TemplateError::InvalidTokenProgram.into());
+    }
+    Ok(())
+}
+
+/// Checks that a new vault is an empty token account of `mint` that `authority` controls
+fn check_vault(vault_info: &AccountInfo, mint: &Pubkey, authority: &Pubkey) -> ProgramResult {
+    check_account_owner(vault_info, &spl_token::id()).map_err(|_| TemplateError::InvalidVault)?;
+    let vault = spl_token::state::Account::unpack(&vault_info.data.borrow())?;
+    if vault.mint != *mint
+        || vault.owner != *authority
+        || vault.amount != 0
+        || vault.delegate.is_some()
+        || vault.close_authority.is_some()
+    {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    Ok(())
+}
+
+/// Transfers tokens from a user-owned account into a vault
+fn transfer_to_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            source_info.key,
+            vault_info.key,
+            owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            source_info.clone(),
+            vault_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+/// Transfers tokens out of a vault, signing as the market authority that owns it
+fn transfer_from_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    authority_seeds: &[&[u8]],
+    amount: u64,
+) -> ProgramResult {
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            vault_info.key,
+            destination_info.key,
+            authority_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            vault_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[authority_seeds],
+    )
+}
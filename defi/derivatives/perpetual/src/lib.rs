@@ -1,8 +1,21 @@
 //! Perpetual futures contracts for synthetic assets
 //!
-//! This program provides [TEMPLATE FUNCTIONALITY].
+//! This program lists perpetual futures markets on synthetic assets whose
+//! index price is read from an oracle. Traders open long or short positions
+//! backed by isolated collateral held in a vault owned by a market PDA.
+//! Trades fill at a mark price that moves with the skew between long and
+//! short open interest, or against virtual reserves on an x*y=k curve that
+//! the admin repegs toward the index price out of the fee pool. The premium
+//! of the mark over the index accrues as funding that positions settle
+//! lazily through a cumulative index.
+//!
+//! Keepers liquidate positions below their maintenance margin, with an
+//! insurance fund and the other side absorbing bad debt. Cross-margin
+//! accounts back positions in several markets with one pool of collateral,
+//! and are checked and liquidated on the health of all of them together.
 
 pub mod instructions;
+pub mod oracle;
 pub mod state;
 pub mod utils;
 pub mod errors;
@@ -13,7 +26,7 @@ pub mod entrypoint;
 // Export current sdk types for downstream users building with a different sdk version
 pub use solana_program;
 
-solana_program::declare_id!("perpetual-futures111111111111111111111111111111");
+solana_program::declare_id!("Perp111111111111111111111111111111111111111");
 
 /// Program entrypoint's implementation
 pub fn process_instruction(
@@ -0,0 +1,111 @@
+//! Price readers for the index price
+
+use solana_program::{account_info::AccountInfo, program_error::ProgramError};
+
+use crate::errors::TemplateError;
+
+/// Price read from an oracle account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OraclePrice {
+    /// Price of one whole synthetic token in whole quote tokens, scaled by `10^expo`
+    pub price: i64,
+
+    /// Decimal exponent of `price`
+    pub expo: i32,
+
+    /// Unix timestamp the price was published at
+    pub publish_time: i64,
+}
+
+impl OraclePrice {
+    /// Converts the price to the quote base units `underlying_amount` base
+    /// units of the underlying are worth, rounded down
+    pub fn quote_amount(
+        &self,
+        underlying_amount: u64,
+        underlying_decimals: u8,
+        quote_decimals: u8,
+    ) -> Result<u64, TemplateError> {
+        if self.price <= 0 {
+            return Err(TemplateError::InvalidOraclePrice);
+        }
+        let value = (self.price as u128)
+            .checked_mul(underlying_amount as u128)
+            .ok_or(TemplateError::MathOverflow)?;
+        let exponent = self.expo as i64 + quote_decimals as i64 - underlying_decimals as i64;
+        let scale = 10u128
+            .checked_pow(exponent.unsigned_abs() as u32)
+            .ok_or(TemplateError::MathOverflow)?;
+        let amount = if exponent >= 0 {
+            value
+                .checked_mul(scale)
+                .ok_or(TemplateError::MathOverflow)?
+        } else {
+            value / scale
+        };
+        u64::try_from(amount).map_err(|_| TemplateError::MathOverflow)
+    }
+}
+
+/// Reads prices from an oracle account
+///
+/// The processor is generic over the reader so that tests can price
+/// markets against accounts in their own format.
+pub trait PriceReader {
+    /// Reads the current price from `price_info`
+    fn read_price(price_info: &AccountInfo) -> Result<OraclePrice, ProgramError>;
+}
+
+/// Magic number at the start of every Pyth account
+pub const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+
+/// Pyth account layout version
+pub const PYTH_VERSION: u32 = 2;
+
+/// Pyth account type of price accounts
+pub const PYTH_PRICE_ACCOUNT_TYPE: u32 = 3;
+
+/// Pyth aggregate price status of a feed that is updating
+pub const PYTH_STATUS_TRADING: u32 = 1;
+
+/// Bytes of a Pyth price account up to the end of the aggregate price
+pub const PYTH_PRICE_ACCOUNT_MIN_LEN: usize = 240;
+
+/// Reads the aggregate price of a Pyth v2 price account
+///
+/// The account is parsed from raw bytes at the offsets of the `PriceAccount`
+/// struct in `pyth-sdk-solana`. Prices whose aggregate status is not trading
+/// are rejected.
+pub struct PythPriceReader;
+
+impl PriceReader for PythPriceReader {
+    fn read_price(price_info: &AccountInfo) -> Result<OraclePrice, ProgramError> {
+        let data = price_info.data.borrow();
+        if data.len() < PYTH_PRICE_ACCOUNT_MIN_LEN
+            || read_u32(&data, 0) != PYTH_MAGIC
+            || read_u32(&data, 4) != PYTH_VERSION
+            || read_u32(&data, 8) != PYTH_PRICE_ACCOUNT_TYPE
+        {
+            return Err(TemplateError::InvalidPriceAccount.into());
+        }
+        if read_u32(&data, 224) != PYTH_STATUS_TRADING {
+            return Err(TemplateError::InvalidOraclePrice.into());
+        }
+
+        Ok(OraclePrice {
+            price: read_u64(&data, 208) as i64,
+            expo: read_u32(&data, 20) as i32,
+            publish_time: read_u64(&data, 96) as i64,
+        })
+    }
+}
+
+/// Reads a little-endian u32 at `offset`
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Reads a little-endian u64 at `offset`
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
@@ -1,20 +1,762 @@
-//! State objects for template account
+//! State objects for perpetual markets and positions
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::pubkey::Pubkey;
 
-/// Template account data
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-pub struct TemplateAccount {
-    /// The account authority (owner)
-    pub authority: Pubkey,
-    
-    /// Example value 1
-    pub value1: u64,
-    
-    /// Example value 2
-    pub value2: u8,
-    
-    /// Is the account initialized
+use crate::errors::TemplateError;
+
+/// Oldest oracle price positions trade against, in seconds
+pub const PRICE_MAX_AGE: i64 = 60;
+
+/// Denominator of margin ratios, fees and funding caps
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Period over which a premium of the mark over the index price is paid
+/// in full as funding, in seconds
+pub const FUNDING_PERIOD: i64 = 86_400;
+
+/// Scale of the cumulative funding index and the funding rate
+pub const FUNDING_PRECISION: i128 = 1_000_000_000;
+
+/// Most positions a margin account can hold at once
+pub const MAX_MARGIN_POSITIONS: usize = 8;
+
+/// Direction of a position
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Side {
+    /// Profits when the price rises
+    #[default]
+    Long,
+    /// Profits when the price falls
+    Short,
+}
+
+impl Side {
+    /// Side of the trade that closes a position on this side
+    pub fn opposite(&self) -> Side {
+        match self {
+            Side::Long => Side::Short,
+            Side::Short => Side::Long,
+        }
+    }
+}
+
+/// How a market prices trades
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PricingMode {
+    /// The index price plus a premium proportional to the skew
+    #[default]
+    Skew,
+    /// Virtual base and quote reserves on an x*y=k curve
+    Vamm,
+}
+
+/// Perpetual futures market on a synthetic asset
+///
+/// Positions are margined in the collateral token, which is held by a vault
+/// owned by the market authority PDA. Prices are quote base units of the
+/// collateral token per whole synthetic token, and position sizes are base
+/// units of the synthetic token with `base_decimals` decimals.
+///
+/// The index price is read from `price_account`. In `PricingMode::Skew` the
+/// mark price adds a premium proportional to the skew between long and short
+/// open interest, `index * skew / skew_scale`, and trades fill at the average
+/// mark price over the skew they move through. In `PricingMode::Vamm` trades
+/// swap against virtual reserves instead: longs take base out of the curve
+/// and shorts put it in, so the base reserve is `amm_base_reserve - skew`
+/// and the quote reserve keeps their product constant. The premium of the mark over the index
+/// accrues into `cumulative_funding`, which longs pay and shorts receive
+/// when the mark trades above the index.
+///
+/// Positions whose equity falls below `maintenance_margin_bps` of their
+/// notional can be liquidated by anyone. Bad debt left by a liquidation is
+/// paid from the insurance vault, and what the insurance fund cannot cover
+/// is socialized across the positions on the other side through
+/// `cumulative_long_loss` and `cumulative_short_loss`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct PerpMarket {
+    /// Is the market initialized
     pub is_initialized: bool,
+
+    /// Bump seed of the market authority PDA
+    pub bump_seed: u8,
+
+    /// Admin of the market
+    pub admin: Pubkey,
+
+    /// Mint of the collateral token
+    pub collateral_mint: Pubkey,
+
+    /// Vault holding the collateral of every position
+    pub collateral_vault: Pubkey,
+
+    /// Oracle account the index price is read from
+    pub price_account: Pubkey,
+
+    /// Vault of the insurance fund that absorbs bad debt
+    pub insurance_vault: Pubkey,
+
+    /// Decimals of the synthetic asset
+    pub base_decimals: u8,
+
+    /// Decimals of the collateral token
+    pub quote_decimals: u8,
+
+    /// Equity a position needs after a trade, in basis points of its notional
+    pub initial_margin_bps: u64,
+
+    /// Equity below which a position can be liquidated, in basis points of its notional
+    pub maintenance_margin_bps: u64,
+
+    /// Reward paid to liquidators, in basis points of the notional they close
+    pub liquidation_reward_bps: u64,
+
+    /// Fee charged on the notional of every trade, in basis points
+    pub taker_fee_bps: u64,
+
+    /// Skew, in base units, at which the mark price is twice the index price
+    /// in `PricingMode::Skew`
+    pub skew_scale: u64,
+
+    /// Largest premium funding accrues at, in basis points of the index price
+    pub max_funding_rate_bps: u64,
+
+    /// How trades are priced
+    pub pricing_mode: PricingMode,
+
+    /// Virtual base reserve at zero skew in `PricingMode::Vamm`, in base units
+    pub amm_base_reserve: u128,
+
+    /// Virtual quote reserve at zero skew in `PricingMode::Vamm`, in quote base units
+    pub amm_quote_reserve: u128,
+
+    /// Base units held by long positions
+    pub long_open_interest: u64,
+
+    /// Base units held by short positions
+    pub short_open_interest: u64,
+
+    /// Funding paid per whole synthetic token held long since the market
+    /// opened, in quote base units scaled by `FUNDING_PRECISION`
+    pub cumulative_funding: i128,
+
+    /// Funding rate per `FUNDING_PERIOD` at the last accrual, scaled by `FUNDING_PRECISION`
+    pub funding_rate: i64,
+
+    /// Unix timestamp funding was last accrued at
+    pub last_funding_time: i64,
+
+    /// Trading fees collected, which stay in the collateral vault
+    pub fee_pool: u64,
+
+    /// Bad debt socialized per whole synthetic token held long, in quote
+    /// base units scaled by `FUNDING_PRECISION`
+    pub cumulative_long_loss: i128,
+
+    /// Bad debt socialized per whole synthetic token held short, in quote
+    /// base units scaled by `FUNDING_PRECISION`
+    pub cumulative_short_loss: i128,
+
+    /// Bad debt socialized across positions in total
+    pub socialized_loss: u64,
+
+    /// Net cost of repegging the virtual reserves, paid from the fee pool
+    pub cumulative_repeg_cost: i128,
+}
+
+impl PerpMarket {
+    /// Get the size of PerpMarket struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + bump_seed (1 byte) +
+        // admin, collateral_mint, collateral_vault, price_account and
+        // insurance_vault (32 bytes * 5) + decimals (2 bytes) +
+        // risk parameters (8 bytes * 6) + pricing_mode (1 byte) +
+        // virtual reserves (16 bytes * 2) + open interest (8 bytes * 2) +
+        // cumulative_funding (16 bytes) + funding_rate (8 bytes) +
+        // last_funding_time (8 bytes) + fee_pool (8 bytes) +
+        // cumulative losses (16 bytes * 2) + socialized_loss (8 bytes) +
+        // cumulative_repeg_cost (16 bytes)
+        1 + 1 + 32 * 5 + 2 + 8 * 6 + 1 + 16 * 2 + 8 * 2 + 16 + 8 + 8 + 8 + 16 * 2 + 8 + 16
+    }
+
+    /// Base units in one whole synthetic token
+    pub fn base_unit(&self) -> u64 {
+        10u64.pow(self.base_decimals as u32)
+    }
+
+    /// Long minus short open interest, in base units
+    pub fn skew(&self) -> i128 {
+        self.long_open_interest as i128 - self.short_open_interest as i128
+    }
+
+    /// Mark price at the current skew
+    pub fn mark_price(&self, index_price: u64) -> Result<u64, TemplateError> {
+        match self.pricing_mode {
+            PricingMode::Skew => self.price_at_skew(index_price, 2 * self.skew(), false),
+            PricingMode::Vamm => self.vamm_mark_price(),
+        }
+    }
+
+    /// Price a trade of `size` on `side` fills at, rounded against the trader
+    ///
+    /// In `PricingMode::Skew` this is the mark price at the midpoint of the
+    /// skew before and after the trade. In `PricingMode::Vamm` it is the
+    /// quote the trade swaps on the curve per whole synthetic token, or the
+    /// spot price for a trade of zero size.
+    pub fn fill_price(
+        &self,
+        index_price: u64,
+        side: Side,
+        size: u64,
+    ) -> Result<u64, TemplateError> {
+        let skew_after = match side {
+            Side::Long => self.skew() + size as i128,
+            Side::Short => self.skew() - size as i128,
+        };
+        match self.pricing_mode {
+            PricingMode::Skew => {
+                self.price_at_skew(index_price, self.skew() + skew_after, side == Side::Long)
+            }
+            PricingMode::Vamm if size == 0 => self.vamm_mark_price(),
+            PricingMode::Vamm => {
+                // Rounding the reserve after the trade up and the one before
+                // down makes longs pay more and shorts receive less
+                let quote_before = self.vamm_quote_reserve(self.skew(), false)?;
+                let quote_after = self.vamm_quote_reserve(skew_after, true)?;
+                let quote = (quote_after as i128 - quote_before as i128).unsigned_abs();
+                let value = quote
+                    .checked_mul(self.base_unit() as u128)
+                    .and_then(|value| i128::try_from(value).ok())
+                    .ok_or(TemplateError::MathOverflow)?;
+                let price = div_rounded(value, size as i128, side == Side::Long);
+                match u64::try_from(price) {
+                    Ok(price) if price > 0 => Ok(price),
+                    Ok(_) => Err(TemplateError::InvalidMarkPrice),
+                    Err(_) => Err(TemplateError::MathOverflow),
+                }
+            }
+        }
+    }
+
+    /// Virtual base reserve at `skew`, which longs take out of the curve
+    fn vamm_base_reserve(&self, skew: i128) -> Result<u128, TemplateError> {
+        let reserve = self.amm_base_reserve as i128 - skew;
+        if reserve <= 0 {
+            return Err(TemplateError::InsufficientAmmLiquidity);
+        }
+        Ok(reserve as u128)
+    }
+
+    /// Virtual quote reserve at `skew` that keeps the product of the reserves
+    /// at its value at zero skew
+    fn vamm_quote_reserve(&self, skew: i128, round_up: bool) -> Result<u128, TemplateError> {
+        let k = self
+            .amm_base_reserve
+            .checked_mul(self.amm_quote_reserve)
+            .ok_or(TemplateError::MathOverflow)?;
+        let base_reserve = self.vamm_base_reserve(skew)?;
+        Ok(if round_up {
+            k.div_ceil(base_reserve)
+        } else {
+            k / base_reserve
+        })
+    }
+
+    /// Spot price of the virtual reserves at the current skew, rounded down
+    fn vamm_mark_price(&self) -> Result<u64, TemplateError> {
+        let base_reserve = self.vamm_base_reserve(self.skew())?;
+        let price = self
+            .amm_quote_reserve
+            .checked_mul(self.amm_base_reserve)
+            .and_then(|k| k.checked_mul(self.base_unit() as u128))
+            .ok_or(TemplateError::MathOverflow)?
+            / base_reserve
+            / base_reserve;
+        match u64::try_from(price) {
+            Ok(price) if price > 0 => Ok(price),
+            Ok(_) => Err(TemplateError::InvalidMarkPrice),
+            Err(_) => Err(TemplateError::MathOverflow),
+        }
+    }
+
+    /// Quote reserve at zero skew for which the spot price at the current
+    /// skew is `price`
+    fn vamm_quote_reserve_for(&self, price: u64) -> Result<u128, TemplateError> {
+        let base_reserve = self.vamm_base_reserve(self.skew())?;
+        let numerator = base_reserve
+            .checked_mul(base_reserve)
+            .and_then(|square| square.checked_mul(price as u128))
+            .ok_or(TemplateError::MathOverflow)?;
+        let denominator = self
+            .amm_base_reserve
+            .checked_mul(self.base_unit() as u128)
+            .ok_or(TemplateError::MathOverflow)?;
+        Ok(numerator / denominator)
+    }
+
+    /// Cost of moving the quote reserve at zero skew to `quote_reserve`,
+    /// rounded up
+    ///
+    /// The market owes longs the quote they would get back by closing the
+    /// skew through the curve, `k / (amm_base_reserve - skew) -
+    /// amm_quote_reserve`. Repegging changes that amount by
+    /// `(quote_reserve - amm_quote_reserve) * skew / (amm_base_reserve - skew)`,
+    /// which is negative when the repeg moves the price against the side
+    /// holding the skew.
+    pub fn repeg_cost(&self, quote_reserve: u128) -> Result<i128, TemplateError> {
+        let base_reserve = self.vamm_base_reserve(self.skew())?;
+        let change = quote_reserve as i128 - self.amm_quote_reserve as i128;
+        let cost = change
+            .checked_mul(self.skew())
+            .ok_or(TemplateError::MathOverflow)?;
+        Ok(div_rounded(cost, base_reserve as i128, true))
+    }
+
+    /// Moves the virtual reserves so the mark price is `index_price`, or as
+    /// far toward it as the fee pool can pay for, and returns the cost
+    /// charged to the fee pool
+    pub fn repeg(&mut self, index_price: u64) -> Result<i128, TemplateError> {
+        let mut quote_reserve = self.vamm_quote_reserve_for(index_price)?;
+        let mut cost = self.repeg_cost(quote_reserve)?;
+        if cost > self.fee_pool as i128 {
+            // Scale the move down to what the fee pool covers
+            let change = quote_reserve as i128 - self.amm_quote_reserve as i128;
+            let change = change
+                .checked_mul(self.fee_pool as i128)
+                .ok_or(TemplateError::MathOverflow)?
+                / cost;
+            quote_reserve = (self.amm_quote_reserve as i128 + change) as u128;
+            cost = self.repeg_cost(quote_reserve)?.min(self.fee_pool as i128);
+        }
+        if quote_reserve == 0 {
+            return Err(TemplateError::InvalidMarkPrice);
+        }
+        self.amm_quote_reserve = quote_reserve;
+        self.fee_pool = clamp_to_u64(self.fee_pool as i128 - cost);
+        self.cumulative_repeg_cost = self
+            .cumulative_repeg_cost
+            .checked_add(cost)
+            .ok_or(TemplateError::MathOverflow)?;
+        Ok(cost)
+    }
+
+    /// `index + index * double_skew / (2 * skew_scale)`
+    fn price_at_skew(
+        &self,
+        index_price: u64,
+        double_skew: i128,
+        round_up: bool,
+    ) -> Result<u64, TemplateError> {
+        let premium = div_rounded(
+            index_price as i128 * double_skew,
+            2 * self.skew_scale as i128,
+            round_up,
+        );
+        let price = index_price as i128 + premium;
+        if price <= 0 {
+            return Err(TemplateError::InvalidMarkPrice);
+        }
+        u64::try_from(price).map_err(|_| TemplateError::MathOverflow)
+    }
+
+    /// Premium of the mark over the index price, capped at `max_funding_rate_bps`
+    pub fn funding_premium(&self, index_price: u64) -> Result<i128, TemplateError> {
+        let premium = self.mark_price(index_price)? as i128 - index_price as i128;
+        let cap = (index_price as u128 * self.max_funding_rate_bps as u128
+            / BPS_DENOMINATOR as u128) as i128;
+        Ok(premium.clamp(-cap, cap))
+    }
+
+    /// Accrues the funding premium at `index_price` since the last accrual
+    /// into the cumulative funding index
+    pub fn accrue_funding(&mut self, index_price: u64, now: i64) -> Result<(), TemplateError> {
+        let elapsed = now.saturating_sub(self.last_funding_time);
+        if elapsed <= 0 {
+            return Ok(());
+        }
+        let premium = self.funding_premium(index_price)?;
+        let accrued = premium
+            .checked_mul(elapsed as i128 * FUNDING_PRECISION)
+            .ok_or(TemplateError::MathOverflow)?
+            / FUNDING_PERIOD as i128;
+        self.cumulative_funding = self
+            .cumulative_funding
+            .checked_add(accrued)
+            .ok_or(TemplateError::MathOverflow)?;
+        self.funding_rate = (premium * FUNDING_PRECISION / index_price as i128) as i64;
+        self.last_funding_time = now;
+        Ok(())
+    }
+
+    /// Value of `size` base units at `price`, rounded down
+    pub fn notional(&self, size: u64, price: u64) -> Result<u64, TemplateError> {
+        let value = size as u128 * price as u128 / self.base_unit() as u128;
+        u64::try_from(value).map_err(|_| TemplateError::MathOverflow)
+    }
+
+    /// Equity a position of `notional` needs after a trade, rounded up
+    pub fn initial_margin(&self, notional: u64) -> u64 {
+        bps_rounded_up(notional, self.initial_margin_bps)
+    }
+
+    /// Equity below which a position of `notional` can be liquidated, rounded up
+    pub fn maintenance_margin(&self, notional: u64) -> u64 {
+        bps_rounded_up(notional, self.maintenance_margin_bps)
+    }
+
+    /// Reward for liquidating `notional`, rounded down
+    pub fn liquidation_reward(&self, notional: u64) -> u64 {
+        (notional as u128 * self.liquidation_reward_bps as u128 / BPS_DENOMINATOR as u128) as u64
+    }
+
+    /// Fee on a trade of `notional`, rounded up
+    pub fn taker_fee(&self, notional: u64) -> u64 {
+        bps_rounded_up(notional, self.taker_fee_bps)
+    }
+
+    /// Adds `size` to the open interest of `side`
+    pub fn add_open_interest(&mut self, side: Side, size: u64) -> Result<(), TemplateError> {
+        let open_interest = match side {
+            Side::Long => &mut self.long_open_interest,
+            Side::Short => &mut self.short_open_interest,
+        };
+        *open_interest = open_interest
+            .checked_add(size)
+            .ok_or(TemplateError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Socialized loss index of positions on `side`
+    pub fn cumulative_loss(&self, side: Side) -> i128 {
+        match side {
+            Side::Long => self.cumulative_long_loss,
+            Side::Short => self.cumulative_short_loss,
+        }
+    }
+
+    /// Spreads `loss` over the open interest of `side`, rounded up, and
+    /// returns whether there was any open interest to spread it over
+    pub fn socialize_loss(&mut self, side: Side, loss: u64) -> Result<bool, TemplateError> {
+        let open_interest = match side {
+            Side::Long => self.long_open_interest,
+            Side::Short => self.short_open_interest,
+        };
+        if open_interest == 0 {
+            return Ok(false);
+        }
+        let per_token = div_rounded(
+            loss as i128 * self.base_unit() as i128 * FUNDING_PRECISION,
+            open_interest as i128,
+            true,
+        );
+        let cumulative_loss = match side {
+            Side::Long => &mut self.cumulative_long_loss,
+            Side::Short => &mut self.cumulative_short_loss,
+        };
+        *cumulative_loss = cumulative_loss
+            .checked_add(per_token)
+            .ok_or(TemplateError::MathOverflow)?;
+        self.socialized_loss = self
+            .socialized_loss
+            .checked_add(loss)
+            .ok_or(TemplateError::MathOverflow)?;
+        Ok(true)
+    }
+
+    /// Removes `size` from the open interest of `side`
+    pub fn remove_open_interest(&mut self, side: Side, size: u64) -> Result<(), TemplateError> {
+        let open_interest = match side {
+            Side::Long => &mut self.long_open_interest,
+            Side::Short => &mut self.short_open_interest,
+        };
+        *open_interest = open_interest
+            .checked_sub(size)
+            .ok_or(TemplateError::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// Position of one owner in one market
+///
+/// An isolated position is backed by its own `collateral` only. Funding and
+/// socialized losses are settled into it lazily whenever the position is
+/// touched, from the change in the market's cumulative indexes since
+/// `last_cumulative_funding` and `last_cumulative_loss`.
+///
+/// A cross-margin position belongs to `margin_account`, holds no collateral
+/// of its own and settles into the margin account's collateral instead.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct Position {
+    /// Is the position initialized
+    pub is_initialized: bool,
+
+    /// Market the position trades on
+    pub market: Pubkey,
+
+    /// Wallet that controls the position
+    pub owner: Pubkey,
+
+    /// Margin account backing a cross-margin position, or the default
+    /// pubkey for an isolated position
+    pub margin_account: Pubkey,
+
+    /// Long or short
+    pub side: Side,
+
+    /// Base units of the synthetic asset held, zero once closed
+    pub size: u64,
+
+    /// Quote base units paid to open `size`, the cost basis of the position
+    pub entry_notional: u64,
+
+    /// Collateral backing the position, after settled fees, funding and realized PnL
+    pub collateral: u64,
+
+    /// Cumulative funding index funding was last settled at
+    pub last_cumulative_funding: i128,
+
+    /// Socialized loss index of the position's side losses were last settled at
+    pub last_cumulative_loss: i128,
+}
+
+impl Position {
+    /// Get the size of Position struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + market, owner and margin_account
+        // (32 bytes * 3) + side (1 byte) + size (8 bytes) +
+        // entry_notional (8 bytes) + collateral (8 bytes) +
+        // last_cumulative_funding (16 bytes) + last_cumulative_loss (16 bytes)
+        1 + 32 * 3 + 1 + 8 + 8 + 8 + 16 + 16
+    }
+
+    /// Is the position holding any size
+    pub fn is_open(&self) -> bool {
+        self.size > 0
+    }
+
+    /// Is the position backed by a margin account
+    pub fn is_cross_margin(&self) -> bool {
+        self.margin_account != Pubkey::default()
+    }
+
+    /// Part of the entry notional attributed to `size` of the position, rounded down
+    pub fn entry_notional_of(&self, size: u64) -> u64 {
+        if size == self.size {
+            return self.entry_notional;
+        }
+        (self.entry_notional as u128 * size as u128 / self.size as u128) as u64
+    }
+
+    /// Funding owed to the market since the last settlement, rounded
+    /// against the owner
+    ///
+    /// Positive amounts are paid by the position and negative amounts are
+    /// received.
+    pub fn funding_payment(&self, market: &PerpMarket) -> Result<i128, TemplateError> {
+        let delta = market
+            .cumulative_funding
+            .checked_sub(self.last_cumulative_funding)
+            .ok_or(TemplateError::MathOverflow)?;
+        let owed = (self.size as i128)
+            .checked_mul(delta)
+            .ok_or(TemplateError::MathOverflow)?;
+        let owed = match self.side {
+            Side::Long => owed,
+            Side::Short => -owed,
+        };
+        Ok(div_rounded(
+            owed,
+            market.base_unit() as i128 * FUNDING_PRECISION,
+            true,
+        ))
+    }
+
+    /// Socialized losses charged to the position since the last settlement,
+    /// rounded up
+    pub fn socialized_loss(&self, market: &PerpMarket) -> Result<i128, TemplateError> {
+        let delta = market
+            .cumulative_loss(self.side)
+            .checked_sub(self.last_cumulative_loss)
+            .ok_or(TemplateError::MathOverflow)?;
+        let loss = (self.size as i128)
+            .checked_mul(delta)
+            .ok_or(TemplateError::MathOverflow)?;
+        Ok(div_rounded(
+            loss,
+            market.base_unit() as i128 * FUNDING_PRECISION,
+            true,
+        ))
+    }
+
+    /// Starts tracking the market's indexes from now, for a position that
+    /// is being opened
+    pub fn reset_indexes(&mut self, market: &PerpMarket) {
+        self.last_cumulative_funding = market.cumulative_funding;
+        self.last_cumulative_loss = market.cumulative_loss(self.side);
+    }
+
+    /// Settles the funding and socialized losses owed since the last
+    /// settlement into the collateral
+    ///
+    /// Amounts the collateral cannot cover are written off, as the position
+    /// is then already underwater.
+    pub fn settle(&mut self, market: &PerpMarket) -> Result<(), TemplateError> {
+        let owed = self.take_owed(market)?;
+        self.collateral = clamp_to_u64(self.collateral as i128 - owed);
+        Ok(())
+    }
+
+    /// Funding and socialized losses owed since the last settlement, with
+    /// the indexes moved up to the market's so they are only owed once
+    pub fn take_owed(&mut self, market: &PerpMarket) -> Result<i128, TemplateError> {
+        let owed = self.owed(market)?;
+        self.reset_indexes(market);
+        Ok(owed)
+    }
+
+    /// Funding and socialized losses owed since the last settlement
+    pub fn owed(&self, market: &PerpMarket) -> Result<i128, TemplateError> {
+        Ok(self.funding_payment(market)? + self.socialized_loss(market)?)
+    }
+
+    /// Profit or loss of the position at `price`
+    pub fn unrealized_pnl(&self, market: &PerpMarket, price: u64) -> Result<i128, TemplateError> {
+        let value = market.notional(self.size, price)? as i128;
+        Ok(match self.side {
+            Side::Long => value - self.entry_notional as i128,
+            Side::Short => self.entry_notional as i128 - value,
+        })
+    }
+
+    /// Collateral plus unrealized PnL at `price`
+    pub fn equity(&self, market: &PerpMarket, price: u64) -> Result<i128, TemplateError> {
+        Ok(self.collateral as i128 + self.unrealized_pnl(market, price)?)
+    }
+
+    /// Is the equity at `price` below the maintenance margin
+    pub fn is_liquidatable(&self, market: &PerpMarket, price: u64) -> Result<bool, TemplateError> {
+        let notional = market.notional(self.size, price)?;
+        Ok(self.equity(market, price)? < market.maintenance_margin(notional) as i128)
+    }
+
+    /// Smallest size that, closed at `price` with the liquidation reward
+    /// paid out, brings the position back to its maintenance margin
+    ///
+    /// Closing at the index price leaves the equity unchanged except for the
+    /// reward, so closing a fraction `f` of notional `N` with equity `E`
+    /// needs `E - f * r * N >= m * (1 - f) * N`, or
+    /// `f >= (m * N - E) / ((m - r) * N)` for maintenance ratio `m` and
+    /// reward ratio `r < m`. Positions without positive equity close fully.
+    pub fn liquidation_size(&self, market: &PerpMarket, price: u64) -> Result<u64, TemplateError> {
+        let notional = market.notional(self.size, price)? as i128;
+        let equity = self.equity(market, price)?;
+        if equity <= 0 || notional == 0 {
+            return Ok(self.size);
+        }
+        let shortfall =
+            market.maintenance_margin_bps as i128 * notional - equity * BPS_DENOMINATOR as i128;
+        let denominator =
+            (market.maintenance_margin_bps - market.liquidation_reward_bps) as i128 * notional;
+        let numerator = (self.size as i128)
+            .checked_mul(shortfall.max(0))
+            .ok_or(TemplateError::MathOverflow)?;
+        let size = div_rounded(numerator, denominator, true);
+        Ok(clamp_to_u64(size).min(self.size))
+    }
+}
+
+/// Cross-margin account of one owner
+///
+/// Collateral deposited into the account sits in its own vault, owned by the
+/// margin authority PDA, and backs every position listed in `positions`
+/// across any number of markets with the same collateral mint. Its equity is
+/// the collateral plus the unrealized PnL, less funding and socialized losses
+/// owed, of all those positions at their index prices. Trades settle fees,
+/// funding and realized PnL between the margin vault and each market's
+/// collateral vault.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct MarginAccount {
+    /// Is the margin account initialized
+    pub is_initialized: bool,
+
+    /// Bump seed of the margin authority PDA
+    pub bump_seed: u8,
+
+    /// Wallet that controls the margin account
+    pub owner: Pubkey,
+
+    /// Mint of the collateral token
+    pub collateral_mint: Pubkey,
+
+    /// Vault holding the collateral
+    pub vault: Pubkey,
+
+    /// Collateral held in the vault, after settled fees, funding and realized PnL
+    pub collateral: u64,
+
+    /// Open positions backed by the account, with unused slots set to the default pubkey
+    pub positions: [Pubkey; MAX_MARGIN_POSITIONS],
+}
+
+impl MarginAccount {
+    /// Get the size of MarginAccount struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + bump_seed (1 byte) + owner, collateral_mint
+        // and vault (32 bytes * 3) + collateral (8 bytes) +
+        // positions (32 bytes * MAX_MARGIN_POSITIONS)
+        1 + 1 + 32 * 3 + 8 + 32 * MAX_MARGIN_POSITIONS
+    }
+
+    /// Positions the account backs, in slot order
+    pub fn open_positions(&self) -> impl Iterator<Item = &Pubkey> {
+        self.positions
+            .iter()
+            .filter(|position| **position != Pubkey::default())
+    }
+
+    /// Does the account back `position`
+    pub fn has_position(&self, position: &Pubkey) -> bool {
+        self.open_positions().any(|open| open == position)
+    }
+
+    /// Lists `position` in the first free slot unless it is listed already
+    pub fn add_position(&mut self, position: &Pubkey) -> Result<(), TemplateError> {
+        if self.has_position(position) {
+            return Ok(());
+        }
+        let slot = self
+            .positions
+            .iter_mut()
+            .find(|slot| **slot == Pubkey::default())
+            .ok_or(TemplateError::MarginAccountFull)?;
+        *slot = *position;
+        Ok(())
+    }
+
+    /// Frees the slot of `position`
+    pub fn remove_position(&mut self, position: &Pubkey) {
+        if let Some(slot) = self.positions.iter_mut().find(|slot| **slot == *position) {
+            *slot = Pubkey::default();
+        }
+    }
+}
+
+/// Divides by a positive `denominator`, rounding up or down
+fn div_rounded(numerator: i128, denominator: i128, round_up: bool) -> i128 {
+    if round_up {
+        -(-numerator).div_euclid(denominator)
+    } else {
+        numerator.div_euclid(denominator)
+    }
+}
+
+/// `amount * bps / BPS_DENOMINATOR`, rounded up
+fn bps_rounded_up(amount: u64, bps: u64) -> u64 {
+    let product = amount as u128 * bps as u128;
+    product.div_ceil(BPS_DENOMINATOR as u128) as u64
+}
+
+/// Converts to u64, clamping negative amounts to zero
+pub fn clamp_to_u64(amount: i128) -> u64 {
+    amount.clamp(0, u64::MAX as i128) as u64
 }
@@ -1,9 +1,7 @@
 //! Utility functions for the program
 
 use solana_program::{
-    account_info::AccountInfo,
-    entrypoint::ProgramResult,
-    program_error::ProgramError,
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
     pubkey::Pubkey,
 };
 
@@ -31,3 +29,44 @@ pub fn check_signer(account_info: &AccountInfo) -> ProgramResult {
 pub fn safe_addition(a: u64, b: u64) -> Result<u64, TemplateError> {
     a.checked_add(b).ok_or(TemplateError::MathOverflow)
 }
+
+/// Finds the market authority PDA that owns the collateral vault
+pub fn find_market_authority(program_id: &Pubkey, market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[market.as_ref()], program_id)
+}
+
+/// Checks that `authority` is the market authority PDA for the given bump seed
+pub fn check_market_authority(
+    program_id: &Pubkey,
+    market: &Pubkey,
+    bump_seed: u8,
+    authority: &Pubkey,
+) -> ProgramResult {
+    let expected = Pubkey::create_program_address(&[market.as_ref(), &[bump_seed]], program_id)
+        .map_err(|_| TemplateError::InvalidMarketAuthority)?;
+    if expected != *authority {
+        return Err(TemplateError::InvalidMarketAuthority.into());
+    }
+    Ok(())
+}
+
+/// Finds the margin authority PDA that owns the vault of a margin account
+pub fn find_margin_authority(program_id: &Pubkey, margin_account: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[margin_account.as_ref()], program_id)
+}
+
+/// Checks that `authority` is the margin authority PDA for the given bump seed
+pub fn check_margin_authority(
+    program_id: &Pubkey,
+    margin_account: &Pubkey,
+    bump_seed: u8,
+    authority: &Pubkey,
+) -> ProgramResult {
+    let expected =
+        Pubkey::create_program_address(&[margin_account.as_ref(), &[bump_seed]], program_id)
+            .map_err(|_| TemplateError::InvalidMarginAuthority)?;
+    if expected != *authority {
+        return Err(TemplateError::InvalidMarginAuthority.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,537 @@
+//! Fixtures shared by the perpetual-futures integration tests
+//!
+//! Every test binary compiles this module but uses only part of it.
+
+#![allow(dead_code)]
+
+use {
+    borsh::{BorshDeserialize, BorshSerialize},
+    perpetual_futures::{
+        errors::TemplateError,
+        instructions::{
+            close_position, initialize_market, liquidate_position, open_position,
+            processor::process_instruction_with_price_reader,
+        },
+        oracle::{OraclePrice, PriceReader},
+        state::{PerpMarket, Position, PricingMode, Side},
+        utils::find_market_authority,
+    },
+    solana_program::{
+        account_info::AccountInfo,
+        clock::Clock,
+        entrypoint::ProgramResult,
+        instruction::{Instruction, InstructionError},
+        program_error::ProgramError,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        rent::Rent,
+        system_instruction,
+    },
+    solana_program_test::{
+        processor, BanksClient, ProgramTest, ProgramTestBanksClientExt, ProgramTestContext,
+    },
+    solana_sdk::{
+        account::Account,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+pub const NOW: i64 = 1_700_000_000;
+/// One whole synthetic token, with 9 decimals
+pub const TOKEN: u64 = 1_000_000_000;
+/// One whole collateral token, with 6 decimals
+pub const USDC: u64 = 1_000_000;
+/// Oracle price of 2000 collateral tokens per synthetic token, with expo -8
+pub const ORACLE_PRICE: i64 = 200_000_000_000;
+pub const INITIAL_MARGIN_BPS: u64 = 1_000;
+pub const MAINTENANCE_MARGIN_BPS: u64 = 500;
+pub const LIQUIDATION_REWARD_BPS: u64 = 100;
+pub const TAKER_FEE_BPS: u64 = 10;
+/// The mark price moves 0.1% per synthetic token of skew
+pub const SKEW_SCALE: u64 = 1_000 * TOKEN;
+pub const MAX_FUNDING_RATE_BPS: u64 = 100;
+pub const USER_FUNDS: u64 = 100_000 * USDC;
+/// Collateral seeded into the vault to pay out trader profits
+pub const VAULT_LIQUIDITY: u64 = 10_000 * USDC;
+
+/// Price account format read by `FakeOracle`
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct FakePrice {
+    pub price: i64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+/// Reads a borsh-encoded `FakePrice`
+pub struct FakeOracle;
+
+impl PriceReader for FakeOracle {
+    fn read_price(price_info: &AccountInfo) -> Result<OraclePrice, ProgramError> {
+        let fake = FakePrice::try_from_slice(&price_info.data.borrow())
+            .map_err(|_| ProgramError::from(TemplateError::InvalidPriceAccount))?;
+        Ok(OraclePrice {
+            price: fake.price,
+            expo: fake.expo,
+            publish_time: fake.publish_time,
+        })
+    }
+}
+
+pub fn process_with_fake_oracle(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    process_instruction_with_price_reader::<FakeOracle>(program_id, accounts, instruction_data)
+}
+
+/// Market parameters the tests vary, defaulting to a skew-priced market
+pub struct MarketParams {
+    pub maintenance_margin_bps: u64,
+    pub liquidation_reward_bps: u64,
+    pub skew_scale: u64,
+    pub pricing_mode: PricingMode,
+    pub amm_base_reserve: u64,
+}
+
+impl Default for MarketParams {
+    fn default() -> Self {
+        Self {
+            maintenance_margin_bps: MAINTENANCE_MARGIN_BPS,
+            liquidation_reward_bps: LIQUIDATION_REWARD_BPS,
+            skew_scale: SKEW_SCALE,
+            pricing_mode: PricingMode::Skew,
+            amm_base_reserve: 0,
+        }
+    }
+}
+
+/// Accounts of a market
+pub struct MarketFixture {
+    pub program_id: Pubkey,
+    pub market: Pubkey,
+    pub market_authority: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub collateral_vault: Pubkey,
+    pub insurance_vault: Pubkey,
+    pub price_account: Pubkey,
+}
+
+/// A funded trader with an uninitialized position account
+pub struct Trader {
+    pub keypair: Keypair,
+    pub collateral: Pubkey,
+    pub position: Pubkey,
+}
+
+impl MarketFixture {
+    pub fn initialize(&self, admin: &Pubkey, params: MarketParams) -> Instruction {
+        initialize_market(
+            &self.program_id,
+            admin,
+            &self.market,
+            &self.market_authority,
+            &self.collateral_mint,
+            &self.collateral_vault,
+            &self.insurance_vault,
+            &self.price_account,
+            9,
+            INITIAL_MARGIN_BPS,
+            params.maintenance_margin_bps,
+            params.liquidation_reward_bps,
+            TAKER_FEE_BPS,
+            params.skew_scale,
+            MAX_FUNDING_RATE_BPS,
+            params.pricing_mode,
+            params.amm_base_reserve,
+        )
+    }
+
+    pub fn open(
+        &self,
+        trader: &Trader,
+        side: Side,
+        size: u64,
+        collateral: u64,
+        price_limit: u64,
+    ) -> Instruction {
+        open_position(
+            &self.program_id,
+            &trader.keypair.pubkey(),
+            &self.market,
+            &trader.position,
+            &self.price_account,
+            &trader.collateral,
+            &self.collateral_vault,
+            side,
+            size,
+            collateral,
+            price_limit,
+        )
+    }
+
+    pub fn close(&self, trader: &Trader, size: u64, price_limit: u64) -> Instruction {
+        close_position(
+            &self.program_id,
+            &trader.keypair.pubkey(),
+            &self.market,
+            &trader.position,
+            &self.market_authority,
+            &self.price_account,
+            &self.collateral_vault,
+            &trader.collateral,
+            size,
+            price_limit,
+        )
+    }
+
+    pub fn liquidate(
+        &self,
+        keeper: &Keypair,
+        keeper_account: &Pubkey,
+        position: &Pubkey,
+    ) -> Instruction {
+        liquidate_position(
+            &self.program_id,
+            &keeper.pubkey(),
+            &self.market,
+            position,
+            &self.market_authority,
+            &self.price_account,
+            &self.collateral_vault,
+            &self.insurance_vault,
+            keeper_account,
+        )
+    }
+}
+
+pub async fn process(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &all_signers,
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+/// Waits for a blockhash newer than the latest one, so that a repeated
+/// transaction is not deduplicated
+pub async fn refresh_blockhash(context: &mut ProgramTestContext) {
+    let blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+    context
+        .banks_client
+        .get_new_latest_blockhash(&blockhash)
+        .await
+        .unwrap();
+}
+
+pub async fn create_mint(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint_authority: &Pubkey,
+    decimals: u8,
+) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                &mint.pubkey(),
+                mint_authority,
+                None,
+                decimals,
+            )
+            .unwrap(),
+        ],
+        &[&mint],
+    )
+    .await
+    .unwrap();
+    mint.pubkey()
+}
+
+pub async fn create_token_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Pubkey {
+    let account = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &account.pubkey(),
+                mint,
+                owner,
+            )
+            .unwrap(),
+        ],
+        &[&account],
+    )
+    .await
+    .unwrap();
+    account.pubkey()
+}
+
+pub async fn mint_to(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint: &Pubkey,
+    account: &Pubkey,
+    amount: u64,
+) {
+    process(
+        banks_client,
+        payer,
+        &[spl_token::instruction::mint_to(
+            &spl_token::id(),
+            mint,
+            account,
+            &payer.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap()],
+        &[],
+    )
+    .await
+    .unwrap();
+}
+
+/// Creates an empty account of `size` bytes owned by the program
+pub async fn create_program_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    program_id: &Pubkey,
+    size: usize,
+) -> Pubkey {
+    let account = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[system_instruction::create_account(
+            &payer.pubkey(),
+            &account.pubkey(),
+            rent.minimum_balance(size),
+            size as u64,
+            program_id,
+        )],
+        &[&account],
+    )
+    .await
+    .unwrap();
+    account.pubkey()
+}
+
+pub async fn token_balance(banks_client: &mut BanksClient, account: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*account).await.unwrap().unwrap();
+    spl_token::state::Account::unpack(&account.data)
+        .unwrap()
+        .amount
+}
+
+pub async fn get_market(banks_client: &mut BanksClient, market: &Pubkey) -> PerpMarket {
+    let account = banks_client.get_account(*market).await.unwrap().unwrap();
+    PerpMarket::try_from_slice(&account.data).unwrap()
+}
+
+pub async fn get_position(banks_client: &mut BanksClient, position: &Pubkey) -> Position {
+    let account = banks_client.get_account(*position).await.unwrap().unwrap();
+    Position::try_from_slice(&account.data).unwrap()
+}
+
+/// Stores a fake oracle price of `price * 10^-8` collateral tokens per synthetic token
+pub fn set_fake_price(
+    context: &mut ProgramTestContext,
+    price_account: &Pubkey,
+    price: i64,
+    publish_time: i64,
+) {
+    let data = FakePrice {
+        price,
+        expo: -8,
+        publish_time,
+    }
+    .try_to_vec()
+    .unwrap();
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: Pubkey::new_unique(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    context.set_account(price_account, &account.into());
+}
+
+/// Moves the cluster clock to `unix_timestamp`
+pub async fn set_timestamp(context: &mut ProgramTestContext, unix_timestamp: i64) {
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = unix_timestamp;
+    context.set_sysvar(&clock);
+}
+
+pub fn custom_error(error: TemplateError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+/// Starts a test validator at `NOW` reading prices with `FakeOracle`
+pub async fn start_with_fake_oracle() -> (ProgramTestContext, Keypair, Pubkey) {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        "perpetual_futures",
+        program_id,
+        processor!(process_with_fake_oracle),
+    );
+    let mut context = program_test.start_with_context().await;
+    set_timestamp(&mut context, NOW).await;
+    let payer = context.payer.insecure_clone();
+    (context, payer, program_id)
+}
+
+/// Creates the vaults and the uninitialized account of a market whose
+/// price account holds `ORACLE_PRICE`
+pub async fn create_market_account(
+    context: &mut ProgramTestContext,
+    payer: &Keypair,
+    program_id: &Pubkey,
+    collateral_mint: &Pubkey,
+) -> MarketFixture {
+    let price_account = Pubkey::new_unique();
+    set_fake_price(context, &price_account, ORACLE_PRICE, NOW);
+    let banks_client = &mut context.banks_client;
+    let market =
+        create_program_account(banks_client, payer, program_id, PerpMarket::get_size()).await;
+    let (market_authority, _) = find_market_authority(program_id, &market);
+    let collateral_vault =
+        create_token_account(banks_client, payer, collateral_mint, &market_authority).await;
+    let insurance_vault =
+        create_token_account(banks_client, payer, collateral_mint, &market_authority).await;
+    MarketFixture {
+        program_id: *program_id,
+        market,
+        market_authority,
+        collateral_mint: *collateral_mint,
+        collateral_vault,
+        insurance_vault,
+        price_account,
+    }
+}
+
+/// Initializes a market with the payer as admin and seeds its vault with
+/// `VAULT_LIQUIDITY`
+pub async fn initialize_and_fund_market(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    fixture: &MarketFixture,
+    params: MarketParams,
+) {
+    process(
+        banks_client,
+        payer,
+        &[fixture.initialize(&payer.pubkey(), params)],
+        &[],
+    )
+    .await
+    .unwrap();
+    mint_to(
+        banks_client,
+        payer,
+        &fixture.collateral_mint,
+        &fixture.collateral_vault,
+        VAULT_LIQUIDITY,
+    )
+    .await;
+}
+
+/// Starts a test validator at `NOW` reading prices with `FakeOracle`,
+/// with the market account created but not initialized. The payer mints
+/// the collateral token.
+pub async fn start_with_market_account() -> (ProgramTestContext, Keypair, MarketFixture) {
+    let (mut context, payer, program_id) = start_with_fake_oracle().await;
+    let collateral_mint = create_mint(&mut context.banks_client, &payer, &payer.pubkey(), 6).await;
+    let fixture = create_market_account(&mut context, &payer, &program_id, &collateral_mint).await;
+    (context, payer, fixture)
+}
+
+/// Starts a test validator with a market initialized with `params` whose
+/// vault holds `VAULT_LIQUIDITY`
+pub async fn start_with_market(
+    params: MarketParams,
+) -> (ProgramTestContext, Keypair, MarketFixture) {
+    let (mut context, payer, fixture) = start_with_market_account().await;
+    initialize_and_fund_market(&mut context.banks_client, &payer, &fixture, params).await;
+    (context, payer, fixture)
+}
+
+/// Creates a trader holding `USER_FUNDS` of the collateral token and an
+/// empty position account
+pub async fn create_trader(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    fixture: &MarketFixture,
+) -> Trader {
+    let keypair = Keypair::new();
+    let collateral = create_token_account(
+        banks_client,
+        payer,
+        &fixture.collateral_mint,
+        &keypair.pubkey(),
+    )
+    .await;
+    mint_to(
+        banks_client,
+        payer,
+        &fixture.collateral_mint,
+        &collateral,
+        USER_FUNDS,
+    )
+    .await;
+    let position = create_program_account(
+        banks_client,
+        payer,
+        &fixture.program_id,
+        Position::get_size(),
+    )
+    .await;
+    Trader {
+        keypair,
+        collateral,
+        position,
+    }
+}
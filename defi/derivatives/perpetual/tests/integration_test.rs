@@ -1,41 +1,597 @@
 //! Integration tests for perpetual-futures
 
+mod common;
+
 #[cfg(test)]
 mod tests {
     use {
-        borsh::BorshSerialize,
-        solana_program::{
-            instruction::{AccountMeta, Instruction},
-            pubkey::Pubkey,
-            rent::Rent,
-            system_instruction,
-        },
-        solana_program_test::{processor, ProgramTest},
-        solana_sdk::{
-            account::Account,
-            signature::{Keypair, Signer},
-            transaction::Transaction,
+        super::common::{
+            create_trader, custom_error, get_market, get_position, process, refresh_blockhash,
+            set_fake_price, set_timestamp, start_with_market, start_with_market_account,
+            token_balance, MarketFixture, MarketParams, Trader, LIQUIDATION_REWARD_BPS,
+            MAINTENANCE_MARGIN_BPS, NOW, ORACLE_PRICE, SKEW_SCALE, TOKEN, USDC, USER_FUNDS,
         },
-        perpetual-futures::{
-            instructions::TemplateInstruction,
-            process_instruction,
-            state::TemplateAccount,
+        perpetual_futures::{
+            errors::TemplateError,
+            instructions::{close_position, increase_position, open_position, update_funding},
+            state::{Side, FUNDING_PRECISION},
         },
-        std::str::FromStr,
+        solana_program::{instruction::Instruction, pubkey::Pubkey},
+        solana_sdk::signature::Signer,
     };
 
+    /// The oracle price in collateral base units per synthetic token
+    const INDEX_PRICE: u64 = 2_000 * USDC;
+
+    impl MarketFixture {
+        fn increase(
+            &self,
+            trader: &Trader,
+            size: u64,
+            collateral: u64,
+            price_limit: u64,
+        ) -> Instruction {
+            increase_position(
+                &self.program_id,
+                &trader.keypair.pubkey(),
+                &self.market,
+                &trader.position,
+                &self.price_account,
+                &trader.collateral,
+                &self.collateral_vault,
+                size,
+                collateral,
+                price_limit,
+            )
+        }
+
+        fn update_funding(&self) -> Instruction {
+            update_funding(&self.program_id, &self.market, &self.price_account)
+        }
+    }
+
+    fn initialize_instruction(
+        fixture: &MarketFixture,
+        admin: &Pubkey,
+        skew_scale: u64,
+    ) -> Instruction {
+        fixture.initialize(
+            admin,
+            MarketParams {
+                skew_scale,
+                ..MarketParams::default()
+            },
+        )
+    }
+
     #[tokio::test]
-    async fn test_initialize() {
-        // TODO: Implement test logic for initialization
+    async fn test_initialize_market() {
+        let (mut context, payer, fixture) = start_with_market_account().await;
+        let banks_client = &mut context.banks_client;
+
+        // A zero skew scale is rejected
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[initialize_instruction(&fixture, &payer.pubkey(), 0)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidMarketParameters)
+        );
+
+        process(
+            banks_client,
+            &payer,
+            &[initialize_instruction(
+                &fixture,
+                &payer.pubkey(),
+                SKEW_SCALE,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let market = get_market(banks_client, &fixture.market).await;
+        assert!(market.is_initialized);
+        assert_eq!(market.admin, payer.pubkey());
+        assert_eq!(market.collateral_vault, fixture.collateral_vault);
+        assert_eq!(market.insurance_vault, fixture.insurance_vault);
+        assert_eq!(market.price_account, fixture.price_account);
+        assert_eq!((market.base_decimals, market.quote_decimals), (9, 6));
+        assert_eq!(market.skew_scale, SKEW_SCALE);
+        assert_eq!(market.maintenance_margin_bps, MAINTENANCE_MARGIN_BPS);
+        assert_eq!(market.liquidation_reward_bps, LIQUIDATION_REWARD_BPS);
+        assert_eq!(market.last_funding_time, NOW);
+        assert_eq!(market.mark_price(INDEX_PRICE), Ok(INDEX_PRICE));
+
+        // The market cannot be initialized twice
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[initialize_instruction(
+                    &fixture,
+                    &payer.pubkey(),
+                    2 * SKEW_SCALE
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::MarketAlreadyInitialized)
+        );
     }
 
     #[tokio::test]
-    async fn test_operation1() {
-        // TODO: Implement test logic for operation1
+    async fn test_open_and_close_long_with_profit() {
+        let (mut context, payer, fixture) = start_with_market(MarketParams::default()).await;
+        let trader = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let banks_client = &mut context.banks_client;
+
+        // Buying one token moves the skew from 0 to 1 token, so it fills at
+        // the mark price for half a token of skew: 2000 * (1 + 0.0005)
+        let fill_price = 2_001 * USDC;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.open(&trader, Side::Long, TOKEN, 300 * USDC, fill_price - 1)],
+                &[&trader.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::PriceLimitExceeded)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[fixture.open(&trader, Side::Long, TOKEN, 300 * USDC, fill_price)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+
+        let open_fee = 2_001_000;
+        let position = get_position(banks_client, &trader.position).await;
+        assert_eq!(position.side, Side::Long);
+        assert_eq!(position.size, TOKEN);
+        assert_eq!(position.entry_notional, fill_price);
+        assert_eq!(position.collateral, 300 * USDC - open_fee);
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(market.long_open_interest, TOKEN);
+        assert_eq!(market.fee_pool, open_fee);
+        assert_eq!(market.mark_price(INDEX_PRICE), Ok(2_002 * USDC));
+
+        // The price rises 10%. Selling moves the skew back from 1 to 0.
+        set_fake_price(&mut context, &fixture.price_account, 220_000_000_000, NOW);
+        let banks_client = &mut context.banks_client;
+        let exit_price = 2_201_100_000;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.close(&trader, TOKEN, exit_price)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+
+        let close_fee = 2_201_100;
+        let payout = 300 * USDC - open_fee + (exit_price - fill_price) - close_fee;
+        assert_eq!(
+            token_balance(banks_client, &trader.collateral).await,
+            USER_FUNDS - 300 * USDC + payout
+        );
+        let position = get_position(banks_client, &trader.position).await;
+        assert_eq!(
+            (position.size, position.entry_notional, position.collateral),
+            (0, 0, 0)
+        );
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(market.long_open_interest, 0);
+        assert_eq!(market.fee_pool, open_fee + close_fee);
+
+        // A closed position cannot be closed again, but can be reopened
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.close(&trader, TOKEN, 0)],
+                &[&trader.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::PositionNotOpen)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[fixture.open(&trader, Side::Short, TOKEN, 300 * USDC, 0)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            get_position(banks_client, &trader.position).await.side,
+            Side::Short
+        );
     }
 
     #[tokio::test]
-    async fn test_operation2() {
-        // TODO: Implement test logic for operation2
+    async fn test_short_position_and_margin() {
+        let (mut context, payer, fixture) = start_with_market(MarketParams::default()).await;
+        let trader = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let banks_client = &mut context.banks_client;
+
+        // Two tokens need 400 of initial margin plus the fee
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.open(&trader, Side::Short, 2 * TOKEN, 400 * USDC, 0)],
+                &[&trader.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InsufficientMargin)
+        );
+
+        // Selling two tokens moves the skew to -2 and fills at 2000 * (1 - 0.001)
+        let fill_price = 1_998 * USDC;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.open(&trader, Side::Short, 2 * TOKEN, 500 * USDC, fill_price + 1)],
+                &[&trader.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::PriceLimitExceeded)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[fixture.open(&trader, Side::Short, 2 * TOKEN, 500 * USDC, fill_price)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+
+        let open_fee = 3_996_000;
+        let position = get_position(banks_client, &trader.position).await;
+        assert_eq!(position.entry_notional, 2 * fill_price);
+        assert_eq!(position.collateral, 500 * USDC - open_fee);
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(market.short_open_interest, 2 * TOKEN);
+        assert_eq!(market.skew(), -2 * TOKEN as i128);
+
+        // Another position cannot be opened in the same account
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.open(&trader, Side::Long, TOKEN, 500 * USDC, u64::MAX)],
+                &[&trader.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::PositionAlreadyOpen)
+        );
+
+        // The price falls 5%. Buying back moves the skew from -2 to 0.
+        set_fake_price(&mut context, &fixture.price_account, 190_000_000_000, NOW);
+        let banks_client = &mut context.banks_client;
+        let exit_price = 1_898_100_000;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.close(&trader, 2 * TOKEN, exit_price)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+
+        let close_fee = 3_796_200;
+        let payout = 500 * USDC - open_fee + 2 * (fill_price - exit_price) - close_fee;
+        assert_eq!(
+            token_balance(banks_client, &trader.collateral).await,
+            USER_FUNDS - 500 * USDC + payout
+        );
+    }
+
+    #[tokio::test]
+    async fn test_increase_and_partially_close_position() {
+        let (mut context, payer, fixture) = start_with_market(MarketParams::default()).await;
+        let trader = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let other = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let banks_client = &mut context.banks_client;
+
+        process(
+            banks_client,
+            &payer,
+            &[fixture.open(&trader, Side::Long, TOKEN, 300 * USDC, u64::MAX)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+
+        // Three more tokens at the skew midpoint of 2.5 tokens would need
+        // more margin than the position has
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.increase(&trader, 3 * TOKEN, 0, u64::MAX)],
+                &[&trader.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InsufficientMargin)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[fixture.increase(&trader, 3 * TOKEN, 600 * USDC, 2_005 * USDC)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+
+        let position = get_position(banks_client, &trader.position).await;
+        assert_eq!(position.size, 4 * TOKEN);
+        assert_eq!(position.entry_notional, 2_001 * USDC + 3 * 2_005 * USDC);
+        let fees = 2_001_000 + 6_015_000;
+        assert_eq!(position.collateral, 900 * USDC - fees);
+
+        // Margin can be topped up without trading
+        process(
+            banks_client,
+            &payer,
+            &[fixture.increase(&trader, 0, 100 * USDC, 0)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            get_position(banks_client, &trader.position)
+                .await
+                .collateral,
+            1_000 * USDC - fees
+        );
+
+        // Only the owner can touch the position
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[open_position(
+                    &fixture.program_id,
+                    &other.keypair.pubkey(),
+                    &fixture.market,
+                    &trader.position,
+                    &fixture.price_account,
+                    &other.collateral,
+                    &fixture.collateral_vault,
+                    Side::Long,
+                    TOKEN,
+                    300 * USDC,
+                    u64::MAX,
+                )],
+                &[&other.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidPosition)
+        );
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[close_position(
+                    &fixture.program_id,
+                    &other.keypair.pubkey(),
+                    &fixture.market,
+                    &trader.position,
+                    &fixture.market_authority,
+                    &fixture.price_account,
+                    &fixture.collateral_vault,
+                    &other.collateral,
+                    TOKEN,
+                    0,
+                )],
+                &[&other.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidPosition)
+        );
+
+        // Closing more than the size is rejected
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.close(&trader, 5 * TOKEN, 0)],
+                &[&trader.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidAmount)
+        );
+
+        // Selling half moves the skew from 4 to 2 tokens, at 2000 * 1.003.
+        // The PnL realized against the average entry stays in the collateral.
+        let balance_before = token_balance(banks_client, &trader.collateral).await;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.close(&trader, 2 * TOKEN, 2_006 * USDC)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+
+        let exit_notional = 2 * 2_006 * USDC;
+        let entry_notional = (2_001 * USDC + 3 * 2_005 * USDC) / 2;
+        let close_fee = 4_012_000;
+        let position = get_position(banks_client, &trader.position).await;
+        assert_eq!(position.size, 2 * TOKEN);
+        assert_eq!(position.entry_notional, entry_notional);
+        assert_eq!(
+            position.collateral,
+            1_000 * USDC - fees + (exit_notional - entry_notional) - close_fee
+        );
+        assert_eq!(
+            token_balance(banks_client, &trader.collateral).await,
+            balance_before
+        );
+        assert_eq!(
+            get_market(banks_client, &fixture.market)
+                .await
+                .long_open_interest,
+            2 * TOKEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_funding_accrues_and_settles_lazily() {
+        let (mut context, payer, fixture) = start_with_market(MarketParams::default()).await;
+        let long = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let short = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let banks_client = &mut context.banks_client;
+
+        // Longs of 10 tokens and shorts of 2 leave a skew of 8 tokens
+        process(
+            banks_client,
+            &payer,
+            &[fixture.open(&long, Side::Long, 10 * TOKEN, 3_000 * USDC, u64::MAX)],
+            &[&long.keypair],
+        )
+        .await
+        .unwrap();
+        process(
+            banks_client,
+            &payer,
+            &[fixture.open(&short, Side::Short, 2 * TOKEN, 1_000 * USDC, 0)],
+            &[&short.keypair],
+        )
+        .await
+        .unwrap();
+        let long_collateral = get_position(banks_client, &long.position).await.collateral;
+        let short_collateral = get_position(banks_client, &short.position).await.collateral;
+
+        // Nothing accrues without time passing
+        process(banks_client, &payer, &[fixture.update_funding()], &[])
+            .await
+            .unwrap();
+        assert_eq!(
+            get_market(banks_client, &fixture.market)
+                .await
+                .cumulative_funding,
+            0
+        );
+
+        // A day later the mark is 0.8% above the index, so each token held
+        // long pays 16 collateral tokens of funding
+        let later = NOW + 86_400;
+        refresh_blockhash(&mut context).await;
+        set_timestamp(&mut context, later).await;
+        set_fake_price(&mut context, &fixture.price_account, ORACLE_PRICE, later);
+        let banks_client = &mut context.banks_client;
+        process(banks_client, &payer, &[fixture.update_funding()], &[])
+            .await
+            .unwrap();
+
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(market.mark_price(INDEX_PRICE), Ok(2_016 * USDC));
+        assert_eq!(
+            market.cumulative_funding,
+            16 * USDC as i128 * FUNDING_PRECISION
+        );
+        assert_eq!(market.funding_rate, 8_000_000);
+        assert_eq!(market.last_funding_time, later);
+
+        // Positions are untouched until they trade
+        let position = get_position(banks_client, &long.position).await;
+        assert_eq!(position.collateral, long_collateral);
+        assert_eq!(position.funding_payment(&market), Ok(160 * USDC as i128));
+
+        // Topping up margin settles the funding the long owes
+        process(
+            banks_client,
+            &payer,
+            &[fixture.increase(&long, 0, USDC, 0)],
+            &[&long.keypair],
+        )
+        .await
+        .unwrap();
+        let position = get_position(banks_client, &long.position).await;
+        assert_eq!(position.collateral, long_collateral - 160 * USDC + USDC);
+        assert_eq!(position.last_cumulative_funding, market.cumulative_funding);
+
+        // Closing the short settles the funding it receives. It buys back
+        // over the same skew it sold into, so its trading PnL is zero.
+        let close_fee = 4_036_000;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.close(&short, 2 * TOKEN, u64::MAX)],
+            &[&short.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &short.collateral).await,
+            USER_FUNDS - 1_000 * USDC + short_collateral + 32 * USDC - close_fee
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_stale_and_wrong_prices() {
+        let (mut context, payer, fixture) = start_with_market(MarketParams::default()).await;
+        let trader = create_trader(&mut context.banks_client, &payer, &fixture).await;
+
+        set_timestamp(&mut context, NOW + 61).await;
+        let banks_client = &mut context.banks_client;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.open(&trader, Side::Long, TOKEN, 300 * USDC, u64::MAX)],
+                &[&trader.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::StalePrice)
+        );
+        assert_eq!(
+            process(banks_client, &payer, &[fixture.update_funding()], &[])
+                .await
+                .unwrap_err(),
+            custom_error(TemplateError::StalePrice)
+        );
+
+        let wrong_price_account = Pubkey::new_unique();
+        set_fake_price(&mut context, &wrong_price_account, ORACLE_PRICE, NOW + 61);
+        let banks_client = &mut context.banks_client;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[update_funding(
+                    &fixture.program_id,
+                    &fixture.market,
+                    &wrong_price_account
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidPriceAccount)
+        );
     }
 }
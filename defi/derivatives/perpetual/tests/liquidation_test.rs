@@ -0,0 +1,372 @@
+//! Integration tests for liquidations in perpetual-futures
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::common::{
+            create_token_account, create_trader, custom_error, get_market, get_position, mint_to,
+            process, refresh_blockhash, set_fake_price, start_with_market,
+            start_with_market_account, token_balance, MarketFixture, MarketParams,
+            INITIAL_MARGIN_BPS, LIQUIDATION_REWARD_BPS, MAINTENANCE_MARGIN_BPS,
+            MAX_FUNDING_RATE_BPS, NOW, SKEW_SCALE, TAKER_FEE_BPS, TOKEN, USDC, VAULT_LIQUIDITY,
+        },
+        perpetual_futures::{
+            errors::TemplateError,
+            instructions::initialize_market,
+            state::{PricingMode, Side},
+        },
+        solana_program::{instruction::Instruction, pubkey::Pubkey},
+        solana_program_test::{BanksClient, ProgramTestContext},
+        solana_sdk::signature::{Keypair, Signer},
+    };
+
+    fn initialize_instruction(
+        fixture: &MarketFixture,
+        admin: &Pubkey,
+        maintenance_margin_bps: u64,
+        liquidation_reward_bps: u64,
+    ) -> Instruction {
+        fixture.initialize(
+            admin,
+            MarketParams {
+                maintenance_margin_bps,
+                liquidation_reward_bps,
+                ..MarketParams::default()
+            },
+        )
+    }
+
+    /// Starts a test validator with an initialized market whose vault holds
+    /// `VAULT_LIQUIDITY` and whose insurance fund holds `insurance`
+    async fn start_with_insurance(insurance: u64) -> (ProgramTestContext, Keypair, MarketFixture) {
+        let (mut context, payer, fixture) = start_with_market(MarketParams::default()).await;
+        if insurance > 0 {
+            mint_to(
+                &mut context.banks_client,
+                &payer,
+                &fixture.collateral_mint,
+                &fixture.insurance_vault,
+                insurance,
+            )
+            .await;
+        }
+        (context, payer, fixture)
+    }
+
+    /// Creates a token account of the collateral token for the payer, who
+    /// liquidates positions as the keeper
+    async fn create_keeper_account(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &MarketFixture,
+    ) -> Pubkey {
+        create_token_account(
+            banks_client,
+            payer,
+            &fixture.collateral_mint,
+            &payer.pubkey(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_initialize_rejects_invalid_liquidation_parameters() {
+        let (mut context, payer, fixture) = start_with_market_account().await;
+        let banks_client = &mut context.banks_client;
+
+        // The maintenance margin cannot exceed the initial margin, and the
+        // reward must stay below the maintenance margin
+        for (maintenance_margin_bps, liquidation_reward_bps) in
+            [(0, 0), (INITIAL_MARGIN_BPS + 1, 100), (500, 500)]
+        {
+            assert_eq!(
+                process(
+                    banks_client,
+                    &payer,
+                    &[initialize_instruction(
+                        &fixture,
+                        &payer.pubkey(),
+                        maintenance_margin_bps,
+                        liquidation_reward_bps,
+                    )],
+                    &[],
+                )
+                .await
+                .unwrap_err(),
+                custom_error(TemplateError::InvalidMarketParameters)
+            );
+        }
+
+        // The insurance fund needs its own vault
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[initialize_market(
+                    &fixture.program_id,
+                    &payer.pubkey(),
+                    &fixture.market,
+                    &fixture.market_authority,
+                    &fixture.collateral_mint,
+                    &fixture.collateral_vault,
+                    &fixture.collateral_vault,
+                    &fixture.price_account,
+                    9,
+                    INITIAL_MARGIN_BPS,
+                    MAINTENANCE_MARGIN_BPS,
+                    LIQUIDATION_REWARD_BPS,
+                    TAKER_FEE_BPS,
+                    SKEW_SCALE,
+                    MAX_FUNDING_RATE_BPS,
+                    PricingMode::Skew,
+                    0,
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidVault)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_partial_liquidation_restores_maintenance_margin() {
+        let (mut context, payer, fixture) = start_with_insurance(0).await;
+        let trader = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let keeper_account =
+            create_keeper_account(&mut context.banks_client, &payer, &fixture).await;
+
+        // Ten tokens long fill at 2010 for a notional of 20100, and the fee
+        // of 20.1 comes out of the collateral
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.open(&trader, Side::Long, 10 * TOKEN, 2_500 * USDC, u64::MAX)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+
+        // A healthy position cannot be liquidated
+        assert_eq!(
+            process(
+                &mut context.banks_client,
+                &payer,
+                &[fixture.liquidate(&payer, &keeper_account, &trader.position)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::PositionHealthy)
+        );
+
+        // At 1850 the equity of 2479.9 - 1600 = 879.9 is below the
+        // maintenance margin of 925
+        refresh_blockhash(&mut context).await;
+        let shocked_price = 1_850 * USDC;
+        set_fake_price(&mut context, &fixture.price_account, 185_000_000_000, NOW);
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.liquidate(&payer, &keeper_account, &trader.position)],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        // Closing a fraction (925 - 879.9) / (925 - 185) of the position
+        // restores the maintenance margin, and the keeper earns 1% of the
+        // notional closed
+        let liquidated = 609_459_460;
+        let reward = 11_275_000;
+        let position = get_position(banks_client, &trader.position).await;
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(position.size, 10 * TOKEN - liquidated);
+        assert_eq!(market.long_open_interest, position.size);
+        assert_eq!(token_balance(banks_client, &keeper_account).await, reward);
+        let notional = market.notional(position.size, shocked_price).unwrap();
+        assert_eq!(
+            position.equity(&market, shocked_price).unwrap(),
+            market.maintenance_margin(notional) as i128
+        );
+        assert_eq!(position.collateral, 2_371_111_487);
+        assert_eq!(market.socialized_loss, 0);
+
+        // The position is healthy again
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.liquidate(&payer, &keeper_account, &trader.position)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::PositionHealthy)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insurance_fund_covers_bad_debt() {
+        let (mut context, payer, fixture) = start_with_insurance(1_000 * USDC).await;
+        let trader = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let keeper_account =
+            create_keeper_account(&mut context.banks_client, &payer, &fixture).await;
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.open(&trader, Side::Long, 10 * TOKEN, 2_500 * USDC, u64::MAX)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+
+        // At 1700 the loss of 3100 exceeds the collateral of 2479.9, so the
+        // position closes fully and the reward of 170 adds to the bad debt
+        set_fake_price(&mut context, &fixture.price_account, 170_000_000_000, NOW);
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.liquidate(&payer, &keeper_account, &trader.position)],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let bad_debt = 790_100_000;
+        let position = get_position(banks_client, &trader.position).await;
+        assert!(!position.is_open());
+        assert_eq!(position.collateral, 0);
+        assert_eq!(
+            token_balance(banks_client, &keeper_account).await,
+            170 * USDC
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.insurance_vault).await,
+            1_000 * USDC - bad_debt
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.collateral_vault).await,
+            VAULT_LIQUIDITY + 2_500 * USDC + bad_debt - 170 * USDC
+        );
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(market.long_open_interest, 0);
+        assert_eq!(market.socialized_loss, 0);
+
+        // Nothing is left to liquidate or withdraw
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.liquidate(&payer, &keeper_account, &trader.position)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::PositionNotOpen)
+        );
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.close(&trader, 0, 0)],
+                &[&trader.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::PositionNotOpen)
+        );
+
+        // The owner can open a new position in the same account
+        process(
+            banks_client,
+            &payer,
+            &[fixture.open(&trader, Side::Short, TOKEN, 500 * USDC, 0)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bad_debt_socialized_when_insurance_runs_dry() {
+        let (mut context, payer, fixture) = start_with_insurance(100 * USDC).await;
+        let long = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let short = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let keeper_account =
+            create_keeper_account(&mut context.banks_client, &payer, &fixture).await;
+
+        // Two tokens short fill at 1998, then ten tokens long at 2006
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.open(&short, Side::Short, 2 * TOKEN, 1_000 * USDC, 0)],
+            &[&short.keypair],
+        )
+        .await
+        .unwrap();
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.open(&long, Side::Long, 10 * TOKEN, 2_500 * USDC, u64::MAX)],
+            &[&long.keypair],
+        )
+        .await
+        .unwrap();
+        let short_collateral = get_position(&mut context.banks_client, &short.position)
+            .await
+            .collateral;
+        assert_eq!(short_collateral, 996_004_000);
+
+        // At 1700 the long leaves 2479.94 - 3060 - 170 = -750.06 of bad debt,
+        // of which the insurance fund covers only 100
+        set_fake_price(&mut context, &fixture.price_account, 170_000_000_000, NOW);
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.liquidate(&payer, &keeper_account, &long.position)],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let shortfall = 650_060_000;
+        assert_eq!(
+            token_balance(banks_client, &fixture.insurance_vault).await,
+            0
+        );
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(market.long_open_interest, 0);
+        assert_eq!(market.socialized_loss, shortfall);
+        assert!(market.cumulative_short_loss > 0);
+        assert_eq!(market.cumulative_long_loss, 0);
+
+        // The short pays the shortfall when it next settles. Buying back two
+        // tokens fills at 1700 * (1 - 0.001) = 1698.3, realizing 599.4 less
+        // a fee of 3.3966
+        let balance_before = token_balance(banks_client, &short.collateral).await;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.close(&short, 2 * TOKEN, u64::MAX)],
+            &[&short.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &short.collateral).await - balance_before,
+            short_collateral - shortfall + 599_400_000 - 3_396_600
+        );
+        assert_eq!(
+            get_market(banks_client, &fixture.market)
+                .await
+                .short_open_interest,
+            0
+        );
+    }
+}
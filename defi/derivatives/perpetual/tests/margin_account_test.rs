@@ -0,0 +1,883 @@
+//! Integration tests for cross-margin accounts in perpetual-futures
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::common::{
+            create_market_account, create_mint, create_program_account, create_token_account,
+            create_trader, custom_error, get_market, get_position, initialize_and_fund_market,
+            mint_to, process, refresh_blockhash, set_fake_price, start_with_fake_oracle,
+            token_balance, MarketFixture, MarketParams, NOW, TOKEN, USDC, USER_FUNDS,
+            VAULT_LIQUIDITY,
+        },
+        borsh::BorshDeserialize,
+        perpetual_futures::{
+            errors::TemplateError,
+            instructions::{
+                close_position, deposit_margin, increase_margin_position,
+                initialize_margin_account, liquidate_margin_account, reduce_margin_position,
+                withdraw_margin, HealthAccounts, LiquidationAccounts, MarginPositionAccounts,
+            },
+            state::{MarginAccount, Position, Side},
+            utils::find_margin_authority,
+        },
+        solana_program::{instruction::Instruction, pubkey::Pubkey},
+        solana_program_test::{BanksClient, ProgramTestContext},
+        solana_sdk::signature::{Keypair, Signer},
+    };
+
+    /// Two markets sharing a collateral mint and a funded owner with an
+    /// initialized margin account
+    struct MarginFixture {
+        program_id: Pubkey,
+        collateral_mint: Pubkey,
+        markets: Vec<MarketFixture>,
+        owner: Keypair,
+        owner_collateral: Pubkey,
+        margin_account: Pubkey,
+        margin_authority: Pubkey,
+        margin_vault: Pubkey,
+    }
+
+    impl MarginFixture {
+        fn position_accounts(&self, market: usize, position: &Pubkey) -> MarginPositionAccounts {
+            let market = &self.markets[market];
+            MarginPositionAccounts {
+                margin_account: self.margin_account,
+                margin_authority: self.margin_authority,
+                market: market.market,
+                market_authority: market.market_authority,
+                position: *position,
+                price_account: market.price_account,
+                margin_vault: self.margin_vault,
+                collateral_vault: market.collateral_vault,
+            }
+        }
+
+        /// Health accounts of positions given with the index of their market
+        fn health(&self, positions: &[(usize, Pubkey)]) -> Vec<HealthAccounts> {
+            positions
+                .iter()
+                .map(|(market, position)| HealthAccounts {
+                    position: *position,
+                    market: self.markets[*market].market,
+                    price_account: self.markets[*market].price_account,
+                })
+                .collect()
+        }
+
+        fn deposit(&self, amount: u64) -> Instruction {
+            deposit_margin(
+                &self.program_id,
+                &self.owner.pubkey(),
+                &self.margin_account,
+                &self.owner_collateral,
+                &self.margin_vault,
+                amount,
+            )
+        }
+
+        fn withdraw(&self, health: &[(usize, Pubkey)], amount: u64) -> Instruction {
+            withdraw_margin(
+                &self.program_id,
+                &self.owner.pubkey(),
+                &self.margin_account,
+                &self.margin_authority,
+                &self.margin_vault,
+                &self.owner_collateral,
+                &self.health(health),
+                amount,
+            )
+        }
+
+        fn increase(
+            &self,
+            market: usize,
+            position: &Pubkey,
+            health: &[(usize, Pubkey)],
+            side: Side,
+            size: u64,
+            price_limit: u64,
+        ) -> Instruction {
+            increase_margin_position(
+                &self.program_id,
+                &self.owner.pubkey(),
+                &self.position_accounts(market, position),
+                &self.health(health),
+                side,
+                size,
+                price_limit,
+            )
+        }
+
+        fn reduce(
+            &self,
+            market: usize,
+            position: &Pubkey,
+            size: u64,
+            price_limit: u64,
+        ) -> Instruction {
+            reduce_margin_position(
+                &self.program_id,
+                &self.owner.pubkey(),
+                &self.position_accounts(market, position),
+                size,
+                price_limit,
+            )
+        }
+
+        /// Liquidates the margin account, closing positions given with the
+        /// index of their market
+        fn liquidate(
+            &self,
+            keeper: &Keypair,
+            keeper_account: &Pubkey,
+            positions: &[(usize, Pubkey)],
+        ) -> Instruction {
+            let accounts: Vec<_> = positions
+                .iter()
+                .map(|(market, position)| {
+                    let market = &self.markets[*market];
+                    LiquidationAccounts {
+                        position: *position,
+                        market: market.market,
+                        price_account: market.price_account,
+                        market_authority: market.market_authority,
+                        collateral_vault: market.collateral_vault,
+                        insurance_vault: market.insurance_vault,
+                    }
+                })
+                .collect();
+            liquidate_margin_account(
+                &self.program_id,
+                &keeper.pubkey(),
+                &self.margin_account,
+                &self.margin_authority,
+                &self.margin_vault,
+                keeper_account,
+                &accounts,
+            )
+        }
+    }
+
+    async fn get_margin_account(
+        banks_client: &mut BanksClient,
+        margin_account: &Pubkey,
+    ) -> MarginAccount {
+        let account = banks_client
+            .get_account(*margin_account)
+            .await
+            .unwrap()
+            .unwrap();
+        MarginAccount::try_from_slice(&account.data).unwrap()
+    }
+
+    /// Starts a test validator at `NOW` reading prices with `FakeOracle`,
+    /// with two markets and an owner holding `USER_FUNDS` of the collateral
+    /// token in an empty margin account
+    async fn start_with_margin_account() -> (ProgramTestContext, Keypair, MarginFixture) {
+        let (mut context, payer, program_id) = start_with_fake_oracle().await;
+        let collateral_mint =
+            create_mint(&mut context.banks_client, &payer, &payer.pubkey(), 6).await;
+        let mut markets = Vec::new();
+        for _ in 0..2 {
+            let market =
+                create_market_account(&mut context, &payer, &program_id, &collateral_mint).await;
+            initialize_and_fund_market(
+                &mut context.banks_client,
+                &payer,
+                &market,
+                MarketParams::default(),
+            )
+            .await;
+            markets.push(market);
+        }
+
+        let banks_client = &mut context.banks_client;
+        let owner = Keypair::new();
+        let owner_collateral =
+            create_token_account(banks_client, &payer, &collateral_mint, &owner.pubkey()).await;
+        mint_to(
+            banks_client,
+            &payer,
+            &collateral_mint,
+            &owner_collateral,
+            USER_FUNDS,
+        )
+        .await;
+        let margin_account =
+            create_program_account(banks_client, &payer, &program_id, MarginAccount::get_size())
+                .await;
+        let (margin_authority, _) = find_margin_authority(&program_id, &margin_account);
+        let margin_vault =
+            create_token_account(banks_client, &payer, &collateral_mint, &margin_authority).await;
+        process(
+            banks_client,
+            &payer,
+            &[initialize_margin_account(
+                &program_id,
+                &owner.pubkey(),
+                &margin_account,
+                &margin_authority,
+                &collateral_mint,
+                &margin_vault,
+            )],
+            &[&owner],
+        )
+        .await
+        .unwrap();
+
+        let fixture = MarginFixture {
+            program_id,
+            collateral_mint,
+            markets,
+            owner,
+            owner_collateral,
+            margin_account,
+            margin_authority,
+            margin_vault,
+        };
+        (context, payer, fixture)
+    }
+
+    async fn create_position(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &MarginFixture,
+    ) -> Pubkey {
+        create_program_account(
+            banks_client,
+            payer,
+            &fixture.program_id,
+            Position::get_size(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_deposit_and_withdraw_margin() {
+        let (mut context, payer, fixture) = start_with_margin_account().await;
+        let banks_client = &mut context.banks_client;
+
+        let margin_account = get_margin_account(banks_client, &fixture.margin_account).await;
+        assert!(margin_account.is_initialized);
+        assert_eq!(margin_account.owner, fixture.owner.pubkey());
+        assert_eq!(margin_account.collateral_mint, fixture.collateral_mint);
+        assert_eq!(margin_account.vault, fixture.margin_vault);
+        assert_eq!(margin_account.open_positions().count(), 0);
+
+        // The margin account cannot be initialized twice
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[initialize_margin_account(
+                    &fixture.program_id,
+                    &payer.pubkey(),
+                    &fixture.margin_account,
+                    &fixture.margin_authority,
+                    &fixture.collateral_mint,
+                    &fixture.margin_vault,
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::MarginAccountAlreadyInitialized)
+        );
+
+        process(
+            banks_client,
+            &payer,
+            &[fixture.deposit(1_000 * USDC)],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+        process(
+            banks_client,
+            &payer,
+            &[fixture.withdraw(&[], 400 * USDC)],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            get_margin_account(banks_client, &fixture.margin_account)
+                .await
+                .collateral,
+            600 * USDC
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.margin_vault).await,
+            600 * USDC
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.owner_collateral).await,
+            USER_FUNDS - 600 * USDC
+        );
+
+        // Only the collateral held can be withdrawn, and only by the owner
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.withdraw(&[], 600 * USDC + 1)],
+                &[&fixture.owner],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InsufficientMargin)
+        );
+        let other = Keypair::new();
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[withdraw_margin(
+                    &fixture.program_id,
+                    &other.pubkey(),
+                    &fixture.margin_account,
+                    &fixture.margin_authority,
+                    &fixture.margin_vault,
+                    &fixture.owner_collateral,
+                    &[],
+                    600 * USDC,
+                )],
+                &[&other],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidMarginAccount)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unrealized_pnl_backs_positions_in_other_markets() {
+        let (mut context, payer, fixture) = start_with_margin_account().await;
+        let position_a = create_position(&mut context.banks_client, &payer, &fixture).await;
+        let position_b = create_position(&mut context.banks_client, &payer, &fixture).await;
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.deposit(2_500 * USDC)],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+
+        // Ten tokens long on the first market fill at 2010, and the fee of
+        // 20.1 comes out of the margin account
+        process(
+            banks_client,
+            &payer,
+            &[fixture.increase(
+                0,
+                &position_a,
+                &[(0, position_a)],
+                Side::Long,
+                10 * TOKEN,
+                u64::MAX,
+            )],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+        let position = get_position(banks_client, &position_a).await;
+        assert_eq!(position.margin_account, fixture.margin_account);
+        assert_eq!(position.collateral, 0);
+        assert_eq!(
+            get_margin_account(banks_client, &fixture.margin_account)
+                .await
+                .collateral,
+            2_479_900_000
+        );
+
+        // Every position must be covered by the health check
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.increase(
+                    1,
+                    &position_b,
+                    &[(0, position_a)],
+                    Side::Short,
+                    5 * TOKEN,
+                    0
+                )],
+                &[&fixture.owner],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidHealthAccounts)
+        );
+
+        // Five tokens short on the second market would need 3000 of initial
+        // margin against an equity of 2469.925 - 100 - 25
+        let health = [(0, position_a), (1, position_b)];
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.increase(1, &position_b, &health, Side::Short, 5 * TOKEN, 0)],
+                &[&fixture.owner],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InsufficientMargin)
+        );
+
+        // Once the long is 1900 in profit at 2200 it backs the short too
+        refresh_blockhash(&mut context).await;
+        set_fake_price(
+            &mut context,
+            &fixture.markets[0].price_account,
+            220_000_000_000,
+            NOW,
+        );
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.increase(1, &position_b, &health, Side::Short, 5 * TOKEN, 0)],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+        let margin_account = get_margin_account(banks_client, &fixture.margin_account).await;
+        assert_eq!(margin_account.collateral, 2_469_925_000);
+        assert_eq!(margin_account.open_positions().count(), 2);
+
+        // The equity of 4344.925 covers 3200 of initial margin, which leaves
+        // 1144.925 free to withdraw
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.withdraw(&health, 1_144_925_001)],
+                &[&fixture.owner],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InsufficientMargin)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[fixture.withdraw(&health, 1_144_925_000)],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            get_margin_account(banks_client, &fixture.margin_account)
+                .await
+                .collateral,
+            1_325 * USDC
+        );
+
+        // A position cannot stand in for another one
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.withdraw(&[(0, position_a), (0, position_a)], 1)],
+                &[&fixture.owner],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidHealthAccounts)
+        );
+
+        // Cross-margin positions cannot be closed as isolated ones
+        let market = &fixture.markets[0];
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[close_position(
+                    &fixture.program_id,
+                    &fixture.owner.pubkey(),
+                    &market.market,
+                    &position_a,
+                    &market.market_authority,
+                    &market.price_account,
+                    &market.collateral_vault,
+                    &fixture.owner_collateral,
+                    10 * TOKEN,
+                    0,
+                )],
+                &[&fixture.owner],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidPosition)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reduce_settles_pnl_with_the_market() {
+        let (mut context, payer, fixture) = start_with_margin_account().await;
+        let position_a = create_position(&mut context.banks_client, &payer, &fixture).await;
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.deposit(2_500 * USDC),
+                fixture.increase(
+                    0,
+                    &position_a,
+                    &[(0, position_a)],
+                    Side::Long,
+                    10 * TOKEN,
+                    u64::MAX,
+                ),
+            ],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+
+        // Selling the ten tokens back at 2200 * (1 + 0.005) = 2211 realizes
+        // 2010 less a fee of 22.11, paid from the market's vault
+        set_fake_price(
+            &mut context,
+            &fixture.markets[0].price_account,
+            220_000_000_000,
+            NOW,
+        );
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.reduce(0, &position_a, 10 * TOKEN, 0)],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+        let collateral = 2_479_900_000 + 1_987_890_000;
+        let margin_account = get_margin_account(banks_client, &fixture.margin_account).await;
+        assert_eq!(margin_account.collateral, collateral);
+        assert_eq!(margin_account.open_positions().count(), 0);
+        assert_eq!(
+            token_balance(banks_client, &fixture.margin_vault).await,
+            collateral
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.markets[0].collateral_vault).await,
+            VAULT_LIQUIDITY + 20_100_000 - 1_987_890_000
+        );
+        assert!(!get_position(banks_client, &position_a).await.is_open());
+        let market = get_market(banks_client, &fixture.markets[0].market).await;
+        assert_eq!(market.long_open_interest, 0);
+        assert_eq!(market.fee_pool, 20_100_000 + 22_110_000);
+
+        // With no positions left everything can be withdrawn
+        process(
+            banks_client,
+            &payer,
+            &[fixture.withdraw(&[], collateral)],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+        assert_eq!(token_balance(banks_client, &fixture.margin_vault).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_unpaid_loss_only_written_off_on_last_position() {
+        let (mut context, payer, fixture) = start_with_margin_account().await;
+        let position_a = create_position(&mut context.banks_client, &payer, &fixture).await;
+        let position_b = create_position(&mut context.banks_client, &payer, &fixture).await;
+        let banks_client = &mut context.banks_client;
+        let health = [(0, position_a), (1, position_b)];
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.deposit(2_500 * USDC),
+                fixture.increase(
+                    0,
+                    &position_a,
+                    &[(0, position_a)],
+                    Side::Long,
+                    10 * TOKEN,
+                    u64::MAX,
+                ),
+                fixture.increase(1, &position_b, &health, Side::Short, TOKEN, 0),
+            ],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            get_margin_account(banks_client, &fixture.margin_account)
+                .await
+                .collateral,
+            2_477_901_000
+        );
+
+        // At 1700 selling the long realizes a loss of 3015 plus a fee of
+        // 17.085, more than the margin account holds
+        set_fake_price(
+            &mut context,
+            &fixture.markets[0].price_account,
+            170_000_000_000,
+            NOW,
+        );
+        let banks_client = &mut context.banks_client;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.reduce(0, &position_a, 10 * TOKEN, 0)],
+                &[&fixture.owner],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InsufficientMargin)
+        );
+
+        // Buying back the short first pays its fee of 1.999, and closing the
+        // long as the last position writes off what the account cannot pay
+        process(
+            banks_client,
+            &payer,
+            &[fixture.reduce(1, &position_b, TOKEN, u64::MAX)],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+        refresh_blockhash(&mut context).await;
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.reduce(0, &position_a, 10 * TOKEN, 0)],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+        let margin_account = get_margin_account(banks_client, &fixture.margin_account).await;
+        assert_eq!(margin_account.collateral, 0);
+        assert_eq!(margin_account.open_positions().count(), 0);
+        assert_eq!(token_balance(banks_client, &fixture.margin_vault).await, 0);
+        assert_eq!(
+            token_balance(banks_client, &fixture.markets[0].collateral_vault).await,
+            VAULT_LIQUIDITY + 20_100_000 + 2_475_902_000
+        );
+    }
+
+    #[tokio::test]
+    async fn test_liquidate_margin_account_closes_every_position() {
+        let (mut context, payer, fixture) = start_with_margin_account().await;
+        let position_a = create_position(&mut context.banks_client, &payer, &fixture).await;
+        let position_b = create_position(&mut context.banks_client, &payer, &fixture).await;
+        let banks_client = &mut context.banks_client;
+        let keeper_account = create_token_account(
+            banks_client,
+            &payer,
+            &fixture.collateral_mint,
+            &payer.pubkey(),
+        )
+        .await;
+        let health = [(0, position_a), (1, position_b)];
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.deposit(2_500 * USDC),
+                fixture.increase(
+                    0,
+                    &position_a,
+                    &[(0, position_a)],
+                    Side::Long,
+                    10 * TOKEN,
+                    u64::MAX,
+                ),
+                fixture.increase(1, &position_b, &health, Side::Short, TOKEN, 0),
+            ],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+
+        // The equity of 2376.901 covers the maintenance margin of 1100
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.liquidate(&payer, &keeper_account, &health)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::MarginAccountHealthy)
+        );
+
+        // At 1800 the long loses 2100 and at 1900 the short gains 99, which
+        // leaves an equity of 476.901 against a maintenance margin of 995
+        set_fake_price(
+            &mut context,
+            &fixture.markets[0].price_account,
+            180_000_000_000,
+            NOW,
+        );
+        set_fake_price(
+            &mut context,
+            &fixture.markets[1].price_account,
+            190_000_000_000,
+            NOW,
+        );
+        refresh_blockhash(&mut context).await;
+        let banks_client = &mut context.banks_client;
+
+        // Every position must be closed
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.liquidate(&payer, &keeper_account, &[(0, position_a)])],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidHealthAccounts)
+        );
+
+        // The short is closed first and its gain of 99 less a reward of 19
+        // is paid in, then the long pays its loss of 2100 and a reward of 180
+        process(
+            banks_client,
+            &payer,
+            &[fixture.liquidate(&payer, &keeper_account, &health)],
+            &[],
+        )
+        .await
+        .unwrap();
+        let collateral = 2_477_901_000 + 80 * USDC - 2_280 * USDC;
+        let margin_account = get_margin_account(banks_client, &fixture.margin_account).await;
+        assert_eq!(margin_account.collateral, collateral);
+        assert_eq!(margin_account.open_positions().count(), 0);
+        assert_eq!(
+            token_balance(banks_client, &fixture.margin_vault).await,
+            collateral
+        );
+        assert_eq!(
+            token_balance(banks_client, &keeper_account).await,
+            199 * USDC
+        );
+        assert!(!get_position(banks_client, &position_a).await.is_open());
+        assert!(!get_position(banks_client, &position_b).await.is_open());
+        assert_eq!(
+            get_market(banks_client, &fixture.markets[0].market)
+                .await
+                .long_open_interest,
+            0
+        );
+        assert_eq!(
+            get_market(banks_client, &fixture.markets[1].market)
+                .await
+                .short_open_interest,
+            0
+        );
+
+        // What is left can be withdrawn
+        process(
+            banks_client,
+            &payer,
+            &[fixture.withdraw(&[], collateral)],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_margin_account_bad_debt_goes_to_insurance_and_other_side() {
+        let (mut context, payer, fixture) = start_with_margin_account().await;
+        let position_a = create_position(&mut context.banks_client, &payer, &fixture).await;
+        let market = &fixture.markets[0];
+        let trader = create_trader(&mut context.banks_client, &payer, market).await;
+        let banks_client = &mut context.banks_client;
+        let keeper_account = create_token_account(
+            banks_client,
+            &payer,
+            &fixture.collateral_mint,
+            &payer.pubkey(),
+        )
+        .await;
+        mint_to(
+            banks_client,
+            &payer,
+            &fixture.collateral_mint,
+            &market.insurance_vault,
+            500 * USDC,
+        )
+        .await;
+
+        // Ten tokens long from the margin account fill at 2010, and an
+        // isolated short of ten tokens takes the skew back to zero
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.deposit(2_500 * USDC),
+                fixture.increase(
+                    0,
+                    &position_a,
+                    &[(0, position_a)],
+                    Side::Long,
+                    10 * TOKEN,
+                    u64::MAX,
+                ),
+            ],
+            &[&fixture.owner],
+        )
+        .await
+        .unwrap();
+        process(
+            banks_client,
+            &payer,
+            &[market.open(&trader, Side::Short, 10 * TOKEN, 2_500 * USDC, 0)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+
+        // At 1700 the long loses 3100 and the reward is 170, which leaves
+        // bad debt of 790.1 once the margin account's 2479.9 is spent. The
+        // insurance fund pays 500 and the short bears the remaining 290.1
+        set_fake_price(&mut context, &market.price_account, 170_000_000_000, NOW);
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.liquidate(&payer, &keeper_account, &[(0, position_a)])],
+            &[],
+        )
+        .await
+        .unwrap();
+        let margin_account = get_margin_account(banks_client, &fixture.margin_account).await;
+        assert_eq!(margin_account.collateral, 0);
+        assert_eq!(margin_account.open_positions().count(), 0);
+        assert_eq!(token_balance(banks_client, &fixture.margin_vault).await, 0);
+        assert_eq!(
+            token_balance(banks_client, &market.insurance_vault).await,
+            0
+        );
+        assert_eq!(
+            token_balance(banks_client, &keeper_account).await,
+            170 * USDC
+        );
+        let market = get_market(banks_client, &market.market).await;
+        assert_eq!(market.long_open_interest, 0);
+        assert_eq!(market.socialized_loss, 290_100_000);
+        assert!(market.cumulative_short_loss > 0);
+    }
+}
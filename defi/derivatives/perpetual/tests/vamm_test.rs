@@ -0,0 +1,358 @@
+//! Integration tests for vAMM pricing in perpetual-futures
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::common::{
+            create_token_account, create_trader, custom_error, get_market, get_position, process,
+            refresh_blockhash, set_fake_price, start_with_market, start_with_market_account,
+            token_balance, MarketFixture, MarketParams, NOW, ORACLE_PRICE, TOKEN, USDC, USER_FUNDS,
+        },
+        perpetual_futures::{
+            errors::TemplateError,
+            instructions::repeg_amm,
+            state::{PricingMode, Side},
+        },
+        solana_program::{instruction::Instruction, pubkey::Pubkey},
+        solana_program_test::ProgramTestContext,
+        solana_sdk::signature::{Keypair, Signer},
+    };
+
+    /// Virtual base reserve at zero skew, which at 2000 pairs with a
+    /// virtual quote reserve of two million collateral tokens
+    const AMM_BASE_RESERVE: u64 = 1_000 * TOKEN;
+
+    impl MarketFixture {
+        fn repeg(&self, admin: &Keypair) -> Instruction {
+            repeg_amm(
+                &self.program_id,
+                &admin.pubkey(),
+                &self.market,
+                &self.price_account,
+            )
+        }
+    }
+
+    fn initialize_instruction(
+        fixture: &MarketFixture,
+        admin: &Pubkey,
+        pricing_mode: PricingMode,
+        amm_base_reserve: u64,
+    ) -> Instruction {
+        fixture.initialize(
+            admin,
+            MarketParams {
+                pricing_mode,
+                amm_base_reserve,
+                ..MarketParams::default()
+            },
+        )
+    }
+
+    /// Starts a test validator with a market initialized in `pricing_mode`
+    /// whose vault holds `VAULT_LIQUIDITY`
+    async fn start_with_pricing(
+        pricing_mode: PricingMode,
+    ) -> (ProgramTestContext, Keypair, MarketFixture) {
+        start_with_market(MarketParams {
+            pricing_mode,
+            amm_base_reserve: AMM_BASE_RESERVE,
+            ..MarketParams::default()
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_initialize_vamm_market() {
+        let (mut context, payer, fixture) = start_with_market_account().await;
+        let banks_client = &mut context.banks_client;
+
+        // A vAMM market needs a virtual base reserve
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[initialize_instruction(
+                    &fixture,
+                    &payer.pubkey(),
+                    PricingMode::Vamm,
+                    0,
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidMarketParameters)
+        );
+
+        process(
+            banks_client,
+            &payer,
+            &[initialize_instruction(
+                &fixture,
+                &payer.pubkey(),
+                PricingMode::Vamm,
+                AMM_BASE_RESERVE,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        // The quote reserve prices the curve at the index price
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(market.pricing_mode, PricingMode::Vamm);
+        assert_eq!(market.amm_base_reserve, AMM_BASE_RESERVE as u128);
+        assert_eq!(market.amm_quote_reserve, 2_000_000 * USDC as u128);
+        assert_eq!(market.mark_price(2_000 * USDC).unwrap(), 2_000 * USDC);
+        assert_eq!(market.cumulative_repeg_cost, 0);
+    }
+
+    #[tokio::test]
+    async fn test_vamm_trades_move_reserves() {
+        let (mut context, payer, fixture) = start_with_pricing(PricingMode::Vamm).await;
+        let long = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let short = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let banks_client = &mut context.banks_client;
+
+        // Taking ten tokens out of the base reserve costs 20202.020203 of
+        // quote, a fill price of 2020.202021 rounded up
+        process(
+            banks_client,
+            &payer,
+            &[fixture.open(&long, Side::Long, 10 * TOKEN, 3_000 * USDC, u64::MAX)],
+            &[&long.keypair],
+        )
+        .await
+        .unwrap();
+        let position = get_position(banks_client, &long.position).await;
+        assert_eq!(position.entry_notional, 20_202_020_210);
+        assert_eq!(position.collateral, 3_000 * USDC - 20_202_021);
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(market.long_open_interest, 10 * TOKEN);
+        assert_eq!(market.mark_price(2_000 * USDC).unwrap(), 2_040_608_101);
+
+        // Selling the ten tokens back fills at 2020.20202 rounded down, and
+        // the curve returns to where it started
+        process(
+            banks_client,
+            &payer,
+            &[fixture.open(&short, Side::Short, 10 * TOKEN, 3_000 * USDC, 0)],
+            &[&short.keypair],
+        )
+        .await
+        .unwrap();
+        let position = get_position(banks_client, &short.position).await;
+        assert_eq!(position.entry_notional, 20_202_020_200);
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(market.skew(), 0);
+        assert_eq!(market.mark_price(2_000 * USDC).unwrap(), 2_000 * USDC);
+        assert_eq!(market.fee_pool, 2 * 20_202_021);
+
+        // The curve cannot sell more than its base reserve
+        let whale = create_trader(banks_client, &payer, &fixture).await;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.open(&whale, Side::Long, 1_000 * TOKEN, USER_FUNDS, u64::MAX)],
+                &[&whale.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InsufficientAmmLiquidity)
+        );
+
+        // Closing the long sells into the curve too, leaving the skew to
+        // the short and the mark below the index price
+        let balance_before = token_balance(banks_client, &long.collateral).await;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.close(&long, 10 * TOKEN, 0)],
+            &[&long.keypair],
+        )
+        .await
+        .unwrap();
+        assert!(token_balance(banks_client, &long.collateral).await > balance_before);
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(market.long_open_interest, 0);
+        assert!(market.mark_price(2_000 * USDC).unwrap() < 2_000 * USDC);
+    }
+
+    #[tokio::test]
+    async fn test_close_vamm_position_emptied_by_liquidation() {
+        let (mut context, payer, fixture) = start_with_pricing(PricingMode::Vamm).await;
+        let trader = create_trader(&mut context.banks_client, &payer, &fixture).await;
+        let keeper_account = create_token_account(
+            &mut context.banks_client,
+            &payer,
+            &fixture.collateral_mint,
+            &payer.pubkey(),
+        )
+        .await;
+
+        // A long of 110 base units costs 221 of quote, and the fee of one
+        // comes out of the collateral
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.open(&trader, Side::Long, 110, 29, u64::MAX)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+        let position = get_position(&mut context.banks_client, &trader.position).await;
+        assert_eq!(position.entry_notional, 221);
+        assert_eq!(position.collateral, 28);
+
+        // At 1775 the notional is 195 and the equity two. Closing all but
+        // one base unit would leave it short of the maintenance margin, so
+        // the liquidation takes the whole position, and the reward of 1.95
+        // rounds down to leave one behind
+        refresh_blockhash(&mut context).await;
+        set_fake_price(&mut context, &fixture.price_account, 177_500_000_000, NOW);
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.liquidate(&payer, &keeper_account, &trader.position)],
+            &[],
+        )
+        .await
+        .unwrap();
+        let position = get_position(&mut context.banks_client, &trader.position).await;
+        assert_eq!(position.size, 0);
+        assert_eq!(position.collateral, 1);
+        assert_eq!(
+            token_balance(&mut context.banks_client, &keeper_account).await,
+            1
+        );
+
+        // Closing the empty position trades nothing on the curve, so it is
+        // neither priced nor charged a fee
+        let market_before = get_market(&mut context.banks_client, &fixture.market).await;
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.close(&trader, 0, 0)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(&mut context.banks_client, &trader.collateral).await,
+            USER_FUNDS - 29 + 1
+        );
+        let position = get_position(&mut context.banks_client, &trader.position).await;
+        assert_eq!(position.collateral, 0);
+        let market = get_market(&mut context.banks_client, &fixture.market).await;
+        assert_eq!(market.fee_pool, market_before.fee_pool);
+        assert_eq!(market.amm_quote_reserve, market_before.amm_quote_reserve);
+    }
+
+    #[tokio::test]
+    async fn test_repeg_amm_requires_admin_and_vamm_market() {
+        let (mut context, payer, fixture) = start_with_pricing(PricingMode::Vamm).await;
+        let banks_client = &mut context.banks_client;
+        let stranger = Keypair::new();
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.repeg(&stranger)],
+                &[&stranger]
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::InvalidAuthority)
+        );
+
+        let (mut context, payer, fixture) = start_with_pricing(PricingMode::Skew).await;
+        assert_eq!(
+            process(
+                &mut context.banks_client,
+                &payer,
+                &[fixture.repeg(&payer)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(TemplateError::NotVammMarket)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repeg_amm_charges_fee_pool() {
+        let (mut context, payer, fixture) = start_with_pricing(PricingMode::Vamm).await;
+        let trader = create_trader(&mut context.banks_client, &payer, &fixture).await;
+
+        // With no skew the curve moves to the oracle for free
+        set_fake_price(&mut context, &fixture.price_account, 210_000_000_000, NOW);
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.repeg(&payer)],
+            &[],
+        )
+        .await
+        .unwrap();
+        let market = get_market(&mut context.banks_client, &fixture.market).await;
+        assert_eq!(market.amm_quote_reserve, 2_100_000 * USDC as u128);
+        assert_eq!(market.mark_price(2_100 * USDC).unwrap(), 2_100 * USDC);
+        assert_eq!(market.cumulative_repeg_cost, 0);
+
+        // Back at 2000, a ten token long fills as in a fresh market and pays
+        // a fee of 20.202021 into the fee pool
+        refresh_blockhash(&mut context).await;
+        set_fake_price(&mut context, &fixture.price_account, ORACLE_PRICE, NOW);
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.repeg(&payer)],
+            &[],
+        )
+        .await
+        .unwrap();
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.open(&trader, Side::Long, 10 * TOKEN, 3_000 * USDC, u64::MAX)],
+            &[&trader.keypair],
+        )
+        .await
+        .unwrap();
+        let fee = 20_202_021;
+        let market = get_market(&mut context.banks_client, &fixture.market).await;
+        assert_eq!(market.fee_pool, fee);
+
+        // Raising the curve to 2100 would owe the long 587.979798, so the
+        // repeg only moves as far as the fee pool pays for
+        refresh_blockhash(&mut context).await;
+        set_fake_price(&mut context, &fixture.price_account, 210_000_000_000, NOW);
+        let banks_client = &mut context.banks_client;
+        process(banks_client, &payer, &[fixture.repeg(&payer)], &[])
+            .await
+            .unwrap();
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(market.amm_quote_reserve, 2_002_000_000_078);
+        assert_eq!(market.fee_pool, 0);
+        assert_eq!(market.cumulative_repeg_cost, fee as i128);
+        assert_eq!(market.mark_price(2_100 * USDC).unwrap(), 2_042_648_709);
+
+        // Lowering the curve against the long earns the fee pool what the
+        // market no longer owes it, and lands exactly on the oracle price
+        refresh_blockhash(&mut context).await;
+        set_fake_price(&mut context, &fixture.price_account, ORACLE_PRICE, NOW);
+        let banks_client = &mut context.banks_client;
+        process(banks_client, &payer, &[fixture.repeg(&payer)], &[])
+            .await
+            .unwrap();
+        let market = get_market(banks_client, &fixture.market).await;
+        assert_eq!(market.amm_quote_reserve, 1_960_200 * USDC as u128);
+        assert_eq!(market.fee_pool, 422_222_223);
+        assert_eq!(market.cumulative_repeg_cost, fee as i128 - 422_222_223);
+        assert_eq!(market.mark_price(2_000 * USDC).unwrap(), 2_000 * USDC);
+    }
+}
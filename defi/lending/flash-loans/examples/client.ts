@@ -5,110 +5,379 @@ import {
   Connection,
   Keypair,
   PublicKey,
-  SystemProgram,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
   SYSVAR_RENT_PUBKEY,
   Transaction,
+  TransactionInstruction,
   sendAndConfirmTransaction,
 } from '@solana/web3.js';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
 import * as borsh from 'borsh';
 import BN from 'bn.js';
 
 // Define the program ID (replace with your actual program ID)
-const PROGRAM_ID = new PublicKey('flash-loans111111111111111111111111111111');
+const PROGRAM_ID = new PublicKey('F1ash11111111111111111111111111111111111111');
 
 // Define instruction types
 enum TemplateInstructionType {
-  Initialize = 0,
-  Operation1 = 1,
-  Operation2 = 2,
+  InitializeReserve = 0,
+  SetFlashLoanFee = 1,
+  FlashBorrow = 2,
+  FlashRepay = 3,
+  Deposit = 4,
+  Withdraw = 5,
+  FlashLoan = 6,
 }
 
 // Define instruction schema
-class InitializeInstruction {
-  param1: BN;
-  param2: number;
-  
-  constructor(props: { param1: BN; param2: number }) {
-    this.param1 = props.param1;
-    this.param2 = props.param2;
+class FlashLoanFeeInstruction {
+  flash_loan_fee_bps: BN;
+
+  constructor(props: { flash_loan_fee_bps: BN }) {
+    this.flash_loan_fee_bps = props.flash_loan_fee_bps;
   }
-  
+
   static schema = new Map([
     [
-      InitializeInstruction,
+      FlashLoanFeeInstruction,
       {
         kind: 'struct',
-        fields: [
-          ['param1', 'u64'],
-          ['param2', 'u8'],
-        ],
+        fields: [['flash_loan_fee_bps', 'u64']],
       },
     ],
   ]);
 }
 
-class Operation1Instruction {
+class AmountInstruction {
   amount: BN;
-  
+
   constructor(props: { amount: BN }) {
     this.amount = props.amount;
   }
-  
+
   static schema = new Map([
     [
-      Operation1Instruction,
+      AmountInstruction,
+      {
+        kind: 'struct',
+        fields: [['amount', 'u64']],
+      },
+    ],
+  ]);
+}
+
+class FlashLoanInstruction {
+  amount: BN;
+  callback_data: Uint8Array;
+
+  constructor(props: { amount: BN; callback_data: Uint8Array }) {
+    this.amount = props.amount;
+    this.callback_data = props.callback_data;
+  }
+
+  static schema = new Map([
+    [
+      FlashLoanInstruction,
       {
         kind: 'struct',
         fields: [
           ['amount', 'u64'],
+          ['callback_data', ['u8']],
         ],
       },
     ],
   ]);
 }
 
+class SharesInstruction {
+  shares: BN;
+
+  constructor(props: { shares: BN }) {
+    this.shares = props.shares;
+  }
+
+  static schema = new Map([
+    [
+      SharesInstruction,
+      {
+        kind: 'struct',
+        fields: [['shares', 'u64']],
+      },
+    ],
+  ]);
+}
+
 /**
- * Initialize a new template account
+ * Derive the reserve authority PDA that owns the liquidity vault
  */
-async function initialize(
+async function findReserveAuthority(reserve: PublicKey): Promise<PublicKey> {
+  const [authority] = await PublicKey.findProgramAddress([reserve.toBuffer()], PROGRAM_ID);
+  return authority;
+}
+
+/**
+ * Initialize a reserve whose reserve account, liquidity vault and share mint
+ * already exist
+ */
+async function initializeReserve(
   connection: Connection,
-  authority: Keypair,
-  account: Keypair,
-  param1: BN,
-  param2: number
+  admin: Keypair,
+  reserve: PublicKey,
+  liquidityMint: PublicKey,
+  liquidityVault: PublicKey,
+  shareMint: PublicKey,
+  flashLoanFeeBps: BN
 ) {
-  // Create initialize instruction
-  const initializeInstruction = new InitializeInstruction({
-    param1,
-    param2,
-  });
-  
-  const instructionData = Buffer.from([TemplateInstructionType.Initialize]);
-  const serializedData = borsh.serialize(
-    InitializeInstruction.schema,
-    initializeInstruction
-  );
-  
-  const fullData = Buffer.concat([instructionData, serializedData]);
-  
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.InitializeReserve]),
+    borsh.serialize(
+      FlashLoanFeeInstruction.schema,
+      new FlashLoanFeeInstruction({ flash_loan_fee_bps: flashLoanFeeBps })
+    ),
+  ]);
+
   const transaction = new Transaction().add({
     keys: [
-      { pubkey: authority.publicKey, isSigner: true, isWritable: true },
-      { pubkey: account.publicKey, isSigner: true, isWritable: true },
-      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: admin.publicKey, isSigner: true, isWritable: false },
+      { pubkey: reserve, isSigner: false, isWritable: true },
+      { pubkey: await findReserveAuthority(reserve), isSigner: false, isWritable: false },
+      { pubkey: liquidityMint, isSigner: false, isWritable: false },
+      { pubkey: liquidityVault, isSigner: false, isWritable: false },
+      { pubkey: shareMint, isSigner: false, isWritable: false },
       { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
     ],
     programId: PROGRAM_ID,
-    data: fullData,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [admin]);
+
+  console.log('Reserve initialized successfully!');
+}
+
+/**
+ * Change the flash loan fee of a reserve
+ */
+async function setFlashLoanFee(
+  connection: Connection,
+  admin: Keypair,
+  reserve: PublicKey,
+  flashLoanFeeBps: BN
+) {
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.SetFlashLoanFee]),
+    borsh.serialize(
+      FlashLoanFeeInstruction.schema,
+      new FlashLoanFeeInstruction({ flash_loan_fee_bps: flashLoanFeeBps })
+    ),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: admin.publicKey, isSigner: true, isWritable: false },
+      { pubkey: reserve, isSigner: false, isWritable: true },
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [admin]);
+
+  console.log('Flash loan fee updated successfully!');
+}
+
+/**
+ * Build a FlashBorrow instruction lending `amount` into `destination`
+ */
+async function flashBorrowInstruction(
+  reserve: PublicKey,
+  liquidityVault: PublicKey,
+  destination: PublicKey,
+  amount: BN
+): Promise<TransactionInstruction> {
+  return new TransactionInstruction({
+    keys: [
+      { pubkey: reserve, isSigner: false, isWritable: true },
+      { pubkey: liquidityVault, isSigner: false, isWritable: true },
+      { pubkey: await findReserveAuthority(reserve), isSigner: false, isWritable: false },
+      { pubkey: destination, isSigner: false, isWritable: true },
+      { pubkey: SYSVAR_INSTRUCTIONS_PUBKEY, isSigner: false, isWritable: false },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data: Buffer.concat([
+      Buffer.from([TemplateInstructionType.FlashBorrow]),
+      borsh.serialize(AmountInstruction.schema, new AmountInstruction({ amount })),
+    ]),
   });
-  
-  await sendAndConfirmTransaction(
-    connection,
-    transaction,
-    [authority, account]
+}
+
+/**
+ * Build a FlashRepay instruction paying the loan and its fee from `source`
+ */
+function flashRepayInstruction(
+  reserve: PublicKey,
+  liquidityVault: PublicKey,
+  source: PublicKey,
+  sourceOwner: PublicKey
+): TransactionInstruction {
+  return new TransactionInstruction({
+    keys: [
+      { pubkey: reserve, isSigner: false, isWritable: true },
+      { pubkey: liquidityVault, isSigner: false, isWritable: true },
+      { pubkey: source, isSigner: false, isWritable: true },
+      { pubkey: sourceOwner, isSigner: true, isWritable: false },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data: Buffer.from([TemplateInstructionType.FlashRepay]),
+  });
+}
+
+/**
+ * Borrow `amount`, run `instructions` with it and repay it plus the fee, all
+ * in one transaction. The borrower's token account must hold the fee by the
+ * time the repay runs.
+ */
+async function flashLoan(
+  connection: Connection,
+  borrower: Keypair,
+  reserve: PublicKey,
+  liquidityVault: PublicKey,
+  borrowerLiquidity: PublicKey,
+  amount: BN,
+  instructions: TransactionInstruction[],
+  signers: Keypair[] = []
+) {
+  const transaction = new Transaction().add(
+    await flashBorrowInstruction(reserve, liquidityVault, borrowerLiquidity, amount),
+    ...instructions,
+    flashRepayInstruction(reserve, liquidityVault, borrowerLiquidity, borrower.publicKey)
   );
-  
-  console.log('Account initialized successfully!');
+
+  await sendAndConfirmTransaction(connection, transaction, [borrower, ...signers]);
+
+  console.log('Flash loan repaid successfully!');
+}
+
+/**
+ * Borrow `amount` into `destination` for the length of a callback into
+ * `receiverProgram`, which must transfer the loan and the fee back into the
+ * vault before it returns. `receiverAccounts` are passed on to the receiver.
+ */
+async function callbackFlashLoan(
+  connection: Connection,
+  payer: Keypair,
+  reserve: PublicKey,
+  liquidityVault: PublicKey,
+  destination: PublicKey,
+  receiverProgram: PublicKey,
+  receiverAccounts: { pubkey: PublicKey; isSigner: boolean; isWritable: boolean }[],
+  amount: BN,
+  callbackData: Uint8Array,
+  signers: Keypair[] = []
+) {
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.FlashLoan]),
+    borsh.serialize(
+      FlashLoanInstruction.schema,
+      new FlashLoanInstruction({ amount, callback_data: callbackData })
+    ),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: reserve, isSigner: false, isWritable: true },
+      { pubkey: liquidityVault, isSigner: false, isWritable: true },
+      { pubkey: await findReserveAuthority(reserve), isSigner: false, isWritable: false },
+      { pubkey: destination, isSigner: false, isWritable: true },
+      { pubkey: receiverProgram, isSigner: false, isWritable: false },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+      ...receiverAccounts,
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [payer, ...signers]);
+
+  console.log('Callback flash loan repaid successfully!');
+}
+
+/**
+ * Deposit liquidity into a reserve in exchange for shares
+ */
+async function deposit(
+  connection: Connection,
+  depositor: Keypair,
+  reserve: PublicKey,
+  liquidityVault: PublicKey,
+  shareMint: PublicKey,
+  sourceLiquidity: PublicKey,
+  destinationShares: PublicKey,
+  amount: BN
+) {
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.Deposit]),
+    borsh.serialize(AmountInstruction.schema, new AmountInstruction({ amount })),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: depositor.publicKey, isSigner: true, isWritable: false },
+      { pubkey: reserve, isSigner: false, isWritable: true },
+      { pubkey: await findReserveAuthority(reserve), isSigner: false, isWritable: false },
+      { pubkey: liquidityVault, isSigner: false, isWritable: true },
+      { pubkey: shareMint, isSigner: false, isWritable: true },
+      { pubkey: sourceLiquidity, isSigner: false, isWritable: true },
+      { pubkey: destinationShares, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [depositor]);
+
+  console.log('Liquidity deposited successfully!');
+}
+
+/**
+ * Burn shares of a reserve for the liquidity they redeem for
+ */
+async function withdraw(
+  connection: Connection,
+  owner: Keypair,
+  reserve: PublicKey,
+  liquidityVault: PublicKey,
+  shareMint: PublicKey,
+  sourceShares: PublicKey,
+  destinationLiquidity: PublicKey,
+  shares: BN
+) {
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.Withdraw]),
+    borsh.serialize(SharesInstruction.schema, new SharesInstruction({ shares })),
+  ]);
+
+  const transaction = new Transaction().add({
+    keys: [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: reserve, isSigner: false, isWritable: true },
+      { pubkey: await findReserveAuthority(reserve), isSigner: false, isWritable: false },
+      { pubkey: liquidityVault, isSigner: false, isWritable: true },
+      { pubkey: shareMint, isSigner: false, isWritable: true },
+      { pubkey: sourceShares, isSigner: false, isWritable: true },
+      { pubkey: destinationLiquidity, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    programId: PROGRAM_ID,
+    data,
+  });
+
+  await sendAndConfirmTransaction(connection, transaction, [owner]);
+
+  console.log('Liquidity withdrawn successfully!');
 }
 
 /**
@@ -117,25 +386,18 @@ async function initialize(
 async function main() {
   // Connect to devnet
   const connection = new Connection('https://api.devnet.solana.com', 'confirmed');
-  
+
   // Generate keypairs for testing
-  const authority = Keypair.generate();
-  const account = Keypair.generate();
-  
-  console.log('Requesting airdrop for authority...');
-  const airdropSignature = await connection.requestAirdrop(authority.publicKey, 1000000000);
+  const admin = Keypair.generate();
+
+  console.log('Requesting airdrop for admin...');
+  const airdropSignature = await connection.requestAirdrop(admin.publicKey, 1000000000);
   await connection.confirmTransaction(airdropSignature);
-  
-  // Initialize with example values
-  await initialize(
-    connection,
-    authority,
-    account,
-    new BN(100),
-    5
-  );
-  
-  // TODO: Add examples for other operations
+
+  // TODO: Create the reserve account, a liquidity vault owned by the reserve
+  // authority and a share mint whose mint authority it is, call
+  // initializeReserve and deposit, then call flashLoan with the instructions
+  // that use the borrowed liquidity
 }
 
 main().then(
@@ -4,27 +4,91 @@ use solana_program::program_error::ProgramError;
 use thiserror::Error;
 
 /// Errors that may be returned by the flash-loans program
-#[derive(Error, Debug, Copy, Clone)]
+#[derive(Error, Debug, Copy, Clone, PartialEq)]
 pub enum TemplateError {
     /// Invalid instruction
     #[error("Invalid instruction")]
     InvalidInstruction,
-    
+
     /// Not rent exempt
     #[error("Not rent exempt")]
     NotRentExempt,
-    
+
     /// Expected amount mismatch
     #[error("Expected amount mismatch")]
     ExpectedAmountMismatch,
-    
+
     /// Invalid authority
     #[error("Invalid authority")]
     InvalidAuthority,
-    
+
     /// Math operation overflow
     #[error("Math operation overflow")]
     MathOverflow,
+
+    /// Reserve already initialized
+    #[error("Reserve already initialized")]
+    ReserveAlreadyInitialized,
+
+    /// Reserve not initialized
+    #[error("Reserve not initialized")]
+    ReserveNotInitialized,
+
+    /// Invalid reserve authority
+    #[error("Invalid reserve authority")]
+    InvalidReserveAuthority,
+
+    /// Invalid reserve parameters
+    #[error("Invalid reserve parameters")]
+    InvalidReserveParameters,
+
+    /// Invalid vault
+    #[error("Invalid vault")]
+    InvalidVault,
+
+    /// Invalid token program
+    #[error("Invalid token program")]
+    InvalidTokenProgram,
+
+    /// Zero amount
+    #[error("Amount must be greater than zero")]
+    ZeroAmount,
+
+    /// Insufficient liquidity
+    #[error("Reserve vault holds too little liquidity")]
+    InsufficientLiquidity,
+
+    /// Flash loan already in progress
+    #[error("A flash loan from this reserve is already in progress")]
+    FlashLoanInProgress,
+
+    /// No flash loan in progress
+    #[error("No flash loan from this reserve is in progress")]
+    NoFlashLoanInProgress,
+
+    /// Missing flash repay
+    #[error("No matching flash repay later in the transaction")]
+    FlashRepayNotFound,
+
+    /// Flash loan instruction invoked through CPI
+    #[error("Flash loan instructions cannot be invoked through CPI")]
+    FlashLoanCpi,
+
+    /// Invalid share mint
+    #[error("Invalid share mint")]
+    InvalidShareMint,
+
+    /// Deposit too small
+    #[error("Deposit too small to mint a share")]
+    DepositTooSmall,
+
+    /// Withdrawal too small
+    #[error("Withdrawal too small to redeem any liquidity")]
+    WithdrawTooSmall,
+
+    /// Callback flash loan not repaid
+    #[error("Flash loan callback did not repay the loan and its fee")]
+    FlashLoanNotRepaid,
 }
 
 impl From<TemplateError> for ProgramError {
@@ -6,63 +6,185 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
-    system_program, sysvar,
+    sysvar,
 };
 
 /// Instructions supported by the flash-loans program
 #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
 pub enum TemplateInstruction {
-    /// Initialize a new instance
+    /// Initialize a new flash loan reserve
+    ///
+    /// The liquidity vault must be an empty token account of the liquidity
+    /// mint owned by the reserve authority. The share mint must have no
+    /// supply, no freeze authority, the decimals of the liquidity mint and
+    /// the reserve authority as its mint authority.
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to initialize
-    /// 2. `[]` The system program
-    /// 3. `[]` The rent sysvar
-    ///
-    Initialize {
-        /// Custom parameter 1
-        param1: u64,
-        /// Custom parameter 2
-        param2: u8,
+    /// 0. `[signer]` The reserve admin
+    /// 1. `[writable]` The reserve account, owned by this program
+    /// 2. `[]` The reserve authority PDA
+    /// 3. `[]` The liquidity mint
+    /// 4. `[]` The liquidity vault
+    /// 5. `[]` The share mint
+    /// 6. `[]` The rent sysvar
+    ///
+    InitializeReserve {
+        /// Fee charged on every flash loan, in basis points of the amount borrowed
+        flash_loan_fee_bps: u64,
     },
 
-    /// Perform operation 1
+    /// Change the flash loan fee of a reserve
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to modify
+    /// 0. `[signer]` The reserve admin
+    /// 1. `[writable]` The reserve account
+    ///
+    SetFlashLoanFee {
+        /// New fee, in basis points of the amount borrowed
+        flash_loan_fee_bps: u64,
+    },
+
+    /// Borrow liquidity for the rest of the transaction
+    ///
+    /// Fails unless a `FlashRepay` of the same reserve follows later in the
+    /// same transaction, with no other `FlashBorrow` of the reserve in
+    /// between. Must be called directly by the transaction, not through CPI.
     ///
-    Operation1 {
-        /// Operation amount
+    /// Accounts expected:
+    /// 0. `[writable]` The reserve account
+    /// 1. `[writable]` The liquidity vault
+    /// 2. `[]` The reserve authority PDA
+    /// 3. `[writable]` The token account receiving the liquidity
+    /// 4. `[]` The instructions sysvar
+    /// 5. `[]` The token program
+    ///
+    FlashBorrow {
+        /// Amount of liquidity to borrow
         amount: u64,
     },
 
-    /// Perform operation 2
+    /// Repay the flash loan in progress plus the flash loan fee
+    ///
+    /// Must be called directly by the transaction, not through CPI.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The reserve account
+    /// 1. `[writable]` The liquidity vault
+    /// 2. `[writable]` The token account repaying the liquidity
+    /// 3. `[signer]` The owner of the repaying token account
+    /// 4. `[]` The token program
+    ///
+    FlashRepay,
+
+    /// Deposit liquidity and receive reserve shares
+    ///
+    /// Mints shares at the current exchange rate, rounded down. Fails while
+    /// a flash loan is in progress.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The depositor
+    /// 1. `[writable]` The reserve account
+    /// 2. `[]` The reserve authority PDA
+    /// 3. `[writable]` The liquidity vault
+    /// 4. `[writable]` The share mint
+    /// 5. `[writable]` The depositor's liquidity token account
+    /// 6. `[writable]` The token account receiving the shares
+    /// 7. `[]` The token program
+    ///
+    Deposit {
+        /// Amount of liquidity to deposit
+        amount: u64,
+    },
+
+    /// Burn reserve shares and withdraw the liquidity they are worth
+    ///
+    /// Redeems shares at the current exchange rate, rounded down. Fails
+    /// while a flash loan is in progress.
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to modify
+    /// 0. `[signer]` The owner of the shares
+    /// 1. `[writable]` The reserve account
+    /// 2. `[]` The reserve authority PDA
+    /// 3. `[writable]` The liquidity vault
+    /// 4. `[writable]` The share mint
+    /// 5. `[writable]` The owner's share token account
+    /// 6. `[writable]` The token account receiving the liquidity
+    /// 7. `[]` The token program
     ///
-    Operation2,
+    Withdraw {
+        /// Amount of shares to burn
+        shares: u64,
+    },
+
+    /// Borrow liquidity for the length of a callback into a receiver program
+    ///
+    /// Lends `amount` to the destination, invokes the receiver program with
+    /// `callback_data` as its instruction data and the remaining accounts,
+    /// then fails unless the vault holds the loan plus the flash loan fee
+    /// more than before. The receiver repays by transferring into the vault.
+    /// Unlike `FlashBorrow` it can be called through CPI.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The reserve account
+    /// 1. `[writable]` The liquidity vault
+    /// 2. `[]` The reserve authority PDA
+    /// 3. `[writable]` The token account receiving the liquidity
+    /// 4. `[]` The receiver program
+    /// 5. `[]` The token program
+    /// 6. `..` The accounts passed on to the receiver program
+    ///
+    FlashLoan {
+        /// Amount of liquidity to borrow
+        amount: u64,
+        /// Instruction data of the callback into the receiver program
+        callback_data: Vec<u8>,
+    },
 }
 
-/// Creates an Initialize instruction
-pub fn initialize(
+/// Creates an InitializeReserve instruction
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_reserve(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
-    param1: u64,
-    param2: u8,
+    admin: &Pubkey,
+    reserve: &Pubkey,
+    reserve_authority: &Pubkey,
+    liquidity_mint: &Pubkey,
+    liquidity_vault: &Pubkey,
+    share_mint: &Pubkey,
+    flash_loan_fee_bps: u64,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
-        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new_readonly(*reserve_authority, false),
+        AccountMeta::new_readonly(*liquidity_mint, false),
+        AccountMeta::new_readonly(*liquidity_vault, false),
+        AccountMeta::new_readonly(*share_mint, false),
         AccountMeta::new_readonly(sysvar::rent::id(), false),
     ];
 
-    let data = TemplateInstruction::Initialize { param1, param2 };
+    let data = TemplateInstruction::InitializeReserve { flash_loan_fee_bps };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a SetFlashLoanFee instruction
+pub fn set_flash_loan_fee(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    reserve: &Pubkey,
+    flash_loan_fee_bps: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(*reserve, false),
+    ];
+
+    let data = TemplateInstruction::SetFlashLoanFee { flash_loan_fee_bps };
 
     Instruction {
         program_id: *program_id,
@@ -71,19 +193,25 @@ pub fn initialize(
     }
 }
 
-/// Creates an Operation1 instruction
-pub fn operation1(
+/// Creates a FlashBorrow instruction
+pub fn flash_borrow(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
+    reserve: &Pubkey,
+    liquidity_vault: &Pubkey,
+    reserve_authority: &Pubkey,
+    destination: &Pubkey,
     amount: u64,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new(*liquidity_vault, false),
+        AccountMeta::new_readonly(*reserve_authority, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
     ];
 
-    let data = TemplateInstruction::Operation1 { amount };
+    let data = TemplateInstruction::FlashBorrow { amount };
 
     Instruction {
         program_id: *program_id,
@@ -92,18 +220,124 @@ pub fn operation1(
     }
 }
 
-/// Creates an Operation2 instruction
-pub fn operation2(
+/// Creates a FlashRepay instruction
+pub fn flash_repay(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
+    reserve: &Pubkey,
+    liquidity_vault: &Pubkey,
+    source: &Pubkey,
+    source_owner: &Pubkey,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new(*liquidity_vault, false),
+        AccountMeta::new(*source, false),
+        AccountMeta::new_readonly(*source_owner, true),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::FlashRepay;
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a Deposit instruction
+#[allow(clippy::too_many_arguments)]
+pub fn deposit(
+    program_id: &Pubkey,
+    depositor: &Pubkey,
+    reserve: &Pubkey,
+    reserve_authority: &Pubkey,
+    liquidity_vault: &Pubkey,
+    share_mint: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*depositor, true),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new_readonly(*reserve_authority, false),
+        AccountMeta::new(*liquidity_vault, false),
+        AccountMeta::new(*share_mint, false),
+        AccountMeta::new(*source, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::Deposit { amount };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a Withdraw instruction
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    reserve: &Pubkey,
+    reserve_authority: &Pubkey,
+    liquidity_vault: &Pubkey,
+    share_mint: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    shares: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new_readonly(*reserve_authority, false),
+        AccountMeta::new(*liquidity_vault, false),
+        AccountMeta::new(*share_mint, false),
+        AccountMeta::new(*source, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::Withdraw { shares };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a FlashLoan instruction
+#[allow(clippy::too_many_arguments)]
+pub fn flash_loan(
+    program_id: &Pubkey,
+    reserve: &Pubkey,
+    liquidity_vault: &Pubkey,
+    reserve_authority: &Pubkey,
+    destination: &Pubkey,
+    receiver_program: &Pubkey,
+    receiver_accounts: &[AccountMeta],
+    amount: u64,
+    callback_data: Vec<u8>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new(*liquidity_vault, false),
+        AccountMeta::new_readonly(*reserve_authority, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(*receiver_program, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
     ];
+    accounts.extend_from_slice(receiver_accounts);
 
-    let data = TemplateInstruction::Operation2;
+    let data = TemplateInstruction::FlashLoan {
+        amount,
+        callback_data,
+    };
 
     Instruction {
         program_id: *program_id,
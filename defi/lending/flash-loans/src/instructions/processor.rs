@@ -4,17 +4,28 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
+    instruction::{get_stack_height, AccountMeta, Instruction, TRANSACTION_LEVEL_STACK_HEIGHT},
     msg,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
     pubkey::Pubkey,
     rent::Rent,
-    sysvar::Sysvar,
+    sysvar::{
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+        Sysvar,
+    },
 };
 
 use crate::{
     errors::TemplateError,
     instructions::TemplateInstruction,
-    state::TemplateAccount,
+    state::{Reserve, BPS_DENOMINATOR},
+    utils::{
+        check_account_owner, check_reserve_authority, check_signer, find_reserve_authority,
+        safe_addition,
+    },
 };
 
 /// Processes an instruction
@@ -27,141 +38,700 @@ pub fn process_instruction(
         .map_err(|_| ProgramError::InvalidInstructionData)?;
 
     match instruction {
-        TemplateInstruction::Initialize { param1, param2 } => {
-            msg!("Instruction: Initialize");
-            process_initialize(program_id, accounts, param1, param2)
+        TemplateInstruction::InitializeReserve { flash_loan_fee_bps } => {
+            msg!("Instruction: Initialize Reserve");
+            process_initialize_reserve(program_id, accounts, flash_loan_fee_bps)
         }
-        TemplateInstruction::Operation1 { amount } => {
-            msg!("Instruction: Operation1");
-            process_operation1(program_id, accounts, amount)
+        TemplateInstruction::SetFlashLoanFee { flash_loan_fee_bps } => {
+            msg!("Instruction: Set Flash Loan Fee");
+            process_set_flash_loan_fee(program_id, accounts, flash_loan_fee_bps)
         }
-        TemplateInstruction::Operation2 => {
-            msg!("Instruction: Operation2");
-            process_operation2(program_id, accounts)
+        TemplateInstruction::FlashBorrow { amount } => {
+            msg!("Instruction: Flash Borrow");
+            process_flash_borrow(program_id, accounts, amount)
+        }
+        TemplateInstruction::FlashRepay => {
+            msg!("Instruction: Flash Repay");
+            process_flash_repay(program_id, accounts)
+        }
+        TemplateInstruction::Deposit { amount } => {
+            msg!("Instruction: Deposit");
+            process_deposit(program_id, accounts, amount)
+        }
+        TemplateInstruction::Withdraw { shares } => {
+            msg!("Instruction: Withdraw");
+            process_withdraw(program_id, accounts, shares)
+        }
+        TemplateInstruction::FlashLoan {
+            amount,
+            callback_data,
+        } => {
+            msg!("Instruction: Flash Loan");
+            process_flash_loan(program_id, accounts, amount, callback_data)
         }
     }
 }
 
-/// Processes an Initialize instruction
-fn process_initialize(
+/// Processes an InitializeReserve instruction
+fn process_initialize_reserve(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    param1: u64,
-    param2: u8,
+    flash_loan_fee_bps: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    let system_program_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_authority_info = next_account_info(account_info_iter)?;
+    let liquidity_mint_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let share_mint_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
-        return Err(TemplateError::InvalidAuthority.into());
-    }
-    
+
+    // Check the admin is a signer
+    check_signer(admin_info)?;
+
+    // Check reserve account ownership
+    check_account_owner(reserve_info, program_id)?;
+
     // Check for rent exemption
     let rent = &Rent::from_account_info(rent_info)?;
-    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
+    if !rent.is_exempt(reserve_info.lamports(), reserve_info.data_len()) {
         return Err(TemplateError::NotRentExempt.into());
     }
-    
-    // Initialize account data
-    let template_account = TemplateAccount {
-        authority: *authority_info.key,
-        value1: param1,
-        value2: param2,
+
+    // Check the reserve has not been initialized yet
+    if reserve_info.data_len() != Reserve::get_size() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if reserve_info.data.borrow()[0] != 0 {
+        return Err(TemplateError::ReserveAlreadyInitialized.into());
+    }
+
+    // Validate the fee
+    if flash_loan_fee_bps > BPS_DENOMINATOR {
+        return Err(TemplateError::InvalidReserveParameters.into());
+    }
+
+    // Validate reserve authority
+    let (reserve_authority, bump_seed) = find_reserve_authority(program_id, reserve_info.key);
+    if reserve_authority != *reserve_authority_info.key {
+        return Err(TemplateError::InvalidReserveAuthority.into());
+    }
+
+    // Validate the liquidity mint and vault
+    check_account_owner(liquidity_mint_info, &spl_token::id())
+        .map_err(|_| TemplateError::InvalidVault)?;
+    check_vault(
+        liquidity_vault_info,
+        liquidity_mint_info.key,
+        &reserve_authority,
+    )?;
+    let liquidity_decimals =
+        spl_token::state::Mint::unpack(&liquidity_mint_info.data.borrow())?.decimals;
+
+    // Validate the share mint
+    check_account_owner(share_mint_info, &spl_token::id())
+        .map_err(|_| TemplateError::InvalidShareMint)?;
+    let share_mint = spl_token::state::Mint::unpack(&share_mint_info.data.borrow())?;
+    if share_mint.mint_authority != COption::Some(reserve_authority)
+        || share_mint.freeze_authority.is_some()
+        || share_mint.supply != 0
+        || share_mint.decimals != liquidity_decimals
+    {
+        return Err(TemplateError::InvalidShareMint.into());
+    }
+
+    let reserve = Reserve {
         is_initialized: true,
+        bump_seed,
+        admin: *admin_info.key,
+        liquidity_mint: *liquidity_mint_info.key,
+        liquidity_vault: *liquidity_vault_info.key,
+        share_mint: *share_mint_info.key,
+        flash_loan_fee_bps,
+        total_liquidity: 0,
+        flash_loan_amount: 0,
+        cumulative_fees: 0,
     };
-    
-    // Save account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
     Ok(())
 }
 
-/// Processes an Operation1 instruction
-fn process_operation1(
+/// Processes a SetFlashLoanFee instruction
+fn process_set_flash_loan_fee(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    amount: u64,
+    flash_loan_fee_bps: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
+    let admin_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+
+    // Check the admin is a signer
+    check_signer(admin_info)?;
+
+    let mut reserve = load_reserve(program_id, reserve_info)?;
+    if reserve.admin != *admin_info.key {
         return Err(TemplateError::InvalidAuthority.into());
     }
-    
-    // Deserialize account data
-    let mut template_account = TemplateAccount::try_from_slice(&account_info.data.borrow())?;
-    
-    // Verify authority
-    if template_account.authority != *authority_info.key {
-        return Err(TemplateError::InvalidAuthority.into());
+    if flash_loan_fee_bps > BPS_DENOMINATOR {
+        return Err(TemplateError::InvalidReserveParameters.into());
     }
-    
-    // Update account data
-    template_account.value1 = template_account.value1.checked_add(amount)
-        .ok_or(TemplateError::MathOverflow)?;
-    
-    // Save updated account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+
+    // The fee of a loan in progress is fixed when it is borrowed
+    if reserve.has_flash_loan() {
+        return Err(TemplateError::FlashLoanInProgress.into());
+    }
+    reserve.flash_loan_fee_bps = flash_loan_fee_bps;
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
     Ok(())
 }
 
-/// Processes an Operation2 instruction
-fn process_operation2(
+/// Processes a FlashBorrow instruction
+fn process_flash_borrow(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
+    amount: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
-        return Err(TemplateError::InvalidAuthority.into());
+    let reserve_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let reserve_authority_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let instructions_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // A program calling in through CPI could borrow again before repaying
+    check_not_cpi()?;
+
+    if amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
     }
-    
-    // Deserialize account data
-    let mut template_account = TemplateAccount::try_from_slice(&account_info.data.borrow())?;
-    
-    // Verify authority
-    if template_account.authority != *authority_info.key {
-        return Err(TemplateError::InvalidAuthority.into());
+
+    let mut reserve = load_reserve(program_id, reserve_info)?;
+    if reserve.has_flash_loan() {
+        return Err(TemplateError::FlashLoanInProgress.into());
+    }
+    check_liquidity_vault(&reserve, liquidity_vault_info, token_program_info)?;
+    check_reserve_authority(
+        program_id,
+        reserve_info.key,
+        reserve.bump_seed,
+        reserve_authority_info.key,
+    )?;
+    let liquidity = spl_token::state::Account::unpack(&liquidity_vault_info.data.borrow())?.amount;
+    if amount > liquidity {
+        return Err(TemplateError::InsufficientLiquidity.into());
+    }
+
+    // The loan only goes out if the transaction repays it
+    check_flash_repay_follows(program_id, reserve_info.key, instructions_info)?;
+
+    reserve.flash_loan_amount = amount;
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
+    // Lend the liquidity
+    transfer_from_vault(
+        token_program_info,
+        liquidity_vault_info,
+        destination_info,
+        reserve_authority_info,
+        &[reserve_info.key.as_ref(), &[reserve.bump_seed]],
+        amount,
+    )?;
+
+    Ok(())
+}
+
+/// Processes a FlashRepay instruction
+fn process_flash_repay(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let reserve_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let source_owner_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    check_not_cpi()?;
+
+    // Check the owner of the repaying account is a signer
+    check_signer(source_owner_info)?;
+
+    let mut reserve = load_reserve(program_id, reserve_info)?;
+    if !reserve.has_flash_loan() {
+        return Err(TemplateError::NoFlashLoanInProgress.into());
+    }
+    check_liquidity_vault(&reserve, liquidity_vault_info, token_program_info)?;
+
+    // Take back the loan plus the fee, which stays in the vault for the
+    // share holders
+    let amount = reserve.flash_loan_amount;
+    let fee = reserve.flash_loan_fee(amount);
+    transfer_to_vault(
+        token_program_info,
+        source_info,
+        liquidity_vault_info,
+        source_owner_info,
+        safe_addition(amount, fee)?,
+    )?;
+    msg!("Repaid {} plus a fee of {}", amount, fee);
+
+    reserve.flash_loan_amount = 0;
+    reserve.total_liquidity = safe_addition(reserve.total_liquidity, fee)?;
+    reserve.cumulative_fees = safe_addition(reserve.cumulative_fees, fee)?;
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a Deposit instruction
+fn process_deposit(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let depositor_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_authority_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let share_mint_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the depositor is a signer
+    check_signer(depositor_info)?;
+
+    if amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    let mut reserve = load_liquidity_reserve(
+        program_id,
+        reserve_info,
+        reserve_authority_info,
+        liquidity_vault_info,
+        share_mint_info,
+        token_program_info,
+    )?;
+
+    // Mint shares at the exchange rate before the deposit, rounded down
+    let share_supply = spl_token::state::Mint::unpack(&share_mint_info.data.borrow())?.supply;
+    let shares = reserve.shares_for_deposit(amount, share_supply)?;
+    if shares == 0 {
+        return Err(TemplateError::DepositTooSmall.into());
+    }
+    reserve.total_liquidity = safe_addition(reserve.total_liquidity, amount)?;
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
+    transfer_to_vault(
+        token_program_info,
+        source_info,
+        liquidity_vault_info,
+        depositor_info,
+        amount,
+    )?;
+    mint_shares(
+        token_program_info,
+        share_mint_info,
+        destination_info,
+        reserve_authority_info,
+        &[reserve_info.key.as_ref(), &[reserve.bump_seed]],
+        shares,
+    )?;
+    msg!("Deposited {} for {} shares", amount, shares);
+
+    Ok(())
+}
+
+/// Processes a Withdraw instruction
+fn process_withdraw(program_id: &Pubkey, accounts: &[AccountInfo], shares: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_authority_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let share_mint_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the share owner is a signer
+    check_signer(owner_info)?;
+
+    if shares == 0 {
+        return Err(TemplateError::ZeroAmount.into());
     }
-    
-    // Update account data
-    template_account.value2 = template_account.value2.checked_add(1)
+
+    let mut reserve = load_liquidity_reserve(
+        program_id,
+        reserve_info,
+        reserve_authority_info,
+        liquidity_vault_info,
+        share_mint_info,
+        token_program_info,
+    )?;
+
+    // Redeem the shares at the exchange rate before the withdrawal, rounded down
+    let share_supply = spl_token::state::Mint::unpack(&share_mint_info.data.borrow())?.supply;
+    let amount = reserve.liquidity_for_shares(shares, share_supply)?;
+    if amount == 0 {
+        return Err(TemplateError::WithdrawTooSmall.into());
+    }
+    reserve.total_liquidity = reserve
+        .total_liquidity
+        .checked_sub(amount)
         .ok_or(TemplateError::MathOverflow)?;
-    
-    // Save updated account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
+    burn_shares(
+        token_program_info,
+        source_info,
+        share_mint_info,
+        owner_info,
+        shares,
+    )?;
+    transfer_from_vault(
+        token_program_info,
+        liquidity_vault_info,
+        destination_info,
+        reserve_authority_info,
+        &[reserve_info.key.as_ref(), &[reserve.bump_seed]],
+        amount,
+    )?;
+    msg!("Withdrew {} for {} shares", amount, shares);
+
+    Ok(())
+}
+
+/// Processes a FlashLoan instruction
+fn process_flash_loan(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    callback_data: Vec<u8>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let reserve_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let reserve_authority_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let receiver_program_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let receiver_account_infos = account_info_iter.as_slice();
+
+    if amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    let mut reserve = load_reserve(program_id, reserve_info)?;
+    if reserve.has_flash_loan() {
+        return Err(TemplateError::FlashLoanInProgress.into());
+    }
+    check_liquidity_vault(&reserve, liquidity_vault_info, token_program_info)?;
+    check_reserve_authority(
+        program_id,
+        reserve_info.key,
+        reserve.bump_seed,
+        reserve_authority_info.key,
+    )?;
+    let liquidity_before =
+        spl_token::state::Account::unpack(&liquidity_vault_info.data.borrow())?.amount;
+    if amount > liquidity_before {
+        return Err(TemplateError::InsufficientLiquidity.into());
+    }
+
+    // Record the loan so the reserve refuses every other flash loan,
+    // deposit and withdrawal until the callback returns
+    let fee = reserve.flash_loan_fee(amount);
+    reserve.flash_loan_amount = amount;
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
+    // Lend the liquidity
+    transfer_from_vault(
+        token_program_info,
+        liquidity_vault_info,
+        destination_info,
+        reserve_authority_info,
+        &[reserve_info.key.as_ref(), &[reserve.bump_seed]],
+        amount,
+    )?;
+
+    // Hand control to the receiver, without the reserve authority's signature
+    let callback = Instruction {
+        program_id: *receiver_program_info.key,
+        accounts: receiver_account_infos
+            .iter()
+            .map(|info| AccountMeta {
+                pubkey: *info.key,
+                is_signer: info.is_signer,
+                is_writable: info.is_writable,
+            })
+            .collect(),
+        data: callback_data,
+    };
+    let mut callback_account_infos = receiver_account_infos.to_vec();
+    callback_account_infos.push(receiver_program_info.clone());
+    invoke(&callback, &callback_account_infos)?;
+
+    // The vault must hold the loan back plus the fee
+    let liquidity_after =
+        spl_token::state::Account::unpack(&liquidity_vault_info.data.borrow())?.amount;
+    if liquidity_after < safe_addition(liquidity_before, fee)? {
+        return Err(TemplateError::FlashLoanNotRepaid.into());
+    }
+    msg!("Repaid {} plus a fee of {}", amount, fee);
+
+    let mut reserve = load_reserve(program_id, reserve_info)?;
+    reserve.flash_loan_amount = 0;
+    reserve.total_liquidity = safe_addition(reserve.total_liquidity, fee)?;
+    reserve.cumulative_fees = safe_addition(reserve.cumulative_fees, fee)?;
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Checks that the instruction is called directly by the transaction
+fn check_not_cpi() -> ProgramResult {
+    if get_stack_height() != TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Err(TemplateError::FlashLoanCpi.into());
+    }
     Ok(())
 }
+
+/// Checks that a `FlashRepay` of `reserve` follows the current instruction
+/// in the transaction, with no other `FlashBorrow` of it before the repay
+fn check_flash_repay_follows(
+    program_id: &Pubkey,
+    reserve: &Pubkey,
+    instructions_info: &AccountInfo,
+) -> ProgramResult {
+    let current_index = load_current_index_checked(instructions_info)? as usize;
+    for index in current_index + 1.. {
+        let instruction = match load_instruction_at_checked(index, instructions_info) {
+            Ok(instruction) => instruction,
+            // Past the last instruction of the transaction
+            Err(ProgramError::InvalidArgument) => break,
+            Err(error) => return Err(error),
+        };
+        if instruction.program_id != *program_id
+            || instruction.accounts.first().map(|meta| &meta.pubkey) != Some(reserve)
+        {
+            continue;
+        }
+        match TemplateInstruction::try_from_slice(&instruction.data) {
+            Ok(TemplateInstruction::FlashRepay) => return Ok(()),
+            Ok(TemplateInstruction::FlashBorrow { .. }) => {
+                return Err(TemplateError::FlashLoanInProgress.into())
+            }
+            _ => {}
+        }
+    }
+    Err(TemplateError::FlashRepayNotFound.into())
+}
+
+/// Loads an initialized reserve
+fn load_reserve(program_id: &Pubkey, reserve_info: &AccountInfo) -> Result<Reserve, ProgramError> {
+    check_account_owner(reserve_info, program_id)?;
+    let reserve = Reserve::try_from_slice(&reserve_info.data.borrow())?;
+    if !reserve.is_initialized {
+        return Err(TemplateError::ReserveNotInitialized.into());
+    }
+    Ok(reserve)
+}
+
+/// Loads a reserve for a deposit or withdrawal and checks its accounts
+///
+/// Liquidity cannot move while a flash loan is in progress, since the
+/// exchange rate does not include the fee the loan is about to pay.
+fn load_liquidity_reserve(
+    program_id: &Pubkey,
+    reserve_info: &AccountInfo,
+    reserve_authority_info: &AccountInfo,
+    liquidity_vault_info: &AccountInfo,
+    share_mint_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+) -> Result<Reserve, ProgramError> {
+    let reserve = load_reserve(program_id, reserve_info)?;
+    if reserve.has_flash_loan() {
+        return Err(TemplateError::FlashLoanInProgress.into());
+    }
+    check_liquidity_vault(&reserve, liquidity_vault_info, token_program_info)?;
+    check_reserve_authority(
+        program_id,
+        reserve_info.key,
+        reserve.bump_seed,
+        reserve_authority_info.key,
+    )?;
+    if *share_mint_info.key != reserve.share_mint {
+        return Err(TemplateError::InvalidShareMint.into());
+    }
+    Ok(reserve)
+}
+
+/// Checks the liquidity vault of a reserve and the token program
+fn check_liquidity_vault(
+    reserve: &Reserve,
+    liquidity_vault_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+) -> ProgramResult {
+    if *liquidity_vault_info.key != reserve.liquidity_vault {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    if *token_program_info.key != spl_token::id() {
+        return Err(TemplateError::InvalidTokenProgram.into());
+    }
+    Ok(())
+}
+
+/// Checks that a new vault is an empty token account of `mint` that `authority` controls
+fn check_vault(vault_info: &AccountInfo, mint: &Pubkey, authority: &Pubkey) -> ProgramResult {
+    check_account_owner(vault_info, &spl_token::id()).map_err(|_| TemplateError::InvalidVault)?;
+    let vault = spl_token::state::Account::unpack(&vault_info.data.borrow())?;
+    if vault.mint != *mint
+        || vault.owner != *authority
+        || vault.amount != 0
+        || vault.delegate.is_some()
+        || vault.close_authority.is_some()
+    {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    Ok(())
+}
+
+/// Transfers tokens from a user-owned account into a vault
+fn transfer_to_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            source_info.key,
+            vault_info.key,
+            owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            source_info.clone(),
+            vault_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+/// Transfers tokens out of a vault, signing as the reserve authority that owns it
+fn transfer_from_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    authority_seeds: &[&[u8]],
+    amount: u64,
+) -> ProgramResult {
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            vault_info.key,
+            destination_info.key,
+            authority_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            vault_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[authority_seeds],
+    )
+}
+
+/// Mints shares, signing as the reserve authority that controls the share mint
+fn mint_shares<'a>(
+    token_program_info: &AccountInfo<'a>,
+    share_mint_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    authority_seeds: &[&[u8]],
+    shares: u64,
+) -> ProgramResult {
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            token_program_info.key,
+            share_mint_info.key,
+            destination_info.key,
+            authority_info.key,
+            &[],
+            shares,
+        )?,
+        &[
+            share_mint_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[authority_seeds],
+    )
+}
+
+/// Burns shares from a user-owned account
+fn burn_shares<'a>(
+    token_program_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    share_mint_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    shares: u64,
+) -> ProgramResult {
+    invoke(
+        &spl_token::instruction::burn(
+            token_program_info.key,
+            source_info.key,
+            share_mint_info.key,
+            owner_info.key,
+            &[],
+            shares,
+        )?,
+        &[
+            source_info.clone(),
+            share_mint_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
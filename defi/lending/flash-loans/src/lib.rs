@@ -1,6 +1,10 @@
 //! Flash loan protocol for uncollateralized borrowing
 //!
-//! This program provides [TEMPLATE FUNCTIONALITY].
+//! This program lends the liquidity held in a reserve vault without
+//! collateral, for the length of one transaction. `FlashBorrow` reads the
+//! instructions sysvar and only pays out when a matching `FlashRepay` later
+//! in the same transaction returns the amount plus a fee in basis points,
+//! which stays in the vault for the reserve's liquidity providers.
 
 pub mod instructions;
 pub mod state;
@@ -13,7 +17,7 @@ pub mod entrypoint;
 // Export current sdk types for downstream users building with a different sdk version
 pub use solana_program;
 
-solana_program::declare_id!("flash-loans111111111111111111111111111111");
+solana_program::declare_id!("F1ash11111111111111111111111111111111111111");
 
 /// Program entrypoint's implementation
 pub fn process_instruction(
@@ -1,20 +1,109 @@
-//! State objects for template account
+//! State objects for flash loan reserves
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::pubkey::Pubkey;
 
-/// Template account data
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-pub struct TemplateAccount {
-    /// The account authority (owner)
-    pub authority: Pubkey,
-    
-    /// Example value 1
-    pub value1: u64,
-    
-    /// Example value 2
-    pub value2: u8,
-    
-    /// Is the account initialized
+use crate::errors::TemplateError;
+
+/// Denominator of flash loan fees
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Pool of one token lent out by flash loans
+///
+/// The liquidity is held by a vault owned by the reserve authority PDA.
+/// Liquidity providers hold shares of `total_liquidity`, minted by the share
+/// mint whose authority is the same PDA, so repaid fees raise the liquidity
+/// every share redeems for.
+///
+/// While a flash loan is in progress `flash_loan_amount` records what was
+/// borrowed, so the matching repay knows what it owes and no other flash
+/// loan can start from the reserve until it is repaid.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct Reserve {
+    /// Is the reserve initialized
     pub is_initialized: bool,
+
+    /// Bump seed of the reserve authority PDA
+    pub bump_seed: u8,
+
+    /// Admin allowed to change the flash loan fee
+    pub admin: Pubkey,
+
+    /// Mint of the token lent out
+    pub liquidity_mint: Pubkey,
+
+    /// Token account holding the liquidity, owned by the reserve authority
+    pub liquidity_vault: Pubkey,
+
+    /// Mint of the reserve's share tokens, whose mint authority is the
+    /// reserve authority
+    pub share_mint: Pubkey,
+
+    /// Fee charged on every flash loan, in basis points of the amount borrowed
+    pub flash_loan_fee_bps: u64,
+
+    /// Liquidity owed to share holders: deposits and repaid fees less
+    /// withdrawals. Tokens sent to the vault directly are not counted.
+    pub total_liquidity: u64,
+
+    /// Amount borrowed by the flash loan in progress, zero when none is
+    pub flash_loan_amount: u64,
+
+    /// Flash loan fees repaid into the vault in total
+    pub cumulative_fees: u64,
+}
+
+impl Reserve {
+    /// Get the size of Reserve struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + bump_seed (1 byte) +
+        // admin, liquidity_mint, liquidity_vault and share_mint
+        // (32 bytes * 4) + flash_loan_fee_bps (8 bytes) +
+        // total_liquidity (8 bytes) + flash_loan_amount (8 bytes) +
+        // cumulative_fees (8 bytes)
+        1 + 1 + 32 * 4 + 8 + 8 + 8 + 8
+    }
+
+    /// Whether a flash loan from the reserve is in progress
+    pub fn has_flash_loan(&self) -> bool {
+        self.flash_loan_amount > 0
+    }
+
+    /// Fee owed on a flash loan of `amount`, rounded up
+    pub fn flash_loan_fee(&self, amount: u64) -> u64 {
+        let product = amount as u128 * self.flash_loan_fee_bps as u128;
+        product.div_ceil(BPS_DENOMINATOR as u128) as u64
+    }
+
+    /// Shares minted for a deposit of `amount` when `share_supply` shares
+    /// exist, rounded down
+    ///
+    /// The first deposit, and any deposit into a reserve without shares,
+    /// mints one share per base unit of liquidity.
+    pub fn shares_for_deposit(&self, amount: u64, share_supply: u64) -> Result<u64, TemplateError> {
+        if share_supply == 0 || self.total_liquidity == 0 {
+            return Ok(amount);
+        }
+        mul_div_floor(amount, share_supply, self.total_liquidity)
+    }
+
+    /// Liquidity paid out for redeeming `shares` of `share_supply`, rounded down
+    pub fn liquidity_for_shares(
+        &self,
+        shares: u64,
+        share_supply: u64,
+    ) -> Result<u64, TemplateError> {
+        if shares > share_supply {
+            return Err(TemplateError::MathOverflow);
+        }
+        mul_div_floor(shares, self.total_liquidity, share_supply)
+    }
+}
+
+/// `a * b / denominator`, rounded down
+fn mul_div_floor(a: u64, b: u64, denominator: u64) -> Result<u64, TemplateError> {
+    let quotient = (a as u128 * b as u128)
+        .checked_div(denominator as u128)
+        .ok_or(TemplateError::MathOverflow)?;
+    u64::try_from(quotient).map_err(|_| TemplateError::MathOverflow)
 }
@@ -1,9 +1,7 @@
 //! Utility functions for the program
 
 use solana_program::{
-    account_info::AccountInfo,
-    entrypoint::ProgramResult,
-    program_error::ProgramError,
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
     pubkey::Pubkey,
 };
 
@@ -31,3 +29,23 @@ pub fn check_signer(account_info: &AccountInfo) -> ProgramResult {
 pub fn safe_addition(a: u64, b: u64) -> Result<u64, TemplateError> {
     a.checked_add(b).ok_or(TemplateError::MathOverflow)
 }
+
+/// Finds the reserve authority PDA that owns the liquidity vault
+pub fn find_reserve_authority(program_id: &Pubkey, reserve: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[reserve.as_ref()], program_id)
+}
+
+/// Checks that `authority` is the reserve authority PDA for the given bump seed
+pub fn check_reserve_authority(
+    program_id: &Pubkey,
+    reserve: &Pubkey,
+    bump_seed: u8,
+    authority: &Pubkey,
+) -> ProgramResult {
+    let expected = Pubkey::create_program_address(&[reserve.as_ref(), &[bump_seed]], program_id)
+        .map_err(|_| TemplateError::InvalidReserveAuthority)?;
+    if expected != *authority {
+        return Err(TemplateError::InvalidReserveAuthority.into());
+    }
+    Ok(())
+}
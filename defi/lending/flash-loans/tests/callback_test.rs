@@ -0,0 +1,375 @@
+//! Integration tests for callback flash loans in flash-loans
+
+mod common;
+mod flash_loan_receiver;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            common::{
+                create_mint, create_token_account, custom_error, get_reserve, mint_to, process,
+                process_cpi_forwarder, through_cpi, token_balance, BORROWER_FUNDS,
+                FLASH_LOAN_FEE_BPS, USDC, VAULT_LIQUIDITY,
+            },
+            flash_loan_receiver::{self, find_receiver_authority, repay_callback},
+        },
+        flash_loans::{
+            errors::TemplateError,
+            instructions::{deposit, flash_borrow, flash_loan, flash_repay, initialize_reserve},
+            process_instruction,
+            state::Reserve,
+            utils::find_reserve_authority,
+        },
+        solana_program::{
+            instruction::Instruction, pubkey::Pubkey, rent::Rent, system_instruction,
+        },
+        solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestContext},
+        solana_sdk::signature::{Keypair, Signer},
+    };
+
+    /// Accounts of an initialized reserve and of the receiver program
+    struct ReserveFixture {
+        program_id: Pubkey,
+        forwarder_id: Pubkey,
+        receiver_id: Pubkey,
+        reserve: Pubkey,
+        reserve_authority: Pubkey,
+        liquidity_mint: Pubkey,
+        liquidity_vault: Pubkey,
+        /// Token account of the receiver's PDA, holding `BORROWER_FUNDS`
+        receiver_liquidity: Pubkey,
+    }
+
+    /// A borrower holding `BORROWER_FUNDS` of the liquidity token
+    struct Borrower {
+        keypair: Keypair,
+        liquidity: Pubkey,
+    }
+
+    impl ReserveFixture {
+        /// Lends `amount` to the receiver, whose callback repays `repay_amount`
+        fn flash_loan(&self, amount: u64, repay_amount: u64) -> Instruction {
+            let (callback_data, receiver_accounts) = repay_callback(
+                &self.receiver_id,
+                &self.receiver_liquidity,
+                &self.liquidity_vault,
+                repay_amount,
+            );
+            flash_loan(
+                &self.program_id,
+                &self.reserve,
+                &self.liquidity_vault,
+                &self.reserve_authority,
+                &self.receiver_liquidity,
+                &self.receiver_id,
+                &receiver_accounts,
+                amount,
+                callback_data,
+            )
+        }
+
+        fn borrow(&self, borrower: &Borrower, amount: u64) -> Instruction {
+            flash_borrow(
+                &self.program_id,
+                &self.reserve,
+                &self.liquidity_vault,
+                &self.reserve_authority,
+                &borrower.liquidity,
+                amount,
+            )
+        }
+
+        fn repay(&self, borrower: &Borrower) -> Instruction {
+            flash_repay(
+                &self.program_id,
+                &self.reserve,
+                &self.liquidity_vault,
+                &borrower.liquidity,
+                &borrower.keypair.pubkey(),
+            )
+        }
+
+        /// Wraps `instruction` in a call to the CPI forwarder
+        fn through_cpi(&self, instruction: Instruction) -> Instruction {
+            through_cpi(&self.forwarder_id, instruction)
+        }
+    }
+
+    /// Starts a test validator with the receiver program and an initialized
+    /// reserve into which the payer has deposited `VAULT_LIQUIDITY`. The
+    /// payer mints the liquidity token.
+    async fn start_with_reserve() -> (ProgramTestContext, Keypair, ReserveFixture) {
+        let program_id = Pubkey::new_unique();
+        let forwarder_id = Pubkey::new_unique();
+        let receiver_id = Pubkey::new_unique();
+        let mut program_test =
+            ProgramTest::new("flash_loans", program_id, processor!(process_instruction));
+        program_test.add_program(
+            "cpi_forwarder",
+            forwarder_id,
+            processor!(process_cpi_forwarder),
+        );
+        program_test.add_program(
+            "flash_loan_receiver",
+            receiver_id,
+            processor!(flash_loan_receiver::process_instruction),
+        );
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let banks_client = &mut context.banks_client;
+
+        let reserve = Keypair::new();
+        let (reserve_authority, _) = find_reserve_authority(&program_id, &reserve.pubkey());
+        let liquidity_mint = create_mint(banks_client, &payer, &payer.pubkey(), 6).await;
+        let liquidity_vault =
+            create_token_account(banks_client, &payer, &liquidity_mint, &reserve_authority).await;
+        let share_mint = create_mint(banks_client, &payer, &reserve_authority, 6).await;
+
+        let rent = Rent::default();
+        process(
+            banks_client,
+            &payer,
+            &[
+                system_instruction::create_account(
+                    &payer.pubkey(),
+                    &reserve.pubkey(),
+                    rent.minimum_balance(Reserve::get_size()),
+                    Reserve::get_size() as u64,
+                    &program_id,
+                ),
+                initialize_reserve(
+                    &program_id,
+                    &payer.pubkey(),
+                    &reserve.pubkey(),
+                    &reserve_authority,
+                    &liquidity_mint,
+                    &liquidity_vault,
+                    &share_mint,
+                    FLASH_LOAN_FEE_BPS,
+                ),
+            ],
+            &[&reserve],
+        )
+        .await
+        .unwrap();
+
+        // Seed the vault through a deposit of the payer
+        let liquidity =
+            create_token_account(banks_client, &payer, &liquidity_mint, &payer.pubkey()).await;
+        let shares = create_token_account(banks_client, &payer, &share_mint, &payer.pubkey()).await;
+        mint_to(
+            banks_client,
+            &payer,
+            &liquidity_mint,
+            &liquidity,
+            VAULT_LIQUIDITY,
+        )
+        .await;
+        process(
+            banks_client,
+            &payer,
+            &[deposit(
+                &program_id,
+                &payer.pubkey(),
+                &reserve.pubkey(),
+                &reserve_authority,
+                &liquidity_vault,
+                &share_mint,
+                &liquidity,
+                &shares,
+                VAULT_LIQUIDITY,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        // Give the receiver the liquidity to pay fees with
+        let (receiver_authority, _) = find_receiver_authority(&receiver_id);
+        let receiver_liquidity =
+            create_token_account(banks_client, &payer, &liquidity_mint, &receiver_authority).await;
+        mint_to(
+            banks_client,
+            &payer,
+            &liquidity_mint,
+            &receiver_liquidity,
+            BORROWER_FUNDS,
+        )
+        .await;
+
+        let fixture = ReserveFixture {
+            program_id,
+            forwarder_id,
+            receiver_id,
+            reserve: reserve.pubkey(),
+            reserve_authority,
+            liquidity_mint,
+            liquidity_vault,
+            receiver_liquidity,
+        };
+        (context, payer, fixture)
+    }
+
+    /// Creates a borrower holding `BORROWER_FUNDS` of the liquidity token
+    async fn create_borrower(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &ReserveFixture,
+    ) -> Borrower {
+        let keypair = Keypair::new();
+        let liquidity = create_token_account(
+            banks_client,
+            payer,
+            &fixture.liquidity_mint,
+            &keypair.pubkey(),
+        )
+        .await;
+        mint_to(
+            banks_client,
+            payer,
+            &fixture.liquidity_mint,
+            &liquidity,
+            BORROWER_FUNDS,
+        )
+        .await;
+        Borrower { keypair, liquidity }
+    }
+
+    #[tokio::test]
+    async fn test_flash_loan_callback() {
+        let (mut context, payer, fixture) = start_with_reserve().await;
+        let banks_client = &mut context.banks_client;
+
+        // 0.09% of 500 is 0.45
+        let amount = 500 * USDC;
+        let fee = 450_000;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.flash_loan(amount, amount + fee)],
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.liquidity_vault).await,
+            VAULT_LIQUIDITY + fee
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.receiver_liquidity).await,
+            BORROWER_FUNDS - fee
+        );
+        let reserve = get_reserve(banks_client, &fixture.reserve).await;
+        assert!(!reserve.has_flash_loan());
+        assert_eq!(reserve.total_liquidity, VAULT_LIQUIDITY + fee);
+        assert_eq!(reserve.cumulative_fees, fee);
+
+        // Unlike the introspection mode, a program can take the loan through CPI
+        process(
+            banks_client,
+            &payer,
+            &[fixture.through_cpi(fixture.flash_loan(amount, amount + fee))],
+            &[],
+        )
+        .await
+        .unwrap();
+        let reserve = get_reserve(banks_client, &fixture.reserve).await;
+        assert_eq!(reserve.total_liquidity, VAULT_LIQUIDITY + 2 * fee);
+        assert_eq!(reserve.cumulative_fees, 2 * fee);
+
+        // Repaying more than owed succeeds, but only the fee is owed to the
+        // share holders
+        process(
+            banks_client,
+            &payer,
+            &[fixture.flash_loan(amount, amount + fee + USDC)],
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &fixture.liquidity_vault).await,
+            VAULT_LIQUIDITY + 3 * fee + USDC
+        );
+        let reserve = get_reserve(banks_client, &fixture.reserve).await;
+        assert_eq!(reserve.total_liquidity, VAULT_LIQUIDITY + 3 * fee);
+        assert_eq!(reserve.cumulative_fees, 3 * fee);
+    }
+
+    #[tokio::test]
+    async fn test_flash_loan_callback_must_repay() {
+        let (mut context, payer, fixture) = start_with_reserve().await;
+        let banks_client = &mut context.banks_client;
+        let amount = 500 * USDC;
+        let fee = 450_000;
+
+        // Keeping the loan, or one base unit of the fee, fails the loan
+        for repay_amount in [0, amount, amount + fee - 1] {
+            assert_eq!(
+                process(
+                    banks_client,
+                    &payer,
+                    &[fixture.flash_loan(amount, repay_amount)],
+                    &[],
+                )
+                .await
+                .unwrap_err(),
+                custom_error(0, TemplateError::FlashLoanNotRepaid)
+            );
+        }
+        assert_eq!(
+            token_balance(banks_client, &fixture.liquidity_vault).await,
+            VAULT_LIQUIDITY
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.receiver_liquidity).await,
+            BORROWER_FUNDS
+        );
+        let reserve = get_reserve(banks_client, &fixture.reserve).await;
+        assert!(!reserve.has_flash_loan());
+        assert_eq!(reserve.cumulative_fees, 0);
+    }
+
+    #[tokio::test]
+    async fn test_flash_loan_callback_checks() {
+        let (mut context, payer, fixture) = start_with_reserve().await;
+        let banks_client = &mut context.banks_client;
+        let borrower = create_borrower(banks_client, &payer, &fixture).await;
+
+        assert_eq!(
+            process(banks_client, &payer, &[fixture.flash_loan(0, 0)], &[])
+                .await
+                .unwrap_err(),
+            custom_error(0, TemplateError::ZeroAmount)
+        );
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.flash_loan(VAULT_LIQUIDITY + 1, VAULT_LIQUIDITY + 1)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InsufficientLiquidity)
+        );
+
+        // The two modes share the reserve's single loan in progress
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.borrow(&borrower, 100 * USDC),
+                    fixture.flash_loan(100 * USDC, 100 * USDC + 90_000),
+                    fixture.repay(&borrower),
+                ],
+                &[&borrower.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(1, TemplateError::FlashLoanInProgress)
+        );
+    }
+}
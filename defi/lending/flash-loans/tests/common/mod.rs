@@ -0,0 +1,213 @@
+//! Fixtures shared by the flash-loans integration tests
+//!
+//! Every test binary compiles this module but uses only part of it.
+
+#![allow(dead_code)]
+
+use {
+    borsh::BorshDeserialize,
+    flash_loans::{errors::TemplateError, state::Reserve},
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        program::invoke,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        rent::Rent,
+        system_instruction,
+    },
+    solana_program_test::BanksClient,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+/// One whole liquidity token, with 6 decimals
+pub const USDC: u64 = 1_000_000;
+/// Flash loans cost 0.09% of the amount borrowed
+pub const FLASH_LOAN_FEE_BPS: u64 = 9;
+/// Liquidity seeded into the vault
+pub const VAULT_LIQUIDITY: u64 = 1_000 * USDC;
+/// Tokens the receiver and borrowers hold to pay fees with
+pub const BORROWER_FUNDS: u64 = 10 * USDC;
+/// Tokens every liquidity provider starts with
+pub const USER_FUNDS: u64 = 10_000 * USDC;
+
+/// Forwards its instruction data and remaining accounts to the program in
+/// its first account, to call the flash-loans program through CPI
+pub fn process_cpi_forwarder(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let (program_info, forwarded) = accounts.split_first().unwrap();
+    let instruction = Instruction {
+        program_id: *program_info.key,
+        accounts: forwarded
+            .iter()
+            .map(|info| AccountMeta {
+                pubkey: *info.key,
+                is_signer: info.is_signer,
+                is_writable: info.is_writable,
+            })
+            .collect(),
+        data: instruction_data.to_vec(),
+    };
+    invoke(&instruction, accounts)
+}
+
+/// Wraps `instruction` in a call to the CPI forwarder at `forwarder_id`
+pub fn through_cpi(forwarder_id: &Pubkey, instruction: Instruction) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(instruction.program_id, false)];
+    accounts.extend(instruction.accounts);
+    Instruction {
+        program_id: *forwarder_id,
+        accounts,
+        data: instruction.data,
+    }
+}
+
+pub async fn process(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &all_signers,
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+pub async fn create_mint(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint_authority: &Pubkey,
+    decimals: u8,
+) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                &mint.pubkey(),
+                mint_authority,
+                None,
+                decimals,
+            )
+            .unwrap(),
+        ],
+        &[&mint],
+    )
+    .await
+    .unwrap();
+    mint.pubkey()
+}
+
+pub async fn create_token_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Pubkey {
+    let account = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &account.pubkey(),
+                mint,
+                owner,
+            )
+            .unwrap(),
+        ],
+        &[&account],
+    )
+    .await
+    .unwrap();
+    account.pubkey()
+}
+
+pub async fn mint_to(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint: &Pubkey,
+    account: &Pubkey,
+    amount: u64,
+) {
+    process(
+        banks_client,
+        payer,
+        &[spl_token::instruction::mint_to(
+            &spl_token::id(),
+            mint,
+            account,
+            &payer.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap()],
+        &[],
+    )
+    .await
+    .unwrap();
+}
+
+pub async fn token_balance(banks_client: &mut BanksClient, account: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*account).await.unwrap().unwrap();
+    spl_token::state::Account::unpack(&account.data)
+        .unwrap()
+        .amount
+}
+
+pub async fn share_supply(banks_client: &mut BanksClient, share_mint: &Pubkey) -> u64 {
+    let account = banks_client
+        .get_account(*share_mint)
+        .await
+        .unwrap()
+        .unwrap();
+    spl_token::state::Mint::unpack(&account.data)
+        .unwrap()
+        .supply
+}
+
+pub async fn get_reserve(banks_client: &mut BanksClient, reserve: &Pubkey) -> Reserve {
+    let account = banks_client.get_account(*reserve).await.unwrap().unwrap();
+    Reserve::try_from_slice(&account.data).unwrap()
+}
+
+/// Error of the instruction at `index` failing with `error`
+pub fn custom_error(index: u8, error: TemplateError) -> TransactionError {
+    TransactionError::InstructionError(index, InstructionError::Custom(error as u32))
+}
@@ -0,0 +1,110 @@
+//! Sample receiver program for callback flash loans
+//!
+//! The flash-loans program invokes the receiver with the `callback_data` of
+//! a `FlashLoan` as its instruction data, right after lending the liquidity
+//! to the receiver's token account. A real receiver would put the loan to
+//! work here, for an arbitrage or a liquidation, before paying it back. This
+//! one only repays, from a token account owned by its own PDA, which lets
+//! the tests repay in full, too little or too much.
+
+use {
+    borsh::{BorshDeserialize, BorshSerialize},
+    solana_program::{
+        account_info::{next_account_info, AccountInfo},
+        entrypoint::ProgramResult,
+        instruction::AccountMeta,
+        msg,
+        program::invoke_signed,
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+};
+
+/// Seed of the PDA owning the receiver's token account
+const AUTHORITY_SEED: &[u8] = b"receiver";
+
+/// Callbacks understood by the receiver program
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub enum ReceiverInstruction {
+    /// Transfer `amount` from the receiver's token account to the vault
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The receiver's token account
+    /// 1. `[]` The receiver authority PDA
+    /// 2. `[writable]` The liquidity vault
+    /// 3. `[]` The token program
+    ///
+    Repay {
+        /// Amount sent back to the vault
+        amount: u64,
+    },
+}
+
+/// Finds the PDA that owns the receiver's token account
+pub fn find_receiver_authority(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AUTHORITY_SEED], program_id)
+}
+
+/// Callback data and receiver accounts of a callback repaying `amount`
+pub fn repay_callback(
+    program_id: &Pubkey,
+    receiver_liquidity: &Pubkey,
+    liquidity_vault: &Pubkey,
+    amount: u64,
+) -> (Vec<u8>, Vec<AccountMeta>) {
+    let (authority, _) = find_receiver_authority(program_id);
+    let accounts = vec![
+        AccountMeta::new(*receiver_liquidity, false),
+        AccountMeta::new_readonly(authority, false),
+        AccountMeta::new(*liquidity_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    let data = borsh::to_vec(&ReceiverInstruction::Repay { amount }).unwrap();
+    (data, accounts)
+}
+
+/// Processes a callback from the flash-loans program
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let instruction = ReceiverInstruction::try_from_slice(instruction_data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    match instruction {
+        ReceiverInstruction::Repay { amount } => {
+            msg!("Receiver: Repay");
+            let account_info_iter = &mut accounts.iter();
+
+            // Get accounts
+            let source_info = next_account_info(account_info_iter)?;
+            let authority_info = next_account_info(account_info_iter)?;
+            let vault_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+
+            let (authority, bump_seed) = find_receiver_authority(program_id);
+            if authority != *authority_info.key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    token_program_info.key,
+                    source_info.key,
+                    vault_info.key,
+                    authority_info.key,
+                    &[],
+                    amount,
+                )?,
+                &[
+                    source_info.clone(),
+                    vault_info.clone(),
+                    authority_info.clone(),
+                    token_program_info.clone(),
+                ],
+                &[&[AUTHORITY_SEED, &[bump_seed]]],
+            )
+        }
+    }
+}
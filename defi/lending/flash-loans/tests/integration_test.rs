@@ -1,41 +1,599 @@
 //! Integration tests for flash-loans
 
+mod common;
+
 #[cfg(test)]
 mod tests {
     use {
-        borsh::BorshSerialize,
-        solana_program::{
-            instruction::{AccountMeta, Instruction},
-            pubkey::Pubkey,
-            rent::Rent,
-            system_instruction,
-        },
-        solana_program_test::{processor, ProgramTest},
-        solana_sdk::{
-            account::Account,
-            signature::{Keypair, Signer},
-            transaction::Transaction,
+        super::common::{
+            create_mint, create_token_account, custom_error, get_reserve, mint_to, process,
+            process_cpi_forwarder, through_cpi, token_balance, BORROWER_FUNDS, FLASH_LOAN_FEE_BPS,
+            USDC, VAULT_LIQUIDITY,
         },
-        flash-loans::{
-            instructions::TemplateInstruction,
+        flash_loans::{
+            errors::TemplateError,
+            instructions::{
+                deposit, flash_borrow, flash_repay, initialize_reserve, set_flash_loan_fee,
+            },
             process_instruction,
-            state::TemplateAccount,
+            state::Reserve,
+            utils::find_reserve_authority,
         },
-        std::str::FromStr,
+        solana_program::{
+            instruction::Instruction, pubkey::Pubkey, rent::Rent, system_instruction,
+        },
+        solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestContext},
+        solana_sdk::signature::{Keypair, Signer},
     };
 
+    /// Accounts of an initialized reserve
+    struct ReserveFixture {
+        program_id: Pubkey,
+        forwarder_id: Pubkey,
+        reserve: Pubkey,
+        reserve_authority: Pubkey,
+        liquidity_mint: Pubkey,
+        liquidity_vault: Pubkey,
+        share_mint: Pubkey,
+    }
+
+    /// A borrower holding `BORROWER_FUNDS` of the liquidity token
+    struct Borrower {
+        keypair: Keypair,
+        liquidity: Pubkey,
+    }
+
+    impl ReserveFixture {
+        fn borrow(&self, borrower: &Borrower, amount: u64) -> Instruction {
+            flash_borrow(
+                &self.program_id,
+                &self.reserve,
+                &self.liquidity_vault,
+                &self.reserve_authority,
+                &borrower.liquidity,
+                amount,
+            )
+        }
+
+        fn repay(&self, borrower: &Borrower) -> Instruction {
+            flash_repay(
+                &self.program_id,
+                &self.reserve,
+                &self.liquidity_vault,
+                &borrower.liquidity,
+                &borrower.keypair.pubkey(),
+            )
+        }
+
+        /// Wraps `instruction` in a call to the CPI forwarder
+        fn through_cpi(&self, instruction: Instruction) -> Instruction {
+            through_cpi(&self.forwarder_id, instruction)
+        }
+    }
+
+    fn initialize_instruction(
+        fixture: &ReserveFixture,
+        admin: &Pubkey,
+        flash_loan_fee_bps: u64,
+    ) -> Instruction {
+        initialize_reserve(
+            &fixture.program_id,
+            admin,
+            &fixture.reserve,
+            &fixture.reserve_authority,
+            &fixture.liquidity_mint,
+            &fixture.liquidity_vault,
+            &fixture.share_mint,
+            flash_loan_fee_bps,
+        )
+    }
+
+    /// Starts a test validator with the reserve account created but not
+    /// initialized. The payer mints the liquidity token.
+    async fn start_with_reserve_account() -> (ProgramTestContext, Keypair, ReserveFixture) {
+        let program_id = Pubkey::new_unique();
+        let forwarder_id = Pubkey::new_unique();
+        let mut program_test =
+            ProgramTest::new("flash_loans", program_id, processor!(process_instruction));
+        program_test.add_program(
+            "cpi_forwarder",
+            forwarder_id,
+            processor!(process_cpi_forwarder),
+        );
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let banks_client = &mut context.banks_client;
+
+        let reserve = Keypair::new();
+        let (reserve_authority, _) = find_reserve_authority(&program_id, &reserve.pubkey());
+        let liquidity_mint = create_mint(banks_client, &payer, &payer.pubkey(), 6).await;
+        let liquidity_vault =
+            create_token_account(banks_client, &payer, &liquidity_mint, &reserve_authority).await;
+        let share_mint = create_mint(banks_client, &payer, &reserve_authority, 6).await;
+
+        let rent = Rent::default();
+        process(
+            banks_client,
+            &payer,
+            &[system_instruction::create_account(
+                &payer.pubkey(),
+                &reserve.pubkey(),
+                rent.minimum_balance(Reserve::get_size()),
+                Reserve::get_size() as u64,
+                &program_id,
+            )],
+            &[&reserve],
+        )
+        .await
+        .unwrap();
+
+        let fixture = ReserveFixture {
+            program_id,
+            forwarder_id,
+            reserve: reserve.pubkey(),
+            reserve_authority,
+            liquidity_mint,
+            liquidity_vault,
+            share_mint,
+        };
+        (context, payer, fixture)
+    }
+
+    /// Starts a test validator with an initialized reserve into which the
+    /// payer has deposited `VAULT_LIQUIDITY`
+    async fn start_with_reserve() -> (ProgramTestContext, Keypair, ReserveFixture) {
+        let (mut context, payer, fixture) = start_with_reserve_account().await;
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[initialize_instruction(
+                &fixture,
+                &payer.pubkey(),
+                FLASH_LOAN_FEE_BPS,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+        let liquidity = create_token_account(
+            banks_client,
+            &payer,
+            &fixture.liquidity_mint,
+            &payer.pubkey(),
+        )
+        .await;
+        let shares =
+            create_token_account(banks_client, &payer, &fixture.share_mint, &payer.pubkey()).await;
+        mint_to(
+            banks_client,
+            &payer,
+            &fixture.liquidity_mint,
+            &liquidity,
+            VAULT_LIQUIDITY,
+        )
+        .await;
+        process(
+            banks_client,
+            &payer,
+            &[deposit(
+                &fixture.program_id,
+                &payer.pubkey(),
+                &fixture.reserve,
+                &fixture.reserve_authority,
+                &fixture.liquidity_vault,
+                &fixture.share_mint,
+                &liquidity,
+                &shares,
+                VAULT_LIQUIDITY,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+        (context, payer, fixture)
+    }
+
+    /// Creates a borrower holding `BORROWER_FUNDS` of the liquidity token
+    async fn create_borrower(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &ReserveFixture,
+    ) -> Borrower {
+        let keypair = Keypair::new();
+        let liquidity = create_token_account(
+            banks_client,
+            payer,
+            &fixture.liquidity_mint,
+            &keypair.pubkey(),
+        )
+        .await;
+        mint_to(
+            banks_client,
+            payer,
+            &fixture.liquidity_mint,
+            &liquidity,
+            BORROWER_FUNDS,
+        )
+        .await;
+        Borrower { keypair, liquidity }
+    }
+
+    #[tokio::test]
+    async fn test_initialize_reserve() {
+        let (mut context, payer, fixture) = start_with_reserve_account().await;
+        let banks_client = &mut context.banks_client;
+
+        // The fee cannot exceed the amount borrowed
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[initialize_instruction(&fixture, &payer.pubkey(), 10_001)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidReserveParameters)
+        );
+
+        // The reserve authority must control the share mint
+        let foreign_mint = create_mint(banks_client, &payer, &payer.pubkey(), 6).await;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[initialize_reserve(
+                    &fixture.program_id,
+                    &payer.pubkey(),
+                    &fixture.reserve,
+                    &fixture.reserve_authority,
+                    &fixture.liquidity_mint,
+                    &fixture.liquidity_vault,
+                    &foreign_mint,
+                    FLASH_LOAN_FEE_BPS,
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidShareMint)
+        );
+
+        process(
+            banks_client,
+            &payer,
+            &[initialize_instruction(
+                &fixture,
+                &payer.pubkey(),
+                FLASH_LOAN_FEE_BPS,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+        let reserve = get_reserve(banks_client, &fixture.reserve).await;
+        assert!(reserve.is_initialized);
+        assert_eq!(reserve.admin, payer.pubkey());
+        assert_eq!(reserve.liquidity_mint, fixture.liquidity_mint);
+        assert_eq!(reserve.liquidity_vault, fixture.liquidity_vault);
+        assert_eq!(reserve.share_mint, fixture.share_mint);
+        assert_eq!(reserve.flash_loan_fee_bps, FLASH_LOAN_FEE_BPS);
+        assert_eq!(reserve.total_liquidity, 0);
+        assert!(!reserve.has_flash_loan());
+
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[initialize_instruction(&fixture, &payer.pubkey(), 0)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::ReserveAlreadyInitialized)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flash_borrow_and_repay() {
+        let (mut context, payer, fixture) = start_with_reserve().await;
+        let banks_client = &mut context.banks_client;
+        let borrower = create_borrower(banks_client, &payer, &fixture).await;
+
+        // The borrower holds the loan between the borrow and the repay, so
+        // it can move 500 it would not have otherwise. Borrowing 500 costs a
+        // fee of 0.45, which stays in the vault.
+        let spend = spl_token::instruction::transfer(
+            &spl_token::id(),
+            &borrower.liquidity,
+            &borrower.liquidity,
+            &borrower.keypair.pubkey(),
+            &[],
+            500 * USDC,
+        )
+        .unwrap();
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.borrow(&borrower, 500 * USDC),
+                spend,
+                fixture.repay(&borrower),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        let fee = 450_000;
+        assert_eq!(
+            token_balance(banks_client, &fixture.liquidity_vault).await,
+            VAULT_LIQUIDITY + fee
+        );
+        assert_eq!(
+            token_balance(banks_client, &borrower.liquidity).await,
+            BORROWER_FUNDS - fee
+        );
+        let reserve = get_reserve(banks_client, &fixture.reserve).await;
+        assert!(!reserve.has_flash_loan());
+        assert_eq!(reserve.cumulative_fees, fee);
+        assert_eq!(reserve.total_liquidity, VAULT_LIQUIDITY + fee);
+
+        // The fee rounds up, so even the smallest loan pays one base unit,
+        // and the whole vault can be borrowed
+        process(
+            banks_client,
+            &payer,
+            &[fixture.borrow(&borrower, 1), fixture.repay(&borrower)],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        let liquidity = VAULT_LIQUIDITY + fee + 1;
+        assert_eq!(
+            token_balance(banks_client, &fixture.liquidity_vault).await,
+            liquidity
+        );
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.borrow(&borrower, liquidity),
+                fixture.repay(&borrower),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            get_reserve(banks_client, &fixture.reserve)
+                .await
+                .cumulative_fees,
+            fee + 1 + 900_406
+        );
+    }
+
     #[tokio::test]
-    async fn test_initialize() {
-        // TODO: Implement test logic for initialization
+    async fn test_flash_borrow_requires_repay() {
+        let (mut context, payer, fixture) = start_with_reserve().await;
+        let banks_client = &mut context.banks_client;
+        let borrower = create_borrower(banks_client, &payer, &fixture).await;
+
+        // A borrow with no repay after it
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.borrow(&borrower, 100 * USDC)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::FlashRepayNotFound)
+        );
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.repay(&borrower),
+                    fixture.borrow(&borrower, 100 * USDC),
+                ],
+                &[&borrower.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::NoFlashLoanInProgress)
+        );
+
+        // A second borrow before the repay
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.borrow(&borrower, 100 * USDC),
+                    fixture.borrow(&borrower, 200 * USDC),
+                    fixture.repay(&borrower),
+                ],
+                &[&borrower.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::FlashLoanInProgress)
+        );
+
+        // More than the vault holds
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.borrow(&borrower, VAULT_LIQUIDITY + 1),
+                    fixture.repay(&borrower),
+                ],
+                &[&borrower.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InsufficientLiquidity)
+        );
+
+        // A borrower who cannot pay the fee fails the whole transaction
+        let broke = create_borrower(banks_client, &payer, &fixture).await;
+        let burn = spl_token::instruction::burn(
+            &spl_token::id(),
+            &broke.liquidity,
+            &fixture.liquidity_mint,
+            &broke.keypair.pubkey(),
+            &[],
+            BORROWER_FUNDS,
+        )
+        .unwrap();
+        process(banks_client, &payer, &[burn], &[&broke.keypair])
+            .await
+            .unwrap();
+        assert!(process(
+            banks_client,
+            &payer,
+            &[fixture.borrow(&broke, 100 * USDC), fixture.repay(&broke)],
+            &[&broke.keypair],
+        )
+        .await
+        .is_err());
+        assert_eq!(
+            token_balance(banks_client, &fixture.liquidity_vault).await,
+            VAULT_LIQUIDITY
+        );
+        assert_eq!(token_balance(banks_client, &broke.liquidity).await, 0);
+        assert!(!get_reserve(banks_client, &fixture.reserve)
+            .await
+            .has_flash_loan());
     }
 
     #[tokio::test]
-    async fn test_operation1() {
-        // TODO: Implement test logic for operation1
+    async fn test_flash_loan_rejects_cpi() {
+        let (mut context, payer, fixture) = start_with_reserve().await;
+        let banks_client = &mut context.banks_client;
+        let borrower = create_borrower(banks_client, &payer, &fixture).await;
+
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.through_cpi(fixture.borrow(&borrower, 100 * USDC)),
+                    fixture.repay(&borrower),
+                ],
+                &[&borrower.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::FlashLoanCpi)
+        );
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.borrow(&borrower, 100 * USDC),
+                    fixture.through_cpi(fixture.repay(&borrower)),
+                    fixture.repay(&borrower),
+                ],
+                &[&borrower.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(1, TemplateError::FlashLoanCpi)
+        );
     }
 
     #[tokio::test]
-    async fn test_operation2() {
-        // TODO: Implement test logic for operation2
+    async fn test_set_flash_loan_fee() {
+        let (mut context, payer, fixture) = start_with_reserve().await;
+        let banks_client = &mut context.banks_client;
+        let borrower = create_borrower(banks_client, &payer, &fixture).await;
+
+        // Only the admin can change the fee
+        let stranger = Keypair::new();
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[set_flash_loan_fee(
+                    &fixture.program_id,
+                    &stranger.pubkey(),
+                    &fixture.reserve,
+                    0,
+                )],
+                &[&stranger],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidAuthority)
+        );
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[set_flash_loan_fee(
+                    &fixture.program_id,
+                    &payer.pubkey(),
+                    &fixture.reserve,
+                    10_001,
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidReserveParameters)
+        );
+
+        // Nor while a loan is in progress
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.borrow(&borrower, 100 * USDC),
+                    set_flash_loan_fee(&fixture.program_id, &payer.pubkey(), &fixture.reserve, 0),
+                    fixture.repay(&borrower),
+                ],
+                &[&borrower.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(1, TemplateError::FlashLoanInProgress)
+        );
+
+        // At 1% a loan of 100 costs 1
+        process(
+            banks_client,
+            &payer,
+            &[set_flash_loan_fee(
+                &fixture.program_id,
+                &payer.pubkey(),
+                &fixture.reserve,
+                100,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.borrow(&borrower, 100 * USDC),
+                fixture.repay(&borrower),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &borrower.liquidity).await,
+            BORROWER_FUNDS - USDC
+        );
     }
 }
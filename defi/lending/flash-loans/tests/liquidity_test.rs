@@ -0,0 +1,612 @@
+//! Integration tests for liquidity provider deposits and withdrawals in flash-loans
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::common::{
+            create_mint, create_token_account, custom_error, get_reserve, mint_to, process,
+            share_supply, token_balance, FLASH_LOAN_FEE_BPS, USDC, USER_FUNDS,
+        },
+        flash_loans::{
+            errors::TemplateError,
+            instructions::{deposit, flash_borrow, flash_repay, initialize_reserve, withdraw},
+            process_instruction,
+            state::Reserve,
+            utils::find_reserve_authority,
+        },
+        solana_program::{
+            instruction::Instruction, pubkey::Pubkey, rent::Rent, system_instruction,
+        },
+        solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestContext},
+        solana_sdk::signature::{Keypair, Signer},
+    };
+
+    /// Accounts of an initialized reserve
+    struct ReserveFixture {
+        program_id: Pubkey,
+        reserve: Pubkey,
+        reserve_authority: Pubkey,
+        liquidity_mint: Pubkey,
+        liquidity_vault: Pubkey,
+        share_mint: Pubkey,
+    }
+
+    /// A user holding `USER_FUNDS` of the liquidity token and an empty
+    /// account of the share token
+    struct User {
+        keypair: Keypair,
+        liquidity: Pubkey,
+        shares: Pubkey,
+    }
+
+    impl ReserveFixture {
+        fn deposit(&self, user: &User, amount: u64) -> Instruction {
+            deposit(
+                &self.program_id,
+                &user.keypair.pubkey(),
+                &self.reserve,
+                &self.reserve_authority,
+                &self.liquidity_vault,
+                &self.share_mint,
+                &user.liquidity,
+                &user.shares,
+                amount,
+            )
+        }
+
+        fn withdraw(&self, user: &User, shares: u64) -> Instruction {
+            withdraw(
+                &self.program_id,
+                &user.keypair.pubkey(),
+                &self.reserve,
+                &self.reserve_authority,
+                &self.liquidity_vault,
+                &self.share_mint,
+                &user.shares,
+                &user.liquidity,
+                shares,
+            )
+        }
+
+        fn borrow(&self, user: &User, amount: u64) -> Instruction {
+            flash_borrow(
+                &self.program_id,
+                &self.reserve,
+                &self.liquidity_vault,
+                &self.reserve_authority,
+                &user.liquidity,
+                amount,
+            )
+        }
+
+        fn repay(&self, user: &User) -> Instruction {
+            flash_repay(
+                &self.program_id,
+                &self.reserve,
+                &self.liquidity_vault,
+                &user.liquidity,
+                &user.keypair.pubkey(),
+            )
+        }
+    }
+
+    /// Starts a test validator with an initialized reserve and no liquidity.
+    /// The payer mints the liquidity token.
+    async fn start_with_reserve() -> (ProgramTestContext, Keypair, ReserveFixture) {
+        let program_id = Pubkey::new_unique();
+        let program_test =
+            ProgramTest::new("flash_loans", program_id, processor!(process_instruction));
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let banks_client = &mut context.banks_client;
+
+        let reserve = Keypair::new();
+        let (reserve_authority, _) = find_reserve_authority(&program_id, &reserve.pubkey());
+        let liquidity_mint = create_mint(banks_client, &payer, &payer.pubkey(), 6).await;
+        let liquidity_vault =
+            create_token_account(banks_client, &payer, &liquidity_mint, &reserve_authority).await;
+        let share_mint = create_mint(banks_client, &payer, &reserve_authority, 6).await;
+
+        let rent = Rent::default();
+        process(
+            banks_client,
+            &payer,
+            &[
+                system_instruction::create_account(
+                    &payer.pubkey(),
+                    &reserve.pubkey(),
+                    rent.minimum_balance(Reserve::get_size()),
+                    Reserve::get_size() as u64,
+                    &program_id,
+                ),
+                initialize_reserve(
+                    &program_id,
+                    &payer.pubkey(),
+                    &reserve.pubkey(),
+                    &reserve_authority,
+                    &liquidity_mint,
+                    &liquidity_vault,
+                    &share_mint,
+                    FLASH_LOAN_FEE_BPS,
+                ),
+            ],
+            &[&reserve],
+        )
+        .await
+        .unwrap();
+
+        let fixture = ReserveFixture {
+            program_id,
+            reserve: reserve.pubkey(),
+            reserve_authority,
+            liquidity_mint,
+            liquidity_vault,
+            share_mint,
+        };
+        (context, payer, fixture)
+    }
+
+    /// Creates a user holding `USER_FUNDS` of the liquidity token
+    async fn create_user(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &ReserveFixture,
+    ) -> User {
+        let keypair = Keypair::new();
+        let liquidity = create_token_account(
+            banks_client,
+            payer,
+            &fixture.liquidity_mint,
+            &keypair.pubkey(),
+        )
+        .await;
+        let shares =
+            create_token_account(banks_client, payer, &fixture.share_mint, &keypair.pubkey()).await;
+        mint_to(
+            banks_client,
+            payer,
+            &fixture.liquidity_mint,
+            &liquidity,
+            USER_FUNDS,
+        )
+        .await;
+        User {
+            keypair,
+            liquidity,
+            shares,
+        }
+    }
+
+    /// Borrows `amount` and repays it in one transaction, returning the fee
+    async fn flash_loan(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &ReserveFixture,
+        borrower: &User,
+        amount: u64,
+    ) -> u64 {
+        let fees_before = get_reserve(banks_client, &fixture.reserve)
+            .await
+            .cumulative_fees;
+        process(
+            banks_client,
+            payer,
+            &[fixture.borrow(borrower, amount), fixture.repay(borrower)],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        get_reserve(banks_client, &fixture.reserve)
+            .await
+            .cumulative_fees
+            - fees_before
+    }
+
+    #[tokio::test]
+    async fn test_deposit_and_withdraw() {
+        let (mut context, payer, fixture) = start_with_reserve().await;
+        let banks_client = &mut context.banks_client;
+        let provider = create_user(banks_client, &payer, &fixture).await;
+
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.deposit(&provider, 0)],
+                &[&provider.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::ZeroAmount)
+        );
+
+        // The first deposit mints one share per base unit
+        process(
+            banks_client,
+            &payer,
+            &[fixture.deposit(&provider, 1_000 * USDC)],
+            &[&provider.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &provider.shares).await,
+            1_000 * USDC
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.liquidity_vault).await,
+            1_000 * USDC
+        );
+        let reserve = get_reserve(banks_client, &fixture.reserve).await;
+        assert_eq!(reserve.total_liquidity, 1_000 * USDC);
+
+        // Tokens sent to the vault directly do not change the exchange rate
+        mint_to(
+            banks_client,
+            &payer,
+            &fixture.liquidity_mint,
+            &fixture.liquidity_vault,
+            USDC,
+        )
+        .await;
+
+        // Nobody else holds shares, so a partial and a full withdrawal
+        // return the deposit exactly
+        process(
+            banks_client,
+            &payer,
+            &[fixture.withdraw(&provider, 400 * USDC)],
+            &[&provider.keypair],
+        )
+        .await
+        .unwrap();
+        process(
+            banks_client,
+            &payer,
+            &[fixture.withdraw(&provider, 600 * USDC)],
+            &[&provider.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &provider.liquidity).await,
+            USER_FUNDS
+        );
+        assert_eq!(token_balance(banks_client, &provider.shares).await, 0);
+        assert_eq!(share_supply(banks_client, &fixture.share_mint).await, 0);
+        assert_eq!(
+            get_reserve(banks_client, &fixture.reserve)
+                .await
+                .total_liquidity,
+            0
+        );
+
+        // Shares the provider does not hold cannot be burned
+        assert!(process(
+            banks_client,
+            &payer,
+            &[
+                fixture.deposit(&provider, USDC),
+                fixture.withdraw(&provider, USDC + 1),
+            ],
+            &[&provider.keypair],
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_flash_loan_fees_raise_exchange_rate() {
+        let (mut context, payer, fixture) = start_with_reserve().await;
+        let banks_client = &mut context.banks_client;
+        let alice = create_user(banks_client, &payer, &fixture).await;
+        let bob = create_user(banks_client, &payer, &fixture).await;
+        let borrower = create_user(banks_client, &payer, &fixture).await;
+
+        process(
+            banks_client,
+            &payer,
+            &[fixture.deposit(&alice, 1_000 * USDC)],
+            &[&alice.keypair],
+        )
+        .await
+        .unwrap();
+
+        // A loan of the whole vault pays 0.9 to Alice's shares
+        let fee = flash_loan(banks_client, &payer, &fixture, &borrower, 1_000 * USDC).await;
+        assert_eq!(fee, 900_000);
+
+        // Bob deposits at the new rate of 1.0009 and gets as many shares as
+        // Alice for his larger deposit
+        process(
+            banks_client,
+            &payer,
+            &[fixture.deposit(&bob, 1_000 * USDC + fee)],
+            &[&bob.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(token_balance(banks_client, &bob.shares).await, 1_000 * USDC);
+
+        // The next fee of 1.8 is split evenly
+        let fee = flash_loan(banks_client, &payer, &fixture, &borrower, 2_000 * USDC).await;
+        assert_eq!(fee, 1_800_000);
+        for provider in [&alice, &bob] {
+            process(
+                banks_client,
+                &payer,
+                &[fixture.withdraw(provider, 1_000 * USDC)],
+                &[&provider.keypair],
+            )
+            .await
+            .unwrap();
+        }
+        assert_eq!(
+            token_balance(banks_client, &alice.liquidity).await,
+            USER_FUNDS + 1_800_000
+        );
+        assert_eq!(
+            token_balance(banks_client, &bob.liquidity).await,
+            USER_FUNDS + 900_000
+        );
+        assert_eq!(
+            token_balance(banks_client, &borrower.liquidity).await,
+            USER_FUNDS - 2_700_000
+        );
+        let reserve = get_reserve(banks_client, &fixture.reserve).await;
+        assert_eq!(reserve.total_liquidity, 0);
+        assert_eq!(reserve.cumulative_fees, 2_700_000);
+    }
+
+    #[tokio::test]
+    async fn test_liquidity_frozen_during_flash_loan() {
+        let (mut context, payer, fixture) = start_with_reserve().await;
+        let banks_client = &mut context.banks_client;
+        let provider = create_user(banks_client, &payer, &fixture).await;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.deposit(&provider, 1_000 * USDC)],
+            &[&provider.keypair],
+        )
+        .await
+        .unwrap();
+
+        // Depositing just before the fee lands, or withdrawing the borrowed
+        // liquidity's shares, must wait for the repay
+        for instruction in [
+            fixture.deposit(&provider, 1_000 * USDC),
+            fixture.withdraw(&provider, 1_000 * USDC),
+        ] {
+            assert_eq!(
+                process(
+                    banks_client,
+                    &payer,
+                    &[
+                        fixture.borrow(&provider, 500 * USDC),
+                        instruction,
+                        fixture.repay(&provider),
+                    ],
+                    &[&provider.keypair],
+                )
+                .await
+                .unwrap_err(),
+                custom_error(1, TemplateError::FlashLoanInProgress)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deposit_too_small_for_a_share() {
+        let (mut context, payer, fixture) = start_with_reserve().await;
+        let banks_client = &mut context.banks_client;
+        let provider = create_user(banks_client, &payer, &fixture).await;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.deposit(&provider, 1_000)],
+            &[&provider.keypair],
+        )
+        .await
+        .unwrap();
+
+        // A loan of 1000 base units pays the minimum fee of one, so a share
+        // is now worth 1.001 base units
+        let fee = flash_loan(banks_client, &payer, &fixture, &provider, 1_000).await;
+        assert_eq!(fee, 1);
+
+        // One base unit buys no share, and two buy one
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.deposit(&provider, 1)],
+                &[&provider.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::DepositTooSmall)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[fixture.deposit(&provider, 2)],
+            &[&provider.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(token_balance(banks_client, &provider.shares).await, 1_001);
+        assert_eq!(
+            get_reserve(banks_client, &fixture.reserve)
+                .await
+                .total_liquidity,
+            1_003
+        );
+    }
+
+    #[tokio::test]
+    async fn test_withdrawals_never_exceed_deposits_plus_fees() {
+        let (mut context, payer, fixture) = start_with_reserve().await;
+        let banks_client = &mut context.banks_client;
+        let borrower = create_user(banks_client, &payer, &fixture).await;
+        let mut providers = Vec::new();
+        for _ in 0..3 {
+            providers.push(create_user(banks_client, &payer, &fixture).await);
+        }
+
+        let mut deposited = [0u64; 3];
+        let mut withdrawn = [0u64; 3];
+
+        // Deposits, withdrawals and loans of awkward sizes, so every
+        // conversion has a remainder to round
+        enum Step {
+            Deposit(usize, u64),
+            Withdraw(usize, u64),
+            Loan(u64),
+        }
+        let steps = [
+            Step::Deposit(0, 1_000_000_007),
+            Step::Loan(777_777_777),
+            Step::Deposit(1, 333_333_333),
+            Step::Loan(1_111_111_111),
+            Step::Deposit(2, 5),
+            Step::Withdraw(0, 499_999_999),
+            Step::Loan(999),
+            Step::Deposit(2, 123_456_789),
+            Step::Loan(888_888_888),
+            Step::Withdraw(1, 3),
+            Step::Loan(1),
+            Step::Withdraw(1, u64::MAX),
+            Step::Withdraw(2, u64::MAX),
+            Step::Withdraw(0, u64::MAX),
+        ];
+        for step in steps {
+            let before = get_reserve(banks_client, &fixture.reserve).await;
+            let supply_before = share_supply(banks_client, &fixture.share_mint).await;
+            match step {
+                Step::Deposit(index, amount) => {
+                    let provider = &providers[index];
+                    let shares_before = token_balance(banks_client, &provider.shares).await;
+                    process(
+                        banks_client,
+                        &payer,
+                        &[fixture.deposit(provider, amount)],
+                        &[&provider.keypair],
+                    )
+                    .await
+                    .unwrap();
+                    deposited[index] += amount;
+
+                    // The minted shares redeem for no more than the deposit
+                    let minted =
+                        token_balance(banks_client, &provider.shares).await - shares_before;
+                    let after = get_reserve(banks_client, &fixture.reserve).await;
+                    let supply_after = share_supply(banks_client, &fixture.share_mint).await;
+                    assert!(
+                        minted as u128 * after.total_liquidity as u128
+                            <= amount as u128 * supply_after as u128
+                    );
+                }
+                Step::Withdraw(index, shares) => {
+                    let provider = &providers[index];
+                    let burned = shares.min(token_balance(banks_client, &provider.shares).await);
+                    let liquidity_before = token_balance(banks_client, &provider.liquidity).await;
+                    process(
+                        banks_client,
+                        &payer,
+                        &[fixture.withdraw(provider, burned)],
+                        &[&provider.keypair],
+                    )
+                    .await
+                    .unwrap();
+
+                    // The payout is no more than the burned shares were worth
+                    let paid =
+                        token_balance(banks_client, &provider.liquidity).await - liquidity_before;
+                    assert!(
+                        paid as u128 * supply_before as u128
+                            <= burned as u128 * before.total_liquidity as u128
+                    );
+                    withdrawn[index] += paid;
+                }
+                Step::Loan(amount) => {
+                    flash_loan(banks_client, &payer, &fixture, &borrower, amount).await;
+                }
+            }
+
+            // Rounding leaves its dust with the remaining shares, so what a
+            // share redeems for never drops
+            let after = get_reserve(banks_client, &fixture.reserve).await;
+            let supply_after = share_supply(banks_client, &fixture.share_mint).await;
+            if supply_before > 0 && supply_after > 0 {
+                assert!(
+                    after.total_liquidity as u128 * supply_before as u128
+                        >= before.total_liquidity as u128 * supply_after as u128
+                );
+            }
+            assert!(
+                token_balance(banks_client, &fixture.liquidity_vault).await
+                    >= after.total_liquidity
+            );
+        }
+
+        // Together the providers got back their deposits and every fee, the
+        // last one out collecting the rounding dust
+        assert_eq!(share_supply(banks_client, &fixture.share_mint).await, 0);
+        let reserve = get_reserve(banks_client, &fixture.reserve).await;
+        assert_eq!(reserve.total_liquidity, 0);
+        assert_eq!(
+            withdrawn.iter().sum::<u64>() + reserve.total_liquidity,
+            deposited.iter().sum::<u64>() + reserve.cumulative_fees
+        );
+        for index in 0..3 {
+            assert!(withdrawn[index] > deposited[index]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_share_conversions_round_against_the_user() {
+        // Every deposit followed by a withdrawal of the shares it minted
+        // returns at most the deposit, whatever the exchange rate
+        let values = [1, 2, 3, 7, 999, 1_000, 1_001, 65_537, 999_999_999, 1 << 40];
+        for total_liquidity in values {
+            for share_supply in values {
+                if share_supply > total_liquidity {
+                    // Fees only ever raise the rate above one
+                    continue;
+                }
+                let reserve = Reserve {
+                    total_liquidity,
+                    ..Reserve::default()
+                };
+                for amount in values {
+                    let shares = reserve.shares_for_deposit(amount, share_supply).unwrap();
+                    assert!(
+                        shares as u128 * total_liquidity as u128
+                            <= amount as u128 * share_supply as u128
+                    );
+                    let after = Reserve {
+                        total_liquidity: total_liquidity + amount,
+                        ..Reserve::default()
+                    };
+                    let redeemed = after
+                        .liquidity_for_shares(shares, share_supply + shares)
+                        .unwrap();
+                    assert!(redeemed <= amount);
+                }
+                for shares in values {
+                    if shares > share_supply {
+                        continue;
+                    }
+                    let redeemed = reserve.liquidity_for_shares(shares, share_supply).unwrap();
+                    assert!(
+                        redeemed as u128 * share_supply as u128
+                            <= shares as u128 * total_liquidity as u128
+                    );
+                }
+            }
+        }
+    }
+}
@@ -2,113 +2,653 @@
  * Example client for overcollateralized-lending operations
  */
 import {
+  AccountMeta,
   Connection,
   Keypair,
   PublicKey,
-  SystemProgram,
   SYSVAR_RENT_PUBKEY,
   Transaction,
+  TransactionInstruction,
   sendAndConfirmTransaction,
 } from '@solana/web3.js';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
 import * as borsh from 'borsh';
 import BN from 'bn.js';
 
 // Define the program ID (replace with your actual program ID)
-const PROGRAM_ID = new PublicKey('overcollateralized-lending111111111111111111111111111111');
+const PROGRAM_ID = new PublicKey('Lend111111111111111111111111111111111111111');
+
+/** Scale of fixed-point prices */
+const WAD = new BN('1000000000000000000');
 
 // Define instruction types
 enum TemplateInstructionType {
-  Initialize = 0,
-  Operation1 = 1,
-  Operation2 = 2,
+  InitLendingMarket = 0,
+  InitReserve = 1,
+  SetReservePrice = 2,
+  InitObligation = 3,
+  DepositReserveLiquidity = 4,
+  RedeemReserveCollateral = 5,
+  DepositObligationCollateral = 6,
+  BorrowObligationLiquidity = 7,
+  RepayObligationLiquidity = 8,
+  RefreshObligation = 9,
+  LiquidateObligation = 10,
+  SetObligationEmode = 11,
+  RefreshReserve = 12,
+  WithdrawObligationCollateral = 13,
+}
+
+/** How a reserve's deposits can back borrows, serialized as a u8 */
+enum AssetTier {
+  Standard = 0,
+  Stable = 1,
+  Isolated = 2,
+}
+
+/** Kind of oracle a reserve reads its price from, serialized as a u8 */
+enum OracleSource {
+  Fixed = 0,
+  Pyth = 1,
+  Switchboard = 2,
 }
 
 // Define instruction schema
-class InitializeInstruction {
-  param1: BN;
-  param2: number;
-  
-  constructor(props: { param1: BN; param2: number }) {
-    this.param1 = props.param1;
-    this.param2 = props.param2;
+class ReserveConfig {
+  loan_to_value_bps: BN;
+  liquidation_threshold_bps: BN;
+  liquidation_bonus_bps: BN;
+  close_factor_bps: BN;
+  optimal_utilization_bps: BN;
+  min_borrow_rate_bps: BN;
+  optimal_borrow_rate_bps: BN;
+  max_borrow_rate_bps: BN;
+  asset_tier: AssetTier;
+  debt_ceiling: BN;
+  emode_category: number;
+  emode_loan_to_value_bps: BN;
+  emode_liquidation_threshold_bps: BN;
+  max_staleness_slots: BN;
+  max_confidence_bps: BN;
+
+  constructor(props: {
+    loan_to_value_bps: BN;
+    liquidation_threshold_bps: BN;
+    liquidation_bonus_bps: BN;
+    close_factor_bps: BN;
+    optimal_utilization_bps: BN;
+    min_borrow_rate_bps: BN;
+    optimal_borrow_rate_bps: BN;
+    max_borrow_rate_bps: BN;
+    asset_tier: AssetTier;
+    debt_ceiling: BN;
+    emode_category: number;
+    emode_loan_to_value_bps: BN;
+    emode_liquidation_threshold_bps: BN;
+    max_staleness_slots: BN;
+    max_confidence_bps: BN;
+  }) {
+    this.loan_to_value_bps = props.loan_to_value_bps;
+    this.liquidation_threshold_bps = props.liquidation_threshold_bps;
+    this.liquidation_bonus_bps = props.liquidation_bonus_bps;
+    this.close_factor_bps = props.close_factor_bps;
+    this.optimal_utilization_bps = props.optimal_utilization_bps;
+    this.min_borrow_rate_bps = props.min_borrow_rate_bps;
+    this.optimal_borrow_rate_bps = props.optimal_borrow_rate_bps;
+    this.max_borrow_rate_bps = props.max_borrow_rate_bps;
+    this.asset_tier = props.asset_tier;
+    this.debt_ceiling = props.debt_ceiling;
+    this.emode_category = props.emode_category;
+    this.emode_loan_to_value_bps = props.emode_loan_to_value_bps;
+    this.emode_liquidation_threshold_bps = props.emode_liquidation_threshold_bps;
+    this.max_staleness_slots = props.max_staleness_slots;
+    this.max_confidence_bps = props.max_confidence_bps;
   }
-  
-  static schema = new Map([
+}
+
+class InitReserveInstruction {
+  config: ReserveConfig;
+  oracle_source: OracleSource;
+  market_price: BN;
+
+  constructor(props: { config: ReserveConfig; oracle_source: OracleSource; market_price: BN }) {
+    this.config = props.config;
+    this.oracle_source = props.oracle_source;
+    this.market_price = props.market_price;
+  }
+
+  static schema = new Map<any, any>([
+    [
+      InitReserveInstruction,
+      {
+        kind: 'struct',
+        fields: [
+          ['config', ReserveConfig],
+          ['oracle_source', 'u8'],
+          ['market_price', 'u128'],
+        ],
+      },
+    ],
     [
-      InitializeInstruction,
+      ReserveConfig,
       {
         kind: 'struct',
         fields: [
-          ['param1', 'u64'],
-          ['param2', 'u8'],
+          ['loan_to_value_bps', 'u64'],
+          ['liquidation_threshold_bps', 'u64'],
+          ['liquidation_bonus_bps', 'u64'],
+          ['close_factor_bps', 'u64'],
+          ['optimal_utilization_bps', 'u64'],
+          ['min_borrow_rate_bps', 'u64'],
+          ['optimal_borrow_rate_bps', 'u64'],
+          ['max_borrow_rate_bps', 'u64'],
+          ['asset_tier', 'u8'],
+          ['debt_ceiling', 'u64'],
+          ['emode_category', 'u8'],
+          ['emode_loan_to_value_bps', 'u64'],
+          ['emode_liquidation_threshold_bps', 'u64'],
+          ['max_staleness_slots', 'u64'],
+          ['max_confidence_bps', 'u64'],
         ],
       },
     ],
   ]);
 }
 
-class Operation1Instruction {
+class PriceInstruction {
+  market_price: BN;
+
+  constructor(props: { market_price: BN }) {
+    this.market_price = props.market_price;
+  }
+
+  static schema = new Map([
+    [
+      PriceInstruction,
+      {
+        kind: 'struct',
+        fields: [['market_price', 'u128']],
+      },
+    ],
+  ]);
+}
+
+class AmountInstruction {
   amount: BN;
-  
+
   constructor(props: { amount: BN }) {
     this.amount = props.amount;
   }
-  
+
   static schema = new Map([
     [
-      Operation1Instruction,
+      AmountInstruction,
       {
         kind: 'struct',
-        fields: [
-          ['amount', 'u64'],
-        ],
+        fields: [['amount', 'u64']],
       },
     ],
   ]);
 }
 
+/** Accounts of a reserve, as recorded in the reserve account */
+interface ReserveAccounts {
+  reserve: PublicKey;
+  liquidityMint: PublicKey;
+  liquidityVault: PublicKey;
+  collateralMint: PublicKey;
+  collateralVault: PublicKey;
+}
+
 /**
- * Initialize a new template account
+ * Derive the lending market authority PDA that owns the vaults of the market
  */
-async function initialize(
+async function findMarketAuthority(lendingMarket: PublicKey): Promise<PublicKey> {
+  const [authority] = await PublicKey.findProgramAddress([lendingMarket.toBuffer()], PROGRAM_ID);
+  return authority;
+}
+
+function amountData(type: TemplateInstructionType, amount: BN): Buffer {
+  return Buffer.concat([
+    Buffer.from([type]),
+    borsh.serialize(AmountInstruction.schema, new AmountInstruction({ amount })),
+  ]);
+}
+
+/**
+ * Send one instruction of the program, after any instructions in `before`
+ */
+async function send(
   connection: Connection,
-  authority: Keypair,
-  account: Keypair,
-  param1: BN,
-  param2: number
+  keys: AccountMeta[],
+  data: Buffer,
+  signers: Keypair[],
+  before: TransactionInstruction[] = []
 ) {
-  // Create initialize instruction
-  const initializeInstruction = new InitializeInstruction({
-    param1,
-    param2,
-  });
-  
-  const instructionData = Buffer.from([TemplateInstructionType.Initialize]);
-  const serializedData = borsh.serialize(
-    InitializeInstruction.schema,
-    initializeInstruction
+  const transaction = new Transaction().add(...before, { keys, programId: PROGRAM_ID, data });
+  await sendAndConfirmTransaction(connection, transaction, signers);
+}
+
+/**
+ * Initialize a lending market whose account already exists
+ */
+async function initLendingMarket(
+  connection: Connection,
+  owner: Keypair,
+  lendingMarket: PublicKey
+) {
+  await send(
+    connection,
+    [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: lendingMarket, isSigner: false, isWritable: true },
+      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+    ],
+    Buffer.from([TemplateInstructionType.InitLendingMarket]),
+    [owner]
   );
-  
-  const fullData = Buffer.concat([instructionData, serializedData]);
-  
-  const transaction = new Transaction().add({
-    keys: [
-      { pubkey: authority.publicKey, isSigner: true, isWritable: true },
-      { pubkey: account.publicKey, isSigner: true, isWritable: true },
-      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+
+  console.log('Lending market initialized successfully!');
+}
+
+/**
+ * Add a reserve whose reserve account, vaults and collateral mint already
+ * exist to a lending market. Pyth and Switchboard reserves pass the `oracle`
+ * account they read their price from. `marketPrice` is the value of one
+ * whole token, scaled by `WAD`, until the first RefreshReserve of an oracle
+ * reserve.
+ */
+async function initReserve(
+  connection: Connection,
+  owner: Keypair,
+  lendingMarket: PublicKey,
+  reserve: ReserveAccounts,
+  config: ReserveConfig,
+  oracleSource: OracleSource,
+  oracle: PublicKey | null,
+  marketPrice: BN
+) {
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.InitReserve]),
+    borsh.serialize(
+      InitReserveInstruction.schema,
+      new InitReserveInstruction({ config, oracle_source: oracleSource, market_price: marketPrice })
+    ),
+  ]);
+
+  await send(
+    connection,
+    [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: lendingMarket, isSigner: false, isWritable: false },
+      { pubkey: reserve.reserve, isSigner: false, isWritable: true },
+      { pubkey: await findMarketAuthority(lendingMarket), isSigner: false, isWritable: false },
+      { pubkey: reserve.liquidityMint, isSigner: false, isWritable: false },
+      { pubkey: reserve.liquidityVault, isSigner: false, isWritable: false },
+      { pubkey: reserve.collateralMint, isSigner: false, isWritable: false },
+      { pubkey: reserve.collateralVault, isSigner: false, isWritable: false },
+      { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+      ...(oracle ? [{ pubkey: oracle, isSigner: false, isWritable: false }] : []),
+    ],
+    data,
+    [owner]
+  );
+
+  console.log('Reserve initialized successfully!');
+}
+
+/**
+ * Set the price of a fixed price reserve's liquidity, scaled by `WAD`
+ */
+async function setReservePrice(
+  connection: Connection,
+  owner: Keypair,
+  lendingMarket: PublicKey,
+  reserve: PublicKey,
+  marketPrice: BN
+) {
+  const data = Buffer.concat([
+    Buffer.from([TemplateInstructionType.SetReservePrice]),
+    borsh.serialize(PriceInstruction.schema, new PriceInstruction({ market_price: marketPrice })),
+  ]);
+
+  await send(
+    connection,
+    [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: lendingMarket, isSigner: false, isWritable: false },
+      { pubkey: reserve, isSigner: false, isWritable: true },
+    ],
+    data,
+    [owner]
+  );
+
+  console.log('Reserve price updated successfully!');
+}
+
+/**
+ * Initialize an obligation whose account already exists
+ */
+async function initObligation(
+  connection: Connection,
+  owner: Keypair,
+  lendingMarket: PublicKey,
+  obligation: PublicKey
+) {
+  await send(
+    connection,
+    [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: lendingMarket, isSigner: false, isWritable: false },
+      { pubkey: obligation, isSigner: false, isWritable: true },
       { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
     ],
+    Buffer.from([TemplateInstructionType.InitObligation]),
+    [owner]
+  );
+
+  console.log('Obligation initialized successfully!');
+}
+
+/**
+ * Deposit liquidity into a reserve in exchange for cTokens
+ */
+async function depositReserveLiquidity(
+  connection: Connection,
+  depositor: Keypair,
+  lendingMarket: PublicKey,
+  reserve: ReserveAccounts,
+  sourceLiquidity: PublicKey,
+  destinationCollateral: PublicKey,
+  amount: BN
+) {
+  await send(
+    connection,
+    [
+      { pubkey: depositor.publicKey, isSigner: true, isWritable: false },
+      { pubkey: reserve.reserve, isSigner: false, isWritable: true },
+      { pubkey: lendingMarket, isSigner: false, isWritable: false },
+      { pubkey: await findMarketAuthority(lendingMarket), isSigner: false, isWritable: false },
+      { pubkey: reserve.liquidityVault, isSigner: false, isWritable: true },
+      { pubkey: reserve.collateralMint, isSigner: false, isWritable: true },
+      { pubkey: sourceLiquidity, isSigner: false, isWritable: true },
+      { pubkey: destinationCollateral, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    amountData(TemplateInstructionType.DepositReserveLiquidity, amount),
+    [depositor]
+  );
+
+  console.log('Liquidity deposited successfully!');
+}
+
+/**
+ * Burn cTokens of a reserve for the liquidity they redeem for
+ */
+async function redeemReserveCollateral(
+  connection: Connection,
+  owner: Keypair,
+  lendingMarket: PublicKey,
+  reserve: ReserveAccounts,
+  sourceCollateral: PublicKey,
+  destinationLiquidity: PublicKey,
+  collateralAmount: BN
+) {
+  await send(
+    connection,
+    [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: reserve.reserve, isSigner: false, isWritable: true },
+      { pubkey: lendingMarket, isSigner: false, isWritable: false },
+      { pubkey: await findMarketAuthority(lendingMarket), isSigner: false, isWritable: false },
+      { pubkey: reserve.liquidityVault, isSigner: false, isWritable: true },
+      { pubkey: reserve.collateralMint, isSigner: false, isWritable: true },
+      { pubkey: sourceCollateral, isSigner: false, isWritable: true },
+      { pubkey: destinationLiquidity, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    amountData(TemplateInstructionType.RedeemReserveCollateral, collateralAmount),
+    [owner]
+  );
+
+  console.log('Collateral redeemed successfully!');
+}
+
+/**
+ * Deposit cTokens into an obligation as collateral
+ */
+async function depositObligationCollateral(
+  connection: Connection,
+  owner: Keypair,
+  obligation: PublicKey,
+  reserve: ReserveAccounts,
+  sourceCollateral: PublicKey,
+  collateralAmount: BN
+) {
+  await send(
+    connection,
+    [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: obligation, isSigner: false, isWritable: true },
+      { pubkey: reserve.reserve, isSigner: false, isWritable: false },
+      { pubkey: reserve.collateralVault, isSigner: false, isWritable: true },
+      { pubkey: sourceCollateral, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    amountData(TemplateInstructionType.DepositObligationCollateral, collateralAmount),
+    [owner]
+  );
+
+  console.log('Obligation collateral deposited successfully!');
+}
+
+/**
+ * Build a RefreshObligation instruction. `obligationReserves` lists the
+ * reserves of the obligation's deposits and then of its borrows, in slot
+ * order.
+ */
+function refreshObligationInstruction(
+  obligation: PublicKey,
+  obligationReserves: PublicKey[]
+): TransactionInstruction {
+  return new TransactionInstruction({
+    keys: [
+      { pubkey: obligation, isSigner: false, isWritable: true },
+      ...obligationReserves.map(pubkey => ({ pubkey, isSigner: false, isWritable: true })),
+    ],
     programId: PROGRAM_ID,
-    data: fullData,
+    data: Buffer.from([TemplateInstructionType.RefreshObligation]),
   });
-  
-  await sendAndConfirmTransaction(
+}
+
+/**
+ * Build a RefreshReserve instruction, which reads the price of a Pyth or
+ * Switchboard reserve from its `oracle`. Fixed price reserves pass none.
+ */
+function refreshReserveInstruction(reserve: PublicKey, oracle?: PublicKey): TransactionInstruction {
+  return new TransactionInstruction({
+    keys: [
+      { pubkey: reserve, isSigner: false, isWritable: true },
+      ...(oracle ? [{ pubkey: oracle, isSigner: false, isWritable: false }] : []),
+    ],
+    programId: PROGRAM_ID,
+    data: Buffer.from([TemplateInstructionType.RefreshReserve]),
+  });
+}
+
+/** The trailing isolated reserve account of an isolated obligation */
+function isolatedReserveKeys(isolatedReserve?: PublicKey): AccountMeta[] {
+  return isolatedReserve ? [{ pubkey: isolatedReserve, isSigner: false, isWritable: true }] : [];
+}
+
+/**
+ * Borrow liquidity against an obligation, refreshing it in the same
+ * transaction after `reserveRefreshes`, which refresh every oracle reserve
+ * of the obligation and the borrow. An isolated obligation passes its
+ * `isolatedReserve`.
+ */
+async function borrowObligationLiquidity(
+  connection: Connection,
+  owner: Keypair,
+  obligation: PublicKey,
+  lendingMarket: PublicKey,
+  reserve: ReserveAccounts,
+  destinationLiquidity: PublicKey,
+  obligationReserves: PublicKey[],
+  reserveRefreshes: TransactionInstruction[],
+  amount: BN,
+  isolatedReserve?: PublicKey
+) {
+  await send(
+    connection,
+    [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: obligation, isSigner: false, isWritable: true },
+      { pubkey: reserve.reserve, isSigner: false, isWritable: true },
+      { pubkey: lendingMarket, isSigner: false, isWritable: false },
+      { pubkey: await findMarketAuthority(lendingMarket), isSigner: false, isWritable: false },
+      { pubkey: reserve.liquidityVault, isSigner: false, isWritable: true },
+      { pubkey: destinationLiquidity, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+      ...isolatedReserveKeys(isolatedReserve),
+    ],
+    amountData(TemplateInstructionType.BorrowObligationLiquidity, amount),
+    [owner],
+    [...reserveRefreshes, refreshObligationInstruction(obligation, obligationReserves)]
+  );
+
+  console.log('Liquidity borrowed successfully!');
+}
+
+/**
+ * Repay an obligation's debt to a reserve. Pass `u64::MAX` to repay it all.
+ * An isolated obligation passes its `isolatedReserve`.
+ */
+async function repayObligationLiquidity(
+  connection: Connection,
+  repayer: Keypair,
+  obligation: PublicKey,
+  reserve: ReserveAccounts,
+  sourceLiquidity: PublicKey,
+  amount: BN,
+  isolatedReserve?: PublicKey
+) {
+  await send(
+    connection,
+    [
+      { pubkey: repayer.publicKey, isSigner: true, isWritable: false },
+      { pubkey: obligation, isSigner: false, isWritable: true },
+      { pubkey: reserve.reserve, isSigner: false, isWritable: true },
+      { pubkey: reserve.liquidityVault, isSigner: false, isWritable: true },
+      { pubkey: sourceLiquidity, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+      ...isolatedReserveKeys(isolatedReserve),
+    ],
+    amountData(TemplateInstructionType.RepayObligationLiquidity, amount),
+    [repayer]
+  );
+
+  console.log('Debt repaid successfully!');
+}
+
+/**
+ * Liquidate an unhealthy obligation, refreshing it in the same transaction
+ * after `reserveRefreshes`, which refresh every oracle reserve of it. Repays up to `amount` of its debt to `repayReserve`, `u64::MAX` for the
+ * most the close factor allows, for cTokens of `withdrawReserve`.
+ */
+async function liquidateObligation(
+  connection: Connection,
+  liquidator: Keypair,
+  obligation: PublicKey,
+  lendingMarket: PublicKey,
+  repayReserve: ReserveAccounts,
+  sourceLiquidity: PublicKey,
+  withdrawReserve: ReserveAccounts,
+  destinationCollateral: PublicKey,
+  obligationReserves: PublicKey[],
+  reserveRefreshes: TransactionInstruction[],
+  amount: BN
+) {
+  await send(
+    connection,
+    [
+      { pubkey: liquidator.publicKey, isSigner: true, isWritable: false },
+      { pubkey: obligation, isSigner: false, isWritable: true },
+      { pubkey: repayReserve.reserve, isSigner: false, isWritable: true },
+      { pubkey: repayReserve.liquidityVault, isSigner: false, isWritable: true },
+      { pubkey: sourceLiquidity, isSigner: false, isWritable: true },
+      { pubkey: withdrawReserve.reserve, isSigner: false, isWritable: true },
+      { pubkey: withdrawReserve.collateralVault, isSigner: false, isWritable: true },
+      { pubkey: destinationCollateral, isSigner: false, isWritable: true },
+      { pubkey: lendingMarket, isSigner: false, isWritable: false },
+      { pubkey: await findMarketAuthority(lendingMarket), isSigner: false, isWritable: false },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    amountData(TemplateInstructionType.LiquidateObligation, amount),
+    [liquidator],
+    [...reserveRefreshes, refreshObligationInstruction(obligation, obligationReserves)]
+  );
+
+  console.log('Obligation liquidated successfully!');
+}
+
+/**
+ * Move an obligation without borrows into an e-mode category, or out of it
+ * with 0
+ */
+async function setObligationEmode(
+  connection: Connection,
+  owner: Keypair,
+  obligation: PublicKey,
+  emodeCategory: number
+) {
+  await send(
     connection,
-    transaction,
-    [authority, account]
+    [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: obligation, isSigner: false, isWritable: true },
+    ],
+    Buffer.from([TemplateInstructionType.SetObligationEmode, emodeCategory]),
+    [owner]
   );
-  
-  console.log('Account initialized successfully!');
+
+  console.log('Obligation e-mode set successfully!');
+}
+
+/**
+ * Withdraw cTokens from an obligation, refreshing it in the same transaction
+ * after `reserveRefreshes`, which refresh every oracle reserve of the
+ * obligation. Pass `u64::MAX` to withdraw the whole deposit.
+ */
+async function withdrawObligationCollateral(
+  connection: Connection,
+  owner: Keypair,
+  obligation: PublicKey,
+  lendingMarket: PublicKey,
+  reserve: ReserveAccounts,
+  destinationCollateral: PublicKey,
+  obligationReserves: PublicKey[],
+  reserveRefreshes: TransactionInstruction[],
+  collateralAmount: BN
+) {
+  await send(
+    connection,
+    [
+      { pubkey: owner.publicKey, isSigner: true, isWritable: false },
+      { pubkey: obligation, isSigner: false, isWritable: true },
+      { pubkey: reserve.reserve, isSigner: false, isWritable: false },
+      { pubkey: lendingMarket, isSigner: false, isWritable: false },
+      { pubkey: await findMarketAuthority(lendingMarket), isSigner: false, isWritable: false },
+      { pubkey: reserve.collateralVault, isSigner: false, isWritable: true },
+      { pubkey: destinationCollateral, isSigner: false, isWritable: true },
+      { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    amountData(TemplateInstructionType.WithdrawObligationCollateral, collateralAmount),
+    [owner],
+    [...reserveRefreshes, refreshObligationInstruction(obligation, obligationReserves)]
+  );
+
+  console.log('Obligation collateral withdrawn successfully!');
 }
 
 /**
@@ -117,25 +657,24 @@ async function initialize(
 async function main() {
   // Connect to devnet
   const connection = new Connection('https://api.devnet.solana.com', 'confirmed');
-  
+
   // Generate keypairs for testing
-  const authority = Keypair.generate();
-  const account = Keypair.generate();
-  
-  console.log('Requesting airdrop for authority...');
-  const airdropSignature = await connection.requestAirdrop(authority.publicKey, 1000000000);
+  const owner = Keypair.generate();
+
+  console.log('Requesting airdrop for owner...');
+  const airdropSignature = await connection.requestAirdrop(owner.publicKey, 1000000000);
   await connection.confirmTransaction(airdropSignature);
-  
-  // Initialize with example values
-  await initialize(
-    connection,
-    authority,
-    account,
-    new BN(100),
-    5
-  );
-  
-  // TODO: Add examples for other operations
+
+  // TODO: Create the lending market account and call initLendingMarket, then
+  // for each token create a reserve account, vaults owned by the market
+  // authority and a collateral mint whose mint authority it is, and call
+  // initReserve with a Pyth or Switchboard oracle, or a fixed price such as
+  // `WAD` for a stablecoin. Lenders then
+  // call depositReserveLiquidity, and borrowers initObligation,
+  // depositObligationCollateral and borrowObligationLiquidity after
+  // refreshReserveInstruction for each oracle reserve involved, and
+  // withdrawObligationCollateral once their debt no longer needs it. Anyone
+  // can liquidateObligation once its debt passes its liquidation threshold.
 }
 
 main().then(
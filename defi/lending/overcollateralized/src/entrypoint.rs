@@ -1,17 +1,6 @@
 //! Program entrypoint definition
 use crate::process_instruction;
-use solana_program::{
-    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, pubkey::Pubkey,
-};
+use solana_program::entrypoint;
 
 // Declare and export the program's entrypoint
 entrypoint!(process_instruction);
-
-/// Program entrypoint's implementation
-pub fn process_instruction(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    instruction_data: &[u8],
-) -> ProgramResult {
-    process_instruction(program_id, accounts, instruction_data)
-}
@@ -4,27 +4,147 @@ use solana_program::program_error::ProgramError;
 use thiserror::Error;
 
 /// Errors that may be returned by the overcollateralized-lending program
-#[derive(Error, Debug, Copy, Clone)]
+#[derive(Error, Debug, Copy, Clone, PartialEq)]
 pub enum TemplateError {
     /// Invalid instruction
     #[error("Invalid instruction")]
     InvalidInstruction,
-    
+
     /// Not rent exempt
     #[error("Not rent exempt")]
     NotRentExempt,
-    
+
     /// Expected amount mismatch
     #[error("Expected amount mismatch")]
     ExpectedAmountMismatch,
-    
+
     /// Invalid authority
     #[error("Invalid authority")]
     InvalidAuthority,
-    
+
     /// Math operation overflow
     #[error("Math operation overflow")]
     MathOverflow,
+
+    /// Account already initialized
+    #[error("Account already initialized")]
+    AlreadyInitialized,
+
+    /// Account not initialized
+    #[error("Account not initialized")]
+    NotInitialized,
+
+    /// Account belongs to another lending market
+    #[error("Account belongs to another lending market")]
+    InvalidLendingMarket,
+
+    /// Invalid lending market authority
+    #[error("Invalid lending market authority")]
+    InvalidMarketAuthority,
+
+    /// Invalid reserve config
+    #[error("Invalid reserve config")]
+    InvalidReserveConfig,
+
+    /// Invalid vault
+    #[error("Invalid vault")]
+    InvalidVault,
+
+    /// Invalid collateral mint
+    #[error("Invalid collateral mint")]
+    InvalidCollateralMint,
+
+    /// Invalid token program
+    #[error("Invalid token program")]
+    InvalidTokenProgram,
+
+    /// Reserve accounts do not match the obligation
+    #[error("Reserve accounts do not match the obligation")]
+    InvalidObligationReserves,
+
+    /// Zero amount
+    #[error("Amount must be greater than zero")]
+    ZeroAmount,
+
+    /// Insufficient liquidity
+    #[error("Reserve holds too little available liquidity")]
+    InsufficientLiquidity,
+
+    /// Deposit too small
+    #[error("Deposit too small to mint a collateral token")]
+    DepositTooSmall,
+
+    /// Redemption too small
+    #[error("Redemption too small to return any liquidity")]
+    RedeemTooSmall,
+
+    /// Obligation full
+    #[error("Obligation holds the most reserves it can")]
+    ObligationFull,
+
+    /// Borrow too large
+    #[error("Borrow exceeds the obligation's borrowing power")]
+    BorrowTooLarge,
+
+    /// Nothing borrowed
+    #[error("Obligation has not borrowed from this reserve")]
+    ObligationLiquidityEmpty,
+
+    /// Nothing deposited
+    #[error("Obligation has no collateral in this reserve")]
+    ObligationCollateralEmpty,
+
+    /// Obligation stale
+    #[error("Obligation must be refreshed in the current slot")]
+    ObligationStale,
+
+    /// Obligation healthy
+    #[error("Obligation is healthy and cannot be liquidated")]
+    ObligationHealthy,
+
+    /// Liquidation too small
+    #[error("Liquidation too small to repay or seize anything")]
+    LiquidationTooSmall,
+
+    /// Isolated collateral mixed
+    #[error("Isolated collateral must be an obligation's only collateral")]
+    IsolatedCollateralMixed,
+
+    /// Isolated borrow not stable
+    #[error("Obligations with isolated collateral can only borrow stable assets")]
+    IsolatedBorrowNotStable,
+
+    /// Debt ceiling exceeded
+    #[error("Borrow exceeds the debt ceiling of the isolated collateral")]
+    DebtCeilingExceeded,
+
+    /// E-mode category mismatch
+    #[error("Reserve is not in the obligation's e-mode category")]
+    EmodeCategoryMismatch,
+
+    /// E-mode change with borrows
+    #[error("E-mode can only change while the obligation has no borrows")]
+    EmodeChangeWithBorrows,
+
+    /// Invalid oracle
+    #[error("Oracle account does not match the reserve or holds no valid price")]
+    InvalidOracle,
+
+    /// Stale oracle
+    #[error("Oracle price is older than the reserve allows")]
+    StaleOracle,
+
+    /// Oracle confidence too wide
+    #[error("Oracle confidence interval is wider than the reserve allows")]
+    OracleConfidenceTooWide,
+
+    /// Reserve stale
+    #[error("Reserve price must be refreshed in the current slot")]
+    ReserveStale,
+
+    /// Withdraw too large
+    #[error("Withdrawal would leave the debt above the borrowing power of the collateral")]
+    WithdrawTooLarge,
 }
 
 impl From<TemplateError> for ProgramError {
@@ -6,63 +6,439 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
-    system_program, sysvar,
+    sysvar,
 };
 
+use crate::{oracle::OracleSource, state::ReserveConfig};
+
 /// Instructions supported by the overcollateralized-lending program
 #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
 pub enum TemplateInstruction {
-    /// Initialize a new instance
+    /// Initialize a new lending market
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The lending market owner
+    /// 1. `[writable]` The lending market account, owned by this program
+    /// 2. `[]` The rent sysvar
+    ///
+    InitLendingMarket,
+
+    /// Add a reserve to a lending market
+    ///
+    /// The liquidity and collateral vaults must be empty token accounts of
+    /// the liquidity and collateral mints owned by the lending market
+    /// authority. The collateral mint must have no supply, no freeze
+    /// authority, the decimals of the liquidity mint and the lending market
+    /// authority as its mint authority. Pyth and Switchboard sources take
+    /// the oracle account the reserve is priced by.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The lending market owner
+    /// 1. `[]` The lending market account
+    /// 2. `[writable]` The reserve account, owned by this program
+    /// 3. `[]` The lending market authority PDA
+    /// 4. `[]` The liquidity mint
+    /// 5. `[]` The liquidity vault
+    /// 6. `[]` The collateral mint
+    /// 7. `[]` The collateral vault
+    /// 8. `[]` The rent sysvar
+    /// 9. `[]` The oracle account, unless the source is `Fixed`
+    ///
+    InitReserve {
+        /// Risk and interest rate parameters
+        config: ReserveConfig,
+        /// Kind of oracle the reserve is priced by
+        oracle_source: OracleSource,
+        /// Value of one whole liquidity token in the quote currency, scaled
+        /// by `WAD`, until the oracle is first read
+        market_price: u128,
+    },
+
+    /// Set the price a reserve with a `Fixed` oracle source is valued at
+    ///
+    /// The price takes effect immediately and always counts as current, with
+    /// no staleness or confidence checks.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The lending market owner
+    /// 1. `[]` The lending market account
+    /// 2. `[writable]` The reserve account
+    ///
+    SetReservePrice {
+        /// Value of one whole liquidity token in the quote currency, scaled by `WAD`
+        market_price: u128,
+    },
+
+    /// Initialize an obligation for its owner
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to initialize
-    /// 2. `[]` The system program
+    /// 0. `[signer]` The obligation owner
+    /// 1. `[]` The lending market account
+    /// 2. `[writable]` The obligation account, owned by this program
     /// 3. `[]` The rent sysvar
     ///
-    Initialize {
-        /// Custom parameter 1
-        param1: u64,
-        /// Custom parameter 2
-        param2: u8,
+    InitObligation,
+
+    /// Deposit liquidity into a reserve in exchange for cTokens
+    ///
+    /// Mints cTokens at the current exchange rate, rounded down.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The depositor
+    /// 1. `[writable]` The reserve account
+    /// 2. `[]` The lending market account
+    /// 3. `[]` The lending market authority PDA
+    /// 4. `[writable]` The liquidity vault
+    /// 5. `[writable]` The collateral mint
+    /// 6. `[writable]` The depositor's liquidity token account
+    /// 7. `[writable]` The token account receiving the cTokens
+    /// 8. `[]` The token program
+    ///
+    DepositReserveLiquidity {
+        /// Amount of liquidity to deposit
+        liquidity_amount: u64,
+    },
+
+    /// Burn cTokens for the liquidity they are worth
+    ///
+    /// Redeems at the current exchange rate, rounded down, out of the
+    /// liquidity that is not borrowed.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The owner of the cTokens
+    /// 1. `[writable]` The reserve account
+    /// 2. `[]` The lending market account
+    /// 3. `[]` The lending market authority PDA
+    /// 4. `[writable]` The liquidity vault
+    /// 5. `[writable]` The collateral mint
+    /// 6. `[writable]` The owner's cToken account
+    /// 7. `[writable]` The token account receiving the liquidity
+    /// 8. `[]` The token program
+    ///
+    RedeemReserveCollateral {
+        /// Amount of cTokens to burn
+        collateral_amount: u64,
+    },
+
+    /// Deposit cTokens into an obligation as collateral
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The obligation owner, who owns the source cTokens
+    /// 1. `[writable]` The obligation account
+    /// 2. `[]` The reserve of the cTokens
+    /// 3. `[writable]` The reserve's collateral vault
+    /// 4. `[writable]` The owner's cToken account
+    /// 5. `[]` The token program
+    ///
+    DepositObligationCollateral {
+        /// Amount of cTokens to deposit
+        collateral_amount: u64,
+    },
+
+    /// Borrow liquidity against the obligation's collateral
+    ///
+    /// The obligation and the price of the reserve must be refreshed in the
+    /// current slot. Fails if the value of the obligation's debt after the
+    /// borrow would exceed the loan-to-value share of its collateral value.
+    /// An obligation in an e-mode category can only borrow from reserves in
+    /// it, and an isolated obligation only from stable reserves, within the
+    /// debt ceiling of its collateral.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The obligation owner
+    /// 1. `[writable]` The obligation account
+    /// 2. `[writable]` The reserve to borrow from
+    /// 3. `[]` The lending market account
+    /// 4. `[]` The lending market authority PDA
+    /// 5. `[writable]` The reserve's liquidity vault
+    /// 6. `[writable]` The token account receiving the liquidity
+    /// 7. `[]` The token program
+    /// 8. `[writable]` The obligation's isolated reserve, if it is isolated
+    ///
+    BorrowObligationLiquidity {
+        /// Amount of liquidity to borrow
+        liquidity_amount: u64,
     },
 
-    /// Perform operation 1
+    /// Repay liquidity borrowed by an obligation
+    ///
+    /// Anyone can repay. Repays the whole debt when `liquidity_amount`
+    /// exceeds it.
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to modify
+    /// 0. `[signer]` The owner of the source liquidity
+    /// 1. `[writable]` The obligation account
+    /// 2. `[writable]` The reserve borrowed from
+    /// 3. `[writable]` The reserve's liquidity vault
+    /// 4. `[writable]` The token account repaying the liquidity
+    /// 5. `[]` The token program
+    /// 6. `[writable]` The obligation's isolated reserve, if it is isolated
     ///
-    Operation1 {
-        /// Operation amount
-        amount: u64,
+    RepayObligationLiquidity {
+        /// Amount of liquidity to repay, `u64::MAX` for the whole debt
+        liquidity_amount: u64,
     },
 
-    /// Perform operation 2
+    /// Accrue interest on an obligation's reserves and record the values of
+    /// its deposits and debt as of the current slot
+    ///
+    /// The prices of the reserves must be refreshed in the current slot.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The obligation account
+    /// 1. `..` `[writable]` The reserves of the obligation's deposits and then
+    ///    of its borrows, in slot order
+    ///
+    RefreshObligation,
+
+    /// Repay part of an unhealthy obligation's debt in exchange for its
+    /// collateral at a discount
+    ///
+    /// The obligation must be refreshed in the current slot, and its debt
+    /// must be worth more than the liquidation threshold of its collateral.
+    /// Repays at most the close factor of the debt to the repay reserve and
+    /// transfers cTokens of the withdraw reserve worth the repayment plus the
+    /// liquidation bonus to the liquidator.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The liquidator, who owns the source liquidity
+    /// 1. `[writable]` The obligation account
+    /// 2. `[writable]` The reserve of the debt to repay
+    /// 3. `[writable]` The repay reserve's liquidity vault
+    /// 4. `[writable]` The liquidator's liquidity token account
+    /// 5. `[writable]` The reserve of the collateral to seize
+    /// 6. `[writable]` The withdraw reserve's collateral vault
+    /// 7. `[writable]` The token account receiving the cTokens
+    /// 8. `[]` The lending market account
+    /// 9. `[]` The lending market authority PDA
+    /// 10. `[]` The token program
+    ///
+    LiquidateObligation {
+        /// Amount of liquidity to repay, `u64::MAX` for the most allowed
+        liquidity_amount: u64,
+    },
+
+    /// Move an obligation into an e-mode category, or out of it with 0
+    ///
+    /// The obligation must have no borrows.
     ///
     /// Accounts expected:
-    /// 0. `[writable, signer]` The authority account
-    /// 1. `[writable]` The account to modify
+    /// 0. `[signer]` The obligation owner
+    /// 1. `[writable]` The obligation account
     ///
-    Operation2,
+    SetObligationEmode {
+        /// E-mode category to borrow in, 0 for none
+        emode_category: u8,
+    },
+
+    /// Accrue interest on a reserve and read its price from its oracle
+    ///
+    /// Fails if the price is older than the reserve's `max_staleness_slots`
+    /// or its confidence interval wider than `max_confidence_bps` of it.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The reserve account
+    /// 1. `[]` The reserve's oracle account, unless its source is `Fixed`
+    ///
+    RefreshReserve,
+
+    /// Withdraw cTokens from an obligation's collateral
+    ///
+    /// The obligation must be refreshed in the current slot, and the value
+    /// of its debt must stay within the borrowing power of the collateral
+    /// left. Withdraws the whole deposit when `collateral_amount` exceeds
+    /// it.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` The obligation owner
+    /// 1. `[writable]` The obligation account
+    /// 2. `[]` The reserve of the cTokens
+    /// 3. `[]` The lending market account
+    /// 4. `[]` The lending market authority PDA
+    /// 5. `[writable]` The reserve's collateral vault
+    /// 6. `[writable]` The token account receiving the cTokens
+    /// 7. `[]` The token program
+    ///
+    WithdrawObligationCollateral {
+        /// Amount of cTokens to withdraw, `u64::MAX` for the whole deposit
+        collateral_amount: u64,
+    },
+}
+
+/// Creates an InitLendingMarket instruction
+pub fn init_lending_market(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    lending_market: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*lending_market, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    let data = TemplateInstruction::InitLendingMarket;
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates an InitReserve instruction
+#[allow(clippy::too_many_arguments)]
+pub fn init_reserve(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    lending_market: &Pubkey,
+    reserve: &Pubkey,
+    market_authority: &Pubkey,
+    liquidity_mint: &Pubkey,
+    liquidity_vault: &Pubkey,
+    collateral_mint: &Pubkey,
+    collateral_vault: &Pubkey,
+    config: ReserveConfig,
+    oracle_source: OracleSource,
+    oracle: Option<&Pubkey>,
+    market_price: u128,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(*lending_market, false),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new_readonly(*market_authority, false),
+        AccountMeta::new_readonly(*liquidity_mint, false),
+        AccountMeta::new_readonly(*liquidity_vault, false),
+        AccountMeta::new_readonly(*collateral_mint, false),
+        AccountMeta::new_readonly(*collateral_vault, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    if let Some(oracle) = oracle {
+        accounts.push(AccountMeta::new_readonly(*oracle, false));
+    }
+
+    let data = TemplateInstruction::InitReserve {
+        config,
+        oracle_source,
+        market_price,
+    };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a SetReservePrice instruction
+pub fn set_reserve_price(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    lending_market: &Pubkey,
+    reserve: &Pubkey,
+    market_price: u128,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(*lending_market, false),
+        AccountMeta::new(*reserve, false),
+    ];
+
+    let data = TemplateInstruction::SetReservePrice { market_price };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
 }
 
-/// Creates an Initialize instruction
-pub fn initialize(
+/// Creates an InitObligation instruction
+pub fn init_obligation(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
-    param1: u64,
-    param2: u8,
+    owner: &Pubkey,
+    lending_market: &Pubkey,
+    obligation: &Pubkey,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
-        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(*lending_market, false),
+        AccountMeta::new(*obligation, false),
         AccountMeta::new_readonly(sysvar::rent::id(), false),
     ];
 
-    let data = TemplateInstruction::Initialize { param1, param2 };
+    let data = TemplateInstruction::InitObligation;
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a DepositReserveLiquidity instruction
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_reserve_liquidity(
+    program_id: &Pubkey,
+    depositor: &Pubkey,
+    reserve: &Pubkey,
+    lending_market: &Pubkey,
+    market_authority: &Pubkey,
+    liquidity_vault: &Pubkey,
+    collateral_mint: &Pubkey,
+    source_liquidity: &Pubkey,
+    destination_collateral: &Pubkey,
+    liquidity_amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*depositor, true),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new_readonly(*lending_market, false),
+        AccountMeta::new_readonly(*market_authority, false),
+        AccountMeta::new(*liquidity_vault, false),
+        AccountMeta::new(*collateral_mint, false),
+        AccountMeta::new(*source_liquidity, false),
+        AccountMeta::new(*destination_collateral, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::DepositReserveLiquidity { liquidity_amount };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a RedeemReserveCollateral instruction
+#[allow(clippy::too_many_arguments)]
+pub fn redeem_reserve_collateral(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    reserve: &Pubkey,
+    lending_market: &Pubkey,
+    market_authority: &Pubkey,
+    liquidity_vault: &Pubkey,
+    collateral_mint: &Pubkey,
+    source_collateral: &Pubkey,
+    destination_liquidity: &Pubkey,
+    collateral_amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new_readonly(*lending_market, false),
+        AccountMeta::new_readonly(*market_authority, false),
+        AccountMeta::new(*liquidity_vault, false),
+        AccountMeta::new(*collateral_mint, false),
+        AccountMeta::new(*source_collateral, false),
+        AccountMeta::new(*destination_liquidity, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::RedeemReserveCollateral { collateral_amount };
 
     Instruction {
         program_id: *program_id,
@@ -71,19 +447,201 @@ pub fn initialize(
     }
 }
 
-/// Creates an Operation1 instruction
-pub fn operation1(
+/// Creates a DepositObligationCollateral instruction
+pub fn deposit_obligation_collateral(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
-    amount: u64,
+    owner: &Pubkey,
+    obligation: &Pubkey,
+    reserve: &Pubkey,
+    collateral_vault: &Pubkey,
+    source_collateral: &Pubkey,
+    collateral_amount: u64,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*obligation, false),
+        AccountMeta::new_readonly(*reserve, false),
+        AccountMeta::new(*collateral_vault, false),
+        AccountMeta::new(*source_collateral, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::DepositObligationCollateral { collateral_amount };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a BorrowObligationLiquidity instruction
+#[allow(clippy::too_many_arguments)]
+pub fn borrow_obligation_liquidity(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    obligation: &Pubkey,
+    reserve: &Pubkey,
+    lending_market: &Pubkey,
+    market_authority: &Pubkey,
+    liquidity_vault: &Pubkey,
+    destination_liquidity: &Pubkey,
+    isolated_reserve: Option<&Pubkey>,
+    liquidity_amount: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*obligation, false),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new_readonly(*lending_market, false),
+        AccountMeta::new_readonly(*market_authority, false),
+        AccountMeta::new(*liquidity_vault, false),
+        AccountMeta::new(*destination_liquidity, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
     ];
+    if let Some(isolated_reserve) = isolated_reserve {
+        accounts.push(AccountMeta::new(*isolated_reserve, false));
+    }
+
+    let data = TemplateInstruction::BorrowObligationLiquidity { liquidity_amount };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a RepayObligationLiquidity instruction
+#[allow(clippy::too_many_arguments)]
+pub fn repay_obligation_liquidity(
+    program_id: &Pubkey,
+    repayer: &Pubkey,
+    obligation: &Pubkey,
+    reserve: &Pubkey,
+    liquidity_vault: &Pubkey,
+    source_liquidity: &Pubkey,
+    isolated_reserve: Option<&Pubkey>,
+    liquidity_amount: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*repayer, true),
+        AccountMeta::new(*obligation, false),
+        AccountMeta::new(*reserve, false),
+        AccountMeta::new(*liquidity_vault, false),
+        AccountMeta::new(*source_liquidity, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if let Some(isolated_reserve) = isolated_reserve {
+        accounts.push(AccountMeta::new(*isolated_reserve, false));
+    }
+
+    let data = TemplateInstruction::RepayObligationLiquidity { liquidity_amount };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a RefreshObligation instruction
+///
+/// `obligation_reserves` lists the reserves of the obligation's deposits and
+/// then of its borrows, in slot order.
+pub fn refresh_obligation(
+    program_id: &Pubkey,
+    obligation: &Pubkey,
+    obligation_reserves: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*obligation, false)];
+    accounts.extend(
+        obligation_reserves
+            .iter()
+            .map(|reserve| AccountMeta::new(*reserve, false)),
+    );
+
+    let data = TemplateInstruction::RefreshObligation;
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a LiquidateObligation instruction
+#[allow(clippy::too_many_arguments)]
+pub fn liquidate_obligation(
+    program_id: &Pubkey,
+    liquidator: &Pubkey,
+    obligation: &Pubkey,
+    repay_reserve: &Pubkey,
+    repay_liquidity_vault: &Pubkey,
+    source_liquidity: &Pubkey,
+    withdraw_reserve: &Pubkey,
+    withdraw_collateral_vault: &Pubkey,
+    destination_collateral: &Pubkey,
+    lending_market: &Pubkey,
+    market_authority: &Pubkey,
+    liquidity_amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*liquidator, true),
+        AccountMeta::new(*obligation, false),
+        AccountMeta::new(*repay_reserve, false),
+        AccountMeta::new(*repay_liquidity_vault, false),
+        AccountMeta::new(*source_liquidity, false),
+        AccountMeta::new(*withdraw_reserve, false),
+        AccountMeta::new(*withdraw_collateral_vault, false),
+        AccountMeta::new(*destination_collateral, false),
+        AccountMeta::new_readonly(*lending_market, false),
+        AccountMeta::new_readonly(*market_authority, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    let data = TemplateInstruction::LiquidateObligation { liquidity_amount };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a SetObligationEmode instruction
+pub fn set_obligation_emode(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    obligation: &Pubkey,
+    emode_category: u8,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*obligation, false),
+    ];
+
+    let data = TemplateInstruction::SetObligationEmode { emode_category };
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&data).unwrap(),
+    }
+}
+
+/// Creates a RefreshReserve instruction
+pub fn refresh_reserve(
+    program_id: &Pubkey,
+    reserve: &Pubkey,
+    oracle: Option<&Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*reserve, false)];
+    if let Some(oracle) = oracle {
+        accounts.push(AccountMeta::new_readonly(*oracle, false));
+    }
 
-    let data = TemplateInstruction::Operation1 { amount };
+    let data = TemplateInstruction::RefreshReserve;
 
     Instruction {
         program_id: *program_id,
@@ -92,18 +650,31 @@ pub fn operation1(
     }
 }
 
-/// Creates an Operation2 instruction
-pub fn operation2(
+/// Creates a WithdrawObligationCollateral instruction
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_obligation_collateral(
     program_id: &Pubkey,
-    authority: &Pubkey,
-    account: &Pubkey,
+    owner: &Pubkey,
+    obligation: &Pubkey,
+    reserve: &Pubkey,
+    lending_market: &Pubkey,
+    market_authority: &Pubkey,
+    collateral_vault: &Pubkey,
+    destination_collateral: &Pubkey,
+    collateral_amount: u64,
 ) -> Instruction {
     let accounts = vec![
-        AccountMeta::new(*authority, true),
-        AccountMeta::new(*account, false),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*obligation, false),
+        AccountMeta::new_readonly(*reserve, false),
+        AccountMeta::new_readonly(*lending_market, false),
+        AccountMeta::new_readonly(*market_authority, false),
+        AccountMeta::new(*collateral_vault, false),
+        AccountMeta::new(*destination_collateral, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
     ];
 
-    let data = TemplateInstruction::Operation2;
+    let data = TemplateInstruction::WithdrawObligationCollateral { collateral_amount };
 
     Instruction {
         program_id: *program_id,
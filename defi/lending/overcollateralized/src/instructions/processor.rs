@@ -3,9 +3,13 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
     pubkey::Pubkey,
     rent::Rent,
     sysvar::Sysvar,
@@ -14,7 +18,15 @@ use solana_program::{
 use crate::{
     errors::TemplateError,
     instructions::TemplateInstruction,
-    state::TemplateAccount,
+    oracle::{
+        FixedPriceSource, OraclePrice, OracleSource, PriceSource, PythPriceSource,
+        SwitchboardPriceSource,
+    },
+    state::{AssetTier, LendingMarket, Obligation, Reserve, ReserveConfig, WAD},
+    utils::{
+        check_account_owner, check_market_authority, check_signer, find_market_authority,
+        safe_addition,
+    },
 };
 
 /// Processes an instruction
@@ -27,141 +39,1369 @@ pub fn process_instruction(
         .map_err(|_| ProgramError::InvalidInstructionData)?;
 
     match instruction {
-        TemplateInstruction::Initialize { param1, param2 } => {
-            msg!("Instruction: Initialize");
-            process_initialize(program_id, accounts, param1, param2)
+        TemplateInstruction::InitLendingMarket => {
+            msg!("Instruction: Init Lending Market");
+            process_init_lending_market(program_id, accounts)
         }
-        TemplateInstruction::Operation1 { amount } => {
-            msg!("Instruction: Operation1");
-            process_operation1(program_id, accounts, amount)
+        TemplateInstruction::InitReserve {
+            config,
+            oracle_source,
+            market_price,
+        } => {
+            msg!("Instruction: Init Reserve");
+            process_init_reserve(program_id, accounts, config, oracle_source, market_price)
         }
-        TemplateInstruction::Operation2 => {
-            msg!("Instruction: Operation2");
-            process_operation2(program_id, accounts)
+        TemplateInstruction::SetReservePrice { market_price } => {
+            msg!("Instruction: Set Reserve Price");
+            process_set_reserve_price(program_id, accounts, market_price)
+        }
+        TemplateInstruction::InitObligation => {
+            msg!("Instruction: Init Obligation");
+            process_init_obligation(program_id, accounts)
+        }
+        TemplateInstruction::DepositReserveLiquidity { liquidity_amount } => {
+            msg!("Instruction: Deposit Reserve Liquidity");
+            process_deposit_reserve_liquidity(program_id, accounts, liquidity_amount)
+        }
+        TemplateInstruction::RedeemReserveCollateral { collateral_amount } => {
+            msg!("Instruction: Redeem Reserve Collateral");
+            process_redeem_reserve_collateral(program_id, accounts, collateral_amount)
+        }
+        TemplateInstruction::DepositObligationCollateral { collateral_amount } => {
+            msg!("Instruction: Deposit Obligation Collateral");
+            process_deposit_obligation_collateral(program_id, accounts, collateral_amount)
+        }
+        TemplateInstruction::BorrowObligationLiquidity { liquidity_amount } => {
+            msg!("Instruction: Borrow Obligation Liquidity");
+            process_borrow_obligation_liquidity(program_id, accounts, liquidity_amount)
+        }
+        TemplateInstruction::RepayObligationLiquidity { liquidity_amount } => {
+            msg!("Instruction: Repay Obligation Liquidity");
+            process_repay_obligation_liquidity(program_id, accounts, liquidity_amount)
+        }
+        TemplateInstruction::RefreshObligation => {
+            msg!("Instruction: Refresh Obligation");
+            process_refresh_obligation(program_id, accounts)
+        }
+        TemplateInstruction::LiquidateObligation { liquidity_amount } => {
+            msg!("Instruction: Liquidate Obligation");
+            process_liquidate_obligation(program_id, accounts, liquidity_amount)
+        }
+        TemplateInstruction::SetObligationEmode { emode_category } => {
+            msg!("Instruction: Set Obligation Emode");
+            process_set_obligation_emode(program_id, accounts, emode_category)
+        }
+        TemplateInstruction::RefreshReserve => {
+            msg!("Instruction: Refresh Reserve");
+            process_refresh_reserve(program_id, accounts)
+        }
+        TemplateInstruction::WithdrawObligationCollateral { collateral_amount } => {
+            msg!("Instruction: Withdraw Obligation Collateral");
+            process_withdraw_obligation_collateral(program_id, accounts, collateral_amount)
         }
     }
 }
 
-/// Processes an Initialize instruction
-fn process_initialize(
+/// Processes an InitLendingMarket instruction
+fn process_init_lending_market(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+
+    check_new_account(
+        program_id,
+        lending_market_info,
+        rent_info,
+        LendingMarket::get_size(),
+    )?;
+
+    let (_, bump_seed) = find_market_authority(program_id, lending_market_info.key);
+    let lending_market = LendingMarket {
+        is_initialized: true,
+        bump_seed,
+        owner: *owner_info.key,
+    };
+
+    // Save lending market data
+    lending_market.serialize(&mut &mut lending_market_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes an InitReserve instruction
+fn process_init_reserve(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    param1: u64,
-    param2: u8,
+    config: ReserveConfig,
+    oracle_source: OracleSource,
+    market_price: u128,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    let system_program_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let market_authority_info = next_account_info(account_info_iter)?;
+    let liquidity_mint_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let collateral_mint_info = next_account_info(account_info_iter)?;
+    let collateral_vault_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
+
+    // Check the lending market owner is a signer
+    check_signer(owner_info)?;
+    let lending_market = load_lending_market(program_id, lending_market_info)?;
+    if lending_market.owner != *owner_info.key {
         return Err(TemplateError::InvalidAuthority.into());
     }
-    
-    // Check for rent exemption
-    let rent = &Rent::from_account_info(rent_info)?;
-    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
-        return Err(TemplateError::NotRentExempt.into());
+
+    check_new_account(program_id, reserve_info, rent_info, Reserve::get_size())?;
+
+    // Validate the parameters
+    config.validate()?;
+    if market_price == 0 {
+        return Err(TemplateError::InvalidReserveConfig.into());
     }
-    
-    // Initialize account data
-    let template_account = TemplateAccount {
-        authority: *authority_info.key,
-        value1: param1,
-        value2: param2,
+
+    // Validate the oracle holds a price, however old
+    let current_slot = Clock::get()?.slot;
+    let oracle = match oracle_source {
+        OracleSource::Fixed => Pubkey::default(),
+        _ => {
+            let oracle_info = next_account_info(account_info_iter)?;
+            read_oracle_price(oracle_source, oracle_info, current_slot)?;
+            *oracle_info.key
+        }
+    };
+
+    // Validate the lending market authority
+    check_market_authority(
+        program_id,
+        lending_market_info.key,
+        lending_market.bump_seed,
+        market_authority_info.key,
+    )?;
+
+    // Validate the liquidity mint and vault
+    check_account_owner(liquidity_mint_info, &spl_token::id())
+        .map_err(|_| TemplateError::InvalidVault)?;
+    check_vault(
+        liquidity_vault_info,
+        liquidity_mint_info.key,
+        market_authority_info.key,
+    )?;
+    let liquidity_decimals =
+        spl_token::state::Mint::unpack(&liquidity_mint_info.data.borrow())?.decimals;
+
+    // Validate the collateral mint and vault
+    check_account_owner(collateral_mint_info, &spl_token::id())
+        .map_err(|_| TemplateError::InvalidCollateralMint)?;
+    let collateral_mint = spl_token::state::Mint::unpack(&collateral_mint_info.data.borrow())?;
+    if collateral_mint.mint_authority != COption::Some(*market_authority_info.key)
+        || collateral_mint.freeze_authority.is_some()
+        || collateral_mint.supply != 0
+        || collateral_mint.decimals != liquidity_decimals
+    {
+        return Err(TemplateError::InvalidCollateralMint.into());
+    }
+    check_vault(
+        collateral_vault_info,
+        collateral_mint_info.key,
+        market_authority_info.key,
+    )?;
+
+    let reserve = Reserve {
         is_initialized: true,
+        lending_market: *lending_market_info.key,
+        liquidity_mint: *liquidity_mint_info.key,
+        liquidity_decimals,
+        liquidity_vault: *liquidity_vault_info.key,
+        collateral_mint: *collateral_mint_info.key,
+        collateral_vault: *collateral_vault_info.key,
+        config,
+        available_liquidity: 0,
+        borrowed_amount_wads: 0,
+        cumulative_borrow_rate_wads: WAD,
+        last_update_slot: current_slot,
+        collateral_supply: 0,
+        market_price,
+        oracle_source,
+        oracle,
+        price_slot: 0,
+        isolated_debt_wads: 0,
     };
-    
-    // Save account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
     Ok(())
 }
 
-/// Processes an Operation1 instruction
-fn process_operation1(
+/// Processes a SetReservePrice instruction
+fn process_set_reserve_price(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    amount: u64,
+    market_price: u128,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+
+    // Check the lending market owner is a signer
+    check_signer(owner_info)?;
+    let lending_market = load_lending_market(program_id, lending_market_info)?;
+    if lending_market.owner != *owner_info.key {
+        return Err(TemplateError::InvalidAuthority.into());
+    }
+
+    let mut reserve = load_reserve(program_id, reserve_info)?;
+    if reserve.lending_market != *lending_market_info.key {
+        return Err(TemplateError::InvalidLendingMarket.into());
+    }
+    if reserve.oracle_source != OracleSource::Fixed {
+        return Err(TemplateError::InvalidOracle.into());
+    }
+    if market_price == 0 {
+        return Err(TemplateError::InvalidReserveConfig.into());
+    }
+    reserve.market_price = market_price;
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes an InitObligation instruction
+fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+
+    // Check the owner is a signer
+    check_signer(owner_info)?;
+    load_lending_market(program_id, lending_market_info)?;
+
+    check_new_account(
+        program_id,
+        obligation_info,
+        rent_info,
+        Obligation::get_size(),
+    )?;
+
+    let obligation = Obligation {
+        is_initialized: true,
+        lending_market: *lending_market_info.key,
+        owner: *owner_info.key,
+        stale: true,
+        ..Obligation::default()
+    };
+
+    // Save obligation data
+    obligation.serialize(&mut &mut obligation_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a DepositReserveLiquidity instruction
+fn process_deposit_reserve_liquidity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let depositor_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let market_authority_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let collateral_mint_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the depositor is a signer
+    check_signer(depositor_info)?;
+
+    if liquidity_amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    let mut reserve = load_reserve(program_id, reserve_info)?;
+    let lending_market = check_reserve_accounts(
+        program_id,
+        &reserve,
+        lending_market_info,
+        market_authority_info,
+        liquidity_vault_info,
+        token_program_info,
+    )?;
+    if *collateral_mint_info.key != reserve.collateral_mint {
+        return Err(TemplateError::InvalidCollateralMint.into());
+    }
+
+    // Mint cTokens at the exchange rate before the deposit, rounded down
+    reserve.accrue_interest(Clock::get()?.slot)?;
+    let collateral_amount = reserve.collateral_for_liquidity(liquidity_amount)?;
+    if collateral_amount == 0 {
+        return Err(TemplateError::DepositTooSmall.into());
+    }
+    reserve.available_liquidity = safe_addition(reserve.available_liquidity, liquidity_amount)?;
+    reserve.collateral_supply = safe_addition(reserve.collateral_supply, collateral_amount)?;
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
+    transfer_to_vault(
+        token_program_info,
+        source_info,
+        liquidity_vault_info,
+        depositor_info,
+        liquidity_amount,
+    )?;
+    mint_collateral(
+        token_program_info,
+        collateral_mint_info,
+        destination_info,
+        market_authority_info,
+        &[
+            lending_market_info.key.as_ref(),
+            &[lending_market.bump_seed],
+        ],
+        collateral_amount,
+    )?;
+    msg!(
+        "Deposited {} for {} cTokens",
+        liquidity_amount,
+        collateral_amount
+    );
+
+    Ok(())
+}
+
+/// Processes a RedeemReserveCollateral instruction
+fn process_redeem_reserve_collateral(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    collateral_amount: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
+    let owner_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let market_authority_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let collateral_mint_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the cToken owner is a signer
+    check_signer(owner_info)?;
+
+    if collateral_amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    let mut reserve = load_reserve(program_id, reserve_info)?;
+    let lending_market = check_reserve_accounts(
+        program_id,
+        &reserve,
+        lending_market_info,
+        market_authority_info,
+        liquidity_vault_info,
+        token_program_info,
+    )?;
+    if *collateral_mint_info.key != reserve.collateral_mint {
+        return Err(TemplateError::InvalidCollateralMint.into());
+    }
+
+    // Redeem at the exchange rate before the redemption, rounded down
+    reserve.accrue_interest(Clock::get()?.slot)?;
+    let liquidity_amount = reserve.liquidity_for_collateral(collateral_amount)?;
+    if liquidity_amount == 0 {
+        return Err(TemplateError::RedeemTooSmall.into());
+    }
+    if liquidity_amount > reserve.available_liquidity {
+        return Err(TemplateError::InsufficientLiquidity.into());
+    }
+    reserve.available_liquidity -= liquidity_amount;
+    reserve.collateral_supply -= collateral_amount;
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
+    burn_collateral(
+        token_program_info,
+        source_info,
+        collateral_mint_info,
+        owner_info,
+        collateral_amount,
+    )?;
+    transfer_from_vault(
+        token_program_info,
+        liquidity_vault_info,
+        destination_info,
+        market_authority_info,
+        &[
+            lending_market_info.key.as_ref(),
+            &[lending_market.bump_seed],
+        ],
+        liquidity_amount,
+    )?;
+    msg!(
+        "Redeemed {} cTokens for {}",
+        collateral_amount,
+        liquidity_amount
+    );
+
+    Ok(())
+}
+
+/// Processes a DepositObligationCollateral instruction
+fn process_deposit_obligation_collateral(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    collateral_amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let collateral_vault_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the obligation owner is a signer
+    check_signer(owner_info)?;
+
+    if collateral_amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    let mut obligation = load_obligation(program_id, obligation_info)?;
+    if obligation.owner != *owner_info.key {
         return Err(TemplateError::InvalidAuthority.into());
     }
-    
-    // Deserialize account data
-    let mut template_account = TemplateAccount::try_from_slice(&account_info.data.borrow())?;
-    
-    // Verify authority
-    if template_account.authority != *authority_info.key {
+    let reserve = load_reserve(program_id, reserve_info)?;
+    if reserve.lending_market != obligation.lending_market {
+        return Err(TemplateError::InvalidLendingMarket.into());
+    }
+    if *collateral_vault_info.key != reserve.collateral_vault {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    check_token_program(token_program_info)?;
+
+    // Isolated collateral must be the obligation's only collateral
+    if reserve.config.asset_tier == AssetTier::Isolated {
+        if !obligation.is_empty() && obligation.isolated_reserve != *reserve_info.key {
+            return Err(TemplateError::IsolatedCollateralMixed.into());
+        }
+        obligation.isolated_reserve = *reserve_info.key;
+    } else if obligation.is_isolated() {
+        return Err(TemplateError::IsolatedCollateralMixed.into());
+    }
+
+    let deposit = obligation.deposit_mut(reserve_info.key)?;
+    deposit.deposited_amount = safe_addition(deposit.deposited_amount, collateral_amount)?;
+    obligation.mark_stale();
+
+    // Save obligation data
+    obligation.serialize(&mut &mut obligation_info.data.borrow_mut()[..])?;
+
+    transfer_to_vault(
+        token_program_info,
+        source_info,
+        collateral_vault_info,
+        owner_info,
+        collateral_amount,
+    )?;
+
+    Ok(())
+}
+
+/// Processes a BorrowObligationLiquidity instruction
+fn process_borrow_obligation_liquidity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let market_authority_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the obligation owner is a signer
+    check_signer(owner_info)?;
+
+    if liquidity_amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    let mut obligation = load_obligation(program_id, obligation_info)?;
+    if obligation.owner != *owner_info.key {
         return Err(TemplateError::InvalidAuthority.into());
     }
-    
-    // Update account data
-    template_account.value1 = template_account.value1.checked_add(amount)
+    let mut reserve = load_reserve(program_id, reserve_info)?;
+    if reserve.lending_market != obligation.lending_market {
+        return Err(TemplateError::InvalidLendingMarket.into());
+    }
+    let lending_market = check_reserve_accounts(
+        program_id,
+        &reserve,
+        lending_market_info,
+        market_authority_info,
+        liquidity_vault_info,
+        token_program_info,
+    )?;
+
+    // E-mode obligations only borrow within their category, and isolated
+    // ones only stable assets
+    if obligation.emode_category != 0 && reserve.config.emode_category != obligation.emode_category
+    {
+        return Err(TemplateError::EmodeCategoryMismatch.into());
+    }
+    let mut isolated = None;
+    if obligation.is_isolated() {
+        if reserve.config.asset_tier != AssetTier::Stable {
+            return Err(TemplateError::IsolatedBorrowNotStable.into());
+        }
+        let isolated_reserve_info = next_account_info(account_info_iter)?;
+        let isolated_reserve =
+            load_isolated_reserve(program_id, &obligation, isolated_reserve_info)?;
+        isolated = Some((isolated_reserve_info, isolated_reserve));
+    }
+
+    let current_slot = Clock::get()?.slot;
+    if !obligation.is_fresh(current_slot) {
+        return Err(TemplateError::ObligationStale.into());
+    }
+    if !reserve.is_price_fresh(current_slot) {
+        return Err(TemplateError::ReserveStale.into());
+    }
+    reserve.accrue_interest(current_slot)?;
+    if liquidity_amount > reserve.available_liquidity {
+        return Err(TemplateError::InsufficientLiquidity.into());
+    }
+
+    // The debt after the borrow must stay within the borrowing power of
+    // the collateral
+    let borrow_value = reserve.market_value(liquidity_amount, true)?;
+    let borrowed_value = obligation
+        .borrowed_value_wads
+        .checked_add(borrow_value)
         .ok_or(TemplateError::MathOverflow)?;
-    
-    // Save updated account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+    if borrowed_value > obligation.allowed_borrow_value_wads {
+        return Err(TemplateError::BorrowTooLarge.into());
+    }
+    obligation.borrowed_value_wads = borrowed_value;
+
+    // Isolated collateral backs at most its debt ceiling across obligations
+    if let Some((_, isolated_reserve)) = isolated.as_mut() {
+        let isolated_debt = isolated_reserve
+            .isolated_debt_wads
+            .checked_add(borrow_value)
+            .ok_or(TemplateError::MathOverflow)?;
+        if isolated_debt > isolated_reserve.config.debt_ceiling as u128 * WAD {
+            return Err(TemplateError::DebtCeilingExceeded.into());
+        }
+        isolated_reserve.isolated_debt_wads = isolated_debt;
+    }
+
+    let borrow_wads = liquidity_amount as u128 * WAD;
+    let borrow = obligation.borrow_mut(reserve_info.key, reserve.cumulative_borrow_rate_wads)?;
+    borrow.accrue_interest(reserve.cumulative_borrow_rate_wads)?;
+    borrow.borrowed_amount_wads = borrow
+        .borrowed_amount_wads
+        .checked_add(borrow_wads)
+        .ok_or(TemplateError::MathOverflow)?;
+    borrow.market_value_wads = borrow
+        .market_value_wads
+        .checked_add(borrow_value)
+        .ok_or(TemplateError::MathOverflow)?;
+    reserve.available_liquidity -= liquidity_amount;
+    reserve.borrowed_amount_wads = reserve
+        .borrowed_amount_wads
+        .checked_add(borrow_wads)
+        .ok_or(TemplateError::MathOverflow)?;
+
+    // Save obligation and reserve data
+    obligation.serialize(&mut &mut obligation_info.data.borrow_mut()[..])?;
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+    if let Some((isolated_reserve_info, isolated_reserve)) = isolated {
+        isolated_reserve.serialize(&mut &mut isolated_reserve_info.data.borrow_mut()[..])?;
+    }
+
+    transfer_from_vault(
+        token_program_info,
+        liquidity_vault_info,
+        destination_info,
+        market_authority_info,
+        &[
+            lending_market_info.key.as_ref(),
+            &[lending_market.bump_seed],
+        ],
+        liquidity_amount,
+    )?;
+    msg!("Borrowed {}", liquidity_amount);
+
+    Ok(())
+}
+
+/// Processes a RepayObligationLiquidity instruction
+fn process_repay_obligation_liquidity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let repayer_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the owner of the repaying account is a signer
+    check_signer(repayer_info)?;
+
+    if liquidity_amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    let mut obligation = load_obligation(program_id, obligation_info)?;
+    let mut reserve = load_reserve(program_id, reserve_info)?;
+    if reserve.lending_market != obligation.lending_market {
+        return Err(TemplateError::InvalidLendingMarket.into());
+    }
+    if *liquidity_vault_info.key != reserve.liquidity_vault {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    check_token_program(token_program_info)?;
+    let mut isolated = None;
+    if obligation.is_isolated() {
+        let isolated_reserve_info = next_account_info(account_info_iter)?;
+        let isolated_reserve =
+            load_isolated_reserve(program_id, &obligation, isolated_reserve_info)?;
+        isolated = Some((isolated_reserve_info, isolated_reserve));
+    }
+
+    reserve.accrue_interest(Clock::get()?.slot)?;
+    let borrow = obligation
+        .find_borrow_mut(reserve_info.key)
+        .ok_or(TemplateError::ObligationLiquidityEmpty)?;
+    borrow.accrue_interest(reserve.cumulative_borrow_rate_wads)?;
+
+    // Repaying the debt rounded up to whole base units clears it
+    let repay_amount = liquidity_amount.min(borrow.borrowed_amount()?);
+    let repay_wads = repay_amount as u128 * WAD;
+    borrow.borrowed_amount_wads = borrow.borrowed_amount_wads.saturating_sub(repay_wads);
+    obligation.remove_repaid_borrow(reserve_info.key);
+    obligation.mark_stale();
+    reserve.available_liquidity = safe_addition(reserve.available_liquidity, repay_amount)?;
+    reserve.borrowed_amount_wads = reserve.borrowed_amount_wads.saturating_sub(repay_wads);
+    if let Some((_, isolated_reserve)) = isolated.as_mut() {
+        let repay_value = reserve.market_value(repay_amount, false)?;
+        isolated_reserve.isolated_debt_wads = isolated_reserve
+            .isolated_debt_wads
+            .saturating_sub(repay_value);
+    }
+
+    // Save obligation and reserve data
+    obligation.serialize(&mut &mut obligation_info.data.borrow_mut()[..])?;
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+    if let Some((isolated_reserve_info, isolated_reserve)) = isolated {
+        isolated_reserve.serialize(&mut &mut isolated_reserve_info.data.borrow_mut()[..])?;
+    }
+
+    transfer_to_vault(
+        token_program_info,
+        source_info,
+        liquidity_vault_info,
+        repayer_info,
+        repay_amount,
+    )?;
+    msg!("Repaid {}", repay_amount);
+
+    Ok(())
+}
+
+/// Processes a RefreshObligation instruction
+fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let obligation_info = next_account_info(account_info_iter)?;
+    let reserve_infos = account_info_iter.as_slice();
+
+    let mut obligation = load_obligation(program_id, obligation_info)?;
+    let deposit_count = obligation.active_deposits().count();
+    let borrow_count = obligation.active_borrows().count();
+    if reserve_infos.len() != deposit_count + borrow_count {
+        return Err(TemplateError::InvalidObligationReserves.into());
+    }
+    let (deposit_reserve_infos, borrow_reserve_infos) = reserve_infos.split_at(deposit_count);
+    let lending_market = obligation.lending_market;
+    let emode_category = obligation.emode_category;
+    let current_slot = Clock::get()?.slot;
+
+    let mut deposited_value = 0u128;
+    let mut allowed_borrow_value = 0u128;
+    let mut unhealthy_borrow_value = 0u128;
+    let deposits = obligation
+        .deposits
+        .iter_mut()
+        .filter(|deposit| deposit.deposit_reserve != Pubkey::default());
+    for (deposit, reserve_info) in deposits.zip(deposit_reserve_infos) {
+        let reserve = refresh_obligation_reserve(
+            program_id,
+            &lending_market,
+            reserve_info,
+            &deposit.deposit_reserve,
+            current_slot,
+        )?;
+        deposit.market_value_wads = reserve.collateral_market_value(deposit.deposited_amount)?;
+        deposited_value = deposited_value
+            .checked_add(deposit.market_value_wads)
+            .ok_or(TemplateError::MathOverflow)?;
+        allowed_borrow_value = allowed_borrow_value
+            .checked_add(reserve.borrowing_power(deposit.deposited_amount, emode_category)?)
+            .ok_or(TemplateError::MathOverflow)?;
+        unhealthy_borrow_value = unhealthy_borrow_value
+            .checked_add(reserve.liquidation_threshold(deposit.deposited_amount, emode_category)?)
+            .ok_or(TemplateError::MathOverflow)?;
+    }
+
+    let mut borrowed_value = 0u128;
+    let borrows = obligation
+        .borrows
+        .iter_mut()
+        .filter(|borrow| borrow.borrow_reserve != Pubkey::default());
+    for (borrow, reserve_info) in borrows.zip(borrow_reserve_infos) {
+        let reserve = refresh_obligation_reserve(
+            program_id,
+            &lending_market,
+            reserve_info,
+            &borrow.borrow_reserve,
+            current_slot,
+        )?;
+        borrow.accrue_interest(reserve.cumulative_borrow_rate_wads)?;
+        borrow.market_value_wads = reserve.market_value(borrow.borrowed_amount()?, true)?;
+        borrowed_value = borrowed_value
+            .checked_add(borrow.market_value_wads)
+            .ok_or(TemplateError::MathOverflow)?;
+    }
+
+    obligation.deposited_value_wads = deposited_value;
+    obligation.borrowed_value_wads = borrowed_value;
+    obligation.allowed_borrow_value_wads = allowed_borrow_value;
+    obligation.unhealthy_borrow_value_wads = unhealthy_borrow_value;
+    obligation.last_update_slot = current_slot;
+    obligation.stale = false;
+
+    // Save obligation data
+    obligation.serialize(&mut &mut obligation_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a LiquidateObligation instruction
+fn process_liquidate_obligation(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let liquidator_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let repay_liquidity_vault_info = next_account_info(account_info_iter)?;
+    let source_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let withdraw_collateral_vault_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let market_authority_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the liquidator is a signer
+    check_signer(liquidator_info)?;
+
+    if liquidity_amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    let mut obligation = load_obligation(program_id, obligation_info)?;
+    let mut repay_reserve = load_reserve(program_id, repay_reserve_info)?;
+    let mut withdraw_reserve = load_reserve(program_id, withdraw_reserve_info)?;
+    if repay_reserve.lending_market != obligation.lending_market
+        || withdraw_reserve.lending_market != obligation.lending_market
+        || *lending_market_info.key != obligation.lending_market
+    {
+        return Err(TemplateError::InvalidLendingMarket.into());
+    }
+    let lending_market = load_lending_market(program_id, lending_market_info)?;
+    check_market_authority(
+        program_id,
+        lending_market_info.key,
+        lending_market.bump_seed,
+        market_authority_info.key,
+    )?;
+    if *repay_liquidity_vault_info.key != repay_reserve.liquidity_vault
+        || *withdraw_collateral_vault_info.key != withdraw_reserve.collateral_vault
+    {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    check_token_program(token_program_info)?;
+
+    // Liquidate from the values of a refresh in this slot
+    let current_slot = Clock::get()?.slot;
+    if !obligation.is_fresh(current_slot) {
+        return Err(TemplateError::ObligationStale.into());
+    }
+    if obligation.is_healthy() {
+        return Err(TemplateError::ObligationHealthy.into());
+    }
+    repay_reserve.accrue_interest(current_slot)?;
+    withdraw_reserve.accrue_interest(current_slot)?;
+
+    let liquidation = obligation.calculate_liquidation(
+        repay_reserve_info.key,
+        &repay_reserve,
+        withdraw_reserve_info.key,
+        &withdraw_reserve,
+        liquidity_amount,
+    )?;
+
+    // Debt repaid against isolated collateral frees its debt ceiling
+    let isolated = *withdraw_reserve_info.key == obligation.isolated_reserve;
+    if isolated {
+        let repay_value = repay_reserve.market_value(liquidation.repay_amount, false)?;
+        withdraw_reserve.isolated_debt_wads = withdraw_reserve
+            .isolated_debt_wads
+            .saturating_sub(repay_value);
+    }
+
+    let repay_wads = liquidation.repay_amount as u128 * WAD;
+    if let Some(borrow) = obligation.find_borrow_mut(repay_reserve_info.key) {
+        borrow.borrowed_amount_wads = borrow.borrowed_amount_wads.saturating_sub(repay_wads);
+    }
+    obligation.remove_repaid_borrow(repay_reserve_info.key);
+    if let Some(deposit) = obligation.find_deposit_mut(withdraw_reserve_info.key) {
+        deposit.deposited_amount -= liquidation.withdraw_amount;
+    }
+    obligation.remove_empty_deposit(withdraw_reserve_info.key);
+    obligation.mark_stale();
+    repay_reserve.available_liquidity =
+        safe_addition(repay_reserve.available_liquidity, liquidation.repay_amount)?;
+    repay_reserve.borrowed_amount_wads = repay_reserve
+        .borrowed_amount_wads
+        .saturating_sub(repay_wads);
+
+    // Save obligation and reserve data
+    obligation.serialize(&mut &mut obligation_info.data.borrow_mut()[..])?;
+    repay_reserve.serialize(&mut &mut repay_reserve_info.data.borrow_mut()[..])?;
+    if isolated {
+        withdraw_reserve.serialize(&mut &mut withdraw_reserve_info.data.borrow_mut()[..])?;
+    }
+
+    transfer_to_vault(
+        token_program_info,
+        source_info,
+        repay_liquidity_vault_info,
+        liquidator_info,
+        liquidation.repay_amount,
+    )?;
+    transfer_from_vault(
+        token_program_info,
+        withdraw_collateral_vault_info,
+        destination_info,
+        market_authority_info,
+        &[
+            lending_market_info.key.as_ref(),
+            &[lending_market.bump_seed],
+        ],
+        liquidation.withdraw_amount,
+    )?;
+    msg!(
+        "Repaid {} for {} cTokens",
+        liquidation.repay_amount,
+        liquidation.withdraw_amount
+    );
+
     Ok(())
 }
 
-/// Processes an Operation2 instruction
-fn process_operation2(
+/// Processes a SetObligationEmode instruction
+fn process_set_obligation_emode(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
+    emode_category: u8,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get accounts
-    let authority_info = next_account_info(account_info_iter)?;
-    let account_info = next_account_info(account_info_iter)?;
-    
-    // Check the authority is a signer
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Check account ownership
-    if account_info.owner != program_id {
+    let owner_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+
+    // Check the obligation owner is a signer
+    check_signer(owner_info)?;
+
+    let mut obligation = load_obligation(program_id, obligation_info)?;
+    if obligation.owner != *owner_info.key {
         return Err(TemplateError::InvalidAuthority.into());
     }
-    
-    // Deserialize account data
-    let mut template_account = TemplateAccount::try_from_slice(&account_info.data.borrow())?;
-    
-    // Verify authority
-    if template_account.authority != *authority_info.key {
+    if obligation.active_borrows().next().is_some() {
+        return Err(TemplateError::EmodeChangeWithBorrows.into());
+    }
+
+    obligation.emode_category = emode_category;
+    obligation.mark_stale();
+
+    // Save obligation data
+    obligation.serialize(&mut &mut obligation_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a RefreshReserve instruction
+fn process_refresh_reserve(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let reserve_info = next_account_info(account_info_iter)?;
+
+    let mut reserve = load_reserve(program_id, reserve_info)?;
+    let current_slot = Clock::get()?.slot;
+    let price = match reserve.oracle_source {
+        OracleSource::Fixed => FixedPriceSource {
+            price_wads: reserve.market_price,
+        }
+        .get_price(current_slot)?,
+        oracle_source => {
+            let oracle_info = next_account_info(account_info_iter)?;
+            if *oracle_info.key != reserve.oracle {
+                return Err(TemplateError::InvalidOracle.into());
+            }
+            read_oracle_price(oracle_source, oracle_info, current_slot)?
+        }
+    };
+    price.validate(
+        current_slot,
+        reserve.config.max_staleness_slots,
+        reserve.config.max_confidence_bps,
+    )?;
+
+    reserve.accrue_interest(current_slot)?;
+    reserve.market_price = price.price_wads;
+    reserve.price_slot = current_slot;
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Processes a WithdrawObligationCollateral instruction
+fn process_withdraw_obligation_collateral(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    collateral_amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get accounts
+    let owner_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let market_authority_info = next_account_info(account_info_iter)?;
+    let collateral_vault_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Check the obligation owner is a signer
+    check_signer(owner_info)?;
+
+    if collateral_amount == 0 {
+        return Err(TemplateError::ZeroAmount.into());
+    }
+
+    let mut obligation = load_obligation(program_id, obligation_info)?;
+    if obligation.owner != *owner_info.key {
         return Err(TemplateError::InvalidAuthority.into());
     }
-    
-    // Update account data
-    template_account.value2 = template_account.value2.checked_add(1)
-        .ok_or(TemplateError::MathOverflow)?;
-    
-    // Save updated account data
-    template_account.serialize(&mut *account_info.data.borrow_mut())?;
-    
+    let reserve = load_reserve(program_id, reserve_info)?;
+    if reserve.lending_market != obligation.lending_market
+        || *lending_market_info.key != obligation.lending_market
+    {
+        return Err(TemplateError::InvalidLendingMarket.into());
+    }
+    let lending_market = load_lending_market(program_id, lending_market_info)?;
+    check_market_authority(
+        program_id,
+        lending_market_info.key,
+        lending_market.bump_seed,
+        market_authority_info.key,
+    )?;
+    if *collateral_vault_info.key != reserve.collateral_vault {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    check_token_program(token_program_info)?;
+
+    // Withdraw against the values of a refresh in this slot
+    let current_slot = Clock::get()?.slot;
+    if !obligation.is_fresh(current_slot) {
+        return Err(TemplateError::ObligationStale.into());
+    }
+    if !reserve.is_price_fresh(current_slot) {
+        return Err(TemplateError::ReserveStale.into());
+    }
+
+    let deposited_amount = obligation
+        .find_deposit_mut(reserve_info.key)
+        .ok_or(TemplateError::ObligationCollateralEmpty)?
+        .deposited_amount;
+    let withdraw_amount = collateral_amount.min(deposited_amount);
+    let remaining_amount = deposited_amount - withdraw_amount;
+
+    // The debt must stay within the borrowing power of the collateral left
+    let emode_category = obligation.emode_category;
+    let withdrawn_power = reserve
+        .borrowing_power(deposited_amount, emode_category)?
+        .saturating_sub(reserve.borrowing_power(remaining_amount, emode_category)?);
+    let allowed_borrow_value = obligation
+        .allowed_borrow_value_wads
+        .saturating_sub(withdrawn_power);
+    if obligation.borrowed_value_wads > allowed_borrow_value {
+        return Err(TemplateError::WithdrawTooLarge.into());
+    }
+
+    if let Some(deposit) = obligation.find_deposit_mut(reserve_info.key) {
+        deposit.deposited_amount = remaining_amount;
+    }
+    obligation.remove_empty_deposit(reserve_info.key);
+    obligation.mark_stale();
+
+    // Save obligation data
+    obligation.serialize(&mut &mut obligation_info.data.borrow_mut()[..])?;
+
+    transfer_from_vault(
+        token_program_info,
+        collateral_vault_info,
+        destination_info,
+        market_authority_info,
+        &[
+            lending_market_info.key.as_ref(),
+            &[lending_market.bump_seed],
+        ],
+        withdraw_amount,
+    )?;
+    msg!("Withdrew {} cTokens", withdraw_amount);
+
+    Ok(())
+}
+
+/// Accrues interest on the reserve `expected` of an obligation's deposit or
+/// borrow slot up to `current_slot` and saves it
+fn refresh_obligation_reserve(
+    program_id: &Pubkey,
+    lending_market: &Pubkey,
+    reserve_info: &AccountInfo,
+    expected: &Pubkey,
+    current_slot: u64,
+) -> Result<Reserve, ProgramError> {
+    if reserve_info.key != expected {
+        return Err(TemplateError::InvalidObligationReserves.into());
+    }
+    let mut reserve = load_reserve(program_id, reserve_info)?;
+    if reserve.lending_market != *lending_market {
+        return Err(TemplateError::InvalidLendingMarket.into());
+    }
+    if !reserve.is_price_fresh(current_slot) {
+        return Err(TemplateError::ReserveStale.into());
+    }
+    reserve.accrue_interest(current_slot)?;
+
+    // Save reserve data
+    reserve.serialize(&mut &mut reserve_info.data.borrow_mut()[..])?;
+
+    Ok(reserve)
+}
+
+/// Reads the price an oracle account of `oracle_source` reports as of
+/// `current_slot`
+fn read_oracle_price(
+    oracle_source: OracleSource,
+    oracle_info: &AccountInfo,
+    current_slot: u64,
+) -> Result<OraclePrice, ProgramError> {
+    let data = oracle_info.data.borrow();
+    let price = match oracle_source {
+        OracleSource::Fixed => return Err(TemplateError::InvalidOracle.into()),
+        OracleSource::Pyth => PythPriceSource::new(&data)?.get_price(current_slot)?,
+        OracleSource::Switchboard => SwitchboardPriceSource::new(&data)?.get_price(current_slot)?,
+    };
+    Ok(price)
+}
+
+/// Loads the reserve an isolated obligation's collateral comes from
+fn load_isolated_reserve(
+    program_id: &Pubkey,
+    obligation: &Obligation,
+    reserve_info: &AccountInfo,
+) -> Result<Reserve, ProgramError> {
+    if *reserve_info.key != obligation.isolated_reserve {
+        return Err(TemplateError::InvalidObligationReserves.into());
+    }
+    load_reserve(program_id, reserve_info)
+}
+
+/// Checks that a new account is owned by this program, rent exempt, of
+/// `size` bytes and not initialized yet
+fn check_new_account(
+    program_id: &Pubkey,
+    account_info: &AccountInfo,
+    rent_info: &AccountInfo,
+    size: usize,
+) -> ProgramResult {
+    check_account_owner(account_info, program_id)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
+        return Err(TemplateError::NotRentExempt.into());
+    }
+
+    if account_info.data_len() != size {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if account_info.data.borrow()[0] != 0 {
+        return Err(TemplateError::AlreadyInitialized.into());
+    }
     Ok(())
 }
+
+/// Loads an initialized lending market
+fn load_lending_market(
+    program_id: &Pubkey,
+    lending_market_info: &AccountInfo,
+) -> Result<LendingMarket, ProgramError> {
+    check_account_owner(lending_market_info, program_id)?;
+    let lending_market = LendingMarket::try_from_slice(&lending_market_info.data.borrow())?;
+    if !lending_market.is_initialized {
+        return Err(TemplateError::NotInitialized.into());
+    }
+    Ok(lending_market)
+}
+
+/// Loads an initialized reserve
+fn load_reserve(program_id: &Pubkey, reserve_info: &AccountInfo) -> Result<Reserve, ProgramError> {
+    check_account_owner(reserve_info, program_id)?;
+    let reserve = Reserve::try_from_slice(&reserve_info.data.borrow())?;
+    if !reserve.is_initialized {
+        return Err(TemplateError::NotInitialized.into());
+    }
+    Ok(reserve)
+}
+
+/// Loads an initialized obligation
+fn load_obligation(
+    program_id: &Pubkey,
+    obligation_info: &AccountInfo,
+) -> Result<Obligation, ProgramError> {
+    check_account_owner(obligation_info, program_id)?;
+    let obligation = Obligation::try_from_slice(&obligation_info.data.borrow())?;
+    if !obligation.is_initialized {
+        return Err(TemplateError::NotInitialized.into());
+    }
+    Ok(obligation)
+}
+
+/// Checks the lending market, its authority and the liquidity vault of a
+/// reserve moving liquidity out of the vault, and loads the lending market
+fn check_reserve_accounts(
+    program_id: &Pubkey,
+    reserve: &Reserve,
+    lending_market_info: &AccountInfo,
+    market_authority_info: &AccountInfo,
+    liquidity_vault_info: &AccountInfo,
+    token_program_info: &AccountInfo,
+) -> Result<LendingMarket, ProgramError> {
+    if *lending_market_info.key != reserve.lending_market {
+        return Err(TemplateError::InvalidLendingMarket.into());
+    }
+    let lending_market = load_lending_market(program_id, lending_market_info)?;
+    check_market_authority(
+        program_id,
+        lending_market_info.key,
+        lending_market.bump_seed,
+        market_authority_info.key,
+    )?;
+    if *liquidity_vault_info.key != reserve.liquidity_vault {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    check_token_program(token_program_info)?;
+    Ok(lending_market)
+}
+
+/// Checks the token program
+fn check_token_program(token_program_info: &AccountInfo) -> ProgramResult {
+    if *token_program_info.key != spl_token::id() {
+        return Err(TemplateError::InvalidTokenProgram.into());
+    }
+    Ok(())
+}
+
+/// Checks that a new vault is an empty token account of `mint` that `authority` controls
+fn check_vault(vault_info: &AccountInfo, mint: &Pubkey, authority: &Pubkey) -> ProgramResult {
+    check_account_owner(vault_info, &spl_token::id()).map_err(|_| TemplateError::InvalidVault)?;
+    let vault = spl_token::state::Account::unpack(&vault_info.data.borrow())?;
+    if vault.mint != *mint
+        || vault.owner != *authority
+        || vault.amount != 0
+        || vault.delegate.is_some()
+        || vault.close_authority.is_some()
+    {
+        return Err(TemplateError::InvalidVault.into());
+    }
+    Ok(())
+}
+
+/// Transfers tokens from a user-owned account into a vault
+fn transfer_to_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            source_info.key,
+            vault_info.key,
+            owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            source_info.clone(),
+            vault_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+/// Transfers tokens out of a vault, signing as the lending market authority that owns it
+fn transfer_from_vault<'a>(
+    token_program_info: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    authority_seeds: &[&[u8]],
+    amount: u64,
+) -> ProgramResult {
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            vault_info.key,
+            destination_info.key,
+            authority_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            vault_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[authority_seeds],
+    )
+}
+
+/// Mints cTokens, signing as the lending market authority that controls the collateral mint
+fn mint_collateral<'a>(
+    token_program_info: &AccountInfo<'a>,
+    collateral_mint_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    authority_seeds: &[&[u8]],
+    amount: u64,
+) -> ProgramResult {
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            token_program_info.key,
+            collateral_mint_info.key,
+            destination_info.key,
+            authority_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            collateral_mint_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[authority_seeds],
+    )
+}
+
+/// Burns cTokens from a user-owned account
+fn burn_collateral<'a>(
+    token_program_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    collateral_mint_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    invoke(
+        &spl_token::instruction::burn(
+            token_program_info.key,
+            source_info.key,
+            collateral_mint_info.key,
+            owner_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            source_info.clone(),
+            collateral_mint_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
@@ -1,11 +1,18 @@
 //! Overcollateralized lending protocol
 //!
-//! This program provides [TEMPLATE FUNCTIONALITY].
+//! This program runs lending markets of reserves, each a pool of one token.
+//! Depositors lend liquidity to a reserve for collateral tokens whose
+//! exchange rate grows with the interest borrowers pay, at a rate that kinks
+//! upward with the reserve's utilization. An obligation deposits collateral
+//! tokens of any reserves in its market and borrows from others up to the
+//! loan-to-value ratio of its collateral. Reserves are priced by Pyth or
+//! Switchboard oracles, or at a fixed price set by the market owner.
 
 pub mod instructions;
 pub mod state;
 pub mod utils;
 pub mod errors;
+pub mod oracle;
 
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
@@ -13,7 +20,7 @@ pub mod entrypoint;
 // Export current sdk types for downstream users building with a different sdk version
 pub use solana_program;
 
-solana_program::declare_id!("overcollateralized-lending111111111111111111111111111111");
+solana_program::declare_id!("Lend111111111111111111111111111111111111111");
 
 /// Program entrypoint's implementation
 pub fn process_instruction(
@@ -0,0 +1,127 @@
+//! Price oracles reserves read their market price from
+//!
+//! Each adapter parses an oracle account's raw bytes into an `OraclePrice`,
+//! so the program depends on no oracle SDK. `FixedPriceSource` reports a
+//! price set by the lending market owner, for tests and assets without a
+//! feed.
+
+pub mod pyth;
+pub mod switchboard;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    errors::TemplateError,
+    state::{BPS_DENOMINATOR, WAD},
+};
+
+pub use pyth::PythPriceSource;
+pub use switchboard::SwitchboardPriceSource;
+
+/// Kind of oracle a reserve reads its price from
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum OracleSource {
+    /// Price set by the lending market owner with `SetReservePrice`
+    #[default]
+    Fixed,
+
+    /// Pyth price account
+    Pyth,
+
+    /// Switchboard aggregator account
+    Switchboard,
+}
+
+/// Price of one whole token in the quote currency, as an oracle reports it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OraclePrice {
+    /// Price, scaled by `WAD`
+    pub price_wads: u128,
+
+    /// Width of the price's confidence interval either side, scaled by `WAD`
+    pub confidence_wads: u128,
+
+    /// Slot the price was published at
+    pub publish_slot: u64,
+}
+
+impl OraclePrice {
+    /// Checks the price was published at most `max_staleness_slots` before
+    /// `current_slot` and its confidence interval is at most
+    /// `max_confidence_bps` of it
+    pub fn validate(
+        &self,
+        current_slot: u64,
+        max_staleness_slots: u64,
+        max_confidence_bps: u64,
+    ) -> Result<(), TemplateError> {
+        if current_slot.saturating_sub(self.publish_slot) > max_staleness_slots {
+            return Err(TemplateError::StaleOracle);
+        }
+
+        let confidence_bps = self
+            .confidence_wads
+            .checked_mul(BPS_DENOMINATOR as u128)
+            .ok_or(TemplateError::MathOverflow)?;
+        let max_confidence = self
+            .price_wads
+            .checked_mul(max_confidence_bps as u128)
+            .ok_or(TemplateError::MathOverflow)?;
+        if confidence_bps > max_confidence {
+            return Err(TemplateError::OracleConfidenceTooWide);
+        }
+        Ok(())
+    }
+}
+
+/// Source of a reserve's market price
+pub trait PriceSource {
+    /// Latest price the source reports, as of `current_slot`
+    fn get_price(&self, current_slot: u64) -> Result<OraclePrice, TemplateError>;
+}
+
+/// Price the lending market owner sets, always current and with no
+/// uncertainty
+///
+/// The owner can move it at any time with `SetReservePrice`, so it suits
+/// tests and assets the owner is trusted to price, not assets a feed should
+/// price.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedPriceSource {
+    /// Price, scaled by `WAD`
+    pub price_wads: u128,
+}
+
+impl PriceSource for FixedPriceSource {
+    fn get_price(&self, current_slot: u64) -> Result<OraclePrice, TemplateError> {
+        Ok(OraclePrice {
+            price_wads: self.price_wads,
+            confidence_wads: 0,
+            publish_slot: current_slot,
+        })
+    }
+}
+
+/// `mantissa * 10^exponent`, scaled by `WAD` and rounded down
+fn decimal_to_wads(mantissa: u128, exponent: i32) -> Result<u128, TemplateError> {
+    let wad_exponent = exponent
+        .checked_add(WAD.ilog10() as i32)
+        .ok_or(TemplateError::MathOverflow)?;
+    let scale = 10u128
+        .checked_pow(wad_exponent.unsigned_abs())
+        .ok_or(TemplateError::MathOverflow)?;
+    if wad_exponent >= 0 {
+        mantissa
+            .checked_mul(scale)
+            .ok_or(TemplateError::MathOverflow)
+    } else {
+        Ok(mantissa / scale)
+    }
+}
+
+/// `N` bytes of an oracle account at `offset`
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], TemplateError> {
+    data.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(TemplateError::InvalidOracle)
+}
@@ -0,0 +1,71 @@
+//! Adapter for Pyth price accounts
+//!
+//! Reads the aggregate price of a version 2 price account. Only the header
+//! and the aggregate price are parsed, at their fixed offsets.
+
+use super::{decimal_to_wads, read_bytes, OraclePrice, PriceSource};
+use crate::errors::TemplateError;
+
+/// Magic number every Pyth account starts with
+pub const MAGIC: u32 = 0xa1b2_c3d4;
+
+/// Version of the account layout parsed
+pub const VERSION: u32 = 2;
+
+/// Account type of price accounts
+pub const ACCOUNT_TYPE_PRICE: u32 = 3;
+
+/// Aggregate price status of a feed that is trading
+pub const STATUS_TRADING: u32 = 1;
+
+// Offsets of the fields read, in bytes
+pub const MAGIC_OFFSET: usize = 0;
+pub const VERSION_OFFSET: usize = 4;
+pub const ACCOUNT_TYPE_OFFSET: usize = 8;
+pub const EXPONENT_OFFSET: usize = 20;
+pub const AGGREGATE_PRICE_OFFSET: usize = 208;
+pub const AGGREGATE_CONFIDENCE_OFFSET: usize = 216;
+pub const AGGREGATE_STATUS_OFFSET: usize = 224;
+pub const AGGREGATE_PUBLISH_SLOT_OFFSET: usize = 232;
+
+/// Pyth price account data
+pub struct PythPriceSource<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PythPriceSource<'a> {
+    /// Wraps the data of a price account, checking its header
+    pub fn new(data: &'a [u8]) -> Result<Self, TemplateError> {
+        let read_u32 = |offset| read_bytes(data, offset).map(u32::from_le_bytes);
+        if read_u32(MAGIC_OFFSET)? != MAGIC
+            || read_u32(VERSION_OFFSET)? != VERSION
+            || read_u32(ACCOUNT_TYPE_OFFSET)? != ACCOUNT_TYPE_PRICE
+        {
+            return Err(TemplateError::InvalidOracle);
+        }
+        Ok(Self { data })
+    }
+}
+
+impl PriceSource for PythPriceSource<'_> {
+    fn get_price(&self, _current_slot: u64) -> Result<OraclePrice, TemplateError> {
+        let data = self.data;
+        let status = u32::from_le_bytes(read_bytes(data, AGGREGATE_STATUS_OFFSET)?);
+        if status != STATUS_TRADING {
+            return Err(TemplateError::InvalidOracle);
+        }
+
+        let exponent = i32::from_le_bytes(read_bytes(data, EXPONENT_OFFSET)?);
+        let price = i64::from_le_bytes(read_bytes(data, AGGREGATE_PRICE_OFFSET)?);
+        let confidence = u64::from_le_bytes(read_bytes(data, AGGREGATE_CONFIDENCE_OFFSET)?);
+        if price <= 0 {
+            return Err(TemplateError::InvalidOracle);
+        }
+
+        Ok(OraclePrice {
+            price_wads: decimal_to_wads(price as u128, exponent)?,
+            confidence_wads: decimal_to_wads(confidence as u128, exponent)?,
+            publish_slot: u64::from_le_bytes(read_bytes(data, AGGREGATE_PUBLISH_SLOT_OFFSET)?),
+        })
+    }
+}
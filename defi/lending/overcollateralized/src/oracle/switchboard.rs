@@ -0,0 +1,70 @@
+//! Adapter for Switchboard aggregator accounts
+//!
+//! Reads the latest confirmed round of a V2 aggregator account, whose
+//! fields are packed after the Anchor account discriminator. Only the
+//! fields of the round that are needed are parsed, at their fixed offsets.
+
+use super::{decimal_to_wads, read_bytes, OraclePrice, PriceSource};
+use crate::errors::TemplateError;
+
+/// Anchor discriminator of aggregator accounts
+pub const DISCRIMINATOR: [u8; 8] = [217, 230, 65, 101, 201, 162, 27, 125];
+
+// Offsets of the fields of the latest confirmed round read, in bytes
+pub const NUM_SUCCESS_OFFSET: usize = 341;
+pub const ROUND_OPEN_SLOT_OFFSET: usize = 350;
+pub const RESULT_MANTISSA_OFFSET: usize = 366;
+pub const RESULT_SCALE_OFFSET: usize = 382;
+pub const STD_DEVIATION_MANTISSA_OFFSET: usize = 386;
+pub const STD_DEVIATION_SCALE_OFFSET: usize = 402;
+
+/// Switchboard aggregator account data
+pub struct SwitchboardPriceSource<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> SwitchboardPriceSource<'a> {
+    /// Wraps the data of an aggregator account, checking its discriminator
+    pub fn new(data: &'a [u8]) -> Result<Self, TemplateError> {
+        if read_bytes::<8>(data, 0)? != DISCRIMINATOR {
+            return Err(TemplateError::InvalidOracle);
+        }
+        Ok(Self { data })
+    }
+
+    /// Decimal of the round at `mantissa_offset` and `scale_offset`, which
+    /// must not be negative, scaled by `WAD`
+    fn read_decimal(
+        &self,
+        mantissa_offset: usize,
+        scale_offset: usize,
+    ) -> Result<u128, TemplateError> {
+        let mantissa = i128::from_le_bytes(read_bytes(self.data, mantissa_offset)?);
+        let scale = u32::from_le_bytes(read_bytes(self.data, scale_offset)?);
+        let mantissa = u128::try_from(mantissa).map_err(|_| TemplateError::InvalidOracle)?;
+        let exponent = i32::try_from(scale).map_err(|_| TemplateError::InvalidOracle)?;
+        decimal_to_wads(mantissa, -exponent)
+    }
+}
+
+impl PriceSource for SwitchboardPriceSource<'_> {
+    fn get_price(&self, _current_slot: u64) -> Result<OraclePrice, TemplateError> {
+        // A round without any successful oracle response has no result
+        let num_success = u32::from_le_bytes(read_bytes(self.data, NUM_SUCCESS_OFFSET)?);
+        if num_success == 0 {
+            return Err(TemplateError::InvalidOracle);
+        }
+
+        let price_wads = self.read_decimal(RESULT_MANTISSA_OFFSET, RESULT_SCALE_OFFSET)?;
+        if price_wads == 0 {
+            return Err(TemplateError::InvalidOracle);
+        }
+
+        Ok(OraclePrice {
+            price_wads,
+            confidence_wads: self
+                .read_decimal(STD_DEVIATION_MANTISSA_OFFSET, STD_DEVIATION_SCALE_OFFSET)?,
+            publish_slot: u64::from_le_bytes(read_bytes(self.data, ROUND_OPEN_SLOT_OFFSET)?),
+        })
+    }
+}
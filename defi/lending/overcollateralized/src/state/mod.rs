@@ -1,20 +1,848 @@
-//! State objects for template account
+//! State objects for lending markets, reserves and obligations
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::pubkey::Pubkey;
 
-/// Template account data
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-pub struct TemplateAccount {
-    /// The account authority (owner)
-    pub authority: Pubkey,
-    
-    /// Example value 1
-    pub value1: u64,
-    
-    /// Example value 2
-    pub value2: u8,
-    
-    /// Is the account initialized
+use crate::{errors::TemplateError, oracle::OracleSource};
+
+/// Scale of fixed-point rates, indexes, values and prices
+pub const WAD: u128 = 1_000_000_000_000_000_000;
+
+/// Denominator of loan-to-value ratios, utilizations and borrow rates
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Slots in a year at 400ms per slot, over which borrow rates are quoted
+pub const SLOTS_PER_YEAR: u64 = 78_840_000;
+
+/// Most reserves an obligation can deposit into, and separately borrow from
+pub const MAX_OBLIGATION_RESERVES: usize = 4;
+
+/// Group of reserves whose deposits can back borrows from one another
+///
+/// The lending market authority PDA, derived from the market address, owns
+/// the vaults of every reserve in the market and is the mint authority of
+/// their collateral tokens.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct LendingMarket {
+    /// Is the lending market initialized
+    pub is_initialized: bool,
+
+    /// Bump seed of the lending market authority PDA
+    pub bump_seed: u8,
+
+    /// Owner allowed to add reserves and set their prices
+    pub owner: Pubkey,
+}
+
+impl LendingMarket {
+    /// Get the size of LendingMarket struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + bump_seed (1 byte) + owner (32 bytes)
+        1 + 1 + 32
+    }
+}
+
+/// How a reserve's deposits can back borrows
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum AssetTier {
+    /// Backs borrows from any reserve, alongside any other collateral
+    #[default]
+    Standard,
+
+    /// Like `Standard`, and can also be borrowed against isolated collateral
+    Stable,
+
+    /// Must be an obligation's only collateral, and backs borrows of
+    /// `Stable` reserves only, up to the reserve's debt ceiling
+    Isolated,
+}
+
+/// Risk and interest rate parameters of a reserve
+///
+/// Deposits can be borrowed against up to `loan_to_value_bps` of their
+/// value, and the obligation can be liquidated once its debt is worth more
+/// than `liquidation_threshold_bps` of its deposits. A liquidator repays at
+/// most `close_factor_bps` of one debt at a time and seizes collateral worth
+/// the repayment plus `liquidation_bonus_bps`.
+///
+/// The borrow rate rises linearly with utilization from `min_borrow_rate_bps`
+/// to `optimal_borrow_rate_bps` at `optimal_utilization_bps`, then more
+/// steeply to `max_borrow_rate_bps` at full utilization.
+///
+/// Obligations in the e-mode category of the reserve, a group of correlated
+/// assets, borrow against its deposits at `emode_loan_to_value_bps` and are
+/// liquidated past `emode_liquidation_threshold_bps` instead.
+///
+/// Oracle prices older than `max_staleness_slots`, or whose confidence
+/// interval is wider than `max_confidence_bps` of the price, are refused.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReserveConfig {
+    /// Share of the collateral value that can be borrowed against
+    pub loan_to_value_bps: u64,
+
+    /// Share of the collateral value that debt can reach before the
+    /// obligation can be liquidated
+    pub liquidation_threshold_bps: u64,
+
+    /// Bonus on the value of the debt a liquidator repays, paid in
+    /// collateral of this reserve
+    pub liquidation_bonus_bps: u64,
+
+    /// Largest share of a debt to this reserve one liquidation can repay
+    pub close_factor_bps: u64,
+
+    /// Utilization at which the borrow rate kinks
+    pub optimal_utilization_bps: u64,
+
+    /// Yearly borrow rate at zero utilization
+    pub min_borrow_rate_bps: u64,
+
+    /// Yearly borrow rate at the optimal utilization
+    pub optimal_borrow_rate_bps: u64,
+
+    /// Yearly borrow rate at full utilization
+    pub max_borrow_rate_bps: u64,
+
+    /// How deposits of this reserve can back borrows
+    pub asset_tier: AssetTier,
+
+    /// Most value, in whole units of the quote currency, that can be
+    /// borrowed against this reserve's deposits when it is `Isolated`
+    pub debt_ceiling: u64,
+
+    /// E-mode category of the reserve, 0 for none
+    pub emode_category: u8,
+
+    /// Loan-to-value ratio for obligations in the reserve's e-mode category
+    pub emode_loan_to_value_bps: u64,
+
+    /// Liquidation threshold for obligations in the reserve's e-mode
+    /// category
+    pub emode_liquidation_threshold_bps: u64,
+
+    /// Most slots an oracle price can be published before it is read
+    pub max_staleness_slots: u64,
+
+    /// Widest confidence interval of an oracle price, either side, as a
+    /// share of the price
+    pub max_confidence_bps: u64,
+}
+
+impl ReserveConfig {
+    /// Size of ReserveConfig struct
+    pub const SIZE: usize = 8 * 8 + 1 + 8 + 1 + 8 * 2 + 8 * 2;
+
+    /// Checks the parameters are consistent
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        if self.loan_to_value_bps > self.liquidation_threshold_bps
+            || self.liquidation_threshold_bps >= BPS_DENOMINATOR
+            || self.liquidation_bonus_bps > BPS_DENOMINATOR
+            || self.close_factor_bps == 0
+            || self.close_factor_bps > BPS_DENOMINATOR
+            || self.optimal_utilization_bps == 0
+            || self.optimal_utilization_bps > BPS_DENOMINATOR
+            || self.min_borrow_rate_bps > self.optimal_borrow_rate_bps
+            || self.optimal_borrow_rate_bps > self.max_borrow_rate_bps
+            || self.max_confidence_bps > BPS_DENOMINATOR
+        {
+            return Err(TemplateError::InvalidReserveConfig);
+        }
+
+        // Only isolated reserves have a debt ceiling, and they must have one
+        let isolated = self.asset_tier == AssetTier::Isolated;
+        if isolated != (self.debt_ceiling > 0) {
+            return Err(TemplateError::InvalidReserveConfig);
+        }
+
+        // E-mode only ever raises the ratios, and isolated reserves have none
+        if self.emode_category == 0 {
+            if self.emode_loan_to_value_bps != 0 || self.emode_liquidation_threshold_bps != 0 {
+                return Err(TemplateError::InvalidReserveConfig);
+            }
+        } else if isolated
+            || self.emode_loan_to_value_bps < self.loan_to_value_bps
+            || self.emode_liquidation_threshold_bps < self.liquidation_threshold_bps
+            || self.emode_loan_to_value_bps > self.emode_liquidation_threshold_bps
+            || self.emode_liquidation_threshold_bps >= BPS_DENOMINATOR
+        {
+            return Err(TemplateError::InvalidReserveConfig);
+        }
+        Ok(())
+    }
+}
+
+/// Pool of one token that depositors lend to borrowers
+///
+/// Depositors receive collateral tokens (cTokens) for their liquidity at an
+/// exchange rate that grows as borrowers pay interest. The total liquidity
+/// is what the vault holds plus what is borrowed, and the borrowed amount
+/// compounds every slot at the borrow rate of the current utilization.
+/// `cumulative_borrow_rate_wads` tracks that compounding since the reserve
+/// was created, so each obligation can bring its own debt up to date from
+/// the index it last saw.
+///
+/// cTokens deposited into obligations sit in the collateral vault.
+///
+/// `RefreshReserve` reads `market_price` from the reserve's oracle. Prices of
+/// oracle sources are only trusted within the slot they were read in, while
+/// a fixed price is always current.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct Reserve {
+    /// Is the reserve initialized
+    pub is_initialized: bool,
+
+    /// Lending market the reserve belongs to
+    pub lending_market: Pubkey,
+
+    /// Mint of the token lent out
+    pub liquidity_mint: Pubkey,
+
+    /// Decimals of the liquidity mint, which the collateral mint shares
+    pub liquidity_decimals: u8,
+
+    /// Token account holding the liquidity not borrowed
+    pub liquidity_vault: Pubkey,
+
+    /// Mint of the reserve's cTokens
+    pub collateral_mint: Pubkey,
+
+    /// Token account holding the cTokens deposited into obligations
+    pub collateral_vault: Pubkey,
+
+    /// Risk and interest rate parameters
+    pub config: ReserveConfig,
+
+    /// Liquidity in the vault that can be borrowed or redeemed
+    pub available_liquidity: u64,
+
+    /// Liquidity borrowed, with accrued interest, scaled by `WAD`
+    pub borrowed_amount_wads: u128,
+
+    /// Growth of a debt since the reserve was created, scaled by `WAD`
+    pub cumulative_borrow_rate_wads: u128,
+
+    /// Slot interest was last accrued at
+    pub last_update_slot: u64,
+
+    /// cTokens minted and not redeemed
+    pub collateral_supply: u64,
+
+    /// Value of one whole liquidity token in the market's quote currency,
+    /// scaled by `WAD`
+    pub market_price: u128,
+
+    /// Kind of oracle the price is read from
+    pub oracle_source: OracleSource,
+
+    /// Oracle account, the default pubkey for a fixed price
+    pub oracle: Pubkey,
+
+    /// Slot the price was last read from the oracle at
+    pub price_slot: u64,
+
+    /// Value borrowed against this reserve's deposits while it is
+    /// `Isolated`, at the prices of the borrows and repayments, scaled by
+    /// `WAD`
+    pub isolated_debt_wads: u128,
+}
+
+impl Reserve {
+    /// Get the size of Reserve struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + lending_market and liquidity_mint
+        // (32 bytes * 2) + liquidity_decimals (1 byte) + liquidity_vault,
+        // collateral_mint and collateral_vault (32 bytes * 3) + config +
+        // available_liquidity (8 bytes) + borrowed_amount_wads and
+        // cumulative_borrow_rate_wads (16 bytes * 2) + last_update_slot
+        // (8 bytes) + collateral_supply (8 bytes) + market_price (16 bytes) +
+        // oracle_source (1 byte) + oracle (32 bytes) + price_slot (8 bytes) +
+        // isolated_debt_wads (16 bytes)
+        1 + 32 * 2 + 1 + 32 * 3 + ReserveConfig::SIZE + 8 + 16 * 2 + 8 + 8 + 16 + 1 + 32 + 8 + 16
+    }
+
+    /// Can the price be trusted in `current_slot`
+    pub fn is_price_fresh(&self, current_slot: u64) -> bool {
+        self.oracle_source == OracleSource::Fixed || self.price_slot == current_slot
+    }
+
+    /// Liquidity owed to cToken holders, scaled by `WAD`
+    pub fn total_liquidity_wads(&self) -> Result<u128, TemplateError> {
+        (self.available_liquidity as u128)
+            .checked_mul(WAD)
+            .and_then(|available| available.checked_add(self.borrowed_amount_wads))
+            .ok_or(TemplateError::MathOverflow)
+    }
+
+    /// Share of the total liquidity that is borrowed, scaled by `WAD`
+    pub fn utilization_wads(&self) -> Result<u128, TemplateError> {
+        let total = self.total_liquidity_wads()?;
+        if total == 0 {
+            return Ok(0);
+        }
+        mul_div(self.borrowed_amount_wads, WAD, total, false)
+    }
+
+    /// Yearly borrow rate at the current utilization, scaled by `WAD`
+    pub fn borrow_rate_wads(&self) -> Result<u128, TemplateError> {
+        let config = &self.config;
+        let utilization = self.utilization_wads()?;
+        let optimal_utilization = bps_to_wads(config.optimal_utilization_bps);
+        let min_rate = bps_to_wads(config.min_borrow_rate_bps);
+        let optimal_rate = bps_to_wads(config.optimal_borrow_rate_bps);
+        let max_rate = bps_to_wads(config.max_borrow_rate_bps);
+
+        if utilization <= optimal_utilization {
+            let slope = mul_div(
+                optimal_rate - min_rate,
+                utilization,
+                optimal_utilization,
+                false,
+            )?;
+            Ok(min_rate + slope)
+        } else {
+            let slope = mul_div(
+                max_rate - optimal_rate,
+                utilization - optimal_utilization,
+                WAD - optimal_utilization,
+                false,
+            )?;
+            Ok(optimal_rate + slope)
+        }
+    }
+
+    /// Compounds the borrowed liquidity and the cumulative borrow rate up to
+    /// `current_slot`, at the borrow rate of the utilization before accrual
+    pub fn accrue_interest(&mut self, current_slot: u64) -> Result<(), TemplateError> {
+        let elapsed = current_slot
+            .checked_sub(self.last_update_slot)
+            .ok_or(TemplateError::MathOverflow)?;
+        if elapsed == 0 {
+            return Ok(());
+        }
+
+        let slot_rate = self.borrow_rate_wads()? / SLOTS_PER_YEAR as u128;
+        let growth = pow_wads(WAD + slot_rate, elapsed)?;
+        self.cumulative_borrow_rate_wads =
+            mul_div(self.cumulative_borrow_rate_wads, growth, WAD, false)?;
+        self.borrowed_amount_wads = mul_div(self.borrowed_amount_wads, growth, WAD, false)?;
+        self.last_update_slot = current_slot;
+        Ok(())
+    }
+
+    /// cTokens minted for a deposit of `liquidity_amount`, rounded down
+    ///
+    /// The first deposit, and any deposit into a reserve without cTokens,
+    /// mints one cToken per base unit of liquidity.
+    pub fn collateral_for_liquidity(&self, liquidity_amount: u64) -> Result<u64, TemplateError> {
+        let total = self.total_liquidity_wads()?;
+        if self.collateral_supply == 0 || total == 0 {
+            return Ok(liquidity_amount);
+        }
+        let collateral = mul_div(
+            liquidity_amount as u128 * WAD,
+            self.collateral_supply as u128,
+            total,
+            false,
+        )?;
+        u64::try_from(collateral).map_err(|_| TemplateError::MathOverflow)
+    }
+
+    /// Liquidity that `collateral_amount` cTokens redeem for, rounded down
+    pub fn liquidity_for_collateral(&self, collateral_amount: u64) -> Result<u64, TemplateError> {
+        if collateral_amount > self.collateral_supply {
+            return Err(TemplateError::MathOverflow);
+        }
+        let liquidity_wads = mul_div(
+            self.total_liquidity_wads()?,
+            collateral_amount as u128,
+            self.collateral_supply as u128,
+            false,
+        )?;
+        u64::try_from(liquidity_wads / WAD).map_err(|_| TemplateError::MathOverflow)
+    }
+
+    /// Value of `liquidity_amount` base units in the quote currency, scaled
+    /// by `WAD`
+    pub fn market_value(
+        &self,
+        liquidity_amount: u64,
+        round_up: bool,
+    ) -> Result<u128, TemplateError> {
+        let one_token = 10u128
+            .checked_pow(self.liquidity_decimals as u32)
+            .ok_or(TemplateError::MathOverflow)?;
+        mul_div(
+            liquidity_amount as u128,
+            self.market_price,
+            one_token,
+            round_up,
+        )
+    }
+
+    /// Value of the liquidity `collateral_amount` cTokens redeem for, in the
+    /// quote currency scaled by `WAD`
+    pub fn collateral_market_value(&self, collateral_amount: u64) -> Result<u128, TemplateError> {
+        let liquidity_amount = self.liquidity_for_collateral(collateral_amount)?;
+        self.market_value(liquidity_amount, false)
+    }
+
+    /// Loan-to-value ratio and liquidation threshold of this reserve's
+    /// deposits in an obligation in `emode_category`
+    pub fn collateral_ratios_bps(&self, emode_category: u8) -> (u64, u64) {
+        let config = &self.config;
+        if emode_category != 0 && emode_category == config.emode_category {
+            (
+                config.emode_loan_to_value_bps,
+                config.emode_liquidation_threshold_bps,
+            )
+        } else {
+            (config.loan_to_value_bps, config.liquidation_threshold_bps)
+        }
+    }
+
+    /// Borrowing power `collateral_amount` cTokens give an obligation in
+    /// `emode_category`, in the quote currency scaled by `WAD`
+    pub fn borrowing_power(
+        &self,
+        collateral_amount: u64,
+        emode_category: u8,
+    ) -> Result<u128, TemplateError> {
+        let (loan_to_value_bps, _) = self.collateral_ratios_bps(emode_category);
+        mul_div(
+            self.collateral_market_value(collateral_amount)?,
+            loan_to_value_bps as u128,
+            BPS_DENOMINATOR as u128,
+            false,
+        )
+    }
+
+    /// Debt value above which `collateral_amount` cTokens can be
+    /// liquidated from an obligation in `emode_category`, in the quote
+    /// currency scaled by `WAD`
+    pub fn liquidation_threshold(
+        &self,
+        collateral_amount: u64,
+        emode_category: u8,
+    ) -> Result<u128, TemplateError> {
+        let (_, liquidation_threshold_bps) = self.collateral_ratios_bps(emode_category);
+        mul_div(
+            self.collateral_market_value(collateral_amount)?,
+            liquidation_threshold_bps as u128,
+            BPS_DENOMINATOR as u128,
+            false,
+        )
+    }
+}
+
+/// cTokens of one reserve deposited into an obligation
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct ObligationCollateral {
+    /// Reserve of the cTokens, the default pubkey for an unused slot
+    pub deposit_reserve: Pubkey,
+
+    /// cTokens deposited
+    pub deposited_amount: u64,
+
+    /// Value of the deposit at the last refresh, scaled by `WAD`
+    pub market_value_wads: u128,
+}
+
+/// Liquidity of one reserve borrowed by an obligation
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct ObligationLiquidity {
+    /// Reserve borrowed from, the default pubkey for an unused slot
+    pub borrow_reserve: Pubkey,
+
+    /// Cumulative borrow rate of the reserve when the debt was last
+    /// accrued, scaled by `WAD`
+    pub cumulative_borrow_rate_wads: u128,
+
+    /// Liquidity owed, with interest up to the last accrual, scaled by `WAD`
+    pub borrowed_amount_wads: u128,
+
+    /// Value of the debt at the last refresh, scaled by `WAD`
+    pub market_value_wads: u128,
+}
+
+impl ObligationLiquidity {
+    /// Size of ObligationLiquidity struct
+    pub const SIZE: usize = 32 + 16 + 16 + 16;
+
+    /// Brings the debt up to a reserve's current cumulative borrow rate
+    pub fn accrue_interest(
+        &mut self,
+        cumulative_borrow_rate_wads: u128,
+    ) -> Result<(), TemplateError> {
+        if cumulative_borrow_rate_wads < self.cumulative_borrow_rate_wads {
+            return Err(TemplateError::MathOverflow);
+        }
+        self.borrowed_amount_wads = mul_div(
+            self.borrowed_amount_wads,
+            cumulative_borrow_rate_wads,
+            self.cumulative_borrow_rate_wads,
+            true,
+        )?;
+        self.cumulative_borrow_rate_wads = cumulative_borrow_rate_wads;
+        Ok(())
+    }
+
+    /// Liquidity owed, rounded up to whole base units
+    pub fn borrowed_amount(&self) -> Result<u64, TemplateError> {
+        u64::try_from(self.borrowed_amount_wads.div_ceil(WAD))
+            .map_err(|_| TemplateError::MathOverflow)
+    }
+}
+
+/// Deposits and borrows of one owner within a lending market
+///
+/// Each slot of `deposits` and `borrows` holds one reserve. Unused slots
+/// have the default pubkey as reserve. `RefreshObligation` takes the
+/// reserves of every used deposit slot and then every used borrow slot, in
+/// slot order, and records the values of the obligation as of the current
+/// slot. Borrows and liquidations only trust those values within the slot
+/// of the refresh, and changes to the deposits or borrows mark them stale.
+///
+/// An obligation whose first deposit is of an `Isolated` reserve stays
+/// isolated to that reserve until it holds no deposits and no borrows.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq)]
+pub struct Obligation {
+    /// Is the obligation initialized
     pub is_initialized: bool,
+
+    /// Lending market the obligation belongs to
+    pub lending_market: Pubkey,
+
+    /// Wallet that controls the obligation
+    pub owner: Pubkey,
+
+    /// cTokens deposited as collateral
+    pub deposits: [ObligationCollateral; MAX_OBLIGATION_RESERVES],
+
+    /// Liquidity borrowed
+    pub borrows: [ObligationLiquidity; MAX_OBLIGATION_RESERVES],
+
+    /// Value of the deposits, scaled by `WAD`
+    pub deposited_value_wads: u128,
+
+    /// Value of the debt, scaled by `WAD`
+    pub borrowed_value_wads: u128,
+
+    /// Debt value the deposits can back, scaled by `WAD`
+    pub allowed_borrow_value_wads: u128,
+
+    /// Debt value above which the obligation can be liquidated, scaled by
+    /// `WAD`
+    pub unhealthy_borrow_value_wads: u128,
+
+    /// Slot the values were last refreshed at
+    pub last_update_slot: u64,
+
+    /// Have the deposits or borrows changed since the last refresh
+    pub stale: bool,
+
+    /// Isolated reserve that is the obligation's only collateral, the
+    /// default pubkey if none
+    pub isolated_reserve: Pubkey,
+
+    /// E-mode category the obligation borrows in, 0 for none
+    pub emode_category: u8,
+}
+
+/// Amounts of a liquidation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Liquidation {
+    /// Liquidity the liquidator repays
+    pub repay_amount: u64,
+
+    /// cTokens the liquidator seizes
+    pub withdraw_amount: u64,
+}
+
+impl Obligation {
+    /// Get the size of Obligation struct
+    pub fn get_size() -> usize {
+        // is_initialized (1 byte) + lending_market and owner (32 bytes * 2) +
+        // deposits ((32 + 8 + 16) bytes * MAX_OBLIGATION_RESERVES) +
+        // borrows (ObligationLiquidity::SIZE * MAX_OBLIGATION_RESERVES) +
+        // values (16 bytes * 4) + last_update_slot (8 bytes) + stale (1 byte)
+        // + isolated_reserve (32 bytes) + emode_category (1 byte)
+        1 + 32 * 2
+            + (32 + 8 + 16) * MAX_OBLIGATION_RESERVES
+            + ObligationLiquidity::SIZE * MAX_OBLIGATION_RESERVES
+            + 16 * 4
+            + 8
+            + 1
+            + 32
+            + 1
+    }
+
+    /// Is the obligation's collateral an isolated reserve
+    pub fn is_isolated(&self) -> bool {
+        self.isolated_reserve != Pubkey::default()
+    }
+
+    /// Does the obligation hold no deposits and no borrows
+    pub fn is_empty(&self) -> bool {
+        self.active_deposits().next().is_none() && self.active_borrows().next().is_none()
+    }
+
+    /// Used deposit slots, in slot order
+    pub fn active_deposits(&self) -> impl Iterator<Item = &ObligationCollateral> {
+        self.deposits
+            .iter()
+            .filter(|deposit| deposit.deposit_reserve != Pubkey::default())
+    }
+
+    /// Used borrow slots, in slot order
+    pub fn active_borrows(&self) -> impl Iterator<Item = &ObligationLiquidity> {
+        self.borrows
+            .iter()
+            .filter(|borrow| borrow.borrow_reserve != Pubkey::default())
+    }
+
+    /// Deposit slot of `reserve`, taking the first free slot if it has none
+    pub fn deposit_mut(
+        &mut self,
+        reserve: &Pubkey,
+    ) -> Result<&mut ObligationCollateral, TemplateError> {
+        let index = match self
+            .deposits
+            .iter()
+            .position(|deposit| deposit.deposit_reserve == *reserve)
+        {
+            Some(index) => index,
+            None => {
+                let index = self
+                    .deposits
+                    .iter()
+                    .position(|deposit| deposit.deposit_reserve == Pubkey::default())
+                    .ok_or(TemplateError::ObligationFull)?;
+                self.deposits[index] = ObligationCollateral {
+                    deposit_reserve: *reserve,
+                    ..ObligationCollateral::default()
+                };
+                index
+            }
+        };
+        Ok(&mut self.deposits[index])
+    }
+
+    /// Deposit slot of `reserve`, if it has one
+    pub fn find_deposit_mut(&mut self, reserve: &Pubkey) -> Option<&mut ObligationCollateral> {
+        self.deposits
+            .iter_mut()
+            .find(|deposit| deposit.deposit_reserve == *reserve && *reserve != Pubkey::default())
+    }
+
+    /// Borrow slot of `reserve`, if it has one
+    pub fn find_borrow_mut(&mut self, reserve: &Pubkey) -> Option<&mut ObligationLiquidity> {
+        self.borrows
+            .iter_mut()
+            .find(|borrow| borrow.borrow_reserve == *reserve && *reserve != Pubkey::default())
+    }
+
+    /// Borrow slot of `reserve`, taking the first free slot if it has none.
+    /// A new slot starts at `cumulative_borrow_rate_wads` with no debt.
+    pub fn borrow_mut(
+        &mut self,
+        reserve: &Pubkey,
+        cumulative_borrow_rate_wads: u128,
+    ) -> Result<&mut ObligationLiquidity, TemplateError> {
+        let index = match self
+            .borrows
+            .iter()
+            .position(|borrow| borrow.borrow_reserve == *reserve)
+        {
+            Some(index) => index,
+            None => {
+                let index = self
+                    .borrows
+                    .iter()
+                    .position(|borrow| borrow.borrow_reserve == Pubkey::default())
+                    .ok_or(TemplateError::ObligationFull)?;
+                self.borrows[index] = ObligationLiquidity {
+                    borrow_reserve: *reserve,
+                    cumulative_borrow_rate_wads,
+                    ..ObligationLiquidity::default()
+                };
+                index
+            }
+        };
+        Ok(&mut self.borrows[index])
+    }
+
+    /// Frees the deposit slot of `reserve` once it holds no cTokens, and
+    /// ends the isolation of an empty obligation
+    pub fn remove_empty_deposit(&mut self, reserve: &Pubkey) {
+        if let Some(deposit) = self.find_deposit_mut(reserve) {
+            if deposit.deposited_amount == 0 {
+                *deposit = ObligationCollateral::default();
+            }
+        }
+        if self.is_empty() {
+            self.isolated_reserve = Pubkey::default();
+        }
+    }
+
+    /// Frees the borrow slot of `reserve` once its debt is repaid, and ends
+    /// the isolation of an empty obligation
+    pub fn remove_repaid_borrow(&mut self, reserve: &Pubkey) {
+        if let Some(borrow) = self.find_borrow_mut(reserve) {
+            if borrow.borrowed_amount_wads == 0 {
+                *borrow = ObligationLiquidity::default();
+            }
+        }
+        if self.is_empty() {
+            self.isolated_reserve = Pubkey::default();
+        }
+    }
+
+    /// Are the values those of `current_slot`
+    pub fn is_fresh(&self, current_slot: u64) -> bool {
+        !self.stale && self.last_update_slot == current_slot
+    }
+
+    /// Requires a refresh before the values are trusted again
+    pub fn mark_stale(&mut self) {
+        self.stale = true;
+    }
+
+    /// Is the debt within the liquidation threshold of the deposits
+    pub fn is_healthy(&self) -> bool {
+        self.borrowed_value_wads <= self.unhealthy_borrow_value_wads
+    }
+
+    /// Amounts of a liquidation offering to repay `liquidity_amount` of the
+    /// debt to `repay_reserve` for cTokens of `withdraw_reserve`, from the
+    /// values of the last refresh
+    ///
+    /// The repayment is capped at the close factor of the debt, rounded up.
+    /// The liquidator seizes cTokens worth the repayment plus the liquidation
+    /// bonus of the collateral, rounded down. When that is more than the
+    /// deposit is worth, the liquidator seizes the whole deposit and repays
+    /// proportionally less, rounded up.
+    pub fn calculate_liquidation(
+        &self,
+        repay_reserve_key: &Pubkey,
+        repay_reserve: &Reserve,
+        withdraw_reserve_key: &Pubkey,
+        withdraw_reserve: &Reserve,
+        liquidity_amount: u64,
+    ) -> Result<Liquidation, TemplateError> {
+        let borrow = self
+            .active_borrows()
+            .find(|borrow| borrow.borrow_reserve == *repay_reserve_key)
+            .ok_or(TemplateError::ObligationLiquidityEmpty)?;
+        let deposit = self
+            .active_deposits()
+            .find(|deposit| deposit.deposit_reserve == *withdraw_reserve_key)
+            .ok_or(TemplateError::ObligationCollateralEmpty)?;
+
+        let max_repay_amount = mul_div(
+            borrow.borrowed_amount()? as u128,
+            repay_reserve.config.close_factor_bps as u128,
+            BPS_DENOMINATOR as u128,
+            true,
+        )?;
+        let mut repay_amount = (liquidity_amount as u128).min(max_repay_amount);
+        let repay_value = repay_reserve.market_value(repay_amount as u64, false)?;
+        if repay_value == 0 {
+            return Err(TemplateError::LiquidationTooSmall);
+        }
+        let seize_value = mul_div(
+            repay_value,
+            (BPS_DENOMINATOR + withdraw_reserve.config.liquidation_bonus_bps) as u128,
+            BPS_DENOMINATOR as u128,
+            false,
+        )?;
+
+        let withdraw_amount = if seize_value >= deposit.market_value_wads {
+            repay_amount = mul_div(repay_amount, deposit.market_value_wads, seize_value, true)?;
+            deposit.deposited_amount as u128
+        } else {
+            mul_div(
+                deposit.deposited_amount as u128,
+                seize_value,
+                deposit.market_value_wads,
+                false,
+            )?
+        };
+
+        if repay_amount == 0 || withdraw_amount == 0 {
+            return Err(TemplateError::LiquidationTooSmall);
+        }
+        Ok(Liquidation {
+            repay_amount: repay_amount as u64,
+            withdraw_amount: withdraw_amount as u64,
+        })
+    }
+}
+
+/// Converts basis points to a fraction scaled by `WAD`
+fn bps_to_wads(bps: u64) -> u128 {
+    bps as u128 * WAD / BPS_DENOMINATOR as u128
+}
+
+/// `a * b / denominator`, rounded down or up
+///
+/// The product is computed at 256 bits, so WAD-scaled operands can be
+/// multiplied together as long as the result fits in u128.
+fn mul_div(a: u128, b: u128, denominator: u128, round_up: bool) -> Result<u128, TemplateError> {
+    if denominator == 0 {
+        return Err(TemplateError::MathOverflow);
+    }
+    let (high, low) = widening_mul(a, b);
+    if high >= denominator {
+        return Err(TemplateError::MathOverflow);
+    }
+
+    // Long division of the 256-bit product, one bit at a time
+    let mut quotient = 0u128;
+    let mut remainder = high;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= denominator {
+            remainder = remainder.wrapping_sub(denominator);
+            quotient |= 1;
+        }
+    }
+
+    if round_up && remainder != 0 {
+        quotient = quotient.checked_add(1).ok_or(TemplateError::MathOverflow)?;
+    }
+    Ok(quotient)
+}
+
+/// Full 256-bit product of `a` and `b`, as its high and low halves
+fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_high, a_low) = (a >> 64, a & MASK);
+    let (b_high, b_low) = (b >> 64, b & MASK);
+
+    let low_low = a_low * b_low;
+    let low_high = a_low * b_high;
+    let high_low = a_high * b_low;
+    let high_high = a_high * b_high;
+
+    let middle = (low_low >> 64) + (low_high & MASK) + (high_low & MASK);
+    let low = (low_low & MASK) | (middle << 64);
+    let high = high_high + (low_high >> 64) + (high_low >> 64) + (middle >> 64);
+    (high, low)
+}
+
+/// `base ^ exponent` for a WAD-scaled base, by repeated squaring
+fn pow_wads(mut base: u128, mut exponent: u64) -> Result<u128, TemplateError> {
+    let mut result = WAD;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_div(result, base, WAD, false)?;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base = mul_div(base, base, WAD, false)?;
+        }
+    }
+    Ok(result)
 }
@@ -1,9 +1,7 @@
 //! Utility functions for the program
 
 use solana_program::{
-    account_info::AccountInfo,
-    entrypoint::ProgramResult,
-    program_error::ProgramError,
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
     pubkey::Pubkey,
 };
 
@@ -31,3 +29,24 @@ pub fn check_signer(account_info: &AccountInfo) -> ProgramResult {
 pub fn safe_addition(a: u64, b: u64) -> Result<u64, TemplateError> {
     a.checked_add(b).ok_or(TemplateError::MathOverflow)
 }
+
+/// Finds the lending market authority PDA that owns the market's vaults
+pub fn find_market_authority(program_id: &Pubkey, lending_market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[lending_market.as_ref()], program_id)
+}
+
+/// Checks that `authority` is the lending market authority PDA for the given bump seed
+pub fn check_market_authority(
+    program_id: &Pubkey,
+    lending_market: &Pubkey,
+    bump_seed: u8,
+    authority: &Pubkey,
+) -> ProgramResult {
+    let expected =
+        Pubkey::create_program_address(&[lending_market.as_ref(), &[bump_seed]], program_id)
+            .map_err(|_| TemplateError::InvalidMarketAuthority)?;
+    if expected != *authority {
+        return Err(TemplateError::InvalidMarketAuthority.into());
+    }
+    Ok(())
+}
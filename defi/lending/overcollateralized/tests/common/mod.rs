@@ -0,0 +1,740 @@
+//! Fixtures shared by the overcollateralized-lending integration tests
+//!
+//! Every test binary compiles this module but uses only part of it.
+
+#![allow(dead_code)]
+
+use {
+    borsh::BorshDeserialize,
+    overcollateralized_lending::{
+        errors::TemplateError,
+        instructions::{
+            borrow_obligation_liquidity, deposit_obligation_collateral, deposit_reserve_liquidity,
+            init_lending_market, init_obligation, init_reserve, liquidate_obligation,
+            redeem_reserve_collateral, refresh_obligation, repay_obligation_liquidity,
+            set_reserve_price, withdraw_obligation_collateral,
+        },
+        oracle::OracleSource,
+        process_instruction,
+        state::{LendingMarket, Obligation, Reserve, ReserveConfig, WAD},
+        utils::find_market_authority,
+    },
+    solana_program::{
+        instruction::{Instruction, InstructionError},
+        program_pack::Pack,
+        pubkey::Pubkey,
+        rent::Rent,
+        system_instruction,
+    },
+    solana_program_test::{
+        processor, BanksClient, ProgramTest, ProgramTestBanksClientExt, ProgramTestContext,
+    },
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+/// One whole USDC, with 6 decimals
+pub const USDC: u64 = 1_000_000;
+/// One whole SOL, with 9 decimals
+pub const SOL: u64 = 1_000_000_000;
+/// Price of one SOL in the quote currency
+pub const SOL_PRICE: u128 = 20 * WAD;
+/// USDC the lender deposits into the USDC reserve
+pub const LENDER_USDC: u64 = 1_000 * USDC;
+/// Tokens each borrower starts with
+pub const BORROWER_SOL: u64 = 100 * SOL;
+pub const BORROWER_USDC: u64 = 100 * USDC;
+
+/// USDC can be borrowed at up to 80% of its value, is liquidated past
+/// 85% with a 5% bonus, and pays 10% a year at 80% utilization
+pub fn usdc_config() -> ReserveConfig {
+    ReserveConfig {
+        loan_to_value_bps: 8_000,
+        liquidation_threshold_bps: 8_500,
+        liquidation_bonus_bps: 500,
+        close_factor_bps: 5_000,
+        optimal_utilization_bps: 8_000,
+        min_borrow_rate_bps: 0,
+        optimal_borrow_rate_bps: 1_000,
+        max_borrow_rate_bps: 10_000,
+        ..ReserveConfig::default()
+    }
+}
+
+/// SOL can be borrowed at up to 75% of its value
+pub fn sol_config() -> ReserveConfig {
+    ReserveConfig {
+        loan_to_value_bps: 7_500,
+        liquidation_threshold_bps: 8_000,
+        ..usdc_config()
+    }
+}
+
+/// Accounts of an initialized reserve
+pub struct ReserveFixture {
+    pub reserve: Pubkey,
+    pub liquidity_mint: Pubkey,
+    pub liquidity_vault: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub collateral_vault: Pubkey,
+}
+
+/// Accounts of a lending market with a USDC and a SOL reserve
+pub struct MarketFixture {
+    pub program_id: Pubkey,
+    pub lending_market: Pubkey,
+    pub market_authority: Pubkey,
+    pub usdc: ReserveFixture,
+    pub sol: ReserveFixture,
+}
+
+/// Token accounts of a user for one reserve
+pub struct Wallet {
+    pub liquidity: Pubkey,
+    pub collateral: Pubkey,
+}
+
+/// A user with an obligation and wallets for both reserves
+pub struct User {
+    pub keypair: Keypair,
+    pub obligation: Pubkey,
+    pub usdc: Wallet,
+    pub sol: Wallet,
+}
+
+impl MarketFixture {
+    pub fn deposit(
+        &self,
+        user: &User,
+        reserve: &ReserveFixture,
+        wallet: &Wallet,
+        amount: u64,
+    ) -> Instruction {
+        deposit_reserve_liquidity(
+            &self.program_id,
+            &user.keypair.pubkey(),
+            &reserve.reserve,
+            &self.lending_market,
+            &self.market_authority,
+            &reserve.liquidity_vault,
+            &reserve.collateral_mint,
+            &wallet.liquidity,
+            &wallet.collateral,
+            amount,
+        )
+    }
+
+    pub fn redeem(
+        &self,
+        user: &User,
+        reserve: &ReserveFixture,
+        wallet: &Wallet,
+        amount: u64,
+    ) -> Instruction {
+        redeem_reserve_collateral(
+            &self.program_id,
+            &user.keypair.pubkey(),
+            &reserve.reserve,
+            &self.lending_market,
+            &self.market_authority,
+            &reserve.liquidity_vault,
+            &reserve.collateral_mint,
+            &wallet.collateral,
+            &wallet.liquidity,
+            amount,
+        )
+    }
+
+    pub fn deposit_collateral(
+        &self,
+        user: &User,
+        reserve: &ReserveFixture,
+        wallet: &Wallet,
+        amount: u64,
+    ) -> Instruction {
+        deposit_obligation_collateral(
+            &self.program_id,
+            &user.keypair.pubkey(),
+            &user.obligation,
+            &reserve.reserve,
+            &reserve.collateral_vault,
+            &wallet.collateral,
+            amount,
+        )
+    }
+
+    pub fn borrow(
+        &self,
+        user: &User,
+        reserve: &ReserveFixture,
+        wallet: &Wallet,
+        amount: u64,
+    ) -> Instruction {
+        borrow_obligation_liquidity(
+            &self.program_id,
+            &user.keypair.pubkey(),
+            &user.obligation,
+            &reserve.reserve,
+            &self.lending_market,
+            &self.market_authority,
+            &reserve.liquidity_vault,
+            &wallet.liquidity,
+            None,
+            amount,
+        )
+    }
+
+    pub fn withdraw_collateral(
+        &self,
+        user: &User,
+        reserve: &ReserveFixture,
+        wallet: &Wallet,
+        amount: u64,
+    ) -> Instruction {
+        withdraw_obligation_collateral(
+            &self.program_id,
+            &user.keypair.pubkey(),
+            &user.obligation,
+            &reserve.reserve,
+            &self.lending_market,
+            &self.market_authority,
+            &reserve.collateral_vault,
+            &wallet.collateral,
+            amount,
+        )
+    }
+
+    pub fn refresh(&self, user: &User, obligation_reserves: &[Pubkey]) -> Instruction {
+        refresh_obligation(&self.program_id, &user.obligation, obligation_reserves)
+    }
+
+    pub fn repay(
+        &self,
+        user: &User,
+        reserve: &ReserveFixture,
+        wallet: &Wallet,
+        amount: u64,
+    ) -> Instruction {
+        repay_obligation_liquidity(
+            &self.program_id,
+            &user.keypair.pubkey(),
+            &user.obligation,
+            &reserve.reserve,
+            &reserve.liquidity_vault,
+            &wallet.liquidity,
+            None,
+            amount,
+        )
+    }
+
+    pub fn liquidate(
+        &self,
+        liquidator: &User,
+        borrower: &User,
+        repay: &ReserveFixture,
+        withdraw: &ReserveFixture,
+        amount: u64,
+    ) -> Instruction {
+        liquidate_obligation(
+            &self.program_id,
+            &liquidator.keypair.pubkey(),
+            &borrower.obligation,
+            &repay.reserve,
+            &repay.liquidity_vault,
+            &self.wallet(liquidator, repay).liquidity,
+            &withdraw.reserve,
+            &withdraw.collateral_vault,
+            &self.wallet(liquidator, withdraw).collateral,
+            &self.lending_market,
+            &self.market_authority,
+            amount,
+        )
+    }
+
+    pub fn set_price(&self, owner: &Keypair, reserve: &ReserveFixture, price: u128) -> Instruction {
+        set_reserve_price(
+            &self.program_id,
+            &owner.pubkey(),
+            &self.lending_market,
+            &reserve.reserve,
+            price,
+        )
+    }
+
+    /// The wallet of `user` for `reserve`
+    pub fn wallet<'a>(&self, user: &'a User, reserve: &ReserveFixture) -> &'a Wallet {
+        if reserve.reserve == self.usdc.reserve {
+            &user.usdc
+        } else {
+            &user.sol
+        }
+    }
+}
+
+pub async fn process(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &all_signers,
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+/// Waits for a blockhash newer than the latest one, so that a repeated
+/// transaction is not deduplicated
+pub async fn refresh_blockhash(context: &mut ProgramTestContext) {
+    let blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+    context
+        .banks_client
+        .get_new_latest_blockhash(&blockhash)
+        .await
+        .unwrap();
+}
+
+/// Creates an account of `size` bytes owned by `owner`
+pub async fn create_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    account: &Keypair,
+    size: usize,
+    owner: &Pubkey,
+) {
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[system_instruction::create_account(
+            &payer.pubkey(),
+            &account.pubkey(),
+            rent.minimum_balance(size),
+            size as u64,
+            owner,
+        )],
+        &[account],
+    )
+    .await
+    .unwrap();
+}
+
+pub async fn create_mint(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint_authority: &Pubkey,
+    decimals: u8,
+) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                &mint.pubkey(),
+                mint_authority,
+                None,
+                decimals,
+            )
+            .unwrap(),
+        ],
+        &[&mint],
+    )
+    .await
+    .unwrap();
+    mint.pubkey()
+}
+
+pub async fn create_token_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Pubkey {
+    let account = Keypair::new();
+    let rent = Rent::default();
+    process(
+        banks_client,
+        payer,
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &account.pubkey(),
+                mint,
+                owner,
+            )
+            .unwrap(),
+        ],
+        &[&account],
+    )
+    .await
+    .unwrap();
+    account.pubkey()
+}
+
+pub async fn mint_to(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    mint: &Pubkey,
+    account: &Pubkey,
+    amount: u64,
+) {
+    process(
+        banks_client,
+        payer,
+        &[spl_token::instruction::mint_to(
+            &spl_token::id(),
+            mint,
+            account,
+            &payer.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap()],
+        &[],
+    )
+    .await
+    .unwrap();
+}
+
+pub async fn token_balance(banks_client: &mut BanksClient, account: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*account).await.unwrap().unwrap();
+    spl_token::state::Account::unpack(&account.data)
+        .unwrap()
+        .amount
+}
+
+pub async fn get_lending_market(
+    banks_client: &mut BanksClient,
+    lending_market: &Pubkey,
+) -> LendingMarket {
+    let account = banks_client
+        .get_account(*lending_market)
+        .await
+        .unwrap()
+        .unwrap();
+    LendingMarket::try_from_slice(&account.data).unwrap()
+}
+
+pub async fn get_reserve(banks_client: &mut BanksClient, reserve: &Pubkey) -> Reserve {
+    let account = banks_client.get_account(*reserve).await.unwrap().unwrap();
+    Reserve::try_from_slice(&account.data).unwrap()
+}
+
+pub async fn get_obligation(banks_client: &mut BanksClient, obligation: &Pubkey) -> Obligation {
+    let account = banks_client
+        .get_account(*obligation)
+        .await
+        .unwrap()
+        .unwrap();
+    Obligation::try_from_slice(&account.data).unwrap()
+}
+
+/// Error of the instruction at `index` failing with `error`
+pub fn custom_error(index: u8, error: TemplateError) -> TransactionError {
+    TransactionError::InstructionError(index, InstructionError::Custom(error as u32))
+}
+
+/// Creates the vaults and collateral mint of a reserve for a new
+/// liquidity mint, and the reserve account, without initializing it
+pub async fn create_reserve_accounts(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    program_id: &Pubkey,
+    market_authority: &Pubkey,
+    decimals: u8,
+) -> ReserveFixture {
+    let reserve = Keypair::new();
+    create_account(
+        banks_client,
+        payer,
+        &reserve,
+        Reserve::get_size(),
+        program_id,
+    )
+    .await;
+    let liquidity_mint = create_mint(banks_client, payer, &payer.pubkey(), decimals).await;
+    let liquidity_vault =
+        create_token_account(banks_client, payer, &liquidity_mint, market_authority).await;
+    let collateral_mint = create_mint(banks_client, payer, market_authority, decimals).await;
+    let collateral_vault =
+        create_token_account(banks_client, payer, &collateral_mint, market_authority).await;
+    ReserveFixture {
+        reserve: reserve.pubkey(),
+        liquidity_mint,
+        liquidity_vault,
+        collateral_mint,
+        collateral_vault,
+    }
+}
+
+/// Adds `reserve` to the lending market at a fixed `market_price`
+pub fn init_reserve_instruction(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    lending_market: &Pubkey,
+    market_authority: &Pubkey,
+    reserve: &ReserveFixture,
+    config: ReserveConfig,
+    market_price: u128,
+) -> Instruction {
+    init_reserve(
+        program_id,
+        owner,
+        lending_market,
+        &reserve.reserve,
+        market_authority,
+        &reserve.liquidity_mint,
+        &reserve.liquidity_vault,
+        &reserve.collateral_mint,
+        &reserve.collateral_vault,
+        config,
+        OracleSource::Fixed,
+        None,
+        market_price,
+    )
+}
+
+/// Starts a test validator running the program
+pub async fn start_program() -> (ProgramTestContext, Keypair, Pubkey) {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        "overcollateralized_lending",
+        program_id,
+        processor!(process_instruction),
+    );
+    let context = program_test.start_with_context().await;
+    let payer = context.payer.insecure_clone();
+    (context, payer, program_id)
+}
+
+/// Creates a lending market owned by the payer, and returns it with its
+/// authority
+pub async fn create_lending_market(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    program_id: &Pubkey,
+) -> (Pubkey, Pubkey) {
+    let lending_market = Keypair::new();
+    let (market_authority, _) = find_market_authority(program_id, &lending_market.pubkey());
+    create_account(
+        banks_client,
+        payer,
+        &lending_market,
+        LendingMarket::get_size(),
+        program_id,
+    )
+    .await;
+    process(
+        banks_client,
+        payer,
+        &[init_lending_market(
+            program_id,
+            &payer.pubkey(),
+            &lending_market.pubkey(),
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+    (lending_market.pubkey(), market_authority)
+}
+
+/// Starts a test validator with a lending market owned by the payer,
+/// holding a USDC and a SOL reserve. The payer mints both liquidity tokens.
+pub async fn start_with_market() -> (ProgramTestContext, Keypair, MarketFixture) {
+    let (mut context, payer, program_id) = start_program().await;
+    let banks_client = &mut context.banks_client;
+    let (lending_market, market_authority) =
+        create_lending_market(banks_client, &payer, &program_id).await;
+
+    let usdc =
+        create_reserve_accounts(banks_client, &payer, &program_id, &market_authority, 6).await;
+    let sol =
+        create_reserve_accounts(banks_client, &payer, &program_id, &market_authority, 9).await;
+    process(
+        banks_client,
+        &payer,
+        &[
+            init_reserve_instruction(
+                &program_id,
+                &payer.pubkey(),
+                &lending_market,
+                &market_authority,
+                &usdc,
+                usdc_config(),
+                WAD,
+            ),
+            init_reserve_instruction(
+                &program_id,
+                &payer.pubkey(),
+                &lending_market,
+                &market_authority,
+                &sol,
+                sol_config(),
+                SOL_PRICE,
+            ),
+        ],
+        &[],
+    )
+    .await
+    .unwrap();
+
+    let fixture = MarketFixture {
+        program_id,
+        lending_market,
+        market_authority,
+        usdc,
+        sol,
+    };
+    (context, payer, fixture)
+}
+
+/// Creates an obligation of `owner` in `lending_market`
+pub async fn create_obligation(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    program_id: &Pubkey,
+    lending_market: &Pubkey,
+    owner: &Keypair,
+) -> Pubkey {
+    let obligation = Keypair::new();
+    create_account(
+        banks_client,
+        payer,
+        &obligation,
+        Obligation::get_size(),
+        program_id,
+    )
+    .await;
+    process(
+        banks_client,
+        payer,
+        &[init_obligation(
+            program_id,
+            &owner.pubkey(),
+            lending_market,
+            &obligation.pubkey(),
+        )],
+        &[owner],
+    )
+    .await
+    .unwrap();
+    obligation.pubkey()
+}
+
+/// Creates the token accounts of `owner` for `reserve`, holding `amount` of
+/// its liquidity token
+pub async fn create_wallet(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    reserve: &ReserveFixture,
+    owner: &Pubkey,
+    amount: u64,
+) -> Wallet {
+    let liquidity = create_token_account(banks_client, payer, &reserve.liquidity_mint, owner).await;
+    let collateral =
+        create_token_account(banks_client, payer, &reserve.collateral_mint, owner).await;
+    mint_to(
+        banks_client,
+        payer,
+        &reserve.liquidity_mint,
+        &liquidity,
+        amount,
+    )
+    .await;
+    Wallet {
+        liquidity,
+        collateral,
+    }
+}
+
+/// Creates a user with an obligation, holding `usdc` and `sol` of the
+/// liquidity tokens
+pub async fn create_user(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    fixture: &MarketFixture,
+    usdc: u64,
+    sol: u64,
+) -> User {
+    let keypair = Keypair::new();
+    let owner = keypair.pubkey();
+    let obligation = create_obligation(
+        banks_client,
+        payer,
+        &fixture.program_id,
+        &fixture.lending_market,
+        &keypair,
+    )
+    .await;
+    let usdc = create_wallet(banks_client, payer, &fixture.usdc, &owner, usdc).await;
+    let sol = create_wallet(banks_client, payer, &fixture.sol, &owner, sol).await;
+    User {
+        keypair,
+        obligation,
+        usdc,
+        sol,
+    }
+}
+
+/// Creates a lender who has deposited `LENDER_USDC` into the USDC
+/// reserve, and a borrower with `collateral` SOL deposited into their
+/// obligation
+pub async fn create_lender_and_borrower(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    fixture: &MarketFixture,
+    collateral: u64,
+) -> (User, User) {
+    let lender = create_user(banks_client, payer, fixture, LENDER_USDC, 0).await;
+    process(
+        banks_client,
+        payer,
+        &[fixture.deposit(&lender, &fixture.usdc, &lender.usdc, LENDER_USDC)],
+        &[&lender.keypair],
+    )
+    .await
+    .unwrap();
+
+    let borrower = create_user(banks_client, payer, fixture, BORROWER_USDC, BORROWER_SOL).await;
+    process(
+        banks_client,
+        payer,
+        &[
+            fixture.deposit(&borrower, &fixture.sol, &borrower.sol, collateral),
+            fixture.deposit_collateral(&borrower, &fixture.sol, &borrower.sol, collateral),
+        ],
+        &[&borrower.keypair],
+    )
+    .await
+    .unwrap();
+    (lender, borrower)
+}
@@ -1,41 +1,725 @@
 //! Integration tests for overcollateralized-lending
 
+mod common;
+
 #[cfg(test)]
 mod tests {
     use {
-        borsh::BorshSerialize,
-        solana_program::{
-            instruction::{AccountMeta, Instruction},
-            pubkey::Pubkey,
-            rent::Rent,
-            system_instruction,
-        },
-        solana_program_test::{processor, ProgramTest},
-        solana_sdk::{
-            account::Account,
-            signature::{Keypair, Signer},
-            transaction::Transaction,
+        super::common::{
+            create_lender_and_borrower, create_mint, create_reserve_accounts, create_token_account,
+            create_user, custom_error, get_lending_market, get_obligation, get_reserve,
+            init_reserve_instruction, process, sol_config, start_with_market, token_balance,
+            usdc_config, ReserveFixture, BORROWER_SOL, BORROWER_USDC, LENDER_USDC, SOL, SOL_PRICE,
+            USDC,
         },
-        overcollateralized-lending::{
-            instructions::TemplateInstruction,
-            process_instruction,
-            state::TemplateAccount,
+        overcollateralized_lending::{
+            errors::TemplateError,
+            instructions::{
+                init_lending_market, redeem_reserve_collateral, repay_obligation_liquidity,
+                set_reserve_price, withdraw_obligation_collateral,
+            },
+            state::{ReserveConfig, SLOTS_PER_YEAR, WAD},
+            utils::find_market_authority,
         },
-        std::str::FromStr,
+        solana_program::pubkey::Pubkey,
+        solana_sdk::signature::{Keypair, Signer},
     };
 
     #[tokio::test]
-    async fn test_initialize() {
-        // TODO: Implement test logic for initialization
+    async fn test_init_lending_market_and_reserves() {
+        let (mut context, payer, fixture) = start_with_market().await;
+        let banks_client = &mut context.banks_client;
+
+        let lending_market = get_lending_market(banks_client, &fixture.lending_market).await;
+        assert!(lending_market.is_initialized);
+        assert_eq!(lending_market.owner, payer.pubkey());
+        assert_eq!(
+            find_market_authority(&fixture.program_id, &fixture.lending_market).1,
+            lending_market.bump_seed
+        );
+
+        let reserve = get_reserve(banks_client, &fixture.sol.reserve).await;
+        assert!(reserve.is_initialized);
+        assert_eq!(reserve.lending_market, fixture.lending_market);
+        assert_eq!(reserve.liquidity_decimals, 9);
+        assert_eq!(reserve.collateral_mint, fixture.sol.collateral_mint);
+        assert_eq!(reserve.config, sol_config());
+        assert_eq!(reserve.cumulative_borrow_rate_wads, WAD);
+        assert_eq!(reserve.market_price, SOL_PRICE);
+
+        // Neither account can be initialized twice
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[init_lending_market(
+                    &fixture.program_id,
+                    &payer.pubkey(),
+                    &fixture.lending_market,
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::AlreadyInitialized)
+        );
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[init_reserve_instruction(
+                    &fixture.program_id,
+                    &payer.pubkey(),
+                    &fixture.lending_market,
+                    &fixture.market_authority,
+                    &fixture.usdc,
+                    usdc_config(),
+                    WAD,
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::AlreadyInitialized)
+        );
+
+        let reserve = create_reserve_accounts(
+            banks_client,
+            &payer,
+            &fixture.program_id,
+            &fixture.market_authority,
+            6,
+        )
+        .await;
+        let init = |owner: &Pubkey, config: ReserveConfig, market_price: u128| {
+            init_reserve_instruction(
+                &fixture.program_id,
+                owner,
+                &fixture.lending_market,
+                &fixture.market_authority,
+                &reserve,
+                config,
+                market_price,
+            )
+        };
+
+        // Only the lending market owner adds reserves
+        let stranger = Keypair::new();
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[init(&stranger.pubkey(), usdc_config(), WAD)],
+                &[&stranger],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidAuthority)
+        );
+
+        // The loan-to-value ratio stays within the liquidation threshold,
+        // which stays below 100%, and the rates increase
+        for config in [
+            ReserveConfig {
+                loan_to_value_bps: 9_000,
+                ..usdc_config()
+            },
+            ReserveConfig {
+                liquidation_threshold_bps: 10_000,
+                ..usdc_config()
+            },
+            ReserveConfig {
+                close_factor_bps: 0,
+                ..usdc_config()
+            },
+            ReserveConfig {
+                optimal_utilization_bps: 0,
+                ..usdc_config()
+            },
+            ReserveConfig {
+                max_borrow_rate_bps: 500,
+                ..usdc_config()
+            },
+        ] {
+            assert_eq!(
+                process(
+                    banks_client,
+                    &payer,
+                    &[init(&payer.pubkey(), config, WAD)],
+                    &[]
+                )
+                .await
+                .unwrap_err(),
+                custom_error(0, TemplateError::InvalidReserveConfig)
+            );
+        }
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[init(&payer.pubkey(), usdc_config(), 0)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidReserveConfig)
+        );
+
+        // The lending market authority must mint the cTokens
+        let collateral_mint = create_mint(banks_client, &payer, &payer.pubkey(), 6).await;
+        let collateral_vault = create_token_account(
+            banks_client,
+            &payer,
+            &collateral_mint,
+            &fixture.market_authority,
+        )
+        .await;
+        let reserve = ReserveFixture {
+            collateral_mint,
+            collateral_vault,
+            ..reserve
+        };
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[init_reserve_instruction(
+                    &fixture.program_id,
+                    &payer.pubkey(),
+                    &fixture.lending_market,
+                    &fixture.market_authority,
+                    &reserve,
+                    usdc_config(),
+                    WAD,
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidCollateralMint)
+        );
     }
 
     #[tokio::test]
-    async fn test_operation1() {
-        // TODO: Implement test logic for operation1
+    async fn test_deposit_and_redeem() {
+        let (mut context, payer, fixture) = start_with_market().await;
+        let banks_client = &mut context.banks_client;
+        let lender = create_user(banks_client, &payer, &fixture, LENDER_USDC, 0).await;
+
+        // Without borrows, a cToken is worth one base unit of liquidity
+        process(
+            banks_client,
+            &payer,
+            &[fixture.deposit(&lender, &fixture.usdc, &lender.usdc, LENDER_USDC)],
+            &[&lender.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &lender.usdc.collateral).await,
+            LENDER_USDC
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.usdc.liquidity_vault).await,
+            LENDER_USDC
+        );
+        let reserve = get_reserve(banks_client, &fixture.usdc.reserve).await;
+        assert_eq!(reserve.available_liquidity, LENDER_USDC);
+        assert_eq!(reserve.collateral_supply, LENDER_USDC);
+
+        process(
+            banks_client,
+            &payer,
+            &[fixture.redeem(&lender, &fixture.usdc, &lender.usdc, 400 * USDC)],
+            &[&lender.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &lender.usdc.liquidity).await,
+            400 * USDC
+        );
+        assert_eq!(
+            token_balance(banks_client, &lender.usdc.collateral).await,
+            600 * USDC
+        );
+        let reserve = get_reserve(banks_client, &fixture.usdc.reserve).await;
+        assert_eq!(reserve.available_liquidity, 600 * USDC);
+        assert_eq!(reserve.collateral_supply, 600 * USDC);
+
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.deposit(&lender, &fixture.usdc, &lender.usdc, 0)],
+                &[&lender.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::ZeroAmount)
+        );
+
+        // The cTokens of one reserve cannot be redeemed from another
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[redeem_reserve_collateral(
+                    &fixture.program_id,
+                    &lender.keypair.pubkey(),
+                    &fixture.sol.reserve,
+                    &fixture.lending_market,
+                    &fixture.market_authority,
+                    &fixture.sol.liquidity_vault,
+                    &fixture.usdc.collateral_mint,
+                    &lender.usdc.collateral,
+                    &lender.sol.liquidity,
+                    USDC,
+                )],
+                &[&lender.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidCollateralMint)
+        );
     }
 
     #[tokio::test]
-    async fn test_operation2() {
-        // TODO: Implement test logic for operation2
+    async fn test_borrow_within_loan_to_value() {
+        let (mut context, payer, fixture) = start_with_market().await;
+        let banks_client = &mut context.banks_client;
+
+        // 10 SOL at 20 is worth 200, which lends 150 at a 75% loan-to-value
+        let (_, borrower) =
+            create_lender_and_borrower(banks_client, &payer, &fixture, 10 * SOL).await;
+        let obligation = get_obligation(banks_client, &borrower.obligation).await;
+        assert_eq!(obligation.deposits[0].deposit_reserve, fixture.sol.reserve);
+        assert_eq!(obligation.deposits[0].deposited_amount, 10 * SOL);
+        assert_eq!(
+            token_balance(banks_client, &fixture.sol.collateral_vault).await,
+            10 * SOL
+        );
+
+        let sol_reserve = [fixture.sol.reserve];
+        let borrow = |amount| fixture.borrow(&borrower, &fixture.usdc, &borrower.usdc, amount);
+
+        // The obligation must be refreshed first
+        assert_eq!(
+            process(banks_client, &payer, &[borrow(USDC)], &[&borrower.keypair])
+                .await
+                .unwrap_err(),
+            custom_error(0, TemplateError::ObligationStale)
+        );
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.refresh(&borrower, &sol_reserve),
+                    borrow(150 * USDC + 1)
+                ],
+                &[&borrower.keypair]
+            )
+            .await
+            .unwrap_err(),
+            custom_error(1, TemplateError::BorrowTooLarge)
+        );
+
+        // The refresh must list the reserves of the obligation
+        for obligation_reserves in [
+            vec![],
+            vec![fixture.usdc.reserve],
+            vec![fixture.sol.reserve, fixture.sol.reserve],
+        ] {
+            assert_eq!(
+                process(
+                    banks_client,
+                    &payer,
+                    &[fixture.refresh(&borrower, &obligation_reserves)],
+                    &[],
+                )
+                .await
+                .unwrap_err(),
+                custom_error(0, TemplateError::InvalidObligationReserves)
+            );
+        }
+
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &sol_reserve),
+                borrow(100 * USDC),
+                borrow(50 * USDC),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &borrower.usdc.liquidity).await,
+            BORROWER_USDC + 150 * USDC
+        );
+        let reserve = get_reserve(banks_client, &fixture.usdc.reserve).await;
+        assert_eq!(reserve.available_liquidity, LENDER_USDC - 150 * USDC);
+        assert_eq!(reserve.borrowed_amount_wads, 150 * USDC as u128 * WAD);
+        let obligation = get_obligation(banks_client, &borrower.obligation).await;
+        assert_eq!(obligation.borrows[0].borrow_reserve, fixture.usdc.reserve);
+        assert_eq!(
+            obligation.borrows[0].borrowed_amount_wads,
+            150 * USDC as u128 * WAD
+        );
+        assert_eq!(obligation.deposited_value_wads, 200 * WAD);
+        assert_eq!(obligation.allowed_borrow_value_wads, 150 * WAD);
+        assert_eq!(obligation.borrowed_value_wads, 150 * WAD);
+
+        // The obligation has no borrowing power left
+        let both_reserves = [fixture.sol.reserve, fixture.usdc.reserve];
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.refresh(&borrower, &both_reserves), borrow(1)],
+                &[&borrower.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(1, TemplateError::BorrowTooLarge)
+        );
+
+        // Only the lending market owner sets prices, and a higher SOL price
+        // lends more
+        let stranger = Keypair::new();
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[set_reserve_price(
+                    &fixture.program_id,
+                    &stranger.pubkey(),
+                    &fixture.lending_market,
+                    &fixture.sol.reserve,
+                    2 * SOL_PRICE,
+                )],
+                &[&stranger],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidAuthority)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[
+                set_reserve_price(
+                    &fixture.program_id,
+                    &payer.pubkey(),
+                    &fixture.lending_market,
+                    &fixture.sol.reserve,
+                    2 * SOL_PRICE,
+                ),
+                fixture.refresh(&borrower, &both_reserves),
+                borrow(100 * USDC),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &borrower.usdc.liquidity).await,
+            BORROWER_USDC + 250 * USDC
+        );
+
+        // Nobody borrows more than the reserve holds
+        let (_, whale) =
+            create_lender_and_borrower(banks_client, &payer, &fixture, BORROWER_SOL).await;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.refresh(&whale, &sol_reserve),
+                    fixture.borrow(&whale, &fixture.usdc, &whale.usdc, 2 * LENDER_USDC),
+                ],
+                &[&whale.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(1, TemplateError::InsufficientLiquidity)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repay() {
+        let (mut context, payer, fixture) = start_with_market().await;
+        let banks_client = &mut context.banks_client;
+        let (_, borrower) =
+            create_lender_and_borrower(banks_client, &payer, &fixture, 10 * SOL).await;
+
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &[fixture.sol.reserve]),
+                fixture.borrow(&borrower, &fixture.usdc, &borrower.usdc, 100 * USDC),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+
+        // Anyone can repay part of the debt
+        let helper = create_user(banks_client, &payer, &fixture, 40 * USDC, 0).await;
+        process(
+            banks_client,
+            &payer,
+            &[repay_obligation_liquidity(
+                &fixture.program_id,
+                &helper.keypair.pubkey(),
+                &borrower.obligation,
+                &fixture.usdc.reserve,
+                &fixture.usdc.liquidity_vault,
+                &helper.usdc.liquidity,
+                None,
+                40 * USDC,
+            )],
+            &[&helper.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(token_balance(banks_client, &helper.usdc.liquidity).await, 0);
+        let obligation = get_obligation(banks_client, &borrower.obligation).await;
+        let debt = obligation.borrows[0].borrowed_amount().unwrap();
+        assert!((60 * USDC..60 * USDC + 10).contains(&debt));
+
+        // Repaying the maximum clears the debt, rounded up, and frees its slot
+        process(
+            banks_client,
+            &payer,
+            &[fixture.repay(&borrower, &fixture.usdc, &borrower.usdc, u64::MAX)],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        let repaid = BORROWER_USDC + 100 * USDC
+            - token_balance(banks_client, &borrower.usdc.liquidity).await;
+        assert!((60 * USDC..60 * USDC + 10).contains(&repaid));
+        let obligation = get_obligation(banks_client, &borrower.obligation).await;
+        assert_eq!(obligation.active_borrows().count(), 0);
+        let reserve = get_reserve(banks_client, &fixture.usdc.reserve).await;
+        assert_eq!(
+            reserve.available_liquidity,
+            LENDER_USDC + repaid - 60 * USDC
+        );
+        assert!(reserve.borrowed_amount_wads < WAD);
+
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.repay(&borrower, &fixture.usdc, &borrower.usdc, USDC)],
+                &[&borrower.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::ObligationLiquidityEmpty)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_withdraw_collateral() {
+        let (mut context, payer, fixture) = start_with_market().await;
+        let banks_client = &mut context.banks_client;
+
+        // 10 SOL at 20 back up to 150 at a 75% loan-to-value
+        let (_, borrower) =
+            create_lender_and_borrower(banks_client, &payer, &fixture, 10 * SOL).await;
+        let withdraw =
+            |amount| fixture.withdraw_collateral(&borrower, &fixture.sol, &borrower.sol, amount);
+
+        // The obligation must be refreshed first
+        assert_eq!(
+            process(banks_client, &payer, &[withdraw(SOL)], &[&borrower.keypair])
+                .await
+                .unwrap_err(),
+            custom_error(0, TemplateError::ObligationStale)
+        );
+        let both_reserves = [fixture.sol.reserve, fixture.usdc.reserve];
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &[fixture.sol.reserve]),
+                fixture.borrow(&borrower, &fixture.usdc, &borrower.usdc, 90 * USDC),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+
+        // Only the obligation owner withdraws
+        let stranger = create_user(banks_client, &payer, &fixture, 0, 0).await;
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[withdraw_obligation_collateral(
+                    &fixture.program_id,
+                    &stranger.keypair.pubkey(),
+                    &borrower.obligation,
+                    &fixture.sol.reserve,
+                    &fixture.lending_market,
+                    &fixture.market_authority,
+                    &fixture.sol.collateral_vault,
+                    &stranger.sol.collateral,
+                    SOL,
+                )],
+                &[&stranger.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidAuthority)
+        );
+
+        // 6 SOL back exactly the 90 borrowed, so the collateral cannot drop
+        // below them
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.refresh(&borrower, &both_reserves),
+                    withdraw(4 * SOL + 1)
+                ],
+                &[&borrower.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(1, TemplateError::WithdrawTooLarge)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &both_reserves),
+                withdraw(3 * SOL),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &borrower.sol.collateral).await,
+            3 * SOL
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.sol.collateral_vault).await,
+            7 * SOL
+        );
+        let obligation = get_obligation(banks_client, &borrower.obligation).await;
+        assert_eq!(obligation.deposits[0].deposited_amount, 7 * SOL);
+        assert!(obligation.stale);
+
+        // Once the debt is repaid, withdrawing the maximum takes the whole
+        // deposit and frees its slot
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.repay(&borrower, &fixture.usdc, &borrower.usdc, u64::MAX),
+                fixture.refresh(&borrower, &[fixture.sol.reserve]),
+                withdraw(u64::MAX),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &borrower.sol.collateral).await,
+            10 * SOL
+        );
+        assert_eq!(
+            token_balance(banks_client, &fixture.sol.collateral_vault).await,
+            0
+        );
+        let obligation = get_obligation(banks_client, &borrower.obligation).await;
+        assert_eq!(obligation.active_deposits().count(), 0);
+
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[fixture.refresh(&borrower, &[]), withdraw(SOL)],
+                &[&borrower.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(1, TemplateError::ObligationCollateralEmpty)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_interest_accrual() {
+        let (mut context, payer, fixture) = start_with_market().await;
+        let banks_client = &mut context.banks_client;
+        let (lender, borrower) =
+            create_lender_and_borrower(banks_client, &payer, &fixture, BORROWER_SOL).await;
+
+        // At 50% utilization, USDC borrows pay 6.25% a year
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &[fixture.sol.reserve]),
+                fixture.borrow(&borrower, &fixture.usdc, &borrower.usdc, LENDER_USDC / 2),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        let reserve = get_reserve(banks_client, &fixture.usdc.reserve).await;
+        assert_eq!(reserve.borrow_rate_wads().unwrap(), WAD / 16);
+
+        // Over a tenth of a year, the debt compounds to 500 * e^0.00625
+        context
+            .warp_to_slot(reserve.last_update_slot + SLOTS_PER_YEAR / 10)
+            .unwrap();
+        let banks_client = &mut context.banks_client;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.repay(&borrower, &fixture.usdc, &borrower.usdc, u64::MAX)],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        let repaid = BORROWER_USDC + LENDER_USDC / 2
+            - token_balance(banks_client, &borrower.usdc.liquidity).await;
+        assert!(
+            (503_134_000..503_136_000).contains(&repaid),
+            "repaid {}",
+            repaid
+        );
+
+        // The interest goes to the cToken holders
+        process(
+            banks_client,
+            &payer,
+            &[fixture.redeem(&lender, &fixture.usdc, &lender.usdc, LENDER_USDC)],
+            &[&lender.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &lender.usdc.liquidity).await,
+            LENDER_USDC / 2 + repaid
+        );
+        let reserve = get_reserve(banks_client, &fixture.usdc.reserve).await;
+        assert_eq!(reserve.collateral_supply, 0);
+        assert_eq!(reserve.available_liquidity, 0);
+        assert_eq!(
+            token_balance(banks_client, &fixture.usdc.liquidity_vault).await,
+            0
+        );
     }
 }
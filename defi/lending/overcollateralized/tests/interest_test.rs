@@ -0,0 +1,117 @@
+//! Tests for the interest rate model of overcollateralized-lending
+
+#[cfg(test)]
+mod tests {
+    use overcollateralized_lending::state::{
+        ObligationLiquidity, Reserve, ReserveConfig, SLOTS_PER_YEAR, WAD,
+    };
+
+    /// Reserve with `available` liquidity in the vault and `borrowed` lent out,
+    /// whose rate rises from 1% to 10% at 80% utilization and 100% when full
+    fn reserve(available: u64, borrowed: u64) -> Reserve {
+        Reserve {
+            is_initialized: true,
+            liquidity_decimals: 6,
+            config: ReserveConfig {
+                loan_to_value_bps: 8_000,
+                liquidation_threshold_bps: 8_500,
+                liquidation_bonus_bps: 500,
+                close_factor_bps: 5_000,
+                optimal_utilization_bps: 8_000,
+                min_borrow_rate_bps: 100,
+                optimal_borrow_rate_bps: 1_000,
+                max_borrow_rate_bps: 10_000,
+                ..ReserveConfig::default()
+            },
+            available_liquidity: available,
+            borrowed_amount_wads: borrowed as u128 * WAD,
+            cumulative_borrow_rate_wads: WAD,
+            market_price: WAD,
+            ..Reserve::default()
+        }
+    }
+
+    #[test]
+    fn test_borrow_rate_kinks_at_optimal_utilization() {
+        let rate = |available, borrowed| reserve(available, borrowed).borrow_rate_wads().unwrap();
+
+        assert_eq!(rate(0, 0), WAD / 100);
+        assert_eq!(rate(100, 0), WAD / 100);
+        // Halfway to the kink, halfway between 1% and 10%
+        assert_eq!(rate(60, 40), WAD / 100 + WAD * 9 / 200);
+        assert_eq!(rate(20, 80), WAD / 10);
+        // Halfway past the kink, halfway between 10% and 100%
+        assert_eq!(rate(10, 90), WAD / 10 + WAD * 9 / 20);
+        assert_eq!(rate(0, 100), WAD);
+
+        // The rate never falls as utilization rises
+        let mut previous = 0;
+        for borrowed in (0..=1_000_000_000_000).step_by(7_777_777_777) {
+            let rate = rate(1_000_000_000_000 - borrowed, borrowed);
+            assert!(rate >= previous);
+            previous = rate;
+        }
+    }
+
+    #[test]
+    fn test_accrue_interest_compounds_per_slot() {
+        // 10% a year at the kink
+        let mut reserve = reserve(200_000_000_000, 800_000_000_000);
+        reserve.accrue_interest(SLOTS_PER_YEAR).unwrap();
+
+        // A year compounded every slot grows by e^0.1
+        let growth = reserve.cumulative_borrow_rate_wads;
+        assert!(
+            (1_105_170_000_000_000_000..1_105_171_000_000_000_000).contains(&growth),
+            "growth {}",
+            growth
+        );
+        assert_eq!(reserve.borrowed_amount_wads, 800_000_000_000 * growth);
+        assert_eq!(reserve.available_liquidity, 200_000_000_000);
+        assert_eq!(reserve.last_update_slot, SLOTS_PER_YEAR);
+
+        // Accruing at the same slot changes nothing, and time cannot go back
+        let accrued = reserve.clone();
+        reserve.accrue_interest(SLOTS_PER_YEAR).unwrap();
+        assert_eq!(reserve, accrued);
+        assert!(reserve.accrue_interest(SLOTS_PER_YEAR - 1).is_err());
+
+        // An obligation that borrowed at the start owes the same growth,
+        // rounded up
+        let mut borrow = ObligationLiquidity {
+            cumulative_borrow_rate_wads: WAD,
+            borrowed_amount_wads: 1_000_000 * WAD,
+            ..ObligationLiquidity::default()
+        };
+        borrow
+            .accrue_interest(reserve.cumulative_borrow_rate_wads)
+            .unwrap();
+        assert_eq!(
+            borrow.borrowed_amount(),
+            Ok((1_000_000 * growth).div_ceil(WAD) as u64)
+        );
+    }
+
+    #[test]
+    fn test_collateral_exchange_rate_rounds_down() {
+        // 1,000 deposited for 1,000 cTokens, then interest grows the
+        // liquidity to 1,100
+        let mut reserve = reserve(500, 600);
+        reserve.collateral_supply = 1_000;
+
+        assert_eq!(reserve.collateral_for_liquidity(11), Ok(10));
+        assert_eq!(reserve.collateral_for_liquidity(10), Ok(9));
+        assert_eq!(reserve.collateral_for_liquidity(1), Ok(0));
+        assert_eq!(reserve.liquidity_for_collateral(10), Ok(11));
+        assert_eq!(reserve.liquidity_for_collateral(9), Ok(9));
+        assert_eq!(reserve.liquidity_for_collateral(1_000), Ok(1_100));
+        assert!(reserve.liquidity_for_collateral(1_001).is_err());
+
+        // Borrowing power is the loan-to-value share of the collateral's
+        // value: 110 base units at 6 decimals and a price of 1
+        assert_eq!(
+            reserve.borrowing_power(100, 0),
+            Ok(110 * WAD / 1_000_000 * 8 / 10)
+        );
+    }
+}
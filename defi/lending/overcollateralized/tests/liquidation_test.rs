@@ -0,0 +1,291 @@
+//! Liquidation tests for overcollateralized-lending
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::common::{
+            create_lender_and_borrower, create_user, custom_error, get_obligation, get_reserve,
+            process, start_with_market, token_balance, MarketFixture, User, LENDER_USDC, SOL, USDC,
+        },
+        overcollateralized_lending::{errors::TemplateError, state::WAD},
+        solana_program_test::ProgramTestContext,
+        solana_sdk::signature::Keypair,
+    };
+
+    /// SOL collateral the borrower deposits, worth 200 USDC at `SOL_PRICE`
+    const COLLATERAL: u64 = 10 * SOL;
+    /// USDC the borrower borrows, below the 150 USDC loan-to-value limit of
+    /// their collateral and the 160 USDC liquidation threshold
+    const DEBT: u64 = 140 * USDC;
+
+    /// Starts a market with a borrower who owes `DEBT` against `COLLATERAL`,
+    /// and a liquidator holding `LENDER_USDC`
+    async fn start_with_borrower() -> (ProgramTestContext, Keypair, MarketFixture, User, User) {
+        let (mut context, payer, fixture) = start_with_market().await;
+        let banks_client = &mut context.banks_client;
+
+        let (_, borrower) =
+            create_lender_and_borrower(banks_client, &payer, &fixture, COLLATERAL).await;
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &[fixture.sol.reserve]),
+                fixture.borrow(&borrower, &fixture.usdc, &borrower.usdc, DEBT),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+
+        let liquidator = create_user(banks_client, &payer, &fixture, LENDER_USDC, 0).await;
+        (context, payer, fixture, borrower, liquidator)
+    }
+
+    #[tokio::test]
+    async fn test_liquidate_after_price_drop() {
+        let (mut context, payer, fixture, borrower, liquidator) = start_with_borrower().await;
+        let banks_client = &mut context.banks_client;
+        let reserves = [fixture.sol.reserve, fixture.usdc.reserve];
+
+        // A healthy obligation cannot be liquidated
+        let result = process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &reserves),
+                fixture.liquidate(
+                    &liquidator,
+                    &borrower,
+                    &fixture.usdc,
+                    &fixture.sol,
+                    u64::MAX,
+                ),
+            ],
+            &[&liquidator.keypair],
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err(),
+            custom_error(1, TemplateError::ObligationHealthy)
+        );
+
+        // At 17 the collateral is worth 170 USDC, and the 136 USDC
+        // liquidation threshold is below the debt
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.set_price(&payer, &fixture.sol, 17 * WAD),
+                fixture.refresh(&borrower, &reserves),
+            ],
+            &[],
+        )
+        .await
+        .unwrap();
+        let obligation = get_obligation(banks_client, &borrower.obligation).await;
+        assert_eq!(obligation.deposited_value_wads, 170 * WAD);
+        assert_eq!(obligation.unhealthy_borrow_value_wads, 136 * WAD);
+        assert!(!obligation.is_healthy());
+
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &reserves),
+                fixture.liquidate(
+                    &liquidator,
+                    &borrower,
+                    &fixture.usdc,
+                    &fixture.sol,
+                    u64::MAX,
+                ),
+            ],
+            &[&liquidator.keypair],
+        )
+        .await
+        .unwrap();
+
+        // At most the close factor of the debt is repaid, for collateral
+        // worth 5% more
+        let repaid = LENDER_USDC - token_balance(banks_client, &liquidator.usdc.liquidity).await;
+        let seized = token_balance(banks_client, &liquidator.sol.collateral).await;
+        assert!(
+            (DEBT / 2..=DEBT / 2 + 10).contains(&repaid),
+            "repaid {}",
+            repaid
+        );
+        let seized_value = seized as u128 * 17 * USDC as u128;
+        let repaid_value = repaid as u128 * SOL as u128 * 105 / 100;
+        assert!(seized_value <= repaid_value);
+        assert!(repaid_value - seized_value < 17 * USDC as u128);
+
+        let obligation = get_obligation(banks_client, &borrower.obligation).await;
+        assert_eq!(obligation.deposits[0].deposited_amount, COLLATERAL - seized);
+        assert!(obligation.borrows[0].borrowed_amount().unwrap() <= DEBT - repaid + 10);
+        assert_eq!(
+            token_balance(banks_client, &fixture.sol.collateral_vault).await,
+            COLLATERAL - seized
+        );
+        let reserve = get_reserve(banks_client, &fixture.usdc.reserve).await;
+        assert_eq!(reserve.available_liquidity, LENDER_USDC - DEBT + repaid);
+
+        // The liquidation leaves the obligation stale, and healthy again once
+        // refreshed
+        let result = process(
+            banks_client,
+            &payer,
+            &[fixture.liquidate(
+                &liquidator,
+                &borrower,
+                &fixture.usdc,
+                &fixture.sol,
+                u64::MAX,
+            )],
+            &[&liquidator.keypair],
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err(),
+            custom_error(0, TemplateError::ObligationStale)
+        );
+        let result = process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &reserves),
+                fixture.liquidate(
+                    &liquidator,
+                    &borrower,
+                    &fixture.usdc,
+                    &fixture.sol,
+                    u64::MAX,
+                ),
+            ],
+            &[&liquidator.keypair],
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err(),
+            custom_error(1, TemplateError::ObligationHealthy)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_liquidate_underwater_obligation() {
+        let (mut context, payer, fixture, borrower, liquidator) = start_with_borrower().await;
+        let banks_client = &mut context.banks_client;
+        let reserves = [fixture.sol.reserve, fixture.usdc.reserve];
+
+        // At 7 the collateral is worth 70 USDC, less than half the debt plus
+        // the bonus, so all of it is seized for what it is worth
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.set_price(&payer, &fixture.sol, 7 * WAD),
+                fixture.refresh(&borrower, &reserves),
+                fixture.liquidate(
+                    &liquidator,
+                    &borrower,
+                    &fixture.usdc,
+                    &fixture.sol,
+                    u64::MAX,
+                ),
+            ],
+            &[&payer, &liquidator.keypair],
+        )
+        .await
+        .unwrap();
+
+        let repaid = LENDER_USDC - token_balance(banks_client, &liquidator.usdc.liquidity).await;
+        assert_eq!(
+            token_balance(banks_client, &liquidator.sol.collateral).await,
+            COLLATERAL
+        );
+        assert_eq!(repaid, 66_666_667);
+
+        // The deposit slot is freed and the rest of the debt remains
+        let obligation = get_obligation(banks_client, &borrower.obligation).await;
+        assert_eq!(obligation.active_deposits().count(), 0);
+        assert!(obligation.borrows[0].borrowed_amount().unwrap() >= DEBT - repaid);
+        assert_eq!(
+            token_balance(banks_client, &fixture.sol.collateral_vault).await,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_liquidation_checks() {
+        let (mut context, payer, fixture, borrower, liquidator) = start_with_borrower().await;
+        let banks_client = &mut context.banks_client;
+        let reserves = [fixture.sol.reserve, fixture.usdc.reserve];
+        process(
+            banks_client,
+            &payer,
+            &[fixture.set_price(&payer, &fixture.sol, 17 * WAD)],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let mut wrong_vault =
+            fixture.liquidate(&liquidator, &borrower, &fixture.usdc, &fixture.sol, USDC);
+        wrong_vault.accounts[3].pubkey = fixture.sol.liquidity_vault;
+        let cases = [
+            (
+                fixture.liquidate(&liquidator, &borrower, &fixture.usdc, &fixture.sol, 0),
+                TemplateError::ZeroAmount,
+            ),
+            // The borrower has no USDC deposited nor SOL borrowed
+            (
+                fixture.liquidate(&liquidator, &borrower, &fixture.usdc, &fixture.usdc, USDC),
+                TemplateError::ObligationCollateralEmpty,
+            ),
+            (
+                fixture.liquidate(&liquidator, &borrower, &fixture.sol, &fixture.sol, USDC),
+                TemplateError::ObligationLiquidityEmpty,
+            ),
+            (wrong_vault, TemplateError::InvalidVault),
+        ];
+        for (instruction, error) in cases {
+            let result = process(
+                banks_client,
+                &payer,
+                &[fixture.refresh(&borrower, &reserves), instruction],
+                &[&liquidator.keypair],
+            )
+            .await;
+            assert_eq!(result, Err(custom_error(1, error)));
+        }
+
+        // Less than the close factor repays exactly what was asked
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &reserves),
+                fixture.liquidate(
+                    &liquidator,
+                    &borrower,
+                    &fixture.usdc,
+                    &fixture.sol,
+                    10 * USDC,
+                ),
+            ],
+            &[&liquidator.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &liquidator.usdc.liquidity).await,
+            LENDER_USDC - 10 * USDC
+        );
+        assert_eq!(
+            token_balance(banks_client, &liquidator.sol.collateral).await,
+            10 * SOL * 105 / 100 / 17
+        );
+    }
+}
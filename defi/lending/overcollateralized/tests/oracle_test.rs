@@ -0,0 +1,654 @@
+//! Oracle tests for overcollateralized-lending
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::common::{
+            self, create_account, create_lending_market, create_reserve_accounts, create_user,
+            custom_error, get_reserve, process, refresh_blockhash, start_program, token_balance,
+            usdc_config, MarketFixture, ReserveFixture, SOL, USDC,
+        },
+        overcollateralized_lending::{
+            errors::TemplateError,
+            instructions::{init_reserve, refresh_reserve, set_reserve_price},
+            oracle::{
+                pyth, switchboard, FixedPriceSource, OraclePrice, OracleSource, PriceSource,
+                PythPriceSource, SwitchboardPriceSource,
+            },
+            state::{ReserveConfig, WAD},
+        },
+        solana_program::{
+            instruction::{Instruction, InstructionError},
+            pubkey::Pubkey,
+        },
+        solana_program_test::ProgramTestContext,
+        solana_sdk::{
+            signature::{Keypair, Signer},
+            transaction::TransactionError,
+        },
+        std::ops::Deref,
+    };
+
+    /// Exponent of the Pyth SOL feed's prices
+    const PYTH_EXPONENT: i32 = -8;
+    /// Size of a Pyth price account
+    const PYTH_ACCOUNT_SIZE: usize = 3_312;
+    /// Size of a Switchboard aggregator account
+    const SWITCHBOARD_ACCOUNT_SIZE: usize = 3_851;
+
+    /// SOL can be borrowed at up to 75% of its value, priced by an oracle
+    /// that must have published within 50 slots, to within 2%
+    fn sol_config() -> ReserveConfig {
+        ReserveConfig {
+            max_staleness_slots: 50,
+            max_confidence_bps: 200,
+            ..common::sol_config()
+        }
+    }
+
+    /// Data of a Pyth price account with an aggregate `price` and
+    /// `confidence` in units of `10^PYTH_EXPONENT`
+    fn pyth_price_data(price: i64, confidence: u64, status: u32, publish_slot: u64) -> Vec<u8> {
+        let mut data = vec![0; PYTH_ACCOUNT_SIZE];
+        let mut write = |offset: usize, bytes: &[u8]| {
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        write(pyth::MAGIC_OFFSET, &pyth::MAGIC.to_le_bytes());
+        write(pyth::VERSION_OFFSET, &pyth::VERSION.to_le_bytes());
+        write(
+            pyth::ACCOUNT_TYPE_OFFSET,
+            &pyth::ACCOUNT_TYPE_PRICE.to_le_bytes(),
+        );
+        write(pyth::EXPONENT_OFFSET, &PYTH_EXPONENT.to_le_bytes());
+        write(pyth::AGGREGATE_PRICE_OFFSET, &price.to_le_bytes());
+        write(pyth::AGGREGATE_CONFIDENCE_OFFSET, &confidence.to_le_bytes());
+        write(pyth::AGGREGATE_STATUS_OFFSET, &status.to_le_bytes());
+        write(
+            pyth::AGGREGATE_PUBLISH_SLOT_OFFSET,
+            &publish_slot.to_le_bytes(),
+        );
+        data
+    }
+
+    /// Data of a Switchboard aggregator account whose latest round has a
+    /// `result` and `std_deviation` of `mantissa * 10^-scale`
+    fn switchboard_data(
+        result: (i128, u32),
+        std_deviation: (i128, u32),
+        num_success: u32,
+        round_open_slot: u64,
+    ) -> Vec<u8> {
+        let mut data = vec![0; SWITCHBOARD_ACCOUNT_SIZE];
+        let mut write = |offset: usize, bytes: &[u8]| {
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        write(0, &switchboard::DISCRIMINATOR);
+        write(switchboard::NUM_SUCCESS_OFFSET, &num_success.to_le_bytes());
+        write(
+            switchboard::ROUND_OPEN_SLOT_OFFSET,
+            &round_open_slot.to_le_bytes(),
+        );
+        write(switchboard::RESULT_MANTISSA_OFFSET, &result.0.to_le_bytes());
+        write(switchboard::RESULT_SCALE_OFFSET, &result.1.to_le_bytes());
+        write(
+            switchboard::STD_DEVIATION_MANTISSA_OFFSET,
+            &std_deviation.0.to_le_bytes(),
+        );
+        write(
+            switchboard::STD_DEVIATION_SCALE_OFFSET,
+            &std_deviation.1.to_le_bytes(),
+        );
+        data
+    }
+
+    #[test]
+    fn test_pyth_price_source() {
+        // 20.5 ± 0.01 in units of 10^-8
+        let data = pyth_price_data(2_050_000_000, 1_000_000, pyth::STATUS_TRADING, 42);
+        let price = PythPriceSource::new(&data).unwrap().get_price(50).unwrap();
+        assert_eq!(
+            price,
+            OraclePrice {
+                price_wads: 20 * WAD + WAD / 2,
+                confidence_wads: WAD / 100,
+                publish_slot: 42,
+            }
+        );
+
+        // Accounts that are not price accounts are refused
+        for offset in [
+            pyth::MAGIC_OFFSET,
+            pyth::VERSION_OFFSET,
+            pyth::ACCOUNT_TYPE_OFFSET,
+        ] {
+            let mut data = data.clone();
+            data[offset] ^= 1;
+            assert_eq!(
+                PythPriceSource::new(&data).err(),
+                Some(TemplateError::InvalidOracle)
+            );
+        }
+        assert_eq!(
+            PythPriceSource::new(&data[..pyth::AGGREGATE_PRICE_OFFSET])
+                .and_then(|source| source.get_price(50))
+                .err(),
+            Some(TemplateError::InvalidOracle)
+        );
+
+        // So are prices of a feed that is not trading, or not positive
+        for data in [
+            pyth_price_data(2_050_000_000, 1_000_000, 0, 42),
+            pyth_price_data(0, 1_000_000, pyth::STATUS_TRADING, 42),
+            pyth_price_data(-2_050_000_000, 1_000_000, pyth::STATUS_TRADING, 42),
+        ] {
+            let source = PythPriceSource::new(&data).unwrap();
+            assert_eq!(source.get_price(50), Err(TemplateError::InvalidOracle));
+        }
+    }
+
+    #[test]
+    fn test_switchboard_price_source() {
+        // 20.5 with a standard deviation of 0.01
+        let data = switchboard_data((205, 1), (1, 2), 3, 42);
+        let price = SwitchboardPriceSource::new(&data)
+            .unwrap()
+            .get_price(50)
+            .unwrap();
+        assert_eq!(
+            price,
+            OraclePrice {
+                price_wads: 20 * WAD + WAD / 2,
+                confidence_wads: WAD / 100,
+                publish_slot: 42,
+            }
+        );
+
+        // Accounts that are not aggregators are refused
+        let mut other = data.clone();
+        other[0] ^= 1;
+        assert_eq!(
+            SwitchboardPriceSource::new(&other).err(),
+            Some(TemplateError::InvalidOracle)
+        );
+
+        // So are rounds without a response, or without a positive result
+        for data in [
+            switchboard_data((205, 1), (1, 2), 0, 42),
+            switchboard_data((0, 1), (1, 2), 3, 42),
+            switchboard_data((-205, 1), (1, 2), 3, 42),
+            switchboard_data((205, 1), (-1, 2), 3, 42),
+        ] {
+            let source = SwitchboardPriceSource::new(&data).unwrap();
+            assert_eq!(source.get_price(50), Err(TemplateError::InvalidOracle));
+        }
+    }
+
+    #[test]
+    fn test_oracle_price_validation() {
+        // Fixed prices are always current and exact
+        let price = FixedPriceSource { price_wads: WAD }.get_price(7).unwrap();
+        assert_eq!(
+            price,
+            OraclePrice {
+                price_wads: WAD,
+                confidence_wads: 0,
+                publish_slot: 7,
+            }
+        );
+        assert_eq!(price.validate(7, 0, 0), Ok(()));
+
+        // Prices go stale once more than the allowed slots old
+        let price = OraclePrice {
+            price_wads: 20 * WAD,
+            confidence_wads: WAD / 5,
+            publish_slot: 100,
+        };
+        assert_eq!(price.validate(150, 50, 100), Ok(()));
+        assert_eq!(
+            price.validate(151, 50, 100),
+            Err(TemplateError::StaleOracle)
+        );
+
+        // A confidence interval of 1% of the price is refused below 1%
+        assert_eq!(
+            price.validate(100, 50, 99),
+            Err(TemplateError::OracleConfidenceTooWide)
+        );
+    }
+
+    /// A lending market with a USDC reserve at a fixed price and a SOL
+    /// reserve priced by a Pyth feed
+    struct OracleMarketFixture {
+        market: MarketFixture,
+        sol_oracle: Pubkey,
+    }
+
+    impl Deref for OracleMarketFixture {
+        type Target = MarketFixture;
+
+        fn deref(&self) -> &MarketFixture {
+            &self.market
+        }
+    }
+
+    impl OracleMarketFixture {
+        fn refresh_sol(&self) -> Instruction {
+            refresh_reserve(&self.program_id, &self.sol.reserve, Some(&self.sol_oracle))
+        }
+    }
+
+    /// Creates an oracle account holding `data`, owned by an arbitrary
+    /// oracle program
+    async fn create_oracle(
+        context: &mut ProgramTestContext,
+        payer: &Keypair,
+        data: Vec<u8>,
+    ) -> Pubkey {
+        let oracle = Keypair::new();
+        create_account(
+            &mut context.banks_client,
+            payer,
+            &oracle,
+            data.len(),
+            &Pubkey::new_unique(),
+        )
+        .await;
+        set_oracle(context, &oracle.pubkey(), data).await;
+        oracle.pubkey()
+    }
+
+    /// Overwrites the data of the oracle account at `oracle`
+    async fn set_oracle(context: &mut ProgramTestContext, oracle: &Pubkey, data: Vec<u8>) {
+        let mut account = context
+            .banks_client
+            .get_account(*oracle)
+            .await
+            .unwrap()
+            .unwrap();
+        account.data = data;
+        context.set_account(oracle, &account.into());
+    }
+
+    /// Pyth data for a trading SOL feed at `price` whole USDC, published at
+    /// `publish_slot`, with a confidence interval of `confidence_bps` of it
+    fn sol_feed(price: i64, confidence_bps: u64, publish_slot: u64) -> Vec<u8> {
+        let price = price * 100_000_000;
+        let confidence = price as u64 * confidence_bps / 10_000;
+        pyth_price_data(price, confidence, pyth::STATUS_TRADING, publish_slot)
+    }
+
+    /// Adds `reserve` to the lending market, priced by `oracle_source`
+    #[allow(clippy::too_many_arguments)]
+    fn init_oracle_reserve_instruction(
+        program_id: &Pubkey,
+        owner: &Pubkey,
+        lending_market: &Pubkey,
+        market_authority: &Pubkey,
+        reserve: &ReserveFixture,
+        config: ReserveConfig,
+        oracle_source: OracleSource,
+        oracle: Option<&Pubkey>,
+    ) -> Instruction {
+        init_reserve(
+            program_id,
+            owner,
+            lending_market,
+            &reserve.reserve,
+            market_authority,
+            &reserve.liquidity_mint,
+            &reserve.liquidity_vault,
+            &reserve.collateral_mint,
+            &reserve.collateral_vault,
+            config,
+            oracle_source,
+            oracle,
+            WAD,
+        )
+    }
+
+    /// Starts a test validator with a lending market owned by the payer,
+    /// holding a USDC reserve at a fixed price of 1 and a SOL reserve priced
+    /// by a Pyth feed at 20. The payer mints both liquidity tokens.
+    async fn start_with_market() -> (ProgramTestContext, Keypair, OracleMarketFixture) {
+        let (mut context, payer, program_id) = start_program().await;
+        let sol_oracle = create_oracle(&mut context, &payer, sol_feed(20, 0, 0)).await;
+        let banks_client = &mut context.banks_client;
+        let (lending_market, market_authority) =
+            create_lending_market(banks_client, &payer, &program_id).await;
+
+        let usdc =
+            create_reserve_accounts(banks_client, &payer, &program_id, &market_authority, 6).await;
+        let sol =
+            create_reserve_accounts(banks_client, &payer, &program_id, &market_authority, 9).await;
+        process(
+            banks_client,
+            &payer,
+            &[
+                init_oracle_reserve_instruction(
+                    &program_id,
+                    &payer.pubkey(),
+                    &lending_market,
+                    &market_authority,
+                    &usdc,
+                    usdc_config(),
+                    OracleSource::Fixed,
+                    None,
+                ),
+                init_oracle_reserve_instruction(
+                    &program_id,
+                    &payer.pubkey(),
+                    &lending_market,
+                    &market_authority,
+                    &sol,
+                    sol_config(),
+                    OracleSource::Pyth,
+                    Some(&sol_oracle),
+                ),
+            ],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let fixture = OracleMarketFixture {
+            market: MarketFixture {
+                program_id,
+                lending_market,
+                market_authority,
+                usdc,
+                sol,
+            },
+            sol_oracle,
+        };
+        (context, payer, fixture)
+    }
+
+    #[tokio::test]
+    async fn test_init_oracle_reserve() {
+        let (mut context, payer, fixture) = start_with_market().await;
+        let reserve = get_reserve(&mut context.banks_client, &fixture.sol.reserve).await;
+        assert_eq!(reserve.oracle_source, OracleSource::Pyth);
+        assert_eq!(reserve.oracle, fixture.sol_oracle);
+        assert_eq!(reserve.price_slot, 0);
+        let reserve = get_reserve(&mut context.banks_client, &fixture.usdc.reserve).await;
+        assert_eq!(reserve.oracle_source, OracleSource::Fixed);
+        assert_eq!(reserve.oracle, Pubkey::default());
+
+        // Oracle reserves need an oracle account that holds a price
+        let bad_oracle = create_oracle(
+            &mut context,
+            &payer,
+            pyth_price_data(0, 0, pyth::STATUS_TRADING, 0),
+        )
+        .await;
+        let switchboard_oracle =
+            create_oracle(&mut context, &payer, switchboard_data((1, 0), (0, 0), 1, 0)).await;
+        let banks_client = &mut context.banks_client;
+        let accounts = create_reserve_accounts(
+            banks_client,
+            &payer,
+            &fixture.program_id,
+            &fixture.market_authority,
+            6,
+        )
+        .await;
+        let init = |oracle_source, oracle| {
+            init_oracle_reserve_instruction(
+                &fixture.program_id,
+                &payer.pubkey(),
+                &fixture.lending_market,
+                &fixture.market_authority,
+                &accounts,
+                usdc_config(),
+                oracle_source,
+                oracle,
+            )
+        };
+        assert_eq!(
+            process(banks_client, &payer, &[init(OracleSource::Pyth, None)], &[])
+                .await
+                .unwrap_err(),
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[init(OracleSource::Pyth, Some(&bad_oracle))],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidOracle)
+        );
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[init(OracleSource::Switchboard, Some(&fixture.sol_oracle))],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidOracle)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[init(OracleSource::Switchboard, Some(&switchboard_oracle))],
+            &[],
+        )
+        .await
+        .unwrap();
+        let reserve = get_reserve(banks_client, &accounts.reserve).await;
+        assert_eq!(reserve.oracle_source, OracleSource::Switchboard);
+        assert_eq!(reserve.oracle, switchboard_oracle);
+
+        // The price of an oracle reserve cannot be set by hand
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[set_reserve_price(
+                    &fixture.program_id,
+                    &payer.pubkey(),
+                    &fixture.lending_market,
+                    &fixture.sol.reserve,
+                    WAD,
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidOracle)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_reserve() {
+        let (mut context, payer, fixture) = start_with_market().await;
+
+        // The reserve takes the feed's price when it is refreshed
+        context.warp_to_slot(100).unwrap();
+        set_oracle(&mut context, &fixture.sol_oracle, sol_feed(25, 100, 90)).await;
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.refresh_sol()],
+            &[],
+        )
+        .await
+        .unwrap();
+        let reserve = get_reserve(&mut context.banks_client, &fixture.sol.reserve).await;
+        assert_eq!(reserve.market_price, 25 * WAD);
+        assert_eq!(reserve.price_slot, 100);
+
+        // Fixed price reserves can be refreshed without an oracle
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[refresh_reserve(
+                &fixture.program_id,
+                &fixture.usdc.reserve,
+                None,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        // Only the reserve's own feed is read
+        let other_oracle = create_oracle(&mut context, &payer, sol_feed(25, 100, 100)).await;
+        assert_eq!(
+            process(
+                &mut context.banks_client,
+                &payer,
+                &[refresh_reserve(
+                    &fixture.program_id,
+                    &fixture.sol.reserve,
+                    Some(&other_oracle),
+                )],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::InvalidOracle)
+        );
+
+        // Prices published more than 50 slots ago are refused. Every retry
+        // of the refresh waits for a new blockhash so it is not deduplicated.
+        context.warp_to_slot(141).unwrap();
+        refresh_blockhash(&mut context).await;
+        assert_eq!(
+            process(
+                &mut context.banks_client,
+                &payer,
+                &[fixture.refresh_sol()],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::StaleOracle)
+        );
+
+        // As are prices less certain than 2%
+        set_oracle(&mut context, &fixture.sol_oracle, sol_feed(25, 201, 141)).await;
+        refresh_blockhash(&mut context).await;
+        assert_eq!(
+            process(
+                &mut context.banks_client,
+                &payer,
+                &[fixture.refresh_sol()],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::OracleConfidenceTooWide)
+        );
+        set_oracle(&mut context, &fixture.sol_oracle, sol_feed(24, 200, 141)).await;
+        refresh_blockhash(&mut context).await;
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[fixture.refresh_sol()],
+            &[],
+        )
+        .await
+        .unwrap();
+        let reserve = get_reserve(&mut context.banks_client, &fixture.sol.reserve).await;
+        assert_eq!(reserve.market_price, 24 * WAD);
+        assert_eq!(reserve.price_slot, 141);
+    }
+
+    #[tokio::test]
+    async fn test_stale_reserve() {
+        let (mut context, payer, fixture) = start_with_market().await;
+        context.warp_to_slot(100).unwrap();
+        set_oracle(&mut context, &fixture.sol_oracle, sol_feed(20, 0, 100)).await;
+        let banks_client = &mut context.banks_client;
+
+        // A lender supplies SOL, and a borrower puts up USDC as collateral
+        let lender = create_user(banks_client, &payer, &fixture, 0, 100 * SOL).await;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.deposit(&lender, &fixture.sol, &lender.sol, 100 * SOL)],
+            &[&lender.keypair],
+        )
+        .await
+        .unwrap();
+        let borrower = create_user(banks_client, &payer, &fixture, 1_000 * USDC, 0).await;
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.deposit(&borrower, &fixture.usdc, &borrower.usdc, 1_000 * USDC),
+                fixture.deposit_collateral(&borrower, &fixture.usdc, &borrower.usdc, 1_000 * USDC),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+
+        // SOL cannot be borrowed at a price not refreshed in the same slot
+        let borrow = fixture.borrow(&borrower, &fixture.sol, &borrower.sol, 10 * SOL);
+        assert_eq!(
+            process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.refresh(&borrower, &[fixture.usdc.reserve]),
+                    borrow.clone(),
+                ],
+                &[&borrower.keypair],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(1, TemplateError::ReserveStale)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh_sol(),
+                fixture.refresh(&borrower, &[fixture.usdc.reserve]),
+                borrow,
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            token_balance(banks_client, &borrower.sol.liquidity).await,
+            10 * SOL
+        );
+
+        // Nor can an obligation owing SOL be refreshed without its price
+        context.warp_to_slot(110).unwrap();
+        let obligation_reserves = [fixture.usdc.reserve, fixture.sol.reserve];
+        assert_eq!(
+            process(
+                &mut context.banks_client,
+                &payer,
+                &[fixture.refresh(&borrower, &obligation_reserves)],
+                &[],
+            )
+            .await
+            .unwrap_err(),
+            custom_error(0, TemplateError::ReserveStale)
+        );
+        process(
+            &mut context.banks_client,
+            &payer,
+            &[
+                fixture.refresh_sol(),
+                fixture.refresh(&borrower, &obligation_reserves),
+            ],
+            &[],
+        )
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,766 @@
+//! Risk tier tests for overcollateralized-lending
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::common::{
+            self, create_lending_market, create_obligation, create_reserve_accounts, create_wallet,
+            custom_error, get_obligation, get_reserve, init_reserve_instruction, mint_to, process,
+            start_program, token_balance, ReserveFixture, Wallet, SOL, SOL_PRICE, USDC,
+        },
+        overcollateralized_lending::{
+            errors::TemplateError,
+            instructions::{
+                borrow_obligation_liquidity, deposit_obligation_collateral,
+                deposit_reserve_liquidity, liquidate_obligation, refresh_obligation,
+                repay_obligation_liquidity, set_obligation_emode, set_reserve_price,
+            },
+            state::{AssetTier, ReserveConfig, WAD},
+        },
+        solana_program::{
+            instruction::{Instruction, InstructionError},
+            pubkey::Pubkey,
+        },
+        solana_program_test::{BanksClient, ProgramTestContext},
+        solana_sdk::{
+            signature::{Keypair, Signer},
+            transaction::TransactionError,
+        },
+    };
+
+    /// One whole long-tail token, with 6 decimals
+    const LONG_TAIL: u64 = 1_000_000;
+    /// Prices of mSOL and the long-tail token in the quote currency
+    const MSOL_PRICE: u128 = 25 * WAD;
+    const LONG_TAIL_PRICE: u128 = 2 * WAD;
+    /// E-mode category of SOL and its liquid staking token
+    const SOL_CATEGORY: u8 = 1;
+
+    /// USDC is a stable asset, borrowed at up to 80% of its value
+    fn usdc_config() -> ReserveConfig {
+        ReserveConfig {
+            asset_tier: AssetTier::Stable,
+            ..common::usdc_config()
+        }
+    }
+
+    /// SOL backs 75% of its value, and 90% in the SOL e-mode category
+    fn sol_config() -> ReserveConfig {
+        ReserveConfig {
+            loan_to_value_bps: 7_500,
+            liquidation_threshold_bps: 8_000,
+            asset_tier: AssetTier::Standard,
+            emode_category: SOL_CATEGORY,
+            emode_loan_to_value_bps: 9_000,
+            emode_liquidation_threshold_bps: 9_300,
+            ..usdc_config()
+        }
+    }
+
+    /// mSOL backs 70% of its value, and 90% in the SOL e-mode category
+    fn msol_config() -> ReserveConfig {
+        ReserveConfig {
+            loan_to_value_bps: 7_000,
+            liquidation_threshold_bps: 7_500,
+            ..sol_config()
+        }
+    }
+
+    /// The long-tail token is isolated, backs 50% of its value and at most
+    /// 100 USDC of debt across obligations
+    fn long_tail_config() -> ReserveConfig {
+        ReserveConfig {
+            loan_to_value_bps: 5_000,
+            liquidation_threshold_bps: 6_000,
+            asset_tier: AssetTier::Isolated,
+            debt_ceiling: 100,
+            ..usdc_config()
+        }
+    }
+
+    /// Accounts of a lending market with a reserve of each risk tier
+    struct MarketFixture {
+        program_id: Pubkey,
+        lending_market: Pubkey,
+        market_authority: Pubkey,
+        usdc: ReserveFixture,
+        sol: ReserveFixture,
+        msol: ReserveFixture,
+        long_tail: ReserveFixture,
+    }
+
+    /// A user with an obligation and a wallet for each reserve, in the
+    /// order of `MarketFixture::reserves`
+    struct User {
+        keypair: Keypair,
+        obligation: Pubkey,
+        wallets: Vec<Wallet>,
+    }
+
+    impl MarketFixture {
+        fn reserves(&self) -> [&ReserveFixture; 4] {
+            [&self.usdc, &self.sol, &self.msol, &self.long_tail]
+        }
+
+        /// The wallet of `user` for `reserve`
+        fn wallet<'a>(&self, user: &'a User, reserve: &ReserveFixture) -> &'a Wallet {
+            let index = self
+                .reserves()
+                .iter()
+                .position(|fixture| fixture.reserve == reserve.reserve)
+                .unwrap();
+            &user.wallets[index]
+        }
+
+        fn deposit(&self, user: &User, reserve: &ReserveFixture, amount: u64) -> Instruction {
+            let wallet = self.wallet(user, reserve);
+            deposit_reserve_liquidity(
+                &self.program_id,
+                &user.keypair.pubkey(),
+                &reserve.reserve,
+                &self.lending_market,
+                &self.market_authority,
+                &reserve.liquidity_vault,
+                &reserve.collateral_mint,
+                &wallet.liquidity,
+                &wallet.collateral,
+                amount,
+            )
+        }
+
+        fn deposit_collateral(
+            &self,
+            user: &User,
+            reserve: &ReserveFixture,
+            amount: u64,
+        ) -> Instruction {
+            deposit_obligation_collateral(
+                &self.program_id,
+                &user.keypair.pubkey(),
+                &user.obligation,
+                &reserve.reserve,
+                &reserve.collateral_vault,
+                &self.wallet(user, reserve).collateral,
+                amount,
+            )
+        }
+
+        fn borrow(
+            &self,
+            user: &User,
+            reserve: &ReserveFixture,
+            isolated_reserve: Option<&Pubkey>,
+            amount: u64,
+        ) -> Instruction {
+            borrow_obligation_liquidity(
+                &self.program_id,
+                &user.keypair.pubkey(),
+                &user.obligation,
+                &reserve.reserve,
+                &self.lending_market,
+                &self.market_authority,
+                &reserve.liquidity_vault,
+                &self.wallet(user, reserve).liquidity,
+                isolated_reserve,
+                amount,
+            )
+        }
+
+        fn repay(
+            &self,
+            user: &User,
+            reserve: &ReserveFixture,
+            isolated_reserve: Option<&Pubkey>,
+            amount: u64,
+        ) -> Instruction {
+            repay_obligation_liquidity(
+                &self.program_id,
+                &user.keypair.pubkey(),
+                &user.obligation,
+                &reserve.reserve,
+                &reserve.liquidity_vault,
+                &self.wallet(user, reserve).liquidity,
+                isolated_reserve,
+                amount,
+            )
+        }
+
+        fn refresh(&self, user: &User, obligation_reserves: &[&ReserveFixture]) -> Instruction {
+            let obligation_reserves: Vec<Pubkey> = obligation_reserves
+                .iter()
+                .map(|reserve| reserve.reserve)
+                .collect();
+            refresh_obligation(&self.program_id, &user.obligation, &obligation_reserves)
+        }
+
+        fn set_emode(&self, user: &User, emode_category: u8) -> Instruction {
+            set_obligation_emode(
+                &self.program_id,
+                &user.keypair.pubkey(),
+                &user.obligation,
+                emode_category,
+            )
+        }
+
+        fn set_price(&self, owner: &Keypair, reserve: &ReserveFixture, price: u128) -> Instruction {
+            set_reserve_price(
+                &self.program_id,
+                &owner.pubkey(),
+                &self.lending_market,
+                &reserve.reserve,
+                price,
+            )
+        }
+
+        /// Liquidates `borrower`, repaying `amount` of their debt to `repay`
+        /// out of the liquidator's wallet and seizing cTokens of `withdraw`
+        /// into it
+        fn liquidate(
+            &self,
+            liquidator: &User,
+            borrower: &User,
+            repay: &ReserveFixture,
+            withdraw: &ReserveFixture,
+            amount: u64,
+        ) -> Instruction {
+            liquidate_obligation(
+                &self.program_id,
+                &liquidator.keypair.pubkey(),
+                &borrower.obligation,
+                &repay.reserve,
+                &repay.liquidity_vault,
+                &self.wallet(liquidator, repay).liquidity,
+                &withdraw.reserve,
+                &withdraw.collateral_vault,
+                &self.wallet(liquidator, withdraw).collateral,
+                &self.lending_market,
+                &self.market_authority,
+                amount,
+            )
+        }
+    }
+
+    /// Starts a test validator with a lending market owned by the payer,
+    /// holding a USDC, a SOL, an mSOL and a long-tail reserve. The payer
+    /// mints every liquidity token.
+    async fn start_with_market() -> (ProgramTestContext, Keypair, MarketFixture) {
+        let (mut context, payer, program_id) = start_program().await;
+        let banks_client = &mut context.banks_client;
+        let (lending_market, market_authority) =
+            create_lending_market(banks_client, &payer, &program_id).await;
+
+        let mut reserves = Vec::new();
+        for (decimals, config, price) in [
+            (6, usdc_config(), WAD),
+            (9, sol_config(), SOL_PRICE),
+            (9, msol_config(), MSOL_PRICE),
+            (6, long_tail_config(), LONG_TAIL_PRICE),
+        ] {
+            let reserve = create_reserve_accounts(
+                banks_client,
+                &payer,
+                &program_id,
+                &market_authority,
+                decimals,
+            )
+            .await;
+            process(
+                banks_client,
+                &payer,
+                &[init_reserve_instruction(
+                    &program_id,
+                    &payer.pubkey(),
+                    &lending_market,
+                    &market_authority,
+                    &reserve,
+                    config,
+                    price,
+                )],
+                &[],
+            )
+            .await
+            .unwrap();
+            reserves.push(reserve);
+        }
+        let long_tail = reserves.pop().unwrap();
+        let msol = reserves.pop().unwrap();
+        let sol = reserves.pop().unwrap();
+        let usdc = reserves.pop().unwrap();
+
+        let fixture = MarketFixture {
+            program_id,
+            lending_market,
+            market_authority,
+            usdc,
+            sol,
+            msol,
+            long_tail,
+        };
+        (context, payer, fixture)
+    }
+
+    /// Creates a user with an obligation, holding `liquidity` of each
+    /// reserve's token in the order of `MarketFixture::reserves`
+    async fn create_user(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &MarketFixture,
+        liquidity: [u64; 4],
+    ) -> User {
+        let keypair = Keypair::new();
+        let owner = keypair.pubkey();
+        let obligation = create_obligation(
+            banks_client,
+            payer,
+            &fixture.program_id,
+            &fixture.lending_market,
+            &keypair,
+        )
+        .await;
+        let mut wallets = Vec::new();
+        for (reserve, amount) in fixture.reserves().into_iter().zip(liquidity) {
+            wallets.push(create_wallet(banks_client, payer, reserve, &owner, amount).await);
+        }
+        User {
+            keypair,
+            obligation,
+            wallets,
+        }
+    }
+
+    /// Creates a user who has deposited `amount` of `reserve`'s token into
+    /// their obligation as collateral
+    async fn create_borrower(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &MarketFixture,
+        reserve: &ReserveFixture,
+        amount: u64,
+    ) -> User {
+        let index = fixture
+            .reserves()
+            .iter()
+            .position(|fixture| fixture.reserve == reserve.reserve)
+            .unwrap();
+        let mut liquidity = [0; 4];
+        liquidity[index] = amount;
+        let borrower = create_user(banks_client, payer, fixture, liquidity).await;
+        process(
+            banks_client,
+            payer,
+            &[
+                fixture.deposit(&borrower, reserve, amount),
+                fixture.deposit_collateral(&borrower, reserve, amount),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        borrower
+    }
+
+    /// Creates a lender who has deposited 1,000 USDC, 100 SOL and 100 mSOL
+    async fn create_lender(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        fixture: &MarketFixture,
+    ) -> User {
+        let lender = create_user(
+            banks_client,
+            payer,
+            fixture,
+            [1_000 * USDC, 100 * SOL, 100 * SOL, 0],
+        )
+        .await;
+        process(
+            banks_client,
+            payer,
+            &[
+                fixture.deposit(&lender, &fixture.usdc, 1_000 * USDC),
+                fixture.deposit(&lender, &fixture.sol, 100 * SOL),
+                fixture.deposit(&lender, &fixture.msol, 100 * SOL),
+            ],
+            &[&lender.keypair],
+        )
+        .await
+        .unwrap();
+        lender
+    }
+
+    #[test]
+    fn test_risk_tier_config_validation() {
+        for config in [
+            usdc_config(),
+            sol_config(),
+            msol_config(),
+            long_tail_config(),
+        ] {
+            assert_eq!(config.validate(), Ok(()));
+        }
+
+        let invalid = [
+            // An isolated reserve needs a debt ceiling, and only it has one
+            ReserveConfig {
+                debt_ceiling: 0,
+                ..long_tail_config()
+            },
+            ReserveConfig {
+                debt_ceiling: 100,
+                ..usdc_config()
+            },
+            // E-mode ratios without a category
+            ReserveConfig {
+                emode_category: 0,
+                ..sol_config()
+            },
+            // E-mode ratios below the reserve's own, or past 100%
+            ReserveConfig {
+                emode_loan_to_value_bps: 7_000,
+                ..sol_config()
+            },
+            ReserveConfig {
+                emode_liquidation_threshold_bps: 7_900,
+                emode_loan_to_value_bps: 7_500,
+                ..sol_config()
+            },
+            ReserveConfig {
+                emode_liquidation_threshold_bps: 8_900,
+                ..sol_config()
+            },
+            ReserveConfig {
+                emode_liquidation_threshold_bps: 10_000,
+                ..sol_config()
+            },
+            // Isolated reserves have no e-mode category
+            ReserveConfig {
+                emode_category: SOL_CATEGORY,
+                emode_loan_to_value_bps: 9_000,
+                emode_liquidation_threshold_bps: 9_300,
+                ..long_tail_config()
+            },
+        ];
+        for config in invalid {
+            assert_eq!(
+                config.validate(),
+                Err(TemplateError::InvalidReserveConfig),
+                "{:?}",
+                config
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_isolated_collateral() {
+        let (mut context, payer, fixture) = start_with_market().await;
+        let banks_client = &mut context.banks_client;
+        let long_tail = Some(&fixture.long_tail.reserve);
+        create_lender(banks_client, &payer, &fixture).await;
+
+        // 200 long-tail tokens are worth 400 USDC and back 200
+        let borrower = create_borrower(
+            banks_client,
+            &payer,
+            &fixture,
+            &fixture.long_tail,
+            200 * LONG_TAIL,
+        )
+        .await;
+        let obligation = get_obligation(banks_client, &borrower.obligation).await;
+        assert_eq!(obligation.isolated_reserve, fixture.long_tail.reserve);
+
+        // Only stable assets can be borrowed, and the isolated reserve must
+        // be passed along
+        let cases = [
+            (
+                fixture.borrow(&borrower, &fixture.sol, long_tail, SOL),
+                InstructionError::Custom(TemplateError::IsolatedBorrowNotStable as u32),
+            ),
+            (
+                fixture.borrow(&borrower, &fixture.usdc, None, USDC),
+                InstructionError::NotEnoughAccountKeys,
+            ),
+            (
+                fixture.borrow(&borrower, &fixture.usdc, Some(&fixture.sol.reserve), USDC),
+                InstructionError::Custom(TemplateError::InvalidObligationReserves as u32),
+            ),
+        ];
+        for (instruction, error) in cases {
+            let result = process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.refresh(&borrower, &[&fixture.long_tail]),
+                    instruction,
+                ],
+                &[&borrower.keypair],
+            )
+            .await;
+            assert_eq!(
+                result.unwrap_err(),
+                TransactionError::InstructionError(1, error)
+            );
+        }
+
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &[&fixture.long_tail]),
+                fixture.borrow(&borrower, &fixture.usdc, long_tail, 80 * USDC),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        let reserve = get_reserve(banks_client, &fixture.long_tail.reserve).await;
+        assert_eq!(reserve.isolated_debt_wads, 80 * WAD);
+
+        // The debt ceiling holds across obligations, well within their
+        // borrowing power
+        let other = create_borrower(
+            banks_client,
+            &payer,
+            &fixture,
+            &fixture.long_tail,
+            200 * LONG_TAIL,
+        )
+        .await;
+        let result = process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&other, &[&fixture.long_tail]),
+                fixture.borrow(&other, &fixture.usdc, long_tail, 30 * USDC),
+            ],
+            &[&other.keypair],
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err(),
+            custom_error(1, TemplateError::DebtCeilingExceeded)
+        );
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&other, &[&fixture.long_tail]),
+                fixture.borrow(&other, &fixture.usdc, long_tail, 20 * USDC),
+            ],
+            &[&other.keypair],
+        )
+        .await
+        .unwrap();
+        let reserve = get_reserve(banks_client, &fixture.long_tail.reserve).await;
+        assert_eq!(reserve.isolated_debt_wads, 100 * WAD);
+
+        // Isolated collateral cannot be mixed with other collateral, in
+        // either order
+        let sol_borrower = create_borrower(banks_client, &payer, &fixture, &fixture.sol, SOL).await;
+        for (user, reserve) in [
+            (&borrower, &fixture.sol),
+            (&sol_borrower, &fixture.long_tail),
+        ] {
+            let amount = 10 * LONG_TAIL;
+            let wallet = fixture.wallet(user, reserve);
+            mint_to(
+                banks_client,
+                &payer,
+                &reserve.liquidity_mint,
+                &wallet.liquidity,
+                amount,
+            )
+            .await;
+            let result = process(
+                banks_client,
+                &payer,
+                &[
+                    fixture.deposit(user, reserve, amount),
+                    fixture.deposit_collateral(user, reserve, amount),
+                ],
+                &[&user.keypair],
+            )
+            .await;
+            assert_eq!(
+                result.unwrap_err(),
+                custom_error(1, TemplateError::IsolatedCollateralMixed)
+            );
+        }
+
+        // Repaying frees the debt ceiling
+        process(
+            banks_client,
+            &payer,
+            &[fixture.repay(&borrower, &fixture.usdc, long_tail, u64::MAX)],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        let reserve = get_reserve(banks_client, &fixture.long_tail.reserve).await;
+        assert!(reserve.isolated_debt_wads <= 20 * WAD);
+        assert!(reserve.isolated_debt_wads >= 19 * WAD);
+
+        // So does liquidating: at 0.1 the collateral is worth 20 USDC, and
+        // half the debt is repaid for it
+        let liquidator = create_user(banks_client, &payer, &fixture, [100 * USDC, 0, 0, 0]).await;
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.set_price(&payer, &fixture.long_tail, WAD / 10),
+                fixture.refresh(&other, &[&fixture.long_tail, &fixture.usdc]),
+                fixture.liquidate(
+                    &liquidator,
+                    &other,
+                    &fixture.usdc,
+                    &fixture.long_tail,
+                    u64::MAX,
+                ),
+            ],
+            &[&liquidator.keypair],
+        )
+        .await
+        .unwrap();
+        let repaid = 100 * USDC
+            - token_balance(
+                banks_client,
+                &fixture.wallet(&liquidator, &fixture.usdc).liquidity,
+            )
+            .await;
+        let reserve = get_reserve(banks_client, &fixture.long_tail.reserve).await;
+        assert!((10 * USDC..=10 * USDC + 10).contains(&repaid));
+        assert!(reserve.isolated_debt_wads <= 10 * WAD);
+        assert!(reserve.isolated_debt_wads >= 9 * WAD);
+    }
+
+    #[tokio::test]
+    async fn test_emode() {
+        let (mut context, payer, fixture) = start_with_market().await;
+        let banks_client = &mut context.banks_client;
+        create_lender(banks_client, &payer, &fixture).await;
+
+        // 10 SOL are worth 200 USDC and back 150 outside e-mode
+        let borrower =
+            create_borrower(banks_client, &payer, &fixture, &fixture.sol, 10 * SOL).await;
+        process(
+            banks_client,
+            &payer,
+            &[fixture.refresh(&borrower, &[&fixture.sol])],
+            &[],
+        )
+        .await
+        .unwrap();
+        let obligation = get_obligation(banks_client, &borrower.obligation).await;
+        assert_eq!(obligation.allowed_borrow_value_wads, 150 * WAD);
+
+        // In the SOL category they back 180 and are liquidated past 186
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.set_emode(&borrower, SOL_CATEGORY),
+                fixture.refresh(&borrower, &[&fixture.sol]),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        let obligation = get_obligation(banks_client, &borrower.obligation).await;
+        assert_eq!(obligation.emode_category, SOL_CATEGORY);
+        assert_eq!(obligation.allowed_borrow_value_wads, 180 * WAD);
+        assert_eq!(obligation.unhealthy_borrow_value_wads, 186 * WAD);
+
+        // Only assets of the category can be borrowed
+        let result = process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &[&fixture.sol]),
+                fixture.borrow(&borrower, &fixture.usdc, None, 10 * USDC),
+            ],
+            &[&borrower.keypair],
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err(),
+            custom_error(1, TemplateError::EmodeCategoryMismatch)
+        );
+
+        // 7 mSOL are worth 175, beyond the borrowing power outside e-mode
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.refresh(&borrower, &[&fixture.sol]),
+                fixture.borrow(&borrower, &fixture.msol, None, 7 * SOL),
+            ],
+            &[&borrower.keypair],
+        )
+        .await
+        .unwrap();
+        let result = process(
+            banks_client,
+            &payer,
+            &[fixture.set_emode(&borrower, 0)],
+            &[&borrower.keypair],
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err(),
+            custom_error(0, TemplateError::EmodeChangeWithBorrows)
+        );
+
+        // At 19 the collateral is past the regular threshold of 152 but
+        // within the e-mode threshold of 176.7
+        let liquidator = create_user(banks_client, &payer, &fixture, [0, 0, 10 * SOL, 0]).await;
+        let reserves = [&fixture.sol, &fixture.msol];
+        let result = process(
+            banks_client,
+            &payer,
+            &[
+                fixture.set_price(&payer, &fixture.sol, 19 * WAD),
+                fixture.refresh(&borrower, &reserves),
+                fixture.liquidate(
+                    &liquidator,
+                    &borrower,
+                    &fixture.msol,
+                    &fixture.sol,
+                    u64::MAX,
+                ),
+            ],
+            &[&liquidator.keypair],
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err(),
+            custom_error(2, TemplateError::ObligationHealthy)
+        );
+
+        // At 18 the e-mode threshold of 167.4 is passed too
+        process(
+            banks_client,
+            &payer,
+            &[
+                fixture.set_price(&payer, &fixture.sol, 18 * WAD),
+                fixture.refresh(&borrower, &reserves),
+                fixture.liquidate(
+                    &liquidator,
+                    &borrower,
+                    &fixture.msol,
+                    &fixture.sol,
+                    u64::MAX,
+                ),
+            ],
+            &[&liquidator.keypair],
+        )
+        .await
+        .unwrap();
+        let seized = token_balance(
+            banks_client,
+            &fixture.wallet(&liquidator, &fixture.sol).collateral,
+        )
+        .await;
+        assert!(seized > 0);
+    }
+}